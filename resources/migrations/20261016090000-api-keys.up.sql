@@ -0,0 +1,35 @@
+CREATE TABLE api_keys (
+    id                  UUID PRIMARY KEY,
+    tenant_id           UUID NOT NULL,
+    user_id             UUID NOT NULL,
+    name                TEXT NOT NULL,
+    prefix              TEXT NOT NULL UNIQUE,
+    secret_hash         TEXT NOT NULL,
+    scopes              TEXT[] NOT NULL DEFAULT '{}',
+    requests_per_minute INTEGER NOT NULL,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_used_at        TIMESTAMPTZ,
+    revoked_at          TIMESTAMPTZ
+);
+
+COMMENT ON TABLE api_keys IS 'Bearer tokens for programmatic access';
+COMMENT ON COLUMN api_keys.tenant_id IS 'Tenant UUID from Datomic';
+COMMENT ON COLUMN api_keys.user_id IS 'References user entity in Datomic';
+COMMENT ON COLUMN api_keys.prefix IS 'Public identifier embedded in the token';
+COMMENT ON COLUMN api_keys.secret_hash IS 'SHA-256 hash of token secret (hex encoded)';
+
+CREATE INDEX api_keys_tenant_user_idx
+    ON api_keys (tenant_id, user_id)
+    WHERE revoked_at IS NULL;
+
+CREATE TABLE api_key_requests (
+    api_key_id   UUID NOT NULL REFERENCES api_keys (id) ON DELETE CASCADE,
+    window_start TIMESTAMPTZ NOT NULL,
+    requests     INTEGER NOT NULL DEFAULT 1,
+    PRIMARY KEY (api_key_id, window_start)
+);
+
+COMMENT ON TABLE api_key_requests IS 'Per-minute request counts for API key rate limiting';
+
+CREATE INDEX api_key_requests_cleanup_idx
+    ON api_key_requests (window_start);
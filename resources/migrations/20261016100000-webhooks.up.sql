@@ -0,0 +1,44 @@
+CREATE TABLE webhook_endpoints (
+    id          UUID PRIMARY KEY,
+    tenant_id   UUID NOT NULL,
+    url         TEXT NOT NULL,
+    secret      TEXT NOT NULL,
+    events      TEXT[] NOT NULL DEFAULT '{}',
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    disabled_at TIMESTAMPTZ
+);
+
+COMMENT ON TABLE webhook_endpoints IS 'Tenant-registered receivers of event notifications';
+COMMENT ON COLUMN webhook_endpoints.tenant_id IS 'Tenant UUID from Datomic';
+COMMENT ON COLUMN webhook_endpoints.secret IS 'HMAC key used to sign payloads (needed in the clear to sign)';
+COMMENT ON COLUMN webhook_endpoints.events IS 'Event names this endpoint subscribes to';
+
+CREATE INDEX webhook_endpoints_tenant_idx
+    ON webhook_endpoints (tenant_id)
+    WHERE disabled_at IS NULL;
+
+CREATE TABLE webhook_deliveries (
+    id              UUID PRIMARY KEY,
+    endpoint_id     UUID NOT NULL REFERENCES webhook_endpoints (id) ON DELETE CASCADE,
+    tenant_id       UUID NOT NULL,
+    event           TEXT NOT NULL,
+    payload         JSONB NOT NULL,
+    status          TEXT NOT NULL DEFAULT 'pending'
+                    CHECK (status IN ('pending', 'succeeded', 'dead')),
+    attempts        INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    response_status INTEGER,
+    last_error      TEXT,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    completed_at    TIMESTAMPTZ
+);
+
+COMMENT ON TABLE webhook_deliveries IS 'Outbound webhook queue and delivery log';
+COMMENT ON COLUMN webhook_deliveries.status IS 'pending until delivered (succeeded) or out of attempts (dead)';
+
+CREATE INDEX webhook_deliveries_due_idx
+    ON webhook_deliveries (next_attempt_at)
+    WHERE status = 'pending';
+
+CREATE INDEX webhook_deliveries_tenant_idx
+    ON webhook_deliveries (tenant_id, created_at DESC);
@@ -0,0 +1,2 @@
+DROP TABLE feature_flag_tenants;
+DROP TABLE feature_flags;
@@ -0,0 +1,23 @@
+CREATE TABLE feature_flags (
+    name            TEXT PRIMARY KEY,
+    enabled         BOOLEAN NOT NULL DEFAULT false,
+    rollout_percent INTEGER NOT NULL DEFAULT 0
+                    CHECK (rollout_percent BETWEEN 0 AND 100),
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at      TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE feature_flags IS 'Server-side feature flags';
+COMMENT ON COLUMN feature_flags.enabled IS 'On for every tenant, regardless of rollout';
+COMMENT ON COLUMN feature_flags.rollout_percent IS 'Share of tenants (by stable hash) the flag is on for';
+
+CREATE TABLE feature_flag_tenants (
+    flag_name  TEXT NOT NULL REFERENCES feature_flags (name) ON DELETE CASCADE,
+    tenant_id  UUID NOT NULL,
+    enabled    BOOLEAN NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (flag_name, tenant_id)
+);
+
+COMMENT ON TABLE feature_flag_tenants IS 'Per-tenant overrides that win over the flag default';
+COMMENT ON COLUMN feature_flag_tenants.tenant_id IS 'Tenant UUID from Datomic';
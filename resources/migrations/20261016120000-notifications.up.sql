@@ -0,0 +1,22 @@
+CREATE TABLE notifications (
+    id         UUID PRIMARY KEY,
+    tenant_id  UUID NOT NULL,
+    user_id    UUID NOT NULL,
+    kind       TEXT NOT NULL,
+    data       JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    read_at    TIMESTAMPTZ
+);
+
+COMMENT ON TABLE notifications IS 'In-app notifications shown under the header bell';
+COMMENT ON COLUMN notifications.tenant_id IS 'Tenant UUID from Datomic';
+COMMENT ON COLUMN notifications.user_id IS 'References user entity in Datomic';
+COMMENT ON COLUMN notifications.kind IS 'What happened, e.g. api-key.issued';
+COMMENT ON COLUMN notifications.data IS 'Values interpolated into the rendered message';
+
+CREATE INDEX notifications_recipient_idx
+    ON notifications (tenant_id, user_id, created_at DESC);
+
+CREATE INDEX notifications_unread_idx
+    ON notifications (tenant_id, user_id)
+    WHERE read_at IS NULL;
@@ -0,0 +1,16 @@
+CREATE TABLE activities (
+    id         UUID PRIMARY KEY,
+    tenant_id  UUID NOT NULL,
+    actor_id   UUID,
+    kind       TEXT NOT NULL,
+    data       JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE activities IS 'Chronological feed of what happened in a tenant';
+COMMENT ON COLUMN activities.tenant_id IS 'Tenant UUID from Datomic';
+COMMENT ON COLUMN activities.actor_id IS 'User who caused the activity, if any';
+COMMENT ON COLUMN activities.kind IS 'What happened, e.g. webhook.registered';
+
+CREATE INDEX activities_feed_idx
+    ON activities (tenant_id, created_at DESC, id DESC);
@@ -0,0 +1 @@
+COMMENT ON COLUMN webhook_endpoints.secret IS 'HMAC key used to sign payloads (needed in the clear to sign)';
@@ -0,0 +1 @@
+COMMENT ON COLUMN webhook_endpoints.secret IS 'HMAC key used to sign payloads, sealed with a field key (enc1.<key-id>.<wrapped key>.<ciphertext>)';
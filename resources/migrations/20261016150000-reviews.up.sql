@@ -0,0 +1,23 @@
+CREATE TABLE reviews (
+    id         UUID PRIMARY KEY,
+    tenant_id  UUID NOT NULL,
+    product_id UUID NOT NULL,
+    author_id  UUID NOT NULL,
+    rating     SMALLINT NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    body       TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    hidden_at  TIMESTAMPTZ,
+    UNIQUE (product_id, author_id)
+);
+
+COMMENT ON TABLE reviews IS 'Star ratings and write-ups from verified buyers';
+COMMENT ON COLUMN reviews.tenant_id IS 'Tenant UUID from Datomic';
+COMMENT ON COLUMN reviews.product_id IS 'References product entity in Datomic';
+COMMENT ON COLUMN reviews.author_id IS 'References user entity in Datomic. One review per buyer per product';
+COMMENT ON COLUMN reviews.body IS 'Markdown as the author wrote it';
+COMMENT ON COLUMN reviews.hidden_at IS 'Set when moderation hides the review';
+
+CREATE INDEX reviews_product_idx
+    ON reviews (product_id, created_at DESC, id DESC)
+    WHERE hidden_at IS NULL;
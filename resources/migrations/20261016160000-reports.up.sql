@@ -0,0 +1,27 @@
+CREATE TABLE reports (
+    id          UUID PRIMARY KEY,
+    tenant_id   UUID NOT NULL,
+    target_type TEXT NOT NULL CHECK (target_type IN ('page', 'review', 'tenant')),
+    target_id   UUID NOT NULL,
+    reason      TEXT NOT NULL,
+    details     TEXT NOT NULL DEFAULT '',
+    reporter_id UUID,
+    status      TEXT NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'dismissed', 'actioned')),
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    resolved_at TIMESTAMPTZ,
+    resolved_by UUID
+);
+
+COMMENT ON TABLE reports IS 'Abuse reports awaiting or past moderation';
+COMMENT ON COLUMN reports.tenant_id IS 'Tenant UUID from Datomic that owns the reported content';
+COMMENT ON COLUMN reports.target_id IS 'Page, review or tenant UUID, depending on target_type';
+COMMENT ON COLUMN reports.reason IS 'Why it was reported, e.g. spam';
+COMMENT ON COLUMN reports.reporter_id IS 'User who filed the report, or NULL when flagged automatically';
+COMMENT ON COLUMN reports.resolved_by IS 'Moderator who dismissed or acted on the report';
+
+CREATE INDEX reports_queue_idx
+    ON reports (created_at, id)
+    WHERE status = 'open';
+
+CREATE INDEX reports_target_idx
+    ON reports (target_type, target_id);
@@ -0,0 +1 @@
+INSERT INTO feature_flags (name) VALUES ('maintenance') ON CONFLICT (name) DO NOTHING;
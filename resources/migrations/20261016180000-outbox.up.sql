@@ -0,0 +1,20 @@
+CREATE TABLE outbox (
+    id           UUID PRIMARY KEY,
+    tenant_id    UUID NOT NULL,
+    actor_id     UUID,
+    topic        TEXT NOT NULL,
+    data         TEXT NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp(),
+    published_at TIMESTAMPTZ
+);
+
+COMMENT ON TABLE outbox IS 'Events written with the change they describe, waiting to be relayed';
+COMMENT ON COLUMN outbox.tenant_id IS 'Tenant UUID from Datomic the event belongs to';
+COMMENT ON COLUMN outbox.actor_id IS 'User who caused the event, or NULL for the system';
+COMMENT ON COLUMN outbox.topic IS 'Event bus topic, e.g. review.submitted';
+COMMENT ON COLUMN outbox.data IS 'Event data as EDN, so UUIDs survive the round trip';
+COMMENT ON COLUMN outbox.published_at IS 'When the relay published the event, or NULL while pending';
+
+CREATE INDEX outbox_pending_idx
+    ON outbox (created_at, id)
+    WHERE published_at IS NULL;
@@ -0,0 +1,2 @@
+DROP TABLE projected_events;
+DROP TABLE daily_rollups;
@@ -0,0 +1,25 @@
+CREATE TABLE daily_rollups (
+    tenant_id UUID NOT NULL,
+    day       DATE NOT NULL,
+    metric    TEXT NOT NULL,
+    currency  TEXT NOT NULL DEFAULT '',
+    value     BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (tenant_id, day, metric, currency)
+);
+
+COMMENT ON TABLE daily_rollups IS 'Per-tenant daily totals projected from events for dashboards';
+COMMENT ON COLUMN daily_rollups.tenant_id IS 'Tenant UUID from Datomic the totals belong to';
+COMMENT ON COLUMN daily_rollups.day IS 'UTC day the events happened on';
+COMMENT ON COLUMN daily_rollups.metric IS 'What is counted, e.g. orders, revenue or signups';
+COMMENT ON COLUMN daily_rollups.currency IS 'ISO 4217 code for revenue, empty for counts';
+COMMENT ON COLUMN daily_rollups.value IS 'Count, or revenue in minor units';
+
+CREATE INDEX daily_rollups_day_idx
+    ON daily_rollups (day);
+
+CREATE TABLE projected_events (
+    id           UUID PRIMARY KEY,
+    projected_at TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp()
+);
+
+COMMENT ON TABLE projected_events IS 'Outbox event IDs already counted, so a relayed repeat is ignored';
@@ -0,0 +1,6 @@
+DROP INDEX projected_events_projected_at_idx;
+DROP INDEX outbox_published_idx;
+DROP INDEX webhook_deliveries_created_at_idx;
+DROP INDEX notifications_created_at_idx;
+DROP INDEX activities_created_at_idx;
+DROP TABLE retention_overrides;
@@ -0,0 +1,29 @@
+CREATE TABLE retention_overrides (
+    tenant_id  UUID NOT NULL,
+    table_name TEXT NOT NULL,
+    days       INTEGER NOT NULL CHECK (days > 0),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (tenant_id, table_name)
+);
+
+COMMENT ON TABLE retention_overrides IS 'Shorter retention tenants chose for their own rows';
+COMMENT ON COLUMN retention_overrides.tenant_id IS 'Tenant UUID from Datomic the override applies to';
+COMMENT ON COLUMN retention_overrides.table_name IS 'Pruned table, e.g. activities';
+COMMENT ON COLUMN retention_overrides.days IS 'Days to keep, always fewer than the platform default';
+
+CREATE INDEX activities_created_at_idx
+    ON activities (created_at);
+
+CREATE INDEX notifications_created_at_idx
+    ON notifications (created_at);
+
+CREATE INDEX webhook_deliveries_created_at_idx
+    ON webhook_deliveries (created_at)
+    WHERE status <> 'pending';
+
+CREATE INDEX outbox_published_idx
+    ON outbox (created_at)
+    WHERE published_at IS NOT NULL;
+
+CREATE INDEX projected_events_projected_at_idx
+    ON projected_events (projected_at);
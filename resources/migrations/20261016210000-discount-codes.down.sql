@@ -0,0 +1,2 @@
+DROP INDEX discount_codes_tenant_id_code_idx;
+DROP TABLE discount_codes;
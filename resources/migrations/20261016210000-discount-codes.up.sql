@@ -0,0 +1,32 @@
+CREATE TABLE discount_codes (
+    id              UUID PRIMARY KEY,
+    tenant_id       UUID NOT NULL,
+    code            TEXT NOT NULL CHECK (code = upper(code)),
+    kind            TEXT NOT NULL CHECK (kind IN ('percent', 'fixed')),
+    percent_off     INTEGER CHECK (percent_off BETWEEN 1 AND 100),
+    amount_off      BIGINT CHECK (amount_off > 0),
+    currency        TEXT,
+    product_ids     UUID[] NOT NULL DEFAULT '{}',
+    stackable       BOOLEAN NOT NULL DEFAULT false,
+    max_redemptions INTEGER CHECK (max_redemptions > 0),
+    redemptions     INTEGER NOT NULL DEFAULT 0 CHECK (redemptions >= 0),
+    expires_at      TIMESTAMPTZ,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    disabled_at     TIMESTAMPTZ,
+    CHECK ((kind = 'percent' AND percent_off IS NOT NULL AND amount_off IS NULL)
+        OR (kind = 'fixed' AND amount_off IS NOT NULL AND currency IS NOT NULL AND percent_off IS NULL)),
+    CHECK (max_redemptions IS NULL OR redemptions <= max_redemptions)
+);
+
+COMMENT ON TABLE discount_codes IS 'Codes buyers enter at checkout for money off';
+COMMENT ON COLUMN discount_codes.tenant_id IS 'Tenant UUID from Datomic that issued the code';
+COMMENT ON COLUMN discount_codes.code IS 'What the buyer types, stored uppercase';
+COMMENT ON COLUMN discount_codes.kind IS 'percent takes percent_off; fixed takes amount_off in currency';
+COMMENT ON COLUMN discount_codes.amount_off IS 'Minor currency units, like :money/amount';
+COMMENT ON COLUMN discount_codes.currency IS 'ISO 4217 code of amount_off';
+COMMENT ON COLUMN discount_codes.product_ids IS 'Product UUIDs from Datomic the code is limited to, or empty for all';
+COMMENT ON COLUMN discount_codes.stackable IS 'Whether the code combines with other stackable codes';
+COMMENT ON COLUMN discount_codes.max_redemptions IS 'Redemptions allowed in total, or NULL for no limit';
+
+CREATE UNIQUE INDEX discount_codes_tenant_id_code_idx
+    ON discount_codes (tenant_id, code);
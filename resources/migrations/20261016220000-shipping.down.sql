@@ -0,0 +1,4 @@
+DROP INDEX shipping_rates_zone_idx;
+DROP TABLE shipping_rates;
+DROP INDEX shipping_zones_tenant_idx;
+DROP TABLE shipping_zones;
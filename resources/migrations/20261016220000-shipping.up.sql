@@ -0,0 +1,41 @@
+CREATE TABLE shipping_zones (
+    id         UUID PRIMARY KEY,
+    tenant_id  UUID NOT NULL,
+    name       TEXT NOT NULL,
+    regions    TEXT[] NOT NULL CHECK (cardinality(regions) > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE shipping_zones IS 'Places a tenant ships to, grouped so they share rates';
+COMMENT ON COLUMN shipping_zones.tenant_id IS 'Tenant UUID from Datomic';
+COMMENT ON COLUMN shipping_zones.regions IS 'ISO 3166-1 countries like GB, or ISO 3166-2 subdivisions like US-CA';
+
+CREATE INDEX shipping_zones_tenant_idx
+    ON shipping_zones (tenant_id);
+
+CREATE TABLE shipping_rates (
+    id           UUID PRIMARY KEY,
+    zone_id      UUID NOT NULL REFERENCES shipping_zones (id) ON DELETE CASCADE,
+    tenant_id    UUID NOT NULL,
+    name         TEXT NOT NULL,
+    kind         TEXT NOT NULL CHECK (kind IN ('flat', 'weight', 'free-above')),
+    amount       BIGINT NOT NULL CHECK (amount >= 0),
+    currency     TEXT NOT NULL,
+    min_grams    INTEGER CHECK (min_grams >= 0),
+    max_grams    INTEGER CHECK (max_grams > 0),
+    threshold    BIGINT CHECK (threshold > 0),
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK (kind <> 'weight' OR max_grams IS NOT NULL),
+    CHECK (kind <> 'free-above' OR threshold IS NOT NULL),
+    CHECK (min_grams IS NULL OR max_grams IS NULL OR min_grams < max_grams)
+);
+
+COMMENT ON TABLE shipping_rates IS 'What shipping to a zone costs';
+COMMENT ON COLUMN shipping_rates.kind IS 'flat always applies; weight applies between min_grams and max_grams; free-above is free from threshold up';
+COMMENT ON COLUMN shipping_rates.amount IS 'Minor currency units, like :money/amount';
+COMMENT ON COLUMN shipping_rates.min_grams IS 'Inclusive lower bound of basket weight for weight rates';
+COMMENT ON COLUMN shipping_rates.max_grams IS 'Exclusive upper bound of basket weight for weight rates';
+COMMENT ON COLUMN shipping_rates.threshold IS 'Subtotal in minor units from which a free-above rate costs nothing';
+
+CREATE INDEX shipping_rates_zone_idx
+    ON shipping_rates (zone_id);
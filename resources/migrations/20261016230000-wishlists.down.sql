@@ -0,0 +1,4 @@
+DROP INDEX wishlist_items_product_idx;
+DROP INDEX wishlist_items_session_idx;
+DROP INDEX wishlist_items_user_idx;
+DROP TABLE wishlist_items;
@@ -0,0 +1,25 @@
+CREATE TABLE wishlist_items (
+    tenant_id  UUID NOT NULL,
+    product_id UUID NOT NULL,
+    user_id    UUID,
+    sid_hash   TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK ((user_id IS NULL) <> (sid_hash IS NULL))
+);
+
+COMMENT ON TABLE wishlist_items IS 'Products storefront customers have saved for later';
+COMMENT ON COLUMN wishlist_items.tenant_id IS 'Tenant UUID from Datomic';
+COMMENT ON COLUMN wishlist_items.product_id IS 'Product UUID from Datomic';
+COMMENT ON COLUMN wishlist_items.user_id IS 'Signed-in owner, from Datomic; NULL when sid_hash owns the item';
+COMMENT ON COLUMN wishlist_items.sid_hash IS 'Anonymous owner, as sessions.sid_hash; moved to user_id on sign-in';
+
+CREATE UNIQUE INDEX wishlist_items_user_idx
+    ON wishlist_items (tenant_id, user_id, product_id)
+    WHERE user_id IS NOT NULL;
+
+CREATE UNIQUE INDEX wishlist_items_session_idx
+    ON wishlist_items (tenant_id, sid_hash, product_id)
+    WHERE sid_hash IS NOT NULL;
+
+CREATE INDEX wishlist_items_product_idx
+    ON wishlist_items (tenant_id, product_id);
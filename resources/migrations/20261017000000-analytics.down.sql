@@ -0,0 +1,4 @@
+DROP TABLE analytics_salts;
+DROP TABLE hourly_rollups;
+DROP INDEX page_views_at_idx;
+DROP TABLE page_views;
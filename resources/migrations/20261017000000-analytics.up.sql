@@ -0,0 +1,35 @@
+CREATE TABLE page_views (
+    tenant_id    UUID NOT NULL,
+    at           TIMESTAMPTZ NOT NULL DEFAULT now(),
+    path         TEXT NOT NULL,
+    visitor_hash TEXT NOT NULL
+);
+
+COMMENT ON TABLE page_views IS 'Storefront page views waiting to be rolled up, then deleted';
+COMMENT ON COLUMN page_views.tenant_id IS 'Tenant UUID from Datomic whose page was viewed';
+COMMENT ON COLUMN page_views.path IS 'Path viewed, without the query string';
+COMMENT ON COLUMN page_views.visitor_hash IS 'SHA-256 of the day''s salt, tenant, truncated IP and user agent';
+
+CREATE INDEX page_views_at_idx
+    ON page_views (at);
+
+CREATE TABLE hourly_rollups (
+    tenant_id UUID NOT NULL,
+    hour      TIMESTAMPTZ NOT NULL,
+    path      TEXT NOT NULL,
+    views     BIGINT NOT NULL DEFAULT 0,
+    visitors  BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (tenant_id, hour, path)
+);
+
+COMMENT ON TABLE hourly_rollups IS 'Per-tenant page views and visitors by hour and path';
+COMMENT ON COLUMN hourly_rollups.hour IS 'Start of the UTC hour the views happened in';
+COMMENT ON COLUMN hourly_rollups.path IS 'Path viewed, or empty for the hour''s totals across every path';
+COMMENT ON COLUMN hourly_rollups.visitors IS 'Distinct visitor hashes in the hour';
+
+CREATE TABLE analytics_salts (
+    day  DATE PRIMARY KEY,
+    salt TEXT NOT NULL
+);
+
+COMMENT ON TABLE analytics_salts IS 'Random salt for each UTC day''s visitor hashes, deleted once the day is over';
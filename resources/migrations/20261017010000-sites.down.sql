@@ -0,0 +1,6 @@
+DROP TABLE sites;
+DROP INDEX site_files_digest_idx;
+DROP TABLE site_files;
+DROP INDEX site_releases_tenant_idx;
+DROP TABLE site_releases;
+DROP TABLE blobs;
@@ -0,0 +1,46 @@
+CREATE TABLE blobs (
+    digest     TEXT PRIMARY KEY,
+    bytes      BYTEA NOT NULL,
+    size       BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE blobs IS 'Content-addressed file contents, stored once however many files share them';
+COMMENT ON COLUMN blobs.digest IS 'Hex SHA-256 of bytes';
+
+CREATE TABLE site_releases (
+    id         UUID PRIMARY KEY,
+    tenant_id  UUID NOT NULL,
+    files      INTEGER NOT NULL,
+    size       BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE site_releases IS 'Each upload of a tenant''s static site';
+COMMENT ON COLUMN site_releases.tenant_id IS 'Tenant UUID from Datomic whose site this is';
+COMMENT ON COLUMN site_releases.size IS 'Total bytes of every file in the release';
+
+CREATE INDEX site_releases_tenant_idx
+    ON site_releases (tenant_id, created_at);
+
+CREATE TABLE site_files (
+    release_id   UUID NOT NULL REFERENCES site_releases (id) ON DELETE CASCADE,
+    path         TEXT NOT NULL,
+    digest       TEXT NOT NULL REFERENCES blobs (digest),
+    content_type TEXT NOT NULL,
+    PRIMARY KEY (release_id, path)
+);
+
+COMMENT ON TABLE site_files IS 'The files of a release, by the path they are served at';
+COMMENT ON COLUMN site_files.path IS 'Path served, starting with a slash';
+
+CREATE INDEX site_files_digest_idx
+    ON site_files (digest);
+
+CREATE TABLE sites (
+    tenant_id   UUID PRIMARY KEY,
+    release_id  UUID NOT NULL REFERENCES site_releases (id),
+    released_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE sites IS 'The release each tenant serves. Switching releases is an update of one row';
@@ -0,0 +1,17 @@
+CREATE TABLE redirects (
+    id          UUID PRIMARY KEY,
+    tenant_id   UUID NOT NULL,
+    source      TEXT NOT NULL,
+    destination TEXT NOT NULL,
+    status      SMALLINT NOT NULL DEFAULT 301 CHECK (status IN (301, 302, 307, 308)),
+    hits        BIGINT NOT NULL DEFAULT 0,
+    last_hit_at TIMESTAMPTZ,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (tenant_id, source)
+);
+
+COMMENT ON TABLE redirects IS 'Paths on a tenant''s domain that redirect elsewhere, checked before routing';
+COMMENT ON COLUMN redirects.tenant_id IS 'Tenant UUID from Datomic whose domain the rule applies to';
+COMMENT ON COLUMN redirects.source IS 'Path to match exactly, or a prefix ending in /* whose rest replaces :splat in the destination';
+COMMENT ON COLUMN redirects.destination IS 'Path on the same domain, or an absolute http(s) URL';
+COMMENT ON COLUMN redirects.hits IS 'Requests the rule has redirected';
@@ -0,0 +1,28 @@
+CREATE TABLE page_revisions (
+    id         UUID PRIMARY KEY,
+    tenant_id  UUID NOT NULL,
+    page_id    UUID NOT NULL,
+    number     INTEGER NOT NULL CHECK (number > 0),
+    title      TEXT NOT NULL,
+    slug       TEXT NOT NULL,
+    blocks     TEXT NOT NULL,
+    author_id  UUID,
+    pinned     BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (page_id, number)
+);
+
+COMMENT ON TABLE page_revisions IS 'Each save of a CMS page, so edits can be compared and restored';
+COMMENT ON COLUMN page_revisions.tenant_id IS 'Tenant UUID from Datomic';
+COMMENT ON COLUMN page_revisions.page_id IS 'Page UUID from Datomic';
+COMMENT ON COLUMN page_revisions.number IS 'Counts up from 1 within the page';
+COMMENT ON COLUMN page_revisions.blocks IS 'The page''s content blocks as JSON, see bits.page';
+COMMENT ON COLUMN page_revisions.author_id IS 'User UUID from Datomic who saved it, NULL when nobody did';
+COMMENT ON COLUMN page_revisions.pinned IS 'The revision visitors see while the page is published';
+
+CREATE UNIQUE INDEX page_revisions_pinned_idx
+    ON page_revisions (page_id)
+    WHERE pinned;
+
+CREATE INDEX page_revisions_tenant_created_idx
+    ON page_revisions (tenant_id, created_at);
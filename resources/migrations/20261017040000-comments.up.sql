@@ -0,0 +1,36 @@
+CREATE TABLE comments (
+    id          UUID PRIMARY KEY,
+    tenant_id   UUID NOT NULL,
+    page_id     UUID NOT NULL,
+    parent_id   UUID REFERENCES comments (id) ON DELETE CASCADE,
+    author_id   UUID,
+    author_name TEXT NOT NULL DEFAULT '',
+    body        TEXT NOT NULL,
+    ip_hash     TEXT NOT NULL,
+    status      TEXT NOT NULL CHECK (status IN ('approved', 'pending', 'spam', 'rejected')),
+    spam_reason TEXT,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    reviewed_at TIMESTAMPTZ,
+    reviewed_by UUID
+);
+
+COMMENT ON TABLE comments IS 'Threaded comments on CMS pages';
+COMMENT ON COLUMN comments.tenant_id IS 'Tenant UUID from Datomic';
+COMMENT ON COLUMN comments.page_id IS 'Page UUID from Datomic';
+COMMENT ON COLUMN comments.parent_id IS 'The comment this replies to, or NULL at the top of a thread';
+COMMENT ON COLUMN comments.author_id IS 'User UUID from Datomic, or NULL when posted anonymously';
+COMMENT ON COLUMN comments.author_name IS 'Name an anonymous commenter gave';
+COMMENT ON COLUMN comments.ip_hash IS 'SHA-256 of the poster''s IP address, for rate limiting';
+COMMENT ON COLUMN comments.spam_reason IS 'Why a spam filter flagged the comment';
+COMMENT ON COLUMN comments.reviewed_by IS 'Tenant admin who approved or rejected the comment';
+
+CREATE INDEX comments_page_idx
+    ON comments (page_id, created_at)
+    WHERE status = 'approved';
+
+CREATE INDEX comments_queue_idx
+    ON comments (tenant_id, created_at, id)
+    WHERE status IN ('pending', 'spam');
+
+CREATE INDEX comments_ip_idx
+    ON comments (ip_hash, created_at);
@@ -0,0 +1,23 @@
+CREATE TABLE plugins (
+    id           UUID PRIMARY KEY,
+    tenant_id    UUID NOT NULL,
+    name         TEXT NOT NULL,
+    digest       TEXT NOT NULL REFERENCES blobs (digest),
+    api_version  INTEGER NOT NULL,
+    capabilities TEXT[] NOT NULL DEFAULT '{}',
+    enabled      BOOLEAN NOT NULL DEFAULT false,
+    last_error   TEXT,
+    failed_at    TIMESTAMPTZ,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (tenant_id, name)
+);
+
+COMMENT ON TABLE plugins IS 'WebAssembly plugins tenants installed';
+COMMENT ON COLUMN plugins.tenant_id IS 'Tenant UUID from Datomic';
+COMMENT ON COLUMN plugins.digest IS 'Blob holding the module';
+COMMENT ON COLUMN plugins.api_version IS 'Host API version the module was built against';
+COMMENT ON COLUMN plugins.capabilities IS 'What the tenant allowed the plugin to do, like checkout or slot';
+COMMENT ON COLUMN plugins.last_error IS 'Why the plugin last failed, shown to tenant admins';
+
+CREATE INDEX plugins_digest_idx
+    ON plugins (digest);
@@ -0,0 +1,15 @@
+CREATE TABLE pricing_rules (
+    id         UUID PRIMARY KEY,
+    tenant_id  UUID NOT NULL,
+    label      TEXT NOT NULL,
+    source     TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE pricing_rules IS 'Rules tenants write to take money off baskets at checkout';
+COMMENT ON COLUMN pricing_rules.tenant_id IS 'Tenant UUID from Datomic';
+COMMENT ON COLUMN pricing_rules.label IS 'What buyers see the discount called';
+COMMENT ON COLUMN pricing_rules.source IS 'The rule as the tenant wrote it, like 10% off when subtotal > 100';
+
+CREATE INDEX pricing_rules_tenant_idx
+    ON pricing_rules (tenant_id, created_at, id);
@@ -435,6 +435,43 @@
     });
   }
 
+  // ---------------------------------------------------------------------------
+  // Page Views
+  //
+  // One beacon per page load for the tenant's own analytics. The server
+  // ignores it when tracking is declined, but there's no need to send it.
+
+  function sendPageView() {
+    if (navigator.globalPrivacyControl || navigator.doNotTrack === "1") return;
+    const csrf = getCsrf();
+    if (!csrf) return;
+    navigator.sendBeacon(
+      "/collect",
+      new URLSearchParams({ csrf, path: window.location.pathname }),
+    );
+  }
+
+  // ---------------------------------------------------------------------------
+  // Environment Banner
+  //
+  // Dismissed for the rest of the tab's session, so it's back the next time
+  // someone opens the site and can't be forgotten about.
+
+  const bannerKey = "bits-banner-dismissed";
+
+  function initBanner() {
+    const banner = document.getElementById("environment-banner");
+    if (!banner) return;
+    if (sessionStorage.getItem(bannerKey)) {
+      banner.hidden = true;
+      return;
+    }
+    banner.querySelector("button")?.addEventListener("click", () => {
+      sessionStorage.setItem(bannerKey, "1");
+      banner.hidden = true;
+    });
+  }
+
   // ---------------------------------------------------------------------------
   // Init
 
@@ -449,5 +486,7 @@
 
     connect();
     initMouseTracking();
+    sendPageView();
+    initBanner();
   });
 })();
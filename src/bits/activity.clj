@@ -0,0 +1,94 @@
+(ns bits.activity
+  (:require
+   [bits.pagination :as pagination]
+   [bits.postgres :as postgres]
+   [bits.postgres.activity :as postgres.activity]
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Kinds
+
+(def kinds
+  #{"api-key.issued"
+    "api-key.revoked"
+    "discount.created"
+    "discount.disabled"
+    "moderation.dismissed"
+    "moderation.hidden"
+    "moderation.reinstated"
+    "moderation.suspended"
+    "page.published"
+    "page.unpublished"
+    "plugin.disabled"
+    "plugin.enabled"
+    "plugin.installed"
+    "plugin.uninstalled"
+    "pricing.changed"
+    "redirects.changed"
+    "resource.deleted"
+    "resource.restored"
+    "retention.changed"
+    "session.signed-in"
+    "shipping.changed"
+    "site.published"
+    "site.rolled-back"
+    "site.taken-down"
+    "webhook.disabled"
+    "webhook.registered"})
+
+;;; ----------------------------------------------------------------------------
+;;; Recording
+
+(defn record!
+  "Appends to the tenant's feed. Actor is the user responsible, or nil for
+  activity the system did on its own."
+  [feed tenant-id actor-id kind data]
+  {:pre [(contains? kinds kind) (map? data)]}
+  (span/with-span! {:name ::record!}
+    (postgres/execute-one! (:postgres feed)
+                           {:insert-into :activities
+                            :values      [{:id        (random-uuid)
+                                           :tenant-id tenant-id
+                                           :actor-id  actor-id
+                                           :kind      kind
+                                           :data      [:lift data]}]})))
+
+;;; ----------------------------------------------------------------------------
+;;; Reading
+;;;
+;;; Pages are keyed on (created_at, id) so rows inserted while someone scrolls
+;;; never shift what the next page returns.
+
+(def ordering
+  {:direction :desc
+   :columns   [[:created-at ::postgres.activity/created-at]
+               [:id ::postgres.activity/id]]})
+
+(defn list-activities
+  "Returns a page of the tenant's activities, newest first."
+  [feed tenant-id page-request]
+  {:post [(s/valid? (s/coll-of ::postgres.activity/persisted) (:items %))]}
+  (span/with-span! {:name ::list-activities}
+    (-> (postgres/execute! (:postgres feed)
+                           (pagination/paginate {:select [:id :actor-id :kind :data :created-at]
+                                                 :from   [:activities]
+                                                 :where  [:= :tenant-id tenant-id]}
+                                                ordering
+                                                page-request))
+        (pagination/page ordering page-request))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Feed [postgres])
+
+(defmethod print-method Feed
+  [_ ^java.io.Writer w]
+  (.write w "#<Feed>"))
+
+(defn make-feed
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Feed config))
@@ -0,0 +1,219 @@
+(ns bits.analytics
+  "First-party page view counts for tenants, without cookies or third parties.
+
+  Storefront pages send a beacon to `/collect` with the path viewed. Nothing
+  that identifies a visitor is stored: the IP address is truncated to its
+  network, then hashed with the tenant, user agent and a random salt for the
+  UTC day. Salts are deleted once their day is over, so a visitor can be told
+  apart from others within a day but not followed from one day to the next,
+  or from one tenant to another.
+
+  Views wait in `page_views` until the leader's reaper rolls every completed
+  hour up into `hourly_rollups`, counting views and distinct visitors by path
+  and across the whole storefront under the empty path, and deletes them.
+  Visitors who send Do Not Track or Global Privacy Control, and anything that
+  looks like a bot, aren't counted at all. Neither are views past
+  `:views-per-minute` from one IP address, so a single client can't flood
+  `page_views`."
+  (:require
+   [bits.cache :as cache]
+   [bits.clock :as clock]
+   [bits.crypto :as crypto]
+   [bits.postgres :as postgres]
+   [bits.postgres.analytics]
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [com.stuartsierra.component :as component]
+   [steffan-westcott.clj-otel.api.metrics.instrument :as instrument]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (com.google.common.net InetAddresses)
+   (java.net InetAddress)
+   (java.time Instant OffsetDateTime ZoneOffset)
+   (java.time.temporal ChronoUnit)))
+
+;;; ----------------------------------------------------------------------------
+;;; Privacy
+
+(defn truncate-ip
+  "The address with its host part zeroed: the last octet of IPv4 addresses,
+  and all but the first 48 bits of IPv6 ones. Nil when s isn't an address."
+  [s]
+  (when (and (string? s) (InetAddresses/isInetAddress s))
+    (let [bytes (.getAddress (InetAddresses/forString s))
+          kept  (if (= 4 (alength bytes)) 3 6)]
+      (doseq [i (range kept (alength bytes))]
+        (aset-byte bytes i 0))
+      (InetAddresses/toAddrString (InetAddress/getByAddress bytes)))))
+
+(defn visitor-hash
+  [salt tenant-id ip user-agent]
+  (crypto/sha256 (str/join "|" [salt tenant-id (truncate-ip ip) user-agent])))
+
+(defn bot?
+  [user-agent]
+  (or (str/blank? user-agent)
+      (boolean (re-find #"(?i)bot|crawl|spider|slurp|headless|preview" user-agent))))
+
+(defn normalize-path
+  "The path without its query or fragment, or nil when it isn't one."
+  [s]
+  (when (and (string? s) (str/starts-with? s "/") (not (str/starts-with? s "//")))
+    (let [path (first (str/split s #"[?#]" 2))]
+      (when (<= 1 (count path) 512)
+        path))))
+
+;;; ----------------------------------------------------------------------------
+;;; Collecting
+
+(defn- utc-day
+  [^OffsetDateTime at]
+  (.toLocalDate (.withOffsetSameInstant at ZoneOffset/UTC)))
+
+(defn- salt!
+  "The day's salt, shared by every instance through Postgres. Each instance
+  keeps only the current day's."
+  [{:keys [postgres randomizer salts]} day]
+  (or (get @salts day)
+      (do
+        (postgres/execute! postgres {:insert-into :analytics-salts
+                                     :values      [{:day day :salt (crypto/random-nonce randomizer)}]
+                                     :on-conflict [:day]
+                                     :do-nothing  true})
+        (let [salt (:bits.postgres.analytics-salt/salt
+                    (postgres/execute-one! postgres {:select [:salt]
+                                                     :from   [:analytics-salts]
+                                                     :where  [:= :day day]}))]
+          (reset! salts {day salt})
+          salt))))
+
+(defn- allow?
+  "Counts a view against the IP's current minute, true while it's within the
+  limit. Counts are kept by each instance for a minute and never stored."
+  [{:keys [views-per-minute windows]} ip ^OffsetDateTime now]
+  (let [window (cache/lookup windows [ip (.truncatedTo now ChronoUnit/MINUTES)] #(atom 0))]
+    (<= (swap! window inc) views-per-minute)))
+
+(defn collect!
+  "Records a view of path on the tenant's storefront. Returns whether it was
+  counted."
+  [analytics {:keys [ip path tenant-id user-agent]}]
+  (span/with-span! {:name ::collect!}
+    (let [path (normalize-path path)
+          now  (clock/now (:clock analytics))]
+      (if (or (nil? tenant-id) (nil? path) (bot? user-agent) (not (allow? analytics ip now)))
+        false
+        (do
+          (postgres/execute! (:postgres analytics)
+                             {:insert-into :page-views
+                              :values      [{:tenant-id    tenant-id
+                                             :at           now
+                                             :path         path
+                                             :visitor-hash (visitor-hash (salt! analytics (utc-day now))
+                                                                         tenant-id ip user-agent)}]})
+          (instrument/add! (:collected-counter analytics) {:value 1})
+          true)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Rolling up
+
+(def ^:private hour-of-view
+  [:date-trunc [:inline "hour"] :at [:inline "UTC"]])
+
+(defn roll-up!
+  "Moves views from every completed hour into the hourly rollups, and deletes
+  the salts of days gone by. Returns how many views were rolled up."
+  [analytics]
+  (span/with-span! {:name ::roll-up!}
+    (let [now    (clock/now (:clock analytics))
+          cutoff (.truncatedTo (.withOffsetSameInstant ^OffsetDateTime now ZoneOffset/UTC) ChronoUnit/HOURS)]
+      (postgres/with-transaction [tx (:postgres analytics)]
+        ;; Once by path, and once across every path so a visitor who viewed
+        ;; several pages in the hour counts once.
+        (doseq [by-path? [true false]]
+          (postgres/execute! tx {:insert-into   [:hourly-rollups [:tenant-id :hour :path :views :visitors]]
+                                 :select        [:tenant-id
+                                                 [hour-of-view]
+                                                 (if by-path? :path [[:inline ""]])
+                                                 [[:count :*]]
+                                                 [[:count [:distinct :visitor-hash]]]]
+                                 :from          [:page-views]
+                                 :where         [:< :at cutoff]
+                                 :group-by      (cond-> [:tenant-id hour-of-view]
+                                                  by-path? (conj :path))
+                                 :on-conflict   [:tenant-id :hour :path]
+                                 :do-update-set {:views    [:+ :hourly-rollups.views :excluded.views]
+                                                 :visitors [:+ :hourly-rollups.visitors :excluded.visitors]}}))
+        (postgres/execute! tx {:delete-from :analytics-salts
+                               :where       [:< :day (utc-day now)]})
+        (let [[{:keys [next.jdbc/update-count]}]
+              (postgres/execute! tx {:delete-from :page-views
+                                     :where       [:< :at cutoff]})]
+          (or update-count 0))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Reading
+
+(defn hourly
+  "The tenant's views and visitors for each hour from `from` up to but not
+  including `to`, oldest first, as `{:hour ... :views n :visitors n}`. Hours
+  without views are left out."
+  [analytics tenant-id ^Instant from ^Instant to]
+  (span/with-span! {:name ::hourly}
+    (mapv (fn [{:bits.postgres.hourly-rollup/keys [hour views visitors]}]
+            {:hour hour :views views :visitors visitors})
+          (postgres/execute! (postgres/replica (:postgres analytics))
+                             {:select   [:hour :views :visitors]
+                              :from     [:hourly-rollups]
+                              :where    [:and
+                                         [:= :tenant-id tenant-id]
+                                         [:= :path ""]
+                                         [:>= :hour from]
+                                         [:< :hour to]]
+                              :order-by [:hour]}))))
+
+(defn top-paths
+  "The tenant's most viewed paths since `from`, as `{:path ... :views n}`."
+  [analytics tenant-id ^Instant from limit]
+  (span/with-span! {:name ::top-paths}
+    (mapv (fn [{:bits.postgres.hourly-rollup/keys [path] :keys [views]}]
+            {:path path :views views})
+          (postgres/execute! (postgres/replica (:postgres analytics))
+                             {:select   [:path [[:cast [:sum :views] :bigint] :views]]
+                              :from     [:hourly-rollups]
+                              :where    [:and
+                                         [:= :tenant-id tenant-id]
+                                         [:<> :path ""]
+                                         [:>= :hour from]]
+                              :group-by [:path]
+                              :order-by [[:views :desc] :path]
+                              :limit    limit}))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Analytics [clock collected-counter maximum-size postgres randomizer salts
+                      views-per-minute windows]
+  component/Lifecycle
+  (start [this]
+    (assoc this
+           :salts             (atom {})
+           :windows           (cache/make-cache {:maximum-size maximum-size
+                                                 :ttl-seconds  60})
+           :collected-counter (instrument/instrument
+                               {:name            "analytics.collected"
+                                :instrument-type :counter
+                                :unit            "{view}"
+                                :description     "Storefront page views counted"})))
+  (stop [this]
+    (assoc this :collected-counter nil :salts nil :windows nil)))
+
+(defmethod print-method Analytics
+  [_ ^java.io.Writer w]
+  (.write w "#<Analytics>"))
+
+(defn make-analytics
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Analytics config))
@@ -1,6 +1,7 @@
 (ns bits.anomaly
   (:require
-   [clojure.spec.alpha :as s]))
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]))
 
 ;;; ----------------------------------------------------------------------------
 ;;; Categories
@@ -13,7 +14,7 @@
     ::conflict    ; state conflict, resolve and retry (409)
     ::fault       ; internal error (500)
     ::forbidden   ; not authorized (403)
-    ::incorrect   ; bad input (400)
+    ::incorrect   ; bad input (422)
     ::interrupted ; request cancelled (499)
     ::not-found   ; resource doesn't exist (404)
     ::unavailable ; service down, don't retry yet (503)
@@ -25,7 +26,8 @@
 
 (s/def ::category categories)
 (s/def ::message string?)
-(s/def ::anomaly (s/keys :req [::category] :opt [::message]))
+(s/def ::retry-after-seconds pos-int?)
+(s/def ::anomaly (s/keys :req [::category] :opt [::message ::retry-after-seconds]))
 
 ;;; ----------------------------------------------------------------------------
 ;;; Predicates
@@ -38,6 +40,34 @@
   [x]
   (contains? #{::busy ::unavailable ::interrupted} (::category x)))
 
+;;; ----------------------------------------------------------------------------
+;;; HTTP
+;;;
+;;; The one place categories become statuses and the codes clients branch on.
+;;; Codes are part of the API, so they never change once shipped.
+
+(def statuses
+  {::busy        429
+   ::conflict    409
+   ::fault       500
+   ::forbidden   403
+   ::incorrect   422
+   ::interrupted 499
+   ::not-found   404
+   ::unavailable 503
+   ::unsupported 501})
+
+(defn status
+  [anom]
+  (get statuses (::category anom) 500))
+
+(defn code
+  "The stable, machine-readable code for the anomaly, like `not_found`."
+  [anom]
+  (if (contains? categories (::category anom))
+    (str/replace (name (::category anom)) "-" "_")
+    "fault"))
+
 ;;; ----------------------------------------------------------------------------
 ;;; Constructors
 
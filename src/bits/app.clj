@@ -1,22 +1,57 @@
 (ns bits.app
   (:require
+   [bits.activity :as activity]
+   [bits.analytics :as analytics]
+   [bits.anomaly :as anom]
    [bits.asset :as asset]
+   [bits.auth.api-key :as api-key]
+   [bits.auth.oidc :as oidc]
    [bits.auth.rate-limit :as rate-limit]
+   [bits.blob :as blob]
    [bits.boot :as boot]
+   [bits.clock :as clock]
    [bits.cluster :as cluster]
+   [bits.comment :as comment]
    [bits.crypto :as crypto]
    [bits.datomic :as datomic]
+   [bits.discount :as discount]
+   [bits.event :as event]
+   [bits.export :as export]
+   [bits.flag :as flag]
+   [bits.leader :as leader]
+   [bits.log.tail :as log.tail]
+   [bits.moderation :as moderation]
    [bits.module :as module]
+   [bits.notification :as notification]
+   [bits.outbox :as outbox]
+   [bits.plugin :as plugin]
    [bits.postgres :as postgres]
+   [bits.pricing :as pricing]
+   [bits.projection :as projection]
+   [bits.realm :as realm]
    [bits.reaper :as reaper]
+   [bits.redirect :as redirect]
+   [bits.retention :as retention]
+   [bits.review :as review]
+   [bits.schedule :as schedule]
+   [bits.secret :as secret]
    [bits.service :as service]
    [bits.session :as session]
+   [bits.shadow :as shadow]
+   [bits.shipping :as shipping]
+   [bits.site :as site]
    [bits.spec]
    [bits.string :as string]
+   [bits.webhook :as webhook]
+   [bits.wishlist :as wishlist]
    [camel-snake-kebab.core :as csk]
+   [clojure.edn :as edn]
+   [clojure.java.io :as io]
    [clojure.spec.alpha :as s]
    [clojure.string :as str]
+   [clojure.walk :as walk]
    [com.stuartsierra.component :as component]
+   [io.pedestal.log :as log]
    [lambdaisland.uri :as uri]
    [medley.core :as medley])
   (:import
@@ -25,14 +60,14 @@
 ;;; ----------------------------------------------------------------------------
 ;;; Config
 
-(defmacro ^:private env
+(defn- env-var
   [k]
-  (let [s (csk/->SCREAMING_SNAKE_CASE_STRING (name k))]
-    `(System/getenv ~s)))
+  (System/getenv (csk/->SCREAMING_SNAKE_CASE_STRING (name k))))
 
-(defmacro ^:private env-or
-  [k default]
-  `(or (env ~k) ~default))
+(defn- parse-long*
+  [s]
+  (or (parse-long s)
+      (throw (ex-info (str "Not an integer: " s) {:value s}))))
 
 (defn- normalize-database-url
   ([s]
@@ -47,6 +82,11 @@
                  :scheme   (str "jdbc:" adapter)
                  :user     nil)))))
 
+(defn- normalize-region
+  [{:keys [database-url replica-url] :as region}]
+  (cond-> (assoc region :database-url (normalize-database-url database-url))
+    replica-url (assoc :replica-url (normalize-database-url replica-url))))
+
 (defn- parse-hosts
   [hosts]
   (if (nil? hosts)
@@ -59,82 +99,419 @@
                   (map parse-host))
             (str/split hosts #",")))))
 
-;; TODO Use Malli (or clojure.spec) to coerce and parse/validate configuration.
-(defn read-config
+;;; ----------------------------------------------------------------------------
+;;; Layers
+;;;
+;;; Config is built from defaults, then an optional EDN file, then environment
+;;; variables, then overrides from the command line. Later layers win, and
+;;; maps merge deeply so a file only needs the keys it changes:
+;;;
+;;;   {:service {:platform-domain "bits.page"}
+;;;    :webhooks {:max-attempts 12}}
+;;;
+;;; The file is read from --config or BITS_CONFIG. Values in it may be
+;;; `#bits/secret` references, which are read through the provider named by
+;;; `:secrets` (SECRETS_PROVIDER) once the layers are merged.
+
+(defn- defaults
+  []
+  {:activities    {}
+   :analytics     {:maximum-size     10000
+                   :views-per-minute 60}
+   :api-keys      {:requests-per-minute 60}
+   :blobs         {}
+   :buster        {:resources #{"public/apple-touch-icon.png"
+                                "public/app.css"
+                                "public/bits.js"
+                                "public/DMSans.woff2"
+                                "public/DMSerifDisplay.woff2"
+                                "public/favicon.ico"
+                                "public/favicon.svg"
+                                "public/idiomorph@0.7.4.min.js"
+                                "public/JetBrainsMono.woff2"
+                                "public/logo.svg"}}
+   :clock         {}
+   :cluster       {:bind-addr     "0.0.0.0"
+                   :bind-port     7800
+                   :cluster-name  "bits"
+                   :initial-hosts "127.0.0.1:7800"
+                   :keystore-path "certs/cluster-keystore.p12"}
+   :comments      {:max-per-window 5
+                   :window-minutes 10}
+   :discounts     {}
+   :events        {:buffer-size 256}
+   :exports       {}
+   :flags         {:maximum-size 10000
+                   :ttl-seconds  5}
+   :keymaster     {:argon {:alg         :argon2id
+                           :iterations  3
+                           :memory      (* 64 1024)
+                           :parallelism 1}}
+   :leader        {:lock-name     "bits.background"
+                   :renew-seconds 10}
+   :log-tail      {:capacity   1000
+                   :refresh-ms 250}
+   :moderator     {}
+   :notifications {:buffer-size 64}
+   :oidc          {:connect-timeout-ms 5000
+                   :maximum-size       1000
+                   :ttl-seconds        3600}
+   :outbox        {:batch-size   100
+                   :poll-seconds 1}
+   :plugins       {:max-bytes        (* 2 1024 1024)
+                   :max-memory-pages 32
+                   :max-output-bytes (* 64 1024)
+                   :maximum-size     10000
+                   :timeout-ms       50
+                   :ttl-seconds      10}
+   :postgres      {:connection-timeout-ms 5000
+                   :maximum-pool-size     10
+                   :minimum-idle          2
+                   :slow-query-ms         250
+                   :statement-timeout-ms  30000}
+   :pricing       {:max-steps    10000
+                   :maximum-size 10000
+                   :timeout-ms   10
+                   :ttl-seconds  10}
+   :projector     {:buffer-size 1024}
+   :rate-limiter  {:email-window-minutes 15
+                   :email-max-attempts   5
+                   :ip-window-minutes    15
+                   :ip-max-attempts      20}
+   :reaper        {:interval-hours 1
+                   :retention-days 30}
+   :redirects     {:maximum-size 10000
+                   :ttl-seconds  10}
+   :resolver      {:maximum-size 10000
+                   :ttl-seconds  60}
+   :retention     {:batch-size 1000
+                   :days       {:activities         365
+                                :api-key-requests   2
+                                :hourly-rollups     400
+                                :notifications      90
+                                :outbox             7
+                                :page-revisions     365
+                                :projected-events   30
+                                :webhook-deliveries 30}}
+   :reviews       {:max-body 5000}
+   :scheduler     {:poll-seconds 30}
+   :secrets       {:provider :env}
+   :shadow        {:candidates {}}
+   :service       {:cookie-name           "__Host-bits"
+                   :cookie-secure         true
+                   :csrf-cookie-name      "__Host-bits-csrf"
+                   :csrf-secret           "default-csrf-secret-change-in-prod"
+                   :environment           :development
+                   :http-host             "0.0.0.0"
+                   :http-port             3000
+                   :maintenance-allowlist #{}
+                   :max-refresh-ms        50
+                   :max-upload-bytes      (* 50 1024 1024)
+                   :server-name           "Bits"
+                   :sse-reconnect-ms      1000}
+   :session-store {:idle-timeout-days 30}
+   :sites         {:max-files     1000
+                   :quota-bytes   (* 100 1024 1024)
+                   :releases-kept 5}
+   :webhooks      {:backoff-base-seconds 30
+                   :batch-size           20
+                   :max-attempts         8
+                   :poll-seconds         5
+                   :timeout-ms           10000}
+   :wishlists     {}})
+
+(defn- read-file
+  [path]
+  (let [file (io/file path)]
+    (when-not (.exists file)
+      (throw (ex-info (str "Config file not found: " path) {:path path})))
+    (try
+      (edn/read-string {:readers {'bits/secret secret/read-ref}} (slurp file))
+      (catch Exception cause
+        (throw (ex-info (str "Config file is not valid EDN: " path) {:path path} cause))))))
+
+(defn- parse-env
+  "Parses an environment variable, naming it when the value doesn't parse."
+  [k parse s]
+  (try
+    (parse s)
+    (catch Exception cause
+      (throw (ex-info (format "Can't parse %s: %s"
+                              (csk/->SCREAMING_SNAKE_CASE_STRING (name k))
+                              (ex-message cause))
+                      {:variable k}
+                      cause)))))
+
+(defn- parse-field-keys
+  "Reads `id:base64,id:base64`."
+  [s]
+  (into {}
+        (map (fn [pair]
+               (let [[id k] (str/split (str/trim pair) #":" 2)]
+                 (when (str/blank? k)
+                   (throw (ex-info "Expected id:base64" {})))
+                 [id k])))
+        (str/split s #",")))
+
+(defn- parse-set
+  "Reads `a,b,c`."
+  [s]
+  (into #{} (comp (map str/trim) (remove str/blank?)) (str/split s #",")))
+
+(defn- environment
+  []
+  (let [layer (fn [config k path parse]
+                (if-some [s (env-var k)]
+                  (assoc-in config path (parse-env k parse s))
+                  config))]
+    (-> {}
+        (layer :aws-access-key-id [:secrets :aws :access-key-id] identity)
+        (layer :aws-region [:secrets :aws :region] identity)
+        (layer :aws-secret-access-key [:secrets :aws :secret-access-key] identity)
+        (layer :aws-session-token [:secrets :aws :session-token] identity)
+        (layer :cluster-bind-addr [:cluster :bind-addr] identity)
+        (layer :cluster-bind-port [:cluster :bind-port] parse-long*)
+        (layer :cluster-initial-hosts [:cluster :initial-hosts] identity)
+        (layer :cluster-keystore-password [:cluster :keystore-password] identity)
+        (layer :cluster-keystore-path [:cluster :keystore-path] identity)
+        (layer :cluster-name [:cluster :cluster-name] identity)
+        (layer :cluster-peer-name [:cluster :peer-name] identity)
+        (layer :csrf-secret [:service :csrf-secret] identity)
+        (layer :field-key-id [:keymaster :active-field-key] identity)
+        (layer :field-keys [:keymaster :field-keys] parse-field-keys)
+        (layer :maintenance-allowlist [:service :maintenance-allowlist] parse-set)
+        (layer :data-region [:postgres :region] identity)
+        (layer :database-credentials-path [:postgres :credentials-path] identity)
+        (layer :database-pool-size [:postgres :maximum-pool-size] parse-long*)
+        (layer :database-replica-url [:postgres :replica-url] identity)
+        (layer :database-url [:postgres :database-url] identity)
+        (layer :datomic-uri [:datomic :uri] identity)
+        (layer :environment [:service :environment] keyword)
+        (layer :platform-domain [:service :platform-domain] identity)
+        (layer :port [:service :http-port] parse-long*)
+        (layer :secrets-provider [:secrets :provider] keyword)
+        (layer :sse-reconnect-ms [:service :sse-reconnect-ms] parse-long*)
+        (layer :vault-addr [:secrets :vault :address] identity)
+        (layer :vault-token [:secrets :vault :token] identity))))
+
+(defn- resolve-secrets
+  "Replaces `#bits/secret` references using the configured provider. The env
+  provider reads the process environment, which only bits.app may touch."
+  [config]
+  (let [config (cond-> config
+                 (= :env (get-in config [:secrets :provider]))
+                 (assoc-in [:secrets :variables] (into {} (System/getenv))))]
+    (if (seq (secret/refs config))
+      (secret/resolve-refs (secret/make-provider (:secrets config)) config)
+      config)))
+
+(defn- dev-field-keys
+  "Field keys shipped with the dev classpath alone. They're public, so builds
+  never include them and a deployment missing FIELD_KEYS fails to start."
   []
-  (let [database-url (-> :database-url env normalize-database-url)]
-    {:buster        {:resources #{"public/apple-touch-icon.png"
-                                  "public/app.css"
-                                  "public/bits.js"
-                                  "public/DMSans.woff2"
-                                  "public/DMSerifDisplay.woff2"
-                                  "public/favicon.ico"
-                                  "public/favicon.svg"
-                                  "public/idiomorph@0.7.4.min.js"
-                                  "public/JetBrainsMono.woff2"
-                                  "public/logo.svg"}}
-     :cluster       {:bind-addr         (env-or :cluster-bind-addr "0.0.0.0")
-                     :bind-port         (parse-long (env-or :cluster-bind-port "7800"))
-                     :cluster-name      "bits"
-                     :initial-hosts     (parse-hosts (env-or :cluster-initial-hosts "127.0.0.1:7800"))
-                     :keystore-password (env :cluster-keystore-password)
-                     :keystore-path     (env-or :cluster-keystore-path "certs/cluster-keystore.p12")}
-     :datomic       {:uri (env :datomic-uri)}
-     :keymaster     {:argon {:alg         :argon2id
-                             :iterations  3
-                             :memory      (* 64 1024)
-                             :parallelism 1}}
-     :postgres      {:database-url database-url}
-     :rate-limiter  {:email-window-minutes 15
-                     :email-max-attempts   5
-                     :ip-window-minutes    15
-                     :ip-max-attempts      20}
-     :reaper        {:interval-hours 1}
-     :service       {:cookie-name      "__Host-bits"
-                     :cookie-secure    true
-                     :csrf-cookie-name "__Host-bits-csrf"
-                     :csrf-secret      (env-or :csrf-secret "default-csrf-secret-change-in-prod")
-                     :http-host        "0.0.0.0"
-                     :http-port        (parse-long (env-or :port "3000"))
-                     :max-refresh-ms   50
-                     :modules          (module/must-combine! service/modules)
-                     :platform-domain  (env :platform-domain)
-                     :server-name      "Bits"
-                     :sse-reconnect-ms (parse-long (env-or :sse-reconnect-ms "1000"))}
-     :session-store {:idle-timeout-days 30}}))
+  (some-> (io/resource "bits/dev-field-keys.edn") slurp edn/read-string))
+
+(defn- field-keys
+  [config]
+  (let [dev-keys (when (= :development (get-in config [:service :environment]))
+                   (dev-field-keys))]
+    (cond
+      (seq (get-in config [:keymaster :field-keys]))
+      config
+
+      dev-keys
+      (update config :keymaster #(merge {:active-field-key "dev"} % {:field-keys dev-keys}))
+
+      :else
+      (throw (ex-info "FIELD_KEYS must be set" {:variable :field-keys})))))
+
+(defn- finalize
+  "Turns values that are easier to write as strings into what components take."
+  [config]
+  (-> config
+      field-keys
+      (update-in [:cluster :initial-hosts] #(cond-> % (string? %) parse-hosts))
+      (update-in [:postgres :database-url] #(some-> % normalize-database-url))
+      (update-in [:postgres :replica-url] #(some-> % normalize-database-url))
+      (update-in [:postgres :regions] #(some-> % (update-vals normalize-region)))
+      (assoc-in [:service :modules] (module/must-combine! service/modules))))
+
+(defn read-config
+  "Options are the command line layer: `:file` names an EDN file and
+  `:overrides` is merged over everything else."
+  ([]
+   (read-config {}))
+  ([{:keys [file overrides]}]
+   (let [file (or file (env-var :bits-config))]
+     (-> (medley/deep-merge (defaults)
+                            (some-> file read-file)
+                            (environment)
+                            overrides)
+         resolve-secrets
+         finalize))))
+
+;;; ----------------------------------------------------------------------------
+;;; Validation
+
+(def ^:private secret-keys
+  #{:csrf-secret
+    :field-keys
+    :keystore-password
+    :secret-access-key
+    :session-token
+    :token
+    :variables})
+
+(defn redact
+  "Config safe to print. Secrets are masked and database URLs lose their
+  passwords."
+  [config]
+  (walk/postwalk
+   (fn [x]
+     (cond
+       (and (map-entry? x) (contains? secret-keys (key x)))
+       [(key x) (when (some? (val x)) "[redacted]")]
+
+       (and (map-entry? x) (#{:database-url :replica-url} (key x)) (string? (val x)))
+       [(key x) (str/replace (val x) #"password=[^&]*" "password=[redacted]")]
+
+       :else
+       x))
+   config))
+
+(defn- missing-key
+  "The key a failed `s/keys` presence check was looking for."
+  [form]
+  (when (and (seq? form) (= 'fn (first form)))
+    (let [body (last form)]
+      (when (and (seq? body) (= 'contains? (first body)))
+        (last body)))))
+
+(defn problems
+  "Explains each invalid key as `{:path [...] :message \"...\"}`. Values are
+  left out so secrets never reach logs."
+  [config]
+  (for [{:keys [in pred]} (::s/problems (s/explain-data :bits.system/config config))
+        :let              [form (s/abbrev pred)
+                           k    (missing-key form)]]
+    (if k
+      {:path (conj (vec in) k) :message "is required"}
+      {:path (vec in) :message (str "must satisfy " (pr-str form))})))
 
 ;;; ----------------------------------------------------------------------------
 ;;; System
 
 (defn components
   [config]
-  {:bootstrapper  (boot/make-bootstrapper     (:bootstrapper config))
+  {:activities    (activity/make-feed         (:activities config))
+   :analytics     (analytics/make-analytics   (:analytics config))
+   :blobs         (blob/make-blob-store       (:blobs config))
+   :api-keys      (api-key/make-registry      (:api-keys config))
+   :bootstrapper  (boot/make-bootstrapper     (:bootstrapper config))
    :buster        (asset/make-buster          (:buster config))
+   :clock         (clock/make-clock           (:clock config))
    :cluster       (cluster/make-peer          (:cluster config))
+   :comments      (comment/make-comments      (:comments config))
    :datomic       (datomic/make-datomic       (:datomic config))
+   :discounts     (discount/make-discounts    (:discounts config))
+   :events        (event/make-bus             (:events config))
+   :exports       (export/make-exporter       (:exports config))
+   :flags         (flag/make-flagger          (:flags config))
    :keymaster     (crypto/make-keymaster      (:keymaster config))
+   :leader        (leader/make-leader         (:leader config))
+   :log-tail      (log.tail/make-tail         (:log-tail config))
    :migrator      (postgres/make-migrator     (:postgres config))
+   :moderator     (moderation/make-moderator  (:moderator config))
+   :notifications (notification/make-notifier (:notifications config))
+   :oidc          (oidc/make-relying-party    (:oidc config))
+   :outbox        (outbox/make-relay          (:outbox config))
+   :plugins       (plugin/make-plugins        (:plugins config))
    :postgres      (postgres/make-postgres     (:postgres config))
+   :pricing       (pricing/make-pricing       (:pricing config))
+   :projector     (projection/make-projector  (:projector config))
    :randomizer    (crypto/make-randomizer     (:randomizer config))
    :rate-limiter  (rate-limit/make-limiter    (:rate-limiter config))
    :reaper        (reaper/make-reaper         (:reaper config))
+   :redirects     (redirect/make-redirects    (:redirects config))
+   :resolver      (realm/make-resolver        (:resolver config))
+   :retention     (retention/make-retention   (:retention config))
+   :reviews       (review/make-reviews        (:reviews config))
+   :scheduler     (schedule/make-scheduler    (:scheduler config))
+   :secrets       (secret/make-keeper         (:secrets config))
    :service       (service/make-service       (:service config))
-   :session-store (session/make-session-store (:session-store config))})
+   :session-store (session/make-session-store (:session-store config))
+   :shadow        (shadow/make-shadow         (:shadow config))
+   :shipping      (shipping/make-shipping     (:shipping config))
+   :sites         (site/make-sites            (:sites config))
+   :webhooks      (webhook/make-dispatcher    (:webhooks config))
+   :wishlists     (wishlist/make-wishlists    (:wishlists config))})
 
 (def dependencies
-  {:cluster       [:randomizer]
-   :postgres      [:migrator :randomizer]
-   :rate-limiter  [:postgres]
-   :reaper        [:postgres :session-store]
-   :service       [:bootstrapper
+  {:activities    [:postgres]
+   :analytics     [:clock :postgres :randomizer]
+   :api-keys      [:clock :postgres :randomizer]
+   :blobs         [:postgres]
+   :cluster       [:randomizer]
+   :comments      [:clock :postgres]
+   :discounts     [:clock :postgres :pricing]
+   :events        [:clock]
+   :flags         [:clock :postgres]
+   :leader        [:postgres]
+   :notifications [:clock :postgres]
+   :migrator      [:secrets]
+   :moderator     [:activities :clock :datomic :postgres]
+   :outbox        [:clock :events :postgres :webhooks]
+   :plugins       [:blobs :clock :postgres]
+   :postgres      [:migrator :randomizer :secrets]
+   :pricing       [:postgres]
+   :projector     [:datomic :events :postgres]
+   :rate-limiter  [:clock :postgres]
+   :reaper        [:analytics :datomic :leader :postgres :rate-limiter :retention :session-store :sites :wishlists]
+   :redirects     [:clock :postgres]
+   :resolver      [:datomic]
+   :retention     [:clock :postgres]
+   :reviews       [:clock :postgres]
+   :scheduler     [:clock :datomic :leader :postgres]
+   :service       [:activities
+                   :analytics
+                   :api-keys
+                   :bootstrapper
                    :buster
+                   :clock
+                   :comments
                    :datomic
+                   :discounts
+                   :events
+                   :exports
+                   :flags
                    :keymaster
+                   :log-tail
+                   :moderator
+                   :notifications
+                   :oidc
+                   :plugins
                    :postgres
+                   :pricing
+                   :projector
                    :randomizer
                    :rate-limiter
-                   :session-store]
-   :session-store [:postgres :randomizer]})
+                   :redirects
+                   :resolver
+                   :retention
+                   :reviews
+                   :scheduler
+                   :session-store
+                   :shadow
+                   :shipping
+                   :sites
+                   :webhooks
+                   :wishlists]
+   :session-store [:clock :postgres :randomizer]
+   :shadow        [:flags]
+   :shipping      [:postgres]
+   :sites         [:blobs :postgres]
+   :webhooks      [:clock :keymaster :postgres :randomizer]
+   :wishlists     [:postgres]})
 
 (defn system
   ([]
@@ -144,6 +521,25 @@
     (medley/mapply component/system-map (components config))
     dependencies)))
 
+;;; ----------------------------------------------------------------------------
+;;; Reload
+;;;
+;;; Rate limits can change while the system runs. Everything else is read once
+;;; at start: feature flags already live in Postgres, and translations are
+;;; compiled into the build.
+
+(defn reload!
+  "Re-reads config with the options the system started with and swaps in the
+  new rate limits. Returns the new limits, or an anomaly when they don't
+  check out and the old ones stay. `trigger` says who asked, for the log."
+  [system options trigger]
+  (let [config (read-config options)
+        result (rate-limit/reconfigure! (:rate-limiter system) (:rate-limiter config))]
+    (if (anom/anomaly? result)
+      (log/warn :msg "Config reload rejected?!" :trigger trigger :problems (::s/problems result))
+      (log/info :msg "Config reloaded." :trigger trigger :rate-limiter result))
+    result))
+
 (comment
   (def config (read-config))
   (s/valid? (s/keys) config)
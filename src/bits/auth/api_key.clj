@@ -0,0 +1,203 @@
+(ns bits.auth.api-key
+  (:require
+   [bits.anomaly :as anom]
+   [bits.clock :as clock]
+   [bits.cryptex :as cryptex]
+   [bits.crypto :as crypto]
+   [bits.locale :refer [tru]]
+   [bits.postgres :as postgres]
+   [bits.postgres.api-key :as postgres.api-key]
+   [bits.spec]
+   [buddy.core.bytes :as buddy.bytes]
+   [buddy.core.codecs :as codecs]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [com.stuartsierra.component :as component]
+   [ring.util.response :as response]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Scopes
+
+(def scopes
+  #{:read :write})
+
+;;; ----------------------------------------------------------------------------
+;;; Tokens
+;;;
+;;; Tokens look like `bits_<prefix>_<secret>`. The prefix is stored in the clear
+;;; so a key can be identified (in logs, by leaked-credential scanners) without
+;;; being usable. Only a SHA-256 of the secret is persisted.
+
+(def ^:const ^:private scheme "bits")
+
+(defn- random-hex
+  [randomizer size]
+  (codecs/bytes->hex (crypto/random-bytes randomizer size)))
+
+(defn parse-token
+  [s]
+  (when (string? s)
+    (let [[token-scheme prefix secret] (str/split s #"_" 3)]
+      (when (and (= scheme token-scheme)
+                 (not (str/blank? prefix))
+                 (not (str/blank? secret)))
+        {:prefix prefix
+         :secret secret}))))
+
+(defn bearer-token
+  [request]
+  (some->> (response/get-header request "authorization")
+           (re-matches #"(?i)Bearer\s+(\S+)")
+           second))
+
+(defn- hash-equals?
+  [expected actual]
+  (buddy.bytes/equals? (.getBytes ^String expected "UTF-8")
+                       (.getBytes ^String actual "UTF-8")))
+
+;;; ----------------------------------------------------------------------------
+;;; Issuance
+
+(defn issue!
+  "Returns the token wrapped in a cryptex. The token is never stored and cannot
+  be recovered once this value is discarded. Each key keeps its own rate limit,
+  which defaults to the registry's when it's issued."
+  [registry {:keys [requests-per-minute tenant-id user-id] key-name :name key-scopes :scopes}]
+  {:pre [(every? scopes key-scopes)]}
+  (let [{:keys [postgres randomizer]} registry
+        requests-per-minute           (or requests-per-minute (:requests-per-minute registry))
+        id                            (random-uuid)
+        prefix                        (random-hex randomizer 4)
+        secret                        (random-hex randomizer 24)]
+    (span/with-span! {:name ::issue!}
+      (postgres/execute-one! postgres
+                             {:insert-into :api-keys
+                              :values      [{:id                  id
+                                             :tenant-id           tenant-id
+                                             :user-id             user-id
+                                             :name                key-name
+                                             :prefix              prefix
+                                             :secret-hash         (crypto/sha256 secret)
+                                             :scopes              [:array (mapv name (sort key-scopes)) :text]
+                                             :requests-per-minute requests-per-minute}]})
+      {::id     id
+       ::prefix prefix
+       ::token  (cryptex/cryptex (str scheme "_" prefix "_" secret))})))
+
+(defn revoke!
+  [registry tenant-id user-id id]
+  (span/with-span! {:name ::revoke!}
+    (let [[{:keys [next.jdbc/update-count]}]
+          (postgres/execute! (:postgres registry)
+                             {:update :api-keys
+                              :set    {:revoked-at (clock/now (:clock registry))}
+                              :where  [:and
+                                       [:= :id id]
+                                       [:= :tenant-id tenant-id]
+                                       [:= :user-id user-id]
+                                       [:= :revoked-at nil]]})]
+      (pos? (or update-count 0)))))
+
+(defn list-keys
+  [registry tenant-id user-id]
+  {:post [(s/valid? (s/coll-of ::postgres.api-key/persisted) %)]}
+  (span/with-span! {:name ::list-keys}
+    (postgres/execute! (:postgres registry)
+                       {:select   [:id :tenant-id :user-id :name :prefix :scopes
+                                   :created-at :last-used-at]
+                        :from     [:api-keys]
+                        :where    [:and
+                                   [:= :tenant-id tenant-id]
+                                   [:= :user-id user-id]
+                                   [:= :revoked-at nil]]
+                        :order-by [[:created-at :desc]]})))
+
+;;; ----------------------------------------------------------------------------
+;;; Authentication
+
+(defn- find-active-key
+  [registry prefix]
+  (postgres/execute-one! (:postgres registry)
+                         {:select [:id :tenant-id :user-id :secret-hash :scopes
+                                   :requests-per-minute]
+                          :from   [:api-keys]
+                          :where  [:and
+                                   [:= :prefix prefix]
+                                   [:= :revoked-at nil]]}))
+
+(defn- record-request!
+  "Counts the request against the key's current one-minute window, returning the
+  number of requests made in that window so far."
+  [registry id]
+  (let [now (clock/now (:clock registry))]
+    (-> (postgres/execute-one! (:postgres registry)
+                               {:insert-into   :api-key-requests
+                                :values        [{:api-key-id   id
+                                                 :window-start [:date_trunc [:inline "minute"] now]}]
+                                :on-conflict   [:api-key-id :window-start]
+                                :do-update-set {:requests [:+ :api-key-requests.requests 1]}
+                                :returning     [:requests]})
+        :bits.postgres.api-key-request/requests)))
+
+(defn- touch!
+  [registry id]
+  (postgres/execute-one! (:postgres registry)
+                         {:update :api-keys
+                          :set    {:last-used-at (clock/now (:clock registry))}
+                          :where  [:= :id id]}))
+
+(defn- invalid
+  []
+  (anom/forbidden {::anom/message (tru "Invalid API key.")}))
+
+(defn authenticate
+  "Resolves a bearer token to its key, or returns an anomaly. Keys only
+  authenticate against the tenant they were issued for."
+  [registry tenant-id token]
+  (span/with-span! {:name ::authenticate}
+    (let [{:keys [prefix secret]} (parse-token token)
+          persisted               (when prefix (find-active-key registry prefix))]
+      (if-not (and persisted
+                   (= tenant-id (::postgres.api-key/tenant-id persisted))
+                   (hash-equals? (::postgres.api-key/secret-hash persisted)
+                                 (crypto/sha256 secret)))
+        (invalid)
+        (let [id       (::postgres.api-key/id persisted)
+              limit    (::postgres.api-key/requests-per-minute persisted)
+              requests (record-request! registry id)]
+          (if (< limit requests)
+            (anom/busy {::anom/message             (tru "Too many requests. Please try again later.")
+                        ::anom/retry-after-seconds 60})
+            (do
+              (touch! registry id)
+              {::id        id
+               ::scopes    (into #{} (map keyword) (::postgres.api-key/scopes persisted))
+               ::tenant-id (::postgres.api-key/tenant-id persisted)
+               ::user-id   (::postgres.api-key/user-id persisted)})))))))
+
+(defn scoped?
+  [api-key required]
+  (every? (::scopes api-key) required))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Registry [clock postgres randomizer requests-per-minute]
+  component/Lifecycle
+  (start [this]
+    (span/with-span! {:name ::start-registry}
+      this))
+  (stop [this]
+    (span/with-span! {:name ::stop-registry}
+      this)))
+
+(defmethod print-method Registry
+  [registry ^java.io.Writer w]
+  (.write w (format "#<Registry requests-per-minute=%d>"
+                    (:requests-per-minute registry))))
+
+(defn make-registry
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Registry config))
@@ -1,7 +1,7 @@
 (ns bits.auth.credential)
 
 (def user-by-email-query
-  '[:find (pull ?u [:user/id :user/password-hash]) .
+  '[:find (pull ?u [:user/id :user/password-hash :user/sso-subject]) .
     :in $ ?email
     :where
     [?u :user/email ?email]])
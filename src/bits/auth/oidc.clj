@@ -0,0 +1,235 @@
+(ns bits.auth.oidc
+  (:require
+   [bits.anomaly :as anom]
+   [bits.cache :as cache]
+   [bits.crypto :as crypto]
+   [bits.egress :as egress]
+   [bits.locale :refer [tru]]
+   [bits.spec]
+   [buddy.core.keys :as keys]
+   [buddy.sign.jws :as jws]
+   [buddy.sign.jwt :as jwt]
+   [charred.api :as json]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [com.stuartsierra.component :as component]
+   [datomic.api :as d]
+   [hato.client :as http]
+   [lambdaisland.uri :as uri]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Connections
+;;;
+;;; Tenants opt in to SSO by pointing at their OpenID Connect provider. Users
+;;; signing in through it are managed by the provider: they never get a local
+;;; password. Tenants that allow it have users and memberships provisioned on
+;;; first sign-in; the rest only let in users who are already members.
+
+(def ^:private secret-context
+  "sso/client-secret")
+
+(def ^:private connection-query
+  '[:find (pull ?s [:sso/issuer :sso/client-id :sso/client-secret]) .
+    :in $ ?tenant-id
+    :where
+    [?t :tenant/id ?tenant-id]
+    [?t :tenant/sso ?s]])
+
+(defn connection
+  "The tenant's SSO connection with its client secret unsealed, or nil."
+  [keymaster db tenant-id]
+  (some-> (d/q connection-query db tenant-id)
+          (update :sso/client-secret #(crypto/unseal keymaster secret-context %))))
+
+(defn connection-tx
+  [keymaster tenant-id {:keys [client-id client-secret issuer provision?]}]
+  [{:tenant/id  tenant-id
+    :tenant/sso {:db/ensure         :sso/ensure
+                 :sso/issuer        issuer
+                 :sso/client-id     client-id
+                 :sso/client-secret (crypto/seal keymaster secret-context client-secret)
+                 :sso/provision?    (boolean provision?)}}])
+
+(defn sso-managed?
+  [user]
+  (some? (:user/sso-subject user)))
+
+;;; ----------------------------------------------------------------------------
+;;; Discovery
+;;;
+;;; Provider metadata and signing keys are cached so a sign-in costs one round
+;;; trip to the provider: the code exchange. Tenants choose the issuer, so every
+;;; request to the provider must go to a public https address.
+
+(defn- get-json
+  [relying-party url]
+  (let [response (http/get (egress/check! url) {:http-client       (:http-client relying-party)
+                                                :throw-exceptions? false})]
+    (when-not (= 200 (:status response))
+      (throw (ex-info "Identity provider request failed?!" {:status (:status response) :url url})))
+    (json/read-json (:body response) :key-fn keyword)))
+
+(defn- cached
+  [relying-party k f]
+  (cache/lookup (:cache relying-party) k f))
+
+(defn discover
+  [relying-party issuer]
+  (span/with-span! {:name ::discover}
+    (cached relying-party [::discovery issuer]
+            #(get-json relying-party (str (str/replace issuer #"/+$" "") "/.well-known/openid-configuration")))))
+
+(defn- signing-keys
+  [relying-party jwks-uri]
+  (cached relying-party [::jwks jwks-uri] #(:keys (get-json relying-party jwks-uri))))
+
+(defn- signing-key
+  "The provider's key with this ID. Providers publish new keys before signing
+  with them, so an unknown ID refetches the keys once rather than waiting for
+  the cache to expire."
+  [relying-party jwks-uri kid]
+  (let [find-key #(some (fn [jwk] (when (= kid (:kid jwk)) jwk)) %)]
+    (or (find-key (signing-keys relying-party jwks-uri))
+        (do (cache/evict! (:cache relying-party) [::jwks jwks-uri])
+            (find-key (signing-keys relying-party jwks-uri))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Authorization code flow
+
+(defn authorization-url
+  [relying-party {:sso/keys [client-id issuer]} {:keys [nonce redirect-uri state]}]
+  (let [{:keys [authorization_endpoint]} (discover relying-party issuer)]
+    (str (assoc (uri/uri authorization_endpoint)
+                :query (uri/map->query-string {:client_id     client-id
+                                               :nonce         nonce
+                                               :redirect_uri  redirect-uri
+                                               :response_type "code"
+                                               :scope         "openid email"
+                                               :state         state})))))
+
+(def ^:private allowed-algs
+  #{:es256 :rs256})
+
+(defn verify-id-token
+  "Returns the token's claims when it was signed by the provider for this
+  client and carries the nonce we sent. Throws otherwise."
+  [relying-party {:sso/keys [client-id issuer]} id-token nonce]
+  (span/with-span! {:name ::verify-id-token}
+    (let [metadata          (discover relying-party issuer)
+          {:keys [alg kid]} (jws/decode-header id-token)
+          jwk               (signing-key relying-party (:jwks_uri metadata) kid)]
+      (when-not (and jwk (contains? allowed-algs alg))
+        (throw (ex-info "No usable signing key for ID token?!" {:alg alg :kid kid})))
+      (let [claims (jwt/unsign id-token
+                               (keys/jwk->public-key jwk)
+                               {:alg alg :aud client-id :iss (:issuer metadata)})]
+        (when-not (= nonce (:nonce claims))
+          (throw (ex-info "ID token nonce mismatch?!" {})))
+        claims))))
+
+(defn exchange-code!
+  "Trades an authorization code for verified ID token claims."
+  [relying-party connection {:keys [code nonce redirect-uri]}]
+  (span/with-span! {:name ::exchange-code!}
+    (let [{:sso/keys [client-id client-secret issuer]} connection
+          {:keys [token_endpoint]}                     (discover relying-party issuer)
+          response                                     (http/post (egress/check! token_endpoint)
+                                                                  {:http-client       (:http-client relying-party)
+                                                                   :basic-auth        {:user client-id :pass client-secret}
+                                                                   :form-params       {:code         code
+                                                                                       :grant_type   "authorization_code"
+                                                                                       :redirect_uri redirect-uri}
+                                                                   :throw-exceptions? false})]
+      (when-not (= 200 (:status response))
+        (throw (ex-info "Code exchange failed?!" {:status (:status response)})))
+      (verify-id-token relying-party
+                       connection
+                       (:id_token (json/read-json (:body response) :key-fn keyword))
+                       nonce))))
+
+;;; ----------------------------------------------------------------------------
+;;; Provisioning
+
+(defn subject
+  [claims]
+  (str (:iss claims) "|" (:sub claims)))
+
+(defn- provisions?
+  [db tenant-id]
+  (true? (get-in (d/entity db [:tenant/id tenant-id]) [:tenant/sso :sso/provision?])))
+
+(defn- member?
+  [db user tenant-id]
+  (some? (d/q '[:find ?m .
+                :in $ ?u ?tenant-id
+                :where
+                [?t :tenant/id ?tenant-id]
+                [?m :membership/tenant ?t]
+                [?m :membership/user ?u]]
+              db
+              user
+              tenant-id)))
+
+(defn- membership-tx
+  [user tenant-id]
+  {:db/ensure         :membership/ensure
+   :membership/id     (random-uuid)
+   :membership/user   user
+   :membership/tenant [:tenant/id tenant-id]
+   :membership/role   :membership.role/member})
+
+(defn provision-tx
+  "Transaction letting the user into the tenant, empty when they're already a
+  member, or an anomaly. Users and memberships are only created when the
+  tenant's connection provisions them, so signing in to one tenant never lets
+  the same identity into another. Emails are unique across tenants, so an
+  identity provider can't claim an account it doesn't already manage."
+  [db tenant-id claims now]
+  (let [user (d/entity db [:user/sso-subject (subject claims)])]
+    (cond
+      (and user (member? db (:db/id user) tenant-id))
+      []
+
+      (not (provisions? db tenant-id))
+      (anom/forbidden {::anom/message (tru "You aren''t a member here. Ask an admin to add you.")})
+
+      user
+      [(membership-tx (:db/id user) tenant-id)]
+
+      (not (and (string? (:email claims)) (true? (:email_verified claims))))
+      (anom/forbidden {::anom/message (tru "Your identity provider didn''t share a verified email.")})
+
+      (d/entity db [:user/email (:email claims)])
+      (anom/conflict {::anom/message (tru "An account with this email already exists. Sign in with your password.")})
+
+      :else
+      [{:db/id            "user"
+        :db/ensure        :user/ensure
+        :user/id          (random-uuid)
+        :user/email       (:email claims)
+        :user/created-at  now
+        :user/sso-subject (subject claims)}
+       (membership-tx "user" tenant-id)])))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord RelyingParty [cache connect-timeout-ms http-client]
+  component/Lifecycle
+  (start [this]
+    (assoc this
+           :cache       (cache/make-cache this)
+           :http-client (http/build-http-client {:connect-timeout connect-timeout-ms
+                                                 :redirect-policy :never})))
+  (stop [this]
+    (assoc this :cache nil :http-client nil)))
+
+(defmethod print-method RelyingParty
+  [_ ^java.io.Writer w]
+  (.write w "#<RelyingParty>"))
+
+(defn make-relying-party
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->RelyingParty config))
@@ -1,13 +1,13 @@
 (ns bits.auth.rate-limit
   (:require
    [bits.anomaly :as anom]
+   [bits.clock :as clock]
    [bits.crypto :as crypto]
    [bits.locale :refer [tru]]
    [bits.postgres :as postgres]
    [bits.spec]
    [clojure.spec.alpha :as s]
    [com.stuartsierra.component :as component]
-   [java-time.api :as time]
    [steffan-westcott.clj-otel.api.metrics.instrument :as instrument]
    [steffan-westcott.clj-otel.api.trace.span :as span]))
 
@@ -31,20 +31,44 @@
                                              :ip-hash   (crypto/sha256 ip-address)
                                              :success   (boolean success)}]}))))
 
+;;; ----------------------------------------------------------------------------
+;;; Limits
+;;;
+;;; Limits start out as configured and can be swapped while running, so
+;;; tightening them during an attack doesn't need a restart.
+
+(def ^:private limit-keys
+  [:email-max-attempts :email-window-minutes :ip-max-attempts :ip-window-minutes])
+
+(defn limits
+  [limiter]
+  (or (some-> (:limits limiter) deref)
+      (select-keys limiter limit-keys)))
+
+(defn reconfigure!
+  "Swaps in new limits, or returns an anomaly and keeps the old ones when
+  they don't check out."
+  [limiter config]
+  (let [new-limits (select-keys config limit-keys)]
+    (if-let [explanation (s/explain-data ::config new-limits)]
+      (anom/incorrect {::anom/message "Invalid rate limits."
+                       ::s/problems   (::s/problems explanation)})
+      (reset! (:limits limiter) new-limits))))
+
 ;;; ----------------------------------------------------------------------------
 ;;; Checking
 
 (defn- failure-counts
-  [limiter source]
+  [limiter limits source]
   (let [{:keys [email-window-minutes
-                ip-window-minutes
-                postgres]}      limiter
+                ip-window-minutes]} limits
         {:keys [tenant-id
                 email
-                ip-hash]}       source
-        window-minutes          (max email-window-minutes ip-window-minutes)
-        now                     (time/offset-date-time)
-        cutoff                  [:- now [:make-interval :mins window-minutes]]]
+                ip-hash]}           source
+        postgres                    (:postgres limiter)
+        window-minutes              (max email-window-minutes ip-window-minutes)
+        now                         (clock/now (:clock limiter))
+        cutoff                      [:- now [:make-interval :mins window-minutes]]]
     (postgres/execute-one!
      postgres
      {:select [[[:sum [:case [:= :email email] [:inline 1] :else [:inline 0]]] :email-failures]
@@ -63,32 +87,35 @@
                     :attributes {"tenant_id" (str tenant-id)
                                  "reason"    (name reason)}}))
 
+(defn evaluate
+  "Which limit the source is over under limits, `::email` or `::ip`, or nil.
+  Records nothing, so limits that aren't enforced can be tried out."
+  [limiter limits tenant-id params]
+  (let [{:keys [email-max-attempts
+                ip-max-attempts]}  limits
+        {:keys [email ip-address]} params
+        source                     {:tenant-id tenant-id
+                                    :email     email
+                                    :ip-hash   (crypto/sha256 ip-address)}
+        {:keys [email-failures
+                ip-failures]}      (failure-counts limiter limits source)]
+    (cond
+      (<= email-max-attempts (or email-failures 0)) ::email
+      (<= ip-max-attempts (or ip-failures 0))       ::ip)))
+
+(def ^:private reason-windows
+  {::email :email-window-minutes
+   ::ip    :ip-window-minutes})
+
 (defn check
   [limiter tenant-id params]
-  (let [{:keys [email-max-attempts
-                email-window-minutes
-                ip-max-attempts
-                ip-window-minutes]} limiter
-        {:keys [email ip-address]}  params
-        source                      {:tenant-id tenant-id
-                                     :email     email
-                                     :ip-hash   (crypto/sha256 ip-address)}]
-    (span/with-span! {:name ::check}
-      (let [{:keys [email-failures ip-failures]} (failure-counts limiter source)]
-        (cond
-          (<= email-max-attempts (or email-failures 0))
-          (do
-            (record-rate-limit! limiter tenant-id ::email)
-            (anom/busy {::anom/message        (tru "Too many attempts. Please try again later.")
-                        ::reason              ::email
-                        ::retry-after-seconds (* email-window-minutes 60)}))
-
-          (<= ip-max-attempts (or ip-failures 0))
-          (do
-            (record-rate-limit! limiter tenant-id ::ip)
-            (anom/busy {::anom/message        (tru "Too many attempts. Please try again later.")
-                        ::reason              ::ip
-                        ::retry-after-seconds (* ip-window-minutes 60)})))))))
+  (span/with-span! {:name ::check}
+    (let [active (limits limiter)]
+      (when-let [reason (evaluate limiter active tenant-id params)]
+        (record-rate-limit! limiter tenant-id reason)
+        (anom/busy {::anom/message             (tru "Too many attempts. Please try again later.")
+                    ::reason                   reason
+                    ::anom/retry-after-seconds (* (get active (reason-windows reason)) 60)})))))
 
 ;;; ----------------------------------------------------------------------------
 ;;; Cleanup
@@ -97,7 +124,7 @@
   [limiter]
   (let [{:keys [postgres]} limiter]
     (span/with-span! {:name ::delete-old-attempts!}
-      (let [now (time/offset-date-time)
+      (let [now (clock/now (:clock limiter))
             [{:keys [next.jdbc/update-count]}]
             (postgres/execute! postgres
                                {:delete-from :authentication-attempts
@@ -108,10 +135,12 @@
 ;;; ----------------------------------------------------------------------------
 ;;; Component
 
-(defrecord Limiter [email-max-attempts
+(defrecord Limiter [clock
+                    email-max-attempts
                     email-window-minutes
                     ip-max-attempts
                     ip-window-minutes
+                    limits
                     postgres
                     ;; Instruments
                     attempt-counter
@@ -119,6 +148,8 @@
   component/Lifecycle
   (start [this]
     (assoc this
+           :limits
+           (atom (select-keys this limit-keys))
            :attempt-counter
            (instrument/instrument {:name            "auth.login.attempt"
                                    :instrument-type :counter
@@ -132,6 +163,7 @@
   (stop [this]
     (assoc this
            :attempt-counter    nil
+           :limits             nil
            :rate-limit-counter nil)))
 
 (defn make-limiter
@@ -0,0 +1,67 @@
+(ns bits.auth.role
+  (:require
+   [datomic.api :as d]))
+
+(def admin-roles
+  #{:membership.role/admin :membership.role/owner})
+
+(defn tenant-admin?
+  "True when the user administers the tenant."
+  [db user-id tenant-id]
+  (and (some? user-id)
+       (some? (d/q '[:find ?m .
+                     :in $ ?user-id ?tenant-id [?role ...]
+                     :where
+                     [?u :user/id ?user-id]
+                     [?t :tenant/id ?tenant-id]
+                     [?m :membership/user ?u]
+                     [?m :membership/tenant ?t]
+                     [?m :membership/role ?role]
+                     (not [?m :entity/deleted-at])]
+                   db
+                   user-id
+                   tenant-id
+                   admin-roles))))
+
+(defn tenant-admin-ids
+  "The IDs of the tenant's admins."
+  [db tenant-id]
+  (set (d/q '[:find [?user-id ...]
+              :in $ ?tenant-id [?role ...]
+              :where
+              [?t :tenant/id ?tenant-id]
+              [?m :membership/tenant ?t]
+              [?m :membership/role ?role]
+              [?m :membership/user ?u]
+              [?u :user/id ?user-id]
+              (not [?m :entity/deleted-at])]
+            db
+            tenant-id
+            admin-roles)))
+
+(defn request-tenant-admin?
+  "True when the request's user administers the realm's tenant."
+  [request]
+  (tenant-admin? (:bits.middleware/db request)
+                 (get-in request [:session/user :user/id])
+                 (get-in request [:session/realm :tenant/id])))
+
+(defn platform-admin?
+  "True when the user runs the platform, which lets them moderate tenants,
+  change flags and read logs and stats across every tenant."
+  [db user-id]
+  (and (some? user-id)
+       (true? (d/q '[:find ?admin .
+                     :in $ ?user-id
+                     :where
+                     [?u :user/id ?user-id]
+                     [?u :user/platform-admin? ?admin]]
+                   db
+                   user-id))))
+
+(defn request-platform-admin?
+  "True when the request is signed in to the platform realm as a platform
+  admin."
+  [request]
+  (and (= :realm.type/platform (get-in request [:session/realm :realm/type]))
+       (platform-admin? (:bits.middleware/db request) (get-in request [:session/user :user/id]))))
@@ -0,0 +1,57 @@
+(ns bits.blob
+  "Content-addressed storage for file contents.
+
+  Blobs are named by the hex SHA-256 of their bytes, so storing the same bytes
+  twice keeps one copy and a digest always names the same contents. Nothing
+  deletes a blob on its own: whatever refers to blobs removes the ones it no
+  longer needs, see `bits.site/purge-blobs!`."
+  (:require
+   [bits.crypto :as crypto]
+   [bits.postgres :as postgres]
+   [bits.postgres.site]
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+(defprotocol BlobStore
+  (put! [store bs]
+    "Stores the byte array, returning its digest.")
+  (fetch [store digest]
+    "The bytes stored under digest, or nil."))
+
+(defn digest
+  [^bytes bs]
+  (crypto/sha256 bs))
+
+;;; ----------------------------------------------------------------------------
+;;; Postgres
+;;;
+;;; Blobs live in a table beside everything else, which keeps backups whole and
+;;; lets a blob be written in the same transaction as whatever refers to it.
+
+(defrecord PostgresBlobStore [postgres]
+  BlobStore
+  (put! [_ bs]
+    (span/with-span! {:name ::put!}
+      (let [d (digest bs)]
+        (postgres/execute! postgres {:insert-into :blobs
+                                     :values      [{:digest d :bytes bs :size (alength ^bytes bs)}]
+                                     :on-conflict [:digest]
+                                     :do-nothing  true})
+        d)))
+  (fetch [_ d]
+    (span/with-span! {:name ::fetch}
+      (:bits.postgres.blob/bytes
+       (postgres/execute-one! (postgres/replica postgres)
+                              {:select [:bytes]
+                               :from   [:blobs]
+                               :where  [:= :digest d]})))))
+
+(defmethod print-method PostgresBlobStore
+  [_ ^java.io.Writer w]
+  (.write w "#<PostgresBlobStore>"))
+
+(defn make-blob-store
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->PostgresBlobStore config))
@@ -0,0 +1,50 @@
+(ns bits.cache
+  "Small in-process caches with a size bound and a time to live.
+
+  Every instance keeps its own, so callers either invalidate on the writes
+  they see or accept being stale for up to the TTL. Loading goes through
+  Guava, which runs one load per key at a time: a burst of misses on a cold
+  key waits for a single query rather than stampeding the database."
+  (:require
+   [bits.spec]
+   [clojure.spec.alpha :as s])
+  (:import
+   (com.google.common.cache Cache CacheBuilder)
+   (com.google.common.util.concurrent UncheckedExecutionException)
+   (java.util.concurrent Callable ExecutionException TimeUnit)))
+
+(defn make-cache
+  ^Cache [{:keys [maximum-size ttl-seconds] :as config}]
+  {:pre [(s/valid? ::config config)]}
+  (-> (CacheBuilder/newBuilder)
+      (.maximumSize maximum-size)
+      (.expireAfterWrite ttl-seconds TimeUnit/SECONDS)
+      (.build)))
+
+(defn fetch
+  "The cached value for k, calling `(load)` to fill it on a miss. Nil is
+  cached too, as ::nil, so unknown keys stay cheap. Returns `[value hit?]`."
+  [^Cache cache k load]
+  (let [loaded? (volatile! false)
+        v       (try
+                  (.get cache k ^Callable (fn []
+                                            (vreset! loaded? true)
+                                            (if-some [v (load)] v ::nil)))
+                  (catch ExecutionException ex
+                    (throw (or (ex-cause ex) ex)))
+                  (catch UncheckedExecutionException ex
+                    (throw (or (ex-cause ex) ex))))]
+    [(when-not (= ::nil v) v) (not @loaded?)]))
+
+(defn lookup
+  "The cached value for k, like `fetch` without saying whether it hit."
+  [cache k load]
+  (first (fetch cache k load)))
+
+(defn evict!
+  [^Cache cache k]
+  (.invalidate cache k))
+
+(defn clear!
+  [^Cache cache]
+  (.invalidateAll cache))
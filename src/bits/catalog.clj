@@ -0,0 +1,206 @@
+(ns bits.catalog
+  "Bulk product imports.
+
+  A catalog is read from CSV, either our own columns or a Shopify product
+  export, and checked line by line before anything is written. Imports are
+  all or nothing: one bad line rejects the whole file, and a clean file lands
+  in a single transaction."
+  (:require
+   [bits.anomaly :as anom]
+   [bits.entity :as entity]
+   [bits.locale :refer [tru]]
+   [charred.api :as charred]
+   [clojure.string :as str]
+   [datomic.api :as d]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.util Currency)))
+
+;;; ----------------------------------------------------------------------------
+;;; CSV
+
+(defn parse-csv
+  "Reads RFC 4180 CSV into a vector of rows, without the byte order mark
+  spreadsheets put at the start."
+  [s]
+  (charred/read-csv (str/replace s #"^\uFEFF" "")))
+
+;;; ----------------------------------------------------------------------------
+;;; Formats
+;;;
+;;; Both formats become one row per variant. Rows sharing a handle are variants
+;;; of the same product, and the product's fields come from its first row.
+
+(def ^:private shopify-columns
+  {"Body (HTML)"               :description
+   "Handle"                    :handle
+   "Option1 Value"             :variant-name
+   "Status"                    :status
+   "Title"                     :title
+   "Variant Price"             :price
+   "Variant Requires Shipping" :requires-shipping
+   "Variant SKU"               :sku})
+
+(defn shopify?
+  [header]
+  (every? (set header) ["Handle" "Variant Price"]))
+
+(defn- shopify-row
+  [row]
+  (cond-> (dissoc row :requires-shipping)
+    (= "Default Title" (:variant-name row)) (assoc :variant-name nil)
+    (= "true" (some-> (:requires-shipping row) str/lower-case)) (assoc :type "physical")))
+
+(defn rows
+  "Returns each data row as a map with its line number. Our own format uses the
+  column names of the products export, plus variant, sku, price, currency and
+  type."
+  [[header & data]]
+  (let [shopify? (shopify? header)
+        columns  (mapv (if shopify?
+                         shopify-columns
+                         (comp keyword str/trim str/lower-case))
+                       header)]
+    (for [[i cells] (map-indexed vector data)
+          :when     (not (every? str/blank? cells))
+          :let      [row (into {:line (+ 2 i)}
+                               (keep (fn [[column cell]]
+                                       (when (and column (not (str/blank? cell)))
+                                         [column (str/trim cell)])))
+                               (map vector columns cells))]
+          ;; Shopify lists extra product images on rows of their own.
+          :when     (not (and shopify? (not-any? row [:price :sku :title])))]
+      (cond-> row
+        shopify?             (shopify-row)
+        (:variant row)       (-> (dissoc :variant) (assoc :variant-name (:variant row)))
+        (nil? (:handle row)) (assoc :handle (str "line-" (:line row)))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Validation
+
+(def ^:private statuses
+  #{"active" "archived" "draft"})
+
+(def ^:private types
+  #{"digital" "physical"})
+
+(def ^:private currencies
+  #{"EUR" "GBP" "USD"})
+
+(def max-rows
+  5000)
+
+(defn amount
+  "Converts a price like `9.99` into minor units, or nil when it isn't a
+  positive price in the currency."
+  [price currency]
+  (when (and price (re-matches #"\d+(?:\.\d+)?" price))
+    (let [digits            (.getDefaultFractionDigits (Currency/getInstance ^String currency))
+          ^BigDecimal price (bigdec price)]
+      (when (and (<= (.scale price) digits) (pos? price))
+        (.longValueExact (.movePointRight price digits))))))
+
+(defn- row-errors
+  [db skus first? {:keys [currency price sku status title type]}]
+  (cond-> []
+    (and first? (not (entity/present? title)))
+    (conj (tru "Title is missing."))
+
+    (and first? status (not (contains? statuses (str/lower-case status))))
+    (conj (tru "Status must be active, draft or archived."))
+
+    (not (entity/present? sku))
+    (conj (tru "SKU is missing."))
+
+    (< 1 (get skus sku 0))
+    (conj (tru "SKU {0} appears more than once." sku))
+
+    (and sku (d/entity db [:sku/code sku]))
+    (conj (tru "SKU {0} already exists." sku))
+
+    (not (contains? currencies currency))
+    (conj (tru "Currency must be GBP, EUR or USD."))
+
+    (and (contains? currencies currency) (nil? (amount price currency)))
+    (conj (tru "Price must be a positive amount, like 9.99."))
+
+    (and type (not (contains? types (str/lower-case type))))
+    (conj (tru "Type must be digital or physical."))))
+
+(defn check
+  "Returns the products a catalog would create and every problem found, by
+  line. Nothing is written."
+  [db text {:keys [currency]}]
+  (span/with-span! {:name ::check}
+    (let [data (rows (parse-csv text))]
+      (if (< max-rows (count data))
+        {:errors   [{:line    (+ 2 max-rows)
+                     :message (tru "Catalogs are limited to {0} rows." max-rows)}]
+         :products []}
+        (let [data     (map #(update % :currency (fnil str/upper-case currency)) data)
+              skus     (frequencies (keep :sku data))
+              products (->> (partition-by :handle data)
+                            (map (fn [[product :as variants]]
+                                   (assoc product :variants variants))))]
+          {:errors   (vec (for [{:keys [variants]} products
+                                [i row]            (map-indexed vector variants)
+                                message            (row-errors db skus (zero? i) row)]
+                            {:line (:line row) :message message}))
+           :products (vec products)})))))
+
+;;; ----------------------------------------------------------------------------
+;;; Transactions
+
+(defn- variant-tx
+  [now {:keys [currency price sku type variant-name]}]
+  {:db/ensure          :variant/ensure
+   :variant/id         (random-uuid)
+   :variant/name       (or variant-name "Default")
+   :variant/type       (keyword "variant.type" (str/lower-case (or type "digital")))
+   :variant/active?    true
+   :variant/created-at now
+   :variant/sku        {:sku/code sku}
+   :variant/price      {:money/amount   (amount price currency)
+                        :money/currency (keyword "currency" currency)}})
+
+(defn- next-position
+  [db tenant-id]
+  (inc (or (d/q '[:find (max ?position) .
+                  :in $ ?tenant-id
+                  :where
+                  [?t :tenant/id ?tenant-id]
+                  [?t :tenant/products ?p]
+                  [?p :product/position ?position]]
+                db tenant-id)
+           0)))
+
+(defn import-tx
+  [db tenant-id products now]
+  (let [start (next-position db tenant-id)]
+    [{:tenant/id       tenant-id
+      :tenant/products (vec (map-indexed
+                             (fn [i {:keys [description status title variants]}]
+                               (cond-> {:db/ensure          :product/ensure
+                                        :product/id         (random-uuid)
+                                        :product/title      title
+                                        :product/status     (keyword "product.status" (str/lower-case (or status "active")))
+                                        :product/position   (+ start i)
+                                        :product/created-at now
+                                        :product/variants   (mapv #(variant-tx now %) variants)}
+                                 description (assoc :product/description description)))
+                             products))}]))
+
+(defn import!
+  "Checks the catalog and, when it's clean, creates every product in one
+  transaction. Returns counts, or an anomaly carrying the `::errors`."
+  [conn tenant-id text opts now]
+  (span/with-span! {:name ::import!}
+    (let [db                        (d/db conn)
+          {:keys [errors products]} (check db text opts)]
+      (if (seq errors)
+        (anom/incorrect {::anom/message (tru "The catalog has problems. Nothing was imported.")
+                         ::errors       errors})
+        (do
+          @(d/transact conn (import-tx db tenant-id products now))
+          {:products (count products)
+           :variants (reduce + (map (comp count :variants) products))})))))
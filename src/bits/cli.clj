@@ -2,12 +2,18 @@
   (:require
    [babashka.cli :as cli]
    [bits.app :as app]
+   [bits.cli.bench :as cli.bench]
+   [bits.cli.config :as cli.config]
+   [bits.cli.keys :as cli.keys]
+   [bits.cli.projections :as cli.projections]
+   [bits.cli.routes :as cli.routes]
    [bits.cli.seed :as cli.seed]
    [bits.cli.serve :as cli.serve]
    [bits.cli.warmup :as cli.warmup]
    [bits.data :refer [keyset]]
    [clansi.core :as ansi]
    [clojure.spec.alpha :as s]
+   [clojure.string :as str]
    [com.stuartsierra.component :as component])
   (:gen-class))
 
@@ -48,9 +54,14 @@
 ;;; Commands
 
 (def ^:private commands
-  {"seed"   cli.seed/command
-   "serve"  cli.serve/command
-   "warmup" cli.warmup/command})
+  {"bench"                cli.bench/command
+   "config validate"      cli.config/command
+   "keys rotate"          cli.keys/command
+   "projections backfill" cli.projections/command
+   "routes"               cli.routes/command
+   "seed"                 cli.seed/command
+   "serve"                cli.serve/command
+   "warmup"               cli.warmup/command})
 
 ;;; ----------------------------------------------------------------------------
 ;;; UI
@@ -75,7 +86,7 @@
 
 (defn- command-help
   [{:keys [cmds desc spec]}]
-  (let [cmd-name (first cmds)
+  (let [cmd-name (str/join " " cmds)
         usage    (str (header "Usage:") " bits " cmd-name
                       (when (seq spec) " [options]"))]
     (if (seq spec)
@@ -118,7 +129,7 @@
                            ((:fn cli.serve/command) nil ctx)))}]
         (map (fn [[string command]]
                (-> command
-                   (assoc :cmds [string])
+                   (assoc :cmds (str/split string #" "))
                    prepare-command)))
         string->command))
 
@@ -0,0 +1,198 @@
+(ns bits.cli.bench
+  (:require
+   [babashka.cli :as cli]
+   [clojure.string :as str]
+   [hato.client :as http])
+  (:import
+   (java.net CookieManager)
+   (java.util.concurrent ConcurrentLinkedQueue Executors ExecutorService TimeUnit)))
+
+;;; ----------------------------------------------------------------------------
+;;; Scenarios
+;;;
+;;; Each request to "/" resolves the realm from the host and loads or creates a
+;;; session. A fresh visitor carries no cookie, so every request writes a new
+;;; session. A returning visitor keeps its cookie, so requests only read one.
+;;; A signed-in visitor logs in once and then requests a page that loads the
+;;; user, which only runs when an email and password are given.
+
+(def ^:private scenarios
+  [{:name "fresh" :cookies? false}
+   {:name "returning" :cookies? true}
+   {:name "signed-in" :cookies? true :sign-in? true}])
+
+;;; ----------------------------------------------------------------------------
+;;; Statistics
+
+(defn percentile
+  "Nearest-rank percentile of a sorted vector."
+  [sorted p]
+  (when (seq sorted)
+    (let [rank (int (Math/ceil (* (/ p 100.0) (count sorted))))]
+      (nth sorted (max 0 (dec rank))))))
+
+(defn summarize
+  "Samples are `[elapsed-ms status]` pairs, where status is nil for requests
+  that never got a response."
+  [samples]
+  (let [sorted (vec (sort (map first samples)))
+        errors (count (remove (fn [[_ status]] (and status (< status 500))) samples))]
+    {:count  (count samples)
+     :errors errors
+     :p50    (percentile sorted 50)
+     :p95    (percentile sorted 95)
+     :p99    (percentile sorted 99)
+     :max    (peek sorted)}))
+
+(defn violations
+  [summary {:keys [max-error-rate p95-ms p99-ms]}]
+  (let [{:keys [count errors p95 p99]} summary
+        error-rate                     (if (pos? count) (/ errors (double count)) 0.0)]
+    (cond-> []
+      (and p95 (> p95 p95-ms))
+      (conj (format "p95 %.1fms exceeds %dms" p95 p95-ms))
+
+      (and p99 (> p99 p99-ms))
+      (conj (format "p99 %.1fms exceeds %dms" p99 p99-ms))
+
+      (> error-rate max-error-rate)
+      (conj (format "error rate %.3f exceeds %.3f" error-rate max-error-rate)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Load
+;;;
+;;; Real traffic is skewed towards a few popular creators, so the first URL
+;;; receives the most requests and each following one proportionally fewer.
+
+(defn- zipf-picker
+  [urls]
+  (let [weights (mapv #(/ 1.0 (inc %)) (range (count urls)))
+        total   (reduce + weights)
+        cumsum  (vec (reductions + weights))]
+    (fn []
+      (let [r (* (rand) total)]
+        (nth urls (min (dec (count urls))
+                       (count (take-while #(< % r) cumsum))))))))
+
+(defn- timed-get
+  [client url]
+  (let [start  (System/nanoTime)
+        status (try
+                 (:status (http/get url {:http-client       client
+                                         :throw-exceptions? false
+                                         :timeout           10000}))
+                 (catch Exception _
+                   nil))]
+    [(/ (- (System/nanoTime) start) 1e6) status]))
+
+(defn- page-url
+  [url path]
+  (str (str/replace url #"/+$" "") path))
+
+(defn- sign-in!
+  "Posts the login form the way a browser would, reading the CSRF token from
+  the form so the cookie jar holds the signed-in session afterwards."
+  [client url {:keys [email password]}]
+  (let [login (http/get (page-url url "/login") {:http-client client
+                                                 :timeout     10000})
+        csrf  (second (re-find #"name=\"csrf\" value=\"([^\"]*)\"" (:body login)))
+        resp  (http/post (page-url url "/action")
+                         {:http-client       client
+                          :throw-exceptions? false
+                          :timeout           10000
+                          :form-params       {"action"     "auth/login"
+                                              "csrf"       csrf
+                                              "_submitted" "true"
+                                              "email"      email
+                                              "password"   password}})]
+    (when-not (get-in resp [:headers "location"])
+      (throw (ex-info "Sign in failed" {:url url :email email :status (:status resp)})))))
+
+(defn- run-scenario
+  [{:keys [cookies? sign-in?]} {:keys [concurrency path requests urls] :as opts}]
+  (let [pick     (if sign-in?
+                   (comp #(page-url % path) (zipf-picker urls))
+                   (zipf-picker urls))
+        samples  (ConcurrentLinkedQueue.)
+        per      (long (Math/ceil (/ requests (double concurrency))))
+        executor ^ExecutorService (Executors/newFixedThreadPool concurrency)]
+    ;; Workers swallow their exceptions, so fail here on bad credentials.
+    (when sign-in?
+      (run! #(sign-in! (http/build-http-client {:redirect-policy :never
+                                                :cookie-handler  (CookieManager.)})
+                       % opts)
+            urls))
+    (dotimes [_ concurrency]
+      (.submit executor
+               ^Runnable
+               (fn []
+                 (let [client (http/build-http-client
+                               (cond-> {:redirect-policy :never}
+                                 cookies? (assoc :cookie-handler (CookieManager.))))]
+                   (when sign-in?
+                     (run! #(sign-in! client % opts) urls))
+                   (dotimes [_ per]
+                     (.add samples (timed-get client (pick))))))))
+    (.shutdown executor)
+    (.awaitTermination executor 1 TimeUnit/HOURS)
+    (summarize (vec samples))))
+
+;;; ----------------------------------------------------------------------------
+;;; Command
+
+(def spec
+  {:urls           {:desc    "Comma-separated base URLs, most popular first"
+                    :default "http://localhost:3000/"}
+   :email          {:desc "Sign in as this user for the signed-in scenario"}
+   :password       {:desc "Password for --email"}
+   :path           {:desc    "Page the signed-in scenario requests"
+                    :default "/notifications"}
+   :requests       {:desc    "Requests per scenario"
+                    :coerce  :long
+                    :default 2000}
+   :concurrency    {:desc    "Concurrent clients"
+                    :coerce  :long
+                    :default 16}
+   :p95-ms         {:desc    "Fail when p95 latency exceeds this"
+                    :coerce  :long
+                    :default 50}
+   :p99-ms         {:desc    "Fail when p99 latency exceeds this"
+                    :coerce  :long
+                    :default 150}
+   :max-error-rate {:desc    "Fail when the share of failed requests exceeds this"
+                    :coerce  :double
+                    :default 0.001}})
+
+(defn- format-ms
+  [ms]
+  (if ms (format "%.1f" ms) "-"))
+
+(defn run
+  [_component {:keys [opts]}]
+  (let [opts    (update opts :urls #(vec (remove str/blank? (str/split % #","))))
+        results (mapv (fn [scenario]
+                        [scenario (run-scenario scenario opts)])
+                      (filter #(or (not (:sign-in? %)) (:email opts)) scenarios))
+        failed  (into []
+                      (mapcat (fn [[scenario summary]]
+                                (map #(str (:name scenario) ": " %)
+                                     (violations summary opts))))
+                      results)]
+    (println
+     (cli/format-table
+      {:rows   (into [["scenario" "requests" "errors" "p50" "p95" "p99" "max"]]
+                     (map (fn [[{:keys [name]} {:keys [count errors p50 p95 p99 max]}]]
+                            [name count errors
+                             (format-ms p50) (format-ms p95) (format-ms p99) (format-ms max)]))
+                     results)
+       :indent 2}))
+    (if (seq failed)
+      (do (binding [*out* *err*]
+            (run! println failed))
+          {:bits.cli.exit/code :bits.cli.exit/software-error})
+      {:bits.cli.exit/code :bits.cli.exit/ok})))
+
+(def command
+  {:desc "Measure realm, session and signed-in latency against a running server"
+   :fn   run
+   :spec spec})
@@ -0,0 +1,45 @@
+(ns bits.cli.config
+  (:require
+   [bits.app :as app]
+   [clojure.pprint :as pprint]
+   [clojure.string :as str]))
+
+(def spec
+  {:config {:desc "EDN file layered over the defaults"
+            :ref  "<path>"}
+   :show   {:desc   "Print the merged configuration with secrets redacted"
+            :coerce :boolean}})
+
+(defn- format-path
+  [path]
+  (str/join "." (map name path)))
+
+(defn run
+  [_component {:keys [opts]}]
+  (let [config   (try
+                   (app/read-config {:file (:config opts)})
+                   (catch clojure.lang.ExceptionInfo ex
+                     ex))
+        problems (when (map? config) (app/problems config))]
+    (cond
+      (instance? Exception config)
+      (binding [*out* *err*]
+        (println (ex-message config))
+        {:bits.cli.exit/code :bits.cli.exit/config-error})
+
+      (seq problems)
+      (binding [*out* *err*]
+        (doseq [{:keys [path message]} problems]
+          (println (str (format-path path) " " message)))
+        {:bits.cli.exit/code :bits.cli.exit/config-error})
+
+      :else
+      (do
+        (when (:show opts)
+          (pprint/pprint (app/redact (update config :service dissoc :modules))))
+        (println "Configuration is valid.")))))
+
+(def command
+  {:desc "Check configuration from defaults, file, environment and flags"
+   :fn   run
+   :spec spec})
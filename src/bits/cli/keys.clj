@@ -0,0 +1,19 @@
+(ns bits.cli.keys
+  (:require
+   [bits.webhook :as webhook]))
+
+(def spec
+  {})
+
+(defn run
+  [dispatcher _ctx]
+  (let [n (webhook/reseal-secrets! dispatcher)]
+    (println (format "Resealed %d webhook secrets with field key %s."
+                     n
+                     (get-in dispatcher [:keymaster :active-field-key])))))
+
+(def command
+  {:component :webhooks
+   :desc      "Rewrap encrypted columns with the active field key"
+   :fn        run
+   :spec      spec})
@@ -0,0 +1,27 @@
+(ns bits.cli.projections
+  (:require
+   [bits.datomic :as datomic]
+   [bits.projection :as projection]
+   [datomic.api :as d]))
+
+(def spec
+  {:tenant {:desc   "Only backfill this tenant's UUID"
+            :ref    "<uuid>"
+            :coerce parse-uuid}})
+
+(defn run
+  [projector {:keys [opts]}]
+  (let [db         (d/db (datomic/conn (:datomic projector)))
+        tenant-ids (if-let [tenant-id (:tenant opts)]
+                     [tenant-id]
+                     (sort (d/q '[:find [?id ...] :where [_ :tenant/id ?id]] db)))]
+    (doseq [tenant-id tenant-ids]
+      (println (format "Backfilled %d daily totals for tenant %s."
+                       (projection/backfill! projector db tenant-id)
+                       tenant-id)))))
+
+(def command
+  {:component :projector
+   :desc      "Rebuild dashboard totals from Datomic"
+   :fn        run
+   :spec      spec})
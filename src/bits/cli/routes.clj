@@ -0,0 +1,29 @@
+(ns bits.cli.routes
+  (:require
+   [babashka.cli :as cli]
+   [bits.module :as module]
+   [bits.service :as service]
+   [clojure.string :as str]))
+
+(def spec
+  {})
+
+(defn rows
+  "Table rows for the routes, sorted by path then method."
+  [table]
+  (into [["PATH" "METHOD" "SCOPES" "MODULE"]]
+        (map (fn [{:keys [method path scopes] module-name :module}]
+               [path
+                (str/upper-case (name method))
+                (if (seq scopes) (str/join "," (sort (map name scopes))) "-")
+                (name module-name)]))
+        (sort-by (juxt :path :method) table)))
+
+(defn run
+  [_component _ctx]
+  (println (cli/format-table {:rows (rows (module/route-table service/modules))})))
+
+(def command
+  {:desc "List every route with its method, API key scopes and module"
+   :fn   run
+   :spec spec})
@@ -2,14 +2,39 @@
   (:require
    [bits.app :as app]
    [com.stuartsierra.component :as component]
-   [io.pedestal.log :as log]))
+   [io.pedestal.log :as log])
+  (:import
+   (sun.misc Signal SignalHandler)))
 
 (def spec
-  {})
+  {:cluster {:desc "Cluster to join, overriding CLUSTER_NAME and the config file"
+             :ref  "<name>"}
+   :config  {:desc "EDN file layered over the defaults"
+             :ref  "<path>"}
+   :port    {:desc   "HTTP port, overriding PORT and the config file"
+             :ref    "<port>"
+             :coerce :long}})
+
+(defn- on-hangup
+  "Reloads config on SIGHUP, as daemons do."
+  [f]
+  (Signal/handle (Signal. "HUP")
+                 (reify SignalHandler
+                   (handle [_ _]
+                     (try
+                       (f)
+                       (catch Exception ex
+                         (log/warn :msg "Failed to reload config?!" :exception ex)))))))
 
 (defn run
-  [_component _ctx]
-  (component/start (app/system))
+  [_component {:keys [opts]}]
+  (let [overrides (cond-> {}
+                    (:cluster opts) (assoc-in [:cluster :cluster-name] (:cluster opts))
+                    (:port opts)    (assoc-in [:service :http-port] (:port opts)))
+        options   {:file      (:config opts)
+                   :overrides overrides}
+        system    (component/start (app/system (app/read-config options)))]
+    (on-hangup #(app/reload! system options "SIGHUP")))
   (log/info :msg "Your Bits are ready.")
   @(promise))
 
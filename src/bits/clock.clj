@@ -0,0 +1,29 @@
+(ns bits.clock
+  (:require
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [java-time.api :as time]))
+
+;;; ----------------------------------------------------------------------------
+;;; Clock
+;;;
+;;; Components that compare against the current time take a clock instead of
+;;; asking the JVM, so tests can hold time still or jump past an expiry.
+
+(defprotocol Tick
+  (now [this]
+    "Returns the current time as an `OffsetDateTime`."))
+
+(defrecord Clock []
+  Tick
+  (now [_this]
+    (time/offset-date-time)))
+
+(defmethod print-method Clock
+  [_ ^java.io.Writer w]
+  (.write w "#<Clock>"))
+
+(defn make-clock
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Clock config))
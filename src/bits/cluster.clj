@@ -33,6 +33,10 @@
 
 ;;; ----------------------------------------------------------------------------
 ;;; Peer name
+;;;
+;;; Peers are named at random each boot unless configured with a name, which
+;;; keeps logs and views comparable across restarts. Names must be unique in
+;;; the cluster. Who may join at all is settled by the keystore, not the name.
 
 (defn random-peer-name
   [peer]
@@ -44,6 +48,10 @@
 
 ;;; ----------------------------------------------------------------------------
 ;;; Stack
+;;;
+;;; A channel only ever joins peers connected under the same cluster name, so
+;;; separate deployments sharing a network stay apart by naming themselves
+;;; differently. The keystore keeps out anyone without the certificate.
 
 (defn- make-protocols
   [peer]
@@ -92,7 +100,7 @@
   "Creates peer state: name, view atom, protocol stack, and channel.
    Does not connect or attach a receiver."
   [peer]
-  (let [peer-name (random-peer-name peer)
+  (let [peer-name (or (:peer-name peer) (random-peer-name peer))
         view      (atom #{})
         protocols (make-protocols peer)
         chan       (-> (JChannel. protocols)
@@ -183,6 +191,7 @@
                  initial-hosts
                  keystore-password
                  keystore-path
+                 peer-name
                  randomizer
                  view]
   component/Lifecycle
@@ -1,6 +1,7 @@
 (ns bits.coerce
   (:require
    [bits.cryptex :as cryptex]
+   [bits.identifier :as identifier]
    [malli.core :as m]
    [malli.transform :as mt]
    [reitit.coercion.malli :as coercion.malli]))
@@ -19,6 +20,15 @@
   "Email address wrapped in cryptex for PII protection."
   [:fn {:decode/string cryptex/cryptex} cryptex/cryptex?])
 
+;;; ----------------------------------------------------------------------------
+;;; Public IDs
+
+(defn public-id
+  "Prefixed public ID of kind, decoded to its UUID. Anything else is left as a
+  string and fails validation."
+  [kind]
+  [:uuid {:decode/string #(or (identifier/parse-prefixed kind %) %)}])
+
 ;;; ----------------------------------------------------------------------------
 ;;; Registry
 
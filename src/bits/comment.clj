@@ -0,0 +1,275 @@
+(ns bits.comment
+  "Threaded comments on CMS pages.
+
+  Tenants choose who may comment: anyone, only people signed in to their
+  realm, or nobody, which is the default. Comments from people who are
+  signed in go up straight away; anonymous ones wait for a tenant admin.
+
+  Spam is kept out in three ways. Forms carry a honeypot field people never
+  see, and anything that fills it in is thanked and dropped. Each IP address
+  may only post so many comments in a while. And every comment passes the
+  spam filters, which hold anything they flag for a tenant admin to decide.
+  Our own heuristics are always one filter; services like Akismet can be
+  added by implementing `SpamFilter` and listing them under
+  `:extra-filters`."
+  (:require
+   [bits.anomaly :as anom]
+   [bits.clock :as clock]
+   [bits.crypto :as crypto]
+   [bits.locale :refer [tru]]
+   [bits.moderation :as moderation]
+   [bits.pagination :as pagination]
+   [bits.postgres :as postgres]
+   [bits.postgres.comment :as postgres.comment]
+   [bits.spec]
+   [clojure.core.async :as a]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [com.stuartsierra.component :as component]
+   [datomic.api :as d]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Settings
+
+(def modes
+  #{:anyone :signed-in})
+
+(defn mode
+  "Who may comment on the tenant's pages, or nil when comments are off."
+  [db tenant-id]
+  (:tenant/comments (d/pull db [:tenant/comments] [:tenant/id tenant-id])))
+
+(defn mode-tx
+  "Sets who may comment, turning comments off when choice is nil."
+  [db tenant-id choice]
+  {:pre [(or (nil? choice) (contains? modes choice))]}
+  (if choice
+    [{:tenant/id tenant-id :tenant/comments choice}]
+    (when-let [old (mode db tenant-id)]
+      [[:db/retract [:tenant/id tenant-id] :tenant/comments old]])))
+
+;;; ----------------------------------------------------------------------------
+;;; Spam filters
+;;;
+;;; A comment is a map of `:body`, `:author-name`, `:author-id` and
+;;; `:ip-address`.
+
+(defprotocol SpamFilter
+  (verdict [spam-filter tenant-id comment]
+    "Nil when the filter sees nothing wrong with the comment, or why it
+    looks like spam."))
+
+(defrecord Heuristics []
+  SpamFilter
+  (verdict [_ _tenant-id {:keys [author-name body]}]
+    (second (moderation/screen {:text (str author-name "\n" body)}))))
+
+(defn- spam-reason
+  [comments tenant-id comment]
+  (some #(verdict % tenant-id comment) (:filters comments)))
+
+;;; ----------------------------------------------------------------------------
+;;; Live updates
+;;;
+;;; Every change puts the page's ID on a mult, so open pages show new comments
+;;; and the queue fills without anyone reloading.
+
+(defn- changed!
+  [comments page-id]
+  (a/put! (:changes comments) page-id))
+
+(defn tap!
+  [comments ch]
+  (a/tap (:mult comments) ch false))
+
+(defn untap!
+  [comments ch]
+  (a/untap (:mult comments) ch))
+
+;;; ----------------------------------------------------------------------------
+;;; Posting
+
+(def max-body
+  2000)
+
+(def max-name
+  80)
+
+(defn- recent-count
+  [comments ip-hash]
+  (:recent (postgres/execute-one! (:postgres comments)
+                                  {:select [[[:count :*] :recent]]
+                                   :from   [:comments]
+                                   :where  [:and
+                                            [:= :ip-hash ip-hash]
+                                            [:> :created-at [:- (clock/now (:clock comments))
+                                                             [:make-interval :mins (:window-minutes comments)]]]]})))
+
+(defn- parent-ok?
+  [comments tenant-id page-id parent-id]
+  (or (nil? parent-id)
+      (some? (postgres/execute-one! (:postgres comments)
+                                    {:select [:id]
+                                     :from   [:comments]
+                                     :where  [:and
+                                              [:= :id parent-id]
+                                              [:= :tenant-id tenant-id]
+                                              [:= :page-id page-id]
+                                              [:= :status "approved"]]}))))
+
+(defn- status
+  [author-id reason]
+  (cond
+    reason           "spam"
+    (nil? author-id) "pending"
+    :else            "approved"))
+
+(defn post!
+  "Posts a comment on the page. Returns the comment's ID and status, an
+  anomaly when the tenant doesn't take comments from the author or the
+  comment doesn't check out, or nil when the honeypot caught it."
+  [comments db {:keys [author-id author-name body honeypot ip-address page-id parent-id tenant-id] :as comment}]
+  (span/with-span! {:name ::post!}
+    (let [body        (str/trim (or body ""))
+          author-name (str/trim (or author-name ""))
+          allowed     (mode db tenant-id)
+          ip-hash     (crypto/sha256 (str ip-address))]
+      (cond
+        (not (str/blank? honeypot))
+        nil
+
+        (nil? allowed)
+        (anom/forbidden {::anom/message (tru "Comments are off.")})
+
+        (and (= :signed-in allowed) (nil? author-id))
+        (anom/forbidden {::anom/message (tru "Sign in to comment.")})
+
+        (str/blank? body)
+        (anom/incorrect {::anom/message (tru "Write something first.")})
+
+        (< max-body (count body))
+        (anom/incorrect {::anom/message (tru "Comments can be at most {0} characters." max-body)})
+
+        (< max-name (count author-name))
+        (anom/incorrect {::anom/message (tru "Names can be at most {0} characters." max-name)})
+
+        (not (parent-ok? comments tenant-id page-id parent-id))
+        (anom/not-found {::anom/message (tru "That comment doesn''t exist.")})
+
+        (<= (:max-per-window comments) (recent-count comments ip-hash))
+        (anom/busy {::anom/message             (tru "You''re commenting too fast. Please try again later.")
+                    ::anom/retry-after-seconds (* 60 (:window-minutes comments))})
+
+        :else
+        (let [reason    (spam-reason comments tenant-id (assoc comment :author-name author-name :body body))
+              persisted (postgres/execute-one! (:postgres comments)
+                                               {:insert-into :comments
+                                                :values      [{:id          (random-uuid)
+                                                               :tenant-id   tenant-id
+                                                               :page-id     page-id
+                                                               :parent-id   parent-id
+                                                               :author-id   author-id
+                                                               :author-name author-name
+                                                               :body        body
+                                                               :ip-hash     ip-hash
+                                                               :status      (status author-id reason)
+                                                               :spam-reason reason}]
+                                                :returning   [:id :status]})]
+          (changed! comments page-id)
+          persisted)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Threads
+
+(defn nest
+  "Arranges comments, oldest first, into threads: the top-level comments,
+  each with its `:replies`. Replies to comments that aren't there are left
+  out."
+  [comments]
+  (let [children (group-by ::postgres.comment/parent-id comments)
+        attach   (fn attach [c]
+                   (assoc c :replies (mapv attach (get children (::postgres.comment/id c)))))]
+    (mapv attach (get children nil))))
+
+(defn thread
+  "The page's approved comments as threads."
+  [comments tenant-id page-id]
+  {:post [(s/valid? (s/coll-of ::postgres.comment/persisted) %)]}
+  (span/with-span! {:name ::thread}
+    (nest
+     (postgres/execute! (:postgres comments)
+                        {:select   [:id :page-id :parent-id :author-id :author-name :body :status :created-at]
+                         :from     [:comments]
+                         :where    [:and
+                                    [:= :tenant-id tenant-id]
+                                    [:= :page-id page-id]
+                                    [:= :status "approved"]]
+                         :order-by [:created-at :id]}))))
+
+;;; ----------------------------------------------------------------------------
+;;; Moderation queue
+
+(def ordering
+  {:direction :asc
+   :columns   [[:created-at ::postgres.comment/created-at]
+               [:id ::postgres.comment/id]]})
+
+(defn queue
+  "Returns a page of the tenant's comments awaiting a decision, oldest first."
+  [comments tenant-id page-request]
+  {:post [(s/valid? (s/coll-of ::postgres.comment/persisted) (:items %))]}
+  (span/with-span! {:name ::queue}
+    (-> (postgres/execute! (:postgres comments)
+                           (pagination/paginate {:select [:id :page-id :parent-id :author-id :author-name
+                                                          :body :status :spam-reason :created-at]
+                                                 :from   [:comments]
+                                                 :where  [:and
+                                                          [:= :tenant-id tenant-id]
+                                                          [:in :status ["pending" "spam"]]]}
+                                                ordering
+                                                page-request))
+        (pagination/page ordering page-request))))
+
+(defn decide!
+  "Approves or rejects one of the tenant's queued comments. Returns true when
+  there was one."
+  [comments tenant-id id decision reviewer-id]
+  {:pre [(contains? #{"approved" "rejected"} decision)]}
+  (span/with-span! {:name ::decide!}
+    (when-let [decided (postgres/execute-one! (:postgres comments)
+                                              {:update    :comments
+                                               :set       {:status      decision
+                                                           :reviewed-at (clock/now (:clock comments))
+                                                           :reviewed-by reviewer-id}
+                                               :where     [:and
+                                                           [:= :id id]
+                                                           [:= :tenant-id tenant-id]
+                                                           [:in :status ["pending" "spam"]]]
+                                               :returning [:page-id]})]
+      (changed! comments (::postgres.comment/page-id decided))
+      true)))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Comments [changes clock extra-filters filters max-per-window mult postgres window-minutes]
+  component/Lifecycle
+  (start [this]
+    (let [changes (a/chan (a/sliding-buffer 64))]
+      (assoc this
+             :changes changes
+             :filters (into [(->Heuristics)] extra-filters)
+             :mult    (a/mult changes))))
+  (stop [this]
+    (some-> changes a/close!)
+    (assoc this :changes nil :filters nil :mult nil)))
+
+(defmethod print-method Comments
+  [_ ^java.io.Writer w]
+  (.write w "#<Comments>"))
+
+(defn make-comments
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Comments config))
@@ -9,8 +9,12 @@
    [buddy.core.nonce :as nonce]
    [buddy.hashers :as hashers]
    [clojure.spec.alpha :as s]
+   [clojure.string :as str]
    [com.stuartsierra.component :as component]
-   [steffan-westcott.clj-otel.api.trace.span :as span]))
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (javax.crypto Cipher)
+   (javax.crypto.spec GCMParameterSpec SecretKeySpec)))
 
 (def ^:private dummy-password
   "Constant password for timing oracle prevention."
@@ -27,17 +31,119 @@
   [_keymaster cryptex hash]
   (hashers/verify (cryptex/reveal cryptex) hash))
 
+;;; ----------------------------------------------------------------------------
+;;; Envelope encryption
+;;;
+;;; Columns that must be readable by the app but not by whoever reads a backup
+;;; are sealed. Every value gets its own data key, encrypted with AES-GCM; the
+;;; data key is then wrapped with a key derived from one of the configured
+;;; field keys. Sealed values look like:
+;;;
+;;;   enc1.<key-id>.<wrapped data key>.<ciphertext>
+;;;
+;;; The context (usually `table.column`) is authenticated with the ciphertext,
+;;; so a value copied into another column won't open. Rotating the active field
+;;; key only rewraps data keys, see `reseal`.
+
+(def ^:private sealed-prefix "enc1.")
+(def ^:private nonce-size 12)
+
+(defn- hkdf
+  "HKDF-SHA256 for a single 32-byte block."
+  [ikm info]
+  (let [prk (mac/hash ikm {:key (byte-array 32) :alg :hmac+sha256})]
+    (mac/hash (byte-array (concat (codecs/str->bytes info) [1])) {:key prk :alg :hmac+sha256})))
+
+(defn- aes-gcm
+  ^bytes [mode ^bytes key ^bytes nonce ^String aad ^bytes input]
+  (let [cipher (Cipher/getInstance "AES/GCM/NoPadding")]
+    (.init cipher (int mode) (SecretKeySpec. key "AES") (GCMParameterSpec. 128 nonce))
+    (when aad
+      (.updateAAD cipher (codecs/str->bytes aad)))
+    (.doFinal cipher input)))
+
+(defn- encrypt
+  [key aad input]
+  (let [nonce (nonce/random-bytes nonce-size)]
+    (codecs/bytes->b64-str (byte-array (concat nonce (aes-gcm Cipher/ENCRYPT_MODE key nonce aad input))) true)))
+
+(defn- decrypt
+  [key aad s]
+  (let [bs (codecs/b64->bytes s true)]
+    (aes-gcm Cipher/DECRYPT_MODE
+             key
+             (java.util.Arrays/copyOfRange ^bytes bs 0 (int nonce-size))
+             aad
+             (java.util.Arrays/copyOfRange ^bytes bs (int nonce-size) (alength ^bytes bs)))))
+
+(defn- key-encryption-key
+  [keymaster key-id]
+  (or (get (:key-encryption-keys keymaster) key-id)
+      (throw (ex-info "Unknown field key?!" {:key-id key-id}))))
+
+(defn sealed?
+  [s]
+  (and (string? s) (str/starts-with? s sealed-prefix)))
+
+(defn sealed-with
+  "The ID of the field key a sealed value was wrapped with."
+  [s]
+  (when (sealed? s)
+    (second (str/split s #"\." 3))))
+
+(defn seal
+  [keymaster context plaintext]
+  (span/with-span! {:name ::seal}
+    (let [key-id   (:active-field-key keymaster)
+          data-key (nonce/random-bytes 32)]
+      (str sealed-prefix
+           key-id "."
+           (encrypt (key-encryption-key keymaster key-id) key-id data-key) "."
+           (encrypt data-key context (codecs/str->bytes plaintext))))))
+
+(defn unseal
+  "Opens a sealed value. Values written before the column was sealed are
+  returned as they are, until `reseal` gets to them."
+  [keymaster context s]
+  (span/with-span! {:name ::unseal}
+    (if-not (sealed? s)
+      s
+      (let [[_ key-id wrapped ciphertext] (str/split s #"\.")
+            data-key                      (decrypt (key-encryption-key keymaster key-id) key-id wrapped)]
+        (codecs/bytes->str (decrypt data-key context ciphertext))))))
+
+(defn reseal
+  "Rewraps a sealed value's data key with the active field key, or seals a
+  plaintext value. Returns nil when there's nothing to do."
+  [keymaster context s]
+  (span/with-span! {:name ::reseal}
+    (let [active (:active-field-key keymaster)]
+      (cond
+        (not (sealed? s))
+        (seal keymaster context s)
+
+        (not= active (sealed-with s))
+        (let [[_ key-id wrapped ciphertext] (str/split s #"\.")
+              data-key                      (decrypt (key-encryption-key keymaster key-id) key-id wrapped)]
+          (str sealed-prefix
+               active "."
+               (encrypt (key-encryption-key keymaster active) active data-key) "."
+               ciphertext))))))
+
 ;;; ----------------------------------------------------------------------------
 ;;; Keymaster
 
-(defrecord Keymaster [argon dummy-hash idle-timeout-days]
+(defrecord Keymaster [active-field-key argon dummy-hash field-keys idle-timeout-days key-encryption-keys]
   component/Lifecycle
   (start [this]
     (span/with-span! {:name ::start-keymaster}
-      (assoc this :dummy-hash (derive this (cryptex/cryptex dummy-password)))))
+      (assoc this
+             :dummy-hash          (derive this (cryptex/cryptex dummy-password))
+             :key-encryption-keys (update-vals field-keys
+                                               #(hkdf (codecs/b64->bytes %) "bits.field-encryption")))))
   (stop [this]
     (span/with-span! {:name ::stop-keymaster}
-      (assoc this :dummy-hash nil))))
+      (assoc this :dummy-hash nil :key-encryption-keys nil))))
 
 (defn make-keymaster
   [config]
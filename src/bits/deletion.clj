@@ -0,0 +1,96 @@
+(ns bits.deletion
+  "Soft deletion for tenant resources.
+
+  Deleting marks an entity with `:entity/deleted-at` and `:entity/deleted-by`
+  rather than retracting it, so a slip can be undone. Queries over tenant data
+  skip marked entities, and the reaper retracts them once they're older than
+  the retention period."
+  (:require
+   [datomic.api :as d]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.util Date)))
+
+;;; ----------------------------------------------------------------------------
+;;; Resources
+;;;
+;;; Each kind names its identity attribute and a rule proving the entity
+;;; belongs to the tenant, so one tenant can't delete or restore another's.
+
+(def resources
+  {:membership {:id    :membership/id
+                :owner '[[(owned-by ?e ?t)
+                          [?e :membership/tenant ?t]]]}
+   :page       {:id    :page/id
+                :owner '[[(owned-by ?e ?t)
+                          [?t :tenant/pages ?e]]]}
+   :product    {:id    :product/id
+                :owner '[[(owned-by ?e ?t)
+                          [?t :tenant/products ?e]]]}})
+
+(defn lookup
+  "The tenant's entity of kind with the given ID, deleted or not."
+  [db tenant-id kind id]
+  (let [{id-attr :id owner :owner} (get resources kind)]
+    (some->> (d/q '[:find ?e .
+                    :in $ % ?id-attr ?id ?tenant-id
+                    :where
+                    [?e ?id-attr ?id]
+                    [?t :tenant/id ?tenant-id]
+                    (owned-by ?e ?t)]
+                  db owner (d/entid db id-attr) id tenant-id)
+             (d/entity db))))
+
+(defn deleted?
+  [entity]
+  (some? (:entity/deleted-at entity)))
+
+;;; ----------------------------------------------------------------------------
+;;; Transactions
+
+(defn delete-tx
+  [entity user-id ^Date now]
+  [{:db/id             (:db/id entity)
+    :entity/deleted-at now
+    :entity/deleted-by [:user/id user-id]}])
+
+(defn restore-tx
+  [entity]
+  [[:db/retract (:db/id entity) :entity/deleted-at (:entity/deleted-at entity)]
+   [:db/retract (:db/id entity) :entity/deleted-by (:db/id (:entity/deleted-by entity))]])
+
+(defn deleted
+  "The tenant's deleted entities of kind, most recent first."
+  [db tenant-id kind]
+  (let [{:keys [owner]} (get resources kind)]
+    (->> (d/q '[:find [?e ...]
+                :in $ % ?tenant-id
+                :where
+                [?t :tenant/id ?tenant-id]
+                [?e :entity/deleted-at]
+                (owned-by ?e ?t)]
+              db owner tenant-id)
+         (map #(d/entity db %))
+         (sort-by :entity/deleted-at #(compare %2 %1)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Purging
+
+(defn purge-tx
+  "Retracts everything deleted before the cutoff."
+  [db ^Date before]
+  (for [e (d/q '[:find [?e ...]
+                 :in $ ?before
+                 :where
+                 [?e :entity/deleted-at ?at]
+                 [(< ?at ?before)]]
+               db before)]
+    [:db/retractEntity e]))
+
+(defn purge!
+  [conn ^Date before]
+  (span/with-span! {:name ::purge!}
+    (let [tx (vec (purge-tx (d/db conn) before))]
+      (when (seq tx)
+        @(d/transact conn tx))
+      (count tx))))
@@ -0,0 +1,324 @@
+(ns bits.discount
+  "Discount codes buyers enter at checkout.
+
+  A code takes either a percentage or a fixed amount off, optionally only on
+  some products, until it expires, is disabled, or has been redeemed as many
+  times as allowed. Codes live in Postgres; products stay in Datomic, so
+  scoping holds product UUIDs.
+
+  Quoting is separate from redeeming. `validate` checks the codes a buyer
+  entered and prices the basket without writing anything, so it can run on
+  every change to the basket. `redeem!` counts the codes once the order is
+  placed, and refuses when one has since run out.
+
+  When several codes are entered, percentages are taken before fixed amounts
+  so the order they were typed in doesn't matter. Codes that aren't stackable
+  never combine with another: the basket gets whichever is worth most, the
+  best of them alone or every stackable code together. The tenant's pricing
+  rules run after the codes, see `bits.pricing`."
+  (:require
+   [bits.anomaly :as anom]
+   [bits.clock :as clock]
+   [bits.locale :refer [tru]]
+   [bits.postgres :as postgres]
+   [bits.postgres.discount-code :as postgres.discount-code]
+   [bits.pricing :as pricing]
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [com.stuartsierra.component :as component]
+   [steffan-westcott.clj-otel.api.metrics.instrument :as instrument]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Codes
+
+(def kinds
+  #{"fixed" "percent"})
+
+(defn normalize
+  "Codes are matched without regard to case or surrounding space."
+  [code]
+  (some-> code str/trim str/upper-case))
+
+(def ^:private code-pattern
+  #"[A-Z0-9_-]{3,32}")
+
+;;; ----------------------------------------------------------------------------
+;;; Managing
+
+(defn- invalid
+  [{:keys [amount-off code currency kind max-redemptions percent-off]}]
+  (cond
+    (not (re-matches code-pattern (str code)))
+    (tru "Codes are 3 to 32 letters, digits, dashes or underscores.")
+
+    (not (contains? kinds kind))
+    (tru "Pick a percentage or a fixed amount.")
+
+    (and (= "percent" kind) (not (and (int? percent-off) (<= 1 percent-off 100))))
+    (tru "Percentages are between 1 and 100.")
+
+    (and (= "fixed" kind) (not (pos-int? amount-off)))
+    (tru "Amounts must be more than zero.")
+
+    (and (= "fixed" kind) (str/blank? currency))
+    (tru "Pick the amount''s currency.")
+
+    (and (some? max-redemptions) (not (pos-int? max-redemptions)))
+    (tru "Allow at least one redemption.")))
+
+(defn create!
+  "Issues a code for the tenant. Returns its ID, or an anomaly when the code
+  doesn't check out or the tenant already has one like it."
+  [discounts {:keys [amount-off currency expires-at kind max-redemptions
+                     percent-off product-ids stackable? tenant-id]
+              :as   params}]
+  (span/with-span! {:name ::create!}
+    (let [code (normalize (:code params))]
+      (if-let [message (invalid (assoc params :code code))]
+        (anom/incorrect {::anom/message message})
+        (if-let [created (postgres/execute-one! (:postgres discounts)
+                                                {:insert-into :discount-codes
+                                                 :values      [{:id              (random-uuid)
+                                                                :tenant-id       tenant-id
+                                                                :code            code
+                                                                :kind            kind
+                                                                :percent-off     (when (= "percent" kind) percent-off)
+                                                                :amount-off      (when (= "fixed" kind) amount-off)
+                                                                :currency        (when (= "fixed" kind) currency)
+                                                                :product-ids     [:array (vec (distinct product-ids)) :uuid]
+                                                                :stackable       (boolean stackable?)
+                                                                :max-redemptions max-redemptions
+                                                                :expires-at      expires-at}]
+                                                 :on-conflict [:tenant-id :code]
+                                                 :do-nothing  true
+                                                 :returning   [:id]})]
+          (::postgres.discount-code/id created)
+          (anom/conflict {::anom/message (tru "There''s already a code called {0}." code)}))))))
+
+(defn list-codes
+  "The tenant's codes that haven't been disabled, newest first."
+  [discounts tenant-id]
+  {:post [(s/valid? (s/coll-of ::postgres.discount-code/persisted) %)]}
+  (span/with-span! {:name ::list-codes}
+    (postgres/execute! (:postgres discounts)
+                       {:select   [:id :code :kind :percent-off :amount-off :currency
+                                   :product-ids :stackable :max-redemptions :redemptions
+                                   :expires-at :created-at]
+                        :from     [:discount-codes]
+                        :where    [:and
+                                   [:= :tenant-id tenant-id]
+                                   [:= :disabled-at nil]]
+                        :order-by [[:created-at :desc]]})))
+
+(defn disable!
+  "Stops the code being accepted. Returns true when it was active."
+  [discounts tenant-id id]
+  (span/with-span! {:name ::disable!}
+    (let [[{:keys [next.jdbc/update-count]}]
+          (postgres/execute! (:postgres discounts)
+                             {:update :discount-codes
+                              :set    {:disabled-at (clock/now (:clock discounts))}
+                              :where  [:and
+                                       [:= :id id]
+                                       [:= :tenant-id tenant-id]
+                                       [:= :disabled-at nil]]})]
+      (pos? (or update-count 0)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Pricing
+;;;
+;;; Line items are maps of `:product-id`, `:currency` and `:amount`, the line's
+;;; total in minor units, with `:quantity` when it's more than one and `:sale?`
+;;; when the product is already reduced, which pricing rules can leave out. Each line remembers how much of it is left to
+;;; discount, so stacked codes can never take a line below zero.
+
+(defn- applies?
+  [code {:keys [currency product-id]}]
+  (let [product-ids (::postgres.discount-code/product-ids code)]
+    (and (or (empty? product-ids) (some #{product-id} product-ids))
+         (or (= "percent" (::postgres.discount-code/kind code))
+             (= currency (::postgres.discount-code/currency code))))))
+
+(defn- take-off
+  [line off]
+  (-> line
+      (update :remaining - off)
+      (update :discount + off)))
+
+(defn- apply-code
+  "Takes code off the lines it applies to. Fixed amounts are used up on lines
+  in basket order."
+  [lines code]
+  (if (= "percent" (::postgres.discount-code/kind code))
+    (let [percent (::postgres.discount-code/percent-off code)]
+      (mapv (fn [line]
+              (if (applies? code line)
+                (take-off line (quot (* (:remaining line) percent) 100))
+                line))
+            lines))
+    (first
+     (reduce (fn [[lines left] line]
+               (if (and (pos? left) (applies? code line))
+                 (let [off (min left (:remaining line))]
+                   [(conj lines (take-off line off)) (- left off)])
+                 [(conj lines line) left]))
+             [[] (::postgres.discount-code/amount-off code)]
+             lines))))
+
+(defn- apply-codes
+  [lines codes]
+  (let [ordered (sort-by (juxt #(if (= "percent" (::postgres.discount-code/kind %)) 0 1)
+                               ::postgres.discount-code/code)
+                         codes)]
+    {:codes ordered
+     :lines (reduce apply-code
+                    (mapv #(assoc % :remaining (:amount %) :discount 0) lines)
+                    ordered)}))
+
+(defn- total-discount
+  [{:keys [lines]}]
+  (transduce (map :discount) + 0 lines))
+
+(defn quote-basket
+  "Prices line items with codes, resolving which of them combine. Returns the
+  codes applied, the discount on each line, and the totals."
+  [line-items codes]
+  (let [{stackable true single false} (group-by #(boolean (::postgres.discount-code/stackable %)) codes)
+        candidates                    (cond-> (mapv #(apply-codes line-items [%]) single)
+                                        (seq stackable) (conj (apply-codes line-items stackable)))
+        best                          (reduce (fn [best candidate]
+                                                (if (> (total-discount candidate) (total-discount best))
+                                                  candidate
+                                                  best))
+                                              (apply-codes line-items [])
+                                              candidates)
+        subtotal                      (transduce (map :amount) + 0 line-items)
+        discount                      (total-discount best)]
+    {:applied  (mapv ::postgres.discount-code/code (:codes best))
+     :lines    (mapv #(dissoc % :remaining) (:lines best))
+     :subtotal subtotal
+     :discount discount
+     :total    (- subtotal discount)}))
+
+;;; ----------------------------------------------------------------------------
+;;; Validating
+
+(defn- lookup-codes
+  [postgres tenant-id codes now]
+  (postgres/execute! postgres
+                     {:select [:id :code :kind :percent-off :amount-off :currency
+                               :product-ids :stackable :max-redemptions :redemptions
+                               [[:<= :expires-at now] :expired]
+                               [[:>= :redemptions :max-redemptions] :exhausted]]
+                      :from   [:discount-codes]
+                      :where  [:and
+                               [:= :tenant-id tenant-id]
+                               [:in :code codes]
+                               [:= :disabled-at nil]]}))
+
+(defn- rejection
+  [line-items codes-by-name code]
+  (let [found (get codes-by-name code)]
+    (cond
+      (nil? found)
+      [:unknown (tru "{0} isn''t a valid code." code)]
+
+      (:expired found)
+      [:expired (tru "{0} has expired." code)]
+
+      (:exhausted found)
+      [:exhausted (tru "{0} has been used up." code)]
+
+      (not-any? #(applies? found %) line-items)
+      [:inapplicable (tru "{0} doesn''t apply to anything in your basket." code)])))
+
+(defn validate
+  "Checks the codes a buyer entered against their basket and runs the
+  tenant's pricing rules. Returns the quote, or an anomaly naming the first
+  code that can't be used."
+  [discounts tenant-id codes line-items]
+  (span/with-span! {:name ::validate}
+    (let [codes         (into [] (comp (keep normalize) (remove str/blank?) (distinct)) codes)
+          found         (when (seq codes)
+                          (lookup-codes (:postgres discounts) tenant-id codes
+                                        (clock/now (:clock discounts))))
+          codes-by-name (into {} (map (juxt ::postgres.discount-code/code identity)) found)]
+      (if-let [[reason message] (some #(rejection line-items codes-by-name %) codes)]
+        (do
+          (instrument/add! (:rejection-counter discounts)
+                           {:value 1 :attributes {"reason" (name reason)}})
+          (anom/incorrect {::anom/message message}))
+        (pricing/reprice (:pricing discounts)
+                         tenant-id
+                         (quote-basket line-items (map #(dissoc % :expired :exhausted) found)))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Redeeming
+
+(defn redeem!
+  "Counts one redemption of every code the quote applied. Locks the codes
+  first so two orders can't both take a code's last redemption; when one has
+  run out or been disabled since the quote, nothing is counted and an
+  anomaly is returned."
+  [discounts tenant-id {:keys [applied]}]
+  (span/with-span! {:name ::redeem!}
+    (if (empty? applied)
+      []
+      (postgres/with-transaction [postgres (:postgres discounts)]
+        (let [now    (clock/now (:clock discounts))
+              usable (postgres/execute! postgres
+                                        {:select [:id :kind :stackable]
+                                         :from   [:discount-codes]
+                                         :where  [:and
+                                                  [:= :tenant-id tenant-id]
+                                                  [:in :code applied]
+                                                  [:= :disabled-at nil]
+                                                  [:or [:= :expires-at nil] [:> :expires-at now]]
+                                                  [:or
+                                                   [:= :max-redemptions nil]
+                                                   [:< :redemptions :max-redemptions]]]
+                                         :for    :update})]
+          (if (< (count usable) (count applied))
+            (anom/conflict {::anom/message (tru "A code in your basket can no longer be used.")})
+            (do
+              (postgres/execute! postgres
+                                 {:update :discount-codes
+                                  :set    {:redemptions [:+ :redemptions 1]}
+                                  :where  [:in :id (mapv ::postgres.discount-code/id usable)]})
+              (doseq [code usable]
+                (instrument/add! (:redemption-counter discounts)
+                                 {:value      1
+                                  :attributes {"kind"      (::postgres.discount-code/kind code)
+                                               "stackable" (str (::postgres.discount-code/stackable code))}}))
+              (mapv ::postgres.discount-code/id usable))))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Discounts [clock postgres pricing redemption-counter rejection-counter]
+  component/Lifecycle
+  (start [this]
+    (assoc this
+           :redemption-counter (instrument/instrument
+                                {:name            "discount.redeemed"
+                                 :instrument-type :counter
+                                 :unit            "{redemption}"
+                                 :description     "Discount codes redeemed by kind"})
+           :rejection-counter  (instrument/instrument
+                                {:name            "discount.rejected"
+                                 :instrument-type :counter
+                                 :unit            "{code}"
+                                 :description     "Discount codes refused at checkout by reason"})))
+  (stop [this]
+    (assoc this :redemption-counter nil :rejection-counter nil)))
+
+(defmethod print-method Discounts
+  [_ ^java.io.Writer w]
+  (.write w "#<Discounts>"))
+
+(defn make-discounts
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Discounts config))
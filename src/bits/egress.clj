@@ -0,0 +1,72 @@
+(ns bits.egress
+  "Guards requests we make to URLs that tenants supply, such as webhook
+  endpoints and identity providers.
+
+  Those requests come from inside our network, so a tenant pointing one at
+  loopback, a private range or the cloud metadata address could reach services
+  that are never meant to be public. Only https URLs whose host resolves to
+  public addresses alone are allowed. Hosts are resolved again before every
+  request, so a name that later moves to a private address is caught too."
+  (:require
+   [clojure.string :as str])
+  (:import
+   (java.net InetAddress URI UnknownHostException)))
+
+;;; ----------------------------------------------------------------------------
+;;; Addresses
+
+(defn- in-prefix?
+  "True when the address starts with the first `bits` of `prefix`."
+  [^bytes address ^bytes prefix bits]
+  (and (= (alength address) (alength prefix))
+       (every? (fn [i]
+                 (let [mask (bit-and 0xff (bit-shift-left 0xff (- 8 (min 8 (- bits (* 8 i))))))]
+                   (= (bit-and mask (aget address i))
+                      (bit-and mask (aget prefix i)))))
+               (range (quot (+ bits 7) 8)))))
+
+(def ^:private private-prefixes
+  "Ranges Java doesn't flag itself: this network, carrier-grade NAT and IPv6
+  unique local addresses."
+  (for [[s bits] [["0.0.0.0" 8] ["100.64.0.0" 10] ["fc00::" 7]]]
+    [(.getAddress (InetAddress/getByName s)) bits]))
+
+(defn public-address?
+  [^InetAddress address]
+  (not (or (.isAnyLocalAddress address)
+           (.isLoopbackAddress address)
+           (.isLinkLocalAddress address)
+           (.isSiteLocalAddress address)
+           (.isMulticastAddress address)
+           (some (fn [[prefix bits]] (in-prefix? (.getAddress address) prefix bits))
+                 private-prefixes))))
+
+;;; ----------------------------------------------------------------------------
+;;; URLs
+
+(defn- host
+  [url]
+  (try
+    (let [uri (URI. url)]
+      (when (= "https" (some-> (.getScheme uri) str/lower-case))
+        (.getHost uri)))
+    (catch Exception _
+      nil)))
+
+(defn public-url?
+  "True when the URL is https and every address its host resolves to is
+  public."
+  [url]
+  (boolean
+   (when-let [h (and (string? url) (host url))]
+     (try
+       (every? public-address? (InetAddress/getAllByName h))
+       (catch UnknownHostException _
+         false)))))
+
+(defn check!
+  "Returns the URL, or throws when a request to it must not be made."
+  [url]
+  (when-not (public-url? url)
+    (throw (ex-info "Refusing to request a non-public address?!" {:url url})))
+  url)
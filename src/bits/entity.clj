@@ -29,6 +29,12 @@
 
 (s/def :product/title present?)
 
+;;; ----------------------------------------------------------------------------
+;;; Page
+
+(s/def :page/slug #(and (string? %) (<= 1 (count %) 60) (some? (re-matches #"[a-z0-9]+(?:-[a-z0-9]+)*" %))))
+(s/def :page/title present?)
+
 ;;; ----------------------------------------------------------------------------
 ;;; Variant
 
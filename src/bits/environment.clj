@@ -0,0 +1,23 @@
+(ns bits.environment
+  "Which deployment this is: `:production`, `:staging` or `:development`.
+
+  Only production faces the public. Anything else, and any demo tenant's
+  realm in production too, asks search engines not to index it and shows a
+  banner so nobody mistakes it for the real thing. Email may only reach real
+  inboxes from production.")
+
+(defn production?
+  [environment]
+  (= :production environment))
+
+(defn public?
+  "Whether the realm is the real, indexable thing."
+  [environment realm]
+  (and (production? environment)
+       (not (:tenant/demo? realm))))
+
+(defn send-email?
+  "Whether email may be delivered rather than logged. Anything that sends
+  email must ask first."
+  [environment]
+  (production? environment))
@@ -0,0 +1,151 @@
+(ns bits.event
+  "In-process publish and subscribe between components.
+
+  Topics are a closed set, each with a spec for its data, so a misspelt topic
+  fails where it's published instead of going unheard. Every subscriber has
+  its own bounded buffer. One that falls behind has events dropped and
+  counted rather than holding up the publisher, so nothing here may be the
+  only record of anything. Subscribing to `*` gets every topic."
+  (:require
+   [bits.clock :as clock]
+   [bits.spec]
+   [clojure.core.async :as a]
+   [clojure.spec.alpha :as s]
+   [com.stuartsierra.component :as component]
+   [io.pedestal.log :as log]
+   [steffan-westcott.clj-otel.api.metrics.instrument :as instrument]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.time OffsetDateTime)))
+
+;;; ----------------------------------------------------------------------------
+;;; Topics
+
+(s/def ::amount pos-int?)
+(s/def ::currency string?)
+(s/def ::line-item-id uuid?)
+(s/def ::page-id uuid?)
+(s/def ::product-id uuid?)
+(s/def ::report-id uuid?)
+(s/def ::review-id uuid?)
+(s/def ::target-type string?)
+(s/def ::user-id uuid?)
+
+(def topics
+  {"member.created"      (s/keys :req-un [::user-id])
+   "order.created"       (s/keys :req-un [::amount ::currency ::line-item-id])
+   "page.published"      (s/keys :req-un [::page-id])
+   "page.unpublished"    (s/keys :req-un [::page-id])
+   "product.published"   (s/keys :req-un [::product-id])
+   "product.unpublished" (s/keys :req-un [::product-id])
+   "report.filed"        (s/keys :req-un [::report-id ::target-type])
+   "review.submitted"    (s/keys :req-un [::product-id ::review-id])
+   "tenant.reinstated"   map?
+   "tenant.suspended"    map?})
+
+(s/def ::actor-id (s/nilable uuid?))
+(s/def ::at #(instance? OffsetDateTime %))
+(s/def ::tenant-id uuid?)
+(s/def ::topic (set (keys topics)))
+
+(s/def ::event
+  (s/and (s/keys :req-un [::actor-id ::at ::tenant-id ::topic])
+         #(s/valid? (get topics (:topic %)) (:data %))))
+
+;;; ----------------------------------------------------------------------------
+;;; Subscribing
+
+(def wildcard
+  "*")
+
+(defn subscribe!
+  "Returns a channel of the topic's events, or every event for `*`. Name says
+  who's listening in metrics and logs."
+  [bus topic subscriber-name buffer-size]
+  {:pre [(or (= wildcard topic) (contains? topics topic))]}
+  (let [ch (a/chan buffer-size)]
+    (swap! (:subscribers bus) assoc ch {:name subscriber-name :topic topic})
+    ch))
+
+(defn unsubscribe!
+  [bus ch]
+  (swap! (:subscribers bus) dissoc ch)
+  (a/close! ch))
+
+(defn dropped
+  "How many events each subscriber has missed by falling behind."
+  [bus]
+  @(:dropped bus))
+
+;;; ----------------------------------------------------------------------------
+;;; Publishing
+
+(defn- drop!
+  [bus subscriber-name topic]
+  (swap! (:dropped bus) update subscriber-name (fnil inc 0))
+  (instrument/add! (:drop-counter bus)
+                   {:value      1
+                    :attributes {"subscriber" subscriber-name "topic" topic}}))
+
+(defn publish!
+  "Offers the event to every subscriber without waiting on any of them.
+  Returns how many took it."
+  [bus tenant-id actor-id topic data]
+  (span/with-span! {:name ::publish!}
+    (let [event {:actor-id  actor-id
+                 :at        (clock/now (:clock bus))
+                 :data      data
+                 :tenant-id tenant-id
+                 :topic     topic}]
+      (when-not (s/valid? ::event event)
+        (throw (ex-info (str "Invalid event: " topic) (s/explain-data ::event event))))
+      (let [delivered (reduce-kv (fn [n ch {subscriber-name :name subscribed :topic}]
+                                   (if (#{topic wildcard} subscribed)
+                                     (if (a/offer! ch event)
+                                       (inc n)
+                                       (do (drop! bus subscriber-name topic) n))
+                                     n))
+                                 0
+                                 @(:subscribers bus))]
+        (span/add-span-data! {:attributes {:topic topic :delivered delivered}})
+        delivered))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+;;;
+;;; The bus logs every event itself, which makes the log the audit trail of
+;;; last resort and proves the wildcard works.
+
+(defn- log-events!
+  [ch]
+  (a/go-loop []
+    (when-let [{:keys [actor-id tenant-id topic]} (a/<! ch)]
+      (log/info :msg "Event" :topic topic :tenant-id tenant-id :actor-id actor-id)
+      (recur))))
+
+(defrecord Bus [buffer-size clock drop-counter dropped subscribers]
+  component/Lifecycle
+  (start [this]
+    (let [this (assoc this
+                      :drop-counter (instrument/instrument
+                                     {:name            "event.dropped"
+                                      :instrument-type :counter
+                                      :unit            "{event}"
+                                      :description     "Events dropped because a subscriber fell behind"})
+                      :dropped      (atom {})
+                      :subscribers  (atom {}))]
+      (log-events! (subscribe! this wildcard "log" buffer-size))
+      this))
+  (stop [this]
+    (doseq [ch (some-> subscribers deref keys)]
+      (a/close! ch))
+    (assoc this :drop-counter nil :dropped nil :subscribers nil)))
+
+(defmethod print-method Bus
+  [_ ^java.io.Writer w]
+  (.write w "#<Bus>"))
+
+(defn make-bus
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Bus config))
@@ -0,0 +1,171 @@
+(ns bits.export
+  (:require
+   [bits.deletion :as deletion]
+   [bits.spec]
+   [charred.api :as json]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [com.stuartsierra.component :as component]
+   [datomic.api :as d]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.io Writer)
+   (java.time Instant LocalDate ZoneOffset)
+   (java.util Date Set)
+   (java.util.concurrent ConcurrentHashMap)))
+
+;;; ----------------------------------------------------------------------------
+;;; Datasets
+;;;
+;;; Each dataset is a fixed list of columns and a lazy seq of rows walked from
+;;; the tenant's datoms, so an export never holds more than one row in memory.
+;;; Buyers are exported by ID only; emails stay in the members export, which
+;;; only admins see anyway.
+
+(defn- instant
+  [^Date d]
+  (some-> d .toInstant str))
+
+(defn- tenant-refs
+  "Live entities the tenant refers to through attr."
+  [db tenant-id attr]
+  (sequence (comp (map :v)
+                  (remove #(deletion/deleted? (d/entity db %))))
+            (d/datoms db :eavt [:tenant/id tenant-id] attr)))
+
+(defn- in-range?
+  [{:keys [from to]} ^Date d]
+  (let [i (.toInstant d)]
+    (and (or (nil? from) (not (.isBefore i ^Instant from)))
+         (or (nil? to) (.isBefore i ^Instant to)))))
+
+(def datasets
+  {"members"
+   {:columns [:id :email :role]
+    :rows    (fn [db tenant-id _range]
+               (for [m (d/q '[:find [?m ...]
+                              :in $ ?tenant-id
+                              :where
+                              [?t :tenant/id ?tenant-id]
+                              [?m :membership/tenant ?t]
+                              (not [?m :entity/deleted-at])]
+                            db tenant-id)
+                     :let [m (d/entity db m)]]
+                 {:email (get-in m [:membership/user :user/email])
+                  :id    (get-in m [:membership/user :user/id])
+                  :role  (some-> m :membership/role name)}))}
+
+   "orders"
+   {:columns [:id :created-at :product-title :variant-name :sku-code :quantity :unit-amount :currency :buyer-id
+              :license-hash]
+    :rows    (fn [db tenant-id range]
+               (for [e    (tenant-refs db tenant-id :tenant/line-items)
+                     :let [li (d/entity db e)]
+                     :when (in-range? range (:line-item/created-at li))]
+                 {:buyer-id      (get-in li [:line-item/buyer :user/id])
+                  :created-at    (instant (:line-item/created-at li))
+                  :currency      (some-> li :line-item/unit-price :money/currency name)
+                  :id            (:line-item/id li)
+                  :license-hash  (:line-item/license-hash li)
+                  :product-title (:line-item/product-title li)
+                  :quantity      (:line-item/quantity li)
+                  :sku-code      (:line-item/sku-code li)
+                  :unit-amount   (get-in li [:line-item/unit-price :money/amount])
+                  :variant-name  (:line-item/variant-name li)}))}
+
+   "products"
+   {:columns [:id :title :status :position :created-at]
+    :rows    (fn [db tenant-id _range]
+               (for [e    (tenant-refs db tenant-id :tenant/products)
+                     :let [p (d/entity db e)]]
+                 {:created-at (instant (:product/created-at p))
+                  :id         (:product/id p)
+                  :position   (:product/position p)
+                  :status     (some-> p :product/status name)
+                  :title      (:product/title p)}))}})
+
+(defn parse-range
+  "Reads `2026-01-01..2026-02-01` into instants at midnight UTC. Either side
+  may be left open. The end is exclusive."
+  [s]
+  (when-not (str/blank? s)
+    (let [[from to] (str/split s #"\.\." 2)
+          parse     #(when-not (str/blank? %)
+                       (.toInstant (.atStartOfDay (LocalDate/parse %) ZoneOffset/UTC)))]
+      {:from (parse from)
+       :to   (parse to)})))
+
+;;; ----------------------------------------------------------------------------
+;;; Formats
+
+(defn- csv-cell
+  [v]
+  (let [s (str v)]
+    (if (re-find #"[\",\r\n]" s)
+      (str "\"" (str/replace s "\"" "\"\"") "\"")
+      s)))
+
+(defn- write-csv!
+  [^Writer w columns rows]
+  (.write w (str (str/join "," (map name columns)) "\r\n"))
+  (doseq [row rows]
+    (.write w (str (str/join "," (map #(csv-cell (get row %)) columns)) "\r\n"))))
+
+(defn- write-json!
+  "Writes an array one element at a time, so it streams like the CSV."
+  [^Writer w columns rows]
+  (.write w "[")
+  (doseq [[i row] (map-indexed vector rows)]
+    (when (pos? i) (.write w ","))
+    (.write w (json/write-json-str (into {}
+                                         (map (fn [k]
+                                                (let [v (get row k)]
+                                                  [(name k) (if (uuid? v) (str v) v)])))
+                                         columns))))
+  (.write w "]"))
+
+(def formats
+  {"csv"  {:content-type "text/csv; charset=utf-8"
+           :write!       write-csv!}
+   "json" {:content-type "application/json; charset=utf-8"
+           :write!       write-json!}})
+
+(defn write!
+  [^Writer w dataset extension db tenant-id range]
+  (span/with-span! {:name ::write!}
+    (let [{:keys [columns rows]} (get datasets dataset)]
+      ((:write! (get formats extension)) w columns (rows db tenant-id range))
+      (.flush w))))
+
+;;; ----------------------------------------------------------------------------
+;;; Concurrency
+;;;
+;;; A tenant runs one export at a time. Exports are cheap per row but unbounded
+;;; in length, so this stops a script from tying up every worker.
+
+(defn acquire!
+  [exporter tenant-id]
+  (.add ^Set (:running exporter) tenant-id))
+
+(defn release!
+  [exporter tenant-id]
+  (.remove ^Set (:running exporter) tenant-id))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Exporter [running]
+  component/Lifecycle
+  (start [this]
+    (assoc this :running (ConcurrentHashMap/newKeySet)))
+  (stop [this]
+    (assoc this :running nil)))
+
+(defmethod print-method Exporter
+  [_ ^java.io.Writer w]
+  (.write w "#<Exporter>"))
+
+(defn make-exporter
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Exporter config))
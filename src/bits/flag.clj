@@ -0,0 +1,163 @@
+(ns bits.flag
+  (:require
+   [bits.cache :as cache]
+   [bits.clock :as clock]
+   [bits.postgres :as postgres]
+   [bits.postgres.feature-flag :as postgres.feature-flag]
+   [bits.spec]
+   [buddy.core.hash :as hash]
+   [clojure.spec.alpha :as s]
+   [com.stuartsierra.component :as component]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Evaluation
+;;;
+;;; A tenant override always wins. Otherwise a flag is on everywhere when
+;;; enabled, or for the tenants whose bucket falls under the rollout
+;;; percentage. Buckets hash the flag name too, so each flag rolls out to a
+;;; different slice of tenants.
+
+(defn bucket
+  "Stable bucket in [0, 100) for a tenant and flag."
+  [flag-name tenant-id]
+  (let [digest (hash/sha256 (str flag-name ":" tenant-id))]
+    (.intValue (.mod (BigInteger. 1 ^bytes digest) (BigInteger/valueOf 100)))))
+
+(defn evaluate
+  [flag override tenant-id]
+  (cond
+    (some? override)
+    override
+
+    (nil? flag)
+    false
+
+    (::postgres.feature-flag/enabled flag)
+    true
+
+    :else
+    (< (bucket (::postgres.feature-flag/name flag) tenant-id)
+       (::postgres.feature-flag/rollout-percent flag))))
+
+;;; Checks run on every request for flags like maintenance mode, so answers
+;;; are cached for a few seconds. Changes made through this instance show up
+;;; at once. Other instances pick them up when their entries expire.
+
+(defn- evaluate-row
+  [flagger tenant-id flag-name]
+  (let [row (postgres/execute-one! (postgres/replica (:postgres flagger))
+                                   {:select    [:feature-flags.name
+                                                :feature-flags.enabled
+                                                :feature-flags.rollout-percent
+                                                :feature-flag-tenants.enabled]
+                                    :from      [:feature-flags]
+                                    :left-join [:feature-flag-tenants
+                                                [:and
+                                                 [:= :feature-flag-tenants.flag-name :feature-flags.name]
+                                                 [:= :feature-flag-tenants.tenant-id tenant-id]]]
+                                    :where     [:= :feature-flags.name flag-name]})]
+    (evaluate (when row (select-keys row [::postgres.feature-flag/name
+                                          ::postgres.feature-flag/enabled
+                                          ::postgres.feature-flag/rollout-percent]))
+              (:bits.postgres.feature-flag-tenant/enabled row)
+              tenant-id)))
+
+(defn enabled?
+  "Unknown flags are off."
+  [flagger tenant-id flag-name]
+  (span/with-span! {:name ::enabled?}
+    (let [[result hit?] (cache/fetch (:cache flagger) [flag-name tenant-id]
+                                     #(evaluate-row flagger tenant-id flag-name))]
+      (span/add-span-data! {:attributes {:flag flag-name :enabled result :cached hit?}})
+      result)))
+
+;;; ----------------------------------------------------------------------------
+;;; Administration
+
+(defn list-flags
+  [flagger]
+  {:post [(s/valid? (s/coll-of ::postgres.feature-flag/persisted) %)]}
+  (span/with-span! {:name ::list-flags}
+    (postgres/execute! (:postgres flagger)
+                       {:select   [:name :enabled :rollout-percent :updated-at]
+                        :from     [:feature-flags]
+                        :order-by [:name]})))
+
+(defn list-overrides
+  [flagger]
+  {:post [(s/valid? (s/coll-of ::postgres.feature-flag/override) %)]}
+  (span/with-span! {:name ::list-overrides}
+    (postgres/execute! (:postgres flagger)
+                       {:select   [:flag-name :tenant-id :enabled]
+                        :from     [:feature-flag-tenants]
+                        :order-by [:flag-name :tenant-id]})))
+
+(defn override
+  "The tenant's override of the flag, or nil when it follows the default."
+  [flagger flag-name tenant-id]
+  (:bits.postgres.feature-flag-tenant/enabled
+   (postgres/execute-one! (:postgres flagger)
+                          {:select [:enabled]
+                           :from   [:feature-flag-tenants]
+                           :where  [:and
+                                    [:= :flag-name flag-name]
+                                    [:= :tenant-id tenant-id]]})))
+
+(defn save-flag!
+  [flagger {flag-name :name :keys [enabled rollout-percent]}]
+  {:pre [(string? flag-name) (boolean? enabled) (<= 0 rollout-percent 100)]}
+  (span/with-span! {:name ::save-flag!}
+    (let [now    (clock/now (:clock flagger))
+          result (postgres/execute-one! (:postgres flagger)
+                                        {:insert-into   :feature-flags
+                                         :values        [{:name            flag-name
+                                                          :enabled         enabled
+                                                          :rollout-percent rollout-percent}]
+                                         :on-conflict   [:name]
+                                         :do-update-set {:enabled         enabled
+                                                         :rollout-percent rollout-percent
+                                                         :updated-at      now}})]
+      (cache/clear! (:cache flagger))
+      result)))
+
+(defn set-override!
+  "Pins the flag on or off for one tenant. Passing nil removes the override so
+  the tenant follows the flag default again."
+  [flagger flag-name tenant-id enabled]
+  (span/with-span! {:name ::set-override!}
+    (let [result (if (nil? enabled)
+                   (postgres/execute-one! (:postgres flagger)
+                                          {:delete-from :feature-flag-tenants
+                                           :where       [:and
+                                                         [:= :flag-name flag-name]
+                                                         [:= :tenant-id tenant-id]]})
+                   (postgres/execute-one! (:postgres flagger)
+                                          {:insert-into   :feature-flag-tenants
+                                           :values        [{:flag-name flag-name
+                                                            :tenant-id tenant-id
+                                                            :enabled   enabled}]
+                                           :on-conflict   [:flag-name :tenant-id]
+                                           :do-update-set {:enabled    enabled
+                                                           :updated-at (clock/now (:clock flagger))}}))]
+      (cache/evict! (:cache flagger) [flag-name tenant-id])
+      result)))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Flagger [cache clock maximum-size postgres ttl-seconds]
+  component/Lifecycle
+  (start [this]
+    (assoc this :cache (cache/make-cache this)))
+  (stop [this]
+    (assoc this :cache nil)))
+
+(defmethod print-method Flagger
+  [_ ^java.io.Writer w]
+  (.write w "#<Flagger>"))
+
+(defn make-flagger
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Flagger config))
@@ -344,18 +344,19 @@
          :or   {rows 3}}                    attrs
         success?                            (:success? f)
         hint-id                             (str field-id "-hint")
-        textarea-attrs                      (-> (dissoc attrs :label)
+        ;; Textareas hold their value as content, not an attribute.
+        content                             (if (and value (not success?)) value (:value attrs))
+        textarea-attrs                      (-> (dissoc attrs :label :value)
                                                 (assoc :id field-id
                                                        :name field-id
                                                        :rows rows)
                                                 (cond->
-                                                 (and value (not success?)) (assoc :value value)
-                                                 (and used (not success?))  (assoc :data-used "true")
-                                                 (= status ::error)         (assoc :aria-invalid "true")
-                                                 message                    (assoc :aria-describedby hint-id)))]
+                                                 (and used (not success?)) (assoc :data-used "true")
+                                                 (= status ::error)        (assoc :aria-invalid "true")
+                                                 message                   (assoc :aria-describedby hint-id)))]
     [:div
      [:label {:for field-id :class label-classes} label]
-     [:textarea (tw/with-defaults textarea-attrs (conj textarea-base-classes ring bg shadow outline))]
+     [:textarea (tw/with-defaults textarea-attrs (conj textarea-base-classes ring bg shadow outline)) content]
      (hint field-id status message)]))
 
 ;;; ----------------------------------------------------------------------------
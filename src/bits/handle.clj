@@ -0,0 +1,88 @@
+(ns bits.handle
+  (:require
+   [bits.anomaly :as anom]
+   [bits.locale :refer [tru]]
+   [clojure.string :as str]
+   [datomic.api :as d])
+  (:import
+   (java.text Normalizer Normalizer$Form)))
+
+;;; ----------------------------------------------------------------------------
+;;; Policy
+;;;
+;;; Handles double as subdomains, so they're limited to what a DNS label can
+;;; hold and kept clear of names we route ourselves or that look official.
+;;; Callers may pass their own policy, e.g. with extra reserved words from
+;;; config.
+
+(def default-policy
+  {:max-length 30
+   :min-length 3
+   :reserved   #{"about" "account" "admin" "api" "app" "assets" "billing" "bits"
+                 "blog" "cdn" "dashboard" "dev" "docs" "help" "login" "mail"
+                 "platform" "root" "security" "signup" "sso" "staging" "static"
+                 "status" "support" "system" "www"}})
+
+;;; ----------------------------------------------------------------------------
+;;; Normalization
+
+(defn normalize
+  "Folds compatibility forms (fullwidth letters, ligatures) and case, so what
+  someone types becomes the handle we'd store."
+  [s]
+  (-> (Normalizer/normalize (str/trim s) Normalizer$Form/NFKC)
+      (str/lower-case)))
+
+(def ^:private lookalikes
+  [["rn" "m"]
+   ["vv" "w"]
+   ["0" "o"]
+   ["1" "l"]
+   ["i" "l"]
+   ["5" "s"]
+   ["-" ""]])
+
+(defn skeleton
+  "Collapses characters that look alike, so `rnilly` and `milly` compare
+  equal. Only used for comparison, never stored."
+  [handle]
+  (reduce (fn [s [from to]] (str/replace s from to))
+          (normalize handle)
+          lookalikes))
+
+;;; ----------------------------------------------------------------------------
+;;; Checks
+
+(defn check
+  "Returns the normalized handle, or an anomaly saying why it can't be used.
+  Existing handles are compared by skeleton to stop impersonation."
+  ([handle]
+   (check default-policy handle []))
+  ([{:keys [max-length min-length reserved]} handle existing]
+   (let [h        (normalize handle)
+         taken    (into #{} (map skeleton) existing)
+         reserved (into #{} (map skeleton) reserved)]
+     (cond
+       (< (count h) min-length)
+       (anom/incorrect {::anom/message (tru "Handles need at least {0} characters." min-length)})
+
+       (< max-length (count h))
+       (anom/incorrect {::anom/message (tru "Handles can''t be longer than {0} characters." max-length)})
+
+       (not (re-matches #"[a-z0-9](?:[a-z0-9]|-(?!-))*[a-z0-9]" h))
+       (anom/incorrect {::anom/message (tru "Use lowercase letters, numbers and single hyphens, starting and ending with a letter or number.")})
+
+       (contains? reserved (skeleton h))
+       (anom/forbidden {::anom/message (tru "That handle is reserved.")})
+
+       (contains? taken (skeleton h))
+       (anom/conflict {::anom/message (tru "That handle is taken.")})
+
+       :else
+       h))))
+
+(defn existing-handles
+  [db]
+  (d/q '[:find [?handle ...]
+         :where [_ :creator/handle ?handle]]
+       db))
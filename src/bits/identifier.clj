@@ -1,4 +1,6 @@
 (ns bits.identifier
+  (:require
+   [clojure.string :as str])
   (:import
    (java.util UUID)))
 
@@ -26,8 +28,12 @@
   (.subtract (.shiftLeft BigInteger/ONE 64) BigInteger/ONE))
 
 (defn decode
+  "Throws unless s is a 128-bit number, so no two strings decode to the same
+  UUID."
   [^String s]
   (let [n   (BigInteger. s radix)
+        _   (when (or (neg? (.signum n)) (< 128 (.bitLength n)))
+              (throw (ex-info "Not a 128-bit identifier?!" {:s s})))
         lo  (.and n mask-64)
         hi  (.shiftRight n 64)
         lsb (.longValue lo)
@@ -42,3 +48,50 @@
       36 (UUID/fromString s)
       nil)
     (catch Exception _ nil)))
+
+;;; ----------------------------------------------------------------------------
+;;; Prefixed public IDs
+;;;
+;;; IDs that leave the system carry a prefix naming their kind, so a key ID
+;;; pasted where a webhook ID belongs is rejected instead of silently matching
+;;; nothing. Only the encoded form is accepted; raw UUIDs are not public IDs.
+
+(def prefixes
+  {:api-key       "key"
+   :comment       "cmt"
+   :discount      "dsc"
+   :event         "evt"
+   :membership    "mem"
+   :notification  "ntf"
+   :option        "opt"
+   :option-value  "optv"
+   :order         "ord"
+   :page          "page"
+   :plugin        "plg"
+   :pricing-rule  "prr"
+   :product       "prod"
+   :redirect      "rdr"
+   :release       "rel"
+   :report        "rpt"
+   :review        "rev"
+   :shipping-rate "shr"
+   :shipping-zone "shz"
+   :tenant        "tnt"
+   :user          "usr"
+   :variant       "var"
+   :webhook       "whe"})
+
+(defn prefixed
+  [kind ^UUID uuid]
+  {:pre [(contains? prefixes kind) (uuid? uuid)]}
+  (str (prefixes kind) "_" (encode uuid)))
+
+(defn parse-prefixed
+  [kind s]
+  {:pre [(contains? prefixes kind)]}
+  (when (string? s)
+    (let [[prefix body] (str/split s #"_" 2)]
+      (when (and (= (prefixes kind) prefix) (= encoded-length (count body)))
+        (try
+          (decode (str/lower-case body))
+          (catch Exception _ nil))))))
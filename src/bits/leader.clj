@@ -0,0 +1,152 @@
+(ns bits.leader
+  "Picks one instance to run the work that must only happen once per cluster.
+
+  Each instance keeps a connection of its own and tries for a session-level
+  advisory lock named by `:lock-name`. Whoever gets it leads for as long as
+  that connection lives. Postgres drops the lock along with the session, so
+  a leader that dies or loses the database can't hold on to it. The lease is
+  renewed every `:renew-seconds` by checking the connection. Followers try
+  for the lock just as often.
+
+  Scheduled work that must run once asks `leader?` before doing anything."
+  (:require
+   [bits.postgres :as postgres]
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [com.stuartsierra.component :as component]
+   [io.pedestal.log :as log]
+   [next.jdbc :as jdbc]
+   [steffan-westcott.clj-otel.api.metrics.instrument :as instrument]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.sql Connection)
+   (java.util.concurrent Executors ScheduledExecutorService TimeUnit)))
+
+;;; ----------------------------------------------------------------------------
+;;; Leadership
+
+(defn leader?
+  [leader]
+  (boolean (some-> (:leading leader) deref)))
+
+(defn watch!
+  "Calls `(f leading?)` whenever this instance gains or loses leadership."
+  [leader k f]
+  (add-watch (:leading leader) k (fn [_ _ was is]
+                                   (when (not= was is)
+                                     (f is)))))
+
+(defn unwatch!
+  [leader k]
+  (remove-watch (:leading leader) k))
+
+(defn- lead!
+  [leader leading?]
+  (when (not= leading? @(:leading leader))
+    (log/info :msg "Leadership changed." :lock-name (:lock-name leader) :leading? leading?)
+    (instrument/add! (:leading-gauge leader)
+                     {:value      (if leading? 1 -1)
+                      :attributes {"lock" (:lock-name leader)}})
+    (reset! (:leading leader) leading?)))
+
+(defn- try-lock!
+  [^Connection conn lock-name]
+  (:locked (jdbc/execute-one! conn ["SELECT pg_try_advisory_lock(hashtext(?)) AS locked" lock-name])))
+
+(defn- release!
+  "Gives the lock back before the connection returns to the pool, where
+  another caller would otherwise inherit it."
+  [^Connection conn lock-name]
+  (try
+    (when (.isValid conn 1)
+      (jdbc/execute-one! conn ["SELECT pg_advisory_unlock(hashtext(?))" lock-name]))
+    (finally
+      (.close conn))))
+
+(defn with-lock
+  "Runs `(f)` holding lock-name on postgres's database and returns what it
+  does, or nil without running it when another instance holds the lock. For
+  work that must not overlap on a database no leader is picked for, such as
+  a region's."
+  [postgres lock-name f]
+  (with-open [^Connection conn (postgres/get-connection (:datasource postgres))]
+    (when (try-lock! conn lock-name)
+      (try
+        (f)
+        (finally
+          (jdbc/execute-one! conn ["SELECT pg_advisory_unlock(hashtext(?))" lock-name]))))))
+
+(defn renew!
+  "Renews the lease when leading, and otherwise tries to take it. Returns
+  whether this instance leads."
+  [leader]
+  (span/with-span! {:name ::renew!}
+    (let [{:keys [connection lock-name postgres]} leader]
+      (try
+        (let [^Connection conn @connection]
+          (when (and conn (not (.isValid conn 1)))
+            (lead! leader false)
+            (reset! connection nil)
+            (.close conn)))
+        (when-not (leader? leader)
+          (let [conn (or @connection
+                         (reset! connection (postgres/get-connection (:datasource postgres))))]
+            (lead! leader (try-lock! conn lock-name))))
+        (catch Exception ex
+          ;; An exception escaping a scheduled task cancels all future runs.
+          (log/warn :msg "Failed to renew leadership?!" :lock-name lock-name :exception ex)
+          (span/add-exception! ex {:escaping? false})
+          (lead! leader false)))
+      (leader? leader))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Leader [connection
+                   ^ScheduledExecutorService executor
+                   leading
+                   leading-gauge
+                   lock-name
+                   postgres
+                   renew-seconds]
+  component/Lifecycle
+  (start [this]
+    (span/with-span! {:name ::start-leader}
+      (let [executor (Executors/newSingleThreadScheduledExecutor)
+            this     (assoc this
+                            :connection    (atom nil)
+                            :executor      executor
+                            :leading       (atom false)
+                            :leading-gauge (instrument/instrument
+                                            {:name            "leader.leading"
+                                             :instrument-type :up-down-counter
+                                             :unit            "{instance}"
+                                             :description     "Whether this instance leads background work"}))]
+        ;; Settled before anything that depends on it starts.
+        (renew! this)
+        (.scheduleWithFixedDelay executor
+                                 ^Runnable #(renew! this)
+                                 renew-seconds renew-seconds TimeUnit/SECONDS)
+        this)))
+
+  (stop [this]
+    (span/with-span! {:name ::stop-leader}
+      (when executor
+        (.shutdown executor)
+        (when-not (.awaitTermination executor 5 TimeUnit/SECONDS)
+          (.shutdownNow executor)))
+      (when-let [conn (some-> connection deref)]
+        (lead! this false)
+        (release! conn lock-name))
+      (assoc this :connection nil :executor nil :leading nil :leading-gauge nil))))
+
+(defmethod print-method Leader
+  [leader ^java.io.Writer w]
+  (.write w (format "#<Leader lock-name=%s leading=%s>"
+                    (:lock-name leader)
+                    (leader? leader))))
+
+(defn make-leader
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Leader config))
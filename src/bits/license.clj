@@ -0,0 +1,44 @@
+(ns bits.license
+  "What buyers may do with what they bought.
+
+  A product is licensed under one of a few standard presets, or under custom
+  terms the tenant writes. Either way the license has a hash, and line items
+  keep the hash they were sold under, so a buyer can always show which terms
+  they agreed to."
+  (:require
+   [bits.crypto :as crypto]))
+
+(def presets
+  {:product.license/all-rights-reserved {:name "All rights reserved"}
+   :product.license/cc-by               {:name "CC BY 4.0"
+                                         :url  "https://creativecommons.org/licenses/by/4.0/"}
+   :product.license/cc-by-nc            {:name "CC BY-NC 4.0"
+                                         :url  "https://creativecommons.org/licenses/by-nc/4.0/"}
+   :product.license/cc-by-sa            {:name "CC BY-SA 4.0"
+                                         :url  "https://creativecommons.org/licenses/by-sa/4.0/"}
+   :product.license/cc0                 {:name "CC0 1.0"
+                                         :url  "https://creativecommons.org/publicdomain/zero/1.0/"}})
+
+(def licenses
+  (conj (set (keys presets)) :product.license/custom))
+
+(def default
+  "Products without a license reserve every right."
+  :product.license/all-rights-reserved)
+
+(defn terms-hash
+  "Hex SHA-256 of the license and its custom terms. Presets have no terms, so
+  every product under the same preset shares a hash."
+  [license terms]
+  (crypto/sha256 (str (name license) "\n" (or terms ""))))
+
+(defn describe
+  "The product's license as buyers see it."
+  [product]
+  (let [license (or (:product/license product) default)
+        terms   (when (= :product.license/custom license) (:product/license-terms product))]
+    (merge {:id    (name license)
+            :hash  (terms-hash license terms)
+            :name  "Custom terms"
+            :terms terms}
+           (get presets license))))
@@ -49,6 +49,11 @@
   `(binding [i18n/*user-locale* ~locale]
      ~@body))
 
+(defn current-locale
+  "The locale bound for this request."
+  ^Locale []
+  i18n/*user-locale*)
+
 (defmacro tru
   [format-string & args]
   `(i18n/tru ~format-string ~@args))
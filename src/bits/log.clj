@@ -0,0 +1,60 @@
+(ns bits.log
+  "Log events as JSON with personal data masked.
+
+  Pedestal formats every log event map with the function named by the
+  `PEDESTAL_LOG_FORMATTER` environment variable, or `pr-str` when it isn't
+  set. Production sets it to `bits.log/format-event`.
+
+  Redaction works from an allow-list. Values under the keys below go out as
+  they are. Secrets are masked whole. In every other string, email addresses
+  and bearer tokens are masked, so an email caught in an exception message
+  doesn't leak."
+  (:require
+   [charred.api :as json]
+   [clojure.string :as str]
+   [clojure.walk :as walk]))
+
+(def allowed-keys
+  #{:action :lock-name :msg :path :request-id :status :tenant-id :topic :trigger :user-id})
+
+(def secret-keys
+  #{:authorization :cookie :password :secret :session-token :token})
+
+(def ^:private email-pattern
+  #"([A-Za-z0-9._%+-])[A-Za-z0-9._%+-]*@([A-Za-z0-9.-]+\.[A-Za-z]{2,})")
+
+(def ^:private bearer-pattern
+  #"(?i)(bearer\s+)[A-Za-z0-9._~+/=-]+")
+
+(defn redact-string
+  [s]
+  (-> s
+      (str/replace email-pattern "$1***@$2")
+      (str/replace bearer-pattern "$1[redacted]")))
+
+(defn redact
+  "The event with secrets and personal data masked."
+  [event]
+  (walk/prewalk
+   (fn [x]
+     (if (map-entry? x)
+       (let [[k v] x]
+         (cond
+           (contains? allowed-keys k) x
+           (contains? secret-keys k)  [k (when (some? v) "[redacted]")]
+           (string? v)                [k (redact-string v)]
+           :else                      x))
+       x))
+   event))
+
+(defn- encodable
+  "Values JSON can carry. Anything else, like exceptions, is printed."
+  [x]
+  (cond
+    (keyword? x)                                      (subs (str x) 1)
+    ((some-fn nil? string? number? boolean? coll?) x) x
+    :else                                             (redact-string (str x))))
+
+(defn format-event
+  [event]
+  (json/write-json-str (walk/postwalk encodable (redact event))))
@@ -0,0 +1,142 @@
+(ns bits.log.tail
+  "The most recent log lines, kept in memory for operators to watch.
+
+  The tail attaches a Logback appender to the root logger and keeps the last
+  `capacity` events it sees in a ring buffer. Events are redacted with
+  `bits.log/redact` before they're kept, whichever formatter Pedestal was
+  configured with, so nothing the JSON logs would mask reaches a browser.
+
+  Watchers re-render on the tail's own refresh signal rather than the
+  service's, throttled to one every `refresh-ms`, so a chatty logger doesn't
+  repaint every open page."
+  (:require
+   [bits.log :as log]
+   [bits.morph :as morph]
+   [bits.spec]
+   [charred.api :as json]
+   [clojure.core.async :as a]
+   [clojure.edn :as edn]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [com.stuartsierra.component :as component])
+  (:import
+   (ch.qos.logback.classic Logger LoggerContext)
+   (ch.qos.logback.classic.spi ILoggingEvent)
+   (ch.qos.logback.core AppenderBase)
+   (clojure.lang PersistentQueue)
+   (java.time Instant)
+   (org.slf4j LoggerFactory)))
+
+;;; ----------------------------------------------------------------------------
+;;; Events
+
+(def levels
+  [:trace :debug :info :warn :error])
+
+(def ^:private level-rank
+  (zipmap levels (range)))
+
+(defn- parse-message
+  "Pedestal logs maps, printed as EDN or JSON depending on the formatter.
+  Returns the map, or nil for anything else."
+  [^String message]
+  (try
+    (cond
+      (str/starts-with? message "{\"") (json/read-json message :key-fn keyword)
+      (str/starts-with? message "{")   (edn/read-string {:default tagged-literal} message))
+    (catch Exception _)))
+
+(defn- ->entry
+  [^ILoggingEvent e]
+  (let [message (.getFormattedMessage e)
+        data    (some-> (parse-message message) log/redact)]
+    {:at        (Instant/ofEpochMilli (.getTimeStamp e))
+     :level     (keyword (str/lower-case (str (.getLevel e))))
+     :logger    (.getLoggerName e)
+     :message   (if (map? data)
+                  (str (or (:msg data) (pr-str (dissoc data :msg))))
+                  (log/redact-string (str message)))
+     :data      (when (map? data) (dissoc data :msg))
+     :tenant-id (some-> (:tenant-id data) str)}))
+
+;;; ----------------------------------------------------------------------------
+;;; Reading
+
+(defn matches?
+  "Whether entry passes the filter. Level is a minimum; logger is a prefix of
+  the logger name, so `bits.outbox` matches `bits.outbox.relay`."
+  [{:keys [level logger tenant-id]} entry]
+  (and (or (nil? level)
+           (>= (level-rank (:level entry) 0) (level-rank level 0)))
+       (or (str/blank? logger)
+           (str/starts-with? (:logger entry) logger))
+       (or (str/blank? tenant-id)
+           (= tenant-id (:tenant-id entry)))))
+
+(defn entries
+  "Kept entries passing the filter, newest first."
+  ([tail]
+   (entries tail {}))
+  ([tail criteria]
+   (into []
+         (filter #(matches? criteria %))
+         (rseq (vec @(:!buffer tail))))))
+
+(defn refresh-mult
+  "Mult signalling that new entries were kept."
+  [tail]
+  (:refresh-mult tail))
+
+;;; ----------------------------------------------------------------------------
+;;; Appending
+
+(defn append!
+  [{:keys [!buffer <appended capacity]} entry]
+  (swap! !buffer (fn [q]
+                   (let [q (conj q entry)]
+                     (if (< capacity (count q)) (pop q) q))))
+  (a/offer! <appended :appended))
+
+(defn- appender
+  [tail]
+  (proxy [AppenderBase] []
+    (append [e]
+      (append! tail (->entry e)))))
+
+(defn- root-logger
+  ^Logger []
+  (.getLogger ^LoggerContext (LoggerFactory/getILoggerFactory) Logger/ROOT_LOGGER_NAME))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Tail [!buffer <appended appender capacity refresh-ms refresh-mult]
+  component/Lifecycle
+  (start [this]
+    (let [<appended (a/chan (a/sliding-buffer 1))
+          tail      (assoc this
+                           :!buffer      (atom PersistentQueue/EMPTY)
+                           :<appended    <appended
+                           :refresh-mult (a/mult (morph/throttle <appended refresh-ms)))
+          appender  (doto ^AppenderBase (appender tail)
+                      (.setContext (LoggerFactory/getILoggerFactory))
+                      (.setName "bits.log.tail")
+                      (.start))]
+      (.addAppender (root-logger) appender)
+      (assoc tail :appender appender)))
+
+  (stop [this]
+    (when appender
+      (.detachAppender (root-logger) ^AppenderBase appender)
+      (.stop ^AppenderBase appender))
+    (some-> <appended a/close!)
+    (assoc this :!buffer nil :<appended nil :appender nil :refresh-mult nil)))
+
+(defmethod print-method Tail
+  [_ ^java.io.Writer w]
+  (.write w "#<Tail>"))
+
+(defn make-tail
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Tail config))
@@ -0,0 +1,32 @@
+(ns bits.maintenance
+  "Taking creator realms offline.
+
+  Maintenance is the `maintenance` feature flag, so the flags page already
+  covers every way of switching it: on for every tenant, rolled out to a
+  share of them, or overridden for one. Tenant admins switch their own
+  storefront through the same override. The platform realm never goes into
+  maintenance, so there's always somewhere to switch it off."
+  (:require
+   [bits.flag :as flag]))
+
+(def flag-name
+  "maintenance")
+
+(def retry-after-seconds
+  300)
+
+(defn on?
+  [flagger tenant-id]
+  (flag/enabled? flagger tenant-id flag-name))
+
+(defn switched-off?
+  "Whether the tenant took their own storefront offline, as opposed to being
+  caught by platform-wide maintenance."
+  [flagger tenant-id]
+  (true? (flag/override flagger flag-name tenant-id)))
+
+(defn switch!
+  "Takes the tenant's storefront offline, or brings it back. Switching it back
+  on removes the override, so platform-wide maintenance still applies."
+  [flagger tenant-id offline?]
+  (flag/set-override! flagger flag-name tenant-id (when offline? true)))
@@ -0,0 +1,100 @@
+(ns bits.meta
+  "Search and link preview metadata.
+
+  Pages describe themselves in `:bits/page` with `:page/title` and optionally
+  `:page/description`, `:page/image` and `:page/type`. The layout turns those
+  into meta tags, and pages without an image point at a card drawn on the fly
+  from their title."
+  (:require
+   [bits.request :as request]
+   [clojure.string :as str])
+  (:import
+   (java.awt Color Font GradientPaint RenderingHints)
+   (java.awt.image BufferedImage)
+   (java.io ByteArrayOutputStream)
+   (javax.imageio ImageIO)))
+
+;;; ----------------------------------------------------------------------------
+;;; Tags
+
+(def ^:private max-description
+  200)
+
+(defn- truncate
+  [s n]
+  (if (< n (count s))
+    (str (str/trimr (subs s 0 (dec n))) "…")
+    s))
+
+(defn- absolute
+  [request url]
+  (if (str/starts-with? url "/")
+    (str (request/origin request) url)
+    url))
+
+(defn tags
+  [request]
+  (let [{:page/keys [description image title type]} (:bits/page request)
+        description                                 (some-> description str/trim not-empty (truncate max-description))]
+    (list
+     (when description
+       [:meta {:name "description" :content description}])
+     [:meta {:property "og:title" :content (or title "Bits")}]
+     (when description
+       [:meta {:property "og:description" :content description}])
+     [:meta {:property "og:type" :content (or type "website")}]
+     [:meta {:property "og:url" :content (str (request/origin request) (:uri request))}]
+     (when image
+       (list
+        [:meta {:property "og:image" :content (absolute request image)}]
+        [:meta {:property "og:image:width" :content "1200"}]
+        [:meta {:property "og:image:height" :content "630"}]))
+     [:meta {:name "twitter:card" :content (if image "summary_large_image" "summary")}])))
+
+;;; ----------------------------------------------------------------------------
+;;; Cards
+;;;
+;;; Drawn with Java2D rather than rasterising SVG, which would need a renderer
+;;; we don't otherwise ship. Titles wrap onto at most three lines.
+
+(def ^:private card-width 1200)
+(def ^:private card-height 630)
+(def ^:private card-margin 80)
+
+(defn- wrap-lines
+  [^java.awt.FontMetrics metrics text width]
+  (reduce (fn [lines word]
+            (let [line      (peek lines)
+                  candidate (str line " " word)]
+              (if (or (empty? lines) (< width (.stringWidth metrics candidate)))
+                (conj lines word)
+                (conj (pop lines) candidate))))
+          []
+          (str/split (str/trim text) #"\s+")))
+
+(defn card
+  "Renders a 1200×630 PNG preview card."
+  ^bytes [{:keys [subtitle title]}]
+  (let [image (BufferedImage. card-width card-height BufferedImage/TYPE_INT_RGB)
+        g     (.createGraphics image)
+        out   (ByteArrayOutputStream.)]
+    (try
+      (doto g
+        (.setRenderingHint RenderingHints/KEY_ANTIALIASING RenderingHints/VALUE_ANTIALIAS_ON)
+        (.setRenderingHint RenderingHints/KEY_TEXT_ANTIALIASING RenderingHints/VALUE_TEXT_ANTIALIAS_ON)
+        (.setPaint (GradientPaint. 0 0 (Color. 0x18181b) card-width card-height (Color. 0x3f3f46)))
+        (.fillRect 0 0 card-width card-height)
+        (.setColor Color/WHITE)
+        (.setFont (Font. Font/SANS_SERIF Font/BOLD 72)))
+      (let [lines (take 3 (wrap-lines (.getFontMetrics g) (or title "Bits") (- card-width (* 2 card-margin))))]
+        (doseq [[i line] (map-indexed vector lines)]
+          (.drawString g ^String line (int card-margin) (int (+ 220 (* i 90))))))
+      (when subtitle
+        (doto g
+          (.setColor (Color. 0xa1a1aa))
+          (.setFont (Font. Font/SANS_SERIF Font/PLAIN 36))
+          (.drawString ^String subtitle (int card-margin) (int (- card-height card-margin)))))
+      (ImageIO/write image "png" out)
+      (.toByteArray out)
+      (finally
+        (.dispose g)))))
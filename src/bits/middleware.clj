@@ -1,19 +1,64 @@
 (ns bits.middleware
   (:require
+   [bits.anomaly :as anom]
    [bits.asset :as asset]
+   [bits.auth.api-key :as api-key]
+   [bits.auth.role :as role]
    [bits.crypto :as crypto]
    [bits.csp :as csp]
    [bits.datomic :as datomic]
+   [bits.environment :as environment]
    [bits.locale :as locale]
+   [bits.maintenance :as maintenance]
+   [bits.postgres :as postgres]
+   [bits.realm :as realm]
+   [bits.redirect :as redirect]
    [bits.request :as request]
+   [bits.response]
    [bits.session :as session]
+   [bits.shadow :as shadow]
    [buddy.core.bytes :as buddy.bytes]
    [clojure.java.io :as io]
    [clojure.string :as str]
    [datomic.api :as d]
+   [io.pedestal.log :as log]
+   [ring.middleware.multipart-params :as multipart-params]
+   [ring.util.codec :as codec]
    [ring.util.response :as response]
    [steffan-westcott.clj-otel.api.trace.span :as span]))
 
+;;; ----------------------------------------------------------------------------
+;;; Request ID
+
+(def ^:private request-id-pattern
+  #"[A-Za-z0-9._-]{8,64}")
+
+(defn wrap-request-id
+  "Keeps the `X-Request-Id` our proxy sent, or makes one up when it's missing or
+  looks wrong, then ties logs, the server span and the response to it."
+  [handler]
+  (fn [request]
+    (let [given (response/get-header request "x-request-id")
+          id    (if (and given (re-matches request-id-pattern given))
+                  given
+                  (str (random-uuid)))]
+      (span/add-span-data! {:attributes {:request-id id}})
+      (log/with-context {:request-id id}
+        (some-> (handler (assoc request ::request/id id))
+                (assoc-in [:headers "x-request-id"] id))))))
+
+(defn wrap-log-context
+  "Adds the tenant and signed-in user to everything logged while handling the
+  request. Goes after `wrap-user`."
+  [handler]
+  (fn [request]
+    (let [tenant-id (get-in request [:session/realm :tenant/id])
+          user-id   (get-in request [:session/user :user/id])]
+      (log/with-context (cond-> {}
+                          tenant-id (assoc :tenant-id tenant-id)
+                          user-id   (assoc :user-id user-id))
+        (handler request)))))
+
 ;;; ----------------------------------------------------------------------------
 ;;; State injection
 
@@ -30,15 +75,41 @@
   {:post [(some? %)]}
   (get-in request [::state k]))
 
+(defn request->activities       [request] (get-state request :activities))
+(defn request->analytics        [request] (get-state request :analytics))
+(defn request->api-keys         [request] (get-state request :api-keys))
 (defn request->buster           [request] (get-state request :buster))
+(defn request->clock            [request] (get-state request :clock))
+(defn request->comments         [request] (get-state request :comments))
 (defn request->csrf-cookie-name [request] (get-state request :csrf-cookie-name))
 (defn request->datomic          [request] (get-state request :datomic))
+(defn request->discounts        [request] (get-state request :discounts))
+(defn request->environment      [request] (get-state request :environment))
+(defn request->events           [request] (get-state request :events))
+(defn request->exports          [request] (get-state request :exports))
+(defn request->flags            [request] (get-state request :flags))
 (defn request->keymaster        [request] (get-state request :keymaster))
+(defn request->log-tail         [request] (get-state request :log-tail))
+(defn request->moderator        [request] (get-state request :moderator))
+(defn request->notifications    [request] (get-state request :notifications))
+(defn request->oidc             [request] (get-state request :oidc))
 (defn request->platform-domain  [request] (get-state request :platform-domain))
+(defn request->plugins          [request] (get-state request :plugins))
 (defn request->postgres         [request] (get-state request :postgres))
+(defn request->pricing          [request] (get-state request :pricing))
+(defn request->projector        [request] (get-state request :projector))
 (defn request->randomizer       [request] (get-state request :randomizer))
 (defn request->realms           [request] (get-state request :realms))
+(defn request->redirects        [request] (get-state request :redirects))
+(defn request->resolver         [request] (get-state request :resolver))
+(defn request->retention        [request] (get-state request :retention))
+(defn request->reviews          [request] (get-state request :reviews))
 (defn request->session-store    [request] (get-state request :session-store))
+(defn request->shadow           [request] (get-state request :shadow))
+(defn request->shipping         [request] (get-state request :shipping))
+(defn request->sites            [request] (get-state request :sites))
+(defn request->webhooks         [request] (get-state request :webhooks))
+(defn request->wishlists        [request] (get-state request :wishlists))
 
 (defn request->state
   [request]
@@ -114,7 +185,8 @@
   [handler]
   (fn [request]
     (let [db      (request->db request)
-          user-id (get-in request [:session :user/id])
+          user-id (or (get-in request [::api-key ::api-key/user-id])
+                      (get-in request [:session :user/id]))
           user    (when (some? user-id)
                     (d/q '[:find (pull ?u [:user/id]) .
                            :in $ ?id
@@ -126,44 +198,128 @@
 ;;; ----------------------------------------------------------------------------
 ;;; Realm
 
-(def ^:private realm-by-domain-query
-  '[:find (pull ?r [:creator/avatar-url
-                    :creator/banner-url
-                    :creator/bio
-                    :creator/display-name
-                    :creator/handle
-                    :tenant/id
-                    {:creator/links [:link/icon
-                                     :link/label
-                                     :link/url]}
-                    {:creator/posts [:post/created-at
-                                     :post/id
-                                     :post/image-url
-                                     :post/text]}]) .
-    :in $ ?domain
-    :where
-    [?d :domain/name ?domain]
-    [?r :tenant/domains ?d]])
-
 (defn- platform?
   [request]
   (= (request/domain request) (request->platform-domain request)))
 
+(defn- shadow-realm
+  "Checks the cached lookup against an uncached one when the tenant it found
+  shadows realm resolution."
+  [request db domain found]
+  (let [shadow    (request->shadow request)
+        tenant-id (:tenant/id found)]
+    (when (shadow/shadowing? shadow tenant-id :realm)
+      (shadow/compare! shadow :realm tenant-id
+                       tenant-id
+                       (:tenant/id (realm/lookup-uncached db domain))))))
+
 (defn wrap-realm
   [handler realms]
   (fn [request]
-    (let [{creator-realm  :realm.type/creator
-           platform-realm :realm.type/platform
-           unknown-realm  :realm.type/unknown} realms]
+    (let [{creator-realm   :realm.type/creator
+           platform-realm  :realm.type/platform
+           suspended-realm :realm.type/suspended
+           unknown-realm   :realm.type/unknown} realms]
       (if (platform? request)
         (handler (assoc request :session/realm platform-realm))
         (let [db     (request->db request)
               domain (request/domain request)
-              realm  (or (some->> (d/q realm-by-domain-query db domain)
-                                  (merge creator-realm))
-                         unknown-realm)]
+              found  (realm/lookup (request->resolver request) db domain)
+              _      (shadow-realm request db domain found)
+              realm  (cond
+                       ;; Suspended tenants keep none of their identity, so
+                       ;; nothing scoped to them can be read or written.
+                       (:tenant/suspended-at found) suspended-realm
+                       found                        (merge creator-realm found)
+                       :else                        unknown-realm)]
           (handler (assoc request :session/realm realm)))))))
 
+;;; ----------------------------------------------------------------------------
+;;; Residency
+;;;
+;;; Tenants tagged with a region are only served by nodes in that region, and
+;;; their requests reach Postgres through its database: both the request's
+;;; postgres and every component in the request's state are pointed at it,
+;;; the session store included. See bits.postgres for background work.
+
+(defn wrap-residency
+  "Refuses requests for tenants in another region with a 421, so the proxy
+  can send them where they belong, and routes the rest to their region's
+  database. Goes after `wrap-realm`."
+  [handler]
+  (fn [request]
+    (let [postgres (request->postgres request)
+          region   (get-in request [:session/realm :tenant/region])]
+      (cond
+        (nil? region)
+        (handler request)
+
+        (not (postgres/serves? postgres region))
+        (do
+          (log/warn :msg       "Refusing a request for a tenant in another region."
+                    :region    region
+                    :tenant-id (get-in request [:session/realm :tenant/id]))
+          bits.response/misdirected-response)
+
+        :else
+        (let [routed (postgres/regional postgres region)]
+          (handler (cond-> request
+                     (not (identical? routed postgres)) (update ::state postgres/repoint routed))))))))
+
+;;; ----------------------------------------------------------------------------
+;;; API keys
+
+(defn request->api-key
+  [request]
+  (::api-key request))
+
+(defn wrap-api-key
+  "Authenticates bearer tokens against the resolved realm's tenant. Requests
+  without an `Authorization` header pass through to cookie sessions."
+  [handler]
+  (fn [request]
+    (if-let [token (api-key/bearer-token request)]
+      (let [registry  (request->api-keys request)
+            tenant-id (get-in request [:session/realm :tenant/id])
+            result    (api-key/authenticate registry tenant-id token)]
+        (if (anom/anomaly? result)
+          (if (= ::anom/busy (::anom/category result))
+            (bits.response/anomaly-response request result)
+            bits.response/unauthorized-response)
+          (handler (assoc request ::api-key result))))
+      (handler request))))
+
+(def scope-middleware
+  {:name    ::scope
+   :compile (fn [route-data _opts]
+              (when-let [scopes (:bits/scopes route-data)]
+                (fn [handler]
+                  (fn [request]
+                    (let [api-key (request->api-key request)]
+                      (if (or (nil? api-key) (api-key/scoped? api-key scopes))
+                        (handler request)
+                        bits.response/forbidden-response))))))})
+
+;;; ----------------------------------------------------------------------------
+;;; Transactions
+;;;
+;;; Actions run in one Postgres transaction, as can any route handler wrapped
+;;; in `wrap-transaction`. Anything that reaches Postgres through
+;;; `request->postgres`, or through a component in the request's state, joins
+;;; it, as each one is repointed at the transaction.
+;;; Whatever the handler wrote commits when it answers, and rolls back when it
+;;; throws or answers with an error or an anomaly. Actions that never touch
+;;; Postgres opt out so they don't hold a connection for nothing.
+
+(defn wrap-transaction
+  [handler]
+  (fn [request]
+    (postgres/with-transaction [tx (request->postgres request)]
+      (let [response (handler (update request ::state postgres/repoint tx))]
+        (when (or (anom/anomaly? response) (<= 400 (:status response 200)))
+          (postgres/rollback! tx))
+        response))))
+
 ;;; ----------------------------------------------------------------------------
 ;;; Secure headers
 
@@ -177,15 +333,64 @@
    "x-permitted-cross-domain-policies" "none"
    "x-xss-protection"                  "1; mode=block"})
 
+(defn- shadow-csp
+  "The candidate policy as a header value when the tenant shadows it."
+  [request active]
+  (let [shadow    (request->shadow request)
+        tenant-id (get-in request [:session/realm :tenant/id])]
+    (when (shadow/shadowing? shadow tenant-id :csp)
+      (let [candidate (csp/csp-map->str (merge active (shadow/candidate shadow :csp)))]
+        (shadow/compare! shadow :csp tenant-id (csp/csp-map->str active) candidate)
+        candidate))))
+
 (defn wrap-secure-headers
+  "Adds the secure headers and CSP to every response. A response that sends
+  CSP directives of its own, like the sandbox site files get, keeps them on
+  top of the policy."
   [handler]
   (fn [request]
     (when-let [response (handler request)]
-      (let [nonce   (get-in request [:session :nonce])
-            policy  (csp/csp-map->str (csp/policy nonce))
-            headers (assoc secure-headers "content-security-policy" policy)]
+      (let [nonce     (get-in request [:session :nonce])
+            active    (csp/policy nonce)
+            own       (get-in response [:headers "content-security-policy"])
+            policy    (cond-> (csp/csp-map->str active)
+                        own (str "; " own))
+            candidate (shadow-csp request active)
+            public?   (environment/public? (request->environment request) (:session/realm request))
+            headers   (cond-> (assoc secure-headers "content-security-policy" policy)
+                        ;; Browsers report what the candidate would block but
+                        ;; block nothing themselves.
+                        candidate     (assoc "content-security-policy-report-only" candidate)
+                        (not public?) (assoc "x-robots-tag" "noindex, nofollow"))]
         (update response :headers merge headers)))))
 
+;;; ----------------------------------------------------------------------------
+;;; Multipart
+;;;
+;;; File uploads are parsed before CSRF is checked, since the token travels in
+;;; the same body. Bodies larger than max-bytes, or of unknown length, are
+;;; refused before anything is written to disk.
+
+(defn- multipart?
+  [request]
+  (some-> (response/get-header request "content-type")
+          (str/starts-with? "multipart/form-data")))
+
+(defn wrap-multipart
+  [handler {:keys [max-bytes]}]
+  (let [parsed (multipart-params/wrap-multipart-params handler)]
+    (fn [request]
+      (cond
+        (not (multipart? request))
+        (handler request)
+
+        (let [length (some-> (response/get-header request "content-length") parse-long)]
+          (or (nil? length) (< max-bytes length)))
+        bits.response/payload-too-large-response
+
+        :else
+        (parsed request)))))
+
 ;;; ----------------------------------------------------------------------------
 ;;; CSRF
 
@@ -211,8 +416,11 @@
           token          (crypto/csrf-token secret sid)
           actual         (get-in request [:params "csrf"])
           current-cookie (get-in request [:cookies cookie-name :value])
+          ;; Bearer tokens are never sent ambiently by browsers, so requests
+          ;; authenticated by an API key can't be forged cross-site.
           safe?          (or (contains? safe-methods (:request-method request))
-                             (sse-request? request))
+                             (sse-request? request)
+                             (some? (request->api-key request)))
           valid?         (or safe? (csrf-equals? token actual))]
       (if valid?
         (cond-> (handler (assoc request ::csrf token))
@@ -259,6 +467,54 @@
            :body    (io/input-stream resource)})
         (handler request)))))
 
+;;; ----------------------------------------------------------------------------
+;;; Redirects
+;;;
+;;; Checked before routing so tenants can redirect any path of the site they
+;;; moved from, apart from those the app routes itself.
+
+(defn wrap-redirects
+  [handler roots]
+  (fn [request]
+    (let [segment (second (re-find #"^/([^/]*)" (codec/url-decode (:uri request))))
+          found   (when (and (contains? #{:get :head} (:request-method request))
+                             (= :realm.type/creator (get-in request [:session/realm :realm/type]))
+                             (not (contains? roots segment)))
+                    (let [redirects (request->redirects request)]
+                      (redirect/match (redirect/cached-rules redirects (get-in request [:session/realm :tenant/id]))
+                                      (:uri request))))]
+      (if-let [[rule matched] found]
+        (do
+          (redirect/hit! (request->redirects request) (:bits.postgres.redirect/id rule))
+          {:status  (:bits.postgres.redirect/status rule)
+           :headers {"location" (redirect/location rule matched (:query-string request))}})
+        (handler request)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Maintenance
+
+(defn- exempt?
+  "Allowlisted addresses and the tenant's admins see the realm as usual, and
+  anyone may sign in so admins can get that far."
+  [request allowlist]
+  (or (contains? allowlist (request/remote-addr request))
+      (= "/login" (:uri request))
+      (some-> (get-in request [:form-params "action"]) (str/starts-with? "auth/"))
+      (role/request-tenant-admin? request)))
+
+(defn wrap-maintenance
+  "Answers creator realms in maintenance with `respond`, which should say so
+  with a 503."
+  [handler {:keys [allowlist respond]}]
+  (fn [request]
+    (if (and (= :realm.type/creator (get-in request [:session/realm :realm/type]))
+             (maintenance/on? (request->flags request) (get-in request [:session/realm :tenant/id]))
+             (not (exempt? request allowlist)))
+      (-> (respond request)
+          (assoc :status 503)
+          (assoc-in [:headers "retry-after"] (str maintenance/retry-after-seconds)))
+      (handler request))))
+
 ;;; ----------------------------------------------------------------------------
 ;;; Locale
 
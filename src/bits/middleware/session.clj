@@ -14,9 +14,16 @@
                          :secure    true}
                         (:cookie-attrs options))})
 
+(defn- request-store
+  "The session store in the request's state, which `wrap-residency` points at
+  the tenant's region, or else the one the middleware was given."
+  [request store]
+  (or (get-in request [:bits.middleware/state :session-store]) store))
+
 (defn- bare-session-request
   [request {:keys [store cookie-name]}]
-  (let [tenant-id   (get-in request [:session/realm :tenant/id])
+  (let [store       (request-store request store)
+        tenant-id   (get-in request [:session/realm :tenant/id])
         sid         (get-in request [:cookies cookie-name :value])
         key         {:tenant-id tenant-id :sid sid}
         session     (when (and tenant-id sid)
@@ -27,7 +34,8 @@
 
 (defn- bare-session-response
   [response {session-key :session/key :as request} {:keys [store cookie-name cookie-attrs]}]
-  (let [tenant-id (get-in request [:session/realm :tenant/id])
+  (let [store     (request-store request store)
+        tenant-id (get-in request [:session/realm :tenant/id])
         new-session-key
         (when (contains? response :session)
           (if-let [session (:session response)]
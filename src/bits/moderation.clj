@@ -0,0 +1,248 @@
+(ns bits.moderation
+  "Abuse reports and what moderators do about them.
+
+  Anyone signed in can report a page, a review or a whole tenant. Content is
+  also screened as it's written, and anything that trips a heuristic is
+  reported on nobody's behalf. Moderators work through open reports from the
+  platform realm: dismissing them, hiding the content, or suspending the
+  tenant. Every decision lands in the tenant's activity feed."
+  (:require
+   [bits.activity :as activity]
+   [bits.clock :as clock]
+   [bits.datomic :as datomic]
+   [bits.locale :refer [tru]]
+   [bits.outbox :as outbox]
+   [bits.page :as page]
+   [bits.pagination :as pagination]
+   [bits.postgres :as postgres]
+   [bits.postgres.report :as postgres.report]
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [datomic.api :as d]
+   [next.jdbc :as jdbc]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.time OffsetDateTime)
+   (java.util Date)))
+
+;;; ----------------------------------------------------------------------------
+;;; Reports
+
+(def reasons
+  #{"abuse" "illegal" "impersonation" "other" "spam"})
+
+(def target-types
+  #{"page" "review" "tenant"})
+
+(defn- open-report
+  [moderator target-type target-id reporter-id]
+  (postgres/execute-one! (:postgres moderator)
+                         {:select [:id]
+                          :from   [:reports]
+                          :where  [:and
+                                   [:= :target-type target-type]
+                                   [:= :target-id target-id]
+                                   [:= :status "open"]
+                                   (if reporter-id
+                                     [:= :reporter-id reporter-id]
+                                     [:= :reporter-id nil])]}))
+
+(defn file!
+  "Files a report and returns its ID. Reporting something you've already
+  reported, and that's still open, returns the existing report."
+  [moderator {:keys [details reason reporter-id target-id target-type tenant-id]}]
+  {:pre [(contains? reasons reason) (contains? target-types target-type) (uuid? target-id) (uuid? tenant-id)]}
+  (span/with-span! {:name ::file!}
+    (or (::postgres.report/id (open-report moderator target-type target-id reporter-id))
+        (jdbc/with-transaction [tx (get-in moderator [:postgres :datasource])]
+          (let [id (::postgres.report/id
+                    (postgres/execute-one! tx
+                                           {:insert-into :reports
+                                            :values      [{:id          (random-uuid)
+                                                           :tenant-id   tenant-id
+                                                           :target-type target-type
+                                                           :target-id   target-id
+                                                           :reason      reason
+                                                           :details     (or details "")
+                                                           :reporter-id reporter-id}]
+                                            :returning   [:id]}))]
+            (outbox/enqueue! tx tenant-id reporter-id "report.filed"
+                             {:report-id id :target-type target-type})
+            id)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Screening
+;;;
+;;; Cheap checks run as content is saved. They only ever report: a person
+;;; decides what happens next, so false positives cost a moderator a click
+;;; rather than a creator their page.
+
+(def ^:private impersonating-words
+  #{"admin" "bits" "moderator" "official" "security" "staff" "support"})
+
+(def ^:private max-links 3)
+
+(defn- shouting?
+  [text]
+  (let [letters (filter #(Character/isLetter ^char %) text)]
+    (and (< 20 (count letters))
+         (< 0.7 (/ (count (filter #(Character/isUpperCase ^char %) letters))
+                   (count letters))))))
+
+(defn screen
+  "Returns the reason and details of the first heuristic the content trips,
+  or nil. Titles are checked for words that pass content off as ours."
+  [{:keys [text title]}]
+  (let [text (or text "")]
+    (or (when-let [word (some impersonating-words
+                              (str/split (str/lower-case (or title "")) #"[^\p{L}\p{N}]+"))]
+          ["impersonation" (tru "Title uses the reserved word \"{0}\"." word)])
+        (when (< max-links (count (re-seq #"https?://" text)))
+          ["spam" (tru "More than {0} links." max-links)])
+        (when (re-find #"(.)\1{9,}" text)
+          ["spam" (tru "The same character ten or more times in a row.")])
+        (when (shouting? text)
+          ["spam" (tru "Mostly capital letters.")]))))
+
+(defn screen!
+  "Screens the content and files a report without a reporter when it trips a
+  heuristic. Returns the report ID, or nil."
+  [moderator {:keys [target-id target-type tenant-id] :as content}]
+  (when-let [[reason details] (screen content)]
+    (file! moderator {:details     details
+                      :reason      reason
+                      :target-id   target-id
+                      :target-type target-type
+                      :tenant-id   tenant-id})))
+
+;;; ----------------------------------------------------------------------------
+;;; Queue
+
+(def ordering
+  {:direction :asc
+   :columns   [[:created-at ::postgres.report/created-at]
+               [:id ::postgres.report/id]]})
+
+(defn queue
+  "Returns a page of open reports, oldest first."
+  [moderator page-request]
+  {:post [(s/valid? (s/coll-of ::postgres.report/persisted) (:items %))]}
+  (span/with-span! {:name ::queue}
+    (-> (postgres/execute! (:postgres moderator)
+                           (pagination/paginate {:select [:id :tenant-id :target-type :target-id :reason
+                                                          :details :reporter-id :status :created-at]
+                                                 :from   [:reports]
+                                                 :where  [:= :status "open"]}
+                                                ordering
+                                                page-request))
+        (pagination/page ordering page-request))))
+
+(defn lookup
+  [moderator id]
+  (postgres/execute-one! (:postgres moderator)
+                         {:select [:*]
+                          :from   [:reports]
+                          :where  [:= :id id]}))
+
+(defn suspended-tenants
+  [db]
+  (->> (d/q '[:find [(pull ?t [:creator/handle :tenant/id :tenant/suspended-at]) ...]
+              :where [?t :tenant/suspended-at]]
+            db)
+       (sort-by :tenant/suspended-at)))
+
+;;; ----------------------------------------------------------------------------
+;;; Decisions
+
+(defn- resolve-reports!
+  "Closes the open reports matching where. Acting on content closes every
+  report about it, so the same content doesn't come round again."
+  [connectable now status moderator-id where]
+  (postgres/execute! connectable
+                     {:update :reports
+                      :set    {:status      status
+                               :resolved-at now
+                               :resolved-by moderator-id}
+                      :where  [:and [:= :status "open"] where]}))
+
+(defn- audit!
+  [moderator report moderator-id kind]
+  (activity/record! (:activities moderator)
+                    (::postgres.report/tenant-id report)
+                    moderator-id
+                    kind
+                    {:reason      (::postgres.report/reason report)
+                     :target-type (::postgres.report/target-type report)}))
+
+(defn dismiss!
+  [moderator report moderator-id]
+  (span/with-span! {:name ::dismiss!}
+    (resolve-reports! (:postgres moderator) (clock/now (:clock moderator)) "dismissed" moderator-id
+                      [:= :id (::postgres.report/id report)])
+    (audit! moderator report moderator-id "moderation.dismissed")))
+
+(defn- suspend-tx
+  [tenant-id ^OffsetDateTime now]
+  [{:tenant/id tenant-id :tenant/suspended-at (Date/from (.toInstant now))}])
+
+(defn hide!
+  "Hides the reported content: reviews are hidden, pages unpublished and
+  reported tenants suspended."
+  [moderator report moderator-id]
+  (span/with-span! {:name ::hide!}
+    (let [{::postgres.report/keys [target-id target-type tenant-id]} report
+          now                                                        (clock/now (:clock moderator))
+          conn                                                       (datomic/conn (:datomic moderator))]
+      (jdbc/with-transaction [tx (get-in moderator [:postgres :datasource])]
+        (case target-type
+          "review" (postgres/execute! tx {:update :reviews
+                                          :set    {:hidden-at now}
+                                          :where  [:and [:= :id target-id] [:= :tenant-id tenant-id]]})
+          "page"   (when-let [p (page/lookup (d/db conn) tenant-id target-id)]
+                     @(d/transact conn (page/unpublish-tx p)))
+          "tenant" @(d/transact conn (suspend-tx tenant-id now)))
+        (resolve-reports! tx now "actioned" moderator-id
+                          [:and [:= :target-type target-type] [:= :target-id target-id]])
+        (when (= "tenant" target-type)
+          (outbox/enqueue! tx tenant-id moderator-id "tenant.suspended" {})))
+      (audit! moderator report moderator-id (if (= "tenant" target-type)
+                                              "moderation.suspended"
+                                              "moderation.hidden")))))
+
+(defn suspend!
+  "Suspends the tenant behind the report, whatever was reported."
+  [moderator report moderator-id]
+  (span/with-span! {:name ::suspend!}
+    (let [tenant-id (::postgres.report/tenant-id report)
+          now       (clock/now (:clock moderator))]
+      @(d/transact (datomic/conn (:datomic moderator)) (suspend-tx tenant-id now))
+      (jdbc/with-transaction [tx (get-in moderator [:postgres :datasource])]
+        (resolve-reports! tx now "actioned" moderator-id [:= :tenant-id tenant-id])
+        (outbox/enqueue! tx tenant-id moderator-id "tenant.suspended" {}))
+      (audit! moderator report moderator-id "moderation.suspended"))))
+
+(defn reinstate!
+  [moderator tenant-id moderator-id]
+  (span/with-span! {:name ::reinstate!}
+    (let [conn                          (datomic/conn (:datomic moderator))
+          {:tenant/keys [suspended-at]} (d/pull (d/db conn) [:tenant/suspended-at] [:tenant/id tenant-id])]
+      (when suspended-at
+        @(d/transact conn [[:db/retract [:tenant/id tenant-id] :tenant/suspended-at suspended-at]])
+        (activity/record! (:activities moderator) tenant-id moderator-id "moderation.reinstated" {})
+        (outbox/enqueue! (:postgres moderator) tenant-id moderator-id "tenant.reinstated" {})
+        true))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Moderator [activities clock datomic postgres])
+
+(defmethod print-method Moderator
+  [_ ^java.io.Writer w]
+  (.write w "#<Moderator>"))
+
+(defn make-moderator
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Moderator config))
@@ -1,6 +1,7 @@
 (ns bits.module
   (:require
    [bits.anomaly :as anom]
+   [clojure.string :as str]
    [medley.core :as medley]))
 
 ;;; ----------------------------------------------------------------------------
@@ -10,6 +11,50 @@
   [actions]
   (medley/map-vals #(cond->> % (fn? %) (hash-map :handler)) actions))
 
+;;; ----------------------------------------------------------------------------
+;;; Routes
+
+(def ^:private request-methods
+  [:get :head :post :put :patch :delete])
+
+(defn route-table
+  "One row per route and method, with the module that serves it and the API
+  key scopes it demands. Scopes on a method win over the route's."
+  [modules]
+  (for [{module-name :name :keys [routes]} modules
+        [path data]                        routes
+        method                             request-methods
+        :let                               [method-data (get data method)]
+        :when                              method-data]
+    {:method method
+     :module module-name
+     :path   path
+     :scopes (or (when (map? method-data) (:bits/scopes method-data))
+                 (:bits/scopes data))}))
+
+(defn route-roots
+  "The first segment of every routed path, which tenants' own paths must
+  leave alone."
+  [routes]
+  (into #{} (keep #(second (re-find #"^/([^/{:*]+)" (first %)))) routes))
+
+(defn- duplicate-paths
+  [modules]
+  (->> (for [{:keys [name routes]} modules
+             [path]                routes]
+         [path name])
+       (group-by first)
+       (into {} (comp (filter (fn [[_ pairs]] (< 1 (count pairs))))
+                      (map (fn [[path pairs]] [path (mapv second pairs)]))))))
+
+(defn- unscoped-api-routes
+  "API routes are for API keys, so every one of them must say what scopes a
+  key needs. Leaving them out lets any key through."
+  [modules]
+  (->> (route-table modules)
+       (filter #(and (str/starts-with? (:path %) "/api/") (nil? (:scopes %))))
+       (mapv #(select-keys % [:method :path]))))
+
 ;;; ----------------------------------------------------------------------------
 ;;; Validation
 
@@ -21,11 +66,24 @@
 
 (defn combine-modules
   [modules]
-  (let [indexed (index-actions modules)
-        dupes   (into {} (filter (fn [[_ v]] (< 1 (count v)))) indexed)]
-    (if (seq dupes)
+  (let [indexed    (index-actions modules)
+        dupes      (into {} (filter (fn [[_ v]] (< 1 (count v)))) indexed)
+        dupe-paths (duplicate-paths modules)
+        unscoped   (unscoped-api-routes modules)]
+    (cond
+      (seq dupes)
       (anom/incorrect {::anom/message "Duplicate action keys?!"
                        :duplicates    dupes})
+
+      (seq dupe-paths)
+      (anom/incorrect {::anom/message "Duplicate routes?!"
+                       :duplicates    dupe-paths})
+
+      (seq unscoped)
+      (anom/incorrect {::anom/message "API routes without scopes?!"
+                       :routes        unscoped})
+
+      :else
       {:actions (->> modules
                      (into {} (mapcat :actions))
                      normalize-actions)
@@ -0,0 +1,85 @@
+(ns bits.module.activity
+  (:require
+   [bits.activity :as activity]
+   [bits.auth.role :as role]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.morph :as morph]
+   [bits.pagination :as pagination]
+   [bits.postgres.activity :as postgres.activity]
+   [bits.ui :as ui]))
+
+;;; ----------------------------------------------------------------------------
+;;; Messages
+
+(defn message
+  [{::postgres.activity/keys [data kind]}]
+  (case kind
+    "api-key.issued"        (tru "API key \"{0}\" was created." (:name data))
+    "api-key.revoked"       (tru "API key \"{0}\" was revoked." (:name data))
+    "discount.created"      (tru "Discount code {0} was created." (:code data))
+    "discount.disabled"     (tru "Discount code {0} was disabled." (:code data))
+    "moderation.dismissed"  (tru "A moderator dismissed a {0} report about a {1}." (:reason data) (:target-type data))
+    "moderation.hidden"     (tru "A moderator hid a {0} after a {1} report." (:target-type data) (:reason data))
+    "moderation.reinstated" (tru "A moderator lifted the suspension.")
+    "moderation.suspended"  (tru "A moderator suspended this Bits after a {0} report." (:reason data))
+    "page.published"        (tru "Page \"{0}\" was published." (:title data))
+    "page.unpublished"      (tru "Page \"{0}\" was unpublished." (:title data))
+    "plugin.disabled"       (tru "Plugin \"{0}\" was disabled." (:name data))
+    "plugin.enabled"        (tru "Plugin \"{0}\" was enabled." (:name data))
+    "plugin.installed"      (tru "Plugin \"{0}\" was installed." (:name data))
+    "plugin.uninstalled"    (tru "Plugin \"{0}\" was uninstalled." (:name data))
+    "pricing.changed"       (tru "Pricing rules were changed.")
+    "redirects.changed"     (tru "Redirects were changed.")
+    "resource.deleted"      (tru "{0} was deleted." (:label data))
+    "resource.restored"     (tru "{0} was restored." (:label data))
+    "retention.changed"     (tru "Data retention was changed.")
+    "session.signed-in"     (tru "A member signed in.")
+    "shipping.changed"      (tru "Shipping zones or rates were changed.")
+    "site.published"        (tru "A new release of the site was published.")
+    "site.rolled-back"      (tru "The site was rolled back to an earlier release.")
+    "site.taken-down"       (tru "The site was taken down.")
+    "webhook.disabled"      (tru "Webhook endpoint {0} was removed." (:url data))
+    "webhook.registered"    (tru "Webhook endpoint {0} was added." (:url data))))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn- activity-row
+  [a]
+  [:li {:class ["py-3"]}
+   [:p {:class ["text-sm" "text-primary"]} (message a)]
+   [:p {:class ["text-xs" "text-muted"]} (str (::postgres.activity/created-at a))]])
+
+(defn activity-view
+  [request]
+  (list
+   (ui/nav-header request "/activity")
+   (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+     [:div {:class ["w-full" "sm:max-w-md" "space-y-6"]}
+      (ui/page-title {:class "text-2xl"} (tru "Activity"))
+      (if-not (role/request-tenant-admin? request)
+        (ui/text-muted {} (tru "Only tenant admins can see activity."))
+        (let [tenant-id                   (get-in request [:session/realm :tenant/id])
+              {:keys [items next-cursor]} (activity/list-activities
+                                           (mw/request->activities request)
+                                           tenant-id
+                                           (pagination/page-request (:query-params request)))]
+          (if (empty? items)
+            (ui/text-muted {} (tru "Nothing has happened yet."))
+            (list
+             [:ul {:class ["divide-y" "divide-border-subtle"]}
+              (map activity-row items)]
+             (when next-cursor
+               [:a {:href  (str "/activity?cursor=" next-cursor)
+                    :class ["text-sm" "text-secondary" "hover:text-primary"]}
+                (tru "Older activity")])))))])))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/activity
+   :routes  [["/activity" (assoc (morph/morphable ui/layout activity-view)
+                                 :bits/page {:page/title "Activity"})]]
+   :actions {}})
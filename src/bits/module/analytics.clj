@@ -0,0 +1,37 @@
+(ns bits.module.analytics
+  (:require
+   [bits.analytics :as analytics]
+   [bits.middleware :as mw]
+   [bits.request :as request]
+   [ring.util.response :as response]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Handlers
+
+(defn- opted-out?
+  [request]
+  (or (= "1" (response/get-header request "dnt"))
+      (= "1" (response/get-header request "sec-gpc"))))
+
+(defn collect
+  "Takes the beacon bits.js sends once a page has loaded, a form with the
+  `path` viewed. Always answers 204 so visitors learn nothing from it."
+  [request]
+  (span/with-span! {:name ::collect}
+    (when (and (= :realm.type/creator (get-in request [:session/realm :realm/type]))
+               (not (opted-out? request)))
+      (analytics/collect! (mw/request->analytics request)
+                          {:ip         (request/remote-addr request)
+                           :path       (get-in request [:params "path"])
+                           :tenant-id  (get-in request [:session/realm :tenant/id])
+                           :user-agent (response/get-header request "user-agent")}))
+    {:status 204}))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/analytics
+   :routes  [["/collect" {:post {:handler collect}}]]
+   :actions {}})
@@ -0,0 +1,154 @@
+(ns bits.module.api-key
+  (:require
+   [bits.activity :as activity]
+   [bits.auth.api-key :as api-key]
+   [bits.auth.role :as role]
+   [bits.coerce :as coerce]
+   [bits.cryptex :as cryptex]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.morph :as morph]
+   [bits.notification :as notification]
+   [bits.postgres.api-key :as postgres.api-key]
+   [bits.response]
+   [bits.ui :as ui]
+   [clojure.string :as str]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(def ^:private issue-schema
+  {:name [:string {:min 1 :max 64}]})
+
+(defn- issue-config
+  []
+  {:schema issue-schema
+   :submit {:idle    (tru "Create key")
+            :success (tru "Key created")}})
+
+(defn- issued-key
+  [issued]
+  [:div {:role  "status"
+         :class ["space-y-2" "rounded-lg" "p-4" "bg-surface-raised"
+                 "border" "border-border-subtle"]}
+   (ui/text-success (tru "Copy this key now. It won''t be shown again."))
+   [:code {:class ["block" "break-all" "font-mono" "text-sm" "text-primary"]}
+    (cryptex/reveal (::api-key/token issued))]])
+
+(defn- key-row
+  [request k]
+  (let [f (form/build request {})]
+    [:li {:class ["flex" "items-center" "justify-between" "gap-4" "py-3"]}
+     [:div
+      [:p {:class ["text-sm" "font-medium" "text-primary"]}
+       (::postgres.api-key/name k)]
+      [:p {:class ["text-xs" "text-muted" "font-mono"]}
+       (str "bits_" (::postgres.api-key/prefix k) "_… · "
+            (str/join ", " (::postgres.api-key/scopes k)))]]
+     (form/form f :api-key/revoke {}
+                [:input {:type  "hidden"
+                         :name  "id"
+                         :value (identifier/prefixed :api-key (::postgres.api-key/id k))}]
+                (ui/button-secondary {} (tru "Revoke")))]))
+
+(defn api-keys-view
+  ([request]
+   (api-keys-view request {}))
+  ([request {:keys [issued]}]
+   (let [user-id   (get-in request [:session/user :user/id])
+         tenant-id (get-in request [:session/realm :tenant/id])
+         f         (form/build request (issue-config))]
+     (list
+      (ui/nav-header request "/api-keys")
+      (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+        [:div {:class ["w-full" "sm:max-w-md" "space-y-6"]}
+         (ui/page-title {:class "text-2xl"} (tru "API keys"))
+         (cond
+           (not user-id)
+           (ui/text-muted {} (tru "Sign in to manage API keys."))
+
+           (not (role/request-tenant-admin? request))
+           (ui/text-muted {} (tru "Only admins can manage API keys."))
+
+           :else
+           (list
+            (when issued
+              (issued-key issued))
+            (form/form f :api-key/issue {:class "rounded-xl p-6"}
+                       (form/field f :name {:label       (tru "Name")
+                                            :placeholder (tru "Inventory sync")})
+                       (form/checkbox f :write {:label (tru "Allow changes")})
+                       [:div {:class "mt-4"}
+                        (form/submit f)])
+            [:ul {:class ["divide-y" "divide-border-subtle"]}
+             (for [k (api-key/list-keys (mw/request->api-keys request) tenant-id user-id)]
+               (key-row request k))]))])))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+;;;
+;;; Keys act for their user, so only tenant admins may hold them, and a key
+;;; can't mint another: a leaked key must not be able to outlive its
+;;; revocation.
+
+(defn issue
+  [request]
+  (span/with-span! {:name ::issue}
+    (let [user-id   (get-in request [:session/user :user/id])
+          tenant-id (get-in request [:session/realm :tenant/id])
+          params    (get-in request [:parameters :form])
+          f         (form/build request (issue-config))]
+      (cond
+        (or (some? (mw/request->api-key request))
+            (not (role/request-tenant-admin? request)))
+        bits.response/forbidden-response
+
+        (not (:success? f))
+        (morph/respond (api-keys-view request))
+
+        :else
+        (let [issued (api-key/issue! (mw/request->api-keys request)
+                                     {:tenant-id tenant-id
+                                      :user-id   user-id
+                                      :name      (:name params)
+                                      :scopes    (cond-> #{:read}
+                                                   (= "true" (:write params)) (conj :write))})]
+          (notification/notify! (mw/request->notifications request) tenant-id user-id
+                                "api-key.issued" {:name (:name params)})
+          (activity/record! (mw/request->activities request) tenant-id user-id
+                            "api-key.issued" {:name (:name params)})
+          (morph/respond (api-keys-view request {:issued issued})))))))
+
+(defn revoke
+  [request]
+  (span/with-span! {:name ::revoke}
+    (let [user-id   (get-in request [:session/user :user/id])
+          tenant-id (get-in request [:session/realm :tenant/id])
+          id        (get-in request [:parameters :form :id])]
+      (if-not (role/request-tenant-admin? request)
+        bits.response/forbidden-response
+        (let [registry (mw/request->api-keys request)
+              k        (some #(when (= id (::postgres.api-key/id %)) %)
+                             (api-key/list-keys registry tenant-id user-id))]
+          (when (api-key/revoke! registry tenant-id user-id id)
+            (notification/notify! (mw/request->notifications request) tenant-id user-id
+                                  "api-key.revoked" {:name (::postgres.api-key/name k)})
+            (activity/record! (mw/request->activities request) tenant-id user-id
+                              "api-key.revoked" {:name (::postgres.api-key/name k)}))
+          (morph/respond (api-keys-view request)))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/api-key
+   :routes  [["/api-keys" (assoc (morph/morphable ui/layout api-keys-view)
+                                 :bits/page {:page/title "API keys"})]]
+   :actions {:api-key/issue  {:handler issue
+                              :params  [[:name :string]
+                                        [:write {:optional true} :string]]}
+             :api-key/revoke {:handler revoke
+                              :params  [[:id (coerce/public-id :api-key)]]}}})
@@ -0,0 +1,67 @@
+(ns bits.module.catalog
+  (:require
+   [bits.anomaly :as anom]
+   [bits.auth.role :as role]
+   [bits.catalog :as catalog]
+   [bits.datomic :as datomic]
+   [bits.middleware :as mw]
+   [bits.response]
+   [clojure.string :as str]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.util Date)))
+
+;;; ----------------------------------------------------------------------------
+;;; Helpers
+
+(def ^:private max-bytes
+  (* 10 1024 1024))
+
+(defn- read-body
+  "The uploaded CSV, or nil without a length we're willing to hold in memory."
+  [request]
+  (let [length (some-> (get-in request [:headers "content-length"]) parse-long)]
+    (when (and (:body request) length (<= length max-bytes))
+      (slurp (:body request) :encoding "UTF-8"))))
+
+;;; ----------------------------------------------------------------------------
+;;; Handlers
+
+(defn import-products
+  "Takes a CSV body. With `dry-run=true` it reports what would be created and
+  every problem by line; otherwise a clean catalog is created in one go."
+  [request]
+  (span/with-span! {:name ::import-products}
+    (let [tenant-id (get-in request [:session/realm :tenant/id])
+          params    (:query-params request)
+          dry-run?  (= "true" (get params "dry-run"))
+          opts      {:currency (str/upper-case (get params "currency" "GBP"))}
+          text      (read-body request)
+          conn      (datomic/conn (mw/request->datomic request))]
+      (cond
+        (not (role/request-tenant-admin? request))
+        bits.response/forbidden-response
+
+        (nil? text)
+        bits.response/bad-request-response
+
+        dry-run?
+        (let [{:keys [errors products]} (catalog/check (mw/request->db request) text opts)]
+          (bits.response/json-response 200 {:errors   errors
+                                            :products (count products)
+                                            :variants (reduce + (map (comp count :variants) products))}))
+
+        :else
+        (let [result (catalog/import! conn tenant-id text opts (Date.))]
+          (if (anom/anomaly? result)
+            (bits.response/anomaly-response request result {:errors (::catalog/errors result)})
+            (bits.response/json-response 201 result)))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/catalog
+   :routes  [["/api/imports/products.csv" {:bits/scopes #{:write}
+                                           :post        {:handler import-products}}]]
+   :actions {}})
@@ -0,0 +1,234 @@
+(ns bits.module.comment
+  (:require
+   [bits.auth.role :as role]
+   [bits.comment :as comment]
+   [bits.datomic :as datomic]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.morph :as morph]
+   [bits.page :as page]
+   [bits.pagination :as pagination]
+   [bits.postgres.comment :as postgres.comment]
+   [bits.response]
+   [bits.ui :as ui]
+   [clojure.string :as str]
+   [datomic.api :as d]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Helpers
+
+(defn- public-id
+  [c]
+  (identifier/prefixed :comment (::postgres.comment/id c)))
+
+(defn- author
+  [c]
+  (cond
+    (::postgres.comment/author-id c)                (tru "Member")
+    (str/blank? (::postgres.comment/author-name c)) (tru "Anonymous")
+    :else                                           (::postgres.comment/author-name c)))
+
+;;; ----------------------------------------------------------------------------
+;;; Thread
+;;;
+;;; Rendered under a page by `bits.module.page`, which owns posting since it
+;;; has to respond with the whole page.
+
+(def ^:private honeypot-classes
+  ;; Off screen rather than hidden, as some bots skip hidden inputs.
+  ["absolute" "-left-[9999px]" "w-px" "h-px" "overflow-hidden"])
+
+(defn- post-form
+  [request p parent-id]
+  (let [f (form/build request {})]
+    (form/form f :page/comment {:class ["mt-2" "space-y-2" "rounded-xl" "p-4"]}
+               [:input {:type "hidden" :name "page-id" :value (identifier/prefixed :page (:page/id p))}]
+               (when parent-id
+                 [:input {:type "hidden" :name "parent-id" :value parent-id}])
+               [:div {:class honeypot-classes :aria-hidden "true"}
+                [:label {:for (str "website-" parent-id)} "Website"]
+                [:input {:id (str "website-" parent-id) :name "website" :type "text" :tabindex "-1" :autocomplete "off"}]]
+               (when-not (get-in request [:session/user :user/id])
+                 (form/field f :author-name {:label     (tru "Name")
+                                             :maxlength comment/max-name}))
+               (form/textarea f :body {:label     (if parent-id (tru "Reply") (tru "Comment"))
+                                       :maxlength comment/max-body
+                                       :rows      3})
+               (ui/button-secondary {} (if parent-id (tru "Post reply") (tru "Post comment"))))))
+
+(defn- comment-item
+  [request p reply? c]
+  [:li {:class ["space-y-1"]}
+   [:p {:class ["text-xs" "text-muted"]}
+    (tru "{0} · {1}" (author c) (str (::postgres.comment/created-at c)))]
+   [:p {:class ["text-sm" "text-secondary" "whitespace-pre-line"]} (::postgres.comment/body c)]
+   (when reply?
+     [:details {:class ["text-xs" "text-muted"]}
+      [:summary {:class ["cursor-pointer" "hover:text-secondary"]} (tru "Reply")]
+      (post-form request p (public-id c))])
+   (when (seq (:replies c))
+     [:ul {:class ["mt-3" "ml-4" "pl-4" "border-l" "border-border-subtle" "space-y-4"]}
+      (map #(comment-item request p reply? %) (:replies c))])])
+
+(defn- can-post?
+  [request]
+  (case (get-in request [:session/realm :tenant/comments])
+    :anyone    true
+    :signed-in (some? (get-in request [:session/user :user/id]))
+    false))
+
+(defn section
+  "A page's comments and the form to add one, or nil when the tenant has
+  comments off. `posted` is the status of a comment just posted."
+  [request p {:keys [error posted]}]
+  (when-let [mode (get-in request [:session/realm :tenant/comments])]
+    (let [threads (comment/thread (mw/request->comments request)
+                                  (get-in request [:session/realm :tenant/id])
+                                  (:page/id p))]
+      [:section {:class ["mt-12" "space-y-6"]}
+       [:h2 {:class ["text-lg" "font-semibold" "text-primary"]} (tru "Comments")]
+       (when error
+         (ui/alert-error error))
+       (case posted
+         "approved"         (ui/text-success (tru "Thanks for your comment."))
+         ("pending" "spam") (ui/text-success (tru "Thanks. Your comment will show once it''s approved."))
+         nil)
+       (if (empty? threads)
+         (ui/text-muted {} (tru "No comments yet."))
+         [:ul {:class ["space-y-6"]}
+          (map #(comment-item request p (can-post? request) %) threads)])
+       (if (can-post? request)
+         (post-form request p nil)
+         (when (= :signed-in mode)
+           (ui/text-muted {} (tru "Sign in to comment."))))])))
+
+;;; ----------------------------------------------------------------------------
+;;; Queue
+
+(defn- mode-label
+  [value]
+  (case value
+    ""          (tru "Off")
+    "signed-in" (tru "People signed in")
+    "anyone"    (tru "Anyone")))
+
+(defn- settings-form
+  [request]
+  (let [f       (form/build request {})
+        current (some-> (comment/mode (mw/request->db request) (get-in request [:session/realm :tenant/id])) name)]
+    (form/form f :comment/settings {:class "rounded-xl p-6 space-y-4"}
+               (form/select f :mode {:label (tru "Who can comment")}
+                            (for [value ["" "signed-in" "anyone"]]
+                              [:option {:value value :selected (= value (or current ""))} (mode-label value)]))
+               (ui/button-secondary {} (tru "Save")))))
+
+(defn- decision-button
+  [request action c label]
+  (form/form (form/build request {}) action {}
+             [:input {:type "hidden" :name "id" :value (public-id c)}]
+             (ui/button-secondary {} label)))
+
+(defn- queued-row
+  [request c]
+  (let [p (page/lookup (mw/request->db request)
+                       (get-in request [:session/realm :tenant/id])
+                       (::postgres.comment/page-id c))]
+    [:li {:class ["py-4" "space-y-2"]}
+     [:div {:class ["flex" "items-center" "justify-between" "gap-4"]}
+      [:p {:class ["text-sm" "font-medium" "text-primary"]}
+       (tru "{0} on {1}" (author c) (or (:page/title p) (tru "a deleted page")))]
+      [:p {:class ["text-xs" "text-muted"]}
+       (if (= "spam" (::postgres.comment/status c))
+         (tru "Flagged: {0}" (::postgres.comment/spam-reason c))
+         (tru "Awaiting approval"))]]
+     [:p {:class ["text-sm" "text-secondary" "whitespace-pre-line" "line-clamp-4"]} (::postgres.comment/body c)]
+     [:div {:class ["flex" "gap-2"]}
+      (decision-button request :comment/approve c (tru "Approve"))
+      (decision-button request :comment/reject c (tru "Reject"))]]))
+
+(defn queue-view
+  [request]
+  (list
+   (ui/nav-header request "/comments")
+   (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+     [:div {:class ["w-full" "sm:max-w-2xl" "space-y-8"]}
+      (ui/page-title {:class "text-2xl"} (tru "Comments"))
+      (if-not (role/request-tenant-admin? request)
+        (ui/text-muted {} (tru "Only tenant admins can moderate comments."))
+        (let [{:keys [items next-cursor]} (comment/queue (mw/request->comments request)
+                                                         (get-in request [:session/realm :tenant/id])
+                                                         (pagination/page-request (:query-params request)))]
+          (list
+           (settings-form request)
+           (if (empty? items)
+             (ui/text-muted {} (tru "No comments waiting."))
+             [:ul {:class ["divide-y" "divide-border-subtle"]}
+              (map #(queued-row request %) items)])
+           (when next-cursor
+             [:a {:href  (str "/comments?cursor=" next-cursor)
+                  :class ["text-sm" "text-secondary" "hover:text-primary"]}
+              (tru "More comments")]))))])))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn- fresh
+  "The request with a database that sees the settings just saved."
+  [request]
+  (assoc request ::mw/db (d/db (datomic/conn (mw/request->datomic request)))))
+
+(defn settings
+  [request]
+  (span/with-span! {:name ::settings}
+    (let [choice (some-> (get-in request [:parameters :form :mode]) not-empty keyword)]
+      (cond
+        (not (role/request-tenant-admin? request))
+        bits.response/forbidden-response
+
+        (and choice (not (contains? comment/modes choice)))
+        bits.response/bad-request-response
+
+        :else
+        (let [conn (datomic/conn (mw/request->datomic request))]
+          (when-let [tx (comment/mode-tx (d/db conn) (get-in request [:session/realm :tenant/id]) choice)]
+            @(d/transact conn tx))
+          (morph/respond (queue-view (fresh request))))))))
+
+(defn- deciding
+  [decision]
+  (fn [request]
+    (span/with-span! {:name ::decide}
+      (let [id (identifier/parse-prefixed :comment (get-in request [:parameters :form :id]))]
+        (cond
+          (not (role/request-tenant-admin? request))
+          bits.response/forbidden-response
+
+          (not (and id (comment/decide! (mw/request->comments request)
+                                        (get-in request [:session/realm :tenant/id])
+                                        id
+                                        decision
+                                        (get-in request [:session/user :user/id]))))
+          bits.response/not-found-response
+
+          :else
+          (morph/respond (queue-view request)))))))
+
+(def approve (deciding "approved"))
+(def reject (deciding "rejected"))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/comment
+   :routes  [["/comments" (assoc (morph/morphable ui/layout queue-view)
+                                 :bits/page {:page/title "Comments"})]]
+   :actions {:comment/approve  {:handler approve
+                                :params  [[:id :string]]}
+             :comment/reject   {:handler reject
+                                :params  [[:id :string]]}
+             :comment/settings {:handler settings
+                                :params  [[:mode {:optional true} :string]]}}})
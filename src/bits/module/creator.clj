@@ -1,9 +1,12 @@
 (ns bits.module.creator
   (:require
    [bits.locale :refer [tru]]
+   [bits.meta :as meta]
    [bits.middleware :as mw]
    [bits.tailwind :as tw]
-   [java-time.api :as time]))
+   [java-time.api :as time])
+  (:import
+   (java.io ByteArrayInputStream)))
 
 ;;; ----------------------------------------------------------------------------
 ;;; Icons
@@ -371,6 +374,11 @@
      " · "
      [:a {:href "#" :class ["text-muted" "no-underline" "hover:text-secondary"]}
       (tru "Privacy")]
+     (when (= :realm.type/creator (get-in request [:session/realm :realm/type]))
+       (list
+        " · "
+        [:a {:href "/report" :class ["text-muted" "no-underline" "hover:text-secondary"]}
+         (tru "Report")]))
      [:div {:class ["mt-2" "text-[0.6875rem]" "opacity-60"]}
       (tru "Self-hostable. Open source. Your data, your rules.")]]))
 
@@ -420,10 +428,32 @@
      (page-footer request)
      (sticky-cta {:price (:price stats)}))))
 
+;;; ----------------------------------------------------------------------------
+;;; Previews
+
+(defn page
+  "Link preview metadata for the creator's home page."
+  [request]
+  (let [realm (:session/realm request)]
+    {:page/description (or (:meta/description realm) (:creator/bio realm))
+     :page/image       (or (:meta/image-url realm) "/og.png")
+     :page/title       (or (:meta/title realm) (:creator/display-name realm))
+     :page/type        "profile"}))
+
+(defn og-image
+  [request]
+  (let [realm (:session/realm request)]
+    {:status  200
+     :headers {"cache-control" "public, max-age=3600"
+               "content-type"  "image/png"}
+     :body    (ByteArrayInputStream.
+                (meta/card {:subtitle (some->> (:creator/handle realm) (str "@"))
+                            :title    (or (:meta/title realm) (:creator/display-name realm))}))}))
+
 ;;; ----------------------------------------------------------------------------
 ;;; Module
 
 (def module
   {:name    :bits.module/creator
-   :routes  []
+   :routes  [["/og.png" {:get {:handler og-image}}]]
    :actions {}})
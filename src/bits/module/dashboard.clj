@@ -0,0 +1,183 @@
+(ns bits.module.dashboard
+  (:require
+   [bits.analytics :as analytics]
+   [bits.auth.role :as role]
+   [bits.locale :as locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.money :as money]
+   [bits.morph :as morph]
+   [bits.product :as product]
+   [bits.projection :as projection]
+   [bits.ui :as ui]
+   [bits.wishlist :as wishlist]
+   [clojure.string :as str])
+  (:import
+   (java.time Duration Instant LocalDate ZoneOffset)
+   (java.time.temporal ChronoUnit)
+   (java.util Currency)))
+
+;;; ----------------------------------------------------------------------------
+;;; Access
+;;;
+;;; Tenant admins see their own tenant. Platform admins see totals across
+;;; every tenant, like moderation.
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(def ^:private days
+  30)
+
+(defn- revenue
+  [totals]
+  (if (empty? totals)
+    "–"
+    (str/join ", " (for [[code amount] (sort totals)]
+                     (money/format-price (locale/current-locale)
+                                         {:money/amount amount
+                                          ::money/iso   (Currency/getInstance ^String code)})))))
+
+(defn- day-row
+  [{:keys [day orders signups] :as totals}]
+  [:tr {:class ["text-sm"]}
+   [:td {:class ["py-2" "pr-4" "text-primary"]} (str day)]
+   [:td {:class ["py-2" "pr-4" "text-muted"]} orders]
+   [:td {:class ["py-2" "pr-4" "text-muted"]} (revenue (:revenue totals))]
+   [:td {:class ["py-2" "text-muted"]} signups]])
+
+(defn- stats-table
+  [stats]
+  (if (empty? stats)
+    (ui/text-muted {} (tru "Nothing to count in the last {0} days." days))
+    [:table {:class ["w-full" "text-left"]}
+     [:thead
+      [:tr {:class ["text-xs" "uppercase" "text-muted"]}
+       [:th {:class ["pb-2"]} (tru "Day")]
+       [:th {:class ["pb-2"]} (tru "Orders")]
+       [:th {:class ["pb-2"]} (tru "Revenue")]
+       [:th {:class ["pb-2"]} (tru "Signups")]]]
+     [:tbody {:class ["divide-y" "divide-border-subtle"]}
+      (map day-row (reverse stats))]]))
+
+(def ^:private chart-hours
+  24)
+
+(defn- hour-label
+  [^Instant hour]
+  (format "%02d:00" (.getHour (.atOffset hour ZoneOffset/UTC))))
+
+(defn- traffic-chart
+  "A bar for each of the last completed hours' views, empty hours included."
+  [hours]
+  (let [tallest (max 1 (reduce max 0 (map :views hours)))]
+    [:svg {:viewBox             (str "0 0 " (* 12 chart-hours) " 80")
+           :preserveAspectRatio "none"
+           :role                "img"
+           :aria-label          (tru "Page views for each of the last {0} hours" chart-hours)
+           :class               ["w-full" "h-24"]}
+     (map-indexed (fn [i {:keys [hour views visitors]}]
+                    (let [height (max 1 (quot (* 80 views) tallest))]
+                      [:rect {:x      (* 12 i)
+                              :y      (- 80 height)
+                              :width  10
+                              :height height
+                              :class  [(if (pos? views) "fill-accent" "fill-border-subtle")]}
+                       [:title (tru "{0}: {1} views, {2} visitors" (hour-label hour) views visitors)]]))
+                  hours)]))
+
+(defn- traffic-section
+  [request]
+  (let [tenant-id (get-in request [:session/realm :tenant/id])
+        svc       (mw/request->analytics request)
+        to        (.truncatedTo (Instant/now) ChronoUnit/HOURS)
+        from      (.minus to (Duration/ofHours chart-hours))
+        by-hour   (into {} (map (juxt :hour identity)) (analytics/hourly svc tenant-id from to))
+        hours     (for [i (range chart-hours)
+                        :let [hour (.plus from (Duration/ofHours i))]]
+                    (get by-hour hour {:hour hour :views 0 :visitors 0}))
+        paths     (analytics/top-paths svc tenant-id (.minus to (Duration/ofDays 7)) 10)]
+    [:section {:class ["space-y-4"]}
+     [:h2 {:class ["text-lg" "font-semibold" "text-primary"]} (tru "Traffic")]
+     (traffic-chart hours)
+     [:div {:class ["flex" "justify-between" "text-xs" "text-muted"]}
+      [:span (hour-label from)]
+      [:span (tru "{0} views in the last {1} hours" (reduce + (map :views hours)) chart-hours)]
+      [:span (hour-label to)]]
+     (if (empty? paths)
+       (ui/text-muted {} (tru "No page views in the last week."))
+       [:table {:class ["w-full" "text-left"]}
+        [:thead
+         [:tr {:class ["text-xs" "uppercase" "text-muted"]}
+          [:th {:class ["pb-2"]} (tru "Page, last 7 days")]
+          [:th {:class ["pb-2"]} (tru "Views")]]]
+        [:tbody {:class ["divide-y" "divide-border-subtle"]}
+         (for [{:keys [path views]} paths]
+           [:tr {:class ["text-sm"]}
+            [:td {:class ["py-2" "pr-4" "text-primary" "font-mono"]} path]
+            [:td {:class ["py-2" "text-muted"]} views]])]])]))
+
+(defn- wishlist-table
+  "The tenant's most saved products, leaving out any since deleted."
+  [request]
+  (let [db        (mw/request->db request)
+        tenant-id (get-in request [:session/realm :tenant/id])
+        rows      (for [{:keys [product-id saves]} (wishlist/popular (mw/request->wishlists request) tenant-id 10)
+                        :let  [p (product/lookup db tenant-id product-id)]
+                        :when p]
+                    [(:product/title p) saves])]
+    [:section {:class ["space-y-2"]}
+     [:h2 {:class ["text-lg" "font-semibold" "text-primary"]} (tru "Most wished for")]
+     (if (empty? rows)
+       (ui/text-muted {} (tru "Nobody has saved a product yet."))
+       [:table {:class ["w-full" "text-left"]}
+        [:thead
+         [:tr {:class ["text-xs" "uppercase" "text-muted"]}
+          [:th {:class ["pb-2"]} (tru "Product")]
+          [:th {:class ["pb-2"]} (tru "Saves")]]]
+        [:tbody {:class ["divide-y" "divide-border-subtle"]}
+         (for [[title saves] rows]
+           [:tr {:class ["text-sm"]}
+            [:td {:class ["py-2" "pr-4" "text-primary"]} title]
+            [:td {:class ["py-2" "text-muted"]} saves]])]])]))
+
+(defn- region-note
+  [request]
+  (ui/text-muted {}
+    (if-let [region (get-in request [:session/realm :tenant/region])]
+      (tru "Your data is kept in the {0} region." (str/upper-case region))
+      (tru "Your data isn''t tied to a region."))))
+
+(defn stats-view
+  [request]
+  (let [to   (LocalDate/now ZoneOffset/UTC)
+        from (.minusDays to (dec days))]
+    (list
+     (ui/nav-header request "/stats")
+     (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+       [:div {:class ["w-full" "sm:max-w-2xl" "space-y-6"]}
+        (ui/page-title {:class "text-2xl"} (tru "Stats"))
+        (cond
+          (role/request-platform-admin? request)
+          (stats-table (projection/dashboard-stats (mw/request->projector request) nil from to))
+
+          (role/request-tenant-admin? request)
+          (list
+           (region-note request)
+           (stats-table (projection/dashboard-stats (mw/request->projector request)
+                                                    (get-in request [:session/realm :tenant/id])
+                                                    from
+                                                    to))
+           (traffic-section request)
+           (wishlist-table request))
+
+          :else
+          (ui/text-muted {} (tru "Only tenant admins can see stats.")))]))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/dashboard
+   :routes  [["/stats" (assoc (morph/morphable ui/layout stats-view)
+                              :bits/page {:page/title "Stats"})]]
+   :actions {}})
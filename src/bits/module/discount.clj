@@ -0,0 +1,227 @@
+(ns bits.module.discount
+  (:require
+   [bits.activity :as activity]
+   [bits.anomaly :as anom]
+   [bits.auth.role :as role]
+   [bits.catalog :as catalog]
+   [bits.coerce :as coerce]
+   [bits.discount :as discount]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.locale :as locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.money :as money]
+   [bits.morph :as morph]
+   [bits.response]
+   [bits.ui :as ui]
+   [clojure.string :as str]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.time LocalDate ZoneOffset)
+   (java.time.format DateTimeParseException)
+   (java.util Currency)))
+
+;;; ----------------------------------------------------------------------------
+;;; Helpers
+
+(def ^:private currencies
+  ["GBP" "USD" "EUR"])
+
+(defn- parse-products
+  "Product IDs pasted one per line or separated by commas. Returns the UUIDs,
+  or nil when any of them isn't a product ID."
+  [s]
+  (let [ids (remove str/blank? (str/split (str s) #"[\s,]+"))
+        ids (map #(identifier/parse-prefixed :product %) ids)]
+    (when (every? some? ids)
+      (vec ids))))
+
+(defn- parse-expiry
+  "Codes stay valid through the day picked, in UTC."
+  [s]
+  (when-not (str/blank? s)
+    (try
+      (-> (LocalDate/parse s) (.plusDays 1) (.atStartOfDay) (.atOffset ZoneOffset/UTC))
+      (catch DateTimeParseException _
+        ::invalid))))
+
+(defn- amount-label
+  [{:bits.postgres.discount-code/keys [amount-off currency kind percent-off]}]
+  (if (= "percent" kind)
+    (tru "{0}% off" percent-off)
+    (tru "{0} off" (money/format-price (locale/current-locale)
+                                       {:money/amount amount-off
+                                        ::money/iso   (Currency/getInstance ^String currency)}))))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn- create-config
+  []
+  {:schema {:code  [:re {:error/message (tru "Letters, digits, dashes or underscores")}
+                    #"^\s*[A-Za-z0-9_-]{3,32}\s*$"]
+            :kind  [:enum {:error/message (tru "Pick a kind of discount")} "percent" "fixed"]
+            :value [:re {:error/message (tru "Must be a number")}
+                    #"^\d+(\.\d+)?$"]}
+   :submit {:idle    (tru "Create code")
+            :success (tru "Code created")}})
+
+(defn- code-row
+  [request code]
+  (let [f                                                (form/build request {})
+        {:bits.postgres.discount-code/keys [expires-at id max-redemptions
+                                            product-ids redemptions stackable]} code]
+    [:li {:class ["flex" "items-center" "justify-between" "gap-4" "py-3"]}
+     [:div {:class ["min-w-0"]}
+      [:p {:class ["text-sm" "font-medium" "text-primary" "font-mono"]}
+       (:bits.postgres.discount-code/code code)]
+      [:p {:class ["text-xs" "text-muted"]}
+       (str/join " · " (cond-> [(amount-label code)
+                                (if max-redemptions
+                                  (tru "{0} of {1} used" redemptions max-redemptions)
+                                  (tru "{0} used" redemptions))]
+                         (seq product-ids) (conj (tru "{0} products" (count product-ids)))
+                         stackable         (conj (tru "Stacks"))
+                         expires-at        (conj (tru "Expires {0}" (str expires-at)))))]]
+     (form/form f :discount/disable {}
+                [:input {:type  "hidden"
+                         :name  "id"
+                         :value (identifier/prefixed :discount id)}]
+                (ui/button-secondary {} (tru "Disable")))]))
+
+(defn discounts-view
+  ([request]
+   (discounts-view request {}))
+  ([request {:keys [error]}]
+   (let [tenant-id (get-in request [:session/realm :tenant/id])
+         f         (cond-> (form/build request (create-config))
+                     error (form/with-error error))]
+     (list
+      (ui/nav-header request "/discounts")
+      (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+        [:div {:class ["w-full" "sm:max-w-2xl" "space-y-8"]}
+         (ui/page-title {:class "text-2xl"} (tru "Discount codes"))
+         (if-not (role/request-tenant-admin? request)
+           (ui/text-muted {} (tru "Only admins can manage discount codes."))
+           (list
+            (form/form f :discount/create {:class "rounded-xl p-6 space-y-4"}
+                       (form/field f :code {:label       (tru "Code")
+                                            :placeholder "SUMMER25"})
+                       (form/select f :kind {:label (tru "Kind")}
+                                    [[:option {:value "percent"} (tru "Percentage off")]
+                                     [:option {:value "fixed"} (tru "Fixed amount off")]])
+                       (form/field f :value {:label     (tru "Percentage or amount")
+                                             :inputmode "decimal"})
+                       (form/select f :currency {:label (tru "Currency, for fixed amounts")}
+                                    (for [c currencies]
+                                      [:option {:value c} c]))
+                       (form/field f :max-redemptions {:label (tru "Redemptions allowed (optional)")
+                                                       :type  "number"
+                                                       :min   "1"})
+                       (form/field f :expires {:label (tru "Valid until (optional)")
+                                               :type  "date"})
+                       (form/textarea f :products {:label       (tru "Only these product IDs (optional)")
+                                                   :placeholder "prod_…"})
+                       (form/checkbox f :stackable {:label (tru "Combine with other stackable codes")})
+                       [:div {:class "mt-4"}
+                        (form/submit f)])
+            (let [codes (discount/list-codes (mw/request->discounts request) tenant-id)]
+              (if (empty? codes)
+                (ui/text-muted {} (tru "No discount codes yet."))
+                [:ul {:class ["divide-y" "divide-border-subtle"]}
+                 (for [code codes]
+                   (code-row request code))]))))])))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn- create-params
+  "The form's values as `discount/create!` takes them, or an error message."
+  [{:keys [currency expires kind max-redemptions products stackable value]}]
+  (let [product-ids (parse-products products)
+        expires-at  (parse-expiry expires)
+        redemptions (when-not (str/blank? max-redemptions)
+                      (parse-long max-redemptions))]
+    (cond
+      (nil? product-ids)
+      {:error (tru "Product IDs look like prod_ followed by letters and digits.")}
+
+      (= ::invalid expires-at)
+      {:error (tru "Pick a valid date.")}
+
+      (and (not (str/blank? max-redemptions)) (nil? redemptions))
+      {:error (tru "Redemptions allowed must be a whole number.")}
+
+      :else
+      {:params (cond-> {:kind            kind
+                        :product-ids     product-ids
+                        :stackable?      (= "true" stackable)
+                        :max-redemptions redemptions
+                        :expires-at      expires-at}
+                 (= "percent" kind) (assoc :percent-off (parse-long value))
+                 (= "fixed" kind)   (assoc :amount-off (when (some #{currency} currencies)
+                                                         (catalog/amount value currency))
+                                           :currency currency))})))
+
+(defn create
+  [request]
+  (span/with-span! {:name ::create}
+    (let [user-id   (get-in request [:session/user :user/id])
+          tenant-id (get-in request [:session/realm :tenant/id])
+          form      (get-in request [:parameters :form])
+          f         (form/build request (create-config))]
+      (cond
+        (not (role/request-tenant-admin? request))
+        bits.response/forbidden-response
+
+        (not (:success? f))
+        (morph/respond (discounts-view request))
+
+        :else
+        (let [{:keys [error params]} (create-params form)
+              result                 (when params
+                                       (discount/create! (mw/request->discounts request)
+                                                         (assoc params
+                                                                :tenant-id tenant-id
+                                                                :code      (:code form))))]
+          (if-let [message (or error (when (anom/anomaly? result) (::anom/message result)))]
+            (morph/respond (discounts-view request {:error message}))
+            (do
+              (activity/record! (mw/request->activities request) tenant-id user-id
+                                "discount.created" {:code (discount/normalize (:code form))})
+              (morph/respond (discounts-view request)))))))))
+
+(defn disable
+  [request]
+  (span/with-span! {:name ::disable}
+    (let [user-id   (get-in request [:session/user :user/id])
+          tenant-id (get-in request [:session/realm :tenant/id])
+          id        (get-in request [:parameters :form :id])]
+      (if-not (role/request-tenant-admin? request)
+        bits.response/forbidden-response
+        (let [discounts (mw/request->discounts request)
+              code      (some #(when (= id (:bits.postgres.discount-code/id %)) %)
+                              (discount/list-codes discounts tenant-id))]
+          (when (discount/disable! discounts tenant-id id)
+            (activity/record! (mw/request->activities request) tenant-id user-id
+                              "discount.disabled" {:code (:bits.postgres.discount-code/code code)}))
+          (morph/respond (discounts-view request)))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/discount
+   :routes  [["/discounts" (assoc (morph/morphable ui/layout discounts-view)
+                                  :bits/page {:page/title "Discount codes"})]]
+   :actions {:discount/create  {:handler create
+                                :params  [[:code :string]
+                                          [:kind :string]
+                                          [:value :string]
+                                          [:currency {:optional true} :string]
+                                          [:max-redemptions {:optional true} :string]
+                                          [:expires {:optional true} :string]
+                                          [:products {:optional true} :string]
+                                          [:stackable {:optional true} :string]]}
+             :discount/disable {:handler disable
+                                :params  [[:id (coerce/public-id :discount)]]}}})
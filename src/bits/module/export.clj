@@ -0,0 +1,69 @@
+(ns bits.module.export
+  (:require
+   [bits.auth.role :as role]
+   [bits.export :as export]
+   [bits.middleware :as mw]
+   [bits.response]
+   [io.pedestal.log :as log]
+   [ring.util.io :as ring.io]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.io OutputStreamWriter)
+   (java.nio.charset StandardCharsets)
+   (java.time.format DateTimeParseException)))
+
+;;; ----------------------------------------------------------------------------
+;;; Handlers
+
+(defn- stream
+  "Writes the export on ring's piped stream thread, releasing the tenant's slot
+  once the last row is out or the client goes away."
+  [exporter dataset extension db tenant-id range]
+  (ring.io/piped-input-stream
+   (fn [out]
+     (try
+       (with-open [w (OutputStreamWriter. out StandardCharsets/UTF_8)]
+         (export/write! w dataset extension db tenant-id range))
+       (catch Exception ex
+         (log/warn :msg "Export stopped early." :dataset dataset :exception ex))
+       (finally
+         (export/release! exporter tenant-id))))))
+
+(defn- export-handler
+  [dataset extension]
+  (fn [request]
+    (span/with-span! {:name ::export}
+      (let [db        (mw/request->db request)
+            exporter  (mw/request->exports request)
+            tenant-id (get-in request [:session/realm :tenant/id])
+            range     (try
+                        (export/parse-range (get-in request [:query-params "range"]))
+                        (catch DateTimeParseException _
+                          ::invalid))]
+        (cond
+          (not (role/request-tenant-admin? request))
+          bits.response/forbidden-response
+
+          (= ::invalid range)
+          bits.response/bad-request-response
+
+          (not (export/acquire! exporter tenant-id))
+          (assoc-in bits.response/too-many-requests-response [:headers "retry-after"] "30")
+
+          :else
+          {:status  200
+           :headers {"content-disposition" (format "attachment; filename=\"%s.%s\"" dataset extension)
+                     "content-type"        (get-in export/formats [extension :content-type])}
+           :body    (stream exporter dataset extension db tenant-id range)})))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/export
+   :routes  (vec (for [dataset   (sort (keys export/datasets))
+                       extension (sort (keys export/formats))]
+                   [(str "/api/exports/" dataset "." extension)
+                    {:bits/scopes #{:read}
+                     :get         {:handler (export-handler dataset extension)}}]))
+   :actions {}})
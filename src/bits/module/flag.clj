@@ -0,0 +1,172 @@
+(ns bits.module.flag
+  (:require
+   [bits.auth.role :as role]
+   [bits.flag :as flag]
+   [bits.form :as form]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.morph :as morph]
+   [bits.postgres.feature-flag :as postgres.feature-flag]
+   [bits.response]
+   [bits.string :as string]
+   [bits.ui :as ui]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Access
+;;;
+;;; Flags are global, so only platform admins may change them.
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(def ^:private uuid-pattern
+  #"^[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$")
+
+(defn- save-config
+  []
+  {:schema {:name            [:re {:error/message (tru "Lowercase letters, digits and dashes")}
+                              #"^[a-z0-9-]+$"]
+            :rollout-percent [:re {:error/message (tru "Between 0 and 100")}
+                              #"^(100|[1-9]?[0-9])$"]}
+   :submit {:idle    (tru "Save flag")
+            :success (tru "Flag saved")}})
+
+(defn- override-config
+  []
+  {:schema {:tenant-id [:re {:error/message (tru "Must be a tenant UUID")} uuid-pattern]}
+   :submit {:idle    (tru "Set override")
+            :success (tru "Override saved")}})
+
+(defn- build-form
+  "The page holds two forms, so only the one that was submitted should see the
+  posted values and validation state."
+  [request action config]
+  (form/build (if (= (string/keyword->string action)
+                     (get-in request [:form-params "action"]))
+                request
+                (dissoc request :form-params ::form/raw))
+              config))
+
+(defn- override-label
+  [enabled]
+  (if enabled (tru "on") (tru "off")))
+
+(defn- flag-row
+  [flag overrides]
+  (let [flag-name (::postgres.feature-flag/name flag)]
+    [:li {:class ["py-3" "space-y-1"]}
+     [:div {:class ["flex" "items-center" "justify-between" "gap-4"]}
+      [:p {:class ["text-sm" "font-medium" "font-mono" "text-primary"]} flag-name]
+      [:p {:class ["text-xs" "text-muted"]}
+       (if (::postgres.feature-flag/enabled flag)
+         (tru "On for everyone")
+         (tru "{0}% of tenants" (::postgres.feature-flag/rollout-percent flag)))]]
+     (for [override overrides
+           :when    (= flag-name (:bits.postgres.feature-flag-tenant/flag-name override))]
+       [:p {:class ["text-xs" "text-muted" "font-mono"]}
+        (str (:bits.postgres.feature-flag-tenant/tenant-id override) " → "
+             (override-label (:bits.postgres.feature-flag-tenant/enabled override)))])]))
+
+(defn flags-view
+  [request]
+  (let [flagger  (mw/request->flags request)
+        save     (build-form request :flag/save (save-config))
+        override (build-form request :flag/override (override-config))]
+    (list
+     (ui/nav-header request "/flags")
+     (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+       [:div {:class ["w-full" "sm:max-w-xl" "space-y-8"]}
+        (ui/page-title {:class "text-2xl"} (tru "Feature flags"))
+        (if-not (role/request-platform-admin? request)
+          (ui/text-muted {} (tru "Only platform admins can manage feature flags."))
+          (let [flags (flag/list-flags flagger)]
+            (list
+             (form/form save :flag/save {:class "rounded-xl p-6"}
+                        (form/field save :name {:label       (tru "Name")
+                                                :placeholder "new-checkout"})
+                        (form/field save :rollout-percent {:label       (tru "Rollout percent")
+                                                           :inputmode   "numeric"
+                                                           :placeholder "0"})
+                        (form/checkbox save :enabled {:label (tru "On for everyone")})
+                        [:div {:class "mt-4"}
+                         (form/submit save)])
+             (when (seq flags)
+               (form/form override :flag/override {:class "rounded-xl p-6"}
+                          (form/select override :flag {:label (tru "Flag")}
+                                       (for [{::postgres.feature-flag/keys [name]} flags]
+                                         [:option {:value name} name]))
+                          (form/field override :tenant-id {:label       (tru "Tenant ID")
+                                                           :placeholder "00000000-0000-0000-0000-000000000000"})
+                          (form/select override :state {:label (tru "State")}
+                                       [[:option {:value "on"} (tru "On")]
+                                        [:option {:value "off"} (tru "Off")]
+                                        [:option {:value "inherit"} (tru "Follow default")]])
+                          [:div {:class "mt-4"}
+                           (form/submit override)]))
+             [:ul {:class ["divide-y" "divide-border-subtle"]}
+              (let [overrides (flag/list-overrides flagger)]
+                (for [f flags]
+                  (flag-row f overrides)))])))]))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn save
+  [request]
+  (span/with-span! {:name ::save}
+    (let [params (get-in request [:parameters :form])
+          f      (form/build request (save-config))]
+      (cond
+        (not (role/request-platform-admin? request))
+        bits.response/forbidden-response
+
+        (not (:success? f))
+        (morph/respond (flags-view request))
+
+        :else
+        (do
+          (flag/save-flag! (mw/request->flags request)
+                           {:name            (:name params)
+                            :enabled         (= "true" (:enabled params))
+                            :rollout-percent (parse-long (:rollout-percent params))})
+          (morph/respond (flags-view request)))))))
+
+(defn override
+  [request]
+  (span/with-span! {:name ::override}
+    (let [params (get-in request [:parameters :form])
+          f      (form/build request (override-config))]
+      (cond
+        (not (role/request-platform-admin? request))
+        bits.response/forbidden-response
+
+        (not (:success? f))
+        (morph/respond (flags-view request))
+
+        :else
+        (do
+          (flag/set-override! (mw/request->flags request)
+                              (:flag params)
+                              (parse-uuid (:tenant-id params))
+                              (case (:state params)
+                                "on"      true
+                                "off"     false
+                                "inherit" nil))
+          (morph/respond (flags-view request)))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/flag
+   :routes  [["/flags" (assoc (morph/morphable ui/layout flags-view)
+                              :bits/page {:page/title "Feature flags"})]]
+   :actions {:flag/override {:handler override
+                             :params  [[:flag :string]
+                                       [:tenant-id :string]
+                                       [:state [:enum "on" "off" "inherit"]]]}
+             :flag/save     {:handler save
+                             :params  [[:name :string]
+                                       [:rollout-percent :string]
+                                       [:enabled {:optional true} :string]]}}})
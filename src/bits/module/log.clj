@@ -0,0 +1,103 @@
+(ns bits.module.log
+  (:require
+   [bits.auth.role :as role]
+   [bits.locale :refer [tru]]
+   [bits.log.tail :as log.tail]
+   [bits.middleware :as mw]
+   [bits.morph :as morph]
+   [bits.ui :as ui]
+   [clojure.string :as str]))
+
+;;; ----------------------------------------------------------------------------
+;;; Access
+;;;
+;;; Logs cover every tenant, so only platform admins may watch them.
+
+;;; ----------------------------------------------------------------------------
+;;; Filter
+
+(def ^:private shown
+  200)
+
+(defn- query-filter
+  [request]
+  (let [{:strs [level logger tenant]} (:query-params request)
+        level                         (keyword level)]
+    {:level     (when (some #{level} log.tail/levels) level)
+     :logger    (some-> logger str/trim not-empty)
+     :tenant-id (some-> tenant str/trim not-empty)}))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(def ^:private level-classes
+  {:trace ["text-muted"]
+   :debug ["text-muted"]
+   :info  ["text-secondary"]
+   :warn  ["text-yellow-400"]
+   :error ["text-red-400"]})
+
+(defn- filter-form
+  [{:keys [level logger tenant-id]}]
+  [:form {:method "get"
+          :action "/logs"
+          :class  ["flex" "flex-wrap" "items-end" "gap-3"]}
+   [:label {:class ["text-sm" "text-secondary"]}
+    (tru "Level")
+    [:select {:name  "level"
+              :class ["block" "mt-1" "rounded-md" "px-2" "py-1.5" "bg-surface-raised" "text-primary"]}
+     [:option {:value "" :selected (nil? level)} (tru "Any")]
+     (for [l log.tail/levels]
+       [:option {:value (name l) :selected (= l level)} (str/upper-case (name l))])]]
+   [:label {:class ["text-sm" "text-secondary"]}
+    (tru "Logger")
+    (ui/input {:name        "logger"
+               :value       logger
+               :placeholder "bits.outbox"
+               :class       ["block" "mt-1" "rounded-md" "px-2" "py-1.5"]})]
+   [:label {:class ["text-sm" "text-secondary"]}
+    (tru "Tenant ID")
+    (ui/input {:name        "tenant"
+               :value       tenant-id
+               :placeholder "00000000-0000-0000-0000-000000000000"
+               :class       ["block" "mt-1" "rounded-md" "px-2" "py-1.5"]})]
+   (ui/button-secondary {} (tru "Filter"))])
+
+(defn- entry-row
+  [{:keys [at data level logger message]}]
+  [:tr {:class ["align-top"]}
+   [:td {:class ["py-1" "pr-3" "whitespace-nowrap" "text-muted"]} (str at)]
+   [:td {:class (into ["py-1" "pr-3"] (level-classes level))} (str/upper-case (name level))]
+   [:td {:class ["py-1" "pr-3" "text-secondary"]} logger]
+   [:td {:class ["py-1" "text-primary" "break-all"]}
+    message
+    (when (seq data)
+      [:span {:class ["ml-2" "text-muted"]} (pr-str data)])]])
+
+(defn logs-view
+  [request]
+  (list
+   (ui/nav-header request "/logs")
+   (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+     [:div {:class ["w-full" "space-y-6"]}
+      (ui/page-title {:class "text-2xl"} (tru "Logs"))
+      (if-not (role/request-platform-admin? request)
+        (ui/text-muted {} (tru "Only platform admins can watch logs."))
+        (let [criteria (query-filter request)
+              entries  (take shown (log.tail/entries (mw/request->log-tail request) criteria))]
+          (list
+           (filter-form criteria)
+           (if (empty? entries)
+             (ui/text-muted {} (tru "Nothing logged yet."))
+             [:table {:class ["w-full" "font-mono" "text-xs"]}
+              [:tbody
+               (map entry-row entries)]]))))])))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name   :bits.module/log
+   :routes [["/logs" (assoc (morph/morphable ui/layout logs-view
+                                             {:refresh (comp log.tail/refresh-mult mw/request->log-tail)})
+                            :bits/page {:page/title "Logs"})]]})
@@ -0,0 +1,74 @@
+(ns bits.module.maintenance
+  (:require
+   [bits.auth.role :as role]
+   [bits.form :as form]
+   [bits.locale :refer [tru]]
+   [bits.maintenance :as maintenance]
+   [bits.middleware :as mw]
+   [bits.morph :as morph]
+   [bits.response]
+   [bits.ui :as ui]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(def ^:private button-classes
+  ["rounded-md" "px-3" "py-1.5" "text-sm/6" "font-semibold"
+   "text-primary" "bg-surface-hover" "hover:bg-surface-raised"])
+
+(defn maintenance-view
+  [request]
+  (let [flagger   (mw/request->flags request)
+        tenant-id (get-in request [:session/realm :tenant/id])]
+    (list
+     (ui/nav-header request "/maintenance")
+     (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+       [:div {:class ["w-full" "sm:max-w-md" "space-y-6"]}
+        (ui/page-title {:class "text-2xl"} (tru "Maintenance"))
+        (cond
+          (not (role/request-tenant-admin? request))
+          (ui/text-muted {} (tru "Only admins can take this Bits offline."))
+
+          (maintenance/switched-off? flagger tenant-id)
+          (list
+           (ui/text-muted {} (tru "Your storefront is offline. Visitors see a maintenance page; admins see everything as usual."))
+           (form/action-button :maintenance/online {:class button-classes}
+             (tru "Bring it back online")))
+
+          (maintenance/on? flagger tenant-id)
+          (ui/text-muted {} (tru "Bits is down for maintenance. Your storefront will be back when we are."))
+
+          :else
+          (list
+           (ui/text-muted {} (tru "Take your storefront offline while you make changes. Admins can still see everything."))
+           (form/action-button :maintenance/offline {:class button-classes}
+             (tru "Take it offline"))))]))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn- switching
+  [offline?]
+  (fn [request]
+    (span/with-span! {:name ::switch}
+      (if-not (role/request-tenant-admin? request)
+        bits.response/forbidden-response
+        (do
+          (maintenance/switch! (mw/request->flags request)
+                               (get-in request [:session/realm :tenant/id])
+                               offline?)
+          (morph/respond (maintenance-view request)))))))
+
+(def offline (switching true))
+(def online (switching false))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/maintenance
+   :routes  [["/maintenance" (assoc (morph/morphable ui/layout maintenance-view)
+                                    :bits/page {:page/title "Maintenance"})]]
+   :actions {:maintenance/offline offline
+             :maintenance/online  online}})
@@ -0,0 +1,278 @@
+(ns bits.module.moderation
+  (:require
+   [bits.auth.role :as role]
+   [bits.datomic :as datomic]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.moderation :as moderation]
+   [bits.morph :as morph]
+   [bits.page :as page]
+   [bits.pagination :as pagination]
+   [bits.postgres.report :as postgres.report]
+   [bits.postgres.review :as postgres.review]
+   [bits.response]
+   [bits.review :as review]
+   [bits.ui :as ui]
+   [clojure.string :as str]
+   [datomic.api :as d]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Access
+;;;
+;;; Moderation spans every tenant, so like feature flags it's limited to
+;;; platform admins. Reports are filed from creator realms, about what's in
+;;; them.
+
+(defn- creator-realm?
+  [request]
+  (= :realm.type/creator (get-in request [:session/realm :realm/type])))
+
+;;; ----------------------------------------------------------------------------
+;;; Targets
+
+(def ^:private kinds
+  {:page "page" :review "review" :tenant "tenant"})
+
+(defn- parse-target
+  "The target type and UUID of a public ID, if it names something reportable."
+  [s]
+  (some (fn [[kind target-type]]
+          (some->> (identifier/parse-prefixed kind s) (vector target-type)))
+        kinds))
+
+(defn- exists?
+  [request target-type target-id]
+  (let [tenant-id (get-in request [:session/realm :tenant/id])]
+    (case target-type
+      "page"   (some? (page/lookup (mw/request->db request) tenant-id target-id))
+      "review" (some? (review/lookup (mw/request->reviews request) tenant-id target-id))
+      "tenant" (= tenant-id target-id))))
+
+(defn- reason-label
+  [reason]
+  (case reason
+    "abuse"         (tru "Harassment or abuse")
+    "illegal"       (tru "Illegal content")
+    "impersonation" (tru "Pretending to be someone else")
+    "other"         (tru "Something else")
+    "spam"          (tru "Spam")))
+
+;;; ----------------------------------------------------------------------------
+;;; Reporting
+
+(defn report-form
+  "A collapsed form for reporting the target, named by its public ID."
+  [request target]
+  (let [f (form/build request {})]
+    [:details {:class ["text-xs" "text-muted"]}
+     [:summary {:class ["cursor-pointer" "hover:text-secondary"]} (tru "Report")]
+     (form/form f :report/file {:class ["mt-2" "space-y-2" "rounded-xl" "p-4"]}
+                [:input {:type "hidden" :name "target" :value target}]
+                [:input {:type "hidden" :name "return-to" :value (:uri request)}]
+                (form/select f :reason {:label (tru "Reason")}
+                             (for [reason (sort moderation/reasons)]
+                               [:option {:value reason} (reason-label reason)]))
+                (form/textarea f :details {:label (tru "Anything else we should know?")
+                                           :rows  2})
+                (ui/button-secondary {} (tru "Send report")))]))
+
+(defn- reported-view
+  [request return-to]
+  (list
+   (ui/nav-header request return-to)
+   (ui/page-center {:class "space-y-4"}
+     (ui/page-title {:class "text-2xl"} (tru "Thanks for letting us know"))
+     (ui/text-muted {} (tru "A moderator will take a look."))
+     [:a {:href return-to :class ["text-sm" "text-accent" "hover:text-accent-dim"]}
+      (tru "Go back")])))
+
+(defn report-view
+  "Lets visitors report the whole tenant."
+  [request]
+  (list
+   (ui/nav-header request "/report")
+   (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+     [:div {:class ["w-full" "sm:max-w-md" "space-y-6"]}
+      (ui/page-title {:class "text-2xl"} (tru "Report this Bits"))
+      (if (and (creator-realm? request) (get-in request [:session/user :user/id]))
+        (report-form request (identifier/prefixed :tenant (get-in request [:session/realm :tenant/id])))
+        (ui/text-muted {} (tru "Sign in to report.")))])))
+
+(defn file
+  [request]
+  (span/with-span! {:name ::file}
+    (let [params                  (get-in request [:parameters :form])
+          [target-type target-id] (parse-target (:target params))
+          return-to               (if (re-matches #"/(?!/).*" (str (:return-to params)))
+                                    (:return-to params)
+                                    "/")]
+      (cond
+        (not (and (creator-realm? request) (get-in request [:session/user :user/id])))
+        bits.response/forbidden-response
+
+        (not (and target-type (exists? request target-type target-id)))
+        bits.response/not-found-response
+
+        :else
+        (do
+          (moderation/file! (mw/request->moderator request)
+                            {:details     (some-> (:details params) str/trim)
+                             :reason      (:reason params)
+                             :reporter-id (get-in request [:session/user :user/id])
+                             :target-id   target-id
+                             :target-type target-type
+                             :tenant-id   (get-in request [:session/realm :tenant/id])})
+          (morph/respond (reported-view request return-to)))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Queue
+
+(defn- describe
+  "What the report is about, as the moderator needs to see it."
+  [request {::postgres.report/keys [target-id target-type tenant-id]}]
+  (case target-type
+    "page"   (:page/title (page/lookup (mw/request->db request) tenant-id target-id))
+    "review" (some-> (review/lookup (mw/request->reviews request) tenant-id target-id)
+                     ::postgres.review/body)
+    "tenant" nil))
+
+(defn- decision-button
+  [request action report label]
+  (form/form (form/build request {}) action {}
+             [:input {:type "hidden" :name "id" :value (identifier/prefixed :report (::postgres.report/id report))}]
+             (ui/button-secondary {} label)))
+
+(defn- report-row
+  [request report]
+  (let [{:creator/keys [handle]
+         :tenant/keys  [region]} (d/pull (mw/request->db request) [:creator/handle :tenant/region]
+                                         [:tenant/id (::postgres.report/tenant-id report)])]
+    [:li {:class ["py-4" "space-y-2"]}
+     [:div {:class ["flex" "items-center" "justify-between" "gap-4"]}
+      [:p {:class ["text-sm" "font-medium" "text-primary"]}
+       (str (::postgres.report/target-type report) " · @" handle)
+       (when region
+         (str " · " (str/upper-case region)))]
+      [:p {:class ["text-xs" "text-muted"]}
+       (reason-label (::postgres.report/reason report))
+       (when-not (::postgres.report/reporter-id report)
+         (str " · " (tru "flagged automatically")))]]
+     (when-let [text (not-empty (describe request report))]
+       [:p {:class ["text-sm" "text-secondary" "line-clamp-3"]} text])
+     (when-not (str/blank? (::postgres.report/details report))
+       [:p {:class ["text-xs" "text-muted" "whitespace-pre-line"]} (::postgres.report/details report)])
+     [:div {:class ["flex" "gap-2"]}
+      (decision-button request :moderation/dismiss report (tru "Dismiss"))
+      (when-not (= "tenant" (::postgres.report/target-type report))
+        (decision-button request :moderation/hide report (tru "Hide")))
+      (decision-button request :moderation/suspend report (tru "Suspend tenant"))]]))
+
+(defn- suspended-row
+  [request {:keys [creator/handle tenant/id tenant/suspended-at]}]
+  [:li {:class ["flex" "items-center" "justify-between" "gap-4" "py-3"]}
+   [:div
+    [:p {:class ["text-sm" "font-medium" "text-primary"]} (str "@" handle)]
+    [:p {:class ["text-xs" "text-muted"]} (tru "Suspended {0}" (str suspended-at))]]
+   (form/form (form/build request {}) :moderation/reinstate {}
+              [:input {:type "hidden" :name "tenant" :value (identifier/prefixed :tenant id)}]
+              (ui/button-secondary {} (tru "Reinstate")))])
+
+(defn queue-view
+  [request]
+  (list
+   (ui/nav-header request "/moderation")
+   (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+     [:div {:class ["w-full" "sm:max-w-2xl" "space-y-8"]}
+      (ui/page-title {:class "text-2xl"} (tru "Moderation"))
+      (if-not (role/request-platform-admin? request)
+        (ui/text-muted {} (tru "Only platform admins can moderate."))
+        (let [{:keys [items next-cursor]} (moderation/queue (mw/request->moderator request)
+                                                            (pagination/page-request (:query-params request)))
+              suspended                   (moderation/suspended-tenants (mw/request->db request))]
+          (list
+           (if (empty? items)
+             (ui/text-muted {} (tru "No open reports."))
+             [:ul {:class ["divide-y" "divide-border-subtle"]}
+              (map #(report-row request %) items)])
+           (when next-cursor
+             [:a {:href  (str "/moderation?cursor=" next-cursor)
+                  :class ["text-sm" "text-secondary" "hover:text-primary"]}
+              (tru "More reports")])
+           (when (seq suspended)
+             (list
+              (ui/card-title {} (tru "Suspended"))
+              [:ul {:class ["divide-y" "divide-border-subtle"]}
+               (map #(suspended-row request %) suspended)])))))])))
+
+;;; ----------------------------------------------------------------------------
+;;; Decisions
+
+(defn- fresh
+  "The request with a database that sees the decision just made."
+  [request]
+  (assoc request ::mw/db (d/db (datomic/conn (mw/request->datomic request)))))
+
+(defn- deciding
+  [decide!]
+  (fn [request]
+    (span/with-span! {:name ::decide}
+      (let [moderator (mw/request->moderator request)
+            report    (some->> (identifier/parse-prefixed :report (get-in request [:parameters :form :id]))
+                               (moderation/lookup moderator))]
+        (cond
+          (not (role/request-platform-admin? request))
+          bits.response/forbidden-response
+
+          (nil? report)
+          bits.response/not-found-response
+
+          :else
+          (do
+            (decide! moderator report (get-in request [:session/user :user/id]))
+            (morph/respond (queue-view (fresh request)))))))))
+
+(def dismiss (deciding moderation/dismiss!))
+(def hide (deciding moderation/hide!))
+(def suspend (deciding moderation/suspend!))
+
+(defn reinstate
+  [request]
+  (span/with-span! {:name ::reinstate}
+    (let [tenant-id (identifier/parse-prefixed :tenant (get-in request [:parameters :form :tenant]))]
+      (cond
+        (not (role/request-platform-admin? request))
+        bits.response/forbidden-response
+
+        (not (and tenant-id (moderation/reinstate! (mw/request->moderator request)
+                                                   tenant-id
+                                                   (get-in request [:session/user :user/id]))))
+        bits.response/not-found-response
+
+        :else
+        (morph/respond (queue-view (fresh request)))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/moderation
+   :routes  [["/moderation" (assoc (morph/morphable ui/layout queue-view)
+                                   :bits/page {:page/title "Moderation"})]
+             ["/report" (assoc (morph/morphable ui/layout report-view)
+                               :bits/page {:page/title "Report"})]]
+   :actions {:moderation/dismiss   {:handler dismiss
+                                    :params  [[:id :string]]}
+             :moderation/hide      {:handler hide
+                                    :params  [[:id :string]]}
+             :moderation/reinstate {:handler reinstate
+                                    :params  [[:tenant :string]]}
+             :moderation/suspend   {:handler suspend
+                                    :params  [[:id :string]]}
+             :report/file          {:handler file
+                                    :params  [[:target :string]
+                                              [:return-to :string]
+                                              [:reason (into [:enum] (sort moderation/reasons))]
+                                              [:details {:optional true} :string]]}}})
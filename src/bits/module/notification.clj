@@ -0,0 +1,111 @@
+(ns bits.module.notification
+  (:require
+   [bits.coerce :as coerce]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.morph :as morph]
+   [bits.notification :as notification]
+   [bits.pagination :as pagination]
+   [bits.postgres.notification :as postgres.notification]
+   [bits.response]
+   [bits.ui :as ui]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Messages
+
+(defn message
+  [{::postgres.notification/keys [data kind]}]
+  (case kind
+    "api-key.issued"  (tru "API key \"{0}\" was created." (:name data))
+    "api-key.revoked" (tru "API key \"{0}\" was revoked." (:name data))
+    "member.joined"   (tru "{0} joined through single sign-on." (:email data))
+    "member.removed"  (tru "{0} was removed." (:email data))
+    "member.restored" (tru "{0} was added back." (:email data))))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn- notification-row
+  [request n]
+  (let [f     (form/build request {})
+        read? (some? (::postgres.notification/read-at n))]
+    [:li {:class ["flex" "items-center" "justify-between" "gap-4" "py-3"]}
+     [:div {:class ["min-w-0"]}
+      [:p {:class (into ["text-sm"] (if read? ["text-secondary"] ["font-medium" "text-primary"]))}
+       (message n)]
+      [:p {:class ["text-xs" "text-muted"]}
+       (str (::postgres.notification/created-at n))]]
+     (when-not read?
+       (form/form f :notification/read {}
+                  [:input {:type  "hidden"
+                           :name  "id"
+                           :value (identifier/prefixed :notification (::postgres.notification/id n))}]
+                  (ui/button-secondary {} (tru "Mark read"))))]))
+
+(defn notifications-view
+  [request]
+  (let [user-id   (get-in request [:session/user :user/id])
+        tenant-id (get-in request [:session/realm :tenant/id])]
+    (list
+     (ui/nav-header request "/notifications")
+     (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+       [:div {:class ["w-full" "sm:max-w-md" "space-y-6"]}
+        (ui/page-title {:class "text-2xl"} (tru "Notifications"))
+        (if-not user-id
+          (ui/text-muted {} (tru "Sign in to see your notifications."))
+          (let [notifier                    (mw/request->notifications request)
+                {:keys [items next-cursor]} (notification/list-notifications
+                                             notifier
+                                             tenant-id
+                                             user-id
+                                             (pagination/page-request (:query-params request)))]
+            (if (empty? items)
+              (ui/text-muted {} (tru "Nothing new."))
+              (list
+               (form/action-button :notification/read-all
+                 {:class ["text-sm" "text-secondary" "hover:text-primary" "cursor-pointer"]}
+                 (tru "Mark all read"))
+               [:ul {:class ["divide-y" "divide-border-subtle"]}
+                (for [n items]
+                  (notification-row request n))]
+               (when next-cursor
+                 [:a {:href  (str "/notifications?cursor=" next-cursor)
+                      :class ["text-sm" "text-secondary" "hover:text-primary"]}
+                  (tru "Older notifications")])))))]))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn- mark-read
+  [request id]
+  (let [user-id   (get-in request [:session/user :user/id])
+        tenant-id (get-in request [:session/realm :tenant/id])]
+    (if (nil? user-id)
+      bits.response/forbidden-response
+      (do
+        (notification/mark-read! (mw/request->notifications request) tenant-id user-id id)
+        (morph/respond (notifications-view request))))))
+
+(defn read-one
+  [request]
+  (span/with-span! {:name ::read-one}
+    (mark-read request (get-in request [:parameters :form :id]))))
+
+(defn read-all
+  [request]
+  (span/with-span! {:name ::read-all}
+    (mark-read request nil)))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/notification
+   :routes  [["/notifications" (assoc (morph/morphable ui/layout notifications-view)
+                                      :bits/page {:page/title "Notifications"})]]
+   :actions {:notification/read     {:handler read-one
+                                     :params  [[:id (coerce/public-id :notification)]]}
+             :notification/read-all read-all}})
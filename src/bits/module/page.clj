@@ -0,0 +1,616 @@
+(ns bits.module.page
+  (:require
+   [bits.activity :as activity]
+   [bits.anomaly :as anom]
+   [bits.auth.role :as role]
+   [bits.comment :as comment]
+   [bits.datomic :as datomic]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.moderation :as moderation]
+   [bits.module.comment :as module.comment]
+   [bits.module.creator :as creator]
+   [bits.module.moderation :as module.moderation]
+   [bits.morph :as morph]
+   [bits.outbox :as outbox]
+   [bits.page :as page]
+   [bits.plugin :as plugin]
+   [bits.postgres.comment :as postgres.comment]
+   [bits.postgres.page-revision :as postgres.page-revision]
+   [bits.request :as request]
+   [bits.response]
+   [bits.revision :as revision]
+   [bits.schedule :as schedule]
+   [bits.ui :as ui]
+   [clojure.string :as str]
+   [datomic.api :as d]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.time LocalDateTime ZoneOffset)
+   (java.time.format DateTimeFormatter)
+   (java.util Date)))
+
+;;; ----------------------------------------------------------------------------
+;;; Helpers
+
+(defn- screen!
+  "Screens the saved page's title and the text and URLs of its blocks."
+  [request p]
+  (moderation/screen! (mw/request->moderator request)
+                      {:target-id   (:page/id p)
+                       :target-type "page"
+                       :tenant-id   (get-in request [:session/realm :tenant/id])
+                       :text        (str/join "\n" (mapcat (juxt :text :url) (page/blocks p)))
+                       :title       (:page/title p)}))
+
+(defn- saved!
+  "Screens the saved page and records it as a revision."
+  [request p]
+  (screen! request p)
+  (revision/record! (mw/request->postgres request)
+                    (get-in request [:session/realm :tenant/id])
+                    p
+                    (get-in request [:session/user :user/id])))
+
+(defn- public-id
+  [p]
+  (identifier/prefixed :page (:page/id p)))
+
+(defn- edit-path
+  [p]
+  (str "/pages/" (public-id p)))
+
+(defn- now
+  [request]
+  (schedule/now (mw/request->clock request)))
+
+(defn- status-label
+  [p now]
+  (if-not (page/published? p)
+    (tru "Draft")
+    (case (schedule/status (:page/publish-at p) (:page/unpublish-at p) now)
+      :scheduled (tru "Scheduled")
+      :ended     (tru "Ended")
+      :live      (tru "Published"))))
+
+(def ^:private picker-format
+  (DateTimeFormatter/ofPattern "yyyy-MM-dd'T'HH:mm"))
+
+(defn- ->picker
+  "A time as a datetime-local input shows it, in UTC."
+  [^Date d]
+  (some->> d .toInstant (.atOffset ZoneOffset/UTC) (.format ^DateTimeFormatter picker-format)))
+
+(defn- <-picker
+  "The UTC time from a datetime-local input, nil when it's empty, or
+  `::invalid`."
+  [s]
+  (when-not (str/blank? s)
+    (try
+      (Date/from (.toInstant (LocalDateTime/parse s) ZoneOffset/UTC))
+      (catch Exception _ ::invalid))))
+
+(defn- find-page
+  [request s]
+  (some->> (identifier/parse-prefixed :page s)
+           (page/lookup (mw/request->db request) (get-in request [:session/realm :tenant/id]))))
+
+;;; ----------------------------------------------------------------------------
+;;; Rendering
+
+(defn- block
+  [{:keys [alt text type url]}]
+  (case type
+    "heading"   [:h2 {:class ["mt-10" "text-xl" "font-semibold" "text-primary"]} text]
+    "paragraph" [:p {:class ["mt-4" "text-[0.9375rem]" "text-secondary" "leading-relaxed"]} text]
+    "image"     [:img {:src url :alt (or alt "") :class ["mt-6" "w-full" "rounded-2xl"]}]
+    "link"      [:p {:class "mt-4"}
+                 [:a {:href url :class ["text-accent" "hover:text-accent-dim"]} text]]))
+
+(defn- content
+  "The title and blocks visitors see: the pinned revision's once the page is
+  published, or else the page as it is now."
+  [request p]
+  (if-let [r (and (page/published? p)
+                  (revision/pinned (mw/request->postgres request)
+                                   (get-in request [:session/realm :tenant/id])
+                                   (:page/id p)))]
+    {:title (::postgres.page-revision/title r) :blocks (revision/blocks r)}
+    {:title (:page/title p) :blocks (page/blocks p)}))
+
+(defn- storefront-view
+  ([request p]
+   (storefront-view request p {}))
+  ([request p comment-opts]
+   (let [{:keys [blocks title]} (content request p)]
+     (list
+      (creator/bits-bar {:request request})
+      [:article {:class ["max-w-[40rem]" "w-full" "mx-auto" "px-4" "pt-24" "pb-16"]}
+       (when-not (page/live? p (now request))
+         (ui/text-muted {:class ["mb-4"]}
+           (tru "{0}. Only admins can see this page." (status-label p (now request)))))
+       (ui/page-title {:class "text-3xl"} title)
+       (map block blocks)
+       (map block (plugin/render-slot (mw/request->plugins request)
+                                      (get-in request [:session/realm :tenant/id])
+                                      "page.after-content"
+                                      {:slug (:page/slug p) :title title}))
+       (when (get-in request [:session/user :user/id])
+         [:div {:class ["mt-12"]}
+          (module.moderation/report-form request (public-id p))])
+       (when (page/live? p (now request))
+         (module.comment/section request p comment-opts))]
+      (creator/page-footer request)))))
+
+(defn storefront
+  "Serves the tenant's pages at `/{slug}`. Runs after the router finds no
+  route, and returns nil for anything that isn't a page so the not found
+  handler can take over. Drafts are shown to the tenant's admins only."
+  [request]
+  (when-let [[_ slug] (re-matches #"/([a-z0-9-]+)" (:uri request))]
+    (when-let [tenant-id (get-in request [:session/realm :tenant/id])]
+      (let [p (page/by-slug (mw/request->db request) tenant-id slug)]
+        (when (and p (or (page/live? p (now request)) (role/request-tenant-admin? request)))
+          (span/with-span! {:name ::storefront}
+            (let [view                   #(if-let [p (page/by-slug (mw/request->db %) tenant-id slug)]
+                                                (storefront-view % p)
+                                                (ui/not-found-view %))
+                  handler                (get (morph/morphable ui/layout view) (:request-method request))
+                  {:keys [blocks title]} (content request p)
+                  summary                (some #(when (= "paragraph" (:type %)) (:text %)) blocks)]
+              (when handler
+                (handler (assoc request :bits/page {:page/description summary
+                                                    :page/title       title
+                                                    :page/type        "article"}))))))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Editing views
+
+(def ^:private slug-schema
+  [:re {:error/message (tru "Lowercase letters, digits and dashes")}
+   #"^[a-z0-9]+(?:-[a-z0-9]+)*$"])
+
+(defn- create-config
+  []
+  {:schema {:slug  slug-schema
+            :title [:string {:min 1}]}
+   :submit {:idle (tru "Create page")}})
+
+(defn- save-config
+  []
+  {:schema {:blocks [:string {:min 1}]
+            :slug   slug-schema
+            :title  [:string {:min 1}]}
+   :submit {:idle    (tru "Save")
+            :success (tru "Saved")}})
+
+(defn- page-row
+  [p now]
+  [:li {:class ["flex" "items-center" "justify-between" "gap-4" "py-3"]}
+   [:div {:class ["min-w-0"]}
+    [:a {:href (edit-path p) :class ["text-sm" "font-medium" "text-primary" "truncate" "hover:text-accent"]}
+     (:page/title p)]
+    [:p {:class ["text-xs" "text-muted" "font-mono"]} (str "/" (:page/slug p))]]
+   [:span {:class ["text-xs" "text-muted"]}
+    (status-label p now)]])
+
+(defn pages-view
+  ([request]
+   (pages-view request {}))
+  ([request {:keys [error]}]
+   (let [f (cond-> (form/build request (create-config))
+             error (form/with-error error))]
+     (list
+      (ui/nav-header request "/pages")
+      (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+        [:div {:class ["w-full" "sm:max-w-2xl" "space-y-8"]}
+         (ui/page-title {:class "text-2xl"} (tru "Pages"))
+         (if-not (role/request-tenant-admin? request)
+           (ui/text-muted {} (tru "Only tenant admins can manage pages."))
+           (let [pages (page/list-pages (mw/request->db request) (get-in request [:session/realm :tenant/id]))]
+             (list
+              (ui/presence request "/pages")
+              (form/form f :page/create {:class "rounded-xl p-6"}
+                         (form/field f :title {:label       (tru "Title")
+                                               :placeholder (tru "About us")})
+                         (form/field f :slug {:label       (tru "Slug")
+                                              :placeholder "about"})
+                         [:div {:class "mt-4"}
+                          (form/submit f)])
+              (if (empty? pages)
+                (ui/text-muted {} (tru "No pages yet."))
+                [:ul {:class ["divide-y" "divide-border-subtle"]}
+                 (map #(page-row % (now request)) pages)]))))])))))
+
+(defn edit-view
+  ([request]
+   (edit-view request (find-page request (get-in request [:path-params :id])) {}))
+  ([request p {:keys [error schedule-error]}]
+   (let [f (cond-> (form/build request (save-config))
+             error (form/with-error error))]
+     (list
+      (ui/nav-header request "/pages")
+      (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+        [:div {:class ["w-full" "sm:max-w-2xl" "space-y-8"]}
+         (cond
+           (not (role/request-tenant-admin? request))
+           (ui/text-muted {} (tru "Only tenant admins can manage pages."))
+
+           (nil? p)
+           (ui/text-muted {} (tru "This page doesn''t exist or was deleted."))
+
+           :else
+           (list
+            (ui/page-title {:class "text-2xl"} (:page/title p))
+            (ui/presence request (edit-path p))
+            [:a {:href (str "/" (:page/slug p)) :class ["text-sm" "text-accent" "hover:text-accent-dim"]}
+             (if (page/live? p (now request)) (tru "View page") (tru "Preview"))]
+            [:a {:href (str (edit-path p) "/revisions") :class ["ml-4" "text-sm" "text-accent" "hover:text-accent-dim"]}
+             (tru "Revisions")]
+            (form/form f :page/save {:class "rounded-xl p-6"}
+                       [:input {:type "hidden" :name "id" :value (public-id p)}]
+                       (form/field f :title {:label (tru "Title")
+                                             :value (:page/title p)})
+                       (form/field f :slug {:label (tru "Slug")
+                                            :value (:page/slug p)})
+                       (form/textarea f :blocks {:label (tru "Content (JSON blocks)")
+                                                 :class ["font-mono"]
+                                                 :rows  16
+                                                 :value (:page/blocks p)})
+                       [:div {:class "mt-4"}
+                        (form/submit f)])
+            (let [sf (form/build request {})]
+              (form/form sf :page/schedule {:class "rounded-xl p-6 space-y-4"}
+                         [:input {:type "hidden" :name "id" :value (public-id p)}]
+                         (when schedule-error
+                           (ui/alert-error schedule-error))
+                         (ui/text-muted {}
+                           (tru "Leave either empty for no limit. Times are UTC, and the page must be published to show at all."))
+                         (form/field sf :publish-at {:label (tru "Publish at")
+                                                     :type  "datetime-local"
+                                                     :value (->picker (:page/publish-at p))})
+                         (form/field sf :unpublish-at {:label (tru "Unpublish at")
+                                                       :type  "datetime-local"
+                                                       :value (->picker (:page/unpublish-at p))})
+                         (ui/button-secondary {} (tru "Save schedule"))))
+            [:div {:class ["flex" "gap-4"]}
+             (form/form (form/build request {}) (if (page/published? p) :page/unpublish :page/publish) {}
+                        [:input {:type "hidden" :name "id" :value (public-id p)}]
+                        (ui/button-secondary {} (if (page/published? p) (tru "Unpublish") (tru "Publish"))))
+             (form/form (form/build request {}) :trash/delete {}
+                        [:input {:type "hidden" :name "id" :value (public-id p)}]
+                        (ui/button-secondary {} (tru "Delete")))]))])))))
+
+;;; ----------------------------------------------------------------------------
+;;; Revisions view
+
+(def ^:private diff-classes
+  {:added   ["border-l-2" "border-green-500" "pl-3" "text-primary"]
+   :removed ["border-l-2" "border-red-500" "pl-3" "text-muted" "line-through"]
+   :same    ["pl-3" "text-secondary"]})
+
+(defn- diff-block
+  [{:keys [block op]}]
+  [:li {:class (into ["py-1" "text-sm"] (diff-classes op))}
+   [:span {:class ["font-mono" "text-xs" "text-muted" "mr-2"]} (:type block)]
+   (or (:text block) (:alt block))
+   (when (:url block)
+     [:span {:class ["ml-2" "font-mono" "text-xs" "text-muted"]} (:url block)])])
+
+(defn- diff-field
+  [label [old new]]
+  (when old
+    [:p {:class ["text-sm"]}
+     [:span {:class ["text-secondary" "mr-2"]} label]
+     [:span {:class ["text-muted" "line-through" "mr-2"]} old]
+     [:span {:class ["text-primary"]} new]]))
+
+(defn- comparison
+  [from to]
+  (let [{:keys [blocks slug title]} (revision/diff from to)]
+    [:div {:class ["space-y-4"]}
+     [:h2 {:class ["text-lg" "font-semibold" "text-primary"]}
+      (tru "Revision {0} against {1}"
+           (::postgres.page-revision/number to)
+           (::postgres.page-revision/number from))]
+     (diff-field (tru "Title") title)
+     (diff-field (tru "Slug") slug)
+     (if (every? (comp #{:same} :op) blocks)
+       (ui/text-muted {} (tru "The blocks are unchanged."))
+       [:ol {:class ["space-y-1"]}
+        (map diff-block blocks)])]))
+
+(defn- revision-row
+  [request p r]
+  (let [number (::postgres.page-revision/number r)]
+    [:li {:class ["flex" "items-center" "justify-between" "gap-4" "py-3"]}
+     [:div {:class ["min-w-0"]}
+      [:p {:class ["text-sm" "font-medium" "text-primary"]}
+       (tru "Revision {0}" number)
+       (when (::postgres.page-revision/pinned r)
+         [:span {:class ["ml-2" "text-xs" "text-accent"]} (tru "Published")])]
+      [:p {:class ["text-xs" "text-muted"]}
+       (str (::postgres.page-revision/created-at r))]]
+     [:div {:class ["flex" "items-center" "gap-3"]}
+      [:a {:href  (str (edit-path p) "/revisions?to=" number)
+           :class ["text-sm" "text-accent" "hover:text-accent-dim"]}
+       (tru "Compare")]
+      (form/form (form/build request {}) :page/restore {}
+                 [:input {:type "hidden" :name "id" :value (public-id p)}]
+                 [:input {:type "hidden" :name "number" :value number}]
+                 (ui/button-secondary {} (tru "Restore")))
+      (form/form (form/build request {}) :page/publish {}
+                 [:input {:type "hidden" :name "id" :value (public-id p)}]
+                 [:input {:type "hidden" :name "number" :value number}]
+                 (ui/button-secondary {} (tru "Publish")))]]))
+
+(defn- compared
+  "The revisions picked by the `from` and `to` query parameters. `to`
+  defaults to the latest and `from` to the one before `to`."
+  [request revisions]
+  (let [{:strs [from to]} (:query-params request)
+        numbered          (into {} (map (juxt ::postgres.page-revision/number identity)) revisions)
+        to                (or (some-> to parse-long numbered) (first revisions))
+        from              (when to
+                            (or (some-> from parse-long numbered)
+                                (numbered (dec (::postgres.page-revision/number to)))))]
+    (when (and from to)
+      [from to])))
+
+(defn revisions-view
+  ([request]
+   (revisions-view request (find-page request (get-in request [:path-params :id])) {}))
+  ([request p {:keys [error]}]
+   (list
+    (ui/nav-header request "/pages")
+    (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+      [:div {:class ["w-full" "sm:max-w-2xl" "space-y-8"]}
+       (cond
+         (not (role/request-tenant-admin? request))
+         (ui/text-muted {} (tru "Only tenant admins can manage pages."))
+
+         (nil? p)
+         (ui/text-muted {} (tru "This page doesn''t exist or was deleted."))
+
+         :else
+         (let [revisions (revision/list-revisions (mw/request->postgres request)
+                                                  (get-in request [:session/realm :tenant/id])
+                                                  (:page/id p))]
+           (list
+            (ui/page-title {:class "text-2xl"} (tru "Revisions of {0}" (:page/title p)))
+            [:a {:href (edit-path p) :class ["text-sm" "text-accent" "hover:text-accent-dim"]}
+             (tru "Back to editing")]
+            (when error
+              (ui/alert-error error))
+            (when-let [[from to] (compared request revisions)]
+              (comparison from to))
+            (if (empty? revisions)
+              (ui/text-muted {} (tru "No revisions yet. Saving the page records one."))
+              [:ul {:class ["divide-y" "divide-border-subtle"]}
+               (map #(revision-row request p %) revisions)]))))]))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn create
+  [request]
+  (span/with-span! {:name ::create}
+    (let [params (get-in request [:parameters :form])
+          f      (form/build request (create-config))]
+      (cond
+        (not (role/request-tenant-admin? request))
+        bits.response/forbidden-response
+
+        (not (:success? f))
+        (morph/respond (pages-view request))
+
+        :else
+        (let [result (page/create! (datomic/conn (mw/request->datomic request))
+                                   (get-in request [:session/realm :tenant/id])
+                                   (assoc params :blocks "[]")
+                                   (Date.))]
+          (if (anom/anomaly? result)
+            (morph/respond (pages-view request {:error (::anom/message result)}))
+            (do
+              (saved! request result)
+              (morph/redirect (edit-path result)))))))))
+
+(defn save
+  [request]
+  (span/with-span! {:name ::save}
+    (let [params (get-in request [:parameters :form])
+          p      (find-page request (:id params))
+          f      (form/build request (save-config))]
+      (cond
+        (not (role/request-tenant-admin? request))
+        bits.response/forbidden-response
+
+        (nil? p)
+        bits.response/not-found-response
+
+        (not (:success? f))
+        (morph/respond (edit-view request p {}))
+
+        :else
+        (let [result (page/update! (datomic/conn (mw/request->datomic request))
+                                   (get-in request [:session/realm :tenant/id])
+                                   p
+                                   params)]
+          (if (anom/anomaly? result)
+            (morph/respond (edit-view request p {:error (::anom/message result)}))
+            (do
+              (saved! request result)
+              (morph/respond (edit-view request result {})))))))))
+
+(defn- pin!
+  "Pins the revision numbered in the form, or else the latest. Pages saved
+  before revisions were kept get their first one now."
+  [request p]
+  (let [postgres  (mw/request->postgres request)
+        tenant-id (get-in request [:session/realm :tenant/id])
+        number    (or (some-> (get-in request [:parameters :form :number]) parse-long)
+                      (::postgres.page-revision/number (first (revision/list-revisions postgres tenant-id (:page/id p))))
+                      (::postgres.page-revision/number (revision/record! postgres tenant-id p
+                                                                         (get-in request [:session/user :user/id]))))]
+    (revision/pin! postgres tenant-id (:page/id p) number)))
+
+(defn- unpin!
+  [request p]
+  (revision/unpin! (mw/request->postgres request)
+                   (get-in request [:session/realm :tenant/id])
+                   (:page/id p)))
+
+(defn- publishing
+  [kind tx-fn revise!]
+  (fn [request]
+    (span/with-span! {:name ::publishing}
+      (let [p         (find-page request (get-in request [:parameters :form :id]))
+            tenant-id (get-in request [:session/realm :tenant/id])
+            result    (when (and p (role/request-tenant-admin? request))
+                        (revise! request p))]
+        (cond
+          (not (role/request-tenant-admin? request))
+          bits.response/forbidden-response
+
+          (nil? p)
+          bits.response/not-found-response
+
+          (anom/anomaly? result)
+          (morph/respond (edit-view request p {:error (::anom/message result)}))
+
+          :else
+          (let [{:keys [db-after]} @(d/transact (datomic/conn (mw/request->datomic request)) (tx-fn p))]
+            (activity/record! (mw/request->activities request) tenant-id
+                              (get-in request [:session/user :user/id])
+                              kind {:title (:page/title p)})
+            (outbox/enqueue! (mw/request->postgres request) tenant-id
+                             (get-in request [:session/user :user/id])
+                             kind {:page-id (:page/id p)})
+            (morph/respond (edit-view (assoc request ::mw/db db-after)
+                                      (page/lookup db-after tenant-id (:page/id p))
+                                      {}))))))))
+
+(def publish
+  (publishing "page.published" #(page/publish-tx % (Date.)) pin!))
+
+(def unpublish
+  (publishing "page.unpublished" page/unpublish-tx unpin!))
+
+(defn restore
+  "Saves an old revision's content as the page's newest revision."
+  [request]
+  (span/with-span! {:name ::restore}
+    (let [{:keys [id number]} (get-in request [:parameters :form])
+          p                   (find-page request id)
+          tenant-id           (get-in request [:session/realm :tenant/id])
+          r                   (when (and p (parse-long number))
+                                (revision/lookup (mw/request->postgres request) tenant-id (:page/id p) (parse-long number)))]
+      (cond
+        (not (role/request-tenant-admin? request))
+        bits.response/forbidden-response
+
+        (nil? r)
+        bits.response/not-found-response
+
+        :else
+        (let [result (page/update! (datomic/conn (mw/request->datomic request))
+                                   tenant-id
+                                   p
+                                   {:blocks (::postgres.page-revision/blocks r)
+                                    :slug   (::postgres.page-revision/slug r)
+                                    :title  (::postgres.page-revision/title r)})]
+          (if (anom/anomaly? result)
+            (morph/respond (revisions-view request p {:error (::anom/message result)}))
+            (do
+              (saved! request result)
+              (morph/respond (revisions-view request result {})))))))))
+
+(defn post-comment
+  [request]
+  (span/with-span! {:name ::post-comment}
+    (let [params    (get-in request [:parameters :form])
+          p         (find-page request (:page-id params))
+          parent-id (some->> (:parent-id params) (identifier/parse-prefixed :comment))]
+      (if-not (and p (page/live? p (now request)))
+        bits.response/not-found-response
+        (let [result (comment/post! (mw/request->comments request)
+                                    (mw/request->db request)
+                                    {:author-id   (get-in request [:session/user :user/id])
+                                     :author-name (:author-name params)
+                                     :body        (:body params)
+                                     :honeypot    (:website params)
+                                     :ip-address  (request/remote-addr request)
+                                     :page-id     (:page/id p)
+                                     :parent-id   parent-id
+                                     :tenant-id   (get-in request [:session/realm :tenant/id])})]
+          (morph/respond
+           (storefront-view request p (if (anom/anomaly? result)
+                                        {:error (::anom/message result)}
+                                        ;; The honeypot's catch is thanked like anyone held for approval.
+                                        {:posted (or (::postgres.comment/status result) "pending")}))))))))
+
+(defn schedule
+  [request]
+  (span/with-span! {:name ::schedule}
+    (let [params       (get-in request [:parameters :form])
+          p            (find-page request (:id params))
+          publish-at   (<-picker (:publish-at params))
+          unpublish-at (<-picker (:unpublish-at params))]
+      (cond
+        (not (role/request-tenant-admin? request))
+        bits.response/forbidden-response
+
+        (nil? p)
+        bits.response/not-found-response
+
+        (some #{::invalid} [publish-at unpublish-at])
+        (morph/respond (edit-view request p {:schedule-error (tru "Times must be a date and time.")}))
+
+        :else
+        (let [result (page/schedule! (datomic/conn (mw/request->datomic request))
+                                     (get-in request [:session/realm :tenant/id])
+                                     p
+                                     {:publish-at publish-at :unpublish-at unpublish-at})]
+          (if (anom/anomaly? result)
+            (morph/respond (edit-view request p {:schedule-error (::anom/message result)}))
+            (morph/respond (edit-view request result {}))))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/page
+   :routes  [["/pages" (assoc (morph/morphable ui/layout pages-view {:presence? true})
+                              :bits/page {:page/title "Pages"})]
+             ["/pages/:id" (assoc (morph/morphable ui/layout edit-view {:presence? true})
+                                  :bits/page {:page/title "Edit page"})]
+             ["/pages/:id/revisions" (assoc (morph/morphable ui/layout revisions-view)
+                                            :bits/page {:page/title "Page revisions"})]]
+   :actions {:page/comment   {:handler post-comment
+                              :params  [[:page-id :string]
+                                        [:parent-id {:optional true} :string]
+                                        [:author-name {:optional true} :string]
+                                        [:body {:optional true} :string]
+                                        [:website {:optional true} :string]]}
+             :page/create    {:handler create
+                              :params  [[:title :string]
+                                        [:slug :string]]}
+             :page/publish   {:handler publish
+                              :params  [[:id :string]
+                                        [:number {:optional true} :string]]}
+             :page/restore   {:handler restore
+                              :params  [[:id :string]
+                                        [:number :string]]}
+             :page/save      {:handler save
+                              :params  [[:id :string]
+                                        [:title :string]
+                                        [:slug :string]
+                                        [:blocks :string]]}
+             :page/schedule  {:handler schedule
+                              :params  [[:id :string]
+                                        [:publish-at {:optional true} :string]
+                                        [:unpublish-at {:optional true} :string]]}
+             :page/unpublish {:handler unpublish
+                              :params  [[:id :string]]}}})
@@ -4,6 +4,7 @@
    [bits.html :as html]
    [bits.locale :refer [tru]]
    [bits.middleware :as mw]
+   [bits.module.creator :as creator]
    [bits.morph :as morph]
    [bits.ui :as ui]
    [clojure.string :as str]
@@ -251,8 +252,7 @@
 (def module
   {:name    :bits.module/platform
    :routes  [["/"         (assoc (morph/morphable home-layout home-view)
-                                 :bits/page (fn [request]
-                                              {:page/title (-> request :session/realm :creator/display-name)}))]
+                                 :bits/page creator/page)]
              ["/counter"  (assoc (morph/morphable ui/layout counter-view)
                                  :bits/page {:page/title "Counter"})]
              ["/cursors"  (assoc (morph/morphable ui/layout cursors-view {:on-close remove-cursor!})
@@ -261,15 +261,20 @@
                                  :bits/page {:page/title "Forms"})]
              ["/redirect" (assoc (morph/morphable ui/layout redirect-view)
                                  :bits/page {:page/title "Redirect"})]]
-   :actions {:counter/dec   (fn [_req] (swap! !counter update :count dec))
-             :counter/inc   (fn [_req] (swap! !counter update :count inc))
-             :cursor/move   (fn [request]
-                              (let [channel-id (get-in request [:params "channel"])
-                                    x          (parse-long (get-in request [:params "x"] "0"))
-                                    y          (parse-long (get-in request [:params "y"] "0"))]
-                                (when (and channel-id x y (< x 10000) (< y 10000))
-                                  (update-cursor! channel-id x y))))
-             :demo/redirect (fn [_req] (morph/redirect "https://jcf.dev"))
-             :demo/validate (fn [request]
-                              (let [f (form/build request form-config)]
-                                (morph/respond (form-view request f))))}})
+   :actions {:counter/dec   {:handler      (fn [_req] (swap! !counter update :count dec))
+                             :transaction? false}
+             :counter/inc   {:handler      (fn [_req] (swap! !counter update :count inc))
+                             :transaction? false}
+             :cursor/move   {:handler      (fn [request]
+                                             (let [channel-id (get-in request [:params "channel"])
+                                                   x          (parse-long (get-in request [:params "x"] "0"))
+                                                   y          (parse-long (get-in request [:params "y"] "0"))]
+                                               (when (and channel-id x y (< x 10000) (< y 10000))
+                                                 (update-cursor! channel-id x y))))
+                             :transaction? false}
+             :demo/redirect {:handler      (fn [_req] (morph/redirect "https://jcf.dev"))
+                             :transaction? false}
+             :demo/validate {:handler      (fn [request]
+                                             (let [f (form/build request form-config)]
+                                               (morph/respond (form-view request f))))
+                             :transaction? false}}})
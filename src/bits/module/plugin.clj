@@ -0,0 +1,205 @@
+(ns bits.module.plugin
+  "Installing plugins and running their request hook.
+
+  Like sites and redirects, plugins only see requests on the tenant's own
+  domain for paths the app doesn't route, so a broken plugin can't stand
+  between an admin and this page."
+  (:require
+   [bits.activity :as activity]
+   [bits.anomaly :as anom]
+   [bits.auth.role :as role]
+   [bits.coerce :as coerce]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.morph :as morph]
+   [bits.plugin :as plugin]
+   [bits.postgres.plugin :as postgres.plugin]
+   [bits.response]
+   [bits.ui :as ui]
+   [clojure.string :as str]
+   [ring.util.codec :as codec]
+   [ring.util.response :as response]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.nio.file Files)))
+
+;;; ----------------------------------------------------------------------------
+;;; Helpers
+
+(defn- record!
+  [request kind plugin-name]
+  (activity/record! (mw/request->activities request)
+                    (get-in request [:session/realm :tenant/id])
+                    (get-in request [:session/user :user/id])
+                    kind {:name plugin-name}))
+
+(defn- capability-label
+  [capability]
+  (case capability
+    "checkout" (tru "Adjust checkout totals")
+    "log"      (tru "Write to our logs")
+    "request"  (tru "Add headers to your pages")
+    "slot"     (tru "Add content to your pages")))
+
+;;; ----------------------------------------------------------------------------
+;;; Request hook
+
+(defn wrap-plugins
+  "Adds the headers the tenant's plugins ask for to responses on creator
+  realms."
+  [handler roots]
+  (fn [request]
+    (let [segment  (second (re-find #"^/([^/]*)" (codec/url-decode (:uri request))))
+          response (handler request)]
+      (if (and response
+               (= :realm.type/creator (get-in request [:session/realm :realm/type]))
+               (not (contains? roots segment)))
+        (let [headers (plugin/on-request (mw/request->plugins request)
+                                         (get-in request [:session/realm :tenant/id])
+                                         request)]
+          (update response :headers #(merge headers %)))
+        response))))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn- install-form
+  [request]
+  [:form {:method  "post"
+          :action  "/plugins/install"
+          :enctype "multipart/form-data"
+          :class   ["space-y-4" "rounded-xl" "p-6" "bg-surface-raised"]}
+   [:input {:type "hidden" :name "csrf" :value (::mw/csrf request)}]
+   [:label {:class ["block" "text-sm" "text-primary"]}
+    (tru "Name")
+    (ui/input {:name "name" :required true :maxlength 80 :class ["mt-2" "block" "w-full" "rounded-md"]})]
+   [:label {:class ["block" "text-sm" "text-primary"]}
+    (tru "WebAssembly module")
+    [:input {:type     "file"
+             :name     "module"
+             :accept   ".wasm,application/wasm"
+             :required true
+             :class    ["mt-2" "block" "w-full" "text-sm" "text-secondary"]}]]
+   [:fieldset {:class ["space-y-2"]}
+    [:legend {:class ["text-sm" "font-medium" "text-primary"]} (tru "Allow it to")]
+    (for [capability (sort plugin/capabilities)]
+      [:label {:class ["flex" "items-center" "gap-2" "text-sm" "text-secondary"]}
+       [:input {:type "checkbox" :name "capabilities" :value capability}]
+       (capability-label capability)])]
+   (ui/button-primary {:type "submit"} (tru "Install"))])
+
+(defn- toggle-button
+  [request p]
+  (let [enabled? (::postgres.plugin/enabled p)]
+    (form/form (form/build request {}) (if enabled? :plugin/disable :plugin/enable) {}
+               [:input {:type "hidden" :name "id" :value (identifier/prefixed :plugin (::postgres.plugin/id p))}]
+               (ui/button-secondary {} (if enabled? (tru "Disable") (tru "Enable"))))))
+
+(defn- plugin-row
+  [request p]
+  [:li {:class ["py-4" "space-y-2"]}
+   [:div {:class ["flex" "items-center" "justify-between" "gap-4"]}
+    [:div
+     [:p {:class ["text-sm" "font-medium" "text-primary"]} (::postgres.plugin/name p)]
+     [:p {:class ["text-xs" "text-muted"]}
+      (if-let [allowed (seq (::postgres.plugin/capabilities p))]
+        (str/join ", " (map capability-label allowed))
+        (tru "Allowed to do nothing"))]]
+    [:div {:class ["flex" "gap-2"]}
+     (toggle-button request p)
+     (form/form (form/build request {}) :plugin/uninstall {}
+                [:input {:type "hidden" :name "id" :value (identifier/prefixed :plugin (::postgres.plugin/id p))}]
+                (ui/button-secondary {} (tru "Uninstall")))]]
+   (when-let [error (::postgres.plugin/last-error p)]
+     (ui/text-muted {:class ["text-xs"]}
+       (tru "Last failed {0}: {1}" (str (::postgres.plugin/failed-at p)) error)))])
+
+(defn plugins-view
+  [request]
+  (let [plugins (mw/request->plugins request)
+        reason  (some #{(keyword (get-in request [:query-params "error"]))} plugin/reasons)]
+    (list
+     (ui/nav-header request "/plugins")
+     (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+       [:div {:class ["w-full" "sm:max-w-2xl" "space-y-8"]}
+        (ui/page-title {:class "text-2xl"} (tru "Plugins"))
+        (if-not (role/request-tenant-admin? request)
+          (ui/text-muted {} (tru "Only admins can install plugins."))
+          (let [installed (plugin/list-plugins plugins (get-in request [:session/realm :tenant/id]))]
+            (list
+             (ui/text-muted {}
+               (tru "Plugins are WebAssembly modules built for version {0} of our host API. They can only do what you allow, and new ones start disabled."
+                    plugin/api-version))
+             (when reason
+               (ui/alert-error (plugin/message plugins reason)))
+             (install-form request)
+             (if (empty? installed)
+               (ui/text-muted {} (tru "No plugins yet."))
+               [:ul {:class ["divide-y" "divide-border-subtle"]}
+                (for [p installed]
+                  (plugin-row request p))]))))]))))
+
+;;; ----------------------------------------------------------------------------
+;;; Handlers
+
+(defn- as-vector
+  "Multipart fields given once are a string, and more than once a vector."
+  [v]
+  (cond
+    (nil? v)    []
+    (string? v) [v]
+    :else       v))
+
+(defn install
+  [request]
+  (span/with-span! {:name ::install}
+    (let [{:strs [capabilities module] plugin-name "name"} (:multipart-params request)]
+      (if-not (role/request-tenant-admin? request)
+        bits.response/forbidden-response
+        (let [installed (if-let [tempfile (:tempfile module)]
+                          (plugin/install! (mw/request->plugins request)
+                                           (get-in request [:session/realm :tenant/id])
+                                           plugin-name
+                                           (Files/readAllBytes (.toPath ^java.io.File tempfile))
+                                           (as-vector capabilities))
+                          (anom/incorrect {::plugin/reason :empty}))]
+          (if (anom/anomaly? installed)
+            (response/redirect (str "/plugins?error=" (name (::plugin/reason installed))) :see-other)
+            (do
+              (record! request "plugin.installed" (::postgres.plugin/name installed))
+              (response/redirect "/plugins" :see-other))))))))
+
+(defn- changing
+  [f kind]
+  (fn [request]
+    (span/with-span! {:name ::change}
+      (if-not (role/request-tenant-admin? request)
+        bits.response/forbidden-response
+        (if-let [p (f (mw/request->plugins request)
+                      (get-in request [:session/realm :tenant/id])
+                      (get-in request [:parameters :form :id]))]
+          (do
+            (record! request kind (::postgres.plugin/name p))
+            (morph/respond (plugins-view request)))
+          bits.response/not-found-response)))))
+
+(def enable (changing #(plugin/enable! %1 %2 %3 true) "plugin.enabled"))
+(def disable (changing #(plugin/enable! %1 %2 %3 false) "plugin.disabled"))
+(def uninstall (changing plugin/uninstall! "plugin.uninstalled"))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/plugin
+   :routes  [["/plugins" (assoc (morph/morphable ui/layout plugins-view)
+                                :bits/page {:page/title "Plugins"})]
+             ["/plugins/install" {:post {:handler install}}]]
+   :actions {:plugin/disable   {:handler disable
+                                :params  [[:id (coerce/public-id :plugin)]]}
+             :plugin/enable    {:handler enable
+                                :params  [[:id (coerce/public-id :plugin)]]}
+             :plugin/uninstall {:handler uninstall
+                                :params  [[:id (coerce/public-id :plugin)]]}}})
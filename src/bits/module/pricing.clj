@@ -0,0 +1,222 @@
+(ns bits.module.pricing
+  (:require
+   [bits.activity :as activity]
+   [bits.anomaly :as anom]
+   [bits.auth.role :as role]
+   [bits.catalog :as catalog]
+   [bits.coerce :as coerce]
+   [bits.discount :as discount]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.locale :as locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.module.shipping :as module.shipping]
+   [bits.money :as money]
+   [bits.morph :as morph]
+   [bits.postgres.pricing-rule :as postgres.pricing-rule]
+   [bits.pricing :as pricing]
+   [bits.response]
+   [bits.ui :as ui]
+   [clojure.string :as str]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.util Currency)))
+
+;;; ----------------------------------------------------------------------------
+;;; Helpers
+
+(def ^:private currencies
+  ["GBP" "USD" "EUR"])
+
+(defn- format-amount
+  [amount currency]
+  (money/format-price (locale/current-locale)
+                      {:money/amount amount
+                       ::money/iso   (Currency/getInstance ^String currency)}))
+
+;;; ----------------------------------------------------------------------------
+;;; Console
+;;;
+;;; Prices a made-up basket from the query string with the tenant's rules, and
+;;; a draft rule after them, so admins can see what a rule does before buyers
+;;; do.
+
+(defn- parse-basket
+  "Line items from lines like `45.00` or `45.00 sale`, or nil when one
+  doesn't read."
+  [s currency]
+  (let [lines (remove str/blank? (str/split-lines (str s)))
+        items (for [line lines
+                    :let [[price flag] (str/split (str/trim line) #"\s+")]]
+                (when-let [amount (catalog/amount price currency)]
+                  (when (contains? #{nil "sale"} flag)
+                    {:amount amount :currency currency :sale? (= "sale" flag)})))]
+    (when (and (seq items) (every? some? items))
+      (vec items))))
+
+(defn- result-row
+  [{:keys [error label off]} currency]
+  [:li {:class ["flex" "items-center" "justify-between" "gap-4" "py-2" "text-sm"]}
+   [:span {:class ["text-primary"]} label]
+   (cond
+     error      [:span {:class ["text-red-400"]} error]
+     (pos? off) [:span {:class ["text-secondary"]} (str "−" (format-amount off currency))]
+     :else      [:span {:class ["text-muted"]} (tru "Didn''t apply")])])
+
+(defn- console-results
+  [request tenant-id basket currency draft]
+  (let [items  (parse-basket basket currency)
+        parsed (when-not (str/blank? draft) (pricing/parse draft))]
+    (cond
+      (str/blank? basket)
+      nil
+
+      (nil? items)
+      (ui/alert-error (tru "Put one price on each line, followed by sale for items already reduced."))
+
+      (anom/anomaly? parsed)
+      (ui/alert-error (tru "Draft: {0}" (::anom/message parsed)))
+
+      :else
+      (let [svc                     (mw/request->pricing request)
+            rules                   (cond-> (pricing/rules svc tenant-id)
+                                      parsed (conj {:label (tru "Draft") :rule parsed}))
+            {:keys [quote results]} (pricing/run svc (discount/quote-basket items []) rules)]
+        (list
+         (if (empty? results)
+           (ui/text-muted {} (tru "No rules to run yet."))
+           [:ul {:class ["divide-y" "divide-border-subtle"]}
+            (for [result results]
+              (result-row result currency))])
+         (module.shipping/totals-summary (assoc quote :shipping 0) currency))))))
+
+(defn- console
+  [request tenant-id]
+  (let [{:strs [basket currency draft]} (:query-params request)
+        currency                        (or (some #{currency} currencies) (first currencies))]
+    [:section {:class ["space-y-4"]}
+     [:h2 {:class ["text-lg" "font-semibold" "text-primary"]} (tru "Try it")]
+     [:form {:method "get" :action "/pricing" :class ["space-y-4"]}
+      [:textarea {:name        "basket"
+                  :rows        4
+                  :placeholder "60.00\n45.00 sale"
+                  :class       ["w-full" "rounded-md" "px-3" "py-2" "bg-surface-raised" "text-primary" "font-mono" "text-sm"]}
+       basket]
+      [:div {:class ["grid" "grid-cols-3" "gap-3"]}
+       (ui/input {:name        "draft"
+                  :value       draft
+                  :placeholder (tru "A rule to try, like 10% off")
+                  :class       ["col-span-2" "rounded-md" "font-mono"]})
+       [:select {:name "currency" :class ["rounded-md" "px-2" "py-1.5" "bg-surface-raised" "text-primary"]}
+        (for [c currencies]
+          [:option {:value c :selected (= c currency)} c])]]
+      (console-results request tenant-id basket currency draft)
+      (ui/button-secondary {} (tru "Price it"))]]))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn- rule-config
+  []
+  {:schema {:label  [:string {:min 1 :max 80}]
+            :source [:string {:min 1 :max 500}]}
+   :submit {:idle    (tru "Add rule")
+            :success (tru "Rule added")}})
+
+(defn- rule-row
+  [request r]
+  [:li {:class ["flex" "items-center" "justify-between" "gap-4" "py-2" "text-sm"]}
+   [:div
+    [:p {:class ["text-primary"]} (::postgres.pricing-rule/label r)]
+    [:p {:class ["text-xs" "text-muted" "font-mono"]} (::postgres.pricing-rule/source r)]]
+   (form/form (form/build request {}) :pricing/delete {}
+              [:input {:type "hidden" :name "id" :value (identifier/prefixed :pricing-rule (::postgres.pricing-rule/id r))}]
+              (ui/button-secondary {} (tru "Delete")))])
+
+(defn pricing-view
+  ([request]
+   (pricing-view request {}))
+  ([request {:keys [error]}]
+   (let [tenant-id (get-in request [:session/realm :tenant/id])
+         f         (cond-> (form/build request (rule-config))
+                     error (form/with-error error))]
+     (list
+      (ui/nav-header request "/pricing")
+      (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+        [:div {:class ["w-full" "sm:max-w-2xl" "space-y-8"]}
+         (ui/page-title {:class "text-2xl"} (tru "Pricing rules"))
+         (if-not (role/request-tenant-admin? request)
+           (ui/text-muted {} (tru "Only admins can change pricing rules."))
+           (let [rules (pricing/list-rules (mw/request->pricing request) tenant-id)]
+             (list
+              (ui/text-muted {}
+                (tru "Rules run after discount codes, in order. Write them like 10% off when subtotal > 100 except sale, with amounts in the basket''s currency."))
+              (if (empty? rules)
+                (ui/text-muted {} (tru "No rules yet."))
+                [:ul {:class ["divide-y" "divide-border-subtle"]}
+                 (for [r rules]
+                   (rule-row request r))])
+              (form/form f :pricing/create {:class "rounded-xl p-6 space-y-4"}
+                         (form/field f :label {:label       (tru "Name buyers see")
+                                               :placeholder (tru "Big basket discount")})
+                         (form/field f :source {:label       (tru "Rule")
+                                                :placeholder "10% off when subtotal > 100 except sale"})
+                         [:div {:class "mt-4"}
+                          (form/submit f)])
+              (console request tenant-id))))])))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn- respond
+  [request result]
+  (if (anom/anomaly? result)
+    (morph/respond (pricing-view request {:error (::anom/message result)}))
+    (do
+      (activity/record! (mw/request->activities request)
+                        (get-in request [:session/realm :tenant/id])
+                        (get-in request [:session/user :user/id])
+                        "pricing.changed" {})
+      (morph/respond (pricing-view request)))))
+
+(defn create
+  [request]
+  (span/with-span! {:name ::create}
+    (let [{:keys [label source]} (get-in request [:parameters :form])
+          f                      (form/build request (rule-config))]
+      (cond
+        (not (role/request-tenant-admin? request))
+        bits.response/forbidden-response
+
+        (not (:success? f))
+        (morph/respond (pricing-view request))
+
+        :else
+        (respond request (pricing/create! (mw/request->pricing request)
+                                          (get-in request [:session/realm :tenant/id])
+                                          label
+                                          source))))))
+
+(defn delete
+  [request]
+  (span/with-span! {:name ::delete}
+    (if-not (role/request-tenant-admin? request)
+      bits.response/forbidden-response
+      (respond request
+               (when-not (pricing/delete! (mw/request->pricing request)
+                                          (get-in request [:session/realm :tenant/id])
+                                          (get-in request [:parameters :form :id]))
+                 (anom/not-found {::anom/message (tru "That was already deleted.")}))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/pricing
+   :routes  [["/pricing" (assoc (morph/morphable ui/layout pricing-view)
+                                :bits/page {:page/title "Pricing rules"})]]
+   :actions {:pricing/create {:handler create
+                              :params  [[:label :string]
+                                        [:source :string]]}
+             :pricing/delete {:handler delete
+                              :params  [[:id (coerce/public-id :pricing-rule)]]}}})
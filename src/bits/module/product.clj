@@ -0,0 +1,219 @@
+(ns bits.module.product
+  (:require
+   [bits.anomaly :as anom]
+   [bits.auth.role :as role]
+   [bits.datomic :as datomic]
+   [bits.identifier :as identifier]
+   [bits.license :as license]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.product :as product]
+   [bits.response]
+   [bits.variant :as variant]
+   [bits.version :as version]
+   [charred.api :as json]
+   [datomic.api :as d]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.io InputStream)
+   (java.nio.charset StandardCharsets)
+   (java.time Instant)
+   (java.util Date)))
+
+;;; ----------------------------------------------------------------------------
+;;; Representation
+
+(defn- option->json
+  [option]
+  {:id     (identifier/prefixed :option (:option/id option))
+   :name   (:option/name option)
+   :values (for [v (:option/values option)]
+             {:id    (identifier/prefixed :option-value (:option-value/id v))
+              :label (:option-value/label v)})})
+
+(defn- variant->json
+  [v]
+  {:active  (boolean (:variant/active? v))
+   :id      (identifier/prefixed :variant (:variant/id v))
+   :name    (:variant/name v)
+   :options (sort (map #(identifier/prefixed :option-value %) (variant/combination v)))
+   :price   {:amount   (get-in v [:variant/price :money/amount])
+             :currency (some-> v :variant/price :money/currency :db/ident name)}
+   :sku     (get-in v [:variant/sku :sku/code])
+   :stock   (variant/stock (d/entity-db v) v)})
+
+(defn- ->json
+  [p]
+  {:description  (:product/description p)
+   :id           (identifier/prefixed :product (:product/id p))
+   :license      (license/describe p)
+   :options      (map option->json (variant/options p))
+   :position     (:product/position p)
+   :publish-at   (some-> ^Date (:product/publish-at p) .toInstant str)
+   :status       (some-> p :product/status name)
+   :title        (:product/title p)
+   :unpublish-at (some-> ^Date (:product/unpublish-at p) .toInstant str)
+   :variants     (map variant->json (sort-by :variant/created-at (:product/variants p)))
+   :version      (product/version p)})
+
+(defn- parse-time
+  "An ISO 8601 instant as a Date. Anything else is passed on for
+  `product/update!` to reject."
+  [x]
+  (try
+    (some-> x str Instant/parse Date/from)
+    (catch Exception _ x)))
+
+(defn- <-json
+  [body]
+  (cond-> {}
+    (contains? body "description")   (assoc :product/description (get body "description"))
+    (contains? body "license")       (assoc :product/license (some->> (get body "license") str (keyword "product.license")))
+    (contains? body "license-terms") (assoc :product/license-terms (get body "license-terms"))
+    (contains? body "position")      (assoc :product/position (get body "position"))
+    (contains? body "publish-at")    (assoc :product/publish-at (parse-time (get body "publish-at")))
+    (contains? body "status")        (assoc :product/status (some->> (get body "status") str (keyword "product.status")))
+    (contains? body "title")         (assoc :product/title (get body "title"))
+    (contains? body "unpublish-at")  (assoc :product/unpublish-at (parse-time (get body "unpublish-at")))))
+
+(defn- product-response
+  [status p]
+  (bits.response/json-response status (->json p) {"etag" (version/etag (product/version p))}))
+
+(def ^:private max-bytes
+  (* 64 1024))
+
+(defn- read-json
+  "The JSON object in the body, or nil when it isn't one. Reads at most one
+  byte past max-bytes whatever Content-Length says, so chunked bodies work
+  and oversized ones are refused without being held in memory."
+  [request]
+  (when-let [^InputStream in (:body request)]
+    (let [bs (.readNBytes in (inc max-bytes))]
+      (when (<= (alength bs) max-bytes)
+        (try
+          (let [body (json/read-json (String. bs StandardCharsets/UTF_8))]
+            (when (map? body) body))
+          (catch Exception _ nil))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Handlers
+
+(defn- with-product
+  "Calls f with the tenant's product named in the path, when the requester
+  administers the tenant."
+  [request f]
+  (let [tenant-id (get-in request [:session/realm :tenant/id])
+        db        (mw/request->db request)
+        id        (identifier/parse-prefixed :product (get-in request [:path-params :id]))
+        p         (some->> id (product/lookup db tenant-id))]
+    (cond
+      (not (role/request-tenant-admin? request))
+      bits.response/forbidden-response
+
+      (nil? p)
+      bits.response/not-found-response
+
+      :else
+      (f p))))
+
+(defn- with-version
+  "Calls f with the product and the version from `If-Match`, for edits that
+  must not clobber each other."
+  [request f]
+  (with-product request
+    (fn [p]
+      (let [expected (version/parse-etag (get-in request [:headers "if-match"]))
+            body     (read-json request)]
+        (cond
+          (nil? expected)
+          (bits.response/json-response 428 {:code    "precondition_required"
+                                            :message "Send the product's ETag in If-Match."})
+
+          (nil? body)
+          bits.response/bad-request-response
+
+          :else
+          (f p expected body))))))
+
+(defn show
+  [request]
+  (span/with-span! {:name ::show}
+    (with-product request #(product-response 200 %))))
+
+(defn edit
+  "Updates the fields present in the JSON body. The `If-Match` header must
+  hold the version from the `ETag` the caller read, so edits never clobber
+  each other. A stale version gets a 409 with the product as it is now."
+  [request]
+  (span/with-span! {:name ::edit}
+    (with-version request
+      (fn [p expected body]
+        (let [result (product/update! (datomic/conn (mw/request->datomic request)) p expected (<-json body))]
+          (if (anom/anomaly? result)
+            (bits.response/anomaly-response request result (when-let [current (::product/current result)]
+                                                             {:current (->json current)}))
+            (product-response 200 result)))))))
+
+(defn- changed-response
+  "The product as it is after the change, or the anomaly the change became."
+  [request result]
+  (if (anom/anomaly? result)
+    (bits.response/anomaly-response request result)
+    (let [db (d/db (datomic/conn (mw/request->datomic request)))]
+      (product-response 200 (product/lookup db
+                                            (get-in request [:session/realm :tenant/id])
+                                            (identifier/parse-prefixed :product (get-in request [:path-params :id])))))))
+
+(defn add-option
+  "Adds an option from `{\"name\": \"Size\", \"values\": [\"S\", \"M\"]}`. Only
+  products without active variants can gain options."
+  [request]
+  (span/with-span! {:name ::add-option}
+    (with-version request
+      (fn [p expected body]
+        (let [values (get body "values")]
+          (changed-response request
+                            (variant/add-option! (datomic/conn (mw/request->datomic request)) p expected
+                                                 {:name   (str (get body "name"))
+                                                  :values (if (and (sequential? values) (every? string? values))
+                                                            values
+                                                            [])})))))))
+
+(defn add-variant
+  "Adds a variant at the combination of option value IDs in `options`, with
+  `price` in minor units and an optional `stock` limit."
+  [request]
+  (span/with-span! {:name ::add-variant}
+    (with-version request
+      (fn [p expected body]
+        (let [options (get body "options" [])
+              ids     (when (sequential? options)
+                        (map #(identifier/parse-prefixed :option-value %) options))]
+          (changed-response request
+                            (if (or (nil? ids) (some nil? ids))
+                              (anom/incorrect {::anom/message (tru "Options must be option value IDs.")})
+                              (variant/create! (datomic/conn (mw/request->datomic request)) p expected
+                                               {:currency       (get body "currency")
+                                                :name           (get body "name")
+                                                :option-values  (vec ids)
+                                                :price          (get body "price")
+                                                :quantity-limit (get body "stock")
+                                                :sku            (get body "sku")
+                                                :type           (get body "type" "physical")}
+                                               (Date.)))))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/product
+   :routes  [["/api/products/:id" {:get {:bits/scopes #{:read}
+                                         :handler     show}
+                                   :put {:bits/scopes #{:write}
+                                         :handler     edit}}]
+             ["/api/products/:id/options" {:post {:bits/scopes #{:write}
+                                                  :handler     add-option}}]
+             ["/api/products/:id/variants" {:post {:bits/scopes #{:write}
+                                                   :handler     add-variant}}]]
+   :actions {}})
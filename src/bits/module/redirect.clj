@@ -0,0 +1,181 @@
+(ns bits.module.redirect
+  (:require
+   [bits.activity :as activity]
+   [bits.anomaly :as anom]
+   [bits.auth.role :as role]
+   [bits.coerce :as coerce]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.morph :as morph]
+   [bits.redirect :as redirect]
+   [bits.response]
+   [bits.ui :as ui]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn- rule-config
+  []
+  {:schema {:source      [:re {:error/message (tru "A path starting with /")} #"^/.*"]
+            :destination [:string {:min 1}]
+            :status      [:enum "301" "302" "307" "308"]}
+   :submit {:idle    (tru "Add redirect")
+            :success (tru "Redirect added")}})
+
+(defn- import-config
+  []
+  {:schema {:csv [:string {:min 1}]}
+   :submit {:idle    (tru "Import")
+            :success (tru "Imported")}})
+
+(defn- rule-row
+  [request {:bits.postgres.redirect/keys [destination hits id last-hit-at source status]}]
+  [:tr {:class ["text-sm"]}
+   [:td {:class ["py-2" "pr-4" "font-mono" "text-primary"]} source]
+   [:td {:class ["py-2" "pr-4" "font-mono" "text-secondary" "break-all"]} destination]
+   [:td {:class ["py-2" "pr-4" "text-muted"]} status]
+   [:td {:class ["py-2" "pr-4" "text-right" "text-primary"]} hits]
+   [:td {:class ["py-2" "pr-4" "text-xs" "text-muted"]} (if last-hit-at (str last-hit-at) (tru "Never"))]
+   [:td {:class ["py-2"]}
+    (form/form (form/build request {}) :redirect/delete {}
+               [:input {:type "hidden" :name "id" :value (identifier/prefixed :redirect id)}]
+               (ui/button-secondary {} (tru "Delete")))]])
+
+(defn redirects-view
+  ([request]
+   (redirects-view request {}))
+  ([request {:keys [error]}]
+   (let [tenant-id   (get-in request [:session/realm :tenant/id])
+         rule-form   (cond-> (form/build request (rule-config))
+                       (= :rule (:form error)) (form/with-error (:message error)))
+         import-form (cond-> (form/build request (import-config))
+                       (= :import (:form error)) (form/with-error (:message error)))]
+     (list
+      (ui/nav-header request "/redirects")
+      (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+        [:div {:class ["w-full" "sm:max-w-3xl" "space-y-8"]}
+         (ui/page-title {:class "text-2xl"} (tru "Redirects"))
+         (if-not (role/request-tenant-admin? request)
+           (ui/text-muted {} (tru "Only admins can change redirects."))
+           (let [rules (redirect/list-rules (mw/request->redirects request) tenant-id)]
+             (list
+              (ui/text-muted {}
+                (tru "Send visitors from the paths of your old site to their new home. End a source with /* to match everything under it, and put :splat in the destination where the rest should go."))
+              (if (empty? rules)
+                (ui/text-muted {} (tru "No redirects yet."))
+                [:table {:class ["w-full"]}
+                 [:thead
+                  [:tr {:class ["text-left" "text-xs" "uppercase" "text-muted"]}
+                   [:th {:class ["pb-2"]} (tru "From")]
+                   [:th {:class ["pb-2"]} (tru "To")]
+                   [:th {:class ["pb-2"]} (tru "Status")]
+                   [:th {:class ["pb-2" "text-right" "pr-4"]} (tru "Hits")]
+                   [:th {:class ["pb-2"]} (tru "Last hit")]
+                   [:th]]]
+                 [:tbody {:class ["divide-y" "divide-border-subtle"]}
+                  (for [rule rules]
+                    (rule-row request rule))]])
+              (form/form rule-form :redirect/add {:class "rounded-xl p-6 space-y-4"}
+                         (form/field rule-form :source {:label       (tru "From")
+                                                        :placeholder "/old-blog/*"})
+                         (form/field rule-form :destination {:label       (tru "To")
+                                                             :placeholder "/blog/:splat"})
+                         (form/select rule-form :status {:label (tru "Status")}
+                                      [[:option {:value "301"} (tru "301 Moved permanently")]
+                                       [:option {:value "302"} (tru "302 Found")]
+                                       [:option {:value "307"} (tru "307 Temporary redirect")]
+                                       [:option {:value "308"} (tru "308 Permanent redirect")]])
+                         [:div {:class "mt-4"}
+                          (form/submit rule-form)])
+              (form/form import-form :redirect/import {:class "rounded-xl p-6 space-y-4"}
+                         (form/textarea import-form :csv {:label       (tru "Import CSV: source,destination,status")
+                                                          :placeholder "/old,/new,301"})
+                         [:div {:class "mt-4"}
+                          (form/submit import-form)]))))])))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn- respond
+  "Records the change and re-renders, or re-renders with the anomaly's message
+  against the form it came from."
+  [request form-key result]
+  (if (anom/anomaly? result)
+    (morph/respond (redirects-view request {:error {:form    form-key
+                                                    :message (::anom/message result)}}))
+    (do
+      (activity/record! (mw/request->activities request)
+                        (get-in request [:session/realm :tenant/id])
+                        (get-in request [:session/user :user/id])
+                        "redirects.changed" {})
+      (morph/respond (redirects-view request)))))
+
+(defn add
+  [request]
+  (span/with-span! {:name ::add}
+    (let [{:keys [destination source status]} (get-in request [:parameters :form])
+          f                                   (form/build request (rule-config))]
+      (cond
+        (not (role/request-tenant-admin? request))
+        bits.response/forbidden-response
+
+        (not (:success? f))
+        (morph/respond (redirects-view request))
+
+        :else
+        (respond request :rule
+                 (redirect/save! (mw/request->redirects request)
+                                 (get-in request [:session/realm :tenant/id])
+                                 [{:source      source
+                                   :destination destination
+                                   :status      (parse-long status)}]))))))
+
+(defn import-csv
+  [request]
+  (span/with-span! {:name ::import-csv}
+    (let [f     (form/build request (import-config))
+          rules (redirect/parse-csv (get-in request [:parameters :form :csv]))]
+      (cond
+        (not (role/request-tenant-admin? request))
+        bits.response/forbidden-response
+
+        (not (:success? f))
+        (morph/respond (redirects-view request))
+
+        :else
+        (respond request :import
+                 (if (anom/anomaly? rules)
+                   rules
+                   (redirect/save! (mw/request->redirects request)
+                                   (get-in request [:session/realm :tenant/id])
+                                   rules)))))))
+
+(defn delete
+  [request]
+  (span/with-span! {:name ::delete}
+    (if-not (role/request-tenant-admin? request)
+      bits.response/forbidden-response
+      (respond request nil
+               (when-not (redirect/delete! (mw/request->redirects request)
+                                           (get-in request [:session/realm :tenant/id])
+                                           (get-in request [:parameters :form :id]))
+                 (anom/not-found {::anom/message (tru "That was already deleted.")}))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/redirect
+   :routes  [["/redirects" (assoc (morph/morphable ui/layout redirects-view)
+                                  :bits/page {:page/title "Redirects"})]]
+   :actions {:redirect/add    {:handler add
+                               :params  [[:source :string]
+                                         [:destination :string]
+                                         [:status :string]]}
+             :redirect/import {:handler import-csv
+                               :params  [[:csv :string]]}
+             :redirect/delete {:handler delete
+                               :params  [[:id (coerce/public-id :redirect)]]}}})
@@ -0,0 +1,122 @@
+(ns bits.module.retention
+  (:require
+   [bits.activity :as activity]
+   [bits.anomaly :as anom]
+   [bits.auth.role :as role]
+   [bits.form :as form]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.morph :as morph]
+   [bits.postgres :as postgres]
+   [bits.response]
+   [bits.retention :as retention]
+   [bits.ui :as ui]
+   [clojure.string :as str]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Helpers
+
+(def ^:private choices
+  [7 30 90 180])
+
+(defn- table-label
+  [table]
+  (str/capitalize (str/replace (name table) "-" " ")))
+
+(defn- parse-days
+  [s]
+  (when-not (= "default" s)
+    (parse-long s)))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn- retention-config
+  []
+  {:schema (into {}
+                 (for [table retention/tenant-tables]
+                   [table [:re {:error/message (tru "Pick how long to keep these")}
+                           #"^(default|\d+)$"]]))
+   :submit {:idle    (tru "Save retention")
+            :success (tru "Retention saved")}})
+
+(defn- days-select
+  [f table {:keys [default days]}]
+  (form/select f table {:label (table-label table)}
+               (cons [:option {:value "default" :selected (nil? days)}
+                      (tru "Keep for {0} days (default)" default)]
+                     (for [d choices
+                           :when (< d default)]
+                       [:option {:value (str d) :selected (= d days)}
+                        (tru "Keep for {0} days" d)]))))
+
+(defn retention-view
+  ([request]
+   (retention-view request {}))
+  ([request {:keys [error]}]
+   (let [tenant-id (get-in request [:session/realm :tenant/id])
+         f         (cond-> (form/build request (retention-config))
+                     error (form/with-error error))]
+     (list
+      (ui/nav-header request "/retention")
+      (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+        [:div {:class ["w-full" "sm:max-w-md" "space-y-6"]}
+         (ui/page-title {:class "text-2xl"} (tru "Data retention"))
+         (if-not (role/request-tenant-admin? request)
+           (ui/text-muted {} (tru "Only admins can change how long this Bits keeps data."))
+           (list
+            (ui/text-muted {} (tru "Older records are deleted every few minutes. You can keep them for less time than the default, never more."))
+            (form/form f :retention/save {:class "rounded-xl p-6 space-y-4"}
+                       (for [[table days] (retention/tenant-days (mw/request->retention request) tenant-id)]
+                         (days-select f table days))
+                       [:div {:class "mt-4"}
+                        (form/submit f)])))])))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn save
+  [request]
+  (span/with-span! {:name ::save}
+    (let [user-id   (get-in request [:session/user :user/id])
+          tenant-id (get-in request [:session/realm :tenant/id])
+          params    (get-in request [:parameters :form])
+          f         (form/build request (retention-config))]
+      (cond
+        (not (role/request-tenant-admin? request))
+        bits.response/forbidden-response
+
+        (not (:success? f))
+        (morph/respond (retention-view request))
+
+        :else
+        ;; Writes join the action's transaction so one rejected table leaves
+        ;; every table as it was.
+        (let [postgres  (mw/request->postgres request)
+              retention (assoc (mw/request->retention request) :postgres postgres)
+              anomaly   (some (fn [table]
+                                (let [result (retention/set-tenant-days! retention tenant-id table
+                                                                         (parse-days (get params table)))]
+                                  (when (anom/anomaly? result)
+                                    result)))
+                              retention/tenant-tables)]
+          (if anomaly
+            (do
+              (postgres/rollback! postgres)
+              (morph/respond (retention-view request {:error (::anom/message anomaly)})))
+            (do
+              (activity/record! (mw/request->activities request) tenant-id user-id
+                                "retention.changed" {})
+              (morph/respond (retention-view request)))))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/retention
+   :routes  [["/retention" (assoc (morph/morphable ui/layout retention-view)
+                                  :bits/page {:page/title "Data retention"})]]
+   :actions {:retention/save {:handler save
+                              :params  (vec (for [table retention/tenant-tables]
+                                              [table :string]))}}})
@@ -0,0 +1,180 @@
+(ns bits.module.review
+  (:require
+   [bits.anomaly :as anom]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.license :as license]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.moderation :as moderation]
+   [bits.module.moderation :as module.moderation]
+   [bits.morph :as morph]
+   [bits.pagination :as pagination]
+   [bits.postgres.review :as postgres.review]
+   [bits.product :as product]
+   [bits.response]
+   [bits.review :as review]
+   [bits.schedule :as schedule]
+   [bits.ui :as ui]
+   [clojure.string :as str]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Helpers
+
+(defn- find-product
+  [request s]
+  (when-let [id (identifier/parse-prefixed :product s)]
+    (product/lookup-visible (mw/request->db request)
+                            (get-in request [:session/realm :tenant/id])
+                            id
+                            (schedule/now (mw/request->clock request)))))
+
+(defn- reviews-path
+  [p]
+  (str "/products/" (identifier/prefixed :product (:product/id p)) "/reviews"))
+
+(defn- stars
+  [rating]
+  (str (str/join (repeat rating "★")) (str/join (repeat (- 5 rating) "☆"))))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn- submit-config
+  []
+  {:schema {:rating [:enum "1" "2" "3" "4" "5"]}
+   :submit {:idle    (tru "Post review")
+            :success (tru "Thanks for your review")}})
+
+(def ^:private rating-options
+  (for [n (range 5 0 -1)]
+    {:option-value (str n) :option-label (stars n)}))
+
+(defn- review-row
+  [request r]
+  [:li {:class ["py-4" "space-y-1"]}
+   [:p {:class ["text-sm" "text-accent"] :aria-label (tru "{0} out of 5 stars" (::postgres.review/rating r))}
+    (stars (::postgres.review/rating r))]
+   (when-not (str/blank? (::postgres.review/body r))
+     [:p {:class ["text-sm" "text-secondary" "whitespace-pre-line"]} (::postgres.review/body r)])
+   [:p {:class ["text-xs" "text-muted"]}
+    (tru "Verified buyer · {0}" (str (::postgres.review/created-at r)))]
+   (when (get-in request [:session/user :user/id])
+     (module.moderation/report-form request (identifier/prefixed :review (::postgres.review/id r))))])
+
+(defn- review-form
+  [request p error]
+  (let [f (cond-> (form/build request (submit-config))
+            error (form/with-error error))]
+    (form/form f :review/submit {:class "rounded-xl p-6"}
+               [:input {:type "hidden" :name "product-id" :value (identifier/prefixed :product (:product/id p))}]
+               (form/radio-group f :rating {:label (tru "Rating")} rating-options)
+               (form/textarea f :body {:label       (tru "Review")
+                                       :placeholder (tru "What did you think?")
+                                       :rows        4})
+               [:div {:class "mt-4"}
+                (form/submit f)])))
+
+(defn reviews-section
+  "A product's rating, reviews and, for buyers, the form to review it."
+  ([request p]
+   (reviews-section request p {}))
+  ([request p {:keys [error]}]
+   (let [reviews                     (mw/request->reviews request)
+         tenant-id                   (get-in request [:session/realm :tenant/id])
+         user-id                     (get-in request [:session/user :user/id])
+         {:keys [average total]}     (review/summary reviews tenant-id (:product/id p))
+         {:keys [items next-cursor]} (review/list-reviews reviews tenant-id (:product/id p)
+                                                          (pagination/page-request (:query-params request)))]
+     [:section {:class ["space-y-6"]}
+      (if average
+        [:p {:class ["text-sm" "text-secondary"]}
+         (tru "{0} out of 5 from {1} reviews" average total)]
+        (ui/text-muted {} (tru "No reviews yet.")))
+      (when (and user-id (review/purchased? (mw/request->db request) user-id (:product/id p)))
+        (review-form request p error))
+      (when (seq items)
+        [:ul {:class ["divide-y" "divide-border-subtle"]}
+         (map #(review-row request %) items)])
+      (when next-cursor
+        [:a {:href  (str (reviews-path p) "?cursor=" next-cursor)
+             :class ["text-sm" "text-secondary" "hover:text-primary"]}
+         (tru "Older reviews")])])))
+
+(defn- license-note
+  [p]
+  (let [{license-name :name :keys [terms url]} (license/describe p)]
+    [:div {:class ["text-xs" "text-muted"]}
+     (if url
+       [:a {:href url :rel "license" :class ["hover:text-secondary"]} (tru "License: {0}" license-name)]
+       (tru "License: {0}" license-name))
+     (when terms
+       [:details {:class ["mt-1"]}
+        [:summary {:class ["cursor-pointer" "hover:text-secondary"]} (tru "Read the terms")]
+        [:p {:class ["mt-1" "whitespace-pre-line"]} terms]])]))
+
+(defn reviews-view
+  ([request]
+   (reviews-view request (find-product request (get-in request [:path-params :id])) {}))
+  ([request p opts]
+   (list
+    (ui/nav-header request "/products")
+    (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+      [:div {:class ["w-full" "sm:max-w-2xl" "space-y-8"]}
+       (if (nil? p)
+         (ui/text-muted {} (tru "This product doesn''t exist."))
+         (list
+          (ui/page-title {:class "text-2xl"} (:product/title p))
+          (license-note p)
+          (reviews-section request p opts)))]))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn submit
+  [request]
+  (span/with-span! {:name ::submit}
+    (let [params  (get-in request [:parameters :form])
+          p       (find-product request (:product-id params))
+          user-id (get-in request [:session/user :user/id])
+          f       (form/build request (submit-config))]
+      (cond
+        (nil? user-id)
+        bits.response/forbidden-response
+
+        (nil? p)
+        bits.response/not-found-response
+
+        (not (:success? f))
+        (morph/respond (reviews-view request p {}))
+
+        :else
+        (let [result (review/submit! (mw/request->reviews request)
+                                     (mw/request->db request)
+                                     {:author-id  user-id
+                                      :body       (:body params)
+                                      :product-id (:product/id p)
+                                      :rating     (parse-long (:rating params))
+                                      :tenant-id  (get-in request [:session/realm :tenant/id])})]
+          (if (anom/anomaly? result)
+            (morph/respond (reviews-view request p {:error (::anom/message result)}))
+            (do
+              (moderation/screen! (mw/request->moderator request)
+                                  {:target-id   result
+                                   :target-type "review"
+                                   :tenant-id   (get-in request [:session/realm :tenant/id])
+                                   :text        (:body params)})
+              (morph/respond (reviews-view request p {})))))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/review
+   :routes  [["/products/:id/reviews" (assoc (morph/morphable ui/layout reviews-view)
+                                             :bits/page {:page/title "Reviews"})]]
+   :actions {:review/submit {:handler submit
+                             :params  [[:product-id :string]
+                                       [:rating :string]
+                                       [:body {:optional true} :string]]}}})
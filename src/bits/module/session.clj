@@ -1,7 +1,9 @@
 (ns bits.module.session
   (:require
+   [bits.activity :as activity]
    [bits.anomaly :as anom]
    [bits.auth.credential :as credential]
+   [bits.auth.oidc :as oidc]
    [bits.auth.rate-limit :as rate-limit]
    [bits.cryptex :as cryptex]
    [bits.crypto :as crypto]
@@ -13,10 +15,11 @@
    [bits.postgres :as postgres]
    [bits.request :as request]
    [bits.session :as session]
+   [bits.shadow :as shadow]
    [bits.ui :as ui]
+   [bits.wishlist :as wishlist]
    [datomic.api :as d]
    [io.pedestal.log :as log]
-   [next.jdbc :as jdbc]
    [steffan-westcott.clj-otel.api.trace.span :as span]))
 
 ;;; ----------------------------------------------------------------------------
@@ -32,6 +35,16 @@
    :submit {:idle  (tru "Sign in")
             :error (tru "Invalid credentials")}})
 
+(defn- sso?
+  [request]
+  (some? (d/q '[:find ?s .
+                :in $ ?tenant-id
+                :where
+                [?t :tenant/id ?tenant-id]
+                [?t :tenant/sso ?s]]
+              (mw/request->db request)
+              (get-in request [:session/realm :tenant/id]))))
+
 (defn login-view
   [request opts]
   (let [{:keys [auth-failed? action-error]} opts
@@ -56,7 +69,11 @@
                                              :placeholder  "••••••••"
                                              :autocomplete "current-password"})]
                    [:div {:class "mt-4"}
-                    (form/submit f)])]))))
+                    (form/submit f)])
+        (when (sso? request)
+          [:a {:href  "/sso/login"
+               :class ["mt-6" "block" "text-center" "text-sm" "text-secondary" "hover:text-primary"]}
+           (tru "Sign in with your organization")])]))))
 
 (defn authenticated-view
   [request]
@@ -76,6 +93,20 @@
   [database email]
   (d/q credential/user-by-email-query (datomic/db database) email))
 
+(defn- shadow-rate-limits!
+  "Tries the candidate limits on the same attempt when the tenant shadows
+  them. Only the active limits are enforced."
+  [request limiter tenant-id params rate-check]
+  (let [shadow (mw/request->shadow request)]
+    (when (shadow/shadowing? shadow tenant-id :rate-limits)
+      (shadow/compare! shadow :rate-limits tenant-id
+                       (::rate-limit/reason rate-check)
+                       (rate-limit/evaluate limiter
+                                            (merge (rate-limit/limits limiter)
+                                                   (shadow/candidate shadow :rate-limits))
+                                            tenant-id
+                                            params)))))
+
 (defn authenticate
   [request]
   (span/with-span! {:name ::authenticate}
@@ -87,10 +118,12 @@
             password                                          (:password params)
             email-str                                         (cryptex/reveal email)
             ip-address                                        (request/remote-addr request)]
-        (jdbc/with-transaction [tx (:datasource postgres)]
-          (let [limiter    (assoc rate-limiter :postgres (postgres/assoc-conn postgres tx))
-                rate-check (rate-limit/check limiter tenant-id {:email      email-str
-                                                                :ip-address ip-address})]
+        (postgres/with-transaction [tx postgres]
+          (let [limiter    (assoc rate-limiter :postgres tx)
+                attempt    {:email      email-str
+                            :ip-address ip-address}
+                rate-check (rate-limit/check limiter tenant-id attempt)]
+            (shadow-rate-limits! request limiter tenant-id attempt rate-check)
             (if (anom/anomaly? rate-check)
               (do
                 (log/info :msg        "Rate limited."
@@ -98,7 +131,9 @@
                           :ip-address ip-address)
                 (morph/respond (login-view request {:action-error (::anom/message rate-check)})))
               (let [user         (find-user-by-email datomic email-str)
-                    has-user?    (some? user)
+                    ;; SSO-managed users sign in through their identity
+                    ;; provider and have no password to check.
+                    has-user?    (and (some? user) (not (oidc/sso-managed? user)))
                     password-ok? (if has-user?
                                    (:valid (crypto/verify keymaster password (:user/password-hash user)))
                                    (do (crypto/verify keymaster password (:dummy-hash keymaster))
@@ -109,7 +144,12 @@
                 (if password-ok?
                   (let [session-store (mw/request->session-store request)
                         old-sid       (get-in request [:session :sid])
-                        new-sid       (session/rotate-session! session-store tenant-id old-sid (:user/id user))]
+                        new-sid       (session/rotate-session! (assoc session-store :postgres tx)
+                                                               tenant-id old-sid (:user/id user))]
+                    (wishlist/merge! (assoc (mw/request->wishlists request) :postgres tx)
+                                     tenant-id old-sid (:user/id user))
+                    (activity/record! (mw/request->activities request) tenant-id (:user/id user)
+                                      "session.signed-in" {})
                     (log/debug :msg     "Redirecting user..."
                                :user/id (:user/id user))
                     (morph/redirect "/" {:session (assoc (session/new-session session-store)
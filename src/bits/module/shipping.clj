@@ -0,0 +1,337 @@
+(ns bits.module.shipping
+  (:require
+   [bits.activity :as activity]
+   [bits.anomaly :as anom]
+   [bits.auth.role :as role]
+   [bits.catalog :as catalog]
+   [bits.coerce :as coerce]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.locale :as locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.money :as money]
+   [bits.morph :as morph]
+   [bits.plugin :as plugin]
+   [bits.response]
+   [bits.shipping :as shipping]
+   [bits.ui :as ui]
+   [clojure.string :as str]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.util Currency)))
+
+;;; ----------------------------------------------------------------------------
+;;; Helpers
+
+(def ^:private currencies
+  ["GBP" "USD" "EUR"])
+
+(defn- format-amount
+  [amount currency]
+  (money/format-price (locale/current-locale)
+                      {:money/amount amount
+                       ::money/iso   (Currency/getInstance ^String currency)}))
+
+(defn- parse-amount
+  "Minor units of a price like `4.50`, where `0` is allowed for free rates."
+  [s currency]
+  (let [s (some-> s str/trim)]
+    (if (and s (re-matches #"0+(\.0+)?" s))
+      0
+      (when (some #{currency} currencies)
+        (catalog/amount s currency)))))
+
+(defn- parse-grams
+  [s]
+  (some-> s str/trim not-empty parse-long))
+
+;;; ----------------------------------------------------------------------------
+;;; Selection
+;;;
+;;; What checkout shows once it knows where the basket is going.
+
+(defn rate-picker
+  "A radio button for each shipping option, with the chosen one checked."
+  [options chosen]
+  (if (empty? options)
+    (ui/text-muted {} (tru "We don''t ship there yet."))
+    [:fieldset {:class ["space-y-2"]}
+     [:legend {:class ["text-sm" "font-medium" "text-primary"]} (tru "Shipping")]
+     (for [{:keys [amount currency id] option-name :name} options]
+       [:label {:class ["flex" "items-center" "justify-between" "gap-4" "rounded-md" "px-3" "py-2"
+                        "bg-surface-raised" "text-sm" "text-primary"]}
+        [:span {:class ["flex" "items-center" "gap-2"]}
+         [:input {:type "radio" :name "rate" :value id :checked (= id (:id chosen))}]
+         option-name]
+        [:span {:class ["text-secondary"]}
+         (if (zero? amount) (tru "Free") (format-amount amount currency))]])]))
+
+(defn totals-summary
+  [{:keys [adjustments discount shipping subtotal total]} currency]
+  [:dl {:role "status" :class ["grid" "grid-cols-2" "gap-y-1" "text-sm"]}
+   [:dt {:class ["text-secondary"]} (tru "Subtotal")]
+   [:dd {:class ["text-right" "text-primary"]} (format-amount subtotal currency)]
+   (when (pos? discount)
+     (list
+      [:dt {:class ["text-secondary"]} (tru "Discount")]
+      [:dd {:class ["text-right" "text-primary"]} (str "−" (format-amount discount currency))]))
+   [:dt {:class ["text-secondary"]} (tru "Shipping")]
+   [:dd {:class ["text-right" "text-primary"]} (format-amount shipping currency)]
+   (for [{:keys [amount label]} adjustments]
+     (list
+      [:dt {:class ["text-secondary"]} label]
+      [:dd {:class ["text-right" "text-primary"]}
+       (str (when (neg? amount) "−") (format-amount (abs amount) currency))]))
+   [:dt {:class ["font-semibold" "text-primary"]} (tru "Total")]
+   [:dd {:class ["text-right" "font-semibold" "text-primary"]} (format-amount total currency)]])
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn- zone-config
+  []
+  {:schema {:zone-name [:string {:min 1}]
+            :regions   [:re {:error/message (tru "Codes like GB or US-CA, separated by commas")}
+                        #"^[A-Za-z0-9,\s-]+$"]}
+   :submit {:idle    (tru "Add zone")
+            :success (tru "Zone added")}})
+
+(defn- rate-config
+  []
+  {:schema {:zone-id   [:string {:min 1}]
+            :rate-name [:string {:min 1}]
+            :kind      [:enum "flat" "weight" "free-above"]
+            :amount    [:re {:error/message (tru "Must be a number")}
+                        #"^\d+(\.\d+)?$"]
+            :currency  (into [:enum] currencies)}
+   :submit {:idle    (tru "Add rate")
+            :success (tru "Rate added")}})
+
+(defn- delete-button
+  [request action kind id]
+  (form/form (form/build request {}) action {}
+             [:input {:type "hidden" :name "id" :value (identifier/prefixed kind id)}]
+             (ui/button-secondary {} (tru "Delete"))))
+
+(defn- rate-terms
+  [{:bits.postgres.shipping-rate/keys [currency kind max-grams min-grams threshold]}]
+  (case kind
+    "flat"       (tru "Flat")
+    "weight"     (tru "{0}g to {1}g" (or min-grams 0) max-grams)
+    "free-above" (tru "Free from {0}" (format-amount threshold currency))))
+
+(defn- zone-section
+  [request zone rates]
+  [:section {:class ["space-y-2"]}
+   [:div {:class ["flex" "items-center" "justify-between" "gap-4"]}
+    [:div
+     [:h2 {:class ["text-lg" "font-semibold" "text-primary"]} (:bits.postgres.shipping-zone/name zone)]
+     [:p {:class ["text-xs" "text-muted" "font-mono"]}
+      (str/join ", " (:bits.postgres.shipping-zone/regions zone))]]
+    (delete-button request :shipping/delete-zone :shipping-zone (:bits.postgres.shipping-zone/id zone))]
+   (if (empty? rates)
+     (ui/text-muted {} (tru "No rates yet, so nothing ships here."))
+     [:ul {:class ["divide-y" "divide-border-subtle"]}
+      (for [rate rates]
+        [:li {:class ["flex" "items-center" "justify-between" "gap-4" "py-2" "text-sm"]}
+         [:span {:class ["text-primary"]} (:bits.postgres.shipping-rate/name rate)]
+         [:span {:class ["text-muted"]}
+          (str (format-amount (:bits.postgres.shipping-rate/amount rate) (:bits.postgres.shipping-rate/currency rate))
+               " · " (rate-terms rate))]
+         (delete-button request :shipping/delete-rate :shipping-rate (:bits.postgres.shipping-rate/id rate))])])])
+
+(defn- preview
+  "Prices a made-up basket from the query string, the way checkout will."
+  [request tenant-id]
+  (let [{:strs [currency grams rate region subtotal]} (:query-params request)
+        currency                                      (or (some #{currency} currencies) (first currencies))
+        shipment                                      {:region   region
+                                                       :grams    (or (parse-grams grams) 0)
+                                                       :subtotal (or (parse-amount subtotal currency) 0)
+                                                       :currency currency}
+        options                                       (when (shipping/normalize-region region)
+                                                        (shipping/options (mw/request->shipping request) tenant-id shipment))
+        chosen                                        (shipping/choose options rate)]
+    [:section {:class ["space-y-4"]}
+     [:h2 {:class ["text-lg" "font-semibold" "text-primary"]} (tru "Try it")]
+     [:form {:method "get" :action "/shipping" :class ["space-y-4"]}
+      [:div {:class ["grid" "grid-cols-2" "gap-3"]}
+       (ui/input {:name "region" :value region :placeholder "GB" :class ["rounded-md"]})
+       (ui/input {:name "grams" :value grams :placeholder (tru "Weight in grams") :class ["rounded-md"]})
+       (ui/input {:name "subtotal" :value subtotal :placeholder (tru "Subtotal") :class ["rounded-md"]})
+       [:select {:name "currency" :class ["rounded-md" "px-2" "py-1.5" "bg-surface-raised" "text-primary"]}
+        (for [c currencies]
+          [:option {:value c :selected (= c currency)} c])]]
+      (when (some? options)
+        (list
+         (rate-picker options chosen)
+         (when chosen
+           (totals-summary (plugin/adjust-totals (mw/request->plugins request)
+                                                 tenant-id
+                                                 currency
+                                                 (shipping/totals {:subtotal (:subtotal shipment)} chosen))
+                           currency))))
+      (ui/button-secondary {} (tru "Quote"))]]))
+
+(defn shipping-view
+  ([request]
+   (shipping-view request {}))
+  ([request {:keys [error]}]
+   (let [tenant-id (get-in request [:session/realm :tenant/id])
+         zone-form (cond-> (form/build request (zone-config))
+                     (= :zone (:form error)) (form/with-error (:message error)))
+         rate-form (cond-> (form/build request (rate-config))
+                     (= :rate (:form error)) (form/with-error (:message error)))]
+     (list
+      (ui/nav-header request "/shipping")
+      (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+        [:div {:class ["w-full" "sm:max-w-2xl" "space-y-8"]}
+         (ui/page-title {:class "text-2xl"} (tru "Shipping"))
+         (if-not (role/request-tenant-admin? request)
+           (ui/text-muted {} (tru "Only admins can change shipping."))
+           (let [svc   (mw/request->shipping request)
+                 zones (shipping/list-zones svc tenant-id)
+                 rates (group-by :bits.postgres.shipping-rate/zone-id (shipping/list-rates svc tenant-id))]
+             (list
+              (for [zone zones]
+                (zone-section request zone (get rates (:bits.postgres.shipping-zone/id zone))))
+              (form/form zone-form :shipping/add-zone {:class "rounded-xl p-6 space-y-4"}
+                         (form/field zone-form :zone-name {:label       (tru "Zone name")
+                                                           :placeholder (tru "Domestic")})
+                         (form/field zone-form :regions {:label       (tru "Countries and regions")
+                                                         :placeholder "GB, IE"})
+                         [:div {:class "mt-4"}
+                          (form/submit zone-form)])
+              (when (seq zones)
+                (form/form rate-form :shipping/add-rate {:class "rounded-xl p-6 space-y-4"}
+                           (form/select rate-form :zone-id {:label (tru "Zone")}
+                                        (for [zone zones]
+                                          [:option {:value (identifier/prefixed :shipping-zone (:bits.postgres.shipping-zone/id zone))}
+                                           (:bits.postgres.shipping-zone/name zone)]))
+                           (form/field rate-form :rate-name {:label       (tru "Name buyers see")
+                                                             :placeholder (tru "Standard delivery")})
+                           (form/select rate-form :kind {:label (tru "Kind")}
+                                        [[:option {:value "flat"} (tru "Flat")]
+                                         [:option {:value "weight"} (tru "By weight")]
+                                         [:option {:value "free-above"} (tru "Free above a threshold")]])
+                           (form/field rate-form :amount {:label     (tru "Amount")
+                                                          :inputmode "decimal"})
+                           (form/select rate-form :currency {:label (tru "Currency")}
+                                        (for [c currencies]
+                                          [:option {:value c} c]))
+                           (form/field rate-form :min-grams {:label (tru "From grams (by weight)")
+                                                             :type  "number"
+                                                             :min   "0"})
+                           (form/field rate-form :max-grams {:label (tru "Up to grams (by weight)")
+                                                             :type  "number"
+                                                             :min   "1"})
+                           (form/field rate-form :threshold {:label     (tru "Free from subtotal (free above)")
+                                                             :inputmode "decimal"})
+                           [:div {:class "mt-4"}
+                            (form/submit rate-form)]))
+              (preview request tenant-id))))])))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn- respond
+  "Records the change and re-renders, or re-renders with the anomaly's message
+  against the form it came from."
+  [request form-key result]
+  (if (anom/anomaly? result)
+    (morph/respond (shipping-view request {:error {:form    form-key
+                                                   :message (::anom/message result)}}))
+    (do
+      (activity/record! (mw/request->activities request)
+                        (get-in request [:session/realm :tenant/id])
+                        (get-in request [:session/user :user/id])
+                        "shipping.changed" {})
+      (morph/respond (shipping-view request)))))
+
+(defn add-zone
+  [request]
+  (span/with-span! {:name ::add-zone}
+    (let [params (get-in request [:parameters :form])
+          f      (form/build request (zone-config))]
+      (cond
+        (not (role/request-tenant-admin? request))
+        bits.response/forbidden-response
+
+        (not (:success? f))
+        (morph/respond (shipping-view request))
+
+        :else
+        (respond request :zone
+                 (shipping/create-zone! (mw/request->shipping request)
+                                        (get-in request [:session/realm :tenant/id])
+                                        (:zone-name params)
+                                        (str/split (:regions params) #"[\s,]+")))))))
+
+(defn add-rate
+  [request]
+  (span/with-span! {:name ::add-rate}
+    (let [{:keys [amount currency kind] :as params} (get-in request [:parameters :form])
+          f                                         (form/build request (rate-config))]
+      (cond
+        (not (role/request-tenant-admin? request))
+        bits.response/forbidden-response
+
+        (not (:success? f))
+        (morph/respond (shipping-view request))
+
+        :else
+        (respond request :rate
+                 (shipping/create-rate! (mw/request->shipping request)
+                                        (get-in request [:session/realm :tenant/id])
+                                        (identifier/parse-prefixed :shipping-zone (:zone-id params))
+                                        {:name      (:rate-name params)
+                                         :kind      kind
+                                         :amount    (parse-amount amount currency)
+                                         :currency  currency
+                                         :min-grams (parse-grams (:min-grams params))
+                                         :max-grams (parse-grams (:max-grams params))
+                                         :threshold (parse-amount (:threshold params) currency)}))))))
+
+(defn- delete
+  [request f]
+  (if-not (role/request-tenant-admin? request)
+    bits.response/forbidden-response
+    (respond request nil
+             (when-not (f (mw/request->shipping request)
+                          (get-in request [:session/realm :tenant/id])
+                          (get-in request [:parameters :form :id]))
+               (anom/not-found {::anom/message (tru "That was already deleted.")})))))
+
+(defn delete-zone
+  [request]
+  (span/with-span! {:name ::delete-zone}
+    (delete request shipping/delete-zone!)))
+
+(defn delete-rate
+  [request]
+  (span/with-span! {:name ::delete-rate}
+    (delete request shipping/delete-rate!)))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/shipping
+   :routes  [["/shipping" (assoc (morph/morphable ui/layout shipping-view)
+                                 :bits/page {:page/title "Shipping"})]]
+   :actions {:shipping/add-zone    {:handler add-zone
+                                    :params  [[:zone-name :string]
+                                              [:regions :string]]}
+             :shipping/add-rate    {:handler add-rate
+                                    :params  [[:zone-id :string]
+                                              [:rate-name :string]
+                                              [:kind :string]
+                                              [:amount :string]
+                                              [:currency :string]
+                                              [:min-grams {:optional true} :string]
+                                              [:max-grams {:optional true} :string]
+                                              [:threshold {:optional true} :string]]}
+             :shipping/delete-zone {:handler delete-zone
+                                    :params  [[:id (coerce/public-id :shipping-zone)]]}
+             :shipping/delete-rate {:handler delete-rate
+                                    :params  [[:id (coerce/public-id :shipping-rate)]]}}})
@@ -0,0 +1,199 @@
+(ns bits.module.site
+  "Uploading a static site and serving it from the tenant's domain.
+
+  Site files are answered before routing, so `/` can be the site's
+  index.html, but never at a path the app routes itself: `/login`,
+  `/dashboard` and the rest always reach the app, so admins can't lock
+  themselves out. Files get the same secure headers as every other page,
+  including the CSP, so inline scripts and styles won't run. The CSP also
+  sandboxes them: tenants upload whatever HTML and JS they like, and on the
+  app's own origin it could act as whoever is signed in. Sandboxed, a page
+  runs at an origin of its own, without the app's cookies or storage."
+  (:require
+   [bits.activity :as activity]
+   [bits.anomaly :as anom]
+   [bits.auth.role :as role]
+   [bits.coerce :as coerce]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.morph :as morph]
+   [bits.response]
+   [bits.site :as site]
+   [bits.ui :as ui]
+   [clojure.java.io :as io]
+   [clojure.string :as str]
+   [ring.util.codec :as codec]
+   [ring.util.response :as response]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.io ByteArrayInputStream)))
+
+;;; ----------------------------------------------------------------------------
+;;; Helpers
+
+(defn- megabytes
+  [n]
+  (format "%.1f MB" (/ (double n) (* 1024 1024))))
+
+(defn- record!
+  [request kind]
+  (activity/record! (mw/request->activities request)
+                    (get-in request [:session/realm :tenant/id])
+                    (get-in request [:session/user :user/id])
+                    kind {}))
+
+;;; ----------------------------------------------------------------------------
+;;; Serving
+
+(defn- file-response
+  [request {:keys [content-type digest]}]
+  (let [etag    (str "\"" digest "\"")
+        headers {"cache-control"           (if (str/starts-with? content-type "text/html")
+                                             "no-cache"
+                                             "public, max-age=300")
+                 "content-security-policy" "sandbox allow-forms allow-popups allow-scripts"
+                 "content-type"            content-type
+                 "etag"                    etag}]
+    (if (= etag (response/get-header request "if-none-match"))
+      {:status 304 :headers headers}
+      (when-let [bs (site/fetch (mw/request->sites request) digest)]
+        {:status  200
+         :headers headers
+         :body    (when-not (= :head (:request-method request))
+                    (ByteArrayInputStream. bs))}))))
+
+(defn wrap-site
+  "Answers GET and HEAD requests on creator realms with the tenant's site,
+  when it has a file at the path."
+  [handler roots]
+  (fn [request]
+    (let [path    (codec/url-decode (:uri request))
+          segment (second (re-find #"^/([^/]*)" path))]
+      (or (when (and (contains? #{:get :head} (:request-method request))
+                     (= :realm.type/creator (get-in request [:session/realm :realm/type]))
+                     (not (contains? roots segment)))
+            (when-let [file (site/lookup (mw/request->sites request)
+                                         (get-in request [:session/realm :tenant/id])
+                                         path)]
+              (span/with-span! {:name ::serve}
+                (file-response request file))))
+          (handler request)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn- upload-form
+  [request]
+  [:form {:method  "post"
+          :action  "/site/upload"
+          :enctype "multipart/form-data"
+          :class   ["space-y-4" "rounded-xl" "p-6" "bg-surface-raised"]}
+   [:input {:type "hidden" :name "csrf" :value (::mw/csrf request)}]
+   [:label {:class ["block" "text-sm" "text-primary"]}
+    (tru "Zip of your site")
+    [:input {:type     "file"
+             :name     "archive"
+             :accept   ".zip,application/zip"
+             :required true
+             :class    ["mt-2" "block" "w-full" "text-sm" "text-secondary"]}]]
+   (ui/button-primary {:type "submit"} (tru "Upload and publish"))])
+
+(defn- release-row
+  [request {:keys [active? created-at files id size]}]
+  [:li {:class ["flex" "items-center" "justify-between" "gap-4" "py-2" "text-sm"]}
+   [:span {:class ["text-primary"]} (str created-at)]
+   [:span {:class ["text-muted"]} (tru "{0} files, {1}" files (megabytes size))]
+   (if active?
+     [:span {:class ["text-success" "font-medium"]} (tru "Live")]
+     (form/form (form/build request {}) :site/roll-back {}
+                [:input {:type "hidden" :name "id" :value (identifier/prefixed :release id)}]
+                (ui/button-secondary {} (tru "Serve this"))))])
+
+(defn site-view
+  [request]
+  (let [sites     (mw/request->sites request)
+        tenant-id (get-in request [:session/realm :tenant/id])
+        reason    (some #{(keyword (get-in request [:query-params "error"]))} site/reasons)]
+    (list
+     (ui/nav-header request "/site")
+     (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+       [:div {:class ["w-full" "sm:max-w-2xl" "space-y-8"]}
+        (ui/page-title {:class "text-2xl"} (tru "Site"))
+        (if-not (role/request-tenant-admin? request)
+          (ui/text-muted {} (tru "Only admins can publish a site."))
+          (let [releases (site/releases sites tenant-id)]
+            (list
+             (ui/text-muted {}
+               (tru "Upload a zip of static files to serve them from your domain, up to {0} files and {1} in all. index.html answers for its folder."
+                    (:max-files sites) (megabytes (:quota-bytes sites))))
+             (when reason
+               (ui/alert-error (site/message sites reason)))
+             (upload-form request)
+             (when (seq releases)
+               [:section {:class ["space-y-2"]}
+                [:div {:class ["flex" "items-center" "justify-between" "gap-4"]}
+                 [:h2 {:class ["text-lg" "font-semibold" "text-primary"]} (tru "Releases")]
+                 (when (some :active? releases)
+                   (form/form (form/build request {}) :site/take-down {}
+                              (ui/button-secondary {} (tru "Take down"))))]
+                [:ul {:class ["divide-y" "divide-border-subtle"]}
+                 (for [r releases]
+                   (release-row request r))]]))))]))))
+
+;;; ----------------------------------------------------------------------------
+;;; Handlers
+
+(defn upload
+  [request]
+  (span/with-span! {:name ::upload}
+    (let [sites              (mw/request->sites request)
+          tenant-id          (get-in request [:session/realm :tenant/id])
+          {:keys [tempfile]} (get-in request [:multipart-params "archive"])]
+      (if-not (role/request-tenant-admin? request)
+        bits.response/forbidden-response
+        (let [files (if tempfile
+                      (with-open [in (io/input-stream tempfile)]
+                        (site/read-zip sites in))
+                      (anom/incorrect {::site/reason :empty}))]
+          (if (anom/anomaly? files)
+            (response/redirect (str "/site?error=" (name (::site/reason files))) :see-other)
+            (do
+              (site/publish! sites tenant-id files)
+              (record! request "site.published")
+              (response/redirect "/site" :see-other))))))))
+
+(defn roll-back
+  [request]
+  (span/with-span! {:name ::roll-back}
+    (if-not (role/request-tenant-admin? request)
+      bits.response/forbidden-response
+      (let [result (site/roll-back! (mw/request->sites request)
+                                    (get-in request [:session/realm :tenant/id])
+                                    (get-in request [:parameters :form :id]))]
+        (when-not (anom/anomaly? result)
+          (record! request "site.rolled-back"))
+        (morph/respond (site-view request))))))
+
+(defn take-down
+  [request]
+  (span/with-span! {:name ::take-down}
+    (if-not (role/request-tenant-admin? request)
+      bits.response/forbidden-response
+      (do
+        (site/take-down! (mw/request->sites request) (get-in request [:session/realm :tenant/id]))
+        (record! request "site.taken-down")
+        (morph/respond (site-view request))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/site
+   :routes  [["/site" (assoc (morph/morphable ui/layout site-view)
+                             :bits/page {:page/title "Site"})]
+             ["/site/upload" {:post {:handler upload}}]]
+   :actions {:site/roll-back {:handler roll-back
+                              :params  [[:id (coerce/public-id :release)]]}
+             :site/take-down {:handler take-down}}})
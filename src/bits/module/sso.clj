@@ -0,0 +1,148 @@
+(ns bits.module.sso
+  (:require
+   [bits.activity :as activity]
+   [bits.anomaly :as anom]
+   [bits.auth.oidc :as oidc]
+   [bits.auth.role :as role]
+   [bits.crypto :as crypto]
+   [bits.datomic :as datomic]
+   [bits.html :as html]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.notification :as notification]
+   [bits.outbox :as outbox]
+   [bits.request :as request]
+   [bits.session :as session]
+   [bits.ui :as ui]
+   [bits.wishlist :as wishlist]
+   [datomic.api :as d]
+   [io.pedestal.log :as log]
+   [ring.util.response :as response]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.util Date)))
+
+;;; ----------------------------------------------------------------------------
+;;; Helpers
+
+(defn- redirect-uri
+  [request]
+  (str (request/origin request) "/sso/callback"))
+
+(defn- tenant-connection
+  [request]
+  (oidc/connection (mw/request->keymaster request)
+                   (mw/request->db request)
+                   (get-in request [:session/realm :tenant/id])))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn error-view
+  [request message]
+  (list
+   (ui/nav-header request "/login")
+   (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+     [:div {:class ["w-full" "sm:max-w-sm" "space-y-4"]}
+      (ui/page-title {:class "text-2xl"} (tru "Single sign-on failed"))
+      (ui/text-muted {} message)
+      [:a {:href  "/login"
+           :class ["text-sm" "text-secondary" "hover:text-primary"]}
+       (tru "Back to sign in")]])))
+
+(defn- error-response
+  [request status message]
+  {:status  status
+   :headers {"content-type" "text/html; charset=utf-8"}
+   :body    (html/html (ui/layout request (error-view request message)))})
+
+;;; ----------------------------------------------------------------------------
+;;; Handlers
+
+(defn login
+  "Sends the visitor to the tenant's identity provider. State and nonce are
+  kept in the session so the callback can prove the response is for them."
+  [request]
+  (span/with-span! {:name ::login}
+    (if-let [connection (tenant-connection request)]
+      (let [randomizer (mw/request->randomizer request)
+            state      (crypto/random-nonce randomizer)
+            nonce      (crypto/random-nonce randomizer)]
+        (-> (response/redirect (oidc/authorization-url (mw/request->oidc request)
+                                                       connection
+                                                       {:nonce        nonce
+                                                        :redirect-uri (redirect-uri request)
+                                                        :state        state}))
+            (assoc :session (assoc (:session request) :sso/state state :sso/nonce nonce))))
+      (error-response request 404 (tru "Single sign-on isn''t set up here.")))))
+
+(defn- sign-in!
+  [request user-id]
+  (let [session-store (mw/request->session-store request)
+        tenant-id     (get-in request [:session/realm :tenant/id])
+        old-sid       (get-in request [:session :sid])
+        new-sid       (session/rotate-session! session-store tenant-id old-sid user-id)]
+    (wishlist/merge! (mw/request->wishlists request) tenant-id old-sid user-id)
+    (activity/record! (mw/request->activities request) tenant-id user-id "session.signed-in" {:sso true})
+    (assoc (response/redirect "/")
+           :session (assoc (session/new-session session-store)
+                           :sid     new-sid
+                           :user/id user-id))))
+
+(defn callback
+  [request]
+  (span/with-span! {:name ::callback}
+    (let [{:strs [code error state]} (:query-params request)
+          expected                   (get-in request [:session :sso/state])
+          connection                 (tenant-connection request)]
+      (cond
+        (nil? connection)
+        (error-response request 404 (tru "Single sign-on isn''t set up here."))
+
+        (some? error)
+        (error-response request 403 (tru "Your identity provider declined the sign-in."))
+
+        (or (nil? code) (nil? expected) (not= expected state))
+        (error-response request 400 (tru "This sign-in link has expired. Please try again."))
+
+        :else
+        (let [claims    (try
+                          (oidc/exchange-code! (mw/request->oidc request)
+                                               connection
+                                               {:code         code
+                                                :nonce        (get-in request [:session :sso/nonce])
+                                                :redirect-uri (redirect-uri request)})
+                          (catch Exception ex
+                            (log/warn :msg "SSO code exchange failed." :exception ex)
+                            nil))
+              tenant-id (get-in request [:session/realm :tenant/id])
+              conn      (datomic/conn (mw/request->datomic request))
+              tx        (when claims
+                          (oidc/provision-tx (d/db conn) tenant-id claims (Date.)))]
+          (cond
+            (nil? claims)
+            (error-response request 502 (tru "We couldn''t verify your identity provider''s response."))
+
+            (anom/anomaly? tx)
+            (error-response request 403 (::anom/message tx))
+
+            :else
+            (let [db-after (:db-after @(d/transact conn tx))
+                  user     (d/entity db-after [:user/sso-subject (oidc/subject claims)])
+                  user-id  (:user/id user)]
+              (when (seq tx)
+                (outbox/enqueue! (mw/request->postgres request) tenant-id user-id "member.created"
+                                 {:user-id user-id})
+                (notification/notify-all! (mw/request->notifications request) tenant-id
+                                          (role/tenant-admin-ids db-after tenant-id)
+                                          "member.joined" {:email (:user/email user)}))
+              (sign-in! request user-id))))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/sso
+   :routes  [["/sso/login" {:get {:handler login}}]
+             ["/sso/callback" {:get {:handler callback}}]]
+   :actions {}})
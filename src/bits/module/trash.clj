@@ -0,0 +1,165 @@
+(ns bits.module.trash
+  (:require
+   [bits.activity :as activity]
+   [bits.auth.role :as role]
+   [bits.datomic :as datomic]
+   [bits.deletion :as deletion]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.morph :as morph]
+   [bits.notification :as notification]
+   [bits.response]
+   [bits.ui :as ui]
+   [datomic.api :as d]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.util Date)))
+
+;;; ----------------------------------------------------------------------------
+;;; Helpers
+
+(defn- public-id
+  [kind entity]
+  (identifier/prefixed kind (get entity (get-in deletion/resources [kind :id]))))
+
+(defn- parse-id
+  "The kind and UUID of a public ID, whichever deletable kind it names."
+  [s]
+  (some (fn [kind]
+          (some->> (identifier/parse-prefixed kind s) (vector kind)))
+        (keys deletion/resources)))
+
+(defn- label
+  [kind entity]
+  (case kind
+    :membership (get-in entity [:membership/user :user/email])
+    :page       (:page/title entity)
+    :product    (:product/title entity)))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn- deleted-row
+  "A deleted entity with a button to restore it. The one just deleted is
+  picked out, and its button undoes the delete."
+  [request kind entity just-deleted]
+  (let [f     (form/build request {})
+        id    (public-id kind entity)
+        undo? (= id just-deleted)]
+    [:li {:class (cond-> ["flex" "items-center" "justify-between" "gap-4" "py-3"]
+                   undo? (conj "px-3" "rounded-lg" "bg-surface-raised"))}
+     [:div {:class ["min-w-0"]}
+      [:p {:class ["text-sm" "font-medium" "text-primary" "truncate"]}
+       (label kind entity)]
+      [:p {:class ["text-xs" "text-muted"]}
+       (if undo?
+         (tru "Deleted just now by {0}" (get-in entity [:entity/deleted-by :user/email]))
+         (tru "Deleted {0} by {1}"
+              (str (:entity/deleted-at entity))
+              (get-in entity [:entity/deleted-by :user/email])))]]
+     (form/form f :trash/restore {}
+                [:input {:type "hidden" :name "id" :value id}]
+                (ui/button-secondary {} (if undo? (tru "Undo") (tru "Restore"))))]))
+
+(defn trash-view
+  ([request]
+   (trash-view request {}))
+  ([request {:keys [just-deleted]}]
+   (let [tenant-id (get-in request [:session/realm :tenant/id])
+         db        (mw/request->db request)]
+     (list
+      (ui/nav-header request "/trash")
+      (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+        [:div {:class ["w-full" "sm:max-w-2xl" "space-y-8"]}
+         (ui/page-title {:class "text-2xl"} (tru "Recently deleted"))
+         (if-not (role/request-tenant-admin? request)
+           (ui/text-muted {} (tru "Only tenant admins can restore deleted items."))
+           (let [rows (for [kind   (sort (keys deletion/resources))
+                            entity (deletion/deleted db tenant-id kind)]
+                        (deleted-row request kind entity just-deleted))]
+             (list
+              (ui/presence request "/trash")
+              (if (empty? rows)
+                (ui/text-muted {} (tru "Nothing has been deleted."))
+                [:ul {:class ["divide-y" "divide-border-subtle"]} rows]))))])))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn- change!
+  "Looks up the tenant's entity behind the public ID and transacts
+  `(f entity)`. Returns the kind, the entity and the request reading the new
+  database, or nil when there was nothing to change."
+  [request f]
+  (let [tenant-id (get-in request [:session/realm :tenant/id])
+        [kind id] (parse-id (get-in request [:parameters :form :id]))
+        entity    (when kind
+                    (deletion/lookup (mw/request->db request) tenant-id kind id))
+        tx        (when entity (f entity))]
+    (when (seq tx)
+      (let [{:keys [db-after]} @(d/transact (datomic/conn (mw/request->datomic request)) tx)]
+        [kind entity (assoc request ::mw/db db-after)]))))
+
+(defn- notify-admins!
+  "Tells the tenant's other admins when a member is removed or added back."
+  [request kind entity notification-kind]
+  (when (= :membership kind)
+    (notification/notify-all! (mw/request->notifications request)
+                              (get-in request [:session/realm :tenant/id])
+                              (disj (role/tenant-admin-ids (mw/request->db request)
+                                                           (get-in request [:session/realm :tenant/id]))
+                                    (get-in request [:session/user :user/id]))
+                              notification-kind
+                              {:email (label kind entity)})))
+
+(defn delete
+  [request]
+  (span/with-span! {:name ::delete}
+    (let [user-id   (get-in request [:session/user :user/id])
+          tenant-id (get-in request [:session/realm :tenant/id])]
+      (if-not (role/request-tenant-admin? request)
+        bits.response/forbidden-response
+        (if-let [[kind entity request] (change! request
+                                                (fn [entity]
+                                                  ;; Admins can't lock themselves out.
+                                                  (when-not (or (deletion/deleted? entity)
+                                                                (= user-id (get-in entity [:membership/user :user/id])))
+                                                    (deletion/delete-tx entity user-id (Date.)))))]
+          (do
+            (activity/record! (mw/request->activities request) tenant-id user-id
+                              "resource.deleted" {:label (label kind entity)})
+            (notify-admins! request kind entity "member.removed")
+            (morph/respond (trash-view request {:just-deleted (public-id kind entity)})))
+          bits.response/not-found-response)))))
+
+(defn restore
+  [request]
+  (span/with-span! {:name ::restore}
+    (let [user-id   (get-in request [:session/user :user/id])
+          tenant-id (get-in request [:session/realm :tenant/id])]
+      (if-not (role/request-tenant-admin? request)
+        bits.response/forbidden-response
+        (if-let [[kind entity request] (change! request
+                                                (fn [entity]
+                                                  (when (deletion/deleted? entity)
+                                                    (deletion/restore-tx entity))))]
+          (do
+            (activity/record! (mw/request->activities request) tenant-id user-id
+                              "resource.restored" {:label (label kind entity)})
+            (notify-admins! request kind entity "member.restored")
+            (morph/respond (trash-view request)))
+          bits.response/not-found-response)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/trash
+   :routes  [["/trash" (assoc (morph/morphable ui/layout trash-view {:presence? true})
+                              :bits/page {:page/title "Recently deleted"})]]
+   :actions {:trash/delete  {:handler delete
+                             :params  [[:id :string]]}
+             :trash/restore {:handler restore
+                             :params  [[:id :string]]}}})
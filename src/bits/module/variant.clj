@@ -0,0 +1,147 @@
+(ns bits.module.variant
+  (:require
+   [bits.identifier :as identifier]
+   [bits.locale :as locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.module.wishlist :as module.wishlist]
+   [bits.money :as money]
+   [bits.morph :as morph]
+   [bits.product :as product]
+   [bits.schedule :as schedule]
+   [bits.ui :as ui]
+   [bits.variant :as variant]))
+
+;;; ----------------------------------------------------------------------------
+;;; Selection
+;;;
+;;; The picker is a plain GET form: each option is a query parameter named by
+;;; its public ID, holding the public ID of the chosen value. Nothing is
+;;; stored, so a selection can be linked to.
+
+(defn- find-product
+  [request]
+  (when-let [id (identifier/parse-prefixed :product (get-in request [:path-params :id]))]
+    (product/lookup-visible (mw/request->db request)
+                            (get-in request [:session/realm :tenant/id])
+                            id
+                            (schedule/now (mw/request->clock request)))))
+
+(defn- product-path
+  [p]
+  (str "/products/" (identifier/prefixed :product (:product/id p))))
+
+(defn selection
+  "The chosen value ID of each option, keyed by option ID. Values that aren't
+  the option's are ignored."
+  [options query-params]
+  (into {}
+        (for [option options
+              :let   [chosen (->> (get query-params (identifier/prefixed :option (:option/id option)))
+                                  (identifier/parse-prefixed :option-value))]
+              :when  (some #(= chosen (:option-value/id %)) (:option/values option))]
+          [(:option/id option) chosen])))
+
+(defn- available?
+  "Whether any active variant has value alongside the other options' chosen
+  values, so buyers aren't offered combinations that don't exist."
+  [matrix option-id value-id chosen]
+  (let [others (vals (dissoc chosen option-id))]
+    (some (fn [{:keys [values variant]}]
+            (let [ids (into #{} (map :option-value/id) values)]
+              (and variant (contains? ids value-id) (every? ids others))))
+          matrix)))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn- price
+  [v]
+  (let [{:money/keys [amount currency]} (:variant/price v)]
+    (money/format-price (locale/current-locale)
+                        (money/enrich {:money/amount   amount
+                                       :money/currency {:db/ident (:db/ident currency)}}))))
+
+(defn- option-fieldset
+  [matrix chosen {option-id :option/id :as option}]
+  [:fieldset {:class ["space-y-2"]}
+   [:legend {:class ["text-sm" "font-medium" "text-primary"]} (:option/name option)]
+   [:div {:class ["flex" "flex-wrap" "gap-2"]}
+    (for [{value-id :option-value/id label :option-value/label} (:option/values option)
+          :let [available (available? matrix option-id value-id chosen)]]
+      [:label {:class (cond-> ["flex" "items-center" "gap-2" "rounded-md" "px-3" "py-1.5" "text-sm"
+                               "bg-surface-raised" "text-primary"]
+                        (not available) (conj "opacity-50" "line-through"))}
+       [:input {:type     "radio"
+                :name     (identifier/prefixed :option option-id)
+                :value    (identifier/prefixed :option-value value-id)
+                :checked  (= value-id (get chosen option-id))
+                :disabled (not available)}]
+       label])]])
+
+(defn- chosen-variant
+  [db v]
+  (let [stock (variant/stock db v)]
+    [:div {:role "status" :class ["space-y-1"]}
+     [:p {:class ["text-lg" "font-semibold" "text-primary"]} (price v)]
+     [:p {:class ["text-sm" "text-secondary"]} (:variant/name v)]
+     (cond
+       (nil? stock)  nil
+       (zero? stock) (ui/text-error (tru "Sold out"))
+       :else         (ui/text-muted {} (tru "{0} left" stock)))]))
+
+(defn variant-picker
+  "Radio buttons for each of the product's options, and what the chosen
+  combination costs once every option has a value."
+  [request p]
+  (let [options (variant/options p)
+        matrix  (variant/matrix p)
+        chosen  (selection options (:query-params request))
+        v       (if (empty? options)
+                  (first (variant/active-variants p))
+                  (when (= (count chosen) (count options))
+                    (variant/select-variant p (vals chosen))))]
+    [:form {:method "get" :action (product-path p) :class ["space-y-4"]}
+     (for [option options]
+       (option-fieldset matrix chosen option))
+     (cond
+       v
+       (chosen-variant (mw/request->db request) v)
+
+       (= (count chosen) (count options))
+       (ui/text-muted {} (tru "That combination isn''t available."))
+
+       :else
+       (ui/text-muted {} (tru "Choose an option of each kind to see the price.")))
+     (when (seq options)
+       (ui/button-secondary {} (tru "Choose")))]))
+
+(defn product-view
+  [request]
+  (let [p (find-product request)]
+    (list
+     (ui/nav-header request "/products")
+     (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+       [:div {:class ["w-full" "sm:max-w-2xl" "space-y-8"]}
+        (if (nil? p)
+          (ui/text-muted {} (tru "This product doesn''t exist."))
+          (list
+           [:div {:class ["flex" "items-center" "justify-between" "gap-4"]}
+            (ui/page-title {:class "text-2xl"} (:product/title p))
+            (module.wishlist/heart-toggle request
+                                          (:product/id p)
+                                          (contains? (module.wishlist/saved request) (:product/id p))
+                                          (product-path p))]
+           (when-let [description (:product/description p)]
+             [:p {:class ["text-sm" "text-secondary" "whitespace-pre-line"]} description])
+           (variant-picker request p)
+           [:a {:href  (str (product-path p) "/reviews")
+                :class ["text-sm" "text-secondary" "hover:text-primary"]}
+            (tru "Reviews")]))]))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name   :bits.module/variant
+   :routes [["/products/:id" (assoc (morph/morphable ui/layout product-view)
+                                    :bits/page {:page/title "Product"})]]})
@@ -0,0 +1,207 @@
+(ns bits.module.webhook
+  (:require
+   [bits.activity :as activity]
+   [bits.auth.role :as role]
+   [bits.coerce :as coerce]
+   [bits.cryptex :as cryptex]
+   [bits.egress :as egress]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.morph :as morph]
+   [bits.pagination :as pagination]
+   [bits.response]
+   [bits.ui :as ui]
+   [bits.webhook :as webhook]
+   [clojure.string :as str]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Events
+
+(defn- event-field
+  [event]
+  (keyword (str/replace event "." "-")))
+
+(defn- checked-events
+  [params]
+  (into #{}
+        (filter #(= "true" (get params (event-field %))))
+        webhook/events))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn- register-config
+  []
+  {:schema {:url [:re {:error/message (tru "Must be an https:// URL")}
+                  #"^https://\S+$"]}
+   :submit {:idle    (tru "Add endpoint")
+            :success (tru "Endpoint added")}})
+
+(defn- registered-secret
+  [registered]
+  [:div {:role  "status"
+         :class ["space-y-2" "rounded-lg" "p-4" "bg-surface-raised"
+                 "border" "border-border-subtle"]}
+   (ui/text-success (tru "Copy this signing secret now. It won''t be shown again."))
+   [:code {:class ["block" "break-all" "font-mono" "text-sm" "text-primary"]}
+    (cryptex/reveal (::webhook/secret registered))]])
+
+(defn- endpoint-row
+  [request endpoint]
+  (let [f (form/build request {})]
+    [:li {:class ["flex" "items-center" "justify-between" "gap-4" "py-3"]}
+     [:div {:class ["min-w-0"]}
+      [:p {:class ["text-sm" "font-medium" "text-primary" "truncate"]}
+       (:bits.postgres.webhook-endpoint/url endpoint)]
+      [:p {:class ["text-xs" "text-muted" "font-mono"]}
+       (str/join ", " (:bits.postgres.webhook-endpoint/events endpoint))]]
+     (form/form f :webhook/disable {}
+                [:input {:type  "hidden"
+                         :name  "id"
+                         :value (identifier/prefixed :webhook (:bits.postgres.webhook-endpoint/id endpoint))}]
+                (ui/button-secondary {} (tru "Remove")))]))
+
+(def ^:private status-classes
+  {"dead"      ["text-red-400"]
+   "pending"   ["text-muted"]
+   "succeeded" ["text-success"]})
+
+(defn- delivery-row
+  [delivery]
+  (let [status (:bits.postgres.webhook-delivery/status delivery)]
+    [:tr {:class ["text-sm"]}
+     [:td {:class ["py-2" "pr-4" "font-mono" "text-primary"]}
+      (:bits.postgres.webhook-delivery/event delivery)]
+     [:td {:class (into ["py-2" "pr-4"] (status-classes status))}
+      status]
+     [:td {:class ["py-2" "pr-4" "text-muted"]}
+      (:bits.postgres.webhook-delivery/attempts delivery)]
+     [:td {:class ["py-2" "pr-4" "text-muted"]}
+      (or (:bits.postgres.webhook-delivery/response-status delivery)
+          (:bits.postgres.webhook-delivery/last-error delivery))]
+     [:td {:class ["py-2" "text-muted"]}
+      (str (:bits.postgres.webhook-delivery/created-at delivery))]]))
+
+(defn- delivery-log
+  [deliveries]
+  (if (empty? deliveries)
+    (ui/text-muted {} (tru "No deliveries yet."))
+    [:table {:class ["w-full" "text-left"]}
+     [:thead
+      [:tr {:class ["text-xs" "uppercase" "text-muted"]}
+       [:th {:class ["pb-2"]} (tru "Event")]
+       [:th {:class ["pb-2"]} (tru "Status")]
+       [:th {:class ["pb-2"]} (tru "Attempts")]
+       [:th {:class ["pb-2"]} (tru "Result")]
+       [:th {:class ["pb-2"]} (tru "Created")]]]
+     [:tbody {:class ["divide-y" "divide-border-subtle"]}
+      (map delivery-row deliveries)]]))
+
+(defn webhooks-view
+  ([request]
+   (webhooks-view request {}))
+  ([request {:keys [registered error]}]
+   (let [tenant-id  (get-in request [:session/realm :tenant/id])
+         dispatcher (mw/request->webhooks request)
+         f          (cond-> (form/build request (register-config))
+                      error (form/with-error error))]
+     (list
+      (ui/nav-header request "/webhooks")
+      (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+        [:div {:class ["w-full" "sm:max-w-2xl" "space-y-8"]}
+         (ui/page-title {:class "text-2xl"} (tru "Webhooks"))
+         (if-not (role/request-tenant-admin? request)
+           (ui/text-muted {} (tru "Only admins can manage webhooks."))
+           (list
+            (when registered
+              (registered-secret registered))
+            (form/form f :webhook/register {:class "rounded-xl p-6"}
+                       (form/field f :url {:label       (tru "Endpoint URL")
+                                           :type        "url"
+                                           :placeholder "https://example.com/webhooks"})
+                       (for [event (sort webhook/events)]
+                         (form/checkbox f (event-field event) {:label event}))
+                       [:div {:class "mt-4"}
+                        (form/submit f)])
+            [:ul {:class ["divide-y" "divide-border-subtle"]}
+             (for [endpoint (webhook/list-endpoints dispatcher tenant-id)]
+               (endpoint-row request endpoint))]
+            [:section {:class ["space-y-4"]}
+             [:h2 {:class ["text-lg" "font-semibold" "text-primary"]}
+              (tru "Recent deliveries")]
+             (let [{:keys [items next-cursor]} (webhook/list-deliveries
+                                                 dispatcher
+                                                 tenant-id
+                                                 (pagination/page-request (:query-params request)))]
+               (list
+                (delivery-log items)
+                (when next-cursor
+                  [:a {:href  (str "/webhooks?cursor=" next-cursor)
+                       :class ["text-sm" "text-secondary" "hover:text-primary"]}
+                   (tru "Older deliveries")])))]))])))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn register
+  [request]
+  (span/with-span! {:name ::register}
+    (let [user-id   (get-in request [:session/user :user/id])
+          tenant-id (get-in request [:session/realm :tenant/id])
+          params    (get-in request [:parameters :form])
+          events    (checked-events params)
+          f         (form/build request (register-config))]
+      (cond
+        (not (role/request-tenant-admin? request))
+        bits.response/forbidden-response
+
+        (not (:success? f))
+        (morph/respond (webhooks-view request))
+
+        (empty? events)
+        (morph/respond (webhooks-view request {:error (tru "Choose at least one event")}))
+
+        (not (egress/public-url? (:url params)))
+        (morph/respond (webhooks-view request {:error (tru "Endpoints must be on a public address")}))
+
+        :else
+        (let [registered (webhook/register! (mw/request->webhooks request)
+                                            {:tenant-id tenant-id
+                                             :url       (:url params)
+                                             :events    events})]
+          (activity/record! (mw/request->activities request) tenant-id user-id
+                            "webhook.registered" {:url (:url params)})
+          (morph/respond (webhooks-view request {:registered registered})))))))
+
+(defn disable
+  [request]
+  (span/with-span! {:name ::disable}
+    (let [user-id   (get-in request [:session/user :user/id])
+          tenant-id (get-in request [:session/realm :tenant/id])
+          id        (get-in request [:parameters :form :id])]
+      (if-not (role/request-tenant-admin? request)
+        bits.response/forbidden-response
+        (let [dispatcher (mw/request->webhooks request)
+              endpoint   (some #(when (= id (:bits.postgres.webhook-endpoint/id %)) %)
+                               (webhook/list-endpoints dispatcher tenant-id))]
+          (when (webhook/disable! dispatcher tenant-id id)
+            (activity/record! (mw/request->activities request) tenant-id user-id
+                              "webhook.disabled" {:url (:bits.postgres.webhook-endpoint/url endpoint)}))
+          (morph/respond (webhooks-view request)))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/webhook
+   :routes  [["/webhooks" (assoc (morph/morphable ui/layout webhooks-view)
+                                 :bits/page {:page/title "Webhooks"})]]
+   :actions {:webhook/register {:handler register
+                                :params  (into [[:url :string]]
+                                               (for [event (sort webhook/events)]
+                                                 [(event-field event) {:optional true} :string]))}
+             :webhook/disable  {:handler disable
+                                :params  [[:id (coerce/public-id :webhook)]]}}})
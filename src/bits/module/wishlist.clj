@@ -0,0 +1,144 @@
+(ns bits.module.wishlist
+  (:require
+   [bits.coerce :as coerce]
+   [bits.form :as form]
+   [bits.identifier :as identifier]
+   [bits.locale :as locale :refer [tru]]
+   [bits.middleware :as mw]
+   [bits.money :as money]
+   [bits.morph :as morph]
+   [bits.product :as product]
+   [bits.response]
+   [bits.schedule :as schedule]
+   [bits.ui :as ui]
+   [bits.variant :as variant]
+   [bits.wishlist :as wishlist]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Helpers
+
+(defn owner-of
+  "Who the request's wishlist belongs to: the signed-in user, or else the
+  session, or nil without either."
+  [request]
+  (if-let [user-id (get-in request [:session/user :user/id])]
+    {:user-id user-id}
+    (when-let [sid (get-in request [:session :sid])]
+      {:sid sid})))
+
+(defn saved
+  "IDs of the products the request's owner has saved, as a set."
+  [request]
+  (if-let [owner (owner-of request)]
+    (set (wishlist/saved (mw/request->wishlists request)
+                         (get-in request [:session/realm :tenant/id])
+                         owner))
+    #{}))
+
+(defn- product-path
+  [product-id]
+  (str "/products/" (identifier/prefixed :product product-id)))
+
+(def ^:private heart-icon-path
+  (str "M21 8.25c0-2.485-2.099-4.5-4.688-4.5-1.935 0-3.597 1.126-4.312 2.733"
+       "-.715-1.607-2.377-2.733-4.313-2.733C5.1 3.75 3 5.765 3 8.25"
+       "c0 7.22 9 12 9 12s9-4.78 9-12Z"))
+
+(defn heart-toggle
+  "Saves or unsaves the product, coming back to `return-to` afterwards."
+  [request product-id saved? return-to]
+  (form/form (form/build request {}) :wishlist/toggle {}
+             [:input {:type "hidden" :name "product-id" :value (identifier/prefixed :product product-id)}]
+             [:input {:type "hidden" :name "return-to" :value return-to}]
+             [:button {:type         "submit"
+                       :aria-pressed (str saved?)
+                       :aria-label   (if saved? (tru "Remove from wishlist") (tru "Save to wishlist"))
+                       :class        (into ["flex" "items-center" "cursor-pointer"]
+                                           (if saved?
+                                             ["text-accent"]
+                                             ["text-secondary" "hover:text-primary"]))}
+              [:svg {:viewBox      "0 0 24 24"
+                     :fill         (if saved? "currentColor" "none")
+                     :stroke       "currentColor"
+                     :stroke-width "1.5"
+                     :class        ["size-5"]
+                     :aria-hidden  "true"}
+               [:path {:d               heart-icon-path
+                       :stroke-linecap  "round"
+                       :stroke-linejoin "round"}]]]))
+
+;;; ----------------------------------------------------------------------------
+;;; Views
+
+(defn- price
+  [p]
+  (when-let [{:money/keys [amount currency]} (some-> (first (variant/active-variants p)) :variant/price)]
+    (money/format-price (locale/current-locale)
+                        (money/enrich {:money/amount   amount
+                                       :money/currency {:db/ident (:db/ident currency)}}))))
+
+(defn- product-card
+  [request p]
+  [:li {:class ["flex" "items-center" "justify-between" "gap-4" "py-3"]}
+   [:div {:class ["min-w-0"]}
+    [:a {:href  (product-path (:product/id p))
+         :class ["text-sm" "font-medium" "text-primary" "hover:text-accent"]}
+     (:product/title p)]
+    (when-let [amount (price p)]
+      [:p {:class ["text-xs" "text-muted"]} amount])]
+   (heart-toggle request (:product/id p) true "/wishlist")])
+
+(defn wishlist-view
+  [request]
+  (let [db        (mw/request->db request)
+        tenant-id (get-in request [:session/realm :tenant/id])
+        now       (schedule/now (mw/request->clock request))
+        products  (when-let [owner (owner-of request)]
+                    (keep #(product/lookup-visible db tenant-id % now)
+                          (wishlist/saved (mw/request->wishlists request) tenant-id owner)))]
+    (list
+     (ui/nav-header request "/wishlist")
+     (ui/page-center {:class ["px-6" "py-12" "lg:px-8"]}
+       [:div {:class ["w-full" "sm:max-w-2xl" "space-y-6"]}
+        (ui/page-title {:class "text-2xl"} (tru "Wishlist"))
+        (if (empty? products)
+          (ui/text-muted {} (tru "Nothing saved yet. Tap the heart on a product to keep it here."))
+          [:ul {:class ["divide-y" "divide-border-subtle"]}
+           (for [p products]
+             (product-card request p))])
+        (when-not (get-in request [:session/user :user/id])
+          (ui/text-muted {} (tru "Sign in to keep your wishlist on every device.")))]))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn toggle
+  [request]
+  (span/with-span! {:name ::toggle}
+    (let [{:keys [product-id return-to]} (get-in request [:parameters :form])
+          tenant-id                      (get-in request [:session/realm :tenant/id])
+          owner                          (owner-of request)]
+      (cond
+        (nil? owner)
+        bits.response/forbidden-response
+
+        (nil? (product/lookup-visible (mw/request->db request) tenant-id product-id
+                                      (schedule/now (mw/request->clock request))))
+        bits.response/not-found-response
+
+        :else
+        (do
+          (wishlist/toggle! (mw/request->wishlists request) tenant-id owner product-id)
+          (morph/redirect (if (= "/wishlist" return-to) "/wishlist" (product-path product-id))))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Module
+
+(def module
+  {:name    :bits.module/wishlist
+   :routes  [["/wishlist" (assoc (morph/morphable ui/layout wishlist-view)
+                                 :bits/page {:page/title "Wishlist"})]]
+   :actions {:wishlist/toggle {:handler toggle
+                               :params  [[:product-id (coerce/public-id :product)]
+                                         [:return-to {:optional true} :string]]}}})
@@ -119,19 +119,25 @@
   "SSE stream that re-renders view on refresh signals. Brotli compressed.
    Registers channel in the channels atom for REPL inspection.
    Options:
-     :on-close - callback fn called with channel-id when connection closes"
+     :on-close  - callback fn called with channel-id when connection closes
+     :presence? - refresh every view when a channel opens or closes, so views
+                  showing who else is here stay current
+     :refresh   - fn of the request returning the mult whose signals re-render
+                  the view, in place of the service's refresh mult"
   ([view-fn] (render-handler view-fn {}))
-  ([view-fn {:keys [on-close]}]
+  ([view-fn {:keys [on-close presence? refresh]}]
    (fn [request]
      (let [randomizer   (get-in request [:bits.middleware/state :randomizer])
            channels     (::channels request)
            channel-id   (crypto/random-sid randomizer)
-           refresh-mult (::refresh-mult request)
+           refresh-mult ((or refresh ::refresh-mult) request)
            <refresh     (a/tap refresh-mult (a/chan (a/dropping-buffer 1)))
            <cancel      (a/chan)
            last-id      (response/get-header request "last-event-id")
            reconnect-ms (get-in request [:bits.middleware/state :sse-reconnect-ms])
+           refresh-ch   (::refresh-ch request)
            sid          (get-in request [:session :sid])
+           tenant-id    (get-in request [:session/realm :tenant/id])
            user-id      (get-in request [:session :user/id])
            request      (assoc request ::channel-id channel-id)]
        (a/>!! <refresh :init)
@@ -154,7 +160,10 @@
                                             :remote-addr  (:remote-addr request)
                                             :send!        send!
                                             :sid          sid
+                                            :tenant-id    tenant-id
                                             :user-id      user-id})
+                                    (when presence?
+                                      (a/put! refresh-ch :presence))
                                     (send! (retry-field reconnect-ms))
                                     (send! (sse-event "channel" channel-id channel-id))
                                     (try
@@ -166,15 +175,18 @@
                                             (a/close! <cancel))
 
                                           <refresh
-                                          ([_]
-                                           (some->
-                                            (let [html-str (html/htmx (view-fn request))
-                                                  hash     (content-hash html-str)
-                                                  changed? (not= hash last-hash)]
-                                              (when changed?
-                                                (send! (morph-event html-str)))
-                                              hash)
-                                            recur))
+                                          ([signal]
+                                           ;; Nil when whoever signals has
+                                           ;; stopped and closed their mult.
+                                           (when (some? signal)
+                                             (some->
+                                              (let [html-str (html/htmx (view-fn request))
+                                                    hash     (content-hash html-str)
+                                                    changed? (not= hash last-hash)]
+                                                (when changed?
+                                                  (send! (morph-event html-str)))
+                                                hash)
+                                              recur)))
 
                                           :priority true))
                                       (finally
@@ -185,6 +197,7 @@
                            (fn [_ch _status]
                              (swap! channels dissoc channel-id)
                              (when on-close (on-close channel-id))
+                             (when presence? (a/put! refresh-ch :presence))
                              (a/>!! <cancel :stop)
                              (a/untap refresh-mult <refresh))})))))
 
@@ -0,0 +1,134 @@
+(ns bits.notification
+  (:require
+   [bits.clock :as clock]
+   [bits.pagination :as pagination]
+   [bits.postgres :as postgres]
+   [bits.postgres.notification :as postgres.notification]
+   [bits.spec]
+   [clojure.core.async :as a]
+   [clojure.spec.alpha :as s]
+   [com.stuartsierra.component :as component]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Kinds
+;;;
+;;; The kind picks the message a notification renders with. Data holds only the
+;;; values interpolated into it, so copy changes apply to old notifications too.
+
+(def kinds
+  #{"api-key.issued"
+    "api-key.revoked"
+    "member.joined"
+    "member.removed"
+    "member.restored"})
+
+;;; ----------------------------------------------------------------------------
+;;; Producing
+
+(defn notify!
+  [notifier tenant-id user-id kind data]
+  {:pre [(contains? kinds kind) (map? data)]}
+  (span/with-span! {:name ::notify!}
+    (let [id (random-uuid)]
+      (postgres/execute-one! (:postgres notifier)
+                             {:insert-into :notifications
+                              :values      [{:id        id
+                                             :tenant-id tenant-id
+                                             :user-id   user-id
+                                             :kind      kind
+                                             :data      [:lift data]}]})
+      (a/put! (:changes notifier) user-id)
+      id)))
+
+(defn notify-all!
+  "Notifies each of user-ids. Returns the notifications' IDs."
+  [notifier tenant-id user-ids kind data]
+  (mapv #(notify! notifier tenant-id % kind data) user-ids))
+
+;;; ----------------------------------------------------------------------------
+;;; Reading
+
+(def ordering
+  {:direction :desc
+   :columns   [[:created-at ::postgres.notification/created-at]
+               [:id ::postgres.notification/id]]})
+
+(defn list-notifications
+  "Returns a page of the user's notifications, newest first."
+  [notifier tenant-id user-id page-request]
+  {:post [(s/valid? (s/coll-of ::postgres.notification/persisted) (:items %))]}
+  (span/with-span! {:name ::list-notifications}
+    (-> (postgres/execute! (:postgres notifier)
+                           (pagination/paginate {:select [:id :kind :data :created-at :read-at]
+                                                 :from   [:notifications]
+                                                 :where  [:and
+                                                          [:= :tenant-id tenant-id]
+                                                          [:= :user-id user-id]]}
+                                                ordering
+                                                page-request))
+        (pagination/page ordering page-request))))
+
+(defn unread-count
+  [notifier tenant-id user-id]
+  (span/with-span! {:name ::unread-count}
+    (-> (postgres/execute-one! (:postgres notifier)
+                               {:select [[[:count :*] :unread]]
+                                :from   [:notifications]
+                                :where  [:and
+                                         [:= :tenant-id tenant-id]
+                                         [:= :user-id user-id]
+                                         [:= :read-at nil]]})
+        :unread)))
+
+(defn mark-read!
+  "Marks one notification read, or all of them when id is nil."
+  [notifier tenant-id user-id id]
+  (span/with-span! {:name ::mark-read!}
+    (let [[{:keys [next.jdbc/update-count]}]
+          (postgres/execute! (:postgres notifier)
+                             {:update :notifications
+                              :set    {:read-at (clock/now (:clock notifier))}
+                              :where  (cond-> [:and
+                                               [:= :tenant-id tenant-id]
+                                               [:= :user-id user-id]
+                                               [:= :read-at nil]]
+                                        (some? id) (conj [:= :id id]))})]
+      (when (pos? (or update-count 0))
+        (a/put! (:changes notifier) user-id))
+      (or update-count 0))))
+
+;;; ----------------------------------------------------------------------------
+;;; Live updates
+;;;
+;;; Every change puts the recipient's user ID on a mult, so open pages can
+;;; re-render their bell without polling.
+
+(defn tap!
+  [notifier ch]
+  (a/tap (:mult notifier) ch false))
+
+(defn untap!
+  [notifier ch]
+  (a/untap (:mult notifier) ch))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Notifier [buffer-size changes clock mult postgres]
+  component/Lifecycle
+  (start [this]
+    (let [changes (a/chan (a/sliding-buffer buffer-size))]
+      (assoc this :changes changes :mult (a/mult changes))))
+  (stop [this]
+    (some-> changes a/close!)
+    (assoc this :changes nil :mult nil)))
+
+(defmethod print-method Notifier
+  [_ ^java.io.Writer w]
+  (.write w "#<Notifier>"))
+
+(defn make-notifier
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Notifier config))
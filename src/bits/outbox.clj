@@ -0,0 +1,151 @@
+(ns bits.outbox
+  "Events written in the same transaction as the change they describe.
+
+  Publishing straight to the bus after a commit loses the event if the process
+  dies in between. Writing it to the outbox instead means it's there exactly
+  when the change is. The relay publishes pending events to the bus in order,
+  queues webhook deliveries for topics endpoints can subscribe to, and marks
+  them published in one transaction.
+
+  Should the relay die after publishing but before committing, the batch goes
+  out again. Subscribers that can't tolerate a repeat dedupe on `:id`.
+
+  Changes that live in Datomic can't share a Postgres transaction, so their
+  events are written straight after the Datomic transaction instead. That
+  narrows the gap without closing it."
+  (:require
+   [bits.clock :as clock]
+   [bits.event :as event]
+   [bits.postgres :as postgres]
+   [bits.postgres.outbox :as postgres.outbox]
+   [bits.spec]
+   [bits.webhook :as webhook]
+   [clojure.edn :as edn]
+   [clojure.spec.alpha :as s]
+   [com.stuartsierra.component :as component]
+   [io.pedestal.log :as log]
+   [next.jdbc :as jdbc]
+   [steffan-westcott.clj-otel.api.metrics.instrument :as instrument]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.time Duration OffsetDateTime)
+   (java.util Date)
+   (java.util.concurrent Executors ScheduledExecutorService TimeUnit)))
+
+;;; ----------------------------------------------------------------------------
+;;; Writing
+
+(defn enqueue!
+  "Writes an event to the outbox through connectable, which should be the
+  transaction making the change. Returns the event ID."
+  [connectable tenant-id actor-id topic data]
+  {:pre [(contains? event/topics topic) (uuid? tenant-id)]}
+  (when-not (s/valid? (get event/topics topic) data)
+    (throw (ex-info (str "Invalid event: " topic) (s/explain-data (get event/topics topic) data))))
+  (::postgres.outbox/id
+   (postgres/execute-one! connectable
+                          {:insert-into :outbox
+                           :values      [{:id        (random-uuid)
+                                          :tenant-id tenant-id
+                                          :actor-id  actor-id
+                                          :topic     topic
+                                          :data      (pr-str data)}]
+                           :returning   [:id]})))
+
+;;; ----------------------------------------------------------------------------
+;;; Relaying
+
+(defn- claim-pending
+  [tx batch-size]
+  (postgres/execute! tx
+                     {:select   [:id :tenant-id :actor-id :topic :data :created-at]
+                      :from     [:outbox]
+                      :where    [:= :published-at nil]
+                      :order-by [:created-at :id]
+                      :limit    batch-size
+                      :for      [:update :skip-locked]}))
+
+(defn- lag-ms
+  [^OffsetDateTime now ^Date created-at]
+  (.toMillis (Duration/between (.toInstant created-at) (.toInstant now))))
+
+(defn relay!
+  "Publishes the oldest batch of pending events and marks them published.
+  Returns how many went out."
+  [relay]
+  (span/with-span! {:name ::relay!}
+    (let [{:keys [batch-size events lag-histogram postgres webhooks]} relay
+          now                                                         (clock/now (:clock relay))]
+      (jdbc/with-transaction [tx (:datasource postgres)]
+        (let [rows (claim-pending tx batch-size)]
+          (doseq [{::postgres.outbox/keys [actor-id created-at data id tenant-id topic]} rows]
+            (let [data (edn/read-string data)]
+              (event/publish! events tenant-id actor-id topic (assoc data :id id))
+              (when (contains? webhook/events topic)
+                (webhook/publish! (update webhooks :postgres postgres/assoc-conn tx) tenant-id topic data))
+              (instrument/record! lag-histogram {:value      (lag-ms now created-at)
+                                                 :attributes {"topic" topic}})))
+          (when (seq rows)
+            (postgres/execute! tx {:update :outbox
+                                   :set    {:published-at now}
+                                   :where  [:in :id (map ::postgres.outbox/id rows)]}))
+          (span/add-span-data! {:attributes {:relayed (count rows)}})
+          (count rows))))))
+
+(defn- poll!
+  [relay]
+  (try
+    (doseq [postgres (postgres/databases (:postgres relay))
+            :let     [relay (postgres/repoint relay postgres)]]
+      ;; Keep going while there's a backlog rather than waiting a whole poll.
+      (loop []
+        (when (= (:batch-size relay) (relay! relay))
+          (recur))))
+    (catch Exception ex
+      ;; An exception escaping a scheduled task cancels all future runs.
+      (log/warn :msg "Failed to relay outbox?!" :exception ex)
+      (span/add-exception! ex {:escaping? false}))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Relay [batch-size
+                  clock
+                  events
+                  ^ScheduledExecutorService executor
+                  lag-histogram
+                  poll-seconds
+                  postgres
+                  webhooks]
+  component/Lifecycle
+  (start [this]
+    (span/with-span! {:name ::start-relay}
+      (let [executor (Executors/newSingleThreadScheduledExecutor)
+            this     (assoc this
+                            :executor      executor
+                            :lag-histogram (instrument/instrument
+                                            {:name            "outbox.lag"
+                                             :instrument-type :histogram
+                                             :unit            "ms"
+                                             :description     "Time from writing an event to relaying it"}))]
+        (.scheduleWithFixedDelay executor
+                                 ^Runnable #(poll! this)
+                                 poll-seconds poll-seconds TimeUnit/SECONDS)
+        this)))
+
+  (stop [this]
+    (span/with-span! {:name ::stop-relay}
+      (when executor
+        (.shutdown executor)
+        (when-not (.awaitTermination executor 5 TimeUnit/SECONDS)
+          (.shutdownNow executor)))
+      (assoc this :executor nil :lag-histogram nil))))
+
+(defmethod print-method Relay
+  [_ ^java.io.Writer w]
+  (.write w "#<Relay>"))
+
+(defn make-relay
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Relay config))
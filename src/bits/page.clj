@@ -0,0 +1,232 @@
+(ns bits.page
+  "Storefront pages written by tenants.
+
+  A page is a slug, a title and a list of content blocks, served at `/{slug}`
+  in the tenant's realm once published. Blocks are stored as JSON so new kinds
+  don't need schema changes. Each block has a `type`:
+
+      {\"type\": \"heading\",   \"text\": \"About us\"}
+      {\"type\": \"paragraph\", \"text\": \"We make mugs.\"}
+      {\"type\": \"image\",     \"url\": \"https://…\", \"alt\": \"A mug\"}
+      {\"type\": \"link\",      \"url\": \"/\", \"text\": \"Shop\"}"
+  (:require
+   [bits.anomaly :as anom]
+   [bits.deletion :as deletion]
+   [bits.entity]
+   [bits.locale :refer [tru]]
+   [bits.schedule :as schedule]
+   [charred.api :as json]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [datomic.api :as d]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Slugs
+;;;
+;;; Routes always win over pages, so a page slugged like one of them could
+;;; never be reached. Keep this in step with top-level module routes.
+
+(def reserved-slugs
+  #{"action" "activity" "api" "api-keys" "collect" "comments" "counter" "cursors" "discounts"
+    "flags" "form" "login" "logs" "maintenance" "moderation" "notifications"
+    "pages" "plugins" "pricing" "products" "redirect" "redirects" "report" "retention" "shipping" "site" "sso" "stats"
+    "trash" "webhooks" "wishlist"})
+
+(defn- slug-taken?
+  "Deleted pages keep their slug so they can be restored."
+  [db tenant-id page-id slug]
+  (some? (d/q '[:find ?p .
+                :in $ ?tenant-id ?page-id ?slug
+                :where
+                [?t :tenant/id ?tenant-id]
+                [?t :tenant/pages ?p]
+                [?p :page/slug ?slug]
+                [?p :page/id ?id]
+                [(not= ?id ?page-id)]]
+              db tenant-id page-id slug)))
+
+;;; ----------------------------------------------------------------------------
+;;; Blocks
+
+(def ^:private max-blocks
+  200)
+
+(defn- url?
+  "Links and images may point at our own paths or anywhere over HTTPS, never
+  at `javascript:` and friends."
+  [s]
+  (and (string? s)
+       (or (str/starts-with? s "https://")
+           (and (str/starts-with? s "/") (not (str/starts-with? s "//"))))))
+
+(defn- block-error
+  [{:keys [alt text type url]}]
+  (case type
+    ("heading" "paragraph") (when-not (bits.entity/present? text)
+                              (tru "{0} blocks need text." (str/capitalize type)))
+    "image"                 (cond
+                              (not (url? url))                    (tru "Images need an https:// or / URL.")
+                              (not (or (nil? alt) (string? alt))) (tru "Image alt text must be text."))
+    "link"                  (cond
+                              (not (url? url))                  (tru "Links need an https:// or / URL.")
+                              (not (bits.entity/present? text)) (tru "Links need text."))
+    (tru "Unknown block type: {0}" (pr-str type))))
+
+(defn parse-blocks
+  "Reads and checks the JSON blocks. Returns the blocks, or an anomaly naming
+  the first bad one."
+  [s]
+  (let [blocks (try
+                 (json/read-json (or (not-empty (str/trim (str s))) "[]") :key-fn keyword)
+                 (catch Exception _ ::invalid))]
+    (cond
+      (not (and (vector? blocks) (every? map? blocks)))
+      (anom/incorrect {::anom/message (tru "Content must be a JSON array of blocks.")})
+
+      (< max-blocks (count blocks))
+      (anom/incorrect {::anom/message (tru "Pages can have at most {0} blocks." max-blocks)})
+
+      :else
+      (or (first (keep-indexed (fn [i block]
+                                 (when-let [error (block-error block)]
+                                   (anom/incorrect {::anom/message (tru "Block {0}: {1}" (inc i) error)})))
+                               blocks))
+          (mapv #(select-keys % [:alt :text :type :url]) blocks)))))
+
+(defn blocks
+  [page]
+  (json/read-json (:page/blocks page "[]") :key-fn keyword))
+
+;;; ----------------------------------------------------------------------------
+;;; Reading
+
+(defn lookup
+  "The tenant's live page, draft or published, or nil."
+  [db tenant-id page-id]
+  (let [page (deletion/lookup db tenant-id :page page-id)]
+    (when-not (deletion/deleted? page)
+      page)))
+
+(defn by-slug
+  "The tenant's live page at slug, or nil."
+  [db tenant-id slug]
+  (some->> (d/q '[:find ?id .
+                  :in $ ?tenant-id ?slug
+                  :where
+                  [?t :tenant/id ?tenant-id]
+                  [?t :tenant/pages ?p]
+                  [?p :page/slug ?slug]
+                  [(missing? $ ?p :entity/deleted-at)]
+                  [?p :page/id ?id]]
+                db tenant-id slug)
+           (lookup db tenant-id)))
+
+(defn published?
+  [page]
+  (= :page.status/published (:page/status page)))
+
+(defn live?
+  "Whether visitors can see the page at now: it's published and its schedule
+  has it up, see bits.schedule."
+  [page now]
+  (and (published? page)
+       (schedule/within? (:page/publish-at page) (:page/unpublish-at page) now)))
+
+(defn list-pages
+  "The tenant's live pages, by slug."
+  [db tenant-id]
+  (->> (d/q '[:find [?p ...]
+              :in $ ?tenant-id
+              :where
+              [?t :tenant/id ?tenant-id]
+              [?t :tenant/pages ?p]
+              [(missing? $ ?p :entity/deleted-at)]]
+            db tenant-id)
+       (map #(d/entity db %))
+       (sort-by :page/slug)))
+
+;;; ----------------------------------------------------------------------------
+;;; Writing
+
+(defn- check
+  "Returns the attributes ready to transact, or an anomaly."
+  [db tenant-id page-id {:keys [blocks slug title]}]
+  (let [slug   (some-> slug str/trim str/lower-case)
+        parsed (parse-blocks blocks)]
+    (cond
+      (not (s/valid? :page/title title))
+      (anom/incorrect {::anom/message (tru "Title can''t be blank.")})
+
+      (not (s/valid? :page/slug slug))
+      (anom/incorrect {::anom/message (tru "Slugs are lowercase letters, digits and single dashes.")})
+
+      (contains? reserved-slugs slug)
+      (anom/incorrect {::anom/message (tru "{0} is reserved. Pick another slug." slug)})
+
+      (slug-taken? db tenant-id page-id slug)
+      (anom/incorrect {::anom/message (tru "Another page already uses {0}." slug)})
+
+      (anom/anomaly? parsed)
+      parsed
+
+      :else
+      {:page/blocks (json/write-json-str parsed)
+       :page/slug   slug
+       :page/title  (str/trim title)})))
+
+(defn create!
+  "Creates a draft page. Returns it, or an anomaly."
+  [conn tenant-id attrs now]
+  (span/with-span! {:name ::create!}
+    (let [id     (random-uuid)
+          result (check (d/db conn) tenant-id id attrs)]
+      (if (anom/anomaly? result)
+        result
+        (let [{:keys [db-after]} @(d/transact conn [{:tenant/id    tenant-id
+                                                     :tenant/pages [(assoc result
+                                                                           :db/ensure       :page/ensure
+                                                                           :page/id         id
+                                                                           :page/status     :page.status/draft
+                                                                           :page/created-at now)]}])]
+          (lookup db-after tenant-id id))))))
+
+(defn update!
+  "Replaces the page's slug, title and blocks. Returns the page, or an
+  anomaly."
+  [conn tenant-id page attrs]
+  (span/with-span! {:name ::update!}
+    (let [result (check (d/db conn) tenant-id (:page/id page) attrs)]
+      (if (anom/anomaly? result)
+        result
+        (let [{:keys [db-after]} @(d/transact conn [(assoc result :db/id (:db/id page))])]
+          (lookup db-after tenant-id (:page/id page)))))))
+
+(defn publish-tx
+  [page now]
+  [{:db/id             (:db/id page)
+    :page/status       :page.status/published
+    :page/published-at now}])
+
+(defn unpublish-tx
+  [page]
+  [{:db/id       (:db/id page)
+    :page/status :page.status/draft}])
+
+(defn schedule!
+  "Sets when the page goes up and comes down, either of which may be nil to
+  have no limit. The page still has to be published to show at all. Returns
+  the page, or an anomaly."
+  [conn tenant-id page {:keys [^java.util.Date publish-at ^java.util.Date unpublish-at]}]
+  (span/with-span! {:name ::schedule!}
+    (if (and publish-at unpublish-at (not (.before publish-at unpublish-at)))
+      (anom/incorrect {::anom/message (tru "Unpublish at must be after publish at.")})
+      (let [tx                 (for [[attr value] [[:page/publish-at publish-at]
+                                                   [:page/unpublish-at unpublish-at]]
+                                     :let         [current (get page attr)]
+                                     :when        (not= current value)]
+                                 (if (some? value)
+                                   [:db/add (:db/id page) attr value]
+                                   [:db/retract (:db/id page) attr current]))
+            {:keys [db-after]} @(d/transact conn (vec tx))]
+        (lookup db-after tenant-id (:page/id page))))))
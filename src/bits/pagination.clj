@@ -0,0 +1,95 @@
+(ns bits.pagination
+  (:require
+   [buddy.core.codecs :as codecs]
+   [charred.api :as json])
+  (:import
+   (java.time Instant)))
+
+;;; ----------------------------------------------------------------------------
+;;; Orderings
+;;;
+;;; An ordering names the columns a list is sorted by and the result keys that
+;;; hold their values. The last column must be unique, so every row has its own
+;;; position and pages never overlap or skip rows. Every column sorts the same
+;;; way, which lets one row comparison seek past the cursor:
+;;;
+;;;   {:direction :desc
+;;;    :columns   [[:created-at ::postgres.activity/created-at]
+;;;                [:id ::postgres.activity/id]]}
+
+(def ^:private default-limit 25)
+(def ^:private max-limit 100)
+
+(defn page-request
+  "Reads a cursor and limit from query params, clamping the limit so clients
+  can't ask for the whole table."
+  [query-params]
+  (let [limit (some-> (get query-params "limit") parse-long)]
+    {:cursor (not-empty (get query-params "cursor"))
+     :limit  (if limit (max 1 (min max-limit limit)) default-limit)}))
+
+;;; ----------------------------------------------------------------------------
+;;; Cursors
+;;;
+;;; Cursors hold the sort values of the last row on a page. They're opaque to
+;;; clients, but tagged so each value decodes back to the type it was.
+
+(defn- encode-value
+  [v]
+  (cond
+    (instance? Instant v) ["t" (str v)]
+    (uuid? v)             ["u" (str v)]
+    (int? v)              ["n" v]
+    (string? v)           ["s" v]
+    :else                 (throw (ex-info "Unsupported cursor value?!" {:value v}))))
+
+(defn- decode-value
+  [[tag v]]
+  (case tag
+    "t" (Instant/parse v)
+    "u" (parse-uuid v)
+    "n" (long v)
+    "s" v))
+
+(defn encode-cursor
+  [values]
+  (-> (json/write-json-str (mapv encode-value values))
+      (codecs/str->bytes)
+      (codecs/bytes->b64 true)
+      (codecs/bytes->str)))
+
+(defn decode-cursor
+  "Returns the values in the cursor, or nil when it can't be read."
+  [s]
+  (try
+    (mapv decode-value (-> s codecs/str->bytes (codecs/b64->bytes true) codecs/bytes->str json/read-json))
+    (catch Exception _
+      nil)))
+
+;;; ----------------------------------------------------------------------------
+;;; Queries
+
+(defn paginate
+  "Adds ordering, a seek past the cursor and a limit to a HoneySQL query. One
+  extra row is fetched so `page` can tell whether another page follows."
+  [query {:keys [columns direction]} {:keys [cursor limit]}]
+  {:pre [(#{:asc :desc} direction) (pos-int? limit)]}
+  (let [names  (mapv first columns)
+        values (some-> cursor decode-cursor)
+        seek   (when (= (count names) (count values))
+                 [(if (= :desc direction) :< :>)
+                  (into [:composite] names)
+                  (into [:composite] values)])]
+    (cond-> (assoc query
+                   :order-by (mapv #(vector % direction) names)
+                   :limit    (inc limit))
+      seek (update :where (fn [where]
+                            (if where [:and where seek] seek))))))
+
+(defn page
+  "Envelope for rows fetched with `paginate`."
+  [rows {:keys [columns]} {:keys [limit]}]
+  (let [items (vec (take limit rows))]
+    {:items       items
+     :next-cursor (when (< limit (count rows))
+                    (encode-cursor (mapv #(get (peek items) (second %)) columns)))}))
@@ -0,0 +1,416 @@
+(ns bits.plugin
+  "Plugins tenants install to extend their storefront, as WebAssembly modules.
+
+  Plugins talk to us through a small host API with a version number, so the
+  API can change without breaking plugins built against an older one. A
+  version 1 plugin exports its `memory`, `bits_api_version` returning 1, and
+  `bits_alloc(len) -> ptr`, which we call to make room for input. Each hook
+  it implements is an export taking the pointer and length of UTF-8 JSON and
+  returning the pointer and length of its JSON answer packed into an i64,
+  pointer in the high half, or 0 when it has nothing to say:
+
+      bits_on_request   {method, path, query}           -> {headers}
+      bits_on_checkout  {currency, subtotal, total, …}  -> {adjustments}
+      bits_render_slot  {slot, page: {slug, title}}     -> {blocks}
+
+  Plugins may import `bits.log(ptr, len)` to write a line to our logs.
+
+  Nothing runs without the tenant's say-so. Each hook and host function needs
+  a capability the tenant granted at install, and hooks outside them are
+  never called. Every call gets a fresh instance, so plugins keep nothing
+  between calls and never see each other, with a memory ceiling, a deadline
+  and a cap on how much they may answer. A plugin that fails or runs out of
+  time is skipped and its error kept for the tenant's admins; the request
+  carries on as though it weren't installed.
+
+  Modules run on Chicory, a WebAssembly interpreter written in Java, so
+  there are no native libraries to ship for each platform."
+  (:require
+   [bits.anomaly :as anom]
+   [bits.blob :as blob]
+   [bits.cache :as cache]
+   [bits.clock :as clock]
+   [bits.locale :refer [tru]]
+   [bits.page :as page]
+   [bits.postgres :as postgres]
+   [bits.postgres.plugin :as postgres.plugin]
+   [bits.spec]
+   [charred.api :as json]
+   [clojure.set :as set]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [com.stuartsierra.component :as component]
+   [io.pedestal.log :as log]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (com.dylibso.chicory.runtime HostFunction ImportValues Instance WasmFunctionHandle)
+   (com.dylibso.chicory.wasm Parser WasmModule)
+   (com.dylibso.chicory.wasm.types FunctionType MemoryLimits ValType)
+   (java.nio.charset StandardCharsets)
+   (java.util.concurrent Callable ExecutionException ExecutorService Executors Future TimeUnit TimeoutException)))
+
+;;; ----------------------------------------------------------------------------
+;;; Host API
+
+(def api-version
+  1)
+
+(def hooks
+  "The export each capability's hook is called through."
+  {"checkout" "bits_on_checkout"
+   "request"  "bits_on_request"
+   "slot"     "bits_render_slot"})
+
+(def capabilities
+  #{"checkout" "log" "request" "slot"})
+
+(def slots
+  "Where in our pages plugins may add blocks."
+  #{"page.after-content"})
+
+(def ^:private required-exports
+  #{"memory" "bits_alloc" "bits_api_version"})
+
+(def ^:private host-imports
+  {"bits.log" "log"})
+
+(def ^:private max-log-lines
+  16)
+
+(def ^:private max-label
+  80)
+
+;;; ----------------------------------------------------------------------------
+;;; Modules
+
+(defn- exports
+  [^WasmModule module]
+  (let [section (.exportSection module)]
+    (into #{} (map #(.name (.getExport section (int %)))) (range (.exportCount section)))))
+
+(defn- imports
+  [^WasmModule module]
+  (let [section (.importSection module)]
+    (into #{}
+          (map #(let [i (.getImport section (int %))]
+                  (str (.module i) "." (.name i))))
+          (range (.importCount section)))))
+
+(defn- initial-pages
+  [^WasmModule module]
+  (or (some-> (.orElse (.memorySection module) nil) (.getMemory 0) .limits .initialPages)
+      1))
+
+(defn requested
+  "The capabilities a module would need for everything it exports and
+  imports."
+  [module]
+  (let [exported (exports module)]
+    (into (set (keep host-imports (imports module)))
+          (keep (fn [[capability export]] (when (contains? exported export) capability)))
+          hooks)))
+
+(defn- load-module
+  [plugins digest]
+  (cache/lookup (:modules plugins) digest
+                #(some-> (blob/fetch (:blobs plugins) digest) Parser/parse)))
+
+;;; ----------------------------------------------------------------------------
+;;; Running
+
+(defn- read-utf8
+  [^Instance instance ptr len]
+  (String. (.readBytes (.memory instance) (int ptr) (int len)) StandardCharsets/UTF_8))
+
+(defn- log-function
+  "`bits.log`, which traps unless the plugin may log."
+  [granted plugin-id]
+  (let [lines (volatile! 0)]
+    (HostFunction. "bits" "log" (FunctionType/of [ValType/I32 ValType/I32] [])
+                   (reify WasmFunctionHandle
+                     (apply [_ instance args]
+                       (when-not (contains? granted "log")
+                         (throw (ex-info "Plugin may not log" {::reason :forbidden})))
+                       (when (<= (vswap! lines inc) max-log-lines)
+                         (let [line (read-utf8 instance (aget ^longs args 0) (min 1000 (aget ^longs args 1)))]
+                           (log/info :msg "Plugin says" :plugin-id plugin-id :line line)))
+                       nil)))))
+
+(defn- instantiate
+  ^Instance [plugins ^WasmModule module granted plugin-id]
+  (-> (Instance/builder module)
+      (.withImportValues (-> (ImportValues/builder)
+                             (.addFunction (into-array HostFunction [(log-function granted plugin-id)]))
+                             (.build)))
+      (.withMemoryLimits (MemoryLimits. (int (initial-pages module)) (int (:max-memory-pages plugins))))
+      (.build)))
+
+(defn- call-export
+  [^Instance instance export & args]
+  (aget ^longs (.apply (.export instance ^String export) (long-array args)) 0))
+
+(defn- with-limits
+  "Runs f on the plugins' executor, interrupting it past the deadline."
+  [plugins f]
+  (let [^Future fut (.submit ^ExecutorService (:executor plugins) ^Callable f)]
+    (try
+      (.get fut (:timeout-ms plugins) TimeUnit/MILLISECONDS)
+      (catch TimeoutException _
+        (.cancel fut true)
+        (throw (ex-info "Plugin ran out of time" {::reason :timeout})))
+      (catch ExecutionException ex
+        (throw (or (ex-cause ex) ex))))))
+
+(defn- run-hook
+  [plugins instance capability input]
+  (let [bs     (.getBytes ^String (json/write-json-str input) StandardCharsets/UTF_8)
+        ptr    (call-export instance "bits_alloc" (alength bs))
+        _      (.write (.memory ^Instance instance) (int ptr) bs)
+        packed (call-export instance (get hooks capability) ptr (alength bs))
+        len    (bit-and packed 0xffffffff)]
+    (cond
+      (zero? packed)
+      nil
+
+      (< (:max-output-bytes plugins) len)
+      (throw (ex-info "Plugin answered with too much" {::reason :too-much-output}))
+
+      :else
+      (json/read-json (read-utf8 instance (unsigned-bit-shift-right packed 32) len) :key-fn keyword))))
+
+(defn- failed!
+  [plugins plugin-id ^Exception ex]
+  (log/warn :msg "Plugin failed!" :plugin-id plugin-id :exception ex)
+  (let [error (str (or (ex-message ex) (class ex)))]
+    (postgres/execute! (:postgres plugins)
+                       {:update :plugins
+                        :set    {:last-error (subs error 0 (min 500 (count error)))
+                                 :failed-at  (clock/now (:clock plugins))}
+                        :where  [:= :id plugin-id]})))
+
+(defn- call
+  "Calls the plugin's hook for capability with input, returning its answer,
+  or nil when it has none or fails."
+  [plugins p capability input]
+  (span/with-span! {:name ::call}
+    (let [{::postgres.plugin/keys [capabilities digest id]} p]
+      (try
+        (when-let [module (load-module plugins digest)]
+          (with-limits plugins
+            #(run-hook plugins (instantiate plugins module (set capabilities) id) capability input)))
+        (catch Exception ex
+          (failed! plugins id ex)
+          nil)))))
+
+(defn- enabled
+  "The tenant's enabled plugins granted capability. Hooks run on every
+  storefront request, so each tenant's plugins are cached for a few seconds."
+  [plugins tenant-id capability]
+  (filter #(some #{capability} (::postgres.plugin/capabilities %))
+          (cache/lookup (:cache plugins) tenant-id
+                        #(postgres/execute! (postgres/replica (:postgres plugins))
+                                            {:select   [:id :name :digest :capabilities]
+                                             :from     [:plugins]
+                                             :where    [:and [:= :tenant-id tenant-id] :enabled]
+                                             :order-by [:name]}))))
+
+;;; ----------------------------------------------------------------------------
+;;; Hooks
+
+(defn- header
+  "Only `x-` headers are taken, so plugins can't undo our security headers
+  or set cookies."
+  [[k v]]
+  (let [k (str/lower-case (name k))]
+    (when (and (re-matches #"x-[a-z0-9-]+" k)
+               (string? v)
+               (not (re-find #"[\r\n]" v)))
+      [k v])))
+
+(defn on-request
+  "Headers the tenant's plugins add to the response to request."
+  [plugins tenant-id request]
+  (let [input {:method (name (:request-method request))
+               :path   (:uri request)
+               :query  (:query-string request)}]
+    (into {}
+          (comp (keep #(call plugins % "request" input))
+                (mapcat :headers)
+                (keep header))
+          (enabled plugins tenant-id "request"))))
+
+(defn- adjustment
+  [{:keys [amount label]}]
+  (when (and (int? amount) (string? label) (not (str/blank? label)) (<= (count label) max-label))
+    {:amount amount :label label}))
+
+(defn adjust-totals
+  "Applies the tenant's plugins to a basket's totals, as `bits.shipping/totals`
+  makes them. Each plugin sees the basket as it was and may add adjustments,
+  a label and an amount in minor units, negative for money off. The total
+  never goes below zero."
+  [plugins tenant-id currency totals]
+  (let [input       (assoc totals :currency currency)
+        adjustments (into []
+                          (comp (keep #(call plugins % "checkout" input))
+                                (mapcat :adjustments)
+                                (keep adjustment))
+                          (enabled plugins tenant-id "checkout"))]
+    (assoc totals
+           :adjustments adjustments
+           :total       (max 0 (transduce (map :amount) + (:total totals) adjustments)))))
+
+(defn render-slot
+  "Blocks the tenant's plugins put in a slot, checked like a page's own.
+  Answers that don't check out are left out."
+  [plugins tenant-id slot context]
+  {:pre [(contains? slots slot)]}
+  (into []
+        (comp (keep #(call plugins % "slot" {:slot slot :page context}))
+              (map #(page/parse-blocks (json/write-json-str (:blocks % []))))
+              (remove anom/anomaly?)
+              cat)
+        (enabled plugins tenant-id "slot")))
+
+;;; ----------------------------------------------------------------------------
+;;; Installing
+
+(def reasons
+  #{:bad-name :empty :missing-exports :not-wasm :too-large :too-much-memory :unknown-import :wrong-version})
+
+(defn message
+  "Why a plugin was refused, for the tenant."
+  [{:keys [max-bytes max-memory-pages]} reason]
+  (case reason
+    :bad-name        (tru "Plugins need a name of at most {0} characters." max-label)
+    :empty           (tru "That file is empty.")
+    :missing-exports (tru "Plugins must export memory, bits_alloc and bits_api_version.")
+    :not-wasm        (tru "That isn''t a WebAssembly module.")
+    :too-large       (tru "Plugins can be at most {0} KB." (quot max-bytes 1024))
+    :too-much-memory (tru "Plugins can use at most {0} pages of memory." max-memory-pages)
+    :unknown-import  (tru "That plugin imports functions we don''t provide.")
+    :wrong-version   (tru "That plugin isn''t built for version {0} of the host API." api-version)))
+
+(defn- rejected
+  [plugins reason]
+  (anom/incorrect {::anom/message (message plugins reason)
+                   ::reason       reason}))
+
+(defn- reported-version
+  [plugins module]
+  (try
+    (with-limits plugins
+      #(call-export (instantiate plugins module #{} nil) "bits_api_version"))
+    (catch Exception _ nil)))
+
+(defn inspect
+  "Reads a module and checks it against the host API. Returns the module, or
+  an anomaly with a `::reason`."
+  [plugins ^bytes bs]
+  (let [module (when (< 0 (alength bs) (inc (:max-bytes plugins)))
+                 (try (Parser/parse bs) (catch Exception _ nil)))]
+    (cond
+      (zero? (alength bs))                                   (rejected plugins :empty)
+      (< (:max-bytes plugins) (alength bs))                  (rejected plugins :too-large)
+      (nil? module)                                          (rejected plugins :not-wasm)
+      (not-every? host-imports (imports module))             (rejected plugins :unknown-import)
+      (not (set/subset? required-exports (exports module)))  (rejected plugins :missing-exports)
+      (< (:max-memory-pages plugins) (initial-pages module)) (rejected plugins :too-much-memory)
+      (not= api-version (reported-version plugins module))   (rejected plugins :wrong-version)
+      :else                                                  module)))
+
+(defn install!
+  "Installs a plugin, allowed the capabilities it asks for that are among
+  granted. Installing one under a name the tenant already has replaces it,
+  keeping whether it's enabled. Returns the plugin or an anomaly."
+  [plugins tenant-id plugin-name ^bytes bs granted]
+  (span/with-span! {:name ::install!}
+    (let [plugin-name (str/trim (str plugin-name))
+          module      (if (or (str/blank? plugin-name) (< max-label (count plugin-name)))
+                        (rejected plugins :bad-name)
+                        (inspect plugins bs))]
+      (if (anom/anomaly? module)
+        module
+        (let [digest  (blob/put! (:blobs plugins) bs)
+              allowed (vec (sort (set/intersection (requested module) (set granted))))
+              p       (postgres/execute-one! (:postgres plugins)
+                                             {:insert-into   :plugins
+                                              :values        [{:id           (random-uuid)
+                                                               :tenant-id    tenant-id
+                                                               :name         plugin-name
+                                                               :digest       digest
+                                                               :api-version  api-version
+                                                               :capabilities [:array allowed :text]}]
+                                              :on-conflict   [:tenant-id :name]
+                                              :do-update-set {:digest       :excluded.digest
+                                                              :api-version  :excluded.api-version
+                                                              :capabilities :excluded.capabilities
+                                                              :last-error   nil
+                                                              :failed-at    nil}
+                                              :returning     [:id :name :digest :capabilities :enabled]})]
+          (cache/evict! (:cache plugins) tenant-id)
+          p)))))
+
+(defn list-plugins
+  [plugins tenant-id]
+  {:post [(s/valid? (s/coll-of ::postgres.plugin/persisted) %)]}
+  (span/with-span! {:name ::list-plugins}
+    (postgres/execute! (:postgres plugins)
+                       {:select   [:id :name :digest :api-version :capabilities :enabled
+                                   :last-error :failed-at :created-at]
+                        :from     [:plugins]
+                        :where    [:= :tenant-id tenant-id]
+                        :order-by [:name]})))
+
+(defn enable!
+  "Turns one of the tenant's plugins on or off. Returns the plugin, or nil
+  when the tenant has no such plugin."
+  [plugins tenant-id id enabled?]
+  (span/with-span! {:name ::enable!}
+    (let [p (postgres/execute-one! (:postgres plugins)
+                                   {:update    :plugins
+                                    :set       {:enabled enabled?}
+                                    :where     [:and [:= :id id] [:= :tenant-id tenant-id]]
+                                    :returning [:id :name]})]
+      (cache/evict! (:cache plugins) tenant-id)
+      p)))
+
+(defn uninstall!
+  "Removes one of the tenant's plugins. Returns the plugin, or nil when the
+  tenant has no such plugin. Its module is left for `bits.site/purge-blobs!`."
+  [plugins tenant-id id]
+  (span/with-span! {:name ::uninstall!}
+    (let [p (postgres/execute-one! (:postgres plugins)
+                                   {:delete-from :plugins
+                                    :where       [:and [:= :id id] [:= :tenant-id tenant-id]]
+                                    :returning   [:id :name]})]
+      (cache/evict! (:cache plugins) tenant-id)
+      p)))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(def ^:private module-cache
+  {:maximum-size 64
+   :ttl-seconds  3600})
+
+(defrecord Plugins [blobs cache clock executor max-bytes max-memory-pages max-output-bytes
+                    maximum-size modules postgres timeout-ms ttl-seconds]
+  component/Lifecycle
+  (start [this]
+    (assoc this
+           :cache    (cache/make-cache this)
+           :executor (Executors/newVirtualThreadPerTaskExecutor)
+           :modules  (cache/make-cache module-cache)))
+  (stop [this]
+    (some-> ^ExecutorService executor .shutdownNow)
+    (assoc this :cache nil :executor nil :modules nil)))
+
+(defmethod print-method Plugins
+  [_ ^java.io.Writer w]
+  (.write w "#<Plugins>"))
+
+(defn make-plugins
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Plugins config))
@@ -1,6 +1,7 @@
 (ns bits.postgres
   (:require
    [babashka.process :as proc]
+   [bits.secret :as secret]
    [bits.spec]
    [camel-snake-kebab.core :as csk]
    [charred.api :as json]
@@ -285,26 +286,126 @@
   [postgres conn]
   (assoc postgres ::conn conn))
 
+(defn replica
+  "Routes queries to the read replica when one is configured. Transactions stay
+  on their connection so reads inside them see their own writes. Replicas lag,
+  so only use this for reads that can tolerate slightly stale data."
+  [postgres]
+  (cond-> postgres
+    (and (nil? (::conn postgres)) (some? (:read-datasource postgres)))
+    (assoc :datasource (:read-datasource postgres))))
+
+;;; ----------------------------------------------------------------------------
+;;; Regions
+;;;
+;;; Tenants tagged with a region keep their data in that region. A node only
+;;; handles data for tenants in its own `:region`, and untagged ones, and
+;;; reaches its region's database through the pools `:regions` has for it. A
+;;; region without pools of its own uses the primary, which then lives there.
+;;; Only the node's own region is opened and migrated; `:regions` may list the
+;;; rest so every node can share one config.
+;;;
+;;; Requests are routed by `bits.middleware/wrap-residency`. Background work
+;;; runs over each of the node's `databases`: the outbox relay, webhook
+;;; deliveries and the reaper, which purges sessions, prunes tables and rolls
+;;; up analytics. What stays on the primary whatever the tenant's region is
+;;; the leader's lock and scheduled publishing events, which the leader writes
+;;; for every tenant. Datomic, with tenants, users and the catalog, is shared
+;;; by every region.
+
+(defn serves?
+  "Whether this node may handle data for a tenant in region. Untagged tenants
+  can be served anywhere."
+  [postgres region]
+  (or (nil? region) (= region (:region postgres))))
+
+(defn regional
+  "Routes queries to region's pools, and `replica` to its replica. Like
+  `replica`, transactions stay on their connection, so route before opening
+  one."
+  [postgres region]
+  (let [{:keys [datasource read-datasource]} (get-in postgres [:regional region])]
+    (cond-> postgres
+      (and (nil? (::conn postgres)) (some? datasource))
+      (assoc :datasource datasource :read-datasource read-datasource))))
+
+(defn databases
+  "Every database this node keeps tenant data in: the primary, then its own
+  region's when that has pools of its own."
+  [postgres]
+  (let [routed (regional postgres (:region postgres))]
+    (cond-> [postgres]
+      (not (identical? routed postgres)) (conj routed))))
+
+(defn repoint
+  "The component, and every component it holds, with postgres swapped in."
+  [component postgres]
+  (reduce-kv (fn [c k v]
+               (cond
+                 (= :postgres k) (assoc c k postgres)
+                 (record? v)     (assoc c k (repoint v postgres))
+                 :else           c))
+             component
+             component))
+
+(defmacro with-transaction
+  "Runs body with sym bound to postgres pinned to a transaction, which commits
+  when body returns and rolls back when it throws. When postgres is already
+  pinned to a transaction, body joins it instead, so nested calls commit or
+  roll back with the outermost."
+  {:style/indent 1}
+  [[sym postgres] & body]
+  `(let [postgres# ~postgres]
+     (if (::conn postgres#)
+       (let [~sym postgres#]
+         ~@body)
+       (jdbc/with-transaction [tx# (:datasource postgres#)]
+         (let [~sym (assoc-conn postgres# tx#)]
+           ~@body)))))
+
+(defn rollback!
+  "Rolls back the transaction postgres is pinned to, keeping the connection
+  open so the rest of the request can carry on without it."
+  [postgres]
+  (.rollback ^java.sql.Connection (::conn postgres)))
+
 ;;; ------------------------------------------------------------------------------------------------------------------
 ;;; Execute!
 
+;;; Queries slower than the component's `:slow-query-ms` are logged without their
+;;; parameters, which may hold secrets.
+
+(defn- timed
+  [connectable [sql-str] f]
+  (let [threshold (when (map? connectable) (:slow-query-ms connectable))
+        start     (System/nanoTime)
+        result    (f)
+        elapsed   (quot (- (System/nanoTime) start) 1000000)]
+    (when (and threshold (> elapsed threshold))
+      (log/warn :msg "Slow query!" :elapsed-ms elapsed :sql sql-str))
+    result))
+
 (defn execute!
   ([connectable query]
    (execute! connectable query nil))
   ([connectable query options]
    (span/with-span! {:name ::execute!}
-     (jdbc/execute! (->connectable connectable)
-                    (span/with-span! {:name ::format} (sql/format query options))
-                    (merge defaults options)))))
+     (let [sql-params (span/with-span! {:name ::format} (sql/format query options))]
+       (timed connectable sql-params
+              #(jdbc/execute! (->connectable connectable)
+                              sql-params
+                              (merge defaults options)))))))
 
 (defn execute-one!
   ([connectable query]
    (execute-one! connectable query nil))
   ([connectable query options]
    (span/with-span! {:name ::execute-one!}
-     (jdbc/execute-one! (->connectable connectable)
-                        (span/with-span! {:name ::format} (sql/format query options))
-                        (merge defaults options)))))
+     (let [sql-params (span/with-span! {:name ::format} (sql/format query options))]
+       (timed connectable sql-params
+              #(jdbc/execute-one! (->connectable connectable)
+                                  sql-params
+                                  (merge defaults options)))))))
 
 ;;; ------------------------------------------------------------------------------------------------------------------
 ;;; Enums
@@ -369,11 +470,23 @@
 ;;; ----------------------------------------------------------------------------
 ;;; Migrator
 
+;;; When `:credentials-path` is set the username and password come from the
+;;; secrets provider rather than the database URL. Dynamic credentials are
+;;; renewed while the pool is open, and rotated into it without a restart.
+
+(defn- credentials
+  [{:keys [credentials-path secrets]}]
+  (when credentials-path
+    (secret/fetch (:provider secrets) credentials-path)))
+
 (defn migrate
   [migrator]
   (span/with-span! {:name ::migrate}
     (let [{:keys [database-url]} migrator
-          ds                     (get-datasource {:jdbcUrl database-url})
+          {:keys [data]}         (credentials migrator)
+          ds                     (get-datasource (cond-> {:jdbcUrl database-url}
+                                                   data (assoc :user     (:username data)
+                                                               :password (:password data))))
           migrations             (ragtime.next-jdbc/load-resources "migrations")
           ops                    (atom [])
           reporter               (fn [_ op id]
@@ -396,11 +509,13 @@
                       :migration-names (mapv :id migrations)
                       :exception       exception)))))))
 
-(defrecord Migrator [database-url dump-structure? path throw-exceptions?]
+(defrecord Migrator [credentials-path database-url dump-structure? path region regions secrets throw-exceptions?]
   component/Lifecycle
   (start [this]
     (span/with-span! {:name ::start-migrator}
       (let [migrations (migrate this)
+            _          (when-let [{:keys [database-url]} (get regions region)]
+                         (migrate (assoc this :database-url database-url)))
             file       (io/file path)]
         (when (and dump-structure?
                    (or (not (.exists file)) (seq migrations)))
@@ -430,25 +545,118 @@
 ;;; ----------------------------------------------------------------------------
 ;;; Postgres
 
-(defrecord Postgres [crypto database-url datasource pool]
+(defn- pool-config
+  "HikariCP settings shared by the primary and replica pools. The statement
+  timeout is set per connection so a runaway query can't hold one forever."
+  [postgres]
+  (let [{:keys [connection-timeout-ms
+                maximum-pool-size
+                minimum-idle
+                statement-timeout-ms]} postgres]
+    {:connectionInitSql (format "SET statement_timeout = %d" statement-timeout-ms)
+     :connectionTimeout connection-timeout-ms
+     :maximumPoolSize   maximum-pool-size
+     :minimumIdle       minimum-idle}))
+
+(defn- open-pool
+  "Opens a pool and returns it with the datasource queries go through. Tests
+  may pass wrap-datasource to get between the two, e.g. to inject faults."
+  [database-url config wrap-datasource]
+  (let [pool (jdbc.connection/->pool HikariDataSource (assoc config :jdbcUrl database-url))
+        otel (GlobalOpenTelemetry/get)
+        ds   (.wrap (JdbcTelemetry/create otel) pool)]
+    (span/with-span! {:name ::verify-connection}
+      (with-open [_conn (get-connection ds)]
+        (log/trace :msg        "Connection established! Closing."
+                   :datasource ds)))
+    [pool ((or wrap-datasource identity) ds)]))
+
+(defn- rotate!
+  "Points new connections at fresh credentials, and retires idle connections
+  opened with the old ones as they're returned."
+  [^HikariDataSource pool {:keys [password username]}]
+  (when pool
+    (log/info :msg "Rotating database credentials." :username username)
+    (doto (.getHikariConfigMXBean pool)
+      (.setUsername username)
+      (.setPassword password))
+    (.softEvictConnections (.getHikariPoolMXBean pool))))
+
+(defn- open-regional
+  "Opens a region's pools, as `regional` wants them."
+  [{:keys [database-url replica-url]} config wrap-datasource]
+  (let [[pool ds]   (open-pool database-url config wrap-datasource)
+        [rpool rds] (when replica-url
+                      (open-pool replica-url (assoc config :readOnly true) wrap-datasource))]
+    {:datasource      ds
+     :pool            pool
+     :read-datasource rds
+     :read-pool       rpool}))
+
+(defn- close-pool
+  [^HikariDataSource pool database-url]
+  (when pool
+    (log/trace :msg          "Shutting down connection pool..."
+               :database-url database-url)
+    (.close pool)))
+
+(defrecord Postgres [connection-timeout-ms
+                     credentials-path
+                     crypto
+                     database-url
+                     datasource
+                     maximum-pool-size
+                     minimum-idle
+                     pool
+                     read-datasource
+                     read-pool
+                     region
+                     regional
+                     regions
+                     replica-url
+                     secrets
+                     slow-query-ms
+                     statement-timeout-ms
+                     wrap-datasource]
   component/Lifecycle
   (start [this]
     (span/with-span! {:name ::start-postgres}
-      (let [pool (jdbc.connection/->pool HikariDataSource {:jdbcUrl database-url})
-            otel (GlobalOpenTelemetry/get)
-            ds   (.wrap (JdbcTelemetry/create otel) pool)]
-        (span/with-span! {:name ::verify-connection}
-          (with-open [_conn (get-connection ds)]
-            (log/trace :msg        "Connection established! Closing."
-                       :datasource ds)))
-        (assoc this :datasource ds :pool pool))))
+      (let [creds       (credentials this)
+            config      (cond-> (pool-config this)
+                          creds (assoc :username (get-in creds [:data :username])
+                                       :password (get-in creds [:data :password])))
+            [pool ds]   (open-pool database-url config wrap-datasource)
+            [rpool rds] (when replica-url
+                          (open-pool replica-url (assoc config :readOnly true) wrap-datasource))
+            regional    (update-vals (select-keys regions [region])
+                                         #(open-regional % config wrap-datasource))]
+        (when creds
+          (secret/watch! secrets credentials-path creds
+                         (fn [data]
+                           (rotate! pool data)
+                           (rotate! rpool data)
+                           (doseq [pools (vals regional)]
+                             (rotate! (:pool pools) data)
+                             (rotate! (:read-pool pools) data)))))
+        (assoc this
+               :datasource      ds
+               :pool            pool
+               :read-datasource rds
+               :read-pool       rpool
+               :regional        regional))))
   (stop [this]
     (span/with-span! {:name ::stop-postgres}
-      (when-let [pool (:pool this)]
-        (log/trace :msg          "Shutting down connection pool..."
-                   :database-url database-url)
-        (.close ^HikariDataSource pool))
-      (assoc this :datasource nil :pool nil)))
+      (doseq [[region pools] regional]
+        (close-pool (:read-pool pools) (get-in regions [region :replica-url]))
+        (close-pool (:pool pools) (get-in regions [region :database-url])))
+      (close-pool read-pool replica-url)
+      (close-pool pool database-url)
+      (assoc this
+             :datasource      nil
+             :pool            nil
+             :read-datasource nil
+             :read-pool       nil
+             :regional        nil)))
 
   next.jdbc.protocols/Connectable
   (get-connection [this opts]
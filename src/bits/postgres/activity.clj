@@ -0,0 +1,14 @@
+(ns bits.postgres.activity
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::actor-id (s/nilable uuid?))
+(s/def ::created-at inst?)
+(s/def ::data map?)
+(s/def ::id uuid?)
+(s/def ::kind string?)
+(s/def ::tenant-id uuid?)
+
+(s/def ::persisted
+  (s/keys :req [::created-at ::data ::id ::kind]
+          :opt [::actor-id ::tenant-id]))
@@ -0,0 +1,22 @@
+(ns bits.postgres.analytics
+  (:require
+   [clojure.spec.alpha :as s]))
+
+;;; ----------------------------------------------------------------------------
+;;; Hourly rollups
+
+(s/def :bits.postgres.hourly-rollup/hour inst?)
+(s/def :bits.postgres.hourly-rollup/path string?)
+(s/def :bits.postgres.hourly-rollup/tenant-id uuid?)
+(s/def :bits.postgres.hourly-rollup/views nat-int?)
+(s/def :bits.postgres.hourly-rollup/visitors nat-int?)
+
+;;; ----------------------------------------------------------------------------
+;;; Salts
+
+(s/def :bits.postgres.analytics-salt/day #(instance? java.time.LocalDate %))
+(s/def :bits.postgres.analytics-salt/salt string?)
+
+(s/def ::salt
+  (s/keys :req [:bits.postgres.analytics-salt/day
+                :bits.postgres.analytics-salt/salt]))
@@ -0,0 +1,22 @@
+(ns bits.postgres.api-key
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::created-at inst?)
+(s/def ::id uuid?)
+(s/def ::last-used-at (s/nilable inst?))
+(s/def ::name string?)
+(s/def ::prefix string?)
+(s/def ::requests-per-minute pos-int?)
+(s/def ::scopes (s/coll-of string? :kind vector?))
+(s/def ::secret-hash string?)
+(s/def ::tenant-id uuid?)
+(s/def ::user-id uuid?)
+
+(s/def ::persisted
+  (s/keys :req [::id ::prefix ::scopes ::tenant-id ::user-id]
+          :opt [::created-at
+                ::last-used-at
+                ::name
+                ::requests-per-minute
+                ::secret-hash]))
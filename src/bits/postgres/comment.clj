@@ -0,0 +1,22 @@
+(ns bits.postgres.comment
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::author-id (s/nilable uuid?))
+(s/def ::author-name string?)
+(s/def ::body string?)
+(s/def ::created-at inst?)
+(s/def ::id uuid?)
+(s/def ::ip-hash string?)
+(s/def ::page-id uuid?)
+(s/def ::parent-id (s/nilable uuid?))
+(s/def ::reviewed-at (s/nilable inst?))
+(s/def ::reviewed-by (s/nilable uuid?))
+(s/def ::spam-reason (s/nilable string?))
+(s/def ::status #{"approved" "pending" "rejected" "spam"})
+(s/def ::tenant-id uuid?)
+
+(s/def ::persisted
+  (s/keys :req [::body ::created-at ::id ::page-id ::status]
+          :opt [::author-id ::author-name ::ip-hash ::parent-id ::reviewed-at ::reviewed-by
+                ::spam-reason ::tenant-id]))
@@ -0,0 +1,12 @@
+(ns bits.postgres.daily-rollup
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::currency string?)
+(s/def ::day #(instance? java.time.LocalDate %))
+(s/def ::metric string?)
+(s/def ::tenant-id uuid?)
+(s/def ::value int?)
+
+(s/def ::persisted
+  (s/keys :req [::currency ::day ::metric ::tenant-id ::value]))
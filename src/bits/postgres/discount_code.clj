@@ -0,0 +1,23 @@
+(ns bits.postgres.discount-code
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::amount-off (s/nilable pos-int?))
+(s/def ::code string?)
+(s/def ::created-at inst?)
+(s/def ::currency (s/nilable string?))
+(s/def ::disabled-at (s/nilable inst?))
+(s/def ::expires-at (s/nilable inst?))
+(s/def ::id uuid?)
+(s/def ::kind #{"fixed" "percent"})
+(s/def ::max-redemptions (s/nilable pos-int?))
+(s/def ::percent-off (s/nilable (s/int-in 1 101)))
+(s/def ::product-ids (s/coll-of uuid? :kind vector?))
+(s/def ::redemptions nat-int?)
+(s/def ::stackable boolean?)
+(s/def ::tenant-id uuid?)
+
+(s/def ::persisted
+  (s/keys :req [::code ::id ::kind ::product-ids ::redemptions ::stackable]
+          :opt [::amount-off ::created-at ::currency ::disabled-at ::expires-at
+                ::max-redemptions ::percent-off ::tenant-id]))
@@ -0,0 +1,25 @@
+(ns bits.postgres.feature-flag
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::created-at inst?)
+(s/def ::enabled boolean?)
+(s/def ::name string?)
+(s/def ::rollout-percent (s/int-in 0 101))
+(s/def ::updated-at inst?)
+
+(s/def ::persisted
+  (s/keys :req [::enabled ::name ::rollout-percent]
+          :opt [::created-at ::updated-at]))
+
+;;; ----------------------------------------------------------------------------
+;;; Tenant overrides
+
+(s/def :bits.postgres.feature-flag-tenant/enabled boolean?)
+(s/def :bits.postgres.feature-flag-tenant/flag-name string?)
+(s/def :bits.postgres.feature-flag-tenant/tenant-id uuid?)
+
+(s/def ::override
+  (s/keys :req [:bits.postgres.feature-flag-tenant/enabled
+                :bits.postgres.feature-flag-tenant/flag-name
+                :bits.postgres.feature-flag-tenant/tenant-id]))
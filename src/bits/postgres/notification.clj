@@ -0,0 +1,15 @@
+(ns bits.postgres.notification
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::created-at inst?)
+(s/def ::data map?)
+(s/def ::id uuid?)
+(s/def ::kind string?)
+(s/def ::read-at (s/nilable inst?))
+(s/def ::tenant-id uuid?)
+(s/def ::user-id uuid?)
+
+(s/def ::persisted
+  (s/keys :req [::created-at ::data ::id ::kind]
+          :opt [::read-at ::tenant-id ::user-id]))
@@ -0,0 +1,15 @@
+(ns bits.postgres.outbox
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::actor-id (s/nilable uuid?))
+(s/def ::created-at inst?)
+(s/def ::data string?)
+(s/def ::id uuid?)
+(s/def ::published-at (s/nilable inst?))
+(s/def ::tenant-id uuid?)
+(s/def ::topic string?)
+
+(s/def ::persisted
+  (s/keys :req [::created-at ::data ::id ::tenant-id ::topic]
+          :opt [::actor-id ::published-at]))
@@ -0,0 +1,18 @@
+(ns bits.postgres.page-revision
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::author-id (s/nilable uuid?))
+(s/def ::blocks string?)
+(s/def ::created-at inst?)
+(s/def ::id uuid?)
+(s/def ::number pos-int?)
+(s/def ::page-id uuid?)
+(s/def ::pinned boolean?)
+(s/def ::slug string?)
+(s/def ::tenant-id uuid?)
+(s/def ::title string?)
+
+(s/def ::revision
+  (s/keys :req [::blocks ::id ::number ::page-id ::slug ::title]
+          :opt [::author-id ::created-at ::pinned ::tenant-id]))
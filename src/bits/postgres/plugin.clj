@@ -0,0 +1,18 @@
+(ns bits.postgres.plugin
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::api-version pos-int?)
+(s/def ::capabilities (s/coll-of string? :kind vector?))
+(s/def ::created-at inst?)
+(s/def ::digest string?)
+(s/def ::enabled boolean?)
+(s/def ::failed-at (s/nilable inst?))
+(s/def ::id uuid?)
+(s/def ::last-error (s/nilable string?))
+(s/def ::name string?)
+(s/def ::tenant-id uuid?)
+
+(s/def ::persisted
+  (s/keys :req [::capabilities ::digest ::id ::name]
+          :opt [::api-version ::created-at ::enabled ::failed-at ::last-error ::tenant-id]))
@@ -0,0 +1,13 @@
+(ns bits.postgres.pricing-rule
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::created-at inst?)
+(s/def ::id uuid?)
+(s/def ::label string?)
+(s/def ::source string?)
+(s/def ::tenant-id uuid?)
+
+(s/def ::persisted
+  (s/keys :req [::id ::label ::source]
+          :opt [::created-at ::tenant-id]))
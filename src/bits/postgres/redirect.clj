@@ -0,0 +1,22 @@
+(ns bits.postgres.redirect
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def :bits.postgres.redirect/created-at inst?)
+(s/def :bits.postgres.redirect/destination string?)
+(s/def :bits.postgres.redirect/hits nat-int?)
+(s/def :bits.postgres.redirect/id uuid?)
+(s/def :bits.postgres.redirect/last-hit-at (s/nilable inst?))
+(s/def :bits.postgres.redirect/source string?)
+(s/def :bits.postgres.redirect/status #{301 302 307 308})
+(s/def :bits.postgres.redirect/tenant-id uuid?)
+
+(s/def ::rule
+  (s/keys :req [:bits.postgres.redirect/destination
+                :bits.postgres.redirect/id
+                :bits.postgres.redirect/source
+                :bits.postgres.redirect/status]
+          :opt [:bits.postgres.redirect/created-at
+                :bits.postgres.redirect/hits
+                :bits.postgres.redirect/last-hit-at
+                :bits.postgres.redirect/tenant-id]))
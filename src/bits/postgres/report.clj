@@ -0,0 +1,19 @@
+(ns bits.postgres.report
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::created-at inst?)
+(s/def ::details string?)
+(s/def ::id uuid?)
+(s/def ::reason string?)
+(s/def ::reporter-id (s/nilable uuid?))
+(s/def ::resolved-at (s/nilable inst?))
+(s/def ::resolved-by (s/nilable uuid?))
+(s/def ::status #{"actioned" "dismissed" "open"})
+(s/def ::target-id uuid?)
+(s/def ::target-type #{"page" "review" "tenant"})
+(s/def ::tenant-id uuid?)
+
+(s/def ::persisted
+  (s/keys :req [::created-at ::id ::reason ::status ::target-id ::target-type ::tenant-id]
+          :opt [::details ::reporter-id ::resolved-at ::resolved-by]))
@@ -0,0 +1,11 @@
+(ns bits.postgres.retention-override
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::days pos-int?)
+(s/def ::table-name string?)
+(s/def ::tenant-id uuid?)
+(s/def ::updated-at inst?)
+
+(s/def ::persisted
+  (s/keys :req [::days ::table-name ::tenant-id ::updated-at]))
@@ -0,0 +1,17 @@
+(ns bits.postgres.review
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::author-id uuid?)
+(s/def ::body string?)
+(s/def ::created-at inst?)
+(s/def ::hidden-at (s/nilable inst?))
+(s/def ::id uuid?)
+(s/def ::product-id uuid?)
+(s/def ::rating (s/int-in 1 6))
+(s/def ::tenant-id uuid?)
+(s/def ::updated-at inst?)
+
+(s/def ::persisted
+  (s/keys :req [::body ::created-at ::id ::rating]
+          :opt [::author-id ::hidden-at ::product-id ::tenant-id ::updated-at]))
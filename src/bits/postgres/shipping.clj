@@ -0,0 +1,43 @@
+(ns bits.postgres.shipping
+  (:require
+   [clojure.spec.alpha :as s]))
+
+;;; ----------------------------------------------------------------------------
+;;; Zones
+
+(s/def :bits.postgres.shipping-zone/created-at inst?)
+(s/def :bits.postgres.shipping-zone/id uuid?)
+(s/def :bits.postgres.shipping-zone/name string?)
+(s/def :bits.postgres.shipping-zone/regions (s/coll-of string? :kind vector?))
+(s/def :bits.postgres.shipping-zone/tenant-id uuid?)
+
+(s/def ::zone
+  (s/keys :req [:bits.postgres.shipping-zone/id
+                :bits.postgres.shipping-zone/name
+                :bits.postgres.shipping-zone/regions]
+          :opt [:bits.postgres.shipping-zone/created-at
+                :bits.postgres.shipping-zone/tenant-id]))
+
+;;; ----------------------------------------------------------------------------
+;;; Rates
+
+(s/def :bits.postgres.shipping-rate/amount nat-int?)
+(s/def :bits.postgres.shipping-rate/currency string?)
+(s/def :bits.postgres.shipping-rate/id uuid?)
+(s/def :bits.postgres.shipping-rate/kind #{"flat" "free-above" "weight"})
+(s/def :bits.postgres.shipping-rate/max-grams (s/nilable pos-int?))
+(s/def :bits.postgres.shipping-rate/min-grams (s/nilable nat-int?))
+(s/def :bits.postgres.shipping-rate/name string?)
+(s/def :bits.postgres.shipping-rate/threshold (s/nilable pos-int?))
+(s/def :bits.postgres.shipping-rate/zone-id uuid?)
+
+(s/def ::rate
+  (s/keys :req [:bits.postgres.shipping-rate/amount
+                :bits.postgres.shipping-rate/currency
+                :bits.postgres.shipping-rate/id
+                :bits.postgres.shipping-rate/kind
+                :bits.postgres.shipping-rate/name
+                :bits.postgres.shipping-rate/zone-id]
+          :opt [:bits.postgres.shipping-rate/max-grams
+                :bits.postgres.shipping-rate/min-grams
+                :bits.postgres.shipping-rate/threshold]))
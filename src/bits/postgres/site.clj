@@ -0,0 +1,48 @@
+(ns bits.postgres.site
+  (:require
+   [clojure.spec.alpha :as s]))
+
+;;; ----------------------------------------------------------------------------
+;;; Blobs
+
+(s/def :bits.postgres.blob/bytes bytes?)
+(s/def :bits.postgres.blob/created-at inst?)
+(s/def :bits.postgres.blob/digest string?)
+(s/def :bits.postgres.blob/size nat-int?)
+
+;;; ----------------------------------------------------------------------------
+;;; Releases
+
+(s/def :bits.postgres.site-release/created-at inst?)
+(s/def :bits.postgres.site-release/files nat-int?)
+(s/def :bits.postgres.site-release/id uuid?)
+(s/def :bits.postgres.site-release/size nat-int?)
+(s/def :bits.postgres.site-release/tenant-id uuid?)
+
+(s/def ::release
+  (s/keys :req [:bits.postgres.site-release/created-at
+                :bits.postgres.site-release/files
+                :bits.postgres.site-release/id
+                :bits.postgres.site-release/size]
+          :opt [:bits.postgres.site-release/tenant-id]))
+
+;;; ----------------------------------------------------------------------------
+;;; Files
+
+(s/def :bits.postgres.site-file/content-type string?)
+(s/def :bits.postgres.site-file/digest string?)
+(s/def :bits.postgres.site-file/path string?)
+(s/def :bits.postgres.site-file/release-id uuid?)
+
+(s/def ::file
+  (s/keys :req [:bits.postgres.site-file/content-type
+                :bits.postgres.site-file/digest
+                :bits.postgres.site-file/path]
+          :opt [:bits.postgres.site-file/release-id]))
+
+;;; ----------------------------------------------------------------------------
+;;; Sites
+
+(s/def :bits.postgres.site/release-id uuid?)
+(s/def :bits.postgres.site/released-at inst?)
+(s/def :bits.postgres.site/tenant-id uuid?)
@@ -0,0 +1,53 @@
+(ns bits.postgres.webhook
+  (:require
+   [clojure.spec.alpha :as s]))
+
+;;; ----------------------------------------------------------------------------
+;;; Endpoints
+
+(s/def :bits.postgres.webhook-endpoint/created-at inst?)
+(s/def :bits.postgres.webhook-endpoint/disabled-at (s/nilable inst?))
+(s/def :bits.postgres.webhook-endpoint/events (s/coll-of string? :kind vector?))
+(s/def :bits.postgres.webhook-endpoint/id uuid?)
+(s/def :bits.postgres.webhook-endpoint/secret string?)
+(s/def :bits.postgres.webhook-endpoint/tenant-id uuid?)
+(s/def :bits.postgres.webhook-endpoint/url string?)
+
+(s/def ::endpoint
+  (s/keys :req [:bits.postgres.webhook-endpoint/events
+                :bits.postgres.webhook-endpoint/id
+                :bits.postgres.webhook-endpoint/tenant-id
+                :bits.postgres.webhook-endpoint/url]
+          :opt [:bits.postgres.webhook-endpoint/created-at
+                :bits.postgres.webhook-endpoint/disabled-at
+                :bits.postgres.webhook-endpoint/secret]))
+
+;;; ----------------------------------------------------------------------------
+;;; Deliveries
+
+(s/def :bits.postgres.webhook-delivery/attempts nat-int?)
+(s/def :bits.postgres.webhook-delivery/completed-at (s/nilable inst?))
+(s/def :bits.postgres.webhook-delivery/created-at inst?)
+(s/def :bits.postgres.webhook-delivery/endpoint-id uuid?)
+(s/def :bits.postgres.webhook-delivery/event string?)
+(s/def :bits.postgres.webhook-delivery/id uuid?)
+(s/def :bits.postgres.webhook-delivery/last-error (s/nilable string?))
+(s/def :bits.postgres.webhook-delivery/next-attempt-at inst?)
+(s/def :bits.postgres.webhook-delivery/payload map?)
+(s/def :bits.postgres.webhook-delivery/response-status (s/nilable int?))
+(s/def :bits.postgres.webhook-delivery/status #{"pending" "succeeded" "dead"})
+(s/def :bits.postgres.webhook-delivery/tenant-id uuid?)
+
+(s/def ::delivery
+  (s/keys :req [:bits.postgres.webhook-delivery/attempts
+                :bits.postgres.webhook-delivery/event
+                :bits.postgres.webhook-delivery/id
+                :bits.postgres.webhook-delivery/status]
+          :opt [:bits.postgres.webhook-delivery/completed-at
+                :bits.postgres.webhook-delivery/created-at
+                :bits.postgres.webhook-delivery/endpoint-id
+                :bits.postgres.webhook-delivery/last-error
+                :bits.postgres.webhook-delivery/next-attempt-at
+                :bits.postgres.webhook-delivery/payload
+                :bits.postgres.webhook-delivery/response-status
+                :bits.postgres.webhook-delivery/tenant-id]))
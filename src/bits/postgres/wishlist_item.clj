@@ -0,0 +1,13 @@
+(ns bits.postgres.wishlist-item
+  (:require
+   [clojure.spec.alpha :as s]))
+
+(s/def ::created-at inst?)
+(s/def ::product-id uuid?)
+(s/def ::sid-hash (s/nilable string?))
+(s/def ::tenant-id uuid?)
+(s/def ::user-id (s/nilable uuid?))
+
+(s/def ::persisted
+  (s/keys :req [::product-id]
+          :opt [::created-at ::sid-hash ::tenant-id ::user-id]))
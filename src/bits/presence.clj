@@ -0,0 +1,28 @@
+(ns bits.presence
+  "Who else has an admin page open.
+
+  Every open morph stream is registered in the service's channels along with
+  its path, tenant and user, so presence is read from there rather than kept
+  anywhere else. Channels are local to an instance: admins connected to
+  different peers don't see each other."
+  (:require
+   [datomic.api :as d]))
+
+(defn others
+  "The IDs of users other than `user-id` with a stream open on the tenant's
+  path, in the order they arrived."
+  [channels {:keys [path tenant-id user-id]}]
+  (->> channels
+       vals
+       (filter #(and (= path (:path %))
+                     (= tenant-id (:tenant-id %))
+                     (some? (:user-id %))
+                     (not= user-id (:user-id %))))
+       (sort-by :connected-at)
+       (map :user-id)
+       distinct))
+
+(defn emails
+  "The email addresses of the users, in the same order."
+  [db user-ids]
+  (keep #(:user/email (d/pull db [:user/email] [:user/id %])) user-ids))
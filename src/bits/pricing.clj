@@ -0,0 +1,496 @@
+(ns bits.pricing
+  "Pricing rules tenants write themselves, run when a basket is priced.
+
+  A rule takes a percentage or an amount off, optionally only when the
+  basket meets a condition and except on lines matching another:
+
+      10% off when subtotal > 100 except sale
+      5 off when items >= 3 and currency = \"EUR\"
+
+  Conditions are a small expression language: numbers, strings, `true` and
+  `false`, arithmetic, comparisons, `and`, `or`, `not` and parentheses.
+  Amounts are in the basket's currency, in major units, so `100` is €100.
+  The condition after `when` sees the basket as `subtotal`, `total` (after
+  codes), `items` (how many things, counting each line's quantity) and
+  `currency`; the one after `except` sees each line as `price`, `sale` and
+  `product`.
+
+  Rules are parsed and type checked when they're saved, so a rule that
+  compares a price with a string or names something that isn't there is
+  refused with the reason. There are no loops or calls, and each rule gets a
+  budget of steps and a deadline of its own every time it runs; a rule that
+  goes over, or divides by zero, is skipped as though it didn't apply.
+
+  Rules run after discount codes, in the order they were added, each on
+  whatever of the lines is left to take off."
+  (:require
+   [bits.anomaly :as anom]
+   [bits.cache :as cache]
+   [bits.identifier :as identifier]
+   [bits.locale :refer [tru]]
+   [bits.postgres :as postgres]
+   [bits.postgres.pricing-rule :as postgres.pricing-rule]
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [com.stuartsierra.component :as component]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.math MathContext RoundingMode)
+   (java.util Currency)
+   (java.util.regex Matcher)))
+
+;;; ----------------------------------------------------------------------------
+;;; Reading
+;;;
+;;; Expressions are vectors: `[:num 10M]`, `[:var "subtotal"]`,
+;;; `[:cmp ">" a b]`, `[:and a b]` and so on.
+
+(def ^:private max-source
+  500)
+
+(def ^:private max-nodes
+  100)
+
+(def ^:private token-pattern
+  #"\s*(?:(\d+(?:\.\d+)?)|(\"[^\"]*\")|(<=|>=|!=|[=<>+\-*/()%])|([a-z_][a-z0-9_]*)|(\S))")
+
+(defn- tokenize
+  "The source's tokens, each with the index it starts `:at`."
+  [source]
+  (let [^Matcher m (re-matcher token-pattern source)]
+    (loop [tokens []]
+      (if (and (< (.regionStart m) (count source)) (.lookingAt m))
+        (let [[text number string op word other] (re-groups m)
+              end                                (.end m)
+              token                              (cond
+                                                   number {:kind :num :value (bigdec number)}
+                                                   string {:kind :str :value (subs string 1 (dec (count string)))}
+                                                   op     {:kind :op :value op}
+                                                   word   {:kind :word :value word}
+                                                   :else  {:kind :unknown :value other})]
+          (.region m end (count source))
+          (recur (conj tokens (assoc token :at (- end (count (str/triml text)))))))
+        tokens))))
+
+(defn- syntax-error
+  [message]
+  (throw (ex-info message {::syntax true})))
+
+(defn- peek-token
+  [state]
+  (get (:tokens @state) (:pos @state)))
+
+(defn- next-token!
+  [state]
+  (let [token (peek-token state)]
+    (vswap! state update :pos inc)
+    token))
+
+(defn- at?
+  [state value]
+  (= value (:value (peek-token state))))
+
+(defn- describe
+  [token]
+  (if token
+    (tru "\"{0}\" at {1}" (:value token) (inc (:at token)))
+    (tru "the end")))
+
+(defn- expect!
+  [state value]
+  (let [token (next-token! state)]
+    (when-not (= value (:value token))
+      (syntax-error (tru "Expected \"{0}\" but found {1}." value (describe token))))))
+
+(declare parse-or)
+
+(defn- parse-atom
+  [state]
+  (let [token (next-token! state)]
+    (case (:kind token)
+      :num  [:num (:value token)]
+      :str  [:str (:value token)]
+      :word (case (:value token)
+              "true"  [:bool true]
+              "false" [:bool false]
+              ("and" "or" "not" "off" "when" "except")
+              (syntax-error (tru "Expected a value but found {0}." (describe token)))
+              [:var (:value token)])
+      :op   (case (:value token)
+              "(" (let [e (parse-or state)]
+                    (expect! state ")")
+                    e)
+              "-" [:neg (parse-atom state)]
+              (syntax-error (tru "Expected a value but found {0}." (describe token))))
+      (syntax-error (tru "Expected a value but found {0}." (describe token))))))
+
+(defn- parse-product
+  [state]
+  (loop [left (parse-atom state)]
+    (if-let [op (some #(when (at? state %) %) ["*" "/"])]
+      (do
+        (next-token! state)
+        (recur [:arith op left (parse-atom state)]))
+      left)))
+
+(defn- parse-sum
+  [state]
+  (loop [left (parse-product state)]
+    (if-let [op (some #(when (at? state %) %) ["+" "-"])]
+      (do
+        (next-token! state)
+        (recur [:arith op left (parse-product state)]))
+      left)))
+
+(defn- parse-comparison
+  [state]
+  (let [left (parse-sum state)]
+    (if-let [op (some #(when (at? state %) %) ["=" "!=" "<" "<=" ">" ">="])]
+      (do
+        (next-token! state)
+        [:cmp op left (parse-sum state)])
+      left)))
+
+(defn- parse-not
+  [state]
+  (if (at? state "not")
+    (do
+      (next-token! state)
+      [:not (parse-not state)])
+    (parse-comparison state)))
+
+(defn- parse-and
+  [state]
+  (loop [left (parse-not state)]
+    (if (at? state "and")
+      (do
+        (next-token! state)
+        (recur [:and left (parse-not state)]))
+      left)))
+
+(defn- parse-or
+  [state]
+  (loop [left (parse-and state)]
+    (if (at? state "or")
+      (do
+        (next-token! state)
+        (recur [:or left (parse-and state)]))
+      left)))
+
+(defn- parse-rule
+  [state]
+  (let [amount (next-token! state)
+        _      (when-not (= :num (:kind amount))
+                 (syntax-error (tru "Rules start with an amount, like 10% or 5, but found {0}." (describe amount))))
+        off    (if (at? state "%")
+                 (do (next-token! state) {:percent (:value amount)})
+                 {:amount (:value amount)})
+        _      (expect! state "off")
+        rule   (cond-> off
+                 (at? state "when")   (assoc :when (do (next-token! state) (parse-or state)))
+                 (at? state "except") (assoc :except (do (next-token! state) (parse-or state))))]
+    (when-let [token (peek-token state)]
+      (syntax-error (tru "Unexpected {0}." (describe token))))
+    rule))
+
+;;; ----------------------------------------------------------------------------
+;;; Checking
+
+(def ^:private basket-vars
+  {"currency" :string
+   "items"    :number
+   "subtotal" :number
+   "total"    :number})
+
+(def ^:private line-vars
+  {"price"   :number
+   "product" :string
+   "sale"    :boolean})
+
+(defn- type-name
+  [t]
+  (case t
+    :boolean (tru "true or false")
+    :number  (tru "a number")
+    :string  (tru "text")))
+
+(defn- type-of
+  "The expression's type, given the types of the names it can see."
+  [vars [op & args :as e]]
+  (letfn [(want [t x]
+            (let [actual (type-of vars x)]
+              (when-not (= t actual)
+                (syntax-error (tru "Expected {0} but found {1}." (type-name t) (type-name actual))))
+              t))]
+    (case op
+      :num   :number
+      :str   :string
+      :bool  :boolean
+      :var   (or (get vars (first args))
+                 (syntax-error (tru "Unknown name \"{0}\". Here you can use {1}."
+                                    (first args) (str/join ", " (sort (keys vars))))))
+      :neg   (want :number (first args))
+      :arith (do (want :number (nth args 1)) (want :number (nth args 2)))
+      :not   (want :boolean (first args))
+      (:and
+       :or)  (do (want :boolean (first args)) (want :boolean (second args)))
+      :cmp   (let [[cmp a b] args
+                   t         (type-of vars a)]
+               (when (and (not (contains? #{"=" "!="} cmp)) (not= :number t))
+                 (syntax-error (tru "Only numbers can be compared with {0}." cmp)))
+               (want t b)
+               :boolean)
+      (syntax-error (pr-str e)))))
+
+(defn- nodes
+  [e]
+  (if (vector? e)
+    (transduce (map nodes) + 1 (filter vector? e))
+    0))
+
+(defn parse
+  "Reads and checks a rule. Returns it, or an anomaly explaining what's
+  wrong."
+  [source]
+  (try
+    (let [source (str/trim (str source))]
+      (when (< max-source (count source))
+        (syntax-error (tru "Rules can be at most {0} characters." max-source)))
+      (let [rule (parse-rule (volatile! {:tokens (tokenize source) :pos 0}))]
+        (when-let [p (:percent rule)]
+          (when-not (and (pos? p) (<= p 100))
+            (syntax-error (tru "Percentages are more than 0 and at most 100."))))
+        (when-not (or (:percent rule) (pos? (:amount rule)))
+          (syntax-error (tru "Amounts must be more than zero.")))
+        (when-let [e (:when rule)]
+          (when-not (= :boolean (type-of basket-vars e))
+            (syntax-error (tru "The condition after when must be true or false."))))
+        (when-let [e (:except rule)]
+          (when-not (= :boolean (type-of line-vars e))
+            (syntax-error (tru "The condition after except must be true or false."))))
+        (when (< max-nodes (+ (nodes (:when rule)) (nodes (:except rule))))
+          (syntax-error (tru "That rule is too long. Split it in two.")))
+        rule))
+    (catch clojure.lang.ExceptionInfo ex
+      (if (::syntax (ex-data ex))
+        (anom/incorrect {::anom/message (ex-message ex)})
+        (throw ex)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Running
+
+(defn- budget
+  [{:keys [max-steps timeout-ms]}]
+  {:deadline (+ (System/nanoTime) (* 1000000 timeout-ms))
+   :steps    (volatile! max-steps)})
+
+(defn- step!
+  [{:keys [deadline steps]}]
+  (when (or (neg? (vswap! steps dec)) (< deadline (System/nanoTime)))
+    (throw (ex-info (tru "The rules took too long.") {::limit true}))))
+
+(defn- evaluate
+  [b env [op & args]]
+  (step! b)
+  (let [ev #(evaluate b env %)]
+    (case op
+      (:num :str :bool) (first args)
+      :var              (get env (first args))
+      :neg              (.negate ^BigDecimal (ev (first args)))
+      :not              (not (ev (first args)))
+      :and              (and (ev (first args)) (ev (second args)))
+      :or               (or (ev (first args)) (ev (second args)))
+      :arith            (let [[arith a b] args
+                              ^BigDecimal x (ev a)
+                              ^BigDecimal y (ev b)]
+                          (case arith
+                            "+" (.add x y)
+                            "-" (.subtract x y)
+                            "*" (.multiply x y)
+                            "/" (.divide x y MathContext/DECIMAL64)))
+      :cmp              (let [[cmp a b] args
+                              c         (compare (ev a) (ev b))]
+                          (case cmp
+                            "="  (zero? c)
+                            "!=" (not (zero? c))
+                            "<"  (neg? c)
+                            "<=" (not (pos? c))
+                            ">"  (pos? c)
+                            ">=" (not (neg? c)))))))
+
+(defn- digits
+  [currency]
+  (max 0 (.getDefaultFractionDigits (Currency/getInstance ^String currency))))
+
+(defn- major
+  [minor currency]
+  (.movePointLeft (bigdec minor) (digits currency)))
+
+(defn- minor
+  [^BigDecimal amount currency]
+  (-> amount (.movePointRight (digits currency)) (.setScale 0 RoundingMode/DOWN) .longValueExact))
+
+(defn- remaining
+  [line]
+  (- (:amount line) (:discount line 0)))
+
+(defn- take-off
+  "Takes rule's amount off the lines it applies to, percentages line by line
+  and amounts spread over them in basket order. Returns the lines and how
+  much came off."
+  [rule lines eligible? currency]
+  (if-let [percent (:percent rule)]
+    (let [offs (mapv #(if (eligible? %)
+                        (minor (.divide (.multiply ^BigDecimal (major (remaining %) currency) ^BigDecimal percent)
+                                        100M MathContext/DECIMAL64)
+                               currency)
+                        0)
+                     lines)]
+      [(mapv #(update %1 :discount (fnil + 0) %2) lines offs) (reduce + offs)])
+    (let [[lines left] (reduce (fn [[lines left] line]
+                                 (let [off (if (eligible? line) (min left (remaining line)) 0)]
+                                   [(conj lines (update line :discount (fnil + 0) off)) (- left off)]))
+                               [[] (minor (:amount rule) currency)]
+                               lines)]
+      [lines (- (minor (:amount rule) currency) left)])))
+
+(defn- line-env
+  [currency line]
+  {"price"   (major (:amount line) currency)
+   "product" (or (some->> (:product-id line) (identifier/prefixed :product)) "")
+   "sale"    (boolean (:sale? line))})
+
+(defn- run-rule
+  [b quote rule currency]
+  (let [env {"currency" currency
+             "items"    (bigdec (transduce (map #(:quantity % 1)) + 0 (:lines quote)))
+             "subtotal" (major (:subtotal quote) currency)
+             "total"    (major (:total quote) currency)}]
+    (if (and (:when rule) (not (evaluate b env (:when rule))))
+      [(:lines quote) 0]
+      (take-off rule
+                (:lines quote)
+                #(and (pos? (remaining %))
+                      (not (and (:except rule) (evaluate b (line-env currency %) (:except rule)))))
+                currency))))
+
+(defn run
+  "Runs rules, as `{:label ... :rule ...}`, over a quote from
+  `bits.discount/quote-basket`. Returns the quote with the rules' discounts
+  added and a result for each rule: how much it took off, or why it was
+  skipped."
+  [limits quote rules]
+  (let [currency (or (:currency (first (:lines quote))) "GBP")]
+    (reduce (fn [{:keys [quote results]} {:keys [label rule]}]
+              (let [[lines off error] (try
+                                        (run-rule (budget limits) quote rule currency)
+                                        (catch ArithmeticException _
+                                          [nil 0 (tru "It divided by zero.")])
+                                        (catch clojure.lang.ExceptionInfo ex
+                                          (if (::limit (ex-data ex))
+                                            [nil 0 (ex-message ex)]
+                                            (throw ex))))
+                    discount          (+ (:discount quote) off)]
+                {:quote   (cond-> quote
+                            lines      (assoc :lines lines :discount discount :total (- (:subtotal quote) discount))
+                            (pos? off) (update :rules (fnil conj []) label))
+                 :results (conj results (cond-> {:label label :off off}
+                                          error (assoc :error error)))}))
+            {:quote quote :results []}
+            rules)))
+
+;;; ----------------------------------------------------------------------------
+;;; Rules
+
+(def ^:private max-rules
+  50)
+
+(defn- compile-rules
+  [rows]
+  (into []
+        (keep #(let [rule (parse (::postgres.pricing-rule/source %))]
+                 (when-not (anom/anomaly? rule)
+                   {:label (::postgres.pricing-rule/label %) :rule rule})))
+        rows))
+
+(defn list-rules
+  "The tenant's rules in the order they run."
+  [pricing tenant-id]
+  {:post [(s/valid? (s/coll-of ::postgres.pricing-rule/persisted) %)]}
+  (span/with-span! {:name ::list-rules}
+    (postgres/execute! (:postgres pricing)
+                       {:select   [:id :label :source :created-at]
+                        :from     [:pricing-rules]
+                        :where    [:= :tenant-id tenant-id]
+                        :order-by [:created-at :id]})))
+
+(defn rules
+  "The tenant's rules, parsed, for `run`. Quotes are priced on every change
+  to a basket, so each tenant's are cached for a few seconds."
+  [pricing tenant-id]
+  (cache/lookup (:cache pricing) tenant-id #(compile-rules (list-rules pricing tenant-id))))
+
+(defn reprice
+  "Runs the tenant's rules over a quote. Returns the quote."
+  [pricing tenant-id quote]
+  (span/with-span! {:name ::reprice}
+    (:quote (run pricing quote (rules pricing tenant-id)))))
+
+(defn create!
+  "Adds a rule to the end of the tenant's. Returns its ID, or an anomaly when
+  it doesn't check out."
+  [pricing tenant-id label source]
+  (span/with-span! {:name ::create!}
+    (let [label  (str/trim (str label))
+          parsed (parse source)]
+      (cond
+        (anom/anomaly? parsed)
+        parsed
+
+        (str/blank? label)
+        (anom/incorrect {::anom/message (tru "Give the rule a name buyers will see.")})
+
+        (<= max-rules (count (list-rules pricing tenant-id)))
+        (anom/incorrect {::anom/message (tru "You can have at most {0} rules." max-rules)})
+
+        :else
+        (let [created (postgres/execute-one! (:postgres pricing)
+                                             {:insert-into :pricing-rules
+                                              :values      [{:id        (random-uuid)
+                                                             :tenant-id tenant-id
+                                                             :label     label
+                                                             :source    (str/trim source)}]
+                                              :returning   [:id]})]
+          (cache/evict! (:cache pricing) tenant-id)
+          (::postgres.pricing-rule/id created))))))
+
+(defn delete!
+  "Returns true when the tenant had the rule."
+  [pricing tenant-id id]
+  (span/with-span! {:name ::delete!}
+    (let [[{:keys [next.jdbc/update-count]}]
+          (postgres/execute! (:postgres pricing)
+                             {:delete-from :pricing-rules
+                              :where       [:and [:= :id id] [:= :tenant-id tenant-id]]})]
+      (cache/evict! (:cache pricing) tenant-id)
+      (pos? (or update-count 0)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Pricing [cache max-steps maximum-size postgres timeout-ms ttl-seconds]
+  component/Lifecycle
+  (start [this]
+    (assoc this :cache (cache/make-cache this)))
+  (stop [this]
+    (assoc this :cache nil)))
+
+(defmethod print-method Pricing
+  [_ ^java.io.Writer w]
+  (.write w "#<Pricing>"))
+
+(defn make-pricing
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Pricing config))
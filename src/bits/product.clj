@@ -0,0 +1,176 @@
+(ns bits.product
+  (:require
+   [bits.anomaly :as anom]
+   [bits.deletion :as deletion]
+   [bits.entity]
+   [bits.license :as license]
+   [bits.locale :refer [tru]]
+   [bits.schedule :as schedule]
+   [bits.version :as version]
+   [clojure.spec.alpha :as s]
+   [datomic.api :as d]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Reading
+
+(defn lookup
+  "The tenant's live product, or nil."
+  [db tenant-id product-id]
+  (let [product (deletion/lookup db tenant-id :product product-id)]
+    (when-not (deletion/deleted? product)
+      product)))
+
+(defn visible?
+  "Whether buyers can see the product at now, see bits.schedule."
+  [product now]
+  (schedule/within? (:product/publish-at product) (:product/unpublish-at product) now))
+
+(defn lookup-visible
+  "The tenant's live product when buyers can see it at now, or nil."
+  [db tenant-id product-id now]
+  (let [product (lookup db tenant-id product-id)]
+    (when (and product (visible? product now))
+      product)))
+
+(defn version
+  [product]
+  (version/version product :product/version))
+
+(defn sold?
+  "Whether anyone bought any of the product's variants."
+  [db product-id]
+  (some? (d/q '[:find ?li .
+                :in $ ?product-id
+                :where
+                [?p :product/id ?product-id]
+                [?p :product/variants ?v]
+                [?li :line-item/variant ?v]]
+              db product-id)))
+
+;;; ----------------------------------------------------------------------------
+;;; Editing
+
+(def ^:private statuses
+  #{:product.status/active :product.status/archived :product.status/draft})
+
+(defn- invalid
+  [changes]
+  (cond
+    (and (contains? changes :product/title) (not (s/valid? :product/title (:product/title changes))))
+    (tru "Title can''t be blank.")
+
+    (and (contains? changes :product/status) (not (contains? statuses (:product/status changes))))
+    (tru "Status must be active, draft or archived.")
+
+    (and (contains? changes :product/position) (not (pos-int? (:product/position changes))))
+    (tru "Position must be a positive whole number.")
+
+    (and (contains? changes :product/description) (not (string? (:product/description changes))))
+    (tru "Description must be text.")
+
+    (and (contains? changes :product/license) (not (contains? license/licenses (:product/license changes))))
+    (tru "License must be one of the presets or custom.")
+
+    (and (contains? changes :product/license-terms) (not (string? (:product/license-terms changes))))
+    (tru "License terms must be text.")
+
+    (and (contains? changes :product/publish-at) (not ((some-fn nil? inst?) (:product/publish-at changes))))
+    (tru "Publish at must be a time.")
+
+    (and (contains? changes :product/unpublish-at) (not ((some-fn nil? inst?) (:product/unpublish-at changes))))
+    (tru "Unpublish at must be a time.")))
+
+(defn- schedule-error
+  "Why the times the changes leave the product with can't be, or nil."
+  [product changes]
+  (let [^java.util.Date publish-at   (get changes :product/publish-at (:product/publish-at product))
+        ^java.util.Date unpublish-at (get changes :product/unpublish-at (:product/unpublish-at product))]
+    (when (and publish-at unpublish-at (not (.before publish-at unpublish-at)))
+      (tru "Unpublish at must be after publish at."))))
+
+(def ^:private schedule-attrs
+  [:product/publish-at :product/unpublish-at])
+
+(defn- without-cleared
+  [changes]
+  (into {} (remove (fn [[k v]] (and (nil? v) (some #{k} schedule-attrs)))) changes))
+
+(defn- schedule-tx
+  "Retracts the times the changes clear."
+  [product changes]
+  (for [attr  schedule-attrs
+        :when (and (contains? changes attr) (nil? (get changes attr)) (get product attr))]
+    [:db/retract (:db/id product) attr (get product attr)]))
+
+(defn- license-changes
+  "Fills in the license the changes leave the product with, or returns an
+  anomaly. Custom licenses need terms, and presets drop any left over."
+  [product changes]
+  (if-not (some #(contains? changes %) [:product/license :product/license-terms])
+    changes
+    (let [license (get changes :product/license (or (:product/license product) license/default))
+          terms   (get changes :product/license-terms (:product/license-terms product))]
+      (cond
+        (not= :product.license/custom license)
+        (assoc changes :product/license license :product/license-terms nil)
+
+        (bits.entity/present? terms)
+        (assoc changes :product/license license :product/license-terms terms)
+
+        :else
+        (anom/incorrect {::anom/message (tru "Custom licenses need terms.")})))))
+
+(defn- license-tx
+  "Sets the license attributes, retracting terms a preset no longer needs."
+  [product changes]
+  (let [terms (:product/license-terms changes)]
+    (cond-> [(cond-> (dissoc changes :product/license-terms)
+               (some? terms) (assoc :product/license-terms terms)
+               :always       (assoc :db/id (:db/id product)))]
+      (and (contains? changes :product/license-terms) (nil? terms) (:product/license-terms product))
+      (conj [:db/retract (:db/id product) :product/license-terms (:product/license-terms product)]))))
+
+(def editable
+  [:product/description
+   :product/license
+   :product/license-terms
+   :product/position
+   :product/publish-at
+   :product/status
+   :product/title
+   :product/unpublish-at])
+
+(defn update!
+  "Applies the changes when the product is still at the expected version.
+  Returns the updated product, or an anomaly. A conflict carries the product
+  as it is now in `::current`, so the caller can show what changed. Once a
+  product has sold, its license is fixed: buyers agreed to those terms."
+  [conn product expected changes]
+  (span/with-span! {:name ::update!}
+    (let [changes (select-keys changes editable)
+          message (or (invalid changes) (schedule-error product changes))
+          changes (when-not message (license-changes product changes))]
+      (cond
+        message
+        (anom/incorrect {::anom/message message})
+
+        (anom/anomaly? changes)
+        changes
+
+        (and (contains? changes :product/license)
+             (not= (:hash (license/describe product))
+                   (:hash (license/describe (select-keys changes [:product/license :product/license-terms]))))
+             (sold? (d/entity-db product) (:product/id product)))
+        (anom/forbidden {::anom/message (tru "This product has sold, so its license can''t change.")})
+
+        :else
+        (let [result (version/transact! conn
+                                        (-> (version/bump-tx product :product/version expected)
+                                            (into (license-tx product (without-cleared changes)))
+                                            (into (schedule-tx product changes))))]
+          (if (anom/anomaly? result)
+            (-> result
+                (dissoc ::version/current)
+                (assoc ::current (d/entity (::version/current result) (:db/id product))))
+            (d/entity (:db-after result) (:db/id product))))))))
@@ -0,0 +1,196 @@
+(ns bits.projection
+  "Daily per-tenant totals for dashboards, kept in summary tables so nobody
+  aggregates over orders and members to draw a chart.
+
+  The projector subscribes to the event bus and adds each event to its day's
+  totals. Events arrive from the outbox, which may relay one twice, so each
+  event's `:id` is recorded in the same transaction as its totals and a repeat
+  is ignored. A projector that falls behind the bus has events dropped; the
+  totals drift until the tenant is backfilled from Datomic.
+
+  Days are the UTC day an event was published. Revenue is kept per currency
+  in minor units."
+  (:require
+   [bits.deletion :as deletion]
+   [bits.event :as event]
+   [bits.postgres :as postgres]
+   [bits.postgres.daily-rollup :as postgres.daily-rollup]
+   [bits.spec]
+   [clojure.core.async :as a]
+   [clojure.spec.alpha :as s]
+   [com.stuartsierra.component :as component]
+   [datomic.api :as d]
+   [io.pedestal.log :as log]
+   [next.jdbc :as jdbc]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.time LocalDate OffsetDateTime ZoneOffset)
+   (java.util Date)))
+
+;;; ----------------------------------------------------------------------------
+;;; Metrics
+
+(def ^:private increments
+  "What each topic adds, as `[metric currency value]` triples."
+  {"member.created" (fn [_] [["signups" "" 1]])
+   "order.created"  (fn [{:keys [amount currency]}]
+                      [["orders" "" 1]
+                       ["revenue" currency amount]])})
+
+(def topics
+  (set (keys increments)))
+
+(defn- utc-day
+  ^LocalDate [^OffsetDateTime at]
+  (.toLocalDate (.withOffsetSameInstant at ZoneOffset/UTC)))
+
+(defn- date->day
+  ^LocalDate [^Date d]
+  (.toLocalDate (.atOffset (.toInstant d) ZoneOffset/UTC)))
+
+(defn- add!
+  [connectable rows]
+  (when (seq rows)
+    (postgres/execute! connectable
+                       {:insert-into   :daily-rollups
+                        :values        rows
+                        :on-conflict   [:tenant-id :day :metric :currency]
+                        :do-update-set {:value [:+ :daily-rollups.value :excluded.value]}})))
+
+;;; ----------------------------------------------------------------------------
+;;; Projecting
+
+(defn project!
+  "Adds the event to its tenant's totals. Returns false when the event has
+  been counted already or isn't one the projector counts."
+  [projector {:keys [at data tenant-id topic]}]
+  (span/with-span! {:name ::project!}
+    (boolean
+     (when-let [increment (get increments topic)]
+       (jdbc/with-transaction [tx (get-in projector [:postgres :datasource])]
+         (when (postgres/execute-one! tx {:insert-into :projected-events
+                                          :values      [{:id (:id data)}]
+                                          :on-conflict [:id]
+                                          :do-nothing  true
+                                          :returning   [:id]})
+           (add! tx (for [[metric currency value] (increment data)]
+                      {:tenant-id tenant-id
+                       :day       (utc-day at)
+                       :metric    metric
+                       :currency  currency
+                       :value     value}))
+           true))))))
+
+(defn- consume!
+  [projector ch]
+  (a/thread
+    (loop []
+      (when-let [e (a/<!! ch)]
+        (try
+          (project! projector e)
+          (catch Exception ex
+            (log/warn :msg "Failed to project event?!" :topic (:topic e) :exception ex)
+            (span/add-exception! ex {:escaping? false})))
+        (recur)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Backfill
+
+(defn- order-rows
+  [db tenant-id]
+  (for [e     (d/datoms db :eavt [:tenant/id tenant-id] :tenant/line-items)
+        :let  [li (d/entity db (:v e))]
+        :when (not (deletion/deleted? li))
+        :let  [day (date->day (:line-item/created-at li))]
+        row   [{:metric "orders" :currency "" :day day :value 1}
+               {:metric   "revenue"
+                :currency (some-> li :line-item/unit-price :money/currency name)
+                :day      day
+                :value    (* (:line-item/quantity li)
+                             (get-in li [:line-item/unit-price :money/amount]))}]]
+    row))
+
+(defn- signup-rows
+  "Memberships have no creation time of their own, so the transaction that
+  created each one stands in."
+  [db tenant-id]
+  (for [at (d/q '[:find [?at ...]
+                  :in $ ?tenant-id
+                  :where
+                  [?t :tenant/id ?tenant-id]
+                  [?m :membership/tenant ?t]
+                  [?m :membership/id _ ?tx]
+                  [?tx :db/txInstant ?at]
+                  (not [?m :entity/deleted-at])]
+                db tenant-id)]
+    {:metric "signups" :currency "" :day (date->day at) :value 1}))
+
+(defn backfill!
+  "Rebuilds the tenant's totals from Datomic, replacing whatever was
+  projected. Returns how many daily totals were written."
+  [projector db tenant-id]
+  (span/with-span! {:name ::backfill!}
+    (let [totals (->> (concat (order-rows db tenant-id) (signup-rows db tenant-id))
+                      (group-by (juxt :day :metric :currency))
+                      (map (fn [[[day metric currency] rows]]
+                             {:tenant-id tenant-id
+                              :day       day
+                              :metric    metric
+                              :currency  currency
+                              :value     (reduce + (map :value rows))})))]
+      (jdbc/with-transaction [tx (get-in projector [:postgres :datasource])]
+        (postgres/execute! tx {:delete-from :daily-rollups
+                               :where       [:= :tenant-id tenant-id]})
+        (doseq [batch (partition-all 500 totals)]
+          (add! tx batch)))
+      (count totals))))
+
+;;; ----------------------------------------------------------------------------
+;;; Reading
+
+(defn dashboard-stats
+  "Totals per day from `from` up to and including `to`, oldest first, for one
+  tenant or every tenant when tenant-id is nil. Each day looks like
+  `{:day ... :orders 3 :revenue {\"GBP\" 4500} :signups 1}`; days without
+  events are left out."
+  [projector tenant-id ^LocalDate from ^LocalDate to]
+  (span/with-span! {:name ::dashboard-stats}
+    (let [rows (postgres/execute! (postgres/replica (:postgres projector))
+                                  {:select   [:day :metric :currency :value]
+                                   :from     [:daily-rollups]
+                                   :where    (cond-> [:and [:>= :day from] [:<= :day to]]
+                                               tenant-id (conj [:= :tenant-id tenant-id]))
+                                   :order-by [:day]})]
+      (->> rows
+           (reduce (fn [days {::postgres.daily-rollup/keys [currency day metric value]}]
+                     (if (= "revenue" metric)
+                       (update-in days [day :revenue currency] (fnil + 0) value)
+                       (update-in days [day (keyword metric)] (fnil + 0) value)))
+                   (sorted-map))
+           (map (fn [[day totals]]
+                  (merge {:day day :orders 0 :revenue {} :signups 0} totals)))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Projector [buffer-size channels datomic events postgres]
+  component/Lifecycle
+  (start [this]
+    (let [channels (mapv #(event/subscribe! events % "projector" buffer-size) (sort topics))]
+      (doseq [ch channels]
+        (consume! this ch))
+      (assoc this :channels channels)))
+
+  (stop [this]
+    (doseq [ch channels]
+      (event/unsubscribe! events ch))
+    (assoc this :channels nil)))
+
+(defmethod print-method Projector
+  [_ ^java.io.Writer w]
+  (.write w "#<Projector>"))
+
+(defn make-projector
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Projector config))
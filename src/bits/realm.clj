@@ -0,0 +1,144 @@
+(ns bits.realm
+  (:require
+   [bits.cache :as cache]
+   [bits.datomic :as datomic]
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [com.stuartsierra.component :as component]
+   [datomic.api :as d]
+   [io.pedestal.log :as log]
+   [steffan-westcott.clj-otel.api.metrics.instrument :as instrument]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (com.google.common.cache Cache)
+   (java.util.concurrent BlockingQueue ExecutorService Executors)))
+
+;;; ----------------------------------------------------------------------------
+;;; Lookup
+;;;
+;;; The cache maps a domain to the entity ID of its tenant, or to nil so
+;;; enumeration of made-up subdomains never reaches the query. Attributes are
+;;; still pulled per request, so profile edits show up immediately; only the
+;;; domain join is cached.
+
+(def ^:private tenant-by-domain-query
+  '[:find ?r .
+    :in $ ?domain
+    :where
+    [?d :domain/name ?domain]
+    [?r :tenant/domains ?d]])
+
+(def ^:private realm-pattern
+  [:creator/avatar-url
+   :creator/banner-url
+   :creator/bio
+   :creator/display-name
+   :creator/handle
+   :meta/description
+   :meta/image-url
+   :meta/title
+   :tenant/comments
+   :tenant/demo?
+   :tenant/id
+   :tenant/region
+   :tenant/suspended-at
+   {:creator/links [:link/icon
+                    :link/label
+                    :link/url]}
+   {:creator/posts [:post/created-at
+                    :post/id
+                    :post/image-url
+                    :post/text]}])
+
+(defn- record-lookup!
+  [resolver result]
+  (instrument/add! (:lookup-counter resolver)
+                   {:value      1
+                    :attributes {"result" (name result)}}))
+
+(defn lookup
+  "Returns the creator attributes for the tenant serving domain, or nil."
+  [resolver db domain]
+  (span/with-span! {:name ::lookup}
+    (let [[eid hit?] (cache/fetch (:cache resolver) domain #(d/q tenant-by-domain-query db domain))]
+      (record-lookup! resolver (if hit? :hit :miss))
+      (span/add-span-data! {:attributes {:cached hit?}})
+      (when eid
+        (d/pull db realm-pattern eid)))))
+
+(defn lookup-uncached
+  "What `lookup` would return with nothing cached."
+  [db domain]
+  (some->> (d/q tenant-by-domain-query db domain)
+           (d/pull db realm-pattern)))
+
+;;; ----------------------------------------------------------------------------
+;;; Invalidation
+;;;
+;;; Every peer receives every transaction, so watching the report queue keeps
+;;; each node's cache honest without any coordination. Domain changes are rare,
+;;; so dropping everything is simpler than working out which keys moved.
+
+(def ^:private watched-attributes
+  #{:domain/name :tenant/domains})
+
+(defn invalidate!
+  [resolver]
+  (cache/clear! (:cache resolver)))
+
+(defn handle-report!
+  [resolver {:keys [db-after tx-data]}]
+  (when (some #(contains? watched-attributes (d/ident db-after (:a %))) tx-data)
+    (log/debug :msg "Tenant domains changed; invalidating realm cache.")
+    (invalidate! resolver)))
+
+(defn- watch!
+  [resolver ^BlockingQueue queue]
+  (try
+    (loop []
+      (handle-report! resolver (.take queue))
+      (recur))
+    (catch InterruptedException _
+      nil)))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Resolver [^Cache cache
+                     datomic
+                     ^ExecutorService executor
+                     lookup-counter
+                     maximum-size
+                     ttl-seconds]
+  component/Lifecycle
+  (start [this]
+    (span/with-span! {:name ::start-resolver}
+      (let [queue    (d/tx-report-queue (datomic/conn datomic))
+            executor (Executors/newSingleThreadExecutor)
+            this     (assoc this
+                            :cache          (cache/make-cache this)
+                            :executor       executor
+                            :lookup-counter (instrument/instrument
+                                             {:name            "realm.cache.lookup"
+                                              :instrument-type :counter
+                                              :unit            "{lookup}"
+                                              :description     "Realm lookups by cache result"}))]
+        (.submit executor ^Runnable #(watch! this queue))
+        this)))
+  (stop [this]
+    (span/with-span! {:name ::stop-resolver}
+      (some-> executor .shutdownNow)
+      (some-> datomic datomic/conn d/remove-tx-report-queue)
+      (assoc this
+             :cache          nil
+             :executor       nil
+             :lookup-counter nil))))
+
+(defmethod print-method Resolver
+  [_ ^java.io.Writer w]
+  (.write w "#<Resolver>"))
+
+(defn make-resolver
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Resolver config))
@@ -1,37 +1,134 @@
 (ns bits.reaper
   (:require
+   [bits.analytics :as analytics]
    [bits.auth.rate-limit :as rate-limit]
+   [bits.datomic :as datomic]
+   [bits.deletion :as deletion]
+   [bits.leader :as leader]
+   [bits.postgres :as postgres]
+   [bits.retention :as retention]
    [bits.session :as session]
+   [bits.site :as site]
+   [bits.wishlist :as wishlist]
    [com.stuartsierra.component :as component]
    [io.pedestal.log :as log]
    [steffan-westcott.clj-otel.api.trace.span :as span])
   (:import
+   (java.time Duration Instant)
+   (java.util Date)
    (java.util.concurrent Executors ScheduledExecutorService TimeUnit)))
 
 (defn purge-sessions!
   [reaper]
-  (let [{:keys [postgres session-store]} reaper]
+  (let [{:keys [rate-limiter session-store wishlists]} reaper]
     (span/with-span! {:name ::reap}
       (try
-        (let [sessions-deleted (session/delete-expired-sessions! session-store)
-              attempts-deleted (rate-limit/delete-old-attempts! postgres)]
-          (span/add-span-data! {:attributes {:sessions-deleted sessions-deleted
-                                             :attempts-deleted attempts-deleted}})
-          {:attempts-deleted attempts-deleted
-           :sessions-deleted sessions-deleted})
+        (let [sessions-deleted  (session/delete-expired-sessions! session-store)
+              wishlists-deleted (wishlist/purge-orphans! wishlists)
+              attempts-deleted  (rate-limit/delete-old-attempts! rate-limiter)]
+          (span/add-span-data! {:attributes {:sessions-deleted  sessions-deleted
+                                             :wishlists-deleted wishlists-deleted
+                                             :attempts-deleted  attempts-deleted}})
+          {:attempts-deleted  attempts-deleted
+           :sessions-deleted  sessions-deleted
+           :wishlists-deleted wishlists-deleted})
         (catch Exception ex
           (log/warn :msg "Failed to purge sessions?!" :exception ex)
           (span/add-exception! ex {:escaping? false}))))))
 
+(defn purge-deleted!
+  "Retracts soft-deleted entities once they're past the retention period."
+  [reaper]
+  (let [{:keys [datomic retention-days]} reaper]
+    (span/with-span! {:name ::purge-deleted}
+      (try
+        (let [before (Date/from (.minus (Instant/now) (Duration/ofDays retention-days)))
+              purged (deletion/purge! (datomic/conn datomic) before)]
+          (span/add-span-data! {:attributes {:entities-purged purged}})
+          {:entities-purged purged})
+        (catch Exception ex
+          (log/warn :msg "Failed to purge deleted entities?!" :exception ex)
+          (span/add-exception! ex {:escaping? false}))))))
+
+(defn prune-tables!
+  "Deletes Postgres rows past their retention, see bits.retention."
+  [reaper]
+  (span/with-span! {:name ::prune-tables}
+    (try
+      (retention/prune! (:retention reaper))
+      (catch Exception ex
+        (log/warn :msg "Failed to prune tables?!" :exception ex)
+        (span/add-exception! ex {:escaping? false})))))
+
+(defn roll-up-analytics!
+  "Rolls page views up into hourly totals, see bits.analytics."
+  [reaper]
+  (span/with-span! {:name ::roll-up-analytics}
+    (try
+      (let [rolled-up (analytics/roll-up! (:analytics reaper))]
+        (span/add-span-data! {:attributes {:views-rolled-up rolled-up}})
+        {:views-rolled-up rolled-up})
+      (catch Exception ex
+        (log/warn :msg "Failed to roll up analytics?!" :exception ex)
+        (span/add-exception! ex {:escaping? false})))))
+
+(defn purge-blobs!
+  "Deletes blobs no site release refers to, see bits.site."
+  [reaper]
+  (span/with-span! {:name ::purge-blobs}
+    (try
+      (let [purged (site/purge-blobs! (:sites reaper) (Instant/now))]
+        (span/add-span-data! {:attributes {:blobs-purged purged}})
+        {:blobs-purged purged})
+      (catch Exception ex
+        (log/warn :msg "Failed to purge blobs?!" :exception ex)
+        (span/add-exception! ex {:escaping? false})))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+;;;
+;;; Every instance runs a reaper, but only the leader's does any work on
+;;; Datomic and the primary. A region's own database is reaped by whichever
+;;; instance in the region gets its lock first, see bits.postgres.
+
+(defn- reap-postgres!
+  [reaper]
+  (purge-sessions! reaper)
+  (prune-tables! reaper)
+  (roll-up-analytics! reaper)
+  (purge-blobs! reaper))
+
+(defn- reap!
+  [reaper]
+  (let [[_ regional] (postgres/databases (:postgres reaper))]
+    (when (leader/leader? (:leader reaper))
+      (purge-deleted! reaper)
+      (reap-postgres! reaper))
+    (when regional
+      (try
+        (leader/with-lock regional "bits.reaper" #(reap-postgres! (postgres/repoint reaper regional)))
+        (catch Exception ex
+          ;; An exception escaping a scheduled task cancels all future runs.
+          (log/warn :msg "Failed to reap the region's database?!" :exception ex)
+          (span/add-exception! ex {:escaping? false}))))))
+
 (defrecord Reaper [^ScheduledExecutorService executor
+                   analytics
+                   datomic
                    interval-hours
+                   leader
                    postgres
-                   session-store]
+                   rate-limiter
+                   retention
+                   retention-days
+                   session-store
+                   sites
+                   wishlists]
   component/Lifecycle
   (start [this]
     (span/with-span! {:name ::start-reaper}
       (let [executor (Executors/newSingleThreadScheduledExecutor)]
-        (.scheduleAtFixedRate executor purge-sessions!
+        (.scheduleAtFixedRate executor ^Runnable #(reap! this)
                               0 interval-hours TimeUnit/HOURS)
         (assoc this :executor executor))))
 
@@ -44,5 +141,6 @@
       (assoc this :executor nil))))
 
 (defn make-reaper
-  [{:keys [interval-hours] :or {interval-hours 1}}]
-  (map->Reaper {:interval-hours interval-hours}))
+  [{:keys [interval-hours retention-days] :or {interval-hours 1 retention-days 30}}]
+  (map->Reaper {:interval-hours interval-hours
+                :retention-days retention-days}))
@@ -0,0 +1,277 @@
+(ns bits.redirect
+  "Redirects tenants set up on their own domain, usually for the paths of a
+  site they moved from.
+
+  A rule's source is a path matched exactly, ignoring a trailing slash, or a
+  prefix ending in `/*` that matches everything under it. Whatever the `*`
+  matched replaces `:splat` in the destination, so `/blog/*` to `/news/:splat`
+  moves a whole section. Exact rules win over patterns, and longer patterns
+  over shorter ones. Rules that would send a visitor round in a circle are
+  refused when they're saved.
+
+  Rules are checked before routing on every request, so each tenant's are
+  cached for a few seconds. Changes made through this instance show up at
+  once; other instances pick them up when their entries expire."
+  (:require
+   [bits.anomaly :as anom]
+   [bits.cache :as cache]
+   [bits.clock :as clock]
+   [bits.locale :refer [tru]]
+   [bits.postgres :as postgres]
+   [bits.postgres.redirect :as postgres.redirect]
+   [bits.spec]
+   [charred.api :as charred]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [com.stuartsierra.component :as component]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Matching
+
+(def statuses
+  #{301 302 307 308})
+
+(def ^:private max-hops
+  10)
+
+(defn pattern?
+  [source]
+  (str/ends-with? source "/*"))
+
+(defn- encode-path
+  "Percent-encodes anything in path that can't appear in a URL as is, keeping
+  escapes it already has. Requests are matched as they arrived, so sources
+  are encoded to compare with them, and what a pattern matched is encoded
+  again so it can never break out of the `Location` header."
+  [path]
+  (str/replace path
+               #"[^A-Za-z0-9\-._~!$&'()*+,;=:@/%]"
+               (fn [c]
+                 (apply str (map #(format "%%%02X" (bit-and % 0xff)) (.getBytes ^String c "UTF-8"))))))
+
+(defn- trim-slash
+  [path]
+  (if (and (< 1 (count path)) (str/ends-with? path "/"))
+    (subs path 0 (dec (count path)))
+    path))
+
+(defn- splat
+  "What the pattern's `*` matched in path, or nil when it doesn't match."
+  [source path]
+  (let [prefix (encode-path (subs source 0 (dec (count source))))]
+    (cond
+      (str/starts-with? path prefix) (subs path (count prefix))
+      (= path (trim-slash prefix))   "")))
+
+(defn match
+  "The rule for path with what its `*` matched, as `[rule splat]`, or nil."
+  [rules path]
+  (let [path (trim-slash path)]
+    (or (some #(when (and (not (pattern? (::postgres.redirect/source %)))
+                          (= path (trim-slash (encode-path (::postgres.redirect/source %)))))
+                 [% nil])
+              rules)
+        (->> rules
+             (filter #(pattern? (::postgres.redirect/source %)))
+             (sort-by #(- (count (::postgres.redirect/source %))))
+             (some #(when-some [matched (splat (::postgres.redirect/source %) path)]
+                      [% matched]))))))
+
+(defn location
+  "Where the rule sends a request, keeping its query string unless the
+  destination has one of its own."
+  [rule matched query-string]
+  (let [destination (cond-> (::postgres.redirect/destination rule)
+                      (some? matched) (str/replace ":splat" (encode-path matched)))]
+    (if (and (not (str/blank? query-string)) (not (str/includes? destination "?")))
+      (str destination "?" query-string)
+      destination)))
+
+(defn- local-path
+  "The path of a destination on the same domain, or nil for other sites."
+  [destination]
+  (when (str/starts-with? destination "/")
+    (first (str/split destination #"[?#]" 2))))
+
+(defn loops?
+  "Whether following rules from source ever comes back round, or goes on for
+  more hops than any browser would follow."
+  [rules source]
+  (let [sample (if (pattern? source) (str (subs source 0 (dec (count source))) "loop-check") source)]
+    (loop [path sample seen #{} hops 0]
+      (cond
+        (contains? seen (trim-slash path)) true
+        (< max-hops hops)                  true
+        :else
+        (if-let [[rule matched] (match rules path)]
+          (if-let [next-path (local-path (location rule matched nil))]
+            (recur next-path (conj seen (trim-slash path)) (inc hops))
+            false)
+          false)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Validation
+
+(defn- invalid
+  "Why the rule can't be saved, or nil."
+  [{:keys [destination source status]}]
+  (cond
+    (not (and (string? source) (str/starts-with? source "/") (not (str/includes? source "?"))))
+    (tru "Sources are paths starting with /, without a query string.")
+
+    (str/includes? (str/replace source #"/\*$" "") "*")
+    (tru "Only a trailing /* can match more than one path.")
+
+    (not (and (string? destination)
+              (or (str/starts-with? destination "/")
+                  (re-matches #"https?://[^\s]+" destination))))
+    (tru "Destinations are paths starting with /, or http:// or https:// addresses.")
+
+    ;; Browsers read `//host` and `/\host` as another site, and control
+    ;; characters could end the `Location` header early.
+    (or (re-find #"[\x00-\x1f\x7f]" destination)
+        (str/starts-with? destination "//")
+        (str/starts-with? destination "/\\"))
+    (tru "Destinations can''t contain control characters or start with // or /\\.")
+
+    (not (contains? statuses status))
+    (tru "Redirects answer 301, 302, 307 or 308.")))
+
+(defn- normalize
+  "Sources are saved without a trailing slash, since matching ignores it, and
+  only the last rule for a source is kept."
+  [rules]
+  (vals (into {}
+              (map (fn [{:keys [source] :as rule}]
+                     (let [rule (cond-> rule
+                                  (and (string? source) (not (pattern? source))) (update :source trim-slash))]
+                       [(:source rule) rule])))
+              rules)))
+
+(defn- check
+  "An anomaly when there are no rules, or any of them are invalid or,
+  together with the tenant's existing rules, loop."
+  [existing rules]
+  (let [by-source (into {}
+                        (map (juxt ::postgres.redirect/source identity))
+                        (concat existing
+                                (for [{:keys [destination source status]} rules]
+                                  {::postgres.redirect/source      source
+                                   ::postgres.redirect/destination destination
+                                   ::postgres.redirect/status      status})))
+        combined  (vals by-source)]
+    (or (when (empty? rules)
+          (anom/incorrect {::anom/message (tru "There are no redirects to save.")}))
+        (some (fn [rule]
+                (when-let [message (invalid rule)]
+                  (anom/incorrect {::anom/message (tru "{0}: {1}" (:source rule) message)})))
+              rules)
+        (some (fn [{:keys [source]}]
+                (when (loops? combined source)
+                  (anom/conflict {::anom/message (tru "{0} would redirect in a loop." source)})))
+              rules))))
+
+;;; ----------------------------------------------------------------------------
+;;; Rules
+
+(defn list-rules
+  [redirects tenant-id]
+  {:post [(s/valid? (s/coll-of ::postgres.redirect/rule) %)]}
+  (span/with-span! {:name ::list-rules}
+    (postgres/execute! (:postgres redirects)
+                       {:select   [:id :source :destination :status :hits :last-hit-at :created-at]
+                        :from     [:redirects]
+                        :where    [:= :tenant-id tenant-id]
+                        :order-by [:source]})))
+
+(defn cached-rules
+  [redirects tenant-id]
+  (cache/lookup (:cache redirects) tenant-id
+                #(not-empty
+                  (postgres/execute! (postgres/replica (:postgres redirects))
+                                     {:select [:id :source :destination :status]
+                                      :from   [:redirects]
+                                      :where  [:= :tenant-id tenant-id]}))))
+
+(defn save!
+  "Adds rules, as `{:source ... :destination ... :status ...}`, replacing any
+  with the same source. Saves all of them or, when one is invalid or they'd
+  loop, none. Returns how many were saved."
+  [redirects tenant-id rules]
+  (span/with-span! {:name ::save!}
+    (postgres/with-transaction [tx (:postgres redirects)]
+      (let [rules (normalize rules)]
+        (or (check (list-rules (assoc redirects :postgres tx) tenant-id) rules)
+            (do
+              (postgres/execute! tx {:insert-into   :redirects
+                                     :values        (for [{:keys [destination source status]} rules]
+                                                      {:id          (random-uuid)
+                                                       :tenant-id   tenant-id
+                                                       :source      source
+                                                       :destination destination
+                                                       :status      status})
+                                     :on-conflict   [:tenant-id :source]
+                                     :do-update-set {:destination :excluded.destination
+                                                     :status      :excluded.status}})
+              (cache/evict! (:cache redirects) tenant-id)
+              (count rules)))))))
+
+(defn delete!
+  [redirects tenant-id id]
+  (span/with-span! {:name ::delete!}
+    (let [[{:keys [next.jdbc/update-count]}]
+          (postgres/execute! (:postgres redirects)
+                             {:delete-from :redirects
+                              :where       [:and [:= :id id] [:= :tenant-id tenant-id]]})]
+      (cache/evict! (:cache redirects) tenant-id)
+      (pos? (or update-count 0)))))
+
+(defn hit!
+  [redirects id]
+  (postgres/execute! (:postgres redirects)
+                     {:update :redirects
+                      :set    {:hits        [:+ :hits 1]
+                               :last-hit-at (clock/now (:clock redirects))}
+                      :where  [:= :id id]}))
+
+;;; ----------------------------------------------------------------------------
+;;; Import
+
+(defn parse-csv
+  "Rules from CSV lines of `source,destination` with an optional status,
+  which defaults to 301. A header row naming the columns is skipped. Returns
+  an anomaly naming the first line that can't be read."
+  [s]
+  (let [rows (->> (charred/read-csv (str s))
+                  (map-indexed vector)
+                  (remove (fn [[_ row]] (every? str/blank? row)))
+                  (remove (fn [[i row]] (and (zero? i) (= "source" (str/lower-case (str/trim (first row))))))))]
+    (reduce (fn [acc [i [source destination status]]]
+              (let [status (if (str/blank? status) 301 (parse-long (str/trim status)))]
+                (if (or (str/blank? source) (str/blank? destination) (nil? status))
+                  (reduced (anom/incorrect {::anom/message (tru "Line {0} should be source,destination,status." (inc i))}))
+                  (conj acc {:source      (str/trim source)
+                             :destination (str/trim destination)
+                             :status      status}))))
+            []
+            rows)))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Redirects [cache clock maximum-size postgres ttl-seconds]
+  component/Lifecycle
+  (start [this]
+    (assoc this :cache (cache/make-cache this)))
+  (stop [this]
+    (assoc this :cache nil)))
+
+(defmethod print-method Redirects
+  [_ ^java.io.Writer w]
+  (.write w "#<Redirects>"))
+
+(defn make-redirects
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Redirects config))
@@ -5,6 +5,11 @@
   (:import
    (com.google.common.net InetAddresses)))
 
+(defn id
+  "The ID tying the request to its logs, span and any error we show."
+  [request]
+  (::id request))
+
 (defn remote-addr
   [request]
   (or (some-> (response/get-header request "x-forwarded-for")
@@ -28,3 +33,9 @@
         (str/ends-with? d ".localhost")
         (and (InetAddresses/isInetAddress d)
              (.isLoopbackAddress (InetAddresses/forString d))))))
+
+(defn origin
+  "Scheme and host the visitor reached us on, for building absolute URLs."
+  [request]
+  (str (if (local? request) "http" "https")
+       "://" (response/get-header request "host")))
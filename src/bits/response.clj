@@ -1,6 +1,10 @@
 (ns bits.response
   "Plain text responses, preferably only used in internal or machine-to-machine
-  flows.")
+  flows, and the JSON responses our APIs share."
+  (:require
+   [bits.anomaly :as anom]
+   [bits.request :as request]
+   [charred.api :as json]))
 
 (def ^:private text-plain
   "text/plain; charset=utf-8")
@@ -25,6 +29,12 @@
    :headers {"content-type" text-plain}
    :body    "Bad request.\n"})
 
+(def unauthorized-response
+  {:status  401
+   :headers {"content-type"     text-plain
+             "www-authenticate" "Bearer"}
+   :body    "Unauthorized.\n"})
+
 (def forbidden-response
   {:status  403
    :headers {"content-type" text-plain}
@@ -35,12 +45,62 @@
    :headers {"content-type" text-plain}
    :body    "Not found.\n"})
 
+(def misdirected-response
+  {:status  421
+   :headers {"content-type" text-plain}
+   :body    "Misdirected request.\n"})
+
 (def unsupported-event-response
   {:status  422
    :headers {"content-type" text-plain}
    :body    "Unsupported event.\n"})
 
+(def payload-too-large-response
+  {:status  413
+   :headers {"content-type" text-plain}
+   :body    "Payload too large.\n"})
+
+(def too-many-requests-response
+  {:status  429
+   :headers {"content-type" text-plain}
+   :body    "Too many requests.\n"})
+
 (def internal-server-error-response
   {:status  500
    :headers {"content-type" text-plain}
    :body    "Internal server error.\n"})
+
+(defn with-reference
+  "Appends the request ID to a plain text error, so whoever sees it can quote
+  something we can find in the logs."
+  [response request]
+  (cond-> response
+    (request/id request) (update :body str "Reference: " (request/id request) "\n")))
+
+;;; ----------------------------------------------------------------------------
+;;; JSON
+
+(defn json-response
+  ([status body]
+   (json-response status body {}))
+  ([status body headers]
+   {:status  status
+    :headers (assoc headers "content-type" "application/json; charset=utf-8")
+    :body    (json/write-json-str body)}))
+
+(defn anomaly-response
+  "Describes the anomaly as JSON with its status, code, whether it's worth
+  retrying and the request ID to quote. Extra entries are merged into the
+  body."
+  ([request anom]
+   (anomaly-response request anom {}))
+  ([request anom extra]
+   (let [retry-after (::anom/retry-after-seconds anom)]
+     (json-response (anom/status anom)
+                    (merge (cond-> {:code      (anom/code anom)
+                                    :message   (or (::anom/message anom) "Something went wrong.")
+                                    :retryable (anom/retryable? anom)}
+                             (request/id request) (assoc :request-id (request/id request)))
+                           extra)
+                    (cond-> {}
+                      retry-after (assoc "retry-after" (str retry-after)))))))
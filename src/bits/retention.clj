@@ -0,0 +1,177 @@
+(ns bits.retention
+  "How long Postgres keeps rows that only matter for a while.
+
+  Each pruned table has a policy naming the column that dates its rows, and
+  any rows that stay whatever their age, like deliveries still being tried.
+  How many days to keep comes from config. Tenants may keep their own rows in
+  tables with a tenant column for less than that, never more.
+
+  Pruning deletes in batches so no statement locks many rows at once, and
+  only the leader's reaper runs it. Sessions and authentication attempts
+  expire on their own schedules and aren't covered here."
+  (:require
+   [bits.anomaly :as anom]
+   [bits.clock :as clock]
+   [bits.locale :refer [tru]]
+   [bits.postgres :as postgres]
+   [bits.postgres.retention-override :as postgres.retention-override]
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [com.stuartsierra.component :as component]
+   [steffan-westcott.clj-otel.api.metrics.instrument :as instrument]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Policies
+
+(def policies
+  {:activities         {:column :created-at :tenant? true}
+   :api-key-requests   {:column :window-start}
+   :hourly-rollups     {:column :hour :tenant? true}
+   :notifications      {:column :created-at :tenant? true}
+   :outbox             {:column :created-at :keep [:= :published-at nil]}
+   :page-revisions     {:column  :created-at
+                        :tenant? true
+                        ;; What visitors see, and what editors would carry on from.
+                        :keep    [:or :pinned [:= :number {:select [[[:max :r.number]]]
+                                                           :from   [[:page-revisions :r]]
+                                                           :where  [:= :r.page-id :page-revisions.page-id]}]]}
+   :projected-events   {:column :projected-at}
+   :webhook-deliveries {:column :created-at :tenant? true :keep [:= :status "pending"]}})
+
+(def tenant-tables
+  (into (sorted-set) (keep (fn [[table {:keys [tenant?]}]] (when tenant? table))) policies))
+
+;;; ----------------------------------------------------------------------------
+;;; Pruning
+
+(defn- delete-batch!
+  [postgres table where batch-size]
+  (let [[{:keys [next.jdbc/update-count]}]
+        (postgres/execute! postgres
+                           {:delete-from table
+                            :where       [:in :ctid {:select [:ctid]
+                                                     :from   [table]
+                                                     :where  where
+                                                     :limit  batch-size}]})]
+    (or update-count 0)))
+
+(defn- delete-all!
+  [{:keys [batch-size postgres]} table where]
+  (loop [total 0]
+    (let [n (delete-batch! postgres table where batch-size)]
+      (if (< n batch-size)
+        (+ total n)
+        (recur (+ total n))))))
+
+(defn- older-than
+  [retention table days]
+  (let [{:keys [column keep]} (get policies table)
+        cutoff                [:- (clock/now (:clock retention)) [:make-interval :days days]]]
+    (cond-> [:and [:< column cutoff]]
+      keep (conj [:not keep]))))
+
+(defn- overrides
+  [retention table]
+  (postgres/execute! (:postgres retention)
+                     {:select [:tenant-id :days]
+                      :from   [:retention-overrides]
+                      :where  [:= :table-name (name table)]}))
+
+(defn prune-table!
+  "Deletes the table's rows past the platform's retention, then each tenant's
+  rows past their own. Returns how many rows went."
+  [retention table]
+  (span/with-span! {:name ::prune-table! :attributes {:table (name table)}}
+    (let [platform (delete-all! retention table (older-than retention table (get-in retention [:days table])))
+          tenants  (when (get-in policies [table :tenant?])
+                     (for [{::postgres.retention-override/keys [days tenant-id]} (overrides retention table)]
+                       (delete-all! retention table (conj (older-than retention table days)
+                                                          [:= :tenant-id tenant-id]))))
+          pruned   (reduce + platform tenants)]
+      (instrument/add! (:pruned-counter retention) {:value      pruned
+                                                    :attributes {"table" (name table)}})
+      (span/add-span-data! {:attributes {:pruned pruned}})
+      pruned)))
+
+(defn prune!
+  "Prunes every table. Returns how many rows went from each."
+  [retention]
+  (span/with-span! {:name ::prune!}
+    (into (sorted-map)
+          (for [table (sort (keys policies))]
+            [table (prune-table! retention table)]))))
+
+;;; ----------------------------------------------------------------------------
+;;; Tenant overrides
+
+(defn tenant-days
+  "Days each of the tenant's tables keeps rows, as `{table {:default d :days
+  override}}`. The override is nil when the tenant keeps the default."
+  [retention tenant-id]
+  (let [chosen (into {}
+                     (map (juxt (comp keyword ::postgres.retention-override/table-name)
+                                ::postgres.retention-override/days))
+                     (postgres/execute! (:postgres retention)
+                                        {:select [:table-name :days]
+                                         :from   [:retention-overrides]
+                                         :where  [:= :tenant-id tenant-id]}))]
+    (into (sorted-map)
+          (for [table tenant-tables]
+            [table {:default (get-in retention [:days table])
+                    :days    (get chosen table)}]))))
+
+(defn set-tenant-days!
+  "Keeps the tenant's rows in table for days, or for the platform default
+  when days is nil. Returns an anomaly for tables tenants can't change, or
+  days that aren't fewer than the default."
+  [retention tenant-id table days]
+  {:pre [(uuid? tenant-id) (keyword? table)]}
+  (span/with-span! {:name ::set-tenant-days!}
+    (let [default (get-in retention [:days table])]
+      (cond
+        (not (contains? tenant-tables table))
+        (anom/incorrect {::anom/message (tru "Retention for {0} can''t be changed." (name table))})
+
+        (nil? days)
+        (postgres/execute! (:postgres retention)
+                           {:delete-from :retention-overrides
+                            :where       [:and
+                                          [:= :tenant-id tenant-id]
+                                          [:= :table-name (name table)]]})
+
+        (not (and (pos-int? days) (< days default)))
+        (anom/incorrect {::anom/message (tru "Keep {0} for fewer than {1} days." (name table) default)})
+
+        :else
+        (postgres/execute! (:postgres retention)
+                           {:insert-into   :retention-overrides
+                            :values        [{:tenant-id  tenant-id
+                                             :table-name (name table)
+                                             :days       days}]
+                            :on-conflict   [:tenant-id :table-name]
+                            :do-update-set {:days       days
+                                            :updated-at (clock/now (:clock retention))}})))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Retention [batch-size clock days postgres pruned-counter]
+  component/Lifecycle
+  (start [this]
+    (assoc this :pruned-counter (instrument/instrument
+                                 {:name            "retention.pruned"
+                                  :instrument-type :counter
+                                  :unit            "{row}"
+                                  :description     "Rows deleted for being past their retention"})))
+  (stop [this]
+    (assoc this :pruned-counter nil)))
+
+(defmethod print-method Retention
+  [_ ^java.io.Writer w]
+  (.write w "#<Retention>"))
+
+(defn make-retention
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Retention config))
@@ -0,0 +1,143 @@
+(ns bits.review
+  "Product reviews from verified buyers.
+
+  Only someone who bought one of a product's variants may review it, and each
+  buyer has one review per product that they can revise. Reviews live in
+  Postgres next to the activity feed; products and purchases stay in Datomic,
+  so the purchase check happens before anything is written."
+  (:require
+   [bits.anomaly :as anom]
+   [bits.clock :as clock]
+   [bits.locale :refer [tru]]
+   [bits.outbox :as outbox]
+   [bits.pagination :as pagination]
+   [bits.postgres :as postgres]
+   [bits.postgres.review :as postgres.review]
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [datomic.api :as d]
+   [next.jdbc :as jdbc]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.math RoundingMode)))
+
+;;; ----------------------------------------------------------------------------
+;;; Purchases
+
+(defn purchased?
+  "Whether the user bought any variant of the product."
+  [db user-id product-id]
+  (some? (d/q '[:find ?li .
+                :in $ ?user-id ?product-id
+                :where
+                [?u :user/id ?user-id]
+                [?li :line-item/buyer ?u]
+                [?li :line-item/variant ?v]
+                [?p :product/variants ?v]
+                [?p :product/id ?product-id]]
+              db user-id product-id)))
+
+;;; ----------------------------------------------------------------------------
+;;; Writing
+
+(defn submit!
+  "Creates or replaces the author's review of the product, and writes a
+  review.submitted event with it. Returns the review ID, or an anomaly when
+  the author never bought the product or the review doesn't check out."
+  [reviews db {:keys [author-id body product-id rating tenant-id]}]
+  (span/with-span! {:name ::submit!}
+    (let [body (str/trim (or body ""))]
+      (cond
+        (not (purchased? db author-id product-id))
+        (anom/forbidden {::anom/message (tru "Only buyers can review this product.")})
+
+        (not (and (int? rating) (<= 1 rating 5)))
+        (anom/incorrect {::anom/message (tru "Pick between one and five stars.")})
+
+        (< (:max-body reviews) (count body))
+        (anom/incorrect {::anom/message (tru "Reviews can be at most {0} characters." (:max-body reviews))})
+
+        :else
+        (jdbc/with-transaction [tx (get-in reviews [:postgres :datasource])]
+          (let [id (:bits.postgres.review/id
+                    (postgres/execute-one! tx
+                                           {:insert-into   :reviews
+                                            :values        [{:id         (random-uuid)
+                                                             :tenant-id  tenant-id
+                                                             :product-id product-id
+                                                             :author-id  author-id
+                                                             :rating     rating
+                                                             :body       body}]
+                                            :on-conflict   [:product-id :author-id]
+                                            :do-update-set {:rating     rating
+                                                            :body       body
+                                                            :updated-at (clock/now (:clock reviews))}
+                                            :returning     [:id]}))]
+            (outbox/enqueue! tx tenant-id author-id "review.submitted"
+                             {:product-id product-id :review-id id})
+            id))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Reading
+;;;
+;;; Hidden reviews are left out of both the list and the average.
+
+(def ordering
+  {:direction :desc
+   :columns   [[:created-at ::postgres.review/created-at]
+               [:id ::postgres.review/id]]})
+
+(defn list-reviews
+  "Returns a page of the product's visible reviews, newest first."
+  [reviews tenant-id product-id page-request]
+  {:post [(s/valid? (s/coll-of ::postgres.review/persisted) (:items %))]}
+  (span/with-span! {:name ::list-reviews}
+    (-> (postgres/execute! (:postgres reviews)
+                           (pagination/paginate {:select [:id :rating :body :created-at :updated-at]
+                                                 :from   [:reviews]
+                                                 :where  [:and
+                                                          [:= :tenant-id tenant-id]
+                                                          [:= :product-id product-id]
+                                                          [:= :hidden-at nil]]}
+                                                ordering
+                                                page-request))
+        (pagination/page ordering page-request))))
+
+(defn lookup
+  "The tenant's review with the given ID, hidden or not, or nil."
+  [reviews tenant-id id]
+  (postgres/execute-one! (:postgres reviews)
+                         {:select [:id :product-id :rating :body :created-at :hidden-at]
+                          :from   [:reviews]
+                          :where  [:and [:= :id id] [:= :tenant-id tenant-id]]}))
+
+(defn summary
+  "The product's average rating to one decimal place and how many reviews it
+  has. The average is nil until someone reviews it."
+  [reviews tenant-id product-id]
+  (span/with-span! {:name ::summary}
+    (let [{:keys [average total]} (postgres/execute-one! (:postgres reviews)
+                                                         {:select [[[:avg :rating] :average]
+                                                                   [[:count :*] :total]]
+                                                          :from   [:reviews]
+                                                          :where  [:and
+                                                                   [:= :tenant-id tenant-id]
+                                                                   [:= :product-id product-id]
+                                                                   [:= :hidden-at nil]]})]
+      {:average (when average (double (.setScale (bigdec average) 1 RoundingMode/HALF_UP)))
+       :total   total})))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Reviews [clock max-body postgres])
+
+(defmethod print-method Reviews
+  [_ ^java.io.Writer w]
+  (.write w "#<Reviews>"))
+
+(defn make-reviews
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Reviews config))
@@ -0,0 +1,159 @@
+(ns bits.revision
+  "Revisions of CMS pages.
+
+  Every save of a page records its title, slug and blocks as the page's next
+  numbered revision, so nothing an editor does is lost and any two revisions
+  can be compared. Restoring an old revision saves its content again as a
+  new one, so history only ever grows.
+
+  Publishing pins the latest revision: visitors see the pinned revision's
+  title and blocks while editors carry on saving drafts, until the page is
+  published again. Old revisions are pruned under the `:page-revisions`
+  retention policy, which never takes a page's pinned or latest revision."
+  (:require
+   [bits.anomaly :as anom]
+   [bits.locale :refer [tru]]
+   [bits.postgres :as postgres]
+   [bits.postgres.page-revision :as postgres.page-revision]
+   [charred.api :as json]
+   [clojure.spec.alpha :as s]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Reading
+
+(def ^:private columns
+  [:id :page-id :number :title :slug :blocks :author-id :pinned :created-at])
+
+(defn list-revisions
+  "The page's revisions, newest first."
+  [postgres tenant-id page-id]
+  {:post [(s/valid? (s/coll-of ::postgres.page-revision/revision) %)]}
+  (span/with-span! {:name ::list-revisions}
+    (postgres/execute! postgres {:select   columns
+                                 :from     [:page-revisions]
+                                 :where    [:and [:= :tenant-id tenant-id] [:= :page-id page-id]]
+                                 :order-by [[:number :desc]]})))
+
+(defn lookup
+  [postgres tenant-id page-id number]
+  (postgres/execute-one! postgres {:select columns
+                                   :from   [:page-revisions]
+                                   :where  [:and
+                                            [:= :tenant-id tenant-id]
+                                            [:= :page-id page-id]
+                                            [:= :number number]]}))
+
+(defn pinned
+  "The revision visitors see, or nil when none is pinned."
+  [postgres tenant-id page-id]
+  (postgres/execute-one! postgres {:select columns
+                                   :from   [:page-revisions]
+                                   :where  [:and
+                                            [:= :tenant-id tenant-id]
+                                            [:= :page-id page-id]
+                                            :pinned]}))
+
+(defn blocks
+  [revision]
+  (json/read-json (::postgres.page-revision/blocks revision) :key-fn keyword))
+
+;;; ----------------------------------------------------------------------------
+;;; Writing
+
+(defn record!
+  "Saves the page as it is now as its next revision. Returns the revision."
+  [postgres tenant-id page author-id]
+  (span/with-span! {:name ::record!}
+    (postgres/with-transaction [tx postgres]
+      ;; Two editors saving at once would otherwise both take the same number.
+      (postgres/execute! tx {:select [[[:pg-advisory-xact-lock [:hashtext (str (:page/id page))]]]]})
+      (let [{:keys [latest]} (postgres/execute-one! tx {:select [[[:coalesce [:max :number] 0] :latest]]
+                                                        :from   [:page-revisions]
+                                                        :where  [:= :page-id (:page/id page)]})]
+        (postgres/execute-one! tx {:insert-into :page-revisions
+                                   :values      [{:id        (random-uuid)
+                                                  :tenant-id tenant-id
+                                                  :page-id   (:page/id page)
+                                                  :number    (inc latest)
+                                                  :title     (:page/title page)
+                                                  :slug      (:page/slug page)
+                                                  :blocks    (:page/blocks page)
+                                                  :author-id author-id}]
+                                   :returning   columns})))))
+
+(defn pin!
+  "Makes the revision the one visitors see. Returns it, or an anomaly when
+  the page has no such revision."
+  [postgres tenant-id page-id number]
+  (span/with-span! {:name ::pin!}
+    (postgres/with-transaction [tx postgres]
+      (if-not (lookup tx tenant-id page-id number)
+        (anom/not-found {::anom/message (tru "That revision doesn''t exist.")})
+        (do
+          (postgres/execute! tx {:update :page-revisions
+                                 :set    {:pinned false}
+                                 :where  [:and [:= :tenant-id tenant-id] [:= :page-id page-id] :pinned]})
+          (postgres/execute-one! tx {:update    :page-revisions
+                                     :set       {:pinned true}
+                                     :where     [:and
+                                                 [:= :tenant-id tenant-id]
+                                                 [:= :page-id page-id]
+                                                 [:= :number number]]
+                                     :returning columns}))))))
+
+(defn unpin!
+  [postgres tenant-id page-id]
+  (postgres/execute! postgres {:update :page-revisions
+                               :set    {:pinned false}
+                               :where  [:and [:= :tenant-id tenant-id] [:= :page-id page-id] :pinned]})
+  nil)
+
+;;; ----------------------------------------------------------------------------
+;;; Comparing
+
+(defn- lcs-table
+  "Lengths of the longest common subsequences of every pair of suffixes."
+  [a b]
+  (let [n (count a) m (count b)]
+    (reduce (fn [table [i j]]
+              (assoc-in table [i j]
+                        (if (= (nth a i) (nth b j))
+                          (inc (get-in table [(inc i) (inc j)]))
+                          (max (get-in table [(inc i) j]) (get-in table [i (inc j)])))))
+            (vec (repeat (inc n) (vec (repeat (inc m) 0))))
+            (for [i (range (dec n) -1 -1)
+                  j (range (dec m) -1 -1)]
+              [i j]))))
+
+(defn diff-blocks
+  "The edits from blocks a to blocks b, in order, as `{:op ... :block ...}`
+  where op is `:same`, `:removed` or `:added`."
+  [a b]
+  (let [a (vec a) b (vec b) table (lcs-table a b)]
+    (loop [i 0 j 0 out []]
+      (cond
+        (and (< i (count a)) (< j (count b)) (= (nth a i) (nth b j)))
+        (recur (inc i) (inc j) (conj out {:op :same :block (nth a i)}))
+
+        (and (< j (count b))
+             (or (= i (count a))
+                 (< (get-in table [(inc i) j]) (get-in table [i (inc j)]))))
+        (recur i (inc j) (conj out {:op :added :block (nth b j)}))
+
+        (< i (count a))
+        (recur (inc i) j (conj out {:op :removed :block (nth a i)}))
+
+        :else
+        out))))
+
+(defn diff
+  "What changed from revision a to revision b: the title and slug as `[old
+  new]` when they differ, and the blocks as from `diff-blocks`."
+  [a b]
+  (let [changed (fn [k]
+                  (when (not= (get a k) (get b k))
+                    [(get a k) (get b k)]))]
+    {:title  (changed ::postgres.page-revision/title)
+     :slug   (changed ::postgres.page-revision/slug)
+     :blocks (diff-blocks (blocks a) (blocks b))}))
@@ -0,0 +1,180 @@
+(ns bits.schedule
+  "Products and pages that go live, or come down, at a time the tenant picks.
+
+  Visibility is worked out whenever something is read, against the clock, so
+  a product is hidden the moment its `:product/unpublish-at` passes whether
+  or not anything here has run. A published page is hidden until its
+  `:page/publish-at` and from its `:page/unpublish-at`; drafts stay hidden
+  whatever their times.
+
+  What nothing reading can do is tell anyone. The scheduler looks every
+  `:poll-seconds` for times passed since it last looked. Every instance puts
+  what it finds on a mult, so open pages re-render without waiting for an
+  action, and the leader writes `page.published`, `product.unpublished` and
+  friends to the outbox for webhooks. A leader that changes hands between
+  polls can miss a boundary or send it twice."
+  (:require
+   [bits.clock :as clock]
+   [bits.datomic :as datomic]
+   [bits.leader :as leader]
+   [bits.outbox :as outbox]
+   [bits.spec]
+   [clojure.core.async :as a]
+   [clojure.spec.alpha :as s]
+   [com.stuartsierra.component :as component]
+   [datomic.api :as d]
+   [io.pedestal.log :as log]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.time OffsetDateTime)
+   (java.util Date)
+   (java.util.concurrent Executors ScheduledExecutorService TimeUnit)))
+
+;;; ----------------------------------------------------------------------------
+;;; Visibility
+
+(defn ->date
+  "The clock's time as Datomic stores instants."
+  ^Date [^OffsetDateTime t]
+  (Date/from (.toInstant t)))
+
+(defn now
+  ^Date [clock]
+  (->date (clock/now clock)))
+
+(defn within?
+  "Whether now falls between publish-at, inclusive, and unpublish-at. Either
+  may be nil for no limit."
+  [^Date publish-at ^Date unpublish-at ^Date now]
+  (and (or (nil? publish-at) (not (.before now publish-at)))
+       (or (nil? unpublish-at) (.before now unpublish-at))))
+
+(defn status
+  "Where the times leave something live: `:scheduled` before it's published,
+  `:ended` once it's unpublished, or else `:live`."
+  [^Date publish-at ^Date unpublish-at ^Date now]
+  (cond
+    (and publish-at (.before now publish-at))            :scheduled
+    (and unpublish-at (not (.before now unpublish-at))) :ended
+    :else                                                :live))
+
+;;; ----------------------------------------------------------------------------
+;;; Boundaries
+
+(def ^:private boundaries
+  "Each time attribute, the tenant attribute holding its entities, and the
+  topic passing it is published as."
+  [{:attr :page/publish-at      :id :page/id    :owner :tenant/pages    :topic "page.published"}
+   {:attr :page/unpublish-at    :id :page/id    :owner :tenant/pages    :topic "page.unpublished"}
+   {:attr :product/publish-at   :id :product/id :owner :tenant/products :topic "product.published"}
+   {:attr :product/unpublish-at :id :product/id :owner :tenant/products :topic "product.unpublished"}])
+
+(defn- event-data
+  [id-attr id]
+  (case id-attr
+    :page/id    {:page-id id}
+    :product/id {:product-id id}))
+
+(defn crossed
+  "Events for the times that passed after from and up to to, as
+  `{:tenant-id ... :topic ... :data ...}`. Deleted entities and draft pages
+  changed nothing anyone could see, so they're left out."
+  [db ^Date from ^Date to]
+  (for [{:keys [attr id owner topic]} boundaries
+        [tenant-id entity-id]          (d/q '[:find ?tenant-id ?id
+                                               :in $ ?attr ?id-attr ?owner ?from ?to
+                                               :where
+                                               [?e ?attr ?at]
+                                               [(< ?from ?at)]
+                                               [(<= ?at ?to)]
+                                               [(missing? $ ?e :entity/deleted-at)]
+                                               [?t ?owner ?e]
+                                               [?t :tenant/id ?tenant-id]
+                                               [?e ?id-attr ?id]]
+                                             db attr id owner from to)
+        :when                          (or (not= :page/id id)
+                                           (= :page.status/published
+                                              (:page/status (d/entity db [:page/id entity-id]))))]
+    {:tenant-id tenant-id
+     :topic     topic
+     :data      (event-data id entity-id)}))
+
+(defn poll!
+  "Looks for times passed since the last poll. Returns what it found."
+  [scheduler]
+  (span/with-span! {:name ::poll!}
+    (let [{:keys [!checked-at changes datomic postgres]} scheduler
+          from                                           @!checked-at
+          to                                             (now (:clock scheduler))
+          found                                          (vec (crossed (d/db (datomic/conn datomic)) from to))]
+      (reset! !checked-at to)
+      (when (seq found)
+        (span/add-span-data! {:attributes {:boundaries (count found)}})
+        (a/put! changes found)
+        (when (leader/leader? (:leader scheduler))
+          (doseq [{:keys [data tenant-id topic]} found]
+            (outbox/enqueue! postgres tenant-id nil topic data))))
+      found)))
+
+(defn- poll-safely!
+  [scheduler]
+  (try
+    (poll! scheduler)
+    (catch Exception ex
+      ;; An exception escaping a scheduled task cancels all future runs.
+      (log/warn :msg "Failed to poll schedule?!" :exception ex)
+      (span/add-exception! ex {:escaping? false}))))
+
+(defn tap!
+  [scheduler ch]
+  (a/tap (:mult scheduler) ch false))
+
+(defn untap!
+  [scheduler ch]
+  (a/untap (:mult scheduler) ch))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Scheduler [!checked-at
+                      changes
+                      clock
+                      datomic
+                      ^ScheduledExecutorService executor
+                      leader
+                      mult
+                      poll-seconds
+                      postgres]
+  component/Lifecycle
+  (start [this]
+    (span/with-span! {:name ::start-scheduler}
+      (let [changes  (a/chan (a/sliding-buffer 64))
+            executor (Executors/newSingleThreadScheduledExecutor)
+            this     (assoc this
+                            ;; Times passed before start are nobody's news.
+                            :!checked-at (atom (now clock))
+                            :changes     changes
+                            :executor    executor
+                            :mult        (a/mult changes))]
+        (.scheduleWithFixedDelay executor
+                                 ^Runnable #(poll-safely! this)
+                                 poll-seconds poll-seconds TimeUnit/SECONDS)
+        this)))
+
+  (stop [this]
+    (span/with-span! {:name ::stop-scheduler}
+      (when executor
+        (.shutdown executor)
+        (when-not (.awaitTermination executor 5 TimeUnit/SECONDS)
+          (.shutdownNow executor)))
+      (some-> changes a/close!)
+      (assoc this :!checked-at nil :changes nil :executor nil :mult nil))))
+
+(defmethod print-method Scheduler
+  [_ ^java.io.Writer w]
+  (.write w "#<Scheduler>"))
+
+(defn make-scheduler
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Scheduler config))
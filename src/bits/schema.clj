@@ -20,7 +20,18 @@
 
    {:db/ident       :user/created-at
     :db/valueType   :db.type/instant
-    :db/cardinality :db.cardinality/one}])
+    :db/cardinality :db.cardinality/one}
+
+   {:db/ident       :user/sso-subject
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one
+    :db/unique      :db.unique/identity
+    :db/doc         "`<issuer>|<sub>` of the identity provider that manages this user. SSO-managed users have no local password."}
+
+   {:db/ident       :user/platform-admin?
+    :db/valueType   :db.type/boolean
+    :db/cardinality :db.cardinality/one
+    :db/doc         "Platform admins moderate tenants, change flags and read logs and stats across tenants. Only set by hand."}])
 
 ;;; ----------------------------------------------------------------------------
 ;;; Tenant
@@ -42,7 +53,57 @@
 
    {:db/ident       :tenant/domains
     :db/valueType   :db.type/ref
-    :db/cardinality :db.cardinality/many}])
+    :db/cardinality :db.cardinality/many}
+
+   {:db/ident       :tenant/suspended-at
+    :db/valueType   :db.type/instant
+    :db/cardinality :db.cardinality/one
+    :db/doc         "When moderation suspended the tenant. Present means their realm is closed."}
+
+   {:db/ident       :tenant/demo?
+    :db/valueType   :db.type/boolean
+    :db/cardinality :db.cardinality/one
+    :db/doc         "Demo tenants are never indexed and show the staging banner, even in production."}
+
+   {:db/ident       :tenant/comments
+    :db/valueType   :db.type/keyword
+    :db/cardinality :db.cardinality/one
+    :db/doc         "Who may comment on the tenant's pages, :anyone or :signed-in. Absent means comments are off."}
+
+   {:db/ident       :tenant/region
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one
+    :db/doc         "Where the tenant's data must stay, like \"eu\". Absent means anywhere."}])
+
+;;; ----------------------------------------------------------------------------
+;;; SSO
+;;;
+;;; A tenant's OpenID Connect identity provider. The client secret is sealed
+;;; with a field key, see `bits.crypto/seal`.
+
+(def sso-schema
+  [{:db/ident       :tenant/sso
+    :db/valueType   :db.type/ref
+    :db/cardinality :db.cardinality/one
+    :db/isComponent true}
+
+   {:db/ident       :sso/issuer
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one
+    :db/doc         "Issuer URL. Discovery reads `<issuer>/.well-known/openid-configuration`."}
+
+   {:db/ident       :sso/client-id
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one}
+
+   {:db/ident       :sso/client-secret
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one}
+
+   {:db/ident       :sso/provision?
+    :db/valueType   :db.type/boolean
+    :db/cardinality :db.cardinality/one
+    :db/doc         "Whether users the provider vouches for join the tenant on first sign-in. Otherwise only existing members get in."}])
 
 ;;; ----------------------------------------------------------------------------
 ;;; Domain
@@ -141,6 +202,43 @@
     :db/valueType   :db.type/keyword
     :db/cardinality :db.cardinality/one}])
 
+;;; ----------------------------------------------------------------------------
+;;; Meta
+;;;
+;;; Overrides for search results and link previews. Tenants and products may
+;;; carry them; pages fall back to their own title and text when absent.
+
+(def meta-schema
+  [{:db/ident       :meta/title
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one}
+
+   {:db/ident       :meta/description
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one}
+
+   {:db/ident       :meta/image-url
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one
+    :db/doc         "Preview image, ideally 1200×630. Without one, a card is drawn from the title."}])
+
+;;; ----------------------------------------------------------------------------
+;;; Deletion
+;;;
+;;; Tenant resources are soft deleted: marked with when and by whom, hidden from
+;;; queries, and restorable until the reaper retracts them for good.
+
+(def deletion-schema
+  [{:db/ident       :entity/deleted-at
+    :db/valueType   :db.type/instant
+    :db/cardinality :db.cardinality/one
+    :db/doc         "When the entity was deleted. Present means hidden."}
+
+   {:db/ident       :entity/deleted-by
+    :db/valueType   :db.type/ref
+    :db/cardinality :db.cardinality/one
+    :db/doc         "The user who deleted the entity."}])
+
 ;;; ----------------------------------------------------------------------------
 ;;; Shop Idents
 
@@ -150,6 +248,17 @@
    {:db/ident :product.status/active}
    {:db/ident :product.status/archived}
 
+   ;; Product license, see bits.license
+   {:db/ident :product.license/all-rights-reserved}
+   {:db/ident :product.license/cc-by}
+   {:db/ident :product.license/cc-by-nc}
+   {:db/ident :product.license/cc-by-sa}
+   {:db/ident :product.license/cc0}
+   {:db/ident :product.license/custom}
+
+   {:db/ident :page.status/draft}
+   {:db/ident :page.status/published}
+
    ;; Variant type (fulfilment)
    {:db/ident :variant.type/digital}
    {:db/ident :variant.type/physical}
@@ -261,7 +370,86 @@
    {:db/ident       :product/created-at
     :db/valueType   :db.type/instant
     :db/cardinality :db.cardinality/one
-    :db/doc         "When this product was created. Used for 'newest' sort."}])
+    :db/doc         "When this product was created. Used for 'newest' sort."}
+
+   {:db/ident       :product/version
+    :db/valueType   :db.type/long
+    :db/cardinality :db.cardinality/one
+    :db/doc         "Bumped on every edit, see bits.version. Absent means 0."}
+
+   {:db/ident       :product/license
+    :db/valueType   :db.type/ref
+    :db/cardinality :db.cardinality/one
+    :db/doc         "Usage terms. Ref to a :product.license/* ident. Absent means all rights reserved."}
+
+   {:db/ident       :product/license-terms
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one
+    :db/doc         "The tenant's own terms, when the license is :product.license/custom."}
+
+   {:db/ident       :product/options
+    :db/valueType   :db.type/ref
+    :db/cardinality :db.cardinality/many
+    :db/isComponent true
+    :db/doc         "What buyers choose between, like size and colour. See bits.variant."}
+
+   {:db/ident       :product/publish-at
+    :db/valueType   :db.type/instant
+    :db/cardinality :db.cardinality/one
+    :db/doc         "Hidden from buyers until then. Absent means always. See bits.schedule."}
+
+   {:db/ident       :product/unpublish-at
+    :db/valueType   :db.type/instant
+    :db/cardinality :db.cardinality/one
+    :db/doc         "Hidden from buyers from then on. Absent means never."}])
+
+;;; ----------------------------------------------------------------------------
+;;; Page
+;;;
+;;; Storefront pages written by the tenant, served at `/{slug}` in their realm.
+
+(def page-schema
+  [{:db/ident       :page/id
+    :db/valueType   :db.type/uuid
+    :db/cardinality :db.cardinality/one
+    :db/unique      :db.unique/identity}
+
+   {:db/ident       :page/slug
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one
+    :db/doc         "Path segment the page is served at. Unique within the tenant."}
+
+   {:db/ident       :page/title
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one}
+
+   {:db/ident       :page/blocks
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one
+    :db/doc         "The page's content as a JSON array of blocks. See bits.page."}
+
+   {:db/ident       :page/status
+    :db/valueType   :db.type/ref
+    :db/cardinality :db.cardinality/one
+    :db/doc         "Ref to a :page.status/* ident. Drafts are only shown to admins."}
+
+   {:db/ident       :page/published-at
+    :db/valueType   :db.type/instant
+    :db/cardinality :db.cardinality/one}
+
+   {:db/ident       :page/created-at
+    :db/valueType   :db.type/instant
+    :db/cardinality :db.cardinality/one}
+
+   {:db/ident       :page/publish-at
+    :db/valueType   :db.type/instant
+    :db/cardinality :db.cardinality/one
+    :db/doc         "A published page stays hidden until then. Absent means at once. See bits.schedule."}
+
+   {:db/ident       :page/unpublish-at
+    :db/valueType   :db.type/instant
+    :db/cardinality :db.cardinality/one
+    :db/doc         "A published page is hidden from then on. Absent means never."}])
 
 ;;; ----------------------------------------------------------------------------
 ;;; Variant
@@ -303,6 +491,12 @@
     :db.attr/preds  'clojure.core/pos-int?
     :db/doc         "Maximum units available. Absent means unlimited (typical for digital)."}
 
+   {:db/ident       :variant/grams
+    :db/valueType   :db.type/long
+    :db/cardinality :db.cardinality/one
+    :db.attr/preds  'clojure.core/pos-int?
+    :db/doc         "Shipping weight of one unit in grams. Absent means weightless, like a download."}
+
    {:db/ident       :variant/active?
     :db/valueType   :db.type/boolean
     :db/cardinality :db.cardinality/one
@@ -318,6 +512,56 @@
     :db/cardinality :db.cardinality/one
     :db/doc         "When this variant was created."}])
 
+;;; ----------------------------------------------------------------------------
+;;; Option (component of Product)
+;;;
+;;; A product's options span a matrix of combinations, one value per option.
+;;; Each active variant sits at one combination; see bits.variant.
+
+(def option-schema
+  [{:db/ident       :option/id
+    :db/valueType   :db.type/uuid
+    :db/cardinality :db.cardinality/one
+    :db/unique      :db.unique/identity}
+
+   {:db/ident       :option/name
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one
+    :db/doc         "What's being chosen, e.g. 'Size'."}
+
+   {:db/ident       :option/position
+    :db/valueType   :db.type/long
+    :db/cardinality :db.cardinality/one
+    :db.attr/preds  'clojure.core/pos-int?
+    :db/doc         "Order the option is shown in."}
+
+   {:db/ident       :option/values
+    :db/valueType   :db.type/ref
+    :db/cardinality :db.cardinality/many
+    :db/isComponent true
+    :db/doc         "The choices. Component option-value entities."}
+
+   {:db/ident       :option-value/id
+    :db/valueType   :db.type/uuid
+    :db/cardinality :db.cardinality/one
+    :db/unique      :db.unique/identity}
+
+   {:db/ident       :option-value/label
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one
+    :db/doc         "e.g. 'A3' or 'Forest green'."}
+
+   {:db/ident       :option-value/position
+    :db/valueType   :db.type/long
+    :db/cardinality :db.cardinality/one
+    :db.attr/preds  'clojure.core/pos-int?
+    :db/doc         "Order the value is shown in within its option."}
+
+   {:db/ident       :variant/option-values
+    :db/valueType   :db.type/ref
+    :db/cardinality :db.cardinality/many
+    :db/doc         "The variant's combination: one value of each of the product's options."}])
+
 ;;; ----------------------------------------------------------------------------
 ;;; SKU (component of Variant)
 
@@ -556,6 +800,11 @@
     :db/cardinality :db.cardinality/one
     :db/doc         "Snapshot of SKU code at time of purchase."}
 
+   {:db/ident       :line-item/license-hash
+    :db/valueType   :db.type/string
+    :db/cardinality :db.cardinality/one
+    :db/doc         "Hash of the product's license at time of purchase, see bits.license."}
+
    {:db/ident       :line-item/unit-price
     :db/valueType   :db.type/ref
     :db/cardinality :db.cardinality/one
@@ -620,6 +869,11 @@
     :db/cardinality :db.cardinality/many
     :db/doc         "Products belonging to this tenant's shop."}
 
+   {:db/ident       :tenant/pages
+    :db/valueType   :db.type/ref
+    :db/cardinality :db.cardinality/many
+    :db/doc         "Storefront pages belonging to this tenant."}
+
    {:db/ident       :tenant/line-items
     :db/valueType   :db.type/ref
     :db/cardinality :db.cardinality/many
@@ -651,6 +905,9 @@
    {:db/ident        :tenant/ensure
     :db.entity/attrs [:tenant/id :tenant/purpose :tenant/created-at]}
 
+   {:db/ident        :sso/ensure
+    :db.entity/attrs [:sso/issuer :sso/client-id :sso/client-secret]}
+
    {:db/ident        :domain/ensure
     :db.entity/attrs [:domain/name]}
 
@@ -675,6 +932,14 @@
                       :product/status
                       :product/created-at]}
 
+   {:db/ident        :page/ensure
+    :db.entity/attrs [:page/id
+                      :page/slug
+                      :page/title
+                      :page/blocks
+                      :page/status
+                      :page/created-at]}
+
    {:db/ident        :variant/ensure
     :db.entity/attrs [:variant/id
                       :variant/name
@@ -684,6 +949,12 @@
                       :variant/active?
                       :variant/created-at]}
 
+   {:db/ident        :option/ensure
+    :db.entity/attrs [:option/id :option/name :option/position :option/values]}
+
+   {:db/ident        :option-value/ensure
+    :db.entity/attrs [:option-value/id :option-value/label :option-value/position]}
+
    {:db/ident        :sku/ensure
     :db.entity/attrs [:sku/code]}
 
@@ -742,14 +1013,19 @@
 (def schema
   (->> [user-schema
         tenant-schema
+        sso-schema
         domain-schema
         creator-schema
         post-schema
         membership-schema
+        meta-schema
+        deletion-schema
         shop-ident-schema
         money-schema
         product-schema
+        page-schema
         variant-schema
+        option-schema
         sku-schema
         ledger-account-schema
         journal-entry-schema
@@ -0,0 +1,272 @@
+(ns bits.secret
+  (:require
+   [bits.spec]
+   [buddy.core.codecs :as codecs]
+   [buddy.core.hash :as hash]
+   [buddy.core.mac :as mac]
+   [charred.api :as json]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [clojure.walk :as walk]
+   [com.stuartsierra.component :as component]
+   [hato.client :as http]
+   [io.pedestal.log :as log]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.time ZoneOffset ZonedDateTime)
+   (java.time.format DateTimeFormatter)
+   (java.util.concurrent Executors ScheduledExecutorService TimeUnit)))
+
+;;; ----------------------------------------------------------------------------
+;;; Providers
+;;;
+;;; A provider reads a secret by path and returns its data as a map, along with
+;;; a lease when the secret is dynamic. Static secrets have no lease and never
+;;; change while the system runs.
+
+(defprotocol SecretsProvider
+  (fetch [provider path]
+    "Returns `{:data {...}}`, plus `:lease-id`, `:lease-seconds` and
+    `:renewable?` for dynamic secrets.")
+  (renew [provider lease-id]
+    "Extends a lease, returning the new `:lease-seconds`. Throws when the lease
+    can't be extended any further."))
+
+;;; ----------------------------------------------------------------------------
+;;; Environment
+;;;
+;;; The path names a variable, and the secret's data is `{:value "..."}`. This
+;;; is how config worked before providers existed.
+
+(defrecord Env [variables]
+  SecretsProvider
+  (fetch [_ path]
+    (if-some [value (get variables path)]
+      {:data {:value value}}
+      (throw (ex-info (str "Environment variable not set: " path) {:path path}))))
+  (renew [_ lease-id]
+    (throw (ex-info "Environment secrets have no leases?!" {:lease-id lease-id}))))
+
+;;; ----------------------------------------------------------------------------
+;;; HashiCorp Vault
+;;;
+;;; Paths are relative to `/v1/`, so a KV v2 secret is `secret/data/bits` and
+;;; dynamic database credentials are `database/creds/bits`.
+
+(defn- vault-request
+  [{:keys [address token]} method path body]
+  (let [response (http/request {:method            method
+                                :url               (str (str/replace address #"/+$" "") "/v1/" path)
+                                :headers           {"X-Vault-Token" token}
+                                :content-type      :json
+                                :body              (some-> body json/write-json-str)
+                                :throw-exceptions? false})]
+    (when-not (<= 200 (:status response) 299)
+      (throw (ex-info (format "Vault returned %d for %s" (:status response) path)
+                      {:path path :status (:status response)})))
+    (json/read-json (:body response) :key-fn keyword)))
+
+(defn- unwrap-kv
+  "KV v2 nests the secret under a second `data` key beside its metadata."
+  [data]
+  (if (and (map? (:data data)) (contains? data :metadata))
+    (:data data)
+    data))
+
+(defrecord Vault [address token]
+  SecretsProvider
+  (fetch [this path]
+    (span/with-span! {:name ::vault-fetch}
+      (let [{:keys [data lease_duration lease_id renewable]} (vault-request this :get path nil)]
+        (cond-> {:data (unwrap-kv data)}
+          (not (str/blank? lease_id)) (assoc :lease-id      lease_id
+                                             :lease-seconds lease_duration
+                                             :renewable?    (boolean renewable))))))
+  (renew [this lease-id]
+    (span/with-span! {:name ::vault-renew}
+      {:lease-seconds (:lease_duration (vault-request this :put "sys/leases/renew" {:lease_id lease-id}))})))
+
+;;; ----------------------------------------------------------------------------
+;;; AWS Secrets Manager
+;;;
+;;; Requests are signed with Signature Version 4. Secret strings holding a JSON
+;;; object become the data map; anything else is returned as `{:value "..."}`.
+
+(def ^:private amz-date-format
+  (DateTimeFormatter/ofPattern "yyyyMMdd'T'HHmmss'Z'"))
+
+(defn- hmac
+  [key data]
+  (mac/hash data {:key key :alg :hmac+sha256}))
+
+(defn- sha256-hex
+  [s]
+  (codecs/bytes->hex (hash/sha256 s)))
+
+(defn sign
+  "Returns the headers for a SigV4-signed POST to `/` on host."
+  [{:keys [access-key-id region secret-access-key session-token]} service host headers body ^ZonedDateTime now]
+  (let [amz-date       (.format now amz-date-format)
+        date           (subs amz-date 0 8)
+        headers        (cond-> (assoc headers "host" host "x-amz-date" amz-date)
+                         session-token (assoc "x-amz-security-token" session-token))
+        names          (sort (keys headers))
+        signed-headers (str/join ";" names)
+        canonical      (str/join "\n" ["POST"
+                                       "/"
+                                       ""
+                                       (str/join (map #(str % ":" (str/trim (get headers %)) "\n") names))
+                                       signed-headers
+                                       (sha256-hex body)])
+        scope          (str/join "/" [date region service "aws4_request"])
+        string-to-sign (str/join "\n" ["AWS4-HMAC-SHA256" amz-date scope (sha256-hex canonical)])
+        signing-key    (reduce hmac
+                               (codecs/str->bytes (str "AWS4" secret-access-key))
+                               [date region service "aws4_request"])]
+    (assoc headers "authorization"
+           (format "AWS4-HMAC-SHA256 Credential=%s/%s, SignedHeaders=%s, Signature=%s"
+                   access-key-id
+                   scope
+                   signed-headers
+                   (codecs/bytes->hex (hmac signing-key string-to-sign))))))
+
+(defn- parse-secret-string
+  [s]
+  (let [parsed (try (json/read-json s :key-fn keyword) (catch Exception _ nil))]
+    (if (map? parsed) parsed {:value s})))
+
+(defrecord SecretsManager [access-key-id region secret-access-key session-token]
+  SecretsProvider
+  (fetch [this path]
+    (span/with-span! {:name ::secrets-manager-fetch}
+      (let [host     (format "secretsmanager.%s.amazonaws.com" region)
+            body     (json/write-json-str {:SecretId path})
+            headers  (sign this "secretsmanager" host
+                           {"content-type" "application/x-amz-json-1.1"
+                            "x-amz-target" "secretsmanager.GetSecretValue"}
+                           body
+                           (ZonedDateTime/now ZoneOffset/UTC))
+            response (http/post (str "https://" host "/")
+                                {:headers           (dissoc headers "host")
+                                 :body              body
+                                 :throw-exceptions? false})]
+        (when-not (= 200 (:status response))
+          (throw (ex-info (format "Secrets Manager returned %d for %s" (:status response) path)
+                          {:path path :status (:status response)})))
+        {:data (parse-secret-string (:SecretString (json/read-json (:body response) :key-fn keyword)))})))
+  (renew [_ lease-id]
+    (throw (ex-info "Secrets Manager secrets have no leases?!" {:lease-id lease-id}))))
+
+(defn make-provider
+  [{:keys [provider] :as config}]
+  {:pre [(s/valid? ::config config)]}
+  (case provider
+    :aws-secrets-manager (map->SecretsManager (:aws config))
+    :env                 (->Env (:variables config))
+    :vault               (map->Vault (:vault config))))
+
+;;; ----------------------------------------------------------------------------
+;;; References
+;;;
+;;; Config files point at secrets with a tagged literal, which is replaced by
+;;; the value when config is read:
+;;;
+;;;   {:service {:csrf-secret #bits/secret {:path "secret/data/bits" :key :csrf-secret}}}
+
+(defrecord Ref [path key])
+
+(defn read-ref
+  "Reader for `#bits/secret`. A bare string reads the `:value` of that path."
+  [form]
+  (if (string? form)
+    (->Ref form :value)
+    (map->Ref form)))
+
+(defn refs
+  [config]
+  (filter #(instance? Ref %) (tree-seq coll? seq config)))
+
+(defn resolve-refs
+  "Replaces every reference in config with its secret. Each path is fetched
+  once, however many keys are read from it."
+  [provider config]
+  (let [fetched (memoize #(:data (fetch provider %)))]
+    (walk/postwalk
+     (fn [x]
+       (if (instance? Ref x)
+         (let [value (get (fetched (:path x)) (:key x))]
+           (when (nil? value)
+             (throw (ex-info (format "Secret %s has no %s" (:path x) (:key x))
+                             {:path (:path x) :key (:key x)})))
+           value)
+         x))
+     config)))
+
+;;; ----------------------------------------------------------------------------
+;;; Leases
+;;;
+;;; Dynamic secrets are renewed two thirds of the way through their lease. When
+;;; a lease can't be renewed, or was never renewable, the secret is fetched
+;;; again and the new data is handed to the caller so it can rotate without a
+;;; restart.
+
+(defn renew-delay-seconds
+  [lease-seconds]
+  (max 1 (quot (* 2 lease-seconds) 3)))
+
+(declare watch!)
+
+(defn- refresh!
+  [keeper path lease on-rotate]
+  (let [{:keys [provider]} keeper
+        lease              (try
+                             ;; A shorter lease than last time means the
+                             ;; secret is nearing its maximum TTL.
+                             (when (:renewable? lease)
+                               (let [renewed (renew provider (:lease-id lease))]
+                                 (when (<= (:lease-seconds lease) (:lease-seconds renewed))
+                                   (merge lease renewed))))
+                             (catch Exception ex
+                               (log/warn :msg "Lease renewal failed, fetching again." :path path :exception ex)
+                               nil))]
+    (if lease
+      (watch! keeper path lease on-rotate)
+      (try
+        (let [secret (fetch provider path)]
+          (on-rotate (:data secret))
+          (watch! keeper path secret on-rotate))
+        (catch Exception ex
+          (log/error :msg "Can't rotate secret?!" :path path :exception ex)
+          (watch! keeper path {:lease-seconds 30} on-rotate))))))
+
+(defn watch!
+  "Keeps the secret's lease alive, calling on-rotate with new data whenever the
+  secret has to be fetched again. Does nothing for secrets without leases."
+  [keeper path secret on-rotate]
+  (when-let [lease-seconds (:lease-seconds secret)]
+    (let [^ScheduledExecutorService executor (:executor keeper)]
+      (.schedule executor
+                 ^Runnable #(refresh! keeper path secret on-rotate)
+                 (long (renew-delay-seconds lease-seconds))
+                 TimeUnit/SECONDS))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Keeper [^ScheduledExecutorService executor provider]
+  component/Lifecycle
+  (start [this]
+    (assoc this :executor (Executors/newSingleThreadScheduledExecutor)))
+  (stop [this]
+    (some-> executor .shutdownNow)
+    (assoc this :executor nil)))
+
+(defmethod print-method Keeper
+  [keeper ^java.io.Writer w]
+  (.write w (format "#<Keeper %s>" (name (:provider-name keeper)))))
+
+(defn make-keeper
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Keeper {:provider      (make-provider config)
+                :provider-name (:provider config)}))
@@ -1,16 +1,48 @@
 (ns bits.service
   (:require
+   [bits.anomaly :as anom]
    [bits.coerce :as coerce]
+   [bits.comment]
    [bits.form :as form]
    [bits.html :as html]
    [bits.locale :refer [tru]]
    [bits.middleware :as mw]
    [bits.middleware.session :as middleware.session]
+   [bits.module :as module]
+   [bits.module.activity :as activity]
+   [bits.module.analytics :as analytics]
+   [bits.module.api-key :as api-key]
+   [bits.module.catalog :as catalog]
+   [bits.module.comment :as comment]
    [bits.module.creator :as creator]
+   [bits.module.dashboard :as dashboard]
+   [bits.module.discount :as discount]
+   [bits.module.export :as export]
+   [bits.module.flag :as flag]
+   [bits.module.log :as logs]
+   [bits.module.maintenance :as maintenance]
+   [bits.module.moderation :as moderation]
+   [bits.module.notification :as notification]
+   [bits.module.page :as page]
    [bits.module.platform :as platform]
+   [bits.module.plugin :as plugin]
+   [bits.module.pricing :as pricing]
+   [bits.module.product :as product]
+   [bits.module.redirect :as redirect]
+   [bits.module.retention :as retention]
+   [bits.module.review :as review]
    [bits.module.session :as session]
+   [bits.module.shipping :as shipping]
+   [bits.module.site :as site]
+   [bits.module.sso :as sso]
+   [bits.module.trash :as trash]
+   [bits.module.variant :as variant]
+   [bits.module.webhook :as webhook]
+   [bits.module.wishlist :as wishlist]
    [bits.morph :as morph]
+   [bits.notification]
    [bits.response]
+   [bits.schedule :as schedule]
    [bits.ui :as ui]
    [clojure.core.async :as a]
    [clojure.spec.alpha :as s]
@@ -34,11 +66,17 @@
 ;;; Exception handling
 
 (defn- default-error-handler
+  "Anomalies thrown as `ex-info` answer with their own status and code. Faults,
+  and anything else, are logged and hidden behind a plain 500."
   [exception request]
-  (log/error :msg       "Unhandled exception?!"
-             :uri       (:uri request)
-             :exception exception)
-  bits.response/internal-server-error-response)
+  (let [data (ex-data exception)]
+    (if (and (anom/anomaly? data) (not= ::anom/fault (::anom/category data)))
+      (bits.response/anomaly-response request data)
+      (do
+        (log/error :msg       "Unhandled exception?!"
+                   :uri       (:uri request)
+                   :exception exception)
+        (bits.response/with-reference bits.response/internal-server-error-response request)))))
 
 (defn- coercion-error-handler
   [status]
@@ -61,8 +99,7 @@
                         "Invalid request parameters")
           remote-addr (:remote-addr request)]
       (log/warn :msg message :action (or action raw-action) :remote-addr remote-addr :errors data)
-      {:status status
-       :body   message})))
+      (bits.response/with-reference {:status status :body (str message "\n")} request))))
 
 (def exception-middleware
   (exception/create-exception-middleware
@@ -88,6 +125,23 @@
     (ui/text-muted {:class ["mt-4"]}
       (tru "Want your own Bits? We want to hear from you!"))))
 
+(def ^:const ^:private suspended-tenant-id
+  #uuid "00000000-0000-0000-0000-200000000000")
+
+(defn- realm-suspended-view
+  [_request]
+  (ui/page-center {}
+    (ui/page-title {} (tru "Realm suspended"))
+    (ui/text-muted {:class ["mt-4"]}
+      (tru "This Bits has been suspended for breaking our terms."))))
+
+(defn- maintenance-view
+  [_request]
+  (ui/page-center {}
+    (ui/page-title {} (tru "Back soon"))
+    (ui/text-muted {:class ["mt-4"]}
+      (tru "This Bits is down for maintenance. Try again in a few minutes."))))
+
 (def realms
   (medley/index-by
    :realm/type
@@ -98,6 +152,11 @@
       :realm/type   :realm.type/platform
       :realm/view   platform/explore-view
       :tenant/id    platform-tenant-id}
+     {:realm/layout ui/layout
+      :realm/status 451
+      :realm/type   :realm.type/suspended
+      :realm/view   realm-suspended-view
+      :tenant/id    suspended-tenant-id}
      {:realm/layout ui/layout
       :realm/status 404
       :realm/type   :realm.type/unknown
@@ -108,9 +167,36 @@
 ;;; Modules
 
 (def modules
-  [creator/module
+  [activity/module
+   analytics/module
+   api-key/module
+   catalog/module
+   comment/module
+   creator/module
+   dashboard/module
+   discount/module
+   export/module
+   flag/module
+   logs/module
+   maintenance/module
+   moderation/module
+   notification/module
+   page/module
    platform/module
-   session/module])
+   plugin/module
+   pricing/module
+   product/module
+   redirect/module
+   retention/module
+   review/module
+   session/module
+   shipping/module
+   site/module
+   sso/module
+   trash/module
+   variant/module
+   webhook/module
+   wishlist/module])
 
 ;;; ----------------------------------------------------------------------------
 ;;; Broadcast
@@ -123,6 +209,17 @@
 ;;; ----------------------------------------------------------------------------
 ;;; App
 
+(defn- transactional
+  "Wraps each action in a transaction unless it opts out with
+  `:transaction? false`. The action handler refreshes clients after the
+  action returns, so the transaction has to commit inside it, before anyone
+  re-renders."
+  [actions]
+  (medley/map-vals (fn [action]
+                     (cond-> action
+                       (not (false? (:transaction? action))) (update :handler mw/wrap-transaction)))
+                   actions))
+
 (defn make-app
   "Builds Ring handler. Normalizes actions and builds schema at startup."
   [service]
@@ -131,6 +228,8 @@
                 cookie-secure
                 csrf-cookie-name
                 csrf-secret
+                maintenance-allowlist
+                max-upload-bytes
                 modules
                 refresh-ch
                 refresh-mult
@@ -142,14 +241,22 @@
            :headers {"content-type" "text/html; charset=utf-8"}
            :body    (html/html (ui/layout request (ui/not-found-view request)))})
 
+        maintenance-handler
+        (fn [request]
+          {:headers {"content-type" "text/html; charset=utf-8"}
+           :body    (html/html (ui/layout request (maintenance-view request)))})
+
         _             (s/assert :bits.module/combined modules)
-        actions       (:actions modules)
+        actions       (transactional (:actions modules))
         action-schema (morph/actions->schema actions)
         routes        (conj (:routes modules)
                             ["/action"
-                             {:post {:coercion   coerce/coercion
-                                     :parameters {:form action-schema}
-                                     :handler    (morph/action-handler actions)}}])
+                             {:bits/scopes #{:write}
+                              :post        {:coercion   coerce/coercion
+                                            :parameters {:form action-schema}
+                                            :handler    (morph/action-handler actions)}}])
+        ;; Tenants' redirects and sites never answer under these.
+        roots         (module/route-roots routes)
 
         router
         (ring/router
@@ -158,20 +265,29 @@
                  :middleware [trace.http/wrap-reitit-route
                               exception-middleware
                               ring.coercion/coerce-request-middleware
+                              mw/scope-middleware
                               mw/page-middleware]}})
 
+        ;; Tenant pages live at arbitrary top-level paths, so they're tried
+        ;; only once no route matched.
         handler
-        (ring/routes (ring/create-default-handler {:not-found not-found-handler}))
+        (ring/routes page/storefront
+                     (ring/create-default-handler {:not-found not-found-handler}))
 
         middleware
-        [[morph/wrap-refresh refresh-ch refresh-mult]
+        [[mw/wrap-request-id]
+         [morph/wrap-refresh refresh-ch refresh-mult]
          [morph/wrap-channels channels]
          [mw/wrap-state service]
          [mw/wrap-datomic]
          [middleware.params/wrap-params]
+         [mw/wrap-multipart {:max-bytes max-upload-bytes}]
          [form/wrap-form-params]
          [middleware.cookies/wrap-cookies]
          [mw/wrap-realm realms]
+         [mw/wrap-residency]
+         [mw/wrap-redirects roots]
+         [mw/wrap-api-key]
          [middleware.session/wrap-session {:cookie-attrs {:http-only true
                                                           :same-site :lax
                                                           :secure    cookie-secure}
@@ -183,8 +299,13 @@
                         :secret        csrf-secret}]
          [mw/wrap-assets]
          [mw/wrap-user]
+         [mw/wrap-log-context]
+         [mw/wrap-maintenance {:allowlist maintenance-allowlist
+                               :respond   maintenance-handler}]
          [mw/wrap-secure-headers]
-         [mw/wrap-locale]]]
+         [mw/wrap-locale]
+         [plugin/wrap-plugins roots]
+         [site/wrap-site roots]]]
     (-> (ring/ring-handler router handler {:middleware middleware})
         (trace.http/wrap-server-span {:create-span? true}))))
 
@@ -192,6 +313,7 @@
 ;;; Service
 
 (defrecord Service [channels
+                    comments
                     cookie-name
                     cookie-secure
                     csrf-cookie-name
@@ -200,11 +322,15 @@
                     http-host
                     http-port
                     keymaster
+                    maintenance-allowlist
                     max-refresh-ms
+                    max-upload-bytes
                     modules
+                    notifications
                     postgres
                     refresh-ch
                     refresh-mult
+                    scheduler
                     server-name
                     session-store
                     sse-reconnect-ms
@@ -222,11 +348,15 @@
                                 :channels     channels
                                 :refresh-ch   refresh-ch
                                 :refresh-mult refresh-mult)]
+        (bits.notification/tap! notifications refresh-ch)
+        (schedule/tap! scheduler refresh-ch)
+        (bits.comment/tap! comments refresh-ch)
         (set-agent-send-executor! (Executors/newVirtualThreadPerTaskExecutor))
         (set-agent-send-off-executor! (Executors/newVirtualThreadPerTaskExecutor))
         (assoc this :stop-fn (server/run-server (make-app this)
                                                 {:host                       http-host
                                                  :legacy-unsafe-remote-addr? false
+                                                 :max-body                   max-upload-bytes
                                                  :port                       http-port
                                                  :server-header              server-name})))))
   (stop [this]
@@ -238,6 +368,9 @@
       (when-let [stop (:stop-fn this)]
         (stop :timeout 200))
       (when-let [ch (:refresh-ch this)]
+        (bits.notification/untap! notifications ch)
+        (schedule/untap! scheduler ch)
+        (bits.comment/untap! comments ch)
         (a/close! ch))
       (assoc this :channels nil :refresh-ch nil :refresh-mult nil :stop-fn nil))))
 
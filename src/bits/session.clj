@@ -1,13 +1,12 @@
 (ns bits.session
   (:require
+   [bits.clock :as clock]
    [bits.crypto :as crypto]
    [bits.postgres :as postgres]
    [bits.postgres.session :as postgres.session]
    [bits.spec]
    [clojure.spec.alpha :as s]
    [com.stuartsierra.component :as component]
-   [java-time.api :as time]
-   [next.jdbc :as jdbc]
    [ring.middleware.session.store :as session.store]
    [steffan-westcott.clj-otel.api.trace.span :as span]))
 
@@ -31,13 +30,13 @@
                             :where  [:and
                                      [:= :tenant-id tenant-id]
                                      [:= :sid-hash (crypto/sha256 sid)]
-                                     [:> :expires-at (time/offset-date-time)]]})))
+                                     [:> :expires-at (clock/now (:clock store))]]})))
 
 (defn create-session!
   "Create session, handling race conditions with ON CONFLICT."
   [store tenant-id sid data]
   (let [{:keys [postgres idle-timeout-days]} store
-        now (time/offset-date-time)]
+        now (clock/now (:clock store))]
     (span/with-span! {:name ::create-session!}
       (postgres/execute-one! postgres
                              {:insert-into :sessions
@@ -54,7 +53,7 @@
   "Update accessed_at and extend expires_at."
   [store tenant-id sid]
   (let [{:keys [postgres idle-timeout-days]} store
-        now (time/offset-date-time)]
+        now (clock/now (:clock store))]
     (span/with-span! {:name ::touch-session!}
       (postgres/execute-one! postgres
                              {:update :sessions
@@ -69,7 +68,7 @@
   "Insert or update session atomically. Used by write-session."
   [store tenant-id sid data]
   (let [{:keys [postgres idle-timeout-days]} store
-        now (time/offset-date-time)]
+        now (clock/now (:clock store))]
     (span/with-span! {:name ::upsert-session!}
       (postgres/execute-one! postgres
                              {:insert-into   :sessions
@@ -87,14 +86,15 @@
 
 (defn rotate-session!
   "Delete old session, create new session with user-id. Returns new sid.
-   Prevents session fixation attacks. Runs in a transaction.
-   Order is delete-then-insert so partial failure leaves zero sessions (safe)."
+   Prevents session fixation attacks. Runs in a transaction, joining the
+   store's if it has one. Order is delete-then-insert so partial failure
+   leaves zero sessions (safe)."
   [store tenant-id old-sid user-id]
   (let [{:keys [postgres randomizer idle-timeout-days]} store
         new-sid (crypto/random-sid randomizer)
-        now     (time/offset-date-time)]
+        now     (clock/now (:clock store))]
     (span/with-span! {:name ::rotate-session!}
-      (jdbc/with-transaction [tx (:datasource postgres)]
+      (postgres/with-transaction [tx postgres]
         (postgres/execute! tx
                            {:delete-from :sessions
                             :where       [:and
@@ -118,7 +118,7 @@
       (postgres/execute-one! postgres
                              {:update :sessions
                               :set    {:user-id     nil
-                                       :accessed-at (time/offset-date-time)}
+                                       :accessed-at (clock/now (:clock store))}
                               :where  [:and
                                        [:= :tenant-id tenant-id]
                                        [:= :sid-hash (crypto/sha256 sid)]]}))))
@@ -139,7 +139,7 @@
     (let [[{:keys [next.jdbc/update-count]}]
           (postgres/execute! (:postgres store)
                              {:delete-from :sessions
-                              :where       [:<= :expires-at (time/offset-date-time)]})]
+                              :where       [:<= :expires-at (clock/now (:clock store))]})]
       (or update-count 0))))
 
 ;;; ----------------------------------------------------------------------------
@@ -148,7 +148,8 @@
 ;;; Key is a compound map: {:tenant-id uuid :sid string}
 ;;; Middleware constructs this from the resolved tenant and cookie.
 
-(defrecord SessionStore [idle-timeout-days
+(defrecord SessionStore [clock
+                         idle-timeout-days
                          postgres
                          randomizer]
   component/Lifecycle
@@ -0,0 +1,86 @@
+(ns bits.shadow
+  "Candidate policies evaluated alongside the active ones without being
+  enforced, so a risky change can be watched on real traffic first.
+
+  Each policy is shadowed per tenant behind the flag `shadow.<policy>`. A
+  shadowed request evaluates the candidate too, counts whether it agreed with
+  the active policy, and logs what each said when they differ. The active
+  answer is always the one used.
+
+  Candidates for the content security policy and rate limits come from
+  config, as directives and limits merged over the active ones. Realm
+  resolution's candidate is the uncached lookup, which keeps the domain cache
+  honest."
+  (:require
+   [bits.flag :as flag]
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [com.stuartsierra.component :as component]
+   [io.pedestal.log :as log]
+   [steffan-westcott.clj-otel.api.metrics.instrument :as instrument]))
+
+;;; ----------------------------------------------------------------------------
+;;; Policies
+
+(def policies
+  #{:csp :rate-limits :realm})
+
+(defn flag-name
+  [policy]
+  {:pre [(contains? policies policy)]}
+  (str "shadow." (name policy)))
+
+(defn shadowing?
+  "Whether the tenant's requests should evaluate policy's candidate too."
+  [shadow tenant-id policy]
+  (boolean
+   (and shadow
+        (flag/enabled? (:flags shadow) tenant-id (flag-name policy)))))
+
+(defn candidate
+  "What's configured to be merged over the active policy, or nil."
+  [shadow policy]
+  (get-in shadow [:candidates policy]))
+
+;;; ----------------------------------------------------------------------------
+;;; Comparing
+
+(defn compare!
+  "Records whether the candidate's answer agreed with the active one. Returns
+  active, which is what gets enforced."
+  [shadow policy tenant-id active candidate]
+  (let [agreed? (= active candidate)]
+    (instrument/add! (:comparison-counter shadow)
+                     {:value      1
+                      :attributes {"policy" (name policy)
+                                   "agreed" (str agreed?)}})
+    (when-not agreed?
+      (log/info :msg       "Shadow policy disagreed."
+                :policy    policy
+                :tenant-id tenant-id
+                :active    active
+                :candidate candidate))
+    active))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Shadow [candidates comparison-counter flags]
+  component/Lifecycle
+  (start [this]
+    (assoc this :comparison-counter (instrument/instrument
+                                     {:name            "shadow.comparison"
+                                      :instrument-type :counter
+                                      :unit            "{comparison}"
+                                      :description     "Shadowed policy evaluations by agreement"})))
+  (stop [this]
+    (assoc this :comparison-counter nil)))
+
+(defmethod print-method Shadow
+  [_ ^java.io.Writer w]
+  (.write w "#<Shadow>"))
+
+(defn make-shadow
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Shadow config))
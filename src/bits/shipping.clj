@@ -0,0 +1,290 @@
+(ns bits.shipping
+  "What it costs to send a basket somewhere.
+
+  Tenants group the places they ship to into zones, named by ISO 3166-1
+  country codes like `GB` or ISO 3166-2 subdivisions like `US-CA`. A shipment
+  falls in the zone naming its subdivision if there is one, and otherwise the
+  zone naming its country. Each zone has rates:
+
+    flat       - always offered at its amount
+    weight     - offered when the basket weighs at least `min-grams` and less
+                 than `max-grams`
+    free-above - offered at its amount, or free once the subtotal reaches
+                 `threshold`
+
+  Rates come from carriers. The tenant's own table is always one; carriers
+  that quote live prices can be added by implementing `Carrier` and listing
+  them under `:extra-carriers`. Every carrier's rates are offered together,
+  cheapest first."
+  (:require
+   [bits.anomaly :as anom]
+   [bits.locale :refer [tru]]
+   [bits.postgres :as postgres]
+   [bits.postgres.shipping :as postgres.shipping]
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [com.stuartsierra.component :as component]
+   [datomic.api :as d]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Carriers
+;;;
+;;; A shipment is a map of `:region`, `:grams`, `:subtotal` and `:currency`,
+;;; with amounts in minor units. Rates are maps of `:id`, unique across
+;;; carriers, `:name`, `:amount` and `:currency`.
+
+(defprotocol Carrier
+  (rates [carrier tenant-id shipment]
+    "The rates the carrier offers for the tenant's shipment, possibly none."))
+
+;;; ----------------------------------------------------------------------------
+;;; Regions
+
+(defn normalize-region
+  [s]
+  (some-> s str/trim str/upper-case not-empty))
+
+(defn valid-region?
+  [s]
+  (boolean (re-matches #"[A-Z]{2}(-[A-Z0-9]{1,3})?" (str s))))
+
+(defn- country
+  [region]
+  (first (str/split region #"-" 2)))
+
+(defn zone-for
+  "The zone a shipment to region falls in, preferring one naming the
+  subdivision over one naming its country, or nil."
+  [zones region]
+  (when-let [region (normalize-region region)]
+    (let [in? (fn [r zone] (some #{r} (:bits.postgres.shipping-zone/regions zone)))]
+      (or (some #(when (in? region %) %) zones)
+          (some #(when (in? (country region) %) %) zones)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Pricing
+
+(defn price
+  "What the rate charges for the shipment, or nil when it isn't offered."
+  [rate {:keys [currency grams subtotal]}]
+  (let [{:bits.postgres.shipping-rate/keys [amount kind max-grams min-grams threshold]} rate]
+    (when (= currency (:bits.postgres.shipping-rate/currency rate))
+      (case kind
+        "flat"       amount
+        "weight"     (when (and (<= (or min-grams 0) (or grams 0)) (< (or grams 0) max-grams))
+                       amount)
+        "free-above" (if (<= threshold (or subtotal 0)) 0 amount)))))
+
+(defn totals
+  "Adds the chosen rate to a basket's subtotal and discount. The discount
+  never touches shipping."
+  [{:keys [discount subtotal] :or {discount 0}} rate]
+  (let [shipping (or (:amount rate) 0)]
+    {:subtotal subtotal
+     :discount discount
+     :shipping shipping
+     :total    (+ (- subtotal discount) shipping)}))
+
+;;; ----------------------------------------------------------------------------
+;;; Zones
+
+(defn list-zones
+  [shipping tenant-id]
+  {:post [(s/valid? (s/coll-of ::postgres.shipping/zone) %)]}
+  (span/with-span! {:name ::list-zones}
+    (postgres/execute! (:postgres shipping)
+                       {:select   [:id :name :regions :created-at]
+                        :from     [:shipping-zones]
+                        :where    [:= :tenant-id tenant-id]
+                        :order-by [:created-at :id]})))
+
+(defn create-zone!
+  "Returns the zone's ID, or an anomaly when a region is malformed or already
+  in another of the tenant's zones."
+  [shipping tenant-id zone-name regions]
+  (span/with-span! {:name ::create-zone!}
+    (let [regions (into [] (comp (keep normalize-region) (distinct)) regions)
+          taken   (into #{} (mapcat :bits.postgres.shipping-zone/regions) (list-zones shipping tenant-id))]
+      (cond
+        (str/blank? zone-name)
+        (anom/incorrect {::anom/message (tru "Zones need a name.")})
+
+        (empty? regions)
+        (anom/incorrect {::anom/message (tru "List at least one country or region.")})
+
+        (not-every? valid-region? regions)
+        (anom/incorrect {::anom/message (tru "Use codes like GB or US-CA.")})
+
+        (some taken regions)
+        (anom/conflict {::anom/message (tru "{0} is already in another zone." (some taken regions))})
+
+        :else
+        (:bits.postgres.shipping-zone/id
+         (postgres/execute-one! (:postgres shipping)
+                                {:insert-into :shipping-zones
+                                 :values      [{:id        (random-uuid)
+                                                :tenant-id tenant-id
+                                                :name      (str/trim zone-name)
+                                                :regions   [:array regions :text]}]
+                                 :returning   [:id]}))))))
+
+(defn delete-zone!
+  "Deletes the zone and its rates. Returns true when there was one."
+  [shipping tenant-id id]
+  (span/with-span! {:name ::delete-zone!}
+    (let [[{:keys [next.jdbc/update-count]}]
+          (postgres/execute! (:postgres shipping)
+                             {:delete-from :shipping-zones
+                              :where       [:and [:= :id id] [:= :tenant-id tenant-id]]})]
+      (pos? (or update-count 0)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Rates
+
+(def kinds
+  #{"flat" "free-above" "weight"})
+
+(defn list-rates
+  "The tenant's rates across all of their zones, cheapest first."
+  [shipping tenant-id]
+  {:post [(s/valid? (s/coll-of ::postgres.shipping/rate) %)]}
+  (span/with-span! {:name ::list-rates}
+    (postgres/execute! (:postgres shipping)
+                       {:select   [:id :zone-id :name :kind :amount :currency
+                                   :min-grams :max-grams :threshold]
+                        :from     [:shipping-rates]
+                        :where    [:= :tenant-id tenant-id]
+                        :order-by [:amount :name]})))
+
+(defn- invalid-rate
+  [{:keys [amount currency kind max-grams min-grams threshold] rate-name :name}]
+  (cond
+    (str/blank? rate-name)
+    (tru "Rates need a name.")
+
+    (not (contains? kinds kind))
+    (tru "Pick flat, by weight or free above a threshold.")
+
+    (not (nat-int? amount))
+    (tru "The amount can''t be negative.")
+
+    (str/blank? currency)
+    (tru "Pick the amount''s currency.")
+
+    (and (= "weight" kind) (not (pos-int? max-grams)))
+    (tru "Weight rates need a maximum weight.")
+
+    (and (= "weight" kind) (some? min-grams) (not (< -1 min-grams max-grams)))
+    (tru "The minimum weight must be below the maximum.")
+
+    (and (= "free-above" kind) (not (pos-int? threshold)))
+    (tru "Free shipping needs a threshold above zero.")))
+
+(defn create-rate!
+  "Adds a rate to one of the tenant's zones. Returns its ID or an anomaly."
+  [shipping tenant-id zone-id {:keys [amount currency kind max-grams min-grams threshold] rate-name :name
+                               :as   rate}]
+  (span/with-span! {:name ::create-rate!}
+    (let [message (invalid-rate rate)]
+      (cond
+        message
+        (anom/incorrect {::anom/message message})
+
+        (not-any? #(= zone-id (:bits.postgres.shipping-zone/id %)) (list-zones shipping tenant-id))
+        (anom/not-found {::anom/message (tru "That zone doesn''t exist.")})
+
+        :else
+        (:bits.postgres.shipping-rate/id
+         (postgres/execute-one! (:postgres shipping)
+                                {:insert-into :shipping-rates
+                                 :values      [{:id        (random-uuid)
+                                                :zone-id   zone-id
+                                                :tenant-id tenant-id
+                                                :name      (str/trim rate-name)
+                                                :kind      kind
+                                                :amount    amount
+                                                :currency  currency
+                                                :min-grams (when (= "weight" kind) min-grams)
+                                                :max-grams (when (= "weight" kind) max-grams)
+                                                :threshold (when (= "free-above" kind) threshold)}]
+                                 :returning   [:id]}))))))
+
+(defn delete-rate!
+  [shipping tenant-id id]
+  (span/with-span! {:name ::delete-rate!}
+    (let [[{:keys [next.jdbc/update-count]}]
+          (postgres/execute! (:postgres shipping)
+                             {:delete-from :shipping-rates
+                              :where       [:and [:= :id id] [:= :tenant-id tenant-id]]})]
+      (pos? (or update-count 0)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Table carrier
+;;;
+;;; The rates tenants set up themselves.
+
+(defrecord Table [postgres]
+  Carrier
+  (rates [this tenant-id shipment]
+    (when-let [zone (zone-for (list-zones this tenant-id) (:region shipment))]
+      (for [rate  (list-rates this tenant-id)
+            :when (= (:bits.postgres.shipping-zone/id zone) (:bits.postgres.shipping-rate/zone-id rate))
+            :let  [amount (price rate shipment)]
+            :when (some? amount)]
+        {:id       (str "table:" (:bits.postgres.shipping-rate/id rate))
+         :name     (:bits.postgres.shipping-rate/name rate)
+         :amount   amount
+         :currency (:bits.postgres.shipping-rate/currency rate)}))))
+
+;;; ----------------------------------------------------------------------------
+;;; Options
+
+(defn options
+  "Every carrier's rates for the shipment, cheapest first."
+  [shipping tenant-id shipment]
+  (span/with-span! {:name ::options}
+    (->> (:carriers shipping)
+         (mapcat #(rates % tenant-id shipment))
+         (sort-by (juxt :amount :name))
+         vec)))
+
+(defn choose
+  "The option with the given ID, or the cheapest when there's no such option."
+  [options id]
+  (or (some #(when (= id (:id %)) %) options)
+      (first options)))
+
+;;; ----------------------------------------------------------------------------
+;;; Weight
+
+(defn grams
+  "What line items weigh, each a map of `:variant-id` and `:quantity`.
+  Variants without a weight, like downloads, weigh nothing."
+  [db line-items]
+  (transduce (map (fn [{:keys [quantity variant-id]}]
+                    (* (or quantity 1)
+                       (or (:variant/grams (d/entity db [:variant/id variant-id])) 0))))
+             +
+             0
+             line-items))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Shipping [carriers extra-carriers postgres]
+  component/Lifecycle
+  (start [this]
+    (assoc this :carriers (into [(->Table postgres)] extra-carriers)))
+  (stop [this]
+    (assoc this :carriers nil)))
+
+(defmethod print-method Shipping
+  [_ ^java.io.Writer w]
+  (.write w "#<Shipping>"))
+
+(defn make-shipping
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Shipping config))
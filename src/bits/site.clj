@@ -0,0 +1,317 @@
+(ns bits.site
+  "Static sites tenants upload as a zip.
+
+  Each upload becomes a release: its files are stored in the blob store by
+  content, so files unchanged since the last upload cost nothing, and listed
+  by path. A tenant serves one release at a time, and switching is an update
+  of one row, so visitors see the old site or the new one and never a mix.
+  The last few releases are kept to roll back to.
+
+  Files are served from the tenant's domain at their paths, wherever no page
+  of the app is, see `bits.module.site`. Uploads are held to a quota on the
+  total size of the files and how many there are."
+  (:require
+   [bits.anomaly :as anom]
+   [bits.blob :as blob]
+   [bits.locale :refer [tru]]
+   [bits.postgres :as postgres]
+   [bits.postgres.site]
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [clojure.string :as str]
+   [ring.util.mime-type :as mime-type]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.io ByteArrayOutputStream InputStream)
+   (java.time Duration Instant)
+   (java.util.zip ZipEntry ZipException ZipInputStream)))
+
+;;; ----------------------------------------------------------------------------
+;;; Paths
+
+(defn normalize-path
+  "The path an archive entry is served at, or nil for anything that could
+  escape the site or isn't a plain relative path."
+  [entry-name]
+  (let [segments (str/split (str entry-name) #"/")]
+    (when (and (not (str/blank? entry-name))
+               (not (str/starts-with? entry-name "/"))
+               (not (str/ends-with? entry-name "/"))
+               (not-any? #(contains? #{"" "." ".."} %) segments)
+               (not (re-find #"[\\\x00-\x1f\x7f]" entry-name)))
+      (str "/" entry-name))))
+
+(defn- strip-common-root
+  "Zipping a folder puts every file under the folder's name. When that's the
+  case, the folder is the site."
+  [files]
+  (let [roots (into #{} (map #(second (re-matches #"(/[^/]+)/.+" (:path %)))) files)]
+    (if (and (= 1 (count roots)) (some? (first roots)))
+      (mapv #(update % :path subs (count (first roots))) files)
+      files)))
+
+(defn candidates
+  "Paths that could answer a request for path, best first: the path itself,
+  then an index.html inside it or an .html file of that name."
+  [path]
+  (if (str/ends-with? path "/")
+    [(str path "index.html")]
+    [path (str path ".html") (str path "/index.html")]))
+
+;;; ----------------------------------------------------------------------------
+;;; Content types
+
+(def ^:private extra-mime-types
+  {"js"          "text/javascript"
+   "mjs"         "text/javascript"
+   "wasm"        "application/wasm"
+   "webmanifest" "application/manifest+json"})
+
+(defn content-type
+  [path]
+  (let [t (or (mime-type/ext-mime-type path extra-mime-types) "application/octet-stream")]
+    (if (or (str/starts-with? t "text/")
+            (contains? #{"application/json" "application/manifest+json" "image/svg+xml"} t))
+      (str t "; charset=utf-8")
+      t)))
+
+;;; ----------------------------------------------------------------------------
+;;; Archives
+
+(defn- read-entry
+  "The entry's bytes, or nil once remaining is used up."
+  [^InputStream in remaining]
+  (let [out (ByteArrayOutputStream.)
+        buf (byte-array 8192)]
+    (loop [total 0]
+      (let [n (.read in buf)]
+        (cond
+          (neg? n)                  (.toByteArray out)
+          (< remaining (+ total n)) nil
+          :else                     (do (.write out buf 0 n)
+                                        (recur (+ total n))))))))
+
+(def reasons
+  #{:duplicate-path :empty :not-a-zip :too-large :too-many-files :unsafe-path})
+
+(defn message
+  "Why an upload was rejected, for the tenant."
+  [{:keys [max-files quota-bytes]} reason]
+  (case reason
+    :duplicate-path (tru "Each path can only be in the zip once.")
+    :empty          (tru "That zip has no files in it.")
+    :not-a-zip      (tru "That isn''t a zip file.")
+    :too-large      (tru "Sites can be at most {0} MB." (quot quota-bytes (* 1024 1024)))
+    :too-many-files (tru "Sites can have at most {0} files." max-files)
+    :unsafe-path    (tru "Paths in the zip must be relative, without . or .. in them.")))
+
+(defn- rejected
+  [sites reason]
+  (anom/incorrect {::anom/message (message sites reason)
+                   ::reason       reason}))
+
+(defn read-zip
+  "The files in the zip as `{:path ... :bytes ...}`, or an anomaly with a
+  `::reason` when it isn't a zip, holds a path that can't be served or more
+  than one file at a path, or is over quota. Sizes are counted as the files are inflated, so a small archive
+  can't expand past the quota."
+  [{:keys [max-files quota-bytes] :as sites} ^InputStream in]
+  (span/with-span! {:name ::read-zip}
+    (try
+      (with-open [zip (ZipInputStream. in)]
+        (loop [files [] paths #{} total 0]
+          (if-let [^ZipEntry entry (.getNextEntry zip)]
+            (let [path (normalize-path (.getName entry))]
+              (cond
+                ;; Folders, and the resource forks macOS adds to its zips.
+                (or (.isDirectory entry) (str/starts-with? (.getName entry) "__MACOSX/"))
+                (recur files paths total)
+
+                (nil? path)
+                (rejected sites :unsafe-path)
+
+                (contains? paths path)
+                (rejected sites :duplicate-path)
+
+                (<= max-files (count files))
+                (rejected sites :too-many-files)
+
+                :else
+                (if-let [bs (read-entry zip (- quota-bytes total))]
+                  (recur (conj files {:path path :bytes bs})
+                         (conj paths path)
+                         (+ total (alength ^bytes bs)))
+                  (rejected sites :too-large))))
+            (if (empty? files)
+              (rejected sites :empty)
+              (strip-common-root files)))))
+      (catch ZipException _
+        (rejected sites :not-a-zip)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Releases
+;;;
+;;; A publish stores blobs before the files that refer to them, so a purge
+;;; running in between would find them unreferenced. Publishes share a lock
+;;; that purges take alone.
+
+(def ^:private blob-lock
+  "bits.site/blobs")
+
+(defn active-release-id
+  [sites tenant-id]
+  (:bits.postgres.site/release-id
+   (postgres/execute-one! (:postgres sites)
+                          {:select [:release-id]
+                           :from   [:sites]
+                           :where  [:= :tenant-id tenant-id]})))
+
+(defn releases
+  "The tenant's releases, newest first, each marked `:active?` when it's the
+  one being served."
+  [sites tenant-id]
+  (span/with-span! {:name ::releases}
+    (let [active (active-release-id sites tenant-id)]
+      (mapv (fn [{:bits.postgres.site-release/keys [created-at files id size]}]
+              {:active?    (= active id)
+               :created-at created-at
+               :files      files
+               :id         id
+               :size       size})
+            (postgres/execute! (:postgres sites)
+                               {:select   [:id :files :size :created-at]
+                                :from     [:site-releases]
+                                :where    [:= :tenant-id tenant-id]
+                                :order-by [[:created-at :desc] [:id :desc]]})))))
+
+(defn- switch!
+  [pg tenant-id release-id]
+  (postgres/execute! pg {:insert-into   :sites
+                         :values        [{:tenant-id tenant-id :release-id release-id}]
+                         :on-conflict   [:tenant-id]
+                         :do-update-set {:release-id  :excluded.release-id
+                                         :released-at [:now]}}))
+
+(defn- prune!
+  "Deletes all but the newest releases-kept releases, never the active one."
+  [pg tenant-id releases-kept]
+  (postgres/execute! pg {:delete-from :site-releases
+                         :where       [:and
+                                       [:= :tenant-id tenant-id]
+                                       [:not-in :id {:select [:release-id]
+                                                     :from   [:sites]
+                                                     :where  [:= :tenant-id tenant-id]}]
+                                       [:not-in :id {:select   [:id]
+                                                     :from     [:site-releases]
+                                                     :where    [:= :tenant-id tenant-id]
+                                                     :order-by [[:created-at :desc] [:id :desc]]
+                                                     :limit    releases-kept}]]}))
+
+(defn publish!
+  "Stores files, from `read-zip`, as a new release and serves it. Returns the
+  release's ID."
+  [sites tenant-id files]
+  {:pre [(seq files)]}
+  (span/with-span! {:name ::publish!}
+    (let [release-id (random-uuid)]
+      (postgres/with-transaction [tx (:postgres sites)]
+        (postgres/execute! tx {:select [[[:pg-advisory-xact-lock-shared [:hashtext blob-lock]]]]})
+        (let [blobs   (assoc (:blobs sites) :postgres tx)
+              digests (mapv #(blob/put! blobs (:bytes %)) files)]
+          (postgres/execute! tx {:insert-into :site-releases
+                                 :values      [{:id        release-id
+                                                :tenant-id tenant-id
+                                                :files     (count files)
+                                                :size      (transduce (map #(alength ^bytes (:bytes %))) + files)}]})
+          (postgres/execute! tx {:insert-into :site-files
+                                 :values      (mapv (fn [{:keys [path]} digest]
+                                                      {:release-id   release-id
+                                                       :path         path
+                                                       :digest       digest
+                                                       :content-type (content-type path)})
+                                                    files digests)})
+          (switch! tx tenant-id release-id)
+          (prune! tx tenant-id (:releases-kept sites))))
+      release-id)))
+
+(defn roll-back!
+  "Serves one of the tenant's earlier releases again."
+  [sites tenant-id release-id]
+  (span/with-span! {:name ::roll-back!}
+    (postgres/with-transaction [tx (:postgres sites)]
+      (if (postgres/execute-one! tx {:select [:id]
+                                     :from   [:site-releases]
+                                     :where  [:and [:= :id release-id] [:= :tenant-id tenant-id]]})
+        (do (switch! tx tenant-id release-id)
+            release-id)
+        (anom/not-found {::anom/message (tru "That release doesn''t exist.")})))))
+
+(defn take-down!
+  "Stops serving the tenant's site. Releases are kept to publish again."
+  [sites tenant-id]
+  (span/with-span! {:name ::take-down!}
+    (postgres/execute! (:postgres sites) {:delete-from :sites
+                                          :where       [:= :tenant-id tenant-id]})
+    nil))
+
+;;; ----------------------------------------------------------------------------
+;;; Serving
+
+(defn lookup
+  "The file of the tenant's active release that answers path, as
+  `{:path ... :digest ... :content-type ...}`, or nil."
+  [sites tenant-id path]
+  (span/with-span! {:name ::lookup}
+    (let [paths (candidates path)
+          found (into {}
+                      (map (fn [{:bits.postgres.site-file/keys [content-type digest path]}]
+                             [path {:content-type content-type :digest digest :path path}]))
+                      (postgres/execute! (postgres/replica (:postgres sites))
+                                         {:select [:f.path :f.digest :f.content-type]
+                                          :from   [[:sites :s]]
+                                          :join   [[:site-files :f] [:= :f.release-id :s.release-id]]
+                                          :where  [:and
+                                                   [:= :s.tenant-id tenant-id]
+                                                   [:in :f.path paths]]}))]
+      (some found paths))))
+
+(defn fetch
+  [sites digest]
+  (blob/fetch (:blobs sites) digest))
+
+;;; ----------------------------------------------------------------------------
+;;; Blobs
+
+(defn purge-blobs!
+  "Deletes blobs no release or plugin refers to any more. Blobs younger than
+  an hour are left for uploads still on their way in. Returns how many went."
+  [sites ^Instant now]
+  (span/with-span! {:name ::purge-blobs!}
+    (postgres/with-transaction [tx (:postgres sites)]
+      (postgres/execute! tx {:select [[[:pg-advisory-xact-lock [:hashtext blob-lock]]]]})
+      (let [[{:keys [next.jdbc/update-count]}]
+            (postgres/execute! tx
+                               {:delete-from :blobs
+                                :where       [:and
+                                              [:< :created-at (.minus now (Duration/ofHours 1))]
+                                              [:not [:exists {:select [1]
+                                                              :from   [:site-files]
+                                                              :where  [:= :site-files.digest :blobs.digest]}]]
+                                              [:not [:exists {:select [1]
+                                                              :from   [:plugins]
+                                                              :where  [:= :plugins.digest :blobs.digest]}]]]})]
+        (or update-count 0)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Sites [blobs max-files postgres quota-bytes releases-kept])
+
+(defmethod print-method Sites
+  [_ ^java.io.Writer w]
+  (.write w "#<Sites>"))
+
+(defn make-sites
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Sites config))
@@ -24,10 +24,11 @@
 
 (s/def :bits.cluster/bind-addr string?)
 (s/def :bits.cluster/bind-port pos-int?)
-(s/def :bits.cluster/cluster-name string?)
+(s/def :bits.cluster/cluster-name (s/and string? #(re-matches #"[A-Za-z0-9._-]{1,64}" %)))
 (s/def :bits.cluster/initial-hosts (s/coll-of #(instance? java.net.InetSocketAddress %) :kind set?))
 (s/def :bits.cluster/keystore-password string?)
 (s/def :bits.cluster/keystore-path string?)
+(s/def :bits.cluster/peer-name (s/and string? #(re-matches #"[A-Za-z0-9._-]{1,64}" %)))
 
 (s/def :bits.cluster/config
   (s/keys :req-un [:bits.cluster/bind-addr
@@ -35,7 +36,8 @@
                    :bits.cluster/cluster-name
                    :bits.cluster/initial-hosts
                    :bits.cluster/keystore-password
-                   :bits.cluster/keystore-path]))
+                   :bits.cluster/keystore-path]
+          :opt-un [:bits.cluster/peer-name]))
 
 ;;; ----------------------------------------------------------------------------
 ;;; Morph
@@ -46,14 +48,23 @@
 (s/def :bits.morph/event-id string?)
 (s/def :bits.morph/handler fn?)
 (s/def :bits.morph/params vector?)
+(s/def :bits.morph/transaction? boolean?)
 (s/def :bits.morph/action-map
   (s/keys :req-un [:bits.morph/handler]
-          :opt-un [:bits.morph/params]))
+          :opt-un [:bits.morph/params :bits.morph/transaction?]))
 (s/def :bits.morph/action
   (s/or :fn fn? :map :bits.morph/action-map))
 (s/def :bits.morph/actions
   (s/map-of qualified-keyword? :bits.morph/action))
 
+;;; ----------------------------------------------------------------------------
+;;; API keys
+
+(s/def :bits.auth.api-key/requests-per-minute pos-int?)
+
+(s/def :bits.auth.api-key/config
+  (s/keys :req-un [:bits.auth.api-key/requests-per-minute]))
+
 ;;; ----------------------------------------------------------------------------
 ;;; Rate limiter
 
@@ -102,9 +113,12 @@
 (s/def :bits.service/cookie-secure boolean?)
 (s/def :bits.service/csrf-cookie-name string?)
 (s/def :bits.service/csrf-secret string?)
+(s/def :bits.service/environment #{:development :staging :production})
 (s/def :bits.service/http-host string?)
 (s/def :bits.service/http-port (s/or :zero zero? :pos-int pos-int?))
+(s/def :bits.service/maintenance-allowlist (s/coll-of string? :kind set?))
 (s/def :bits.service/max-refresh-ms pos-int?)
+(s/def :bits.service/max-upload-bytes pos-int?)
 (s/def :bits.service/modules :bits.module/combined)
 (s/def :bits.service/platform-domain string?)
 (s/def :bits.service/realms (s/map-of qualified-keyword? :session/realm))
@@ -112,15 +126,34 @@
 (s/def :bits.service/server-name string?)
 (s/def :bits.service/sse-reconnect-ms pos-int?)
 
+;; What an operator configures, as opposed to what the started service holds.
+(s/def :bits.service/settings
+  (s/keys :req-un [:bits.service/cookie-name
+                   :bits.service/cookie-secure
+                   :bits.service/csrf-cookie-name
+                   :bits.service/csrf-secret
+                   :bits.service/environment
+                   :bits.service/http-host
+                   :bits.service/http-port
+                   :bits.service/maintenance-allowlist
+                   :bits.service/max-refresh-ms
+                   :bits.service/max-upload-bytes
+                   :bits.service/platform-domain
+                   :bits.service/server-name
+                   :bits.service/sse-reconnect-ms]))
+
 (s/def :bits.service/config
   (s/keys :req-un [:bits.service/actions
                    :bits.service/cookie-name
                    :bits.service/cookie-secure
                    :bits.service/csrf-cookie-name
                    :bits.service/csrf-secret
+                   :bits.service/environment
                    :bits.service/http-host
                    :bits.service/http-port
+                   :bits.service/maintenance-allowlist
                    :bits.service/max-refresh-ms
+                   :bits.service/max-upload-bytes
                    :bits.service/platform-domain
                    :bits.service/realms
                    :bits.service/routes
@@ -135,12 +168,35 @@
 (s/def :bits.datomic/config
   (s/keys :req-un [:bits.datomic/uri]))
 
+;;; ----------------------------------------------------------------------------
+;;; Events
+
+(s/def :bits.event/buffer-size pos-int?)
+
+(s/def :bits.event/config
+  (s/keys :req-un [:bits.event/buffer-size]))
+
 ;;; ----------------------------------------------------------------------------
 ;;; Crypto
 
+(defn- aes-256-key?
+  [s]
+  (try
+    (= 32 (alength (.decode (java.util.Base64/getDecoder) ^String s)))
+    (catch IllegalArgumentException _
+      false)))
+
+(s/def :bits.crypto/active-field-key string?)
 (s/def :bits.crypto/argon map?)
+(s/def :bits.crypto/field-keys
+  (s/map-of (s/and string? #(re-matches #"[A-Za-z0-9_-]+" %))
+            (s/and string? aes-256-key?)
+            :min-count 1))
 (s/def :bits.crypto/config
-  (s/keys :req-un [:bits.crypto/argon]))
+  (s/and (s/keys :req-un [:bits.crypto/active-field-key
+                          :bits.crypto/argon
+                          :bits.crypto/field-keys])
+         #(contains? (:field-keys %) (:active-field-key %))))
 
 ;;; ----------------------------------------------------------------------------
 ;;; Session
@@ -152,37 +208,424 @@
 ;;; ----------------------------------------------------------------------------
 ;;; Postgres
 
+(s/def :bits.postgres/connection-timeout-ms pos-int?)
+(s/def :bits.postgres/credentials-path (s/nilable string?))
 (s/def :bits.postgres/database-url string?)
+(s/def :bits.postgres/maximum-pool-size pos-int?)
+(s/def :bits.postgres/minimum-idle nat-int?)
+(s/def :bits.postgres/region (s/nilable string?))
+(s/def :bits.postgres/replica-url (s/nilable string?))
+(s/def :bits.postgres/regions
+  (s/map-of string? (s/keys :req-un [:bits.postgres/database-url]
+                            :opt-un [:bits.postgres/replica-url])))
+(s/def :bits.postgres/slow-query-ms pos-int?)
+(s/def :bits.postgres/statement-timeout-ms pos-int?)
 (s/def :bits.postgres/config
-  (s/keys :req-un [:bits.postgres/database-url]))
+  (s/keys :req-un [:bits.postgres/connection-timeout-ms
+                   :bits.postgres/database-url
+                   :bits.postgres/maximum-pool-size
+                   :bits.postgres/minimum-idle
+                   :bits.postgres/slow-query-ms
+                   :bits.postgres/statement-timeout-ms]
+          :opt-un [:bits.postgres/credentials-path
+                   :bits.postgres/region
+                   :bits.postgres/regions
+                   :bits.postgres/replica-url]))
+
+;;; ----------------------------------------------------------------------------
+;;; Flags
+
+(s/def :bits.flag/maximum-size pos-int?)
+(s/def :bits.flag/ttl-seconds pos-int?)
+
+(s/def :bits.flag/config
+  (s/keys :req-un [:bits.flag/maximum-size
+                   :bits.flag/ttl-seconds]))
+
+;;; ----------------------------------------------------------------------------
+;;; Leader
+
+(s/def :bits.leader/lock-name string?)
+(s/def :bits.leader/renew-seconds pos-int?)
+
+(s/def :bits.leader/config
+  (s/keys :req-un [:bits.leader/lock-name
+                   :bits.leader/renew-seconds]))
+
+;;; ----------------------------------------------------------------------------
+;;; Log tail
+
+(s/def :bits.log.tail/capacity pos-int?)
+(s/def :bits.log.tail/refresh-ms pos-int?)
+
+(s/def :bits.log.tail/config
+  (s/keys :req-un [:bits.log.tail/capacity
+                   :bits.log.tail/refresh-ms]))
+
+;;; ----------------------------------------------------------------------------
+;;; Outbox
+
+(s/def :bits.outbox/batch-size pos-int?)
+(s/def :bits.outbox/poll-seconds pos-int?)
+
+(s/def :bits.outbox/config
+  (s/keys :req-un [:bits.outbox/batch-size
+                   :bits.outbox/poll-seconds]))
+
+;;; ----------------------------------------------------------------------------
+;;; Projector
+
+(s/def :bits.projection/buffer-size pos-int?)
+
+(s/def :bits.projection/config
+  (s/keys :req-un [:bits.projection/buffer-size]))
+
+;;; ----------------------------------------------------------------------------
+;;; Retention
+
+(s/def :bits.retention.days/activities pos-int?)
+(s/def :bits.retention.days/api-key-requests pos-int?)
+(s/def :bits.retention.days/hourly-rollups pos-int?)
+(s/def :bits.retention.days/notifications pos-int?)
+(s/def :bits.retention.days/outbox pos-int?)
+(s/def :bits.retention.days/page-revisions pos-int?)
+(s/def :bits.retention.days/projected-events pos-int?)
+(s/def :bits.retention.days/webhook-deliveries pos-int?)
+
+(s/def :bits.retention/batch-size pos-int?)
+(s/def :bits.retention/days
+  (s/keys :req-un [:bits.retention.days/activities
+                   :bits.retention.days/api-key-requests
+                   :bits.retention.days/hourly-rollups
+                   :bits.retention.days/notifications
+                   :bits.retention.days/outbox
+                   :bits.retention.days/page-revisions
+                   :bits.retention.days/projected-events
+                   :bits.retention.days/webhook-deliveries]))
+
+(s/def :bits.retention/config
+  (s/keys :req-un [:bits.retention/batch-size
+                   :bits.retention/days]))
 
 ;;; ----------------------------------------------------------------------------
 ;;; Reaper
 
 (s/def :bits.reaper/interval-hours pos-int?)
+(s/def :bits.reaper/retention-days pos-int?)
 (s/def :bits.reaper/config
-  (s/keys :req-un [:bits.reaper/interval-hours]))
+  (s/keys :req-un [:bits.reaper/interval-hours
+                   :bits.reaper/retention-days]))
+
+;;; ----------------------------------------------------------------------------
+;;; Realm
+
+(s/def :bits.realm/maximum-size pos-int?)
+(s/def :bits.realm/ttl-seconds pos-int?)
+
+(s/def :bits.realm/config
+  (s/keys :req-un [:bits.realm/maximum-size
+                   :bits.realm/ttl-seconds]))
+
+;;; ----------------------------------------------------------------------------
+;;; Secrets
+
+(s/def :bits.secret/provider #{:aws-secrets-manager :env :vault})
+(s/def :bits.secret/variables (s/map-of string? string?))
+
+(s/def :bits.secret.aws/access-key-id string?)
+(s/def :bits.secret.aws/region string?)
+(s/def :bits.secret.aws/secret-access-key string?)
+(s/def :bits.secret.aws/session-token (s/nilable string?))
+(s/def :bits.secret/aws
+  (s/keys :req-un [:bits.secret.aws/access-key-id
+                   :bits.secret.aws/region
+                   :bits.secret.aws/secret-access-key]
+          :opt-un [:bits.secret.aws/session-token]))
+
+(s/def :bits.secret.vault/address string?)
+(s/def :bits.secret.vault/token string?)
+(s/def :bits.secret/vault
+  (s/keys :req-un [:bits.secret.vault/address
+                   :bits.secret.vault/token]))
+
+(defmulti secret-provider :provider)
+(defmethod secret-provider :aws-secrets-manager [_] (s/keys :req-un [:bits.secret/provider :bits.secret/aws]))
+(defmethod secret-provider :env [_] (s/keys :req-un [:bits.secret/provider :bits.secret/variables]))
+(defmethod secret-provider :vault [_] (s/keys :req-un [:bits.secret/provider :bits.secret/vault]))
+
+(s/def :bits.secret/config
+  (s/multi-spec secret-provider :provider))
+
+;;; ----------------------------------------------------------------------------
+;;; Webhooks
+
+(s/def :bits.webhook/backoff-base-seconds pos-int?)
+(s/def :bits.webhook/batch-size pos-int?)
+(s/def :bits.webhook/max-attempts pos-int?)
+(s/def :bits.webhook/poll-seconds pos-int?)
+(s/def :bits.webhook/timeout-ms pos-int?)
+
+(s/def :bits.webhook/config
+  (s/keys :req-un [:bits.webhook/backoff-base-seconds
+                   :bits.webhook/batch-size
+                   :bits.webhook/max-attempts
+                   :bits.webhook/poll-seconds
+                   :bits.webhook/timeout-ms]))
+
+;;; ----------------------------------------------------------------------------
+;;; Shadow
+
+(s/def :bits.shadow/csp (s/map-of keyword? string?))
+(s/def :bits.shadow/rate-limits
+  (s/keys :opt-un [:bits.auth.rate-limit/email-max-attempts
+                   :bits.auth.rate-limit/email-window-minutes
+                   :bits.auth.rate-limit/ip-max-attempts
+                   :bits.auth.rate-limit/ip-window-minutes]))
+
+(s/def :bits.shadow/candidates
+  (s/keys :opt-un [:bits.shadow/csp
+                   :bits.shadow/rate-limits]))
+
+(s/def :bits.shadow/config
+  (s/keys :req-un [:bits.shadow/candidates]))
+
+;;; ----------------------------------------------------------------------------
+;;; Comments
+
+(s/def :bits.comment/max-per-window pos-int?)
+(s/def :bits.comment/window-minutes pos-int?)
+
+(s/def :bits.comment/config
+  (s/keys :req-un [:bits.comment/max-per-window
+                   :bits.comment/window-minutes]))
+
+;;; ----------------------------------------------------------------------------
+;;; Plugins
+
+(s/def :bits.plugin/max-bytes pos-int?)
+(s/def :bits.plugin/max-memory-pages pos-int?)
+(s/def :bits.plugin/max-output-bytes pos-int?)
+(s/def :bits.plugin/maximum-size pos-int?)
+(s/def :bits.plugin/timeout-ms pos-int?)
+(s/def :bits.plugin/ttl-seconds pos-int?)
+
+(s/def :bits.plugin/config
+  (s/keys :req-un [:bits.plugin/max-bytes
+                   :bits.plugin/max-memory-pages
+                   :bits.plugin/max-output-bytes
+                   :bits.plugin/maximum-size
+                   :bits.plugin/timeout-ms
+                   :bits.plugin/ttl-seconds]))
+
+;;; ----------------------------------------------------------------------------
+;;; Pricing
+
+(s/def :bits.pricing/max-steps pos-int?)
+(s/def :bits.pricing/maximum-size pos-int?)
+(s/def :bits.pricing/timeout-ms pos-int?)
+(s/def :bits.pricing/ttl-seconds pos-int?)
+
+(s/def :bits.pricing/config
+  (s/keys :req-un [:bits.pricing/max-steps
+                   :bits.pricing/maximum-size
+                   :bits.pricing/timeout-ms
+                   :bits.pricing/ttl-seconds]))
+
+;;; ----------------------------------------------------------------------------
+;;; Redirects
+
+(s/def :bits.redirect/maximum-size pos-int?)
+(s/def :bits.redirect/ttl-seconds pos-int?)
+
+(s/def :bits.redirect/config
+  (s/keys :req-un [:bits.redirect/maximum-size
+                   :bits.redirect/ttl-seconds]))
+
+;;; ----------------------------------------------------------------------------
+;;; Scheduler
+
+(s/def :bits.schedule/poll-seconds pos-int?)
+
+(s/def :bits.schedule/config
+  (s/keys :req-un [:bits.schedule/poll-seconds]))
+
+;;; ----------------------------------------------------------------------------
+;;; Sites
+
+(s/def :bits.blob/config (s/keys))
+
+(s/def :bits.site/max-files pos-int?)
+(s/def :bits.site/quota-bytes pos-int?)
+(s/def :bits.site/releases-kept pos-int?)
+
+(s/def :bits.site/config
+  (s/keys :req-un [:bits.site/max-files
+                   :bits.site/quota-bytes
+                   :bits.site/releases-kept]))
+
+;;; ----------------------------------------------------------------------------
+;;; Clock
+
+(s/def :bits.clock/config (s/keys))
+
+;;; ----------------------------------------------------------------------------
+;;; Notifications
+
+(s/def :bits.notification/buffer-size pos-int?)
+
+(s/def :bits.notification/config
+  (s/keys :req-un [:bits.notification/buffer-size]))
+
+;;; ----------------------------------------------------------------------------
+;;; Activity
+
+(s/def :bits.activity/config (s/keys))
+
+;;; ----------------------------------------------------------------------------
+;;; OIDC
+
+(s/def :bits.auth.oidc/connect-timeout-ms pos-int?)
+(s/def :bits.auth.oidc/maximum-size pos-int?)
+(s/def :bits.auth.oidc/ttl-seconds pos-int?)
+
+(s/def :bits.auth.oidc/config
+  (s/keys :req-un [:bits.auth.oidc/connect-timeout-ms
+                   :bits.auth.oidc/maximum-size
+                   :bits.auth.oidc/ttl-seconds]))
+
+;;; ----------------------------------------------------------------------------
+;;; Exports
+
+(s/def :bits.export/config (s/keys))
+
+;;; ----------------------------------------------------------------------------
+;;; Reviews
+
+(s/def :bits.review/max-body pos-int?)
+
+(s/def :bits.review/config
+  (s/keys :req-un [:bits.review/max-body]))
+
+;;; ----------------------------------------------------------------------------
+;;; Moderation
+
+(s/def :bits.moderation/config (s/keys))
+
+;;; ----------------------------------------------------------------------------
+;;; Caches
+
+(s/def :bits.cache/maximum-size pos-int?)
+(s/def :bits.cache/ttl-seconds pos-int?)
+
+(s/def :bits.cache/config
+  (s/keys :req-un [:bits.cache/maximum-size
+                   :bits.cache/ttl-seconds]))
+
+;;; ----------------------------------------------------------------------------
+;;; Discounts
+
+(s/def :bits.discount/config (s/keys))
+
+;;; ----------------------------------------------------------------------------
+;;; Shipping
+
+(s/def :bits.shipping/extra-carriers sequential?)
+
+(s/def :bits.shipping/config
+  (s/nilable (s/keys :opt-un [:bits.shipping/extra-carriers])))
+
+;;; ----------------------------------------------------------------------------
+;;; Wishlists
+
+(s/def :bits.wishlist/config (s/keys))
+
+;;; ----------------------------------------------------------------------------
+;;; Analytics
+
+(s/def :bits.analytics/maximum-size pos-int?)
+(s/def :bits.analytics/views-per-minute pos-int?)
+
+(s/def :bits.analytics/config
+  (s/keys :req-un [:bits.analytics/maximum-size
+                   :bits.analytics/views-per-minute]))
 
 ;;; ----------------------------------------------------------------------------
 ;;; System
+(s/def :bits.system/activities :bits.activity/config)
+(s/def :bits.system/analytics :bits.analytics/config)
+(s/def :bits.system/api-keys :bits.auth.api-key/config)
+(s/def :bits.system/blobs :bits.blob/config)
 (s/def :bits.system/buster :bits.asset/config)
+(s/def :bits.system/clock :bits.clock/config)
 (s/def :bits.system/cluster :bits.cluster/config)
+(s/def :bits.system/comments :bits.comment/config)
 (s/def :bits.system/datomic :bits.datomic/config)
+(s/def :bits.system/discounts :bits.discount/config)
+(s/def :bits.system/events :bits.event/config)
+(s/def :bits.system/exports :bits.export/config)
+(s/def :bits.system/flags :bits.flag/config)
 (s/def :bits.system/keymaster :bits.crypto/config)
+(s/def :bits.system/leader :bits.leader/config)
+(s/def :bits.system/log-tail :bits.log.tail/config)
+(s/def :bits.system/moderator :bits.moderation/config)
+(s/def :bits.system/notifications :bits.notification/config)
+(s/def :bits.system/oidc :bits.auth.oidc/config)
+(s/def :bits.system/outbox :bits.outbox/config)
+(s/def :bits.system/plugins :bits.plugin/config)
 (s/def :bits.system/postgres :bits.postgres/config)
+(s/def :bits.system/pricing :bits.pricing/config)
+(s/def :bits.system/projector :bits.projection/config)
 (s/def :bits.system/rate-limiter :bits.auth.rate-limit/config)
 (s/def :bits.system/reaper :bits.reaper/config)
-(s/def :bits.system/service :bits.service/config)
+(s/def :bits.system/redirects :bits.redirect/config)
+(s/def :bits.system/resolver :bits.realm/config)
+(s/def :bits.system/retention :bits.retention/config)
+(s/def :bits.system/reviews :bits.review/config)
+(s/def :bits.system/scheduler :bits.schedule/config)
+(s/def :bits.system/secrets :bits.secret/config)
+(s/def :bits.system/service :bits.service/settings)
 (s/def :bits.system/session-store :bits.session/config)
+(s/def :bits.system/shadow :bits.shadow/config)
+(s/def :bits.system/sites :bits.site/config)
+(s/def :bits.system/webhooks :bits.webhook/config)
+(s/def :bits.system/wishlists :bits.wishlist/config)
 
 (s/def :bits.system/config
-  (s/keys :req-un [:bits.system/buster
+  (s/keys :req-un [:bits.system/activities
+                   :bits.system/analytics
+                   :bits.system/api-keys
+                   :bits.system/blobs
+                   :bits.system/buster
+                   :bits.system/clock
                    :bits.system/cluster
+                   :bits.system/comments
                    :bits.system/datomic
+                   :bits.system/discounts
+                   :bits.system/events
+                   :bits.system/exports
+                   :bits.system/flags
                    :bits.system/keymaster
+                   :bits.system/leader
+                   :bits.system/log-tail
+                   :bits.system/moderator
+                   :bits.system/notifications
+                   :bits.system/oidc
+                   :bits.system/outbox
+                   :bits.system/plugins
                    :bits.system/postgres
+                   :bits.system/pricing
+                   :bits.system/projector
                    :bits.system/rate-limiter
                    :bits.system/reaper
+                   :bits.system/redirects
+                   :bits.system/resolver
+                   :bits.system/retention
+                   :bits.system/reviews
+                   :bits.system/scheduler
+                   :bits.system/secrets
                    :bits.system/service
                    :bits.system/session-store
-                   :bits.system/translator]))
+                   :bits.system/shadow
+                   :bits.system/sites
+                   :bits.system/webhooks
+                   :bits.system/wishlists]))
@@ -1,10 +1,16 @@
 (ns bits.ui
   (:require
    [bits.asset :as asset]
+   [bits.environment :as environment]
    [bits.form :as form]
    [bits.locale :refer [tru]]
+   [bits.meta :as meta]
    [bits.middleware :as mw]
-   [bits.tailwind :as tw]))
+   [bits.morph :as morph]
+   [bits.notification :as notification]
+   [bits.presence :as presence]
+   [bits.tailwind :as tw]
+   [clojure.string :as str]))
 
 ;;; ----------------------------------------------------------------------------
 ;;; Input classes
@@ -109,6 +115,37 @@
    ["/form"      (tru "Forms")]
    ["/redirect"  (tru "Redirect")]])
 
+(def ^:private bell-icon-path
+  (str "M14.857 17.082a23.848 23.848 0 0 0 5.454-1.31A8.967 8.967 0 0 1 18 9.75V9"
+       "A6 6 0 0 0 6 9v.75a8.967 8.967 0 0 1-2.312 6.022c1.733.64 3.56 1.085 5.455 1.31"
+       "m5.714 0a24.255 24.255 0 0 1-5.714 0m5.714 0a3 3 0 1 1-5.714 0"))
+
+(defn- notification-bell
+  [request current-path]
+  (let [unread (notification/unread-count (mw/request->notifications request)
+                                          (get-in request [:session/realm :tenant/id])
+                                          (get-in request [:session/user :user/id]))]
+    [:a {:href       "/notifications"
+         :aria-label (tru "Notifications")
+         :class      (into ["relative" "flex" "items-center"]
+                           (if (= "/notifications" current-path)
+                             ["text-accent"]
+                             ["text-secondary" "hover:text-primary"]))}
+     [:svg {:viewBox      "0 0 24 24"
+            :fill         "none"
+            :stroke       "currentColor"
+            :stroke-width "1.5"
+            :class        ["size-5"]
+            :aria-hidden  "true"}
+      [:path {:d               bell-icon-path
+              :stroke-linecap  "round"
+              :stroke-linejoin "round"}]]
+     (when (pos? unread)
+       [:span {:class ["absolute" "-top-1.5" "-right-2" "min-w-4" "px-1"
+                       "rounded-full" "bg-accent" "text-center"
+                       "text-[0.625rem]" "font-semibold" "text-surface"]}
+        (if (< unread 100) unread "99+")])]))
+
 (defn nav-header
   [request current-path]
   (let [user       (:session/user request)
@@ -123,19 +160,48 @@
         [:a {:href  path
              :class (link-class path)}
          label])]
-     [:div {:class ["p-4"]}
+     [:div {:class ["flex" "items-center" "gap-4" "p-4"]}
       (if (:user/id user)
-        (form/action-button :auth/sign-out
-          {:class ["text-sm"
-                   "font-medium"
-                   "text-secondary"
-                   "hover:text-primary"
-                   "cursor-pointer"]}
-          (tru "Sign out"))
+        (list
+         (notification-bell request current-path)
+         (form/action-button :auth/sign-out
+           {:class ["text-sm"
+                    "font-medium"
+                    "text-secondary"
+                    "hover:text-primary"
+                    "cursor-pointer"]}
+           (tru "Sign out")))
         [:a {:href  "/login"
              :class (link-class "/login")}
          (tru "Login")])]]))
 
+;;; ----------------------------------------------------------------------------
+;;; Environment banner
+;;;
+;;; Anything that isn't the real thing says so, so nobody takes a staging
+;;; deployment or a demo storefront for production. bits.js hides it for the
+;;; rest of the session once dismissed.
+
+(defn environment-banner
+  [request]
+  (let [env   (mw/request->environment request)
+        realm (:session/realm request)]
+    (when-not (environment/public? env realm)
+      [:div {:id    "environment-banner"
+             :role  "status"
+             :class ["flex" "items-center" "justify-center" "gap-4" "px-4" "py-2"
+                     "bg-amber-500/15" "text-sm" "text-amber-200"
+                     "border-b" "border-amber-500/25"]}
+       [:span
+        (cond
+          (:tenant/demo? realm) (tru "This is a demo storefront. Nothing here is real.")
+          (= :staging env)      (tru "This is staging, not production.")
+          :else                 (tru "This is a development server, not production."))]
+       [:button {:type       "button"
+                 :aria-label (tru "Dismiss")
+                 :class      ["rounded-md" "px-2" "hover:bg-amber-500/25"]}
+        "×"]])))
+
 ;;; ----------------------------------------------------------------------------
 ;;; Layout
 
@@ -187,6 +253,29 @@
   (into [:p {:class ["text-sm" "text-success"]}]
         children))
 
+;;; ----------------------------------------------------------------------------
+;;; Presence
+;;;
+;;; Initials of the other admins with the page at path open. The path is
+;;; passed in because actions render views from their own URI. Views that show
+;;; this should be morphable with `:presence? true` so badges come and go.
+
+(defn presence
+  [request path]
+  (let [user-ids (presence/others (some-> (::morph/channels request) deref)
+                                  {:path      path
+                                   :tenant-id (get-in request [:session/realm :tenant/id])
+                                   :user-id   (get-in request [:session/user :user/id])})]
+    (when-let [emails (seq (presence/emails (mw/request->db request) user-ids))]
+      [:div {:class ["flex" "items-center" "gap-2" "text-xs" "text-muted"]}
+       [:span (tru "Also here")]
+       (for [email emails]
+         [:span {:title      email
+                 :aria-label email
+                 :class      ["flex" "items-center" "justify-center" "size-6"
+                              "rounded-full" "bg-surface-hover" "text-accent" "font-semibold"]}
+          (str/upper-case (subs email 0 1))])])))
+
 ;;; ----------------------------------------------------------------------------
 ;;; Icon buttons
 
@@ -227,6 +316,7 @@
       [:meta {:name "viewport" :content "width=device-width, initial-scale=1.0"}]
       [:meta {:name "csrf-cookie" :content csrf-cookie-name}]
       [:title title]
+      (meta/tags request)
       [:link {:rel "icon" :href (asset-path "/favicon.ico") :sizes "any"}]
       [:link {:rel "icon" :type "image/svg+xml" :href (asset-path "/favicon.svg")}]
       [:link {:rel "apple-touch-icon" :href (asset-path "/apple-touch-icon.png")}]
@@ -234,6 +324,7 @@
       [:script {:src (asset-path "/idiomorph@0.7.4.min.js") :defer true}]
       [:script {:src (asset-path "/bits.js") :defer true}]]
      [:body {:class ["min-h-screen" "bg-surface" "text-primary" "font-sans"]}
+      (environment-banner request)
       (into [:main#morph (cond-> {:class ["min-h-screen" "flex" "flex-col"]}
                            (:bits.morph/event-id request)
                            (assoc :data-event-id (:bits.morph/event-id request)))]
@@ -0,0 +1,235 @@
+(ns bits.variant
+  "Options, like size and colour, and the variants buyers pick between.
+
+  A product's options span a matrix: every way of choosing one value of each
+  option. An active variant sits at one combination and carries its own SKU,
+  price and stock. Combinations are unique among active variants, so a
+  selection always names at most one thing to buy. Sold variants never change;
+  to reprice one, deactivate it and create another at the same combination.
+
+  Every write bumps the product's version, so two admins adding the same
+  combination at once can't both succeed."
+  (:require
+   [bits.anomaly :as anom]
+   [bits.entity]
+   [bits.locale :refer [tru]]
+   [bits.version :as version]
+   [clojure.string :as str]
+   [datomic.api :as d]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Reading
+
+(defn options
+  "The product's options in order, each with its values in order."
+  [product]
+  (->> (:product/options product)
+       (sort-by :option/position)
+       (mapv (fn [option]
+               {:option/id     (:option/id option)
+                :option/name   (:option/name option)
+                :option/values (->> (:option/values option)
+                                    (sort-by :option-value/position)
+                                    (mapv #(select-keys % [:option-value/id :option-value/label])))}))))
+
+(defn combination
+  "The set of option value IDs the variant sits at."
+  [variant]
+  (into #{} (map :option-value/id) (:variant/option-values variant)))
+
+(defn active-variants
+  [product]
+  (filter :variant/active? (:product/variants product)))
+
+(defn select-variant
+  "The active variant at the combination of option value IDs, or nil."
+  [product value-ids]
+  (let [wanted (set value-ids)]
+    (some #(when (= wanted (combination %)) %) (active-variants product))))
+
+(defn- combinations
+  [value-lists]
+  (reduce (fn [combos values]
+            (for [combo combos
+                  value values]
+              (conj combo value)))
+          [[]]
+          value-lists))
+
+(defn matrix
+  "Every combination of the product's option values, in order, with the active
+  variant at each or nil where there isn't one."
+  [product]
+  (let [by-combination (into {} (map (juxt combination identity)) (active-variants product))
+        value-lists    (map :option/values (options product))]
+    (if (empty? value-lists)
+      (mapv (fn [v] {:values [] :variant v}) (take 1 (active-variants product)))
+      (mapv (fn [values]
+              {:values  values
+               :variant (get by-combination (into #{} (map :option-value/id) values))})
+            (combinations value-lists)))))
+
+(defn sold
+  "Units of the variant bought so far."
+  [db variant-id]
+  (or (d/q '[:find (sum ?quantity) .
+             :with ?li
+             :in $ ?variant-id
+             :where
+             [?v :variant/id ?variant-id]
+             [?li :line-item/variant ?v]
+             [?li :line-item/quantity ?quantity]]
+           db variant-id)
+      0))
+
+(defn stock
+  "Units of the variant left to sell, or nil when it's unlimited."
+  [db variant]
+  (when-let [limit (:variant/quantity-limit variant)]
+    (max 0 (- limit (sold db (:variant/id variant))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Options
+
+(defn- invalid-option
+  [product {option-name :name :keys [values]}]
+  (let [labels (map str/trim values)]
+    (cond
+      (not (bits.entity/present? option-name))
+      (tru "Options need a name.")
+
+      (some #(= (str/lower-case (str/trim option-name)) (str/lower-case (:option/name %)))
+            (:product/options product))
+      (tru "There''s already an option called {0}." (str/trim option-name))
+
+      (or (empty? labels) (some str/blank? labels))
+      (tru "Options need at least one value, and values can''t be blank.")
+
+      (not (apply distinct? (map str/lower-case labels)))
+      (tru "Each value of an option must be different.")
+
+      (seq (active-variants product))
+      (tru "Deactivate this product''s variants before adding an option, as they''d have no value for it."))))
+
+(defn add-option!
+  "Adds an option with its values after the product's others, when the product
+  is still at the expected version. Returns the option's ID or an anomaly."
+  [conn product expected {option-name :name :keys [values] :as option}]
+  (span/with-span! {:name ::add-option!}
+    (if-let [message (invalid-option product option)]
+      (anom/incorrect {::anom/message message})
+      (let [id     (random-uuid)
+            result (version/transact! conn
+                                      (conj (version/bump-tx product :product/version expected)
+                                            {:db/id           (:db/id product)
+                                             :product/options [{:db/ensure       :option/ensure
+                                                                :option/id       id
+                                                                :option/name     (str/trim option-name)
+                                                                :option/position (inc (count (:product/options product)))
+                                                                :option/values   (vec (map-indexed
+                                                                                       (fn [i label]
+                                                                                         {:db/ensure             :option-value/ensure
+                                                                                          :option-value/id       (random-uuid)
+                                                                                          :option-value/label    (str/trim label)
+                                                                                          :option-value/position (inc i)})
+                                                                                       values))}]}))]
+        (if (anom/anomaly? result)
+          (dissoc result ::version/current)
+          id)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Variants
+
+(def ^:private currencies
+  #{"EUR" "GBP" "USD"})
+
+(def ^:private types
+  #{"digital" "physical"})
+
+(defn- invalid-combination
+  "Checks the values pick exactly one of each of the product's options."
+  [product value-ids]
+  (let [option-of (into {}
+                        (for [option (:product/options product)
+                              value  (:option/values option)]
+                          [(:option-value/id value) (:option/id option)]))
+        picked    (map option-of value-ids)]
+    (cond
+      (some nil? picked)
+      (tru "Pick values from this product''s options.")
+
+      (not= (count (:product/options product)) (count (set picked)) (count value-ids))
+      (tru "Pick one value of every option.")
+
+      (select-variant product value-ids)
+      (tru "Another variant already has these options."))))
+
+(defn- invalid-variant
+  [db product {variant-name :name :keys [currency option-values price quantity-limit sku type]}]
+  (cond
+    (not (bits.entity/present? variant-name))
+    (tru "Variants need a name.")
+
+    (not (bits.entity/present? sku))
+    (tru "Variants need a SKU.")
+
+    (d/entid db [:sku/code (str/trim sku)])
+    (tru "SKU {0} is already in use." (str/trim sku))
+
+    (not (pos-int? price))
+    (tru "Price must be more than zero.")
+
+    (not (contains? currencies currency))
+    (tru "Currency must be GBP, USD or EUR.")
+
+    (not (contains? types type))
+    (tru "Type must be digital or physical.")
+
+    (and (some? quantity-limit) (not (pos-int? quantity-limit)))
+    (tru "Stock must be a positive whole number, or left out for unlimited.")
+
+    :else
+    (invalid-combination product option-values)))
+
+(defn create!
+  "Adds an active variant at the combination of option value IDs, when the
+  product is still at the expected version. Returns the variant's ID or an
+  anomaly."
+  [conn product expected {variant-name :name :keys [currency option-values price quantity-limit sku type]
+                          :as          variant}
+   now]
+  (span/with-span! {:name ::create!}
+    (if-let [message (invalid-variant (d/entity-db product) product variant)]
+      (anom/incorrect {::anom/message message})
+      (let [id     (random-uuid)
+            result (version/transact! conn
+                                      (conj (version/bump-tx product :product/version expected)
+                                            {:db/id            (:db/id product)
+                                             :product/variants [(cond-> {:db/ensure             :variant/ensure
+                                                                         :variant/id            id
+                                                                         :variant/name          (str/trim variant-name)
+                                                                         :variant/type          (keyword "variant.type" type)
+                                                                         :variant/active?       true
+                                                                         :variant/created-at    now
+                                                                         :variant/sku           {:sku/code (str/trim sku)}
+                                                                         :variant/price         {:money/amount   price
+                                                                                                 :money/currency (keyword "currency" currency)}
+                                                                         :variant/option-values (mapv #(vector :option-value/id %) option-values)}
+                                                                  quantity-limit (assoc :variant/quantity-limit quantity-limit))]}))]
+        (if (anom/anomaly? result)
+          (dissoc result ::version/current)
+          id)))))
+
+(defn deactivate!
+  "Stops the variant being sold, freeing its combination for another."
+  [conn product expected variant-id]
+  (span/with-span! {:name ::deactivate!}
+    (if-let [variant (some #(when (= variant-id (:variant/id %)) %) (:product/variants product))]
+      (let [result (version/transact! conn
+                                      (conj (version/bump-tx product :product/version expected)
+                                            [:db/add (:db/id variant) :variant/active? false]))]
+        (if (anom/anomaly? result)
+          (dissoc result ::version/current)
+          variant-id))
+      (anom/not-found {::anom/message (tru "This product has no such variant.")}))))
@@ -0,0 +1,60 @@
+(ns bits.version
+  "Optimistic concurrency for mutable Datomic entities.
+
+  Editable entities carry a version attribute that every edit bumps with
+  `:db/cas`, so an edit based on a stale read fails instead of clobbering
+  whatever changed in between. HTTP callers send the version they read in
+  `If-Match`, as handed out in `ETag`; forms carry it in a hidden `version`
+  field."
+  (:require
+   [bits.anomaly :as anom]
+   [bits.locale :refer [tru]]
+   [clojure.string :as str]
+   [datomic.api :as d]))
+
+;;; ----------------------------------------------------------------------------
+;;; Versions
+
+(defn version
+  "The entity's version. Entities that were never edited are at 0."
+  [entity attr]
+  (get entity attr 0))
+
+(defn etag
+  [version]
+  (str "\"" version "\""))
+
+(defn parse-etag
+  "Reads the version out of an `If-Match` header. Weak validators are accepted
+  since versions are exact either way."
+  [s]
+  (some->> (some-> s str/trim)
+           (re-matches #"(?:W/)?\"(\d+)\"")
+           second
+           parse-long))
+
+;;; ----------------------------------------------------------------------------
+;;; Transactions
+
+(defn bump-tx
+  "Moves the entity from the expected version to the next one, failing the
+  whole transaction when someone else got there first."
+  [entity attr expected]
+  [[:db/cas (:db/id entity) attr (when (pos? expected) expected) (inc expected)]])
+
+(defn- cas-failed?
+  [ex]
+  (some #(= :db.error/cas-failed (:db/error (ex-data %)))
+        (take-while some? (iterate ex-cause ex))))
+
+(defn transact!
+  "Transacts the versioned edit. Returns the transaction report, or a conflict
+  anomaly carrying the `::current` database when the version was stale."
+  [conn tx]
+  (try
+    @(d/transact conn tx)
+    (catch Exception ex
+      (if (cas-failed? ex)
+        (anom/conflict {::anom/message (tru "This was changed by someone else. Review their changes and try again.")
+                        ::current      (d/db conn)})
+        (throw ex)))))
@@ -0,0 +1,359 @@
+(ns bits.webhook
+  (:require
+   [bits.clock :as clock]
+   [bits.cryptex :as cryptex]
+   [bits.crypto :as crypto]
+   [bits.egress :as egress]
+   [bits.identifier :as identifier]
+   [bits.pagination :as pagination]
+   [bits.postgres :as postgres]
+   [bits.postgres.webhook :as postgres.webhook]
+   [bits.spec]
+   [buddy.core.codecs :as codecs]
+   [buddy.core.mac :as mac]
+   [charred.api :as json]
+   [clojure.spec.alpha :as s]
+   [com.stuartsierra.component :as component]
+   [hato.client :as http]
+   [io.pedestal.log :as log]
+   [steffan-westcott.clj-otel.api.trace.span :as span])
+  (:import
+   (java.time OffsetDateTime)
+   (java.util.concurrent Executors ScheduledExecutorService TimeUnit)))
+
+;;; ----------------------------------------------------------------------------
+;;; Events
+
+(def events
+  #{"domain.verified"
+    "member.created"
+    "order.created"
+    "page.published"
+    "page.unpublished"
+    "product.published"
+    "product.unpublished"})
+
+;;; ----------------------------------------------------------------------------
+;;; Signing
+;;;
+;;; Receivers verify a delivery by computing an HMAC-SHA256 over
+;;; `<timestamp>.<body>` with their endpoint secret and comparing it to the `v1`
+;;; value of the `bits-signature` header. The timestamp lets them reject
+;;; replays.
+
+(def ^:const signature-header "bits-signature")
+
+(def ^:private secret-context
+  "webhook_endpoints.secret")
+
+(defn signature
+  [secret timestamp body]
+  (span/with-span! {:name ::signature}
+    (let [digest (mac/hash (str timestamp "." body) {:key secret :alg :hmac+sha256})]
+      (format "t=%d,v1=%s" timestamp (codecs/bytes->hex digest)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Endpoints
+
+(defn register!
+  "Returns the signing secret wrapped in a cryptex alongside the endpoint ID."
+  [dispatcher {:keys [tenant-id url] endpoint-events :events}]
+  {:pre [(seq endpoint-events) (every? events endpoint-events)]}
+  (let [{:keys [keymaster postgres randomizer]} dispatcher
+        id                                      (random-uuid)
+        secret                                  (str "whsec_" (codecs/bytes->hex
+                                                               (crypto/random-bytes randomizer 24)))]
+    (span/with-span! {:name ::register!}
+      (postgres/execute-one! postgres
+                             {:insert-into :webhook-endpoints
+                              :values      [{:id        id
+                                             :tenant-id tenant-id
+                                             :url       url
+                                             :secret    (crypto/seal keymaster secret-context secret)
+                                             :events    [:array (vec (sort endpoint-events)) :text]}]})
+      {::id     id
+       ::secret (cryptex/cryptex secret)})))
+
+(defn disable!
+  "Disables the endpoint and dead-letters anything still waiting to be sent to
+  it."
+  [dispatcher tenant-id id]
+  (span/with-span! {:name ::disable!}
+    (let [now (clock/now (:clock dispatcher))]
+      (postgres/with-transaction [tx (:postgres dispatcher)]
+        (let [[{:keys [next.jdbc/update-count]}]
+              (postgres/execute! tx
+                                 {:update :webhook-endpoints
+                                  :set    {:disabled-at now}
+                                  :where  [:and
+                                           [:= :id id]
+                                           [:= :tenant-id tenant-id]
+                                           [:= :disabled-at nil]]})]
+          (postgres/execute! tx
+                             {:update :webhook-deliveries
+                              :set    {:status       "dead"
+                                       :last-error   "Endpoint disabled"
+                                       :completed-at now}
+                              :where  [:and
+                                       [:= :endpoint-id id]
+                                       [:= :tenant-id tenant-id]
+                                       [:= :status "pending"]]})
+          (pos? (or update-count 0)))))))
+
+(defn list-endpoints
+  [dispatcher tenant-id]
+  {:post [(s/valid? (s/coll-of ::postgres.webhook/endpoint) %)]}
+  (span/with-span! {:name ::list-endpoints}
+    (postgres/execute! (:postgres dispatcher)
+                       {:select   [:id :tenant-id :url :events :created-at]
+                        :from     [:webhook-endpoints]
+                        :where    [:and
+                                   [:= :tenant-id tenant-id]
+                                   [:= :disabled-at nil]]
+                        :order-by [[:created-at :desc]]})))
+
+;;; ----------------------------------------------------------------------------
+;;; Publishing
+
+(defn publish!
+  "Queues a delivery of `event` to every active endpoint of the tenant that
+  subscribes to it. Returns the number of deliveries queued."
+  [dispatcher tenant-id event payload]
+  {:pre [(contains? events event) (map? payload)]}
+  (span/with-span! {:name ::publish!}
+    (let [postgres  (:postgres dispatcher)
+          endpoints (postgres/execute! postgres
+                                       {:select [:id]
+                                        :from   [:webhook-endpoints]
+                                        :where  [:and
+                                                 [:= :tenant-id tenant-id]
+                                                 [:= :disabled-at nil]
+                                                 [:= event [:any :events]]]})]
+      (span/add-span-data! {:attributes {:event     event
+                                         :endpoints (count endpoints)}})
+      (when (seq endpoints)
+        (postgres/execute! postgres
+                           {:insert-into :webhook-deliveries
+                            :values      (for [endpoint endpoints]
+                                           {:id          (random-uuid)
+                                            :endpoint-id (:bits.postgres.webhook-endpoint/id endpoint)
+                                            :tenant-id   tenant-id
+                                            :event       event
+                                            :payload     [:lift payload]})}))
+      (count endpoints))))
+
+;;; ----------------------------------------------------------------------------
+;;; Delivery
+
+(def ^:private lease-minutes
+  "How long a claimed delivery stays invisible to other dispatchers. Must
+  comfortably exceed a batch's worth of request timeouts."
+  10)
+
+(defn backoff-seconds
+  "Exponential backoff: the base delay doubles with every failed attempt."
+  [base-seconds attempts]
+  (* base-seconds (bit-shift-left 1 (max 0 (dec attempts)))))
+
+(defn- claim-due!
+  "Leases a batch of due deliveries so that dispatchers on other nodes skip
+  them."
+  [dispatcher]
+  (let [{:keys [batch-size postgres]} dispatcher
+        now                           (clock/now (:clock dispatcher))]
+    (postgres/execute! postgres
+                       {:update    :webhook-deliveries
+                        :set       {:next-attempt-at [:+ now [:make-interval :mins lease-minutes]]}
+                        :where     [:in :id {:select   [:id]
+                                             :from     [:webhook-deliveries]
+                                             :where    [:and
+                                                        [:= :status "pending"]
+                                                        [:<= :next-attempt-at now]]
+                                             :order-by [:next-attempt-at]
+                                             :limit    batch-size
+                                             :for      [:update :skip-locked]}]
+                        :returning [:id :endpoint-id :tenant-id :event :payload :attempts]})))
+
+(defn- find-endpoints
+  [dispatcher ids]
+  (when (seq ids)
+    (into {}
+          (map (juxt :bits.postgres.webhook-endpoint/id identity))
+          (postgres/execute! (:postgres dispatcher)
+                             {:select [:id :url :secret]
+                              :from   [:webhook-endpoints]
+                              :where  [:in :id ids]}))))
+
+(defn reseal-secrets!
+  "Rewraps every endpoint secret with the active field key, sealing any stored
+  before secrets were encrypted. Returns how many rows changed."
+  [dispatcher]
+  (span/with-span! {:name ::reseal-secrets!}
+    (let [{:keys [keymaster postgres]} dispatcher]
+      (postgres/with-transaction [tx postgres]
+        (reduce (fn [n {:bits.postgres.webhook-endpoint/keys [id secret]}]
+                  (if-some [resealed (crypto/reseal keymaster secret-context secret)]
+                    (do (postgres/execute-one! tx {:update :webhook-endpoints
+                                                   :set    {:secret resealed}
+                                                   :where  [:= :id id]})
+                        (inc n))
+                    n))
+                0
+                (postgres/execute! tx {:select [:id :secret]
+                                       :from   [:webhook-endpoints]
+                                       :for    :update}))))))
+
+(defn- body
+  [delivery]
+  (json/write-json-str
+   {:id        (identifier/prefixed :event (:bits.postgres.webhook-delivery/id delivery))
+    :event     (:bits.postgres.webhook-delivery/event delivery)
+    :tenant-id (identifier/prefixed :tenant (:bits.postgres.webhook-delivery/tenant-id delivery))
+    :data      (:bits.postgres.webhook-delivery/payload delivery)}))
+
+(defn- send!
+  "Returns the HTTP status, or the exception thrown while trying to get one.
+  The endpoint's host is resolved again first, so one that has moved to a
+  private address since it was registered fails like any other attempt."
+  [dispatcher endpoint delivery]
+  (let [{:keys [clock http-client timeout-ms]} dispatcher
+        payload                                (body delivery)
+        timestamp                              (.toEpochSecond ^OffsetDateTime (clock/now clock))]
+    (try
+      (:status (http/post (egress/check! (:bits.postgres.webhook-endpoint/url endpoint))
+                          {:http-client       http-client
+                           :timeout           timeout-ms
+                           :throw-exceptions? false
+                           :content-type      :json
+                           :headers           {signature-header
+                                               (signature (crypto/unseal (:keymaster dispatcher)
+                                                                         secret-context
+                                                                         (:bits.postgres.webhook-endpoint/secret endpoint))
+                                                          timestamp
+                                                          payload)}
+                           :body              payload}))
+      (catch Exception ex
+        ex))))
+
+(defn- record-result!
+  [dispatcher delivery result]
+  (let [{:keys [backoff-base-seconds max-attempts postgres]} dispatcher
+        now        (clock/now (:clock dispatcher))
+        attempts   (inc (:bits.postgres.webhook-delivery/attempts delivery))
+        status     (when (int? result) result)
+        succeeded? (and status (<= 200 status 299))
+        dead?      (and (not succeeded?) (<= max-attempts attempts))]
+    (postgres/execute-one! postgres
+                           {:update :webhook-deliveries
+                            :set    (cond-> {:attempts        attempts
+                                             :response-status status
+                                             :last-error      (cond
+                                                                succeeded?           nil
+                                                                (instance? Exception result)
+                                                                (ex-message result)
+                                                                :else
+                                                                (str "Unexpected HTTP status " status))}
+                                      succeeded?
+                                      (assoc :status "succeeded" :completed-at now)
+
+                                      dead?
+                                      (assoc :status "dead" :completed-at now)
+
+                                      (not (or succeeded? dead?))
+                                      (assoc :next-attempt-at
+                                             [:+ now [:make-interval :secs
+                                                      (backoff-seconds backoff-base-seconds attempts)]]))
+                            :where  [:= :id (:bits.postgres.webhook-delivery/id delivery)]})
+    (cond succeeded? :succeeded dead? :dead :else :retrying)))
+
+(defn deliver-due!
+  "Attempts every due delivery once. Returns a count of outcomes."
+  [dispatcher]
+  (span/with-span! {:name ::deliver-due!}
+    (let [deliveries (claim-due! dispatcher)
+          endpoints  (find-endpoints dispatcher
+                                     (into #{} (map :bits.postgres.webhook-delivery/endpoint-id)
+                                           deliveries))
+          outcomes   (frequencies
+                      (for [delivery deliveries
+                            :let     [endpoint (get endpoints (:bits.postgres.webhook-delivery/endpoint-id delivery))]]
+                        (record-result! dispatcher delivery (send! dispatcher endpoint delivery))))]
+      (span/add-span-data! {:attributes (update-keys outcomes name)})
+      outcomes)))
+
+(def delivery-ordering
+  {:direction :desc
+   :columns   [[:created-at :bits.postgres.webhook-delivery/created-at]
+               [:id :bits.postgres.webhook-delivery/id]]})
+
+(defn list-deliveries
+  "Returns a page of the tenant's deliveries, newest first."
+  [dispatcher tenant-id page-request]
+  {:post [(s/valid? (s/coll-of ::postgres.webhook/delivery) (:items %))]}
+  (span/with-span! {:name ::list-deliveries}
+    (-> (postgres/execute! (postgres/replica (:postgres dispatcher))
+                           (pagination/paginate {:select [:id :endpoint-id :event :status :attempts
+                                                          :response-status :last-error :created-at
+                                                          :completed-at]
+                                                 :from   [:webhook-deliveries]
+                                                 :where  [:= :tenant-id tenant-id]}
+                                                delivery-ordering
+                                                page-request))
+        (pagination/page delivery-ordering page-request))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defn- poll!
+  [dispatcher]
+  (try
+    (doseq [postgres (postgres/databases (:postgres dispatcher))]
+      (deliver-due! (postgres/repoint dispatcher postgres)))
+    (catch Exception ex
+      ;; An exception escaping a scheduled task cancels all future runs.
+      (log/warn :msg "Failed to deliver webhooks?!" :exception ex)
+      (span/add-exception! ex {:escaping? false}))))
+
+(defrecord Dispatcher [backoff-base-seconds
+                       clock
+                       batch-size
+                       ^ScheduledExecutorService executor
+                       http-client
+                       keymaster
+                       max-attempts
+                       poll-seconds
+                       postgres
+                       randomizer
+                       timeout-ms]
+  component/Lifecycle
+  (start [this]
+    (span/with-span! {:name ::start-dispatcher}
+      (let [executor (Executors/newSingleThreadScheduledExecutor)
+            this     (assoc this
+                            :executor    executor
+                            :http-client (http/build-http-client
+                                          {:connect-timeout timeout-ms
+                                           :redirect-policy :never}))]
+        (.scheduleWithFixedDelay executor
+                                 ^Runnable #(poll! this)
+                                 poll-seconds poll-seconds TimeUnit/SECONDS)
+        this)))
+
+  (stop [this]
+    (span/with-span! {:name ::stop-dispatcher}
+      (when executor
+        (.shutdown executor)
+        (when-not (.awaitTermination executor 5 TimeUnit/SECONDS)
+          (.shutdownNow executor)))
+      (assoc this :executor nil :http-client nil))))
+
+(defmethod print-method Dispatcher
+  [dispatcher ^java.io.Writer w]
+  (.write w (format "#<Dispatcher poll-seconds=%d max-attempts=%d>"
+                    (:poll-seconds dispatcher)
+                    (:max-attempts dispatcher))))
+
+(defn make-dispatcher
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Dispatcher config))
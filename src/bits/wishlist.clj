@@ -0,0 +1,135 @@
+(ns bits.wishlist
+  "Products storefront customers save for later.
+
+  An owner is `{:user-id ...}` for a signed-in customer or `{:sid ...}` for
+  anyone else, whose items are kept against a hash of their session ID like
+  the session itself. Signing in rotates the session, so the anonymous items
+  are merged into the user's in the same transaction; products saved both
+  ways are kept once. Items of sessions that expired without signing in are
+  purged along with the sessions."
+  (:require
+   [bits.crypto :as crypto]
+   [bits.postgres :as postgres]
+   [bits.postgres.wishlist-item :as postgres.wishlist-item]
+   [bits.spec]
+   [clojure.spec.alpha :as s]
+   [steffan-westcott.clj-otel.api.trace.span :as span]))
+
+;;; ----------------------------------------------------------------------------
+;;; Owners
+
+(defn- owned-by
+  [tenant-id {:keys [sid user-id]}]
+  {:pre [(or (uuid? user-id) (string? sid))]}
+  [:and
+   [:= :tenant-id tenant-id]
+   (if user-id
+     [:= :user-id user-id]
+     [:= :sid-hash (crypto/sha256 sid)])])
+
+;;; ----------------------------------------------------------------------------
+;;; Items
+
+(defn saved
+  "IDs of the owner's saved products, most recently saved first."
+  [wishlists tenant-id owner]
+  {:post [(s/valid? (s/coll-of uuid?) %)]}
+  (span/with-span! {:name ::saved}
+    (mapv ::postgres.wishlist-item/product-id
+          (postgres/execute! (:postgres wishlists)
+                             {:select   [:product-id]
+                              :from     [:wishlist-items]
+                              :where    (owned-by tenant-id owner)
+                              :order-by [[:created-at :desc] :product-id]}))))
+
+(defn toggle!
+  "Saves the product, or removes it when it's already saved. Returns whether
+  it's saved now."
+  [wishlists tenant-id {:keys [sid user-id] :as owner} product-id]
+  (span/with-span! {:name ::toggle!}
+    (postgres/with-transaction [pg (:postgres wishlists)]
+      (let [[{:keys [next.jdbc/update-count]}]
+            (postgres/execute! pg {:delete-from :wishlist-items
+                                   :where       (conj (owned-by tenant-id owner)
+                                                      [:= :product-id product-id])})]
+        (if (pos? (or update-count 0))
+          false
+          (do
+            (postgres/execute! pg {:insert-into :wishlist-items
+                                   :values      [{:tenant-id  tenant-id
+                                                  :product-id product-id
+                                                  :user-id    user-id
+                                                  :sid-hash   (when-not user-id (crypto/sha256 sid))}]
+                                   :on-conflict []
+                                   :do-nothing  true})
+            true))))))
+
+(defn merge!
+  "Moves the anonymous session's items to the user, who keeps their own copy
+  of anything saved both ways. Returns how many items were moved. Joins the
+  wishlists' transaction if it has one."
+  [wishlists tenant-id sid user-id]
+  (span/with-span! {:name ::merge!}
+    (if (nil? sid)
+      0
+      (postgres/with-transaction [pg (:postgres wishlists)]
+        (let [anonymous (owned-by tenant-id {:sid sid})
+              [{:keys [next.jdbc/update-count]}]
+              (postgres/execute! pg {:insert-into [:wishlist-items [:tenant-id :product-id :user-id :created-at]]
+                                     :select      [:tenant-id :product-id [[:cast user-id :uuid]] :created-at]
+                                     :from        [:wishlist-items]
+                                     :where       anonymous
+                                     :on-conflict []
+                                     :do-nothing  true})]
+          (postgres/execute! pg {:delete-from :wishlist-items
+                                 :where       anonymous})
+          (or update-count 0))))))
+
+(defn purge-orphans!
+  "Deletes anonymous items whose session has gone. Returns how many."
+  [wishlists]
+  (span/with-span! {:name ::purge-orphans!}
+    (let [[{:keys [next.jdbc/update-count]}]
+          (postgres/execute! (:postgres wishlists)
+                             {:delete-from :wishlist-items
+                              :where       [:and
+                                            [:<> :sid-hash nil]
+                                            [:not [:exists {:select [1]
+                                                            :from   [:sessions]
+                                                            :where  [:and
+                                                                     [:= :sessions.sid-hash :wishlist-items.sid-hash]
+                                                                     [:= :sessions.tenant-id :wishlist-items.tenant-id]]}]]]})]
+      (or update-count 0))))
+
+;;; ----------------------------------------------------------------------------
+;;; Analytics
+
+(defn popular
+  "The tenant's most saved products as `{:product-id ... :saves n}`, counting
+  signed-in and anonymous customers alike."
+  [wishlists tenant-id limit]
+  (span/with-span! {:name ::popular}
+    (mapv (fn [{::postgres.wishlist-item/keys [product-id] :keys [saves]}]
+            {:product-id product-id
+             :saves      saves})
+          (postgres/execute! (postgres/replica (:postgres wishlists))
+                             {:select   [:product-id [[:count :*] :saves]]
+                              :from     [:wishlist-items]
+                              :where    [:= :tenant-id tenant-id]
+                              :group-by [:product-id]
+                              :order-by [[:saves :desc] :product-id]
+                              :limit    limit}))))
+
+;;; ----------------------------------------------------------------------------
+;;; Component
+
+(defrecord Wishlists [postgres])
+
+(defmethod print-method Wishlists
+  [_ ^java.io.Writer w]
+  (.write w "#<Wishlists>"))
+
+(defn make-wishlists
+  [config]
+  {:pre [(s/valid? ::config config)]}
+  (map->Wishlists config))
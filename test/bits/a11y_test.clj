@@ -0,0 +1,33 @@
+(ns ^:e2e bits.a11y-test
+  (:require
+   [bits.datomic :as datomic]
+   [bits.test.app :as t]
+   [bits.test.browser :as browser]
+   [bits.test.fixture :as fixture]
+   [clojure.test :refer [deftest is testing]]
+   [datomic.api :as d]))
+
+;;; Rules a page may break for now, by page. Each entry should say why, and
+;;; go once the page is fixed.
+(def ^:private allowed
+  {})
+
+(def ^:private pages
+  [["platform landing" nil "/"]
+   ["platform login" nil "/login"]
+   ["storefront" "acme" "/"]
+   ["storefront login" "acme" "/login"]])
+
+(deftest key-pages-have-no-serious-violations
+  (t/with-system [{:keys [service]} (t/system)]
+    @(d/transact (datomic/conn (:datomic service))
+                 (fixture/realm-txes {:creator/handle "acme"
+                                      :domain/name    "acme.localhost"}))
+    (browser/with-driver [driver service]
+      (doseq [[page subdomain path] pages]
+        (testing page
+          (if subdomain
+            (browser/goto-domain driver subdomain path)
+            (browser/goto driver path))
+          (browser/wait-visible driver {:css "main"})
+          (is (= [] (browser/audit driver (get allowed page #{})))))))))
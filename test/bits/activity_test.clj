@@ -0,0 +1,24 @@
+(ns bits.activity-test
+  (:require
+   [bits.activity :as sut]
+   [bits.test.app :as t]
+   [clojure.test :refer [deftest is]]
+   [matcher-combinators.test]))
+
+(def ^:private tenant-id
+  #uuid "5b7c2a8e-3f0d-4d1e-9a6b-2c4e8f1a7d30")
+
+(deftest pages-walk-the-whole-feed
+  (t/with-system [{:keys [activities]} (t/system)]
+    (dotimes [i 5]
+      (sut/record! activities tenant-id nil "webhook.registered" {:url (str "https://example.com/" i)}))
+    (sut/record! activities (random-uuid) nil "webhook.registered" {:url "https://elsewhere.com/"})
+    (let [first-page  (sut/list-activities activities tenant-id {:limit 2})
+          second-page (sut/list-activities activities tenant-id {:cursor (:next-cursor first-page) :limit 2})
+          last-page   (sut/list-activities activities tenant-id {:cursor (:next-cursor second-page) :limit 2})
+          urls        (map (comp :url :bits.postgres.activity/data)
+                           (mapcat :items [first-page second-page last-page]))]
+      (is (string? (:next-cursor first-page)))
+      (is (match? {:items [map?] :next-cursor nil} last-page))
+      (is (= 5 (count (distinct urls))))
+      (is (every? #(re-find #"example\.com" %) urls)))))
@@ -0,0 +1,74 @@
+(ns bits.analytics-test
+  (:require
+   [bits.analytics :as sut]
+   [bits.postgres :as postgres]
+   [bits.test.app :as t]
+   [clojure.test :refer [deftest is testing]])
+  (:import
+   (java.time Duration OffsetDateTime ZoneOffset)))
+
+(def ^:private now
+  (OffsetDateTime/of 2026 10 16 23 15 0 0 ZoneOffset/UTC))
+
+(def ^:private browser
+  "Mozilla/5.0 (X11; Linux x86_64) Firefox/131.0")
+
+(deftest addresses-lose-their-host
+  (is (= "203.0.113.0" (sut/truncate-ip "203.0.113.42")))
+  (is (= "2001:db8:85a3::" (sut/truncate-ip "2001:db8:85a3:8d3:1319:8a2e:370:7348")))
+  (is (nil? (sut/truncate-ip "not-an-ip")))
+  (is (= (sut/visitor-hash "salt" "t" "203.0.113.1" browser)
+         (sut/visitor-hash "salt" "t" "203.0.113.200" browser))))
+
+(deftest paths-drop-queries
+  (is (= "/products/prod_1" (sut/normalize-path "/products/prod_1?utm_source=x#top")))
+  (is (nil? (sut/normalize-path "https://example.com/")))
+  (is (nil? (sut/normalize-path "//example.com/"))))
+
+(deftest completed-hours-roll-up
+  (let [!now (atom now)]
+    (t/with-system [{{:keys [analytics postgres]} :service} (t/replace-clock (t/system) !now)]
+      (let [tenant-id (random-uuid)
+            view!     #(sut/collect! analytics (merge {:tenant-id  tenant-id
+                                                       :path       "/"
+                                                       :ip         "198.51.100.7"
+                                                       :user-agent browser}
+                                                      %))]
+        (is (true? (view! {})))
+        (is (true? (view! {:path "/about"})))
+        (is (true? (view! {:ip "192.0.2.1"})))
+        (is (false? (view! {:user-agent "Googlebot/2.1"})))
+        (is (false? (view! {:path "about"})))
+
+        (testing "the current hour waits"
+          (is (= 0 (sut/roll-up! analytics))))
+
+        (swap! !now #(.plus ^OffsetDateTime % (Duration/ofHours 1)))
+        (is (= 3 (sut/roll-up! analytics)))
+        (is (= 0 (sut/roll-up! analytics)))
+
+        (let [hour (.toInstant (OffsetDateTime/of 2026 10 16 23 0 0 0 ZoneOffset/UTC))]
+          (is (= [{:hour hour :views 3 :visitors 2}]
+                 (sut/hourly analytics tenant-id hour (.plusSeconds hour 3600))))
+          (is (= [{:path "/" :views 2} {:path "/about" :views 1}]
+                 (sut/top-paths analytics tenant-id hour 10))))
+
+        (testing "yesterday's salt is gone once the day is over"
+          (is (empty? (postgres/execute! postgres {:select [:day] :from [:analytics-salts]}))))))))
+
+(deftest collect!
+  (let [!now (atom now)]
+    (t/with-system [{{:keys [analytics]} :service} (t/replace-clock (t/system) !now)]
+      (let [analytics (assoc analytics :views-per-minute 2)
+            view!     #(sut/collect! analytics {:tenant-id  (random-uuid)
+                                                :path       "/"
+                                                :ip         %
+                                                :user-agent browser})]
+        (is (true? (view! "198.51.100.7")))
+        (is (true? (view! "198.51.100.7")))
+        (is (false? (view! "198.51.100.7")))
+        (is (true? (view! "192.0.2.1")))
+
+        (testing "the next minute starts afresh"
+          (swap! !now #(.plus ^OffsetDateTime % (Duration/ofMinutes 1)))
+          (is (true? (view! "198.51.100.7"))))))))
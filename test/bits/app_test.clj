@@ -0,0 +1,56 @@
+(ns bits.app-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.app :as sut]
+   [bits.auth.rate-limit :as rate-limit]
+   [clojure.test :refer [deftest is]]
+   [com.stuartsierra.component :as component]
+   [matcher-combinators.matchers :as m]
+   [matcher-combinators.test :refer [match?]]))
+
+(deftest read-config-overrides
+  (let [config (sut/read-config {:overrides {:service  {:http-port 4000}
+                                             :webhooks {:max-attempts 3}}})]
+    (is (match? {:service  {:http-port 4000 :cookie-name "__Host-bits"}
+                 :webhooks {:max-attempts 3 :batch-size 20}}
+                config))))
+
+(deftest read-config-field-keys
+  (is (= "dev" (get-in (sut/read-config) [:keymaster :active-field-key])))
+  (is (thrown-with-msg? clojure.lang.ExceptionInfo #"FIELD_KEYS"
+                        (sut/read-config {:overrides {:service {:environment :production}}})))
+  (is (= {"k1" "3q2+7wAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="}
+         (get-in (sut/read-config {:overrides {:keymaster {:active-field-key "k1"
+                                                           :field-keys       {"k1" "3q2+7wAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="}}
+                                               :service   {:environment :production}}})
+                 [:keymaster :field-keys]))))
+
+(deftest redact
+  (is (= {:cluster  {:keystore-password "[redacted]"}
+          :postgres {:database-url "jdbc:postgresql://db/bits?user=bits&password=[redacted]"}
+          :service  {:csrf-secret "[redacted]" :http-port 3000}}
+         (sut/redact {:cluster  {:keystore-password "hunter2"}
+                      :postgres {:database-url "jdbc:postgresql://db/bits?user=bits&password=hunter2"}
+                      :service  {:csrf-secret "s3cret" :http-port 3000}}))))
+
+(deftest problems
+  (let [config (sut/read-config {:overrides {:service {:http-port "nope"}}})]
+    (is (match? (m/embeds [{:path [:service :http-port] :message #"must satisfy"}])
+                (sut/problems config)))
+    (is (match? (m/embeds [{:path [:cluster :keystore-password] :message "is required"}])
+                (sut/problems (update config :cluster dissoc :keystore-password))))
+    (is (match? (m/embeds [{:path [:cluster :peer-name]}])
+                (sut/problems (assoc-in config [:cluster :peer-name] "not a name"))))
+    (is (match? (m/embeds [{:path [:oidc :ttl-seconds] :message "is required"}])
+                (sut/problems (update config :oidc dissoc :ttl-seconds))))))
+
+(deftest reload-swaps-rate-limits
+  (let [config  (sut/read-config)
+        limiter (component/start (rate-limit/make-limiter (:rate-limiter config)))
+        system  {:rate-limiter limiter}]
+    (is (= 20 (:ip-max-attempts (rate-limit/limits limiter))))
+    (is (match? {:ip-max-attempts 3}
+                (sut/reload! system {:overrides {:rate-limiter {:ip-max-attempts 3}}} "test")))
+    (is (= 3 (:ip-max-attempts (rate-limit/limits limiter))))
+    (is (anom/anomaly? (sut/reload! system {:overrides {:rate-limiter {:ip-max-attempts -1}}} "test")))
+    (is (= 3 (:ip-max-attempts (rate-limit/limits limiter))))))
@@ -0,0 +1,129 @@
+(ns bits.auth.api-key-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.auth.api-key :as sut]
+   [bits.cryptex :as cryptex]
+   [bits.datomic :as datomic]
+   [bits.middleware :as mw]
+   [bits.module.api-key :as module.api-key]
+   [bits.response]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [bits.test.snapshot :as snapshot]
+   [clojure.test :refer [are deftest is]]
+   [datomic.api :as d]
+   [matcher-combinators.test]))
+
+(def ^:private platform-tenant-id
+  #uuid "00000000-0000-0000-0000-000000000000")
+
+(defn- bearer
+  [token]
+  {"authorization" (str "Bearer " token)})
+
+;;; ----------------------------------------------------------------------------
+;;; Tokens
+
+(deftest parse-token
+  (are [s expected] (= expected (sut/parse-token s))
+    "bits_abcd_0123"      {:prefix "abcd" :secret "0123"}
+    "bits_abcd_01_23"     {:prefix "abcd" :secret "01_23"}
+    "bits__0123"          nil
+    "bits_abcd_"          nil
+    "other_abcd_0123"     nil
+    ""                    nil
+    nil                   nil))
+
+;;; ----------------------------------------------------------------------------
+;;; Authentication
+
+(deftest authenticate
+  (t/with-system [{:keys [api-keys]} (t/system)]
+    (let [user-id (random-uuid)
+          issued  (sut/issue! api-keys {:tenant-id platform-tenant-id
+                                        :user-id   user-id
+                                        :name      "Test"
+                                        :scopes    #{:read}})
+          token   (cryptex/reveal (::sut/token issued))]
+      (is (match?
+           {::sut/id        (::sut/id issued)
+            ::sut/scopes    #{:read}
+            ::sut/tenant-id platform-tenant-id
+            ::sut/user-id   user-id}
+           (sut/authenticate api-keys platform-tenant-id token)))
+
+      (is (match? {::anom/category ::anom/forbidden} (sut/authenticate api-keys (random-uuid) token)))
+      (is (match? {::anom/category ::anom/forbidden} (sut/authenticate api-keys platform-tenant-id (str token "0"))))
+
+      (is (true? (sut/revoke! api-keys platform-tenant-id user-id (::sut/id issued))))
+      (is (match? {::anom/category ::anom/forbidden} (sut/authenticate api-keys platform-tenant-id token))))))
+
+(deftest authenticate-rate-limit
+  (t/with-system [{:keys [api-keys]} (t/system)]
+    (let [issue! (fn [registry opts]
+                   (-> (sut/issue! registry (merge {:tenant-id platform-tenant-id
+                                                    :user-id   (random-uuid)
+                                                    :name      "Test"
+                                                    :scopes    #{:read}}
+                                                   opts))
+                       ::sut/token
+                       cryptex/reveal))]
+      (let [token (issue! (assoc api-keys :requests-per-minute 1) {})]
+        (is (not (anom/anomaly? (sut/authenticate api-keys platform-tenant-id token))))
+        (is (match? {::anom/category ::anom/busy} (sut/authenticate api-keys platform-tenant-id token))))
+
+      (let [token (issue! api-keys {:requests-per-minute 1})]
+        (is (not (anom/anomaly? (sut/authenticate api-keys platform-tenant-id token))))
+        (is (match? {::anom/category ::anom/busy} (sut/authenticate api-keys platform-tenant-id token)))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Requests
+
+(deftest bearer-requests
+  (t/with-system [{:keys [service]} (t/system)]
+    @(d/transact (datomic/conn (:datomic service)) (fixture/realm-txes))
+    (let [token (-> (sut/issue! (:api-keys service)
+                                {:tenant-id platform-tenant-id
+                                 :user-id   (random-uuid)
+                                 :name      "Test"
+                                 :scopes    #{:read}})
+                    ::sut/token
+                    cryptex/reveal)]
+      (is (match?
+           {:status 200}
+           (t/request service {:request-method :get
+                               :url            "/"
+                               :headers        (bearer token)})))
+
+      (is (match?
+           {:status 401}
+           (t/request service {:request-method :get
+                               :url            "/"
+                               :headers        (bearer "bits_nope_nope")})))
+
+      (is (match?
+           {:status 403}
+           (t/request service {:request-method :post
+                               :url            "/action"
+                               :headers        (bearer token)
+                               :form-params    {:action "auth/sign-out"}}))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Handlers
+
+(deftest issue
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants users]} (fixture/seed! service
+                                                 (fixture/tenant "acme")
+                                                 (-> (fixture/user "owner@acme.test")
+                                                     (fixture/member-of "acme" :membership.role/owner))
+                                                 (-> (fixture/user "member@acme.test")
+                                                     (fixture/member-of "acme")))
+          request                 (fn [email]
+                                    (snapshot/view-request service {:realm   {:tenant/id (get-in tenants ["acme" :tenant/id])}
+                                                                    :user-id (get-in users [email :user/id])}))]
+      (are [req forbidden?] (= forbidden? (= bits.response/forbidden-response (module.api-key/issue req)))
+        (request "owner@acme.test")                                  false
+        (request "member@acme.test")                                 true
+        (request "nobody@acme.test")                                 true
+        (assoc (request "owner@acme.test") ::mw/api-key {::sut/id 1}) true))))
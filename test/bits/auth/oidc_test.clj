@@ -0,0 +1,125 @@
+(ns bits.auth.oidc-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.auth.oidc :as sut]
+   [bits.datomic :as datomic]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [clojure.test :refer [are deftest is testing]]
+   [datomic.api :as d]
+   [matcher-combinators.test])
+  (:import
+   (java.util Date)))
+
+(defn- connect!
+  "Points the tenant at the test identity provider."
+  [service tenant-id provision?]
+  @(d/transact (datomic/conn (:datomic service))
+               (sut/connection-tx (:keymaster service) tenant-id {:client-id     "bits"
+                                                                  :client-secret "shh"
+                                                                  :issuer        "https://idp.example.com"
+                                                                  :provision?    provision?})))
+
+(defn- claims
+  [sub email]
+  {:iss            "https://idp.example.com"
+   :sub            sub
+   :email          email
+   :email_verified true})
+
+;;; ----------------------------------------------------------------------------
+;;; Connections
+
+(deftest connection-secret-is-sealed
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [datomic keymaster]} service
+          {:keys [tenants]}           (fixture/seed! service (fixture/tenant "acme"))
+          tenant-id                   (get-in tenants ["acme" :tenant/id])
+          conn                        (datomic/conn datomic)]
+      @(d/transact conn (sut/connection-tx keymaster tenant-id {:client-id     "bits"
+                                                                :client-secret "shh"
+                                                                :issuer        "https://idp.example.com"}))
+      (is (not= "shh" (d/q '[:find ?secret .
+                             :where [_ :sso/client-secret ?secret]]
+                           (d/db conn))))
+      (is (= {:sso/client-id     "bits"
+              :sso/client-secret "shh"
+              :sso/issuer        "https://idp.example.com"}
+             (sut/connection keymaster (d/db conn) tenant-id)))
+      (is (nil? (sut/connection keymaster (d/db conn) (random-uuid)))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Provisioning
+
+(deftest provisions-users-on-first-sign-in
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants]} (fixture/seed! service (fixture/tenant "acme"))
+          tenant-id         (get-in tenants ["acme" :tenant/id])
+          _                 (connect! service tenant-id true)
+          conn              (datomic/conn (:datomic service))
+          tx                (sut/provision-tx (d/db conn) tenant-id (claims "u-1" "ada@acme.test") (Date.))
+          db                (:db-after @(d/transact conn tx))
+          user              (d/entity db [:user/sso-subject "https://idp.example.com|u-1"])]
+      (is (= "ada@acme.test" (:user/email user)))
+      (is (sut/sso-managed? user))
+      (is (= :membership.role/member
+             (d/q '[:find ?role .
+                    :in $ ?u
+                    :where
+                    [?m :membership/user ?u]
+                    [?m :membership/role ?role]]
+                  db
+                  (:db/id user))))
+      (is (= [] (sut/provision-tx db tenant-id (claims "u-1" "ada@acme.test") (Date.)))))))
+
+(deftest provisioning-never-claims-local-accounts
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants]} (fixture/seed! service
+                                           (fixture/tenant "acme")
+                                           (fixture/user "bob@acme.test"))
+          tenant-id         (get-in tenants ["acme" :tenant/id])
+          _                 (connect! service tenant-id true)
+          db                (datomic/db (:datomic service))]
+      (is (match? {::anom/category ::anom/conflict}
+                  (sut/provision-tx db tenant-id (claims "u-2" "bob@acme.test") (Date.))))
+      (are [c] (match? {::anom/category ::anom/forbidden} (sut/provision-tx db tenant-id c (Date.)))
+        (assoc (claims "u-3" "eve@acme.test") :email_verified false)
+        (dissoc (claims "u-4" "eve@acme.test") :email_verified)
+        (assoc (claims "u-5" "eve@acme.test") :email_verified "true")
+        (dissoc (claims "u-6" "eve@acme.test") :email)))))
+
+(deftest provision-tx
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants]} (fixture/seed! service (fixture/tenant "acme") (fixture/tenant "globex"))
+          acme-id           (get-in tenants ["acme" :tenant/id])
+          globex-id         (get-in tenants ["globex" :tenant/id])
+          conn              (datomic/conn (:datomic service))
+          ada               (claims "u-1" "ada@acme.test")
+          _                 (connect! service acme-id true)
+          _                 (connect! service globex-id false)
+          db                (:db-after @(d/transact conn (sut/provision-tx (d/db conn) acme-id ada (Date.))))]
+      (testing "a tenant sharing the provider doesn't let in members of another"
+        (is (match? {::anom/category ::anom/forbidden}
+                    (sut/provision-tx db globex-id ada (Date.)))))
+
+      (testing "unless it provisions them"
+        (let [db (:db-after (connect! service globex-id true))
+              tx (sut/provision-tx db globex-id ada (Date.))]
+          (is (match? [{:membership/tenant [:tenant/id globex-id]}] tx))
+          (is (= [] (sut/provision-tx (:db-after @(d/transact conn tx)) globex-id ada (Date.))))))
+
+      (testing "new users need a tenant that provisions them"
+        (is (match? {::anom/category ::anom/forbidden}
+                    (sut/provision-tx (d/db conn) (random-uuid) (claims "u-2" "eve@acme.test") (Date.))))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Handlers
+
+(deftest login
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants]} (fixture/seed! service (fixture/tenant "acme"))
+          domain            (get-in tenants ["acme" :domain/name])
+          response          (t/request service (t/host {:request-method :get :url "/sso/login"} domain))]
+      (is (= 404 (:status response)))
+      (testing "apostrophes survive message formatting"
+        (is (re-find #"Single sign-on isn(&#39;|&apos;|')t set up here\." (:body response)))))))
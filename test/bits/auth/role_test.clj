@@ -0,0 +1,65 @@
+(ns bits.auth.role-test
+  (:require
+   [bits.auth.role :as sut]
+   [bits.datomic :as datomic]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [clojure.test :refer [are deftest is]]))
+
+;;; ----------------------------------------------------------------------------
+;;; Platform
+
+(deftest request-platform-admin?
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [users]} (fixture/seed! service
+                                         (fixture/user "admin@example.com" {:user/platform-admin? true})
+                                         (fixture/user "someone@example.com"))
+          db              (datomic/db (:datomic service))
+          request         (fn [email realm-type]
+                            {:bits.middleware/db db
+                             :session/realm      {:realm/type realm-type}
+                             :session/user       {:user/id (get-in users [email :user/id])}})]
+      (are [email realm-type admin?] (= admin? (sut/request-platform-admin? (request email realm-type)))
+        "admin@example.com"   :realm.type/platform true
+        "admin@example.com"   :realm.type/creator  false
+        "someone@example.com" :realm.type/platform false
+        "nobody@example.com"  :realm.type/platform false))))
+
+;;; ----------------------------------------------------------------------------
+;;; Tenants
+
+(deftest request-tenant-admin?
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants users]} (fixture/seed! service
+                                                 (fixture/tenant "acme")
+                                                 (-> (fixture/user "owner@acme.test")
+                                                     (fixture/member-of "acme" :membership.role/owner))
+                                                 (-> (fixture/user "member@acme.test")
+                                                     (fixture/member-of "acme")))
+          db                      (datomic/db (:datomic service))
+          request                 (fn [email tenant-id]
+                                    {:bits.middleware/db db
+                                     :session/realm      {:tenant/id tenant-id}
+                                     :session/user       {:user/id (get-in users [email :user/id])}})
+          acme-id                 (get-in tenants ["acme" :tenant/id])]
+      (are [email tenant-id admin?] (= admin? (sut/request-tenant-admin? (request email tenant-id)))
+        "owner@acme.test"  acme-id       true
+        "owner@acme.test"  (random-uuid) false
+        "member@acme.test" acme-id       false
+        "nobody@acme.test" acme-id       false))))
+
+(deftest tenant-admin-ids
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants users]} (fixture/seed! service
+                                                 (fixture/tenant "acme")
+                                                 (-> (fixture/user "owner@acme.test")
+                                                     (fixture/member-of "acme" :membership.role/owner))
+                                                 (-> (fixture/user "admin@acme.test")
+                                                     (fixture/member-of "acme" :membership.role/admin))
+                                                 (-> (fixture/user "member@acme.test")
+                                                     (fixture/member-of "acme")))
+          db                      (datomic/db (:datomic service))]
+      (is (= #{(get-in users ["owner@acme.test" :user/id])
+               (get-in users ["admin@acme.test" :user/id])}
+             (sut/tenant-admin-ids db (get-in tenants ["acme" :tenant/id]))))
+      (is (= #{} (sut/tenant-admin-ids db (random-uuid)))))))
@@ -0,0 +1,24 @@
+(ns bits.cache-test
+  (:require
+   [bits.cache :as sut]
+   [clojure.test :refer [deftest is]]))
+
+(deftest fetch-loads-once
+  (let [cache (sut/make-cache {:maximum-size 10 :ttl-seconds 60})
+        loads (atom 0)
+        load  #(do (swap! loads inc) nil)]
+    (is (= [nil false] (sut/fetch cache :missing load)))
+    (is (= [nil true] (sut/fetch cache :missing load)))
+    (is (= 1 @loads))
+    (is (= 42 (sut/lookup cache :answer (constantly 42))))
+    (sut/evict! cache :answer)
+    (is (= [43 false] (sut/fetch cache :answer (constantly 43))))
+    (sut/clear! cache)
+    (is (= [nil false] (sut/fetch cache :missing load)))
+    (is (= 2 @loads))))
+
+(deftest fetch-caches-nothing-on-failure
+  (let [cache (sut/make-cache {:maximum-size 10 :ttl-seconds 60})]
+    (is (thrown-with-msg? clojure.lang.ExceptionInfo #"boom"
+                          (sut/fetch cache :k #(throw (ex-info "boom" {})))))
+    (is (= [1 false] (sut/fetch cache :k (constantly 1))))))
@@ -0,0 +1,94 @@
+(ns bits.catalog-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.catalog :as sut]
+   [bits.datomic :as datomic]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [clojure.test :refer [are deftest is]]
+   [datomic.api :as d]
+   [matcher-combinators.matchers :as m]
+   [matcher-combinators.test])
+  (:import
+   (java.util Date)))
+
+(deftest parse-csv
+  (is (= [["a" "b"] ["1, 2" "say \"hi\""] ["multi\nline" ""]]
+         (sut/parse-csv "a,b\r\n\"1, 2\",\"say \"\"hi\"\"\"\n\"multi\nline\",\n")))
+  (is (= [["Handle" "Title"]] (sut/parse-csv "\uFEFFHandle,Title\n"))))
+
+(deftest amount
+  (are [price currency out] (= out (sut/amount price currency))
+    "9.99"  "GBP" 999
+    "10"    "USD" 1000
+    "0.5"   "EUR" 50
+    "0"     "GBP" nil
+    "9.999" "GBP" nil
+    "-1"    "GBP" nil
+    "£9"    "GBP" nil))
+
+(def ^:private shopify
+  (str "Handle,Title,Body (HTML),Option1 Name,Option1 Value,Variant SKU,Variant Price,Variant Requires Shipping,Image Src,Status\n"
+       "mug,Mug,<p>Big</p>,Size,Small,MUG-S,12.00,true,,active\n"
+       "mug,,,,Large,MUG-L,14.00,true,,\n"
+       "mug,,,,,,,,https://cdn.example.com/mug.png,\n"
+       "guide,Guide,,Title,Default Title,GUIDE,5,false,,draft\n"))
+
+(deftest reads-shopify-exports
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [errors products]} (sut/check (d/db (datomic/conn (:datomic service))) shopify {:currency "GBP"})]
+      (is (empty? errors))
+      (is (match? [{:title    "Mug"
+                    :variants [{:sku "MUG-S" :variant-name "Small" :type "physical"}
+                               {:sku "MUG-L" :variant-name "Large"}]}
+                   {:title    "Guide"
+                    :status   "draft"
+                    :variants [{:sku "GUIDE" :variant-name nil}]}]
+                  products)))))
+
+(deftest reports-every-problem-by-line
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [db   (d/db (datomic/conn (:datomic service)))
+          text (str "title,sku,price,currency\n"
+                    "Mug,MUG,9.99,GBP\n"
+                    ",MUG,nope,JPY\n")]
+      (is (match? {:errors (m/in-any-order
+                            [{:line 2 :message #"appears more than once"}
+                             {:line 3 :message #"Title is missing"}
+                             {:line 3 :message #"appears more than once"}
+                             {:line 3 :message #"Currency"}])}
+                  (sut/check db text {:currency "GBP"}))))))
+
+(deftest imports-all-or-nothing
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants]} (fixture/seed! service (fixture/with-products (fixture/tenant "acme") 2))
+          tenant-id         (get-in tenants ["acme" :tenant/id])
+          conn              (datomic/conn (:datomic service))
+          product-count     #(count (d/q '[:find [?p ...]
+                                          :in $ ?tenant-id
+                                          :where
+                                          [?t :tenant/id ?tenant-id]
+                                          [?t :tenant/products ?p]]
+                                        (d/db conn) tenant-id))]
+      (is (match? {::anom/category ::anom/incorrect
+                   ::sut/errors    [{:line 3}]}
+                  (sut/import! conn tenant-id "title,sku,price\nMug,MUG,9.99\nCap,,5\n" {:currency "GBP"} (Date.))))
+      (is (= 2 (product-count)))
+      (is (= {:products 2 :variants 3}
+             (sut/import! conn tenant-id shopify {:currency "GBP"} (Date.))))
+      (is (= 4 (product-count)))
+      (is (= [3 4] (sort (d/q '[:find [?position ...]
+                                :where
+                                [?p :product/title ?title]
+                                [(contains? #{"Mug" "Guide"} ?title)]
+                                [?p :product/position ?position]]
+                              (d/db conn)))))
+      (is (= 1200 (d/q '[:find ?amount .
+                         :where
+                         [?s :sku/code "MUG-S"]
+                         [?v :variant/sku ?s]
+                         [?v :variant/price ?price]
+                         [?price :money/amount ?amount]]
+                       (d/db conn))))
+      (is (match? {:errors (m/seq-of {:message #"already exists"})}
+                  (sut/check (d/db conn) shopify {:currency "GBP"}))))))
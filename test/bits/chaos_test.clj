@@ -0,0 +1,49 @@
+(ns bits.chaos-test
+  (:require
+   [bits.event :as event]
+   [bits.outbox :as outbox]
+   [bits.test.app :as t]
+   [bits.test.chaos :as chaos]
+   [bits.test.fixture :as fixture]
+   [clojure.core.async :as a]
+   [clojure.test :refer [deftest is]]
+   [com.stuartsierra.component :as component]
+   [matcher-combinators.test]))
+
+(deftest session-loading-recovers-from-a-dropped-connection
+  (let [faults (chaos/faults)]
+    (t/with-system [{:keys [service]} (chaos/replace-postgres (t/system) faults)]
+      (let [{:keys [tenants]} (fixture/seed! service (fixture/tenant "acme"))
+            request           (-> {:request-method :get
+                                   :url            "/"
+                                   :headers        {"cookie" "bits=not-a-real-session"}}
+                                  (t/host (get-in tenants ["acme" :domain/name])))]
+        (chaos/inject! faults {:fault :dropped-connection :match #"FROM sessions"})
+        (is (match? {:status 500} (t/request service request)))
+        (is (empty? (chaos/pending faults)))
+        (is (match? {:status 200} (t/request service request)))))))
+
+(deftest relay-republishes-after-a-failed-commit
+  (let [faults (chaos/faults)]
+    (t/with-system [{:keys [clock events postgres webhooks]} (chaos/replace-postgres (t/system) faults)]
+      ;; Hour-long polls so the test drives relaying itself.
+      (let [relay     (component/start (assoc (outbox/make-relay {:batch-size 10 :poll-seconds 3600})
+                                              :clock    clock
+                                              :events   events
+                                              :postgres postgres
+                                              :webhooks webhooks))
+            published (event/subscribe! events "page.published" "test" 8)]
+        (try
+          (let [id (outbox/enqueue! postgres (random-uuid) nil "page.published" {:page-id (random-uuid)})]
+            (chaos/inject! faults {:fault :serialization-failure :match #"UPDATE outbox"})
+            (is (thrown? java.sql.SQLException (outbox/relay! relay)))
+            (is (= 1 (outbox/relay! relay)))
+            (is (= 0 (outbox/relay! relay)))
+            ;; Published before the failed commit and again after, so
+            ;; subscribers see the same ID twice.
+            (is (match? {:data {:id id}} (a/poll! published)))
+            (is (match? {:data {:id id}} (a/poll! published)))
+            (is (nil? (a/poll! published))))
+          (finally
+            (event/unsubscribe! events published)
+            (component/stop relay)))))))
@@ -0,0 +1,25 @@
+(ns bits.cli.bench-test
+  (:require
+   [bits.cli.bench :as sut]
+   [clojure.test :refer [are deftest is]]))
+
+(deftest percentile
+  (let [sorted (vec (range 1 101))]
+    (are [p expected] (= expected (sut/percentile sorted p))
+      50  50
+      95  95
+      99  99
+      100 100))
+  (is (nil? (sut/percentile [] 50))))
+
+(deftest summarize
+  (is (= {:count 4 :errors 2 :p50 2.0 :p95 4.0 :p99 4.0 :max 4.0}
+         (sut/summarize [[1.0 200] [2.0 302] [3.0 500] [4.0 nil]]))))
+
+(deftest violations
+  (let [budget {:max-error-rate 0.01 :p95-ms 50 :p99-ms 100}]
+    (is (= [] (sut/violations {:count 100 :errors 1 :p95 49.0 :p99 99.0} budget)))
+    (is (= ["p95 51.0ms exceeds 50ms"
+            "p99 101.0ms exceeds 100ms"
+            "error rate 0.020 exceeds 0.010"]
+           (sut/violations {:count 100 :errors 2 :p95 51.0 :p99 101.0} budget)))))
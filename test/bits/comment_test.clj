@@ -0,0 +1,103 @@
+(ns bits.comment-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.comment :as sut]
+   [bits.datomic :as datomic]
+   [bits.postgres.comment :as postgres.comment]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [clojure.test :refer [deftest is testing]]
+   [datomic.api :as d]
+   [matcher-combinators.test]))
+
+;;; ----------------------------------------------------------------------------
+;;; Threads
+
+(defn- c
+  [id parent-id]
+  {::postgres.comment/id id ::postgres.comment/parent-id parent-id})
+
+(deftest nest
+  (is (= [{::postgres.comment/id        1
+           ::postgres.comment/parent-id nil
+           :replies                     [{::postgres.comment/id        2
+                                          ::postgres.comment/parent-id 1
+                                          :replies                     [{::postgres.comment/id        4
+                                                                         ::postgres.comment/parent-id 2
+                                                                         :replies                     []}]}
+                                         {::postgres.comment/id        3
+                                          ::postgres.comment/parent-id 1
+                                          :replies                     []}]}
+          {::postgres.comment/id        5
+           ::postgres.comment/parent-id nil
+           :replies                     []}]
+         (sut/nest [(c 1 nil) (c 2 1) (c 3 1) (c 4 2) (c 5 nil) (c 6 99)]))))
+
+;;; ----------------------------------------------------------------------------
+;;; Posting
+
+(defn- seed!
+  [service mode]
+  (let [{:keys [tenants users]} (fixture/seed! service
+                                               (fixture/tenant "acme" (cond-> {} mode (assoc :tenant/comments mode)))
+                                               (fixture/user "member@example.com"))]
+    {:conn      (datomic/conn (:datomic service))
+     :member-id (get-in users ["member@example.com" :user/id])
+     :tenant-id (get-in tenants ["acme" :tenant/id])}))
+
+(deftest posting
+  (t/with-system [{:keys [service]} (assoc-in (t/system) [:comments :max-per-window] 3)]
+    (let [{:keys [conn member-id tenant-id]} (seed! service :anyone)
+          comments                           (:comments service)
+          page-id                            (random-uuid)
+          post!                              #(sut/post! comments (d/db conn)
+                                                         (merge {:body       "Lovely page."
+                                                                 :ip-address "203.0.113.7"
+                                                                 :page-id    page-id
+                                                                 :tenant-id  tenant-id}
+                                                                %))]
+      (testing "members go up straight away, anonymous comments wait"
+        (let [{::postgres.comment/keys [id status]} (post! {:author-id member-id})]
+          (is (= "approved" status))
+          (is (match? {::postgres.comment/status "pending"}
+                      (post! {:author-name "Ada" :parent-id id})))
+          (is (match? [{::postgres.comment/id id :replies []}]
+                      (sut/thread comments tenant-id page-id)))))
+
+      (testing "spam and the honeypot"
+        (is (match? {::postgres.comment/status "spam"}
+                    (post! {:author-id member-id :body "BUY CHEAP WATCHES RIGHT NOW FROM US"})))
+        (is (nil? (post! {:honeypot "https://spam.example"}))))
+
+      (testing "the queue"
+        (let [{:keys [items]} (sut/queue comments tenant-id {:limit 10})]
+          (is (= ["pending" "spam"] (map ::postgres.comment/status items)))
+          (is (sut/decide! comments tenant-id (::postgres.comment/id (first items)) "approved" member-id))
+          (is (not (sut/decide! comments (random-uuid) (::postgres.comment/id (second items)) "approved" member-id)))
+          (is (match? [{:replies [{::postgres.comment/author-name "Ada"}]}]
+                      (sut/thread comments tenant-id page-id)))))
+
+      (testing "too many from one address"
+        (is (= ::anom/busy (::anom/category (post! {:author-id member-id}))))
+        (is (match? {::postgres.comment/status "pending"} (post! {:ip-address "198.51.100.1"}))))
+
+      (testing "replies only to approved comments on the same page"
+        (is (= ::anom/not-found (::anom/category (post! {:ip-address "198.51.100.2" :parent-id (random-uuid)}))))))))
+
+(deftest modes
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [conn member-id tenant-id]} (seed! service nil)
+          post!                              #(sut/post! (:comments service) (d/db conn)
+                                                         (merge {:body       "Hello"
+                                                                 :ip-address "203.0.113.7"
+                                                                 :page-id    (random-uuid)
+                                                                 :tenant-id  tenant-id}
+                                                                %))]
+      (is (= ::anom/forbidden (::anom/category (post! {:author-id member-id}))))
+      @(d/transact conn (sut/mode-tx (d/db conn) tenant-id :signed-in))
+      (is (= :signed-in (sut/mode (d/db conn) tenant-id)))
+      (is (= ::anom/forbidden (::anom/category (post! {}))))
+      (is (= ::anom/incorrect (::anom/category (post! {:author-id member-id :body "  "}))))
+      (is (match? {::postgres.comment/status "approved"} (post! {:author-id member-id})))
+      @(d/transact conn (sut/mode-tx (d/db conn) tenant-id nil))
+      (is (nil? (sut/mode (d/db conn) tenant-id))))))
@@ -2,7 +2,8 @@
   (:require
    [bits.crypto :as sut]
    [bits.test.app :as t]
-   [clojure.test :refer [deftest is testing]]))
+   [clojure.test :refer [are deftest is testing]]
+   [com.stuartsierra.component :as component]))
 
 ;;; ----------------------------------------------------------------------------
 ;;; CSRF Tokens
@@ -29,3 +30,43 @@
     (testing "generates distinct values"
       (let [sids (repeatedly 100 #(sut/random-sid randomizer))]
         (is (= 100 (count (set sids))))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Envelope encryption
+
+(def ^:private field-keys
+  {"a" "rpLNrXDqRmshAvShgyLfW1SivmxUmz3oOxhlTXJqK/E="
+   "b" "3q2+7wAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="})
+
+(defn- keymaster
+  [active]
+  (component/start
+   (sut/make-keymaster {:active-field-key active
+                        :argon            {:alg :argon2id :iterations 1 :memory 1024 :parallelism 1}
+                        :field-keys       field-keys})))
+
+(deftest seal
+  (let [km     (keymaster "a")
+        sealed (sut/seal km "t.c" "whsec_123")]
+    (is (sut/sealed? sealed))
+    (is (= "a" (sut/sealed-with sealed)))
+    (testing "each seal uses a fresh data key"
+      (is (not= sealed (sut/seal km "t.c" "whsec_123"))))
+    (testing "the context is authenticated"
+      (is (thrown? javax.crypto.AEADBadTagException (sut/unseal km "t.other" sealed))))))
+
+(deftest unseal
+  (let [km (keymaster "a")]
+    (are [in out] (= out (sut/unseal km "t.c" in))
+      (sut/seal km "t.c" "whsec_123") "whsec_123"
+      "whsec_legacy"                  "whsec_legacy"
+      nil                             nil)))
+
+(deftest reseal
+  (let [km  (keymaster "b")
+        old (sut/seal (keymaster "a") "t.c" "whsec_123")]
+    (are [in out] (= out (some-> (sut/reseal km "t.c" in) sut/sealed-with))
+      old                             "b"
+      (sut/seal km "t.c" "whsec_123") nil
+      "whsec_legacy"                  "b")
+    (is (= "whsec_123" (sut/unseal km "t.c" (sut/reseal km "t.c" old))))))
@@ -0,0 +1,79 @@
+(ns bits.deletion-test
+  (:require
+   [bits.auth.role :as role]
+   [bits.datomic :as datomic]
+   [bits.deletion :as sut]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [clojure.test :refer [deftest is]]
+   [datomic.api :as d])
+  (:import
+   (java.util Date)))
+
+(defn- product-id
+  [db tenant-id]
+  (d/q '[:find ?id .
+         :in $ ?tenant-id
+         :where
+         [?t :tenant/id ?tenant-id]
+         [?t :tenant/products ?p]
+         [?p :product/id ?id]]
+       db tenant-id))
+
+(deftest lookups-are-scoped-to-the-tenant
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants]} (fixture/seed! service
+                                           (fixture/with-products (fixture/tenant "acme") 1)
+                                           (fixture/tenant "other"))
+          db                (d/db (datomic/conn (:datomic service)))
+          acme-id           (get-in tenants ["acme" :tenant/id])
+          id                (product-id db acme-id)]
+      (is (some? (sut/lookup db acme-id :product id)))
+      (is (nil? (sut/lookup db (get-in tenants ["other" :tenant/id]) :product id))))))
+
+(deftest delete-restore-and-purge
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants users]} (fixture/seed! service
+                                                 (fixture/with-products (fixture/tenant "acme") 1)
+                                                 (fixture/user "owner@example.com"))
+          conn                    (datomic/conn (:datomic service))
+          tenant-id               (get-in tenants ["acme" :tenant/id])
+          user-id                 (get-in users ["owner@example.com" :user/id])
+          id                      (product-id (d/db conn) tenant-id)
+          product                 #(sut/lookup (d/db conn) tenant-id :product id)
+          deleted-at              (Date. 0)]
+      @(d/transact conn (sut/delete-tx (product) user-id deleted-at))
+      (is (sut/deleted? (product)))
+      (is (= [[id user-id]]
+             (map (juxt :product/id (comp :user/id :entity/deleted-by))
+                  (sut/deleted (d/db conn) tenant-id :product))))
+
+      @(d/transact conn (sut/restore-tx (product)))
+      (is (not (sut/deleted? (product))))
+      (is (empty? (sut/deleted (d/db conn) tenant-id :product)))
+
+      @(d/transact conn (sut/delete-tx (product) user-id deleted-at))
+      (is (= 0 (sut/purge! conn deleted-at)))
+      (is (= 1 (sut/purge! conn (Date. 1))))
+      (is (nil? (product))))))
+
+(deftest deleted-members-lose-their-role
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants users]} (fixture/seed! service
+                                                 (fixture/tenant "acme")
+                                                 (fixture/user "owner@example.com")
+                                                 (-> (fixture/user "admin@example.com")
+                                                     (fixture/member-of "acme" :membership.role/admin)))
+          conn                    (datomic/conn (:datomic service))
+          tenant-id               (get-in tenants ["acme" :tenant/id])
+          admin-id                (get-in users ["admin@example.com" :user/id])
+          membership              (d/entity (d/db conn)
+                                            (d/q '[:find ?m .
+                                                   :in $ ?user-id
+                                                   :where
+                                                   [?u :user/id ?user-id]
+                                                   [?m :membership/user ?u]]
+                                                 (d/db conn) admin-id))]
+      (is (role/tenant-admin? (d/db conn) admin-id tenant-id))
+      @(d/transact conn (sut/delete-tx membership (get-in users ["owner@example.com" :user/id]) (Date.)))
+      (is (not (role/tenant-admin? (d/db conn) admin-id tenant-id))))))
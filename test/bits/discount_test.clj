@@ -0,0 +1,94 @@
+(ns bits.discount-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.discount :as sut]
+   [bits.postgres.discount-code :as postgres.discount-code]
+   [bits.test.app :as t]
+   [clojure.test :refer [deftest is testing]]
+   [matcher-combinators.test])
+  (:import
+   (java.time OffsetDateTime ZoneOffset)))
+
+(def ^:private now
+  (OffsetDateTime/of 2026 10 16 12 0 0 0 ZoneOffset/UTC))
+
+(def ^:private mug
+  (random-uuid))
+
+(def ^:private poster
+  (random-uuid))
+
+(def ^:private basket
+  [{:product-id mug :currency "GBP" :amount 1000}
+   {:product-id poster :currency "GBP" :amount 2000}])
+
+(defn- code
+  [m]
+  (merge {::postgres.discount-code/code        "CODE"
+          ::postgres.discount-code/kind        "percent"
+          ::postgres.discount-code/product-ids []
+          ::postgres.discount-code/stackable   false}
+         m))
+
+(deftest stacking-takes-the-better-of-single-or-combined
+  (testing "percentages come off before fixed amounts"
+    (is (match? {:applied  ["TENOFF" "FIVER"]
+                 :discount 800
+                 :total    2200}
+                (sut/quote-basket basket
+                                  [(code {::postgres.discount-code/code        "FIVER"
+                                          ::postgres.discount-code/kind        "fixed"
+                                          ::postgres.discount-code/amount-off  500
+                                          ::postgres.discount-code/currency    "GBP"
+                                          ::postgres.discount-code/stackable   true})
+                                   (code {::postgres.discount-code/code        "TENOFF"
+                                          ::postgres.discount-code/percent-off 10
+                                          ::postgres.discount-code/stackable   true})]))))
+
+  (testing "a code that doesn't stack wins when it's worth more on its own"
+    (is (match? {:applied  ["HALF"]
+                 :discount 1500
+                 :total    1500}
+                (sut/quote-basket basket
+                                  [(code {::postgres.discount-code/code        "HALF"
+                                          ::postgres.discount-code/percent-off 50})
+                                   (code {::postgres.discount-code/code        "TENOFF"
+                                          ::postgres.discount-code/percent-off 10
+                                          ::postgres.discount-code/stackable   true})]))))
+
+  (testing "scoped codes only touch their products and never go below zero"
+    (is (match? {:applied  ["MUGS"]
+                 :discount 1000
+                 :lines    [{:product-id mug :discount 1000}
+                            {:product-id poster :discount 0}]}
+                (sut/quote-basket basket
+                                  [(code {::postgres.discount-code/code        "MUGS"
+                                          ::postgres.discount-code/kind        "fixed"
+                                          ::postgres.discount-code/amount-off  5000
+                                          ::postgres.discount-code/currency    "GBP"
+                                          ::postgres.discount-code/product-ids [mug]})])))))
+
+(deftest codes-are-refused-once-used-up
+  (t/with-system [{{:keys [discounts]} :service} (t/replace-clock (t/system) (atom now))]
+    (let [tenant-id (random-uuid)
+          create!   #(sut/create! discounts (merge {:tenant-id tenant-id :kind "percent" :percent-off 10} %))]
+      (is (uuid? (create! {:code " once " :max-redemptions 1})))
+      (is (uuid? (create! {:code "OLD" :expires-at (.minusDays now 1)})))
+      (is (uuid? (create! {:code "POSTERS" :product-ids [poster]})))
+      (is (= ::anom/conflict (::anom/category (create! {:code "Once"}))))
+      (is (= ::anom/incorrect (::anom/category (create! {:code "x"}))))
+
+      (is (= ::anom/incorrect (::anom/category (sut/validate discounts tenant-id ["NOPE"] basket))))
+      (is (= ::anom/incorrect (::anom/category (sut/validate discounts tenant-id ["old"] basket))))
+      (is (= ::anom/incorrect (::anom/category (sut/validate discounts tenant-id ["POSTERS"] (take 1 basket)))))
+
+      (let [priced (sut/validate discounts tenant-id ["once"] basket)]
+        (is (match? {:applied ["ONCE"] :discount 300} priced))
+        (is (= 1 (count (sut/redeem! discounts tenant-id priced))))
+        (is (= ::anom/conflict (::anom/category (sut/redeem! discounts tenant-id priced)))))
+      (is (= ::anom/incorrect (::anom/category (sut/validate discounts tenant-id ["ONCE"] basket))))
+
+      (let [id (::postgres.discount-code/id (first (sut/list-codes discounts tenant-id)))]
+        (is (true? (sut/disable! discounts tenant-id id)))
+        (is (false? (sut/disable! discounts tenant-id id)))
+        (is (= 2 (count (sut/list-codes discounts tenant-id))))))))
@@ -0,0 +1,39 @@
+(ns bits.egress-test
+  (:require
+   [bits.egress :as sut]
+   [clojure.test :refer [are deftest is]])
+  (:import
+   (java.net InetAddress)))
+
+(deftest public-address?
+  (are [in out] (= out (sut/public-address? (InetAddress/getByName in)))
+    "93.184.216.34"   true
+    "2606:4700::1111" true
+    "0.0.0.0"         false
+    "0.1.2.3"         false
+    "127.0.0.1"       false
+    "10.0.0.1"        false
+    "100.64.0.1"      false
+    "100.128.0.1"     true
+    "169.254.169.254" false
+    "172.16.0.1"      false
+    "192.168.1.1"     false
+    "224.0.0.1"       false
+    "::1"             false
+    "fd00::1"         false
+    "fe80::1"         false))
+
+(deftest public-url?
+  (are [in out] (= out (sut/public-url? in))
+    "https://93.184.216.34/hooks"              true
+    "http://93.184.216.34/hooks"               false
+    "https://127.0.0.1:8443/hooks"             false
+    "https://169.254.169.254/latest/meta-data" false
+    "https://[::1]/hooks"                      false
+    "https:///hooks"                           false
+    "not a url"                                false
+    nil                                        false))
+
+(deftest check!
+  (is (= "https://93.184.216.34/" (sut/check! "https://93.184.216.34/")))
+  (is (thrown? clojure.lang.ExceptionInfo (sut/check! "https://10.0.0.1/"))))
@@ -0,0 +1,17 @@
+(ns bits.environment-test
+  (:require
+   [bits.environment :as sut]
+   [clojure.test :refer [are deftest]]))
+
+(deftest only-production-is-public
+  (are [environment realm public?] (= public? (sut/public? environment realm))
+    :production  {}                   true
+    :production  {:tenant/demo? true} false
+    :staging     {}                   false
+    :development {}                   false))
+
+(deftest only-production-sends-email
+  (are [environment send?] (= send? (sut/send-email? environment))
+    :production  true
+    :staging     false
+    :development false))
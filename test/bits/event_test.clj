@@ -0,0 +1,52 @@
+(ns bits.event-test
+  (:require
+   [bits.clock :as clock]
+   [bits.event :as sut]
+   [clojure.core.async :as a]
+   [clojure.test :refer [deftest is]]
+   [com.stuartsierra.component :as component]
+   [matcher-combinators.test]))
+
+(def ^:private tenant-id
+  #uuid "8a3f2c1e-5b6d-4e7f-9a0b-1c2d3e4f5a6b")
+
+(defn- bus
+  []
+  (component/start (sut/make-bus {:buffer-size 8 :clock (clock/make-clock {})})))
+
+(deftest subscribers-get-their-topics
+  (let [bus        (bus)
+        pages      (sut/subscribe! bus "page.published" "pages" 8)
+        everything (sut/subscribe! bus sut/wildcard "audit" 8)
+        page-id    (random-uuid)]
+    (try
+      (is (= 3 (sut/publish! bus tenant-id nil "page.published" {:page-id page-id})))
+      (is (= 2 (sut/publish! bus tenant-id nil "tenant.suspended" {})))
+      (is (match? {:data {:page-id page-id} :tenant-id tenant-id :topic "page.published"}
+                  (a/poll! pages)))
+      (is (nil? (a/poll! pages)))
+      (is (= ["page.published" "tenant.suspended"]
+             [(:topic (a/poll! everything)) (:topic (a/poll! everything))]))
+      (finally
+        (component/stop bus)))))
+
+(deftest publishing-checks-the-event
+  (let [bus (bus)]
+    (try
+      (is (thrown? clojure.lang.ExceptionInfo
+                   (sut/publish! bus tenant-id nil "page.publsihed" {:page-id (random-uuid)})))
+      (is (thrown? clojure.lang.ExceptionInfo
+                   (sut/publish! bus tenant-id nil "page.published" {})))
+      (finally
+        (component/stop bus)))))
+
+(deftest slow-subscribers-drop-events
+  (let [bus  (bus)
+        slow (sut/subscribe! bus "tenant.suspended" "slow" 1)]
+    (try
+      (sut/publish! bus tenant-id nil "tenant.suspended" {})
+      (sut/publish! bus tenant-id nil "tenant.suspended" {})
+      (is (= {"slow" 1} (select-keys (sut/dropped bus) ["slow"])))
+      (is (some? (a/poll! slow)))
+      (finally
+        (component/stop bus)))))
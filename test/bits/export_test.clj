@@ -0,0 +1,67 @@
+(ns bits.export-test
+  (:require
+   [bits.datomic :as datomic]
+   [bits.export :as sut]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [charred.api :as json]
+   [clojure.string :as str]
+   [clojure.test :refer [deftest is]]
+   [com.stuartsierra.component :as component]
+   [datomic.api :as d]
+   [matcher-combinators.test])
+  (:import
+   (java.io StringWriter)
+   (java.time Instant)))
+
+(defn- export
+  [db dataset extension tenant-id range]
+  (let [w (StringWriter.)]
+    (sut/write! w dataset extension db tenant-id range)
+    (str w)))
+
+(deftest parse-range
+  (is (= {:from (Instant/parse "2026-01-01T00:00:00Z")
+          :to   (Instant/parse "2026-02-01T00:00:00Z")}
+         (sut/parse-range "2026-01-01..2026-02-01")))
+  (is (= {:from (Instant/parse "2026-01-01T00:00:00Z") :to nil}
+         (sut/parse-range "2026-01-01..")))
+  (is (nil? (sut/parse-range nil)))
+  (is (thrown? java.time.format.DateTimeParseException
+               (sut/parse-range "yesterday..today"))))
+
+(deftest products-csv-escapes-cells
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants]} (fixture/seed! service (fixture/with-products (fixture/tenant "acme") 1))
+          tenant-id         (get-in tenants ["acme" :tenant/id])
+          conn              (datomic/conn (:datomic service))
+          product-id        (d/q '[:find ?id . :where [_ :product/id ?id]] (d/db conn))]
+      @(d/transact conn [{:product/id product-id :product/title "Mug, \"large\""}])
+      (let [[header row & more] (str/split-lines (export (d/db conn) "products" "csv" tenant-id nil))]
+        (is (= "id,title,status,position,created-at" header))
+        (is (str/starts-with? row (str product-id ",\"Mug, \"\"large\"\"\",active,1,")))
+        (is (empty? more))))))
+
+(deftest members-json-is-scoped-to-the-tenant
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants users]} (fixture/seed! service
+                                                 (fixture/tenant "acme")
+                                                 (fixture/tenant "other")
+                                                 (-> (fixture/user "owner@example.com")
+                                                     (fixture/member-of "acme" :membership.role/owner))
+                                                 (-> (fixture/user "outsider@example.com")
+                                                     (fixture/member-of "other")))
+          db                      (d/db (datomic/conn (:datomic service)))]
+      (is (= [{"email" "owner@example.com"
+               "id"    (str (get-in users ["owner@example.com" :user/id]))
+               "role"  "owner"}]
+             (json/read-json (export db "members" "json" (get-in tenants ["acme" :tenant/id]) nil)))))))
+
+(deftest one-export-per-tenant
+  (let [exporter  (component/start (sut/make-exporter {}))
+        tenant-id (random-uuid)]
+    (is (true? (sut/acquire! exporter tenant-id)))
+    (is (false? (sut/acquire! exporter tenant-id)))
+    (is (true? (sut/acquire! exporter (random-uuid))))
+    (sut/release! exporter tenant-id)
+    (is (true? (sut/acquire! exporter tenant-id)))))
@@ -0,0 +1,82 @@
+(ns bits.flag-test
+  (:require
+   [bits.flag :as sut]
+   [bits.module.flag :as module.flag]
+   [bits.postgres.feature-flag :as postgres.feature-flag]
+   [bits.response]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [bits.test.snapshot :as snapshot]
+   [clojure.test :refer [are deftest is]]))
+
+(def ^:private tenant-id
+  #uuid "0e3c6b1a-8d4f-4b2e-9c7a-5f1d2e3b4a60")
+
+;;; ----------------------------------------------------------------------------
+;;; Evaluation
+
+(deftest bucket
+  (are [flag-name id out] (= out (sut/bucket flag-name id))
+    "checkout" tenant-id                                     19
+    "search"   tenant-id                                     64
+    "checkout" #uuid "00000000-0000-0000-0000-000000000000" 32)
+  (is (every? #(<= 0 (sut/bucket "checkout" %) 99) (repeatedly 200 random-uuid))))
+
+(deftest evaluate
+  (let [flag #::postgres.feature-flag{:name            "checkout"
+                                      :enabled         false
+                                      :rollout-percent 0}]
+    (is (false? (sut/evaluate nil nil tenant-id)))
+    (is (false? (sut/evaluate flag nil tenant-id)))
+    (is (true? (sut/evaluate flag true tenant-id)))
+    (is (true? (sut/evaluate (assoc flag ::postgres.feature-flag/enabled true) nil tenant-id)))
+    (is (false? (sut/evaluate (assoc flag ::postgres.feature-flag/enabled true) false tenant-id)))
+    (is (true? (sut/evaluate (assoc flag ::postgres.feature-flag/rollout-percent 100) nil tenant-id)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Persistence
+
+(deftest enabled?
+  (t/with-system [{:keys [flags]} (t/system)]
+    (is (false? (sut/enabled? flags tenant-id "checkout")))
+
+    (sut/save-flag! flags {:name "checkout" :enabled false :rollout-percent 0})
+    (is (false? (sut/enabled? flags tenant-id "checkout")))
+
+    (sut/set-override! flags "checkout" tenant-id true)
+    (is (true? (sut/enabled? flags tenant-id "checkout")))
+    (is (false? (sut/enabled? flags (random-uuid) "checkout")))
+
+    (sut/set-override! flags "checkout" tenant-id nil)
+    (sut/save-flag! flags {:name "checkout" :enabled true :rollout-percent 0})
+    (is (true? (sut/enabled? flags tenant-id "checkout")))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn- platform-request
+  "Renders requests on the platform realm as a seeded user, by email."
+  [service]
+  (let [{:keys [users]} (fixture/seed! service
+                                       (fixture/user "admin@example.com" {:user/platform-admin? true})
+                                       (fixture/user "someone@example.com"))]
+    (fn [email]
+      (snapshot/view-request service {:user-id (get-in users [email :user/id])}))))
+
+(deftest save
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [request (platform-request service)]
+      (are [email forbidden?] (= forbidden? (= bits.response/forbidden-response
+                                               (module.flag/save (request email))))
+        "admin@example.com"   false
+        "someone@example.com" true
+        nil                   true))))
+
+(deftest override
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [request (platform-request service)]
+      (are [email forbidden?] (= forbidden? (= bits.response/forbidden-response
+                                               (module.flag/override (request email))))
+        "admin@example.com"   false
+        "someone@example.com" true
+        nil                   true))))
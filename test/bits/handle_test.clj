@@ -0,0 +1,34 @@
+(ns bits.handle-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.handle :as sut]
+   [clojure.test :refer [are deftest is]]))
+
+(deftest normalize
+  (are [in out] (= out (sut/normalize in))
+    "Milly"  "milly"
+    " jcf "  "jcf"
+    "ｍｉｌｌｙ" "milly"))
+
+(deftest skeleton
+  (is (= (sut/skeleton "milly") (sut/skeleton "rnilly")))
+  (is (= (sut/skeleton "leather") (sut/skeleton "1eather")))
+  (is (= (sut/skeleton "jcf") (sut/skeleton "j-cf")))
+  (is (not= (sut/skeleton "milly") (sut/skeleton "molly"))))
+
+(deftest check
+  (are [handle existing category] (= category (::anom/category (sut/check sut/default-policy handle existing)))
+    "ab"         []        ::anom/incorrect
+    "-abc"       []        ::anom/incorrect
+    "abc-"       []        ::anom/incorrect
+    "a--b"       []        ::anom/incorrect
+    "xn--abc"    []        ::anom/incorrect
+    "under_bar"  []        ::anom/incorrect
+    "mïlly"      []        ::anom/incorrect
+    "admin"      []        ::anom/forbidden
+    "adm1n"      []        ::anom/forbidden
+    "rnilly"     ["milly"] ::anom/conflict
+    "milly"      ["milly"] ::anom/conflict)
+  (is (= "milly" (sut/check "Milly")))
+  (is (= "a-b-c" (sut/check sut/default-policy "a-b-c" ["jcf"])))
+  (is (= "admin" (sut/check (assoc sut/default-policy :reserved #{}) "admin" []))))
@@ -1,7 +1,8 @@
 (ns bits.identifier-test
   (:require
    [bits.identifier :as identifier]
-   [clojure.test :refer [are deftest]]
+   [clojure.string :as str]
+   [clojure.test :refer [are deftest is]]
    [clojure.test.check.clojure-test :refer [defspec]]
    [clojure.test.check.generators :as gen]
    [clojure.test.check.properties :as prop]))
@@ -25,12 +26,43 @@
     "not-a-uuid-at-all"                    nil
     "3c7rc6rbqke4pmmp74vsxvv15"            #uuid "3867b6f3-dbb0-4ef5-8078-364897154fd9"
     "3C7RC6RBQKE4PMMP74VSXVV15"            #uuid "3867b6f3-dbb0-4ef5-8078-364897154fd9"
+    "f5lxx1zz5pnorynqglhzmsp34"            nil
+    "zzzzzzzzzzzzzzzzzzzzzzzzz"            nil
+    "-3c7rc6rbqke4pmmp74vsxvv1"            nil
     "3867b6f3-dbb0-4ef5-8078-364897154fd9" #uuid "3867b6f3-dbb0-4ef5-8078-364897154fd9"))
 
 (defspec roundtrip
   (prop/for-all [uuid gen/uuid]
     (= uuid (-> uuid identifier/encode identifier/decode))))
 
+(defspec parse-roundtrip
+  (prop/for-all [s (gen/fmap str/join (gen/vector (gen/elements "0123456789abcdefghijklmnopqrstuvwxyz") 25))]
+    (let [uuid (identifier/parse s)]
+      (or (nil? uuid) (= s (identifier/encode uuid))))))
+
 (defspec pattern
   (prop/for-all [uuid gen/uuid]
     (re-matches #"[0-9a-z]{25}" (identifier/encode uuid))))
+
+;;; ----------------------------------------------------------------------------
+;;; Prefixed
+
+(deftest prefixed
+  (is (= "usr_3c7rc6rbqke4pmmp74vsxvv15"
+         (identifier/prefixed :user #uuid "3867b6f3-dbb0-4ef5-8078-364897154fd9"))))
+
+(deftest parse-prefixed
+  (are [kind in out] (= out (identifier/parse-prefixed kind in))
+    :user   "usr_3c7rc6rbqke4pmmp74vsxvv15"            #uuid "3867b6f3-dbb0-4ef5-8078-364897154fd9"
+    :user   "usr_3C7RC6RBQKE4PMMP74VSXVV15"            #uuid "3867b6f3-dbb0-4ef5-8078-364897154fd9"
+    :tenant "usr_3c7rc6rbqke4pmmp74vsxvv15"            nil
+    :user   "3c7rc6rbqke4pmmp74vsxvv15"                nil
+    :user   "usr_3867b6f3-dbb0-4ef5-8078-364897154fd9" nil
+    :user   "usr_zzzzzzzzzzzzzzzzzzzzzzzzz"            nil
+    :user   "usr_"                                     nil
+    :user   nil                                        nil))
+
+(defspec prefixed-roundtrip
+  (prop/for-all [kind (gen/elements (keys identifier/prefixes))
+                 uuid gen/uuid]
+    (= uuid (identifier/parse-prefixed kind (identifier/prefixed kind uuid)))))
@@ -0,0 +1,37 @@
+(ns bits.leader-test
+  (:require
+   [bits.leader :as sut]
+   [bits.test.app :as t]
+   [clojure.test :refer [deftest is]]
+   [com.stuartsierra.component :as component]))
+
+(defn- leader
+  [postgres]
+  (component/start (assoc (sut/make-leader {:lock-name "bits.test" :renew-seconds 3600})
+                          :postgres postgres)))
+
+(deftest one-leader-at-a-time
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [a       (leader (:postgres service))
+          b       (leader (:postgres service))
+          changes (atom [])]
+      (try
+        (sut/watch! b ::test #(swap! changes conj %))
+        (is (sut/leader? a))
+        (is (not (sut/leader? b)))
+        (is (false? (sut/renew! b)))
+        (is (true? (sut/renew! a)))
+        (component/stop a)
+        (is (true? (sut/renew! b)))
+        (is (= [true] @changes))
+        (finally
+          (component/stop a)
+          (component/stop b))))))
+
+(deftest with-lock
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [postgres (:postgres service)]
+      (is (= :ran (sut/with-lock postgres "bits.test" (constantly :ran))))
+      (is (= [nil :ran]
+             (sut/with-lock postgres "bits.test"
+               #(vector (sut/with-lock postgres "bits.test" (constantly :inner)) :ran)))))))
@@ -0,0 +1,36 @@
+(ns bits.log.tail-test
+  (:require
+   [bits.log.tail :as sut]
+   [clojure.test :refer [deftest is]]
+   [com.stuartsierra.component :as component]
+   [io.pedestal.log :as log]
+   [matcher-combinators.test]))
+
+(defmacro ^:private with-tail
+  [[binding config] & body]
+  `(let [~binding (component/start (sut/make-tail ~config))]
+     (try
+       ~@body
+       (finally
+         (component/stop ~binding)))))
+
+(deftest keeps-the-latest-redacted-entries
+  (with-tail [tail {:capacity 2 :refresh-ms 10}]
+    (let [tenant-id (random-uuid)]
+      (log/info :msg "First")
+      (log/info :msg "Second" :email "ada@example.com")
+      (log/warn :msg "Third" :password "hunter2" :tenant-id tenant-id)
+      (is (match? [{:level     :warn
+                    :logger    "bits.log.tail-test"
+                    :message   "Third"
+                    :data      {:password "[redacted]"}
+                    :tenant-id (str tenant-id)}
+                   {:level   :info
+                    :message "Second"
+                    :data    {:email "a***@example.com"}}]
+                  (sut/entries tail)))
+      (is (match? [{:message "Third"}]
+                  (sut/entries tail {:level :warn})))
+      (is (match? [{:message "Third"}]
+                  (sut/entries tail {:tenant-id (str tenant-id)})))
+      (is (= [] (sut/entries tail {:logger "bits.outbox"}))))))
@@ -0,0 +1,30 @@
+(ns bits.log-test
+  (:require
+   [bits.log :as sut]
+   [charred.api :as json]
+   [clojure.test :refer [deftest is]]))
+
+(deftest redaction
+  (is (= {:msg       "Login failed."
+          :email     "a***@example.com"
+          :password  "[redacted]"
+          :headers   {:authorization "[redacted]"}
+          :exception "Bearer [redacted] rejected for j***@example.org"
+          :user-id   "ada@example.com"}
+         (sut/redact {:msg       "Login failed."
+                      :email     "ada@example.com"
+                      :password  "hunter2"
+                      :headers   {:authorization "Bearer abc.def"}
+                      :exception "Bearer abc.def rejected for jo@example.org"
+                      :user-id   "ada@example.com"}))))
+
+(deftest events-are-json
+  (let [tenant-id (random-uuid)]
+    (is (= {"msg"       "Event"
+            "topic"     "page.published"
+            "tenant-id" (str tenant-id)
+            "kind"      "bits.event/page"}
+           (json/read-json (sut/format-event {:msg       "Event"
+                                              :topic     "page.published"
+                                              :tenant-id tenant-id
+                                              :kind      :bits.event/page}))))))
@@ -0,0 +1,25 @@
+(ns bits.maintenance-test
+  (:require
+   [bits.flag :as flag]
+   [bits.maintenance :as sut]
+   [bits.test.app :as t]
+   [clojure.test :refer [deftest is]]))
+
+(def ^:private tenant-id
+  #uuid "4c1f0e2a-7b3d-4e5f-8a9b-0c1d2e3f4a5b")
+
+(deftest tenants-switch-their-own-storefront
+  (t/with-system [{:keys [flags]} (t/system)]
+    (is (false? (sut/on? flags tenant-id)))
+    (sut/switch! flags tenant-id true)
+    (is (true? (sut/on? flags tenant-id)))
+    (is (true? (sut/switched-off? flags tenant-id)))
+    (sut/switch! flags tenant-id false)
+    (is (false? (sut/on? flags tenant-id)))
+    (is (false? (sut/switched-off? flags tenant-id)))))
+
+(deftest platform-maintenance-covers-everyone
+  (t/with-system [{:keys [flags]} (t/system)]
+    (flag/save-flag! flags {:name sut/flag-name :enabled true :rollout-percent 0})
+    (is (true? (sut/on? flags tenant-id)))
+    (is (false? (sut/switched-off? flags tenant-id)))))
@@ -0,0 +1,30 @@
+(ns bits.meta-test
+  (:require
+   [bits.meta :as sut]
+   [clojure.test :refer [deftest is]]))
+
+(defn- flatten-tags
+  [tags]
+  (mapcat #(if (seq? %) (flatten-tags %) [%]) tags))
+
+(defn- content
+  [tags property]
+  (some (fn [[_ attrs]]
+          (when (= property (or (:property attrs) (:name attrs)))
+            (:content attrs)))
+        (remove nil? (flatten-tags tags))))
+
+(deftest tags-describe-the-page
+  (let [tags (sut/tags {:bits/page {:page/description (apply str (repeat 300 "a"))
+                                    :page/image       "/og.png"
+                                    :page/title       "Acme"}
+                        :headers   {"host" "acme.example.com"}
+                        :uri       "/"})]
+    (is (= "Acme" (content tags "og:title")))
+    (is (= "https://acme.example.com/og.png" (content tags "og:image")))
+    (is (= "summary_large_image" (content tags "twitter:card")))
+    (is (= 200 (count (content tags "og:description"))))))
+
+(deftest cards-are-pngs
+  (let [png (sut/card {:subtitle "@acme" :title "A title long enough to wrap onto more than one line of the card"})]
+    (is (= [-119 80 78 71] (take 4 png)))))
@@ -0,0 +1,75 @@
+(ns bits.middleware-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.middleware :as sut]
+   [bits.postgres :as postgres]
+   [bits.response]
+   [bits.test.app :as t]
+   [clojure.test :refer [are deftest is testing]]))
+
+;;; ----------------------------------------------------------------------------
+;;; Transactions
+
+(defrecord Component [postgres])
+
+(defn- write-then
+  "A handler that writes a session through the request's postgres, then answers
+  with result."
+  [result]
+  (fn [request]
+    (postgres/execute! (sut/request->postgres request)
+                       {:insert-into [:sessions]
+                        :values      [{:sid-hash  (str (random-uuid))
+                                       :tenant-id #uuid "00000000-0000-0000-0000-000000000001"}]})
+    result))
+
+(defn- session-count
+  [postgres]
+  (:count (postgres/execute-one! postgres {:select [[:%count.* :count]] :from [:sessions]})))
+
+(deftest wrap-transaction-commits-only-success
+  (t/with-system [{:keys [postgres]} (t/system)]
+    (are [result written] (let [before (session-count postgres)]
+                            ((sut/wrap-transaction (write-then result)) {::sut/state {:postgres postgres}})
+                            (= written (- (session-count postgres) before)))
+      {:status 200}                            1
+      {:status 303}                            1
+      nil                                      1
+      {:status 422}                            0
+      {:status 500}                            0
+      (anom/incorrect {::anom/message "Nope"}) 0)))
+
+(deftest wrap-transaction-covers-components
+  (t/with-system [{:keys [postgres]} (t/system)]
+    (let [before  (session-count postgres)
+          handler (sut/wrap-transaction
+                   (fn [request]
+                     ((write-then nil) {::sut/state {:postgres (get-in request [::sut/state :thing :postgres])}})
+                     {:status 500}))]
+      (handler {::sut/state {:postgres postgres :thing (->Component postgres)}})
+      (is (= before (session-count postgres))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Residency
+
+(deftest wrap-residency
+  (let [postgres {:datasource :primary
+                  :region     "eu"
+                  :regional   {"eu" {:datasource :eu}}}
+        handler  (sut/wrap-residency
+                  (fn [request]
+                    {:status 200
+                     :body   [(postgres/->connectable (sut/request->postgres request))
+                              (postgres/->connectable (get-in request [::sut/state :thing :postgres]))]}))
+        request  (fn [region]
+                   {::sut/state    {:postgres postgres :thing (->Component postgres)}
+                    :session/realm (cond-> {:tenant/id (random-uuid)}
+                                     region (assoc :tenant/region region))})]
+    (testing "untagged tenants stay on the primary"
+      (is (= [:primary :primary] (:body (handler (request nil))))))
+
+    (testing "tenants in this region reach its database"
+      (is (= [:eu :eu] (:body (handler (request "eu"))))))
+
+    (testing "tenants in other regions are refused"
+      (is (= bits.response/misdirected-response (handler (request "us")))))))
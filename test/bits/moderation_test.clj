@@ -0,0 +1,88 @@
+(ns bits.moderation-test
+  (:require
+   [bits.datomic :as datomic]
+   [bits.moderation :as sut]
+   [bits.module.moderation :as module.moderation]
+   [bits.postgres.report :as postgres.report]
+   [bits.postgres.review :as postgres.review]
+   [bits.response]
+   [bits.review :as review]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [bits.test.snapshot :as snapshot]
+   [clojure.string :as str]
+   [clojure.test :refer [are deftest is]]
+   [datomic.api :as d]
+   [matcher-combinators.test]))
+
+(deftest screening
+  (is (nil? (sut/screen {:text "A lovely mug. Holds tea." :title "Mugs"})))
+  (is (match? ["impersonation" string?] (sut/screen {:title "Official Support"})))
+  (is (match? ["spam" string?] (sut/screen {:text (str/join " " (repeat 4 "https://example.com"))})))
+  (is (match? ["spam" string?] (sut/screen {:text "Buy nowwwwwwwwww"})))
+  (is (match? ["spam" string?] (sut/screen {:text "THIS IS THE BEST MUG YOU WILL EVER OWN"}))))
+
+(defn- seed!
+  [service]
+  (let [{:keys [tenants users]} (fixture/seed! service
+                                               (fixture/with-products (fixture/tenant "acme") 1)
+                                               (fixture/user "buyer@example.com")
+                                               (fixture/user "reporter@example.com")
+                                               (fixture/user "moderator@example.com"))
+        conn                    (datomic/conn (:datomic service))]
+    {:conn         conn
+     :buyer-id     (get-in users ["buyer@example.com" :user/id])
+     :moderator-id (get-in users ["moderator@example.com" :user/id])
+     :product-id   (d/q '[:find ?id . :where [_ :product/id ?id]] (d/db conn))
+     :reporter-id  (get-in users ["reporter@example.com" :user/id])
+     :tenant-id    (get-in tenants ["acme" :tenant/id])}))
+
+(deftest hiding-a-review
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [buyer-id conn moderator-id product-id reporter-id tenant-id]} (seed! service)
+          moderator                                                             (:moderator service)]
+      @(d/transact conn [{:product/id       product-id
+                          :product/variants [{:db/id "variant" :variant/id (random-uuid)}]}
+                         {:line-item/id      (random-uuid)
+                          :line-item/variant "variant"
+                          :line-item/buyer   [:user/id buyer-id]}])
+      (let [review-id (review/submit! (:reviews service) (d/db conn)
+                                      {:author-id buyer-id :product-id product-id :rating 1 :tenant-id tenant-id})
+            report    {:reason      "abuse"
+                       :reporter-id reporter-id
+                       :target-id   review-id
+                       :target-type "review"
+                       :tenant-id   tenant-id}
+            report-id (sut/file! moderator report)]
+        (is (= report-id (sut/file! moderator report)))
+        (is (= [report-id] (map ::postgres.report/id (:items (sut/queue moderator {:limit 10})))))
+        (sut/hide! moderator (sut/lookup moderator report-id) moderator-id)
+        (is (empty? (:items (sut/queue moderator {:limit 10}))))
+        (is (some? (::postgres.review/hidden-at (review/lookup (:reviews service) tenant-id review-id))))
+        (is (empty? (:items (review/list-reviews (:reviews service) tenant-id product-id {:limit 10}))))))))
+
+(deftest suspending-and-reinstating
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [conn moderator-id reporter-id tenant-id]} (seed! service)
+          moderator                                         (:moderator service)
+          report-id                                         (sut/file! moderator {:reason      "illegal"
+                                                                                  :reporter-id reporter-id
+                                                                                  :target-id   tenant-id
+                                                                                  :target-type "tenant"
+                                                                                  :tenant-id   tenant-id})]
+      (sut/suspend! moderator (sut/lookup moderator report-id) moderator-id)
+      (is (= [tenant-id] (map :tenant/id (sut/suspended-tenants (d/db conn)))))
+      (is (true? (sut/reinstate! moderator tenant-id moderator-id)))
+      (is (empty? (sut/suspended-tenants (d/db conn))))
+      (is (nil? (sut/reinstate! moderator tenant-id moderator-id))))))
+
+(deftest reinstate
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [users]} (fixture/seed! service
+                                         (fixture/user "admin@example.com" {:user/platform-admin? true})
+                                         (fixture/user "someone@example.com"))
+          request         #(snapshot/view-request service {:user-id (get-in users [% :user/id])})]
+      (are [email response] (= response (module.moderation/reinstate (request email)))
+        "admin@example.com"   bits.response/not-found-response
+        "someone@example.com" bits.response/forbidden-response
+        nil                   bits.response/forbidden-response))))
@@ -0,0 +1,35 @@
+(ns bits.module-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.module :as sut]
+   [bits.service :as service]
+   [clojure.test :refer [deftest is]]
+   [matcher-combinators.test]))
+
+(deftest route-table
+  (is (= [{:method :get :module :bits.module/a :path "/api/things" :scopes #{:read}}
+          {:method :put :module :bits.module/a :path "/api/things" :scopes #{:write}}
+          {:method :get :module :bits.module/a :path "/things" :scopes nil}]
+         (sut/route-table [{:name   :bits.module/a
+                            :routes [["/api/things" {:bits/scopes #{:read}
+                                                     :get         {:handler identity}
+                                                     :put         {:bits/scopes #{:write}
+                                                                   :handler     identity}}]
+                                     ["/things" {:get identity}]]}]))))
+
+(deftest route-roots
+  (is (= #{"api" "products" "og"}
+         (sut/route-roots [["/" {:get identity}]
+                           ["/api/things" {:get identity}]
+                           ["/products/:id" {:get identity}]
+                           ["/og/{slug}.png" {:get identity}]]))))
+
+(deftest combining-checks-routes
+  (is (match? {::anom/category ::anom/incorrect
+               :duplicates     {"/things" [:bits.module/a :bits.module/b]}}
+              (sut/combine-modules [{:name :bits.module/a :actions {} :routes [["/things" {:get identity}]]}
+                                    {:name :bits.module/b :actions {} :routes [["/things" {:get identity}]]}])))
+  (is (match? {::anom/category ::anom/incorrect
+               :routes         [{:method :post :path "/api/things"}]}
+              (sut/combine-modules [{:name :bits.module/a :actions {} :routes [["/api/things" {:post identity}]]}])))
+  (is (not (anom/anomaly? (sut/combine-modules service/modules)))))
@@ -0,0 +1,46 @@
+(ns bits.notification-test
+  (:require
+   [bits.notification :as sut]
+   [bits.test.app :as t]
+   [clojure.core.async :as a]
+   [clojure.test :refer [deftest is]]
+   [matcher-combinators.test]))
+
+(def ^:private tenant-id
+  #uuid "5b7c2a8e-3f0d-4d1e-9a6b-2c4e8f1a7d30")
+
+(def ^:private user-id
+  #uuid "0d9f4e1c-7a2b-4c8d-9e3f-1a2b3c4d5e6f")
+
+(deftest notify-and-read
+  (t/with-system [{:keys [notifications]} (t/system)]
+    (let [id (sut/notify! notifications tenant-id user-id "api-key.issued" {:name "CI"})]
+      (sut/notify! notifications tenant-id user-id "api-key.revoked" {:name "Old"})
+      (sut/notify! notifications (random-uuid) user-id "api-key.issued" {:name "Elsewhere"})
+      (is (= 2 (sut/unread-count notifications tenant-id user-id)))
+      (is (match? [{:bits.postgres.notification/kind "api-key.revoked"}
+                   {:bits.postgres.notification/data {:name "CI"}
+                    :bits.postgres.notification/id   id
+                    :bits.postgres.notification/kind "api-key.issued"}]
+                  (:items (sut/list-notifications notifications tenant-id user-id {:limit 10}))))
+      (is (= 1 (sut/mark-read! notifications tenant-id user-id id)))
+      (is (= 0 (sut/mark-read! notifications tenant-id user-id id)))
+      (is (= 1 (sut/unread-count notifications tenant-id user-id)))
+      (is (= 1 (sut/mark-read! notifications tenant-id user-id nil)))
+      (is (= 0 (sut/unread-count notifications tenant-id user-id))))))
+
+(deftest notify-all!
+  (t/with-system [{:keys [notifications]} (t/system)]
+    (let [other-id (random-uuid)]
+      (is (= 2 (count (sut/notify-all! notifications tenant-id #{user-id other-id}
+                                       "member.joined" {:email "new@example.com"}))))
+      (is (= 1 (sut/unread-count notifications tenant-id user-id)))
+      (is (= 1 (sut/unread-count notifications tenant-id other-id)))
+      (is (= [] (sut/notify-all! notifications tenant-id #{} "member.joined" {:email "new@example.com"}))))))
+
+(deftest notify-signals-listeners
+  (t/with-system [{:keys [notifications]} (t/system)]
+    (let [ch (a/chan 1)]
+      (sut/tap! notifications ch)
+      (sut/notify! notifications tenant-id user-id "api-key.issued" {:name "CI"})
+      (is (= user-id (a/alt!! ch ([v] v) (a/timeout 1000) ::timeout))))))
@@ -0,0 +1,49 @@
+(ns bits.outbox-test
+  (:require
+   [bits.event :as event]
+   [bits.outbox :as sut]
+   [bits.postgres :as postgres]
+   [bits.test.app :as t]
+   [clojure.core.async :as a]
+   [clojure.test :refer [deftest is]]
+   [com.stuartsierra.component :as component]
+   [matcher-combinators.test]))
+
+(def ^:private tenant-id
+  #uuid "3e9d1b7a-6c2f-4a8e-b5d0-7f1c9e2a4b63")
+
+(defn- relay
+  [{:keys [clock events postgres webhooks]}]
+  ;; Hour-long polls so the test drives relaying itself.
+  (component/start (assoc (sut/make-relay {:batch-size 10 :poll-seconds 3600})
+                          :clock    clock
+                          :events   events
+                          :postgres postgres
+                          :webhooks webhooks)))
+
+(deftest relays-each-event-once
+  (t/with-system [system (t/system)]
+    (let [relay   (relay system)
+          pages   (event/subscribe! (:events relay) "page.published" "test" 8)
+          page-id (random-uuid)]
+      (try
+        (let [id (sut/enqueue! (:postgres relay) tenant-id nil "page.published" {:page-id page-id})]
+          (is (nil? (a/poll! pages)))
+          (is (= 1 (sut/relay! relay)))
+          (is (match? {:data {:id id :page-id page-id} :tenant-id tenant-id :topic "page.published"}
+                      (a/poll! pages)))
+          (is (= 0 (sut/relay! relay)))
+          (is (nil? (a/poll! pages)))
+          (is (some? (:bits.postgres.outbox/published-at
+                      (postgres/execute-one! (:postgres relay)
+                                             {:select [:published-at]
+                                              :from   [:outbox]
+                                              :where  [:= :id id]})))))
+        (finally
+          (event/unsubscribe! (:events relay) pages)
+          (component/stop relay))))))
+
+(deftest enqueue-checks-the-event
+  (t/with-system [{:keys [service]} (t/system)]
+    (is (thrown? clojure.lang.ExceptionInfo
+                 (sut/enqueue! (:postgres service) tenant-id nil "page.published" {})))))
@@ -0,0 +1,70 @@
+(ns bits.page-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.datomic :as datomic]
+   [bits.deletion :as deletion]
+   [bits.page :as sut]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [clojure.test :refer [deftest is]]
+   [datomic.api :as d]
+   [matcher-combinators.test])
+  (:import
+   (java.util Date)))
+
+(defn- fields
+  [p]
+  (select-keys p [:page/slug :page/status :page/title]))
+
+(deftest blocks-are-checked
+  (is (= [{:text "About" :type "heading"}
+          {:text "Shop" :type "link" :url "/"}]
+         (sut/parse-blocks "[{\"type\": \"heading\", \"text\": \"About\", \"size\": 9},
+                             {\"type\": \"link\", \"url\": \"/\", \"text\": \"Shop\"}]")))
+  (is (= [] (sut/parse-blocks "")))
+  (is (match? {::anom/category ::anom/incorrect}
+              (sut/parse-blocks "{\"type\": \"heading\"}")))
+  (is (match? {::anom/category ::anom/incorrect}
+              (sut/parse-blocks "[{\"type\": \"marquee\", \"text\": \"Hi\"}]")))
+  (is (match? {::anom/category ::anom/incorrect}
+              (sut/parse-blocks "[{\"type\": \"link\", \"url\": \"javascript:alert(1)\", \"text\": \"Hi\"}]")))
+  (is (match? {::anom/category ::anom/incorrect}
+              (sut/parse-blocks "[{\"type\": \"image\", \"url\": \"//evil.example\"}]"))))
+
+(deftest create-edit-and-publish
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants]} (fixture/seed! service (fixture/tenant "acme"))
+          tenant-id         (get-in tenants ["acme" :tenant/id])
+          conn              (datomic/conn (:datomic service))
+          p                 (sut/create! conn tenant-id {:blocks "[]" :slug "About" :title "About us"} (Date.))]
+      (is (= {:page/slug "about" :page/status :page.status/draft :page/title "About us"}
+             (fields p)))
+      (is (match? {::anom/category ::anom/incorrect}
+                  (sut/create! conn tenant-id {:blocks "[]" :slug "about" :title "Another"} (Date.))))
+      (is (match? {::anom/category ::anom/incorrect}
+                  (sut/create! conn tenant-id {:blocks "[]" :slug "login" :title "Login"} (Date.))))
+
+      (let [p (sut/update! conn tenant-id p {:blocks "[{\"type\": \"paragraph\", \"text\": \"Hello\"}]"
+                                             :slug   "about"
+                                             :title  "About"})]
+        (is (= [{:text "Hello" :type "paragraph"}] (sut/blocks p)))
+        @(d/transact conn (sut/publish-tx p (Date.))))
+      (is (sut/published? (sut/by-slug (d/db conn) tenant-id "about"))))))
+
+(deftest pages-are-scoped-to-the-tenant
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants users]} (fixture/seed! service
+                                                 (fixture/tenant "acme")
+                                                 (fixture/tenant "other")
+                                                 (fixture/user "owner@example.com"))
+          conn                    (datomic/conn (:datomic service))
+          acme-id                 (get-in tenants ["acme" :tenant/id])
+          other-id                (get-in tenants ["other" :tenant/id])
+          p                       (sut/create! conn acme-id {:blocks "[]" :slug "about" :title "About"} (Date.))]
+      (is (nil? (sut/by-slug (d/db conn) other-id "about")))
+      (is (= {:page/slug "about" :page/status :page.status/draft :page/title "Other"}
+             (fields (sut/create! conn other-id {:blocks "[]" :slug "about" :title "Other"} (Date.)))))
+
+      @(d/transact conn (deletion/delete-tx p (get-in users ["owner@example.com" :user/id]) (Date.)))
+      (is (nil? (sut/by-slug (d/db conn) acme-id "about")))
+      (is (empty? (sut/list-pages (d/db conn) acme-id))))))
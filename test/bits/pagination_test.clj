@@ -0,0 +1,61 @@
+(ns bits.pagination-test
+  (:require
+   [bits.pagination :as sut]
+   [clojure.test :refer [are deftest is]]
+   [honey.sql :as sql])
+  (:import
+   (java.time Instant)))
+
+(def ^:private ordering
+  {:direction :desc
+   :columns   [[:created-at :thing/created-at]
+               [:id :thing/id]]})
+
+;;; ----------------------------------------------------------------------------
+;;; Requests
+
+(deftest page-request
+  (are [params expected] (= expected (sut/page-request params))
+    {}                              {:cursor nil :limit 25}
+    {"limit" "10"}                  {:cursor nil :limit 10}
+    {"limit" "0"}                   {:cursor nil :limit 1}
+    {"limit" "5000"}                {:cursor nil :limit 100}
+    {"cursor" "abc" "limit" "nope"} {:cursor "abc" :limit 25}
+    {"cursor" ""}                   {:cursor nil :limit 25}))
+
+;;; ----------------------------------------------------------------------------
+;;; Cursors
+
+(deftest cursor-roundtrip
+  (let [values [(Instant/parse "2026-10-16T12:00:00.123456Z")
+                #uuid "3867b6f3-dbb0-4ef5-8078-364897154fd9"
+                42
+                "title"]]
+    (is (= values (sut/decode-cursor (sut/encode-cursor values))))
+    (is (nil? (sut/decode-cursor "not a cursor")))))
+
+;;; ----------------------------------------------------------------------------
+;;; Queries
+
+(deftest paginate
+  (let [query {:select [:*] :from [:things] :where [:= :tenant-id 1]}]
+    (is (= ["SELECT * FROM things WHERE tenant_id = ? ORDER BY created_at DESC, id DESC LIMIT ?" 1 3]
+           (sql/format (sut/paginate query ordering {:limit 2}))))
+    (let [instant (Instant/parse "2026-10-16T12:00:00Z")
+          id      #uuid "3867b6f3-dbb0-4ef5-8078-364897154fd9"
+          cursor  (sut/encode-cursor [instant id])]
+      (is (= [(str "SELECT * FROM things WHERE (tenant_id = ?) AND ((created_at, id) < (?, ?))"
+                   " ORDER BY created_at DESC, id DESC LIMIT ?")
+              1 instant id 3]
+             (sql/format (sut/paginate query ordering {:cursor cursor :limit 2})))))))
+
+(deftest page
+  (let [rows [{:thing/created-at (Instant/parse "2026-10-16T12:00:03Z") :thing/id #uuid "00000000-0000-0000-0000-000000000003"}
+              {:thing/created-at (Instant/parse "2026-10-16T12:00:02Z") :thing/id #uuid "00000000-0000-0000-0000-000000000002"}
+              {:thing/created-at (Instant/parse "2026-10-16T12:00:01Z") :thing/id #uuid "00000000-0000-0000-0000-000000000001"}]]
+    (is (= {:items rows :next-cursor nil}
+           (sut/page rows ordering {:limit 3})))
+    (let [{:keys [items next-cursor]} (sut/page rows ordering {:limit 2})]
+      (is (= (take 2 rows) items))
+      (is (= [(Instant/parse "2026-10-16T12:00:02Z") #uuid "00000000-0000-0000-0000-000000000002"]
+             (sut/decode-cursor next-cursor))))))
@@ -0,0 +1,123 @@
+(ns bits.plugin-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.plugin :as sut]
+   [bits.postgres.plugin :as postgres.plugin]
+   [bits.test.app :as t]
+   [charred.api :as json]
+   [clojure.string :as str]
+   [clojure.test :refer [are deftest is testing]]
+   [matcher-combinators.test])
+  (:import
+   (com.dylibso.chicory.wabt Wat2Wasm)))
+
+;;; ----------------------------------------------------------------------------
+;;; Modules
+
+(defn- data
+  "A data segment holding x as JSON at offset."
+  [offset x]
+  (format "(data (i32.const %d) \"%s\")" offset (str/replace (json/write-json-str x) "\"" "\\22")))
+
+(defn- answer
+  "Returns the JSON at offset, packed as the host API wants."
+  [offset x]
+  (format "(i64.const %d)" (bit-or (bit-shift-left offset 32) (count (json/write-json-str x)))))
+
+(def ^:private adjustments
+  {:adjustments [{:label "Loyalty" :amount -250}]})
+
+(def ^:private blocks
+  {:blocks [{:type "paragraph" :text "Hello from a plugin"}]})
+
+(def ^:private headers
+  {:headers {:x-plugin "yes" :set-cookie "a=b"}})
+
+(defn- wasm
+  ^bytes [& {:keys [checkout version] :or {version 1}}]
+  (Wat2Wasm/parse
+   ^String
+   (str "(module
+           (import \"bits\" \"log\" (func $log (param i32 i32)))
+           (memory (export \"memory\") 1)"
+        (data 0 adjustments)
+        (data 256 blocks)
+        (data 512 headers)
+        (data 768 "hello")
+        "(func (export \"bits_api_version\") (result i32) (i32.const " version "))
+         (func (export \"bits_alloc\") (param i32) (result i32) (i32.const 1024))
+         (func (export \"bits_on_checkout\") (param i32 i32) (result i64)"
+        (or checkout (str "(call $log (i32.const 768) (i32.const 7))" (answer 0 adjustments)))
+        ")
+         (func (export \"bits_render_slot\") (param i32 i32) (result i64)" (answer 256 blocks) ")
+         (func (export \"bits_on_request\") (param i32 i32) (result i64)" (answer 512 headers) "))")))
+
+(def ^:private totals
+  {:subtotal 1000 :discount 0 :shipping 200 :total 1200})
+
+;;; ----------------------------------------------------------------------------
+;;; Installing
+
+(deftest inspect
+  (t/with-system [{:keys [plugins]} (t/system)]
+    (are [bs reason] (= reason (::sut/reason (sut/inspect plugins bs)))
+      (byte-array 0)                                          :empty
+      (.getBytes "not wasm")                                  :not-wasm
+      (Wat2Wasm/parse "(module)")                             :missing-exports
+      (Wat2Wasm/parse "(module (import \"env\" \"f\" (func)))") :unknown-import
+      (wasm :version 2)                                       :wrong-version)
+    (is (= #{"checkout" "log" "request" "slot"} (sut/requested (sut/inspect plugins (wasm)))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Hooks
+
+(deftest hooks
+  (t/with-system [{:keys [plugins]} (t/system)]
+    (let [tenant-id (random-uuid)
+          install!  #(sut/install! plugins tenant-id %1 (wasm) %2)
+          p         (install! "everything" ["checkout" "log" "request" "slot"])]
+      (testing "disabled plugins don't run"
+        (is (= [] (:adjustments (sut/adjust-totals plugins tenant-id "GBP" totals)))))
+
+      (sut/enable! plugins tenant-id (::postgres.plugin/id p) true)
+
+      (testing "checkout"
+        (is (= {:adjustments [{:label "Loyalty" :amount -250}] :total 950}
+               (select-keys (sut/adjust-totals plugins tenant-id "GBP" totals) [:adjustments :total]))))
+
+      (testing "slots only take blocks that check out"
+        (is (= [{:type "paragraph" :text "Hello from a plugin"}]
+               (sut/render-slot plugins tenant-id "page.after-content" {:slug "about" :title "About"}))))
+
+      (testing "only x- headers"
+        (is (= {"x-plugin" "yes"}
+               (sut/on-request plugins tenant-id {:request-method :get :uri "/about"}))))
+
+      (testing "other tenants' plugins never run"
+        (is (= {} (sut/on-request plugins (random-uuid) {:request-method :get :uri "/about"})))))))
+
+(deftest capabilities
+  (t/with-system [{:keys [plugins]} (t/system)]
+    (let [tenant-id (random-uuid)
+          p         (sut/install! plugins tenant-id "checkout only" (wasm) ["checkout" "nonsense"])]
+      (is (= ["checkout"] (::postgres.plugin/capabilities p)))
+      (sut/enable! plugins tenant-id (::postgres.plugin/id p) true)
+      (is (= [] (sut/render-slot plugins tenant-id "page.after-content" {})))
+
+      (testing "host functions it wasn't allowed trap"
+        (is (= 1200 (:total (sut/adjust-totals plugins tenant-id "GBP" totals))))
+        (is (match? [{::postgres.plugin/last-error string?}] (sut/list-plugins plugins tenant-id)))))))
+
+(deftest limits
+  (t/with-system [{:keys [plugins]} (t/system)]
+    (let [tenant-id (random-uuid)
+          p         (sut/install! plugins tenant-id "spinner"
+                                  (wasm :checkout "(loop $l (br $l)) (i64.const 0)")
+                                  ["checkout"])]
+      (sut/enable! plugins tenant-id (::postgres.plugin/id p) true)
+      (is (= totals (dissoc (sut/adjust-totals plugins tenant-id "GBP" totals) :adjustments)))
+      (is (match? [{::postgres.plugin/last-error "Plugin ran out of time"}] (sut/list-plugins plugins tenant-id))))
+
+    (testing "modules over the size limit"
+      (is (= ::anom/incorrect
+             (::anom/category (sut/install! (assoc plugins :max-bytes 16) (random-uuid) "big" (wasm) [])))))))
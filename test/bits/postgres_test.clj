@@ -2,7 +2,7 @@
   (:require
    [bits.postgres :as sut]
    [bits.test.app :as t]
-   [clojure.test :refer [deftest is]]
+   [clojure.test :refer [are deftest is]]
    [honey.sql :as sql]))
 
 ;;; ----------------------------------------------------------------------------
@@ -32,6 +32,49 @@
   (is (= ["make_interval(days => CAST(? AS INTEGER))" 30]
          (sql/format [:make-interval :days 30]))))
 
+;;; ----------------------------------------------------------------------------
+;;; Replica
+
+(deftest replica
+  (let [postgres {:datasource :primary :read-datasource :replica}]
+    (is (= :replica (sut/->connectable (sut/replica postgres))))
+    (is (= :primary (sut/->connectable (sut/replica (dissoc postgres :read-datasource)))))
+    (is (= :tx (sut/->connectable (sut/replica (sut/assoc-conn postgres :tx)))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Regions
+
+(def ^:private regional-postgres
+  {:datasource :primary
+   :region     "eu"
+   :regional   {"eu" {:datasource :eu :read-datasource :eu-replica}}})
+
+(deftest serves?
+  (are [postgres region out] (= out (sut/serves? postgres region))
+    regional-postgres                  nil  true
+    regional-postgres                  "eu" true
+    regional-postgres                  "us" false
+    (dissoc regional-postgres :region) "eu" false))
+
+(deftest regional
+  (let [postgres regional-postgres]
+    (is (= :eu (sut/->connectable (sut/regional postgres "eu"))))
+    (is (= :eu-replica (sut/->connectable (sut/replica (sut/regional postgres "eu")))))
+    (is (= :primary (sut/->connectable (sut/regional (dissoc postgres :regional) "eu"))))
+    (is (= :tx (sut/->connectable (sut/regional (sut/assoc-conn postgres :tx) "eu"))))))
+
+(deftest databases
+  (are [in out] (= out (map sut/->connectable (sut/databases in)))
+    regional-postgres                    [:primary :eu]
+    (dissoc regional-postgres :regional) [:primary]
+    (dissoc regional-postgres :region)   [:primary]))
+
+(defrecord Component [postgres inner])
+
+(deftest repoint
+  (is (= (->Component :eu (->Component :eu nil))
+         (sut/repoint (->Component :primary (->Component :primary nil)) :eu))))
+
 ;;; ----------------------------------------------------------------------------
 ;;; Qualify
 
@@ -46,3 +89,39 @@
          (sut/execute-one! postgres {:select [:*]
                                      :from   [:sessions]
                                      :limit  1})))))
+
+;;; ----------------------------------------------------------------------------
+;;; Transactions
+
+(defn- insert-session!
+  [postgres sid-hash]
+  (sut/execute! postgres {:insert-into [:sessions]
+                          :values      [{:sid-hash  sid-hash
+                                         :tenant-id #uuid "00000000-0000-0000-0000-000000000001"}]}))
+
+(defn- session-count
+  [postgres]
+  (:count (sut/execute-one! postgres {:select [[:%count.* :count]] :from [:sessions]})))
+
+(deftest nested-transactions-join-the-outermost
+  (t/with-system [{:keys [postgres]} (t/system)]
+    (is (thrown? clojure.lang.ExceptionInfo
+                 (sut/with-transaction [outer postgres]
+                   (insert-session! outer "outer")
+                   (sut/with-transaction [inner outer]
+                     (is (identical? outer inner))
+                     (insert-session! inner "inner"))
+                   (throw (ex-info "Boom" {})))))
+    (is (= 0 (session-count postgres)))
+    (sut/with-transaction [outer postgres]
+      (sut/with-transaction [inner outer]
+        (insert-session! inner "inner")))
+    (is (= 1 (session-count postgres)))))
+
+(deftest rollback-keeps-the-connection
+  (t/with-system [{:keys [postgres]} (t/system)]
+    (sut/with-transaction [tx postgres]
+      (insert-session! tx "rolled-back")
+      (sut/rollback! tx)
+      (is (= 0 (session-count tx))))
+    (is (= 0 (session-count postgres)))))
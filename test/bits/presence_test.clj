@@ -0,0 +1,27 @@
+(ns bits.presence-test
+  (:require
+   [bits.presence :as sut]
+   [clojure.test :refer [deftest is]])
+  (:import
+   (java.time Instant)))
+
+(def ^:private tenant-id
+  #uuid "8a3f2c1e-5b6d-4e7f-9a0b-1c2d3e4f5a6b")
+
+(deftest others-on-the-same-page
+  (let [[ada bob cy] (repeatedly 3 random-uuid)
+        channel      (fn [seconds path tenant-id user-id]
+                       {:connected-at (Instant/ofEpochSecond seconds)
+                        :path         path
+                        :tenant-id    tenant-id
+                        :user-id      user-id})
+        channels     {"a" (channel 3 "/pages" tenant-id ada)
+                      "b" (channel 2 "/pages" tenant-id bob)
+                      "c" (channel 1 "/pages" tenant-id bob)
+                      "d" (channel 4 "/trash" tenant-id cy)
+                      "e" (channel 5 "/pages" (random-uuid) cy)
+                      "f" (channel 6 "/pages" tenant-id nil)}]
+    (is (= [bob] (sut/others channels {:path "/pages" :tenant-id tenant-id :user-id ada})))
+    (is (= [bob ada] (sut/others channels {:path "/pages" :tenant-id tenant-id :user-id cy})))
+    (is (empty? (sut/others channels {:path "/trash" :tenant-id tenant-id :user-id cy})))
+    (is (empty? (sut/others nil {:path "/pages" :tenant-id tenant-id :user-id ada})))))
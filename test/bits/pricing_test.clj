@@ -0,0 +1,113 @@
+(ns bits.pricing-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.discount :as discount]
+   [bits.postgres.pricing-rule :as postgres.pricing-rule]
+   [bits.pricing :as sut]
+   [bits.test.app :as t]
+   [clojure.string :as str]
+   [clojure.test :refer [are deftest is testing]]
+   [matcher-combinators.test]))
+
+(def ^:private basket
+  [{:product-id (random-uuid) :currency "EUR" :amount 6000}
+   {:product-id (random-uuid) :currency "EUR" :amount 4500 :sale? true}])
+
+(def ^:private limits
+  {:max-steps 1000 :timeout-ms 1000})
+
+(defn- run
+  [basket & sources]
+  (sut/run limits
+           (discount/quote-basket basket [])
+           (map-indexed (fn [i source] {:label (str "Rule " i) :rule (sut/parse source)}) sources)))
+
+;;; ----------------------------------------------------------------------------
+;;; Reading and checking
+
+(deftest parse
+  (is (= {:percent 10M
+          :when    [:cmp ">" [:var "subtotal"] [:num 100M]]
+          :except  [:var "sale"]}
+         (sut/parse "10% off when subtotal > 100 except sale")))
+  (is (= {:amount 5M
+          :when   [:and
+                   [:cmp ">=" [:var "items"] [:num 3M]]
+                   [:not [:cmp "=" [:var "currency"] [:str "EUR"]]]]}
+         (sut/parse "5 off when items >= 3 and not currency = \"EUR\"")))
+  (are [source error] (str/includes? (::anom/message (sut/parse source)) error)
+    "off"                                   "Rules start with an amount"
+    "10% when subtotal > 1"                 "Expected \"off\""
+    "150% off"                              "Percentages"
+    "0 off"                                 "more than zero"
+    "10% off when subtotal"                 "must be true or false"
+    "10% off when subtotal > \"EUR\""       "Expected a number but found text"
+    "10% off when currency < \"EUR\""       "Only numbers"
+    "10% off when price > 1"                "Unknown name \"price\""
+    "10% off except subtotal > 1"           "Unknown name \"subtotal\""
+    "10% off when (subtotal > 1"            "Expected \")\""
+    "10% off when subtotal > 1 banana"      "Unexpected \"banana\" at 27"
+    "10% off when subtotal > 1 & items > 1" "Unexpected \"&\""))
+
+;;; ----------------------------------------------------------------------------
+;;; Running
+
+(deftest rules
+  (testing "conditions and exceptions"
+    (is (match? {:quote   {:discount 600 :total 9900 :rules ["Rule 0"]
+                           :lines    [{:discount 600} {:discount 0}]}
+                 :results [{:label "Rule 0" :off 600}]}
+                (run basket "10% off when subtotal > 100 except sale")))
+    (is (match? {:quote {:discount 0 :total 10500}}
+                (run (take 1 basket) "10% off when subtotal > 100 except sale"))))
+
+  (testing "amounts are spread over lines and never take one below zero"
+    (is (match? {:quote {:discount 10500 :lines [{:discount 6000} {:discount 4500}]}}
+                (run basket "200 off")))
+    (is (match? {:quote {:discount 10500} :results [{:off 10000} {:off 500}]}
+                (run basket "100 off" "5 off when total > 0"))))
+
+  (testing "items counts each line's quantity"
+    (is (match? {:quote {:discount 500}}
+                (run [(assoc (first basket) :quantity 3)] "5 off when items >= 3")))
+    (is (match? {:quote {:discount 0}}
+                (run [(assoc (first basket) :quantity 3)] "5 off when items < 3"))))
+
+  (testing "rules that fail are skipped"
+    (is (match? {:quote   {:discount 500}
+                 :results [{:off 0 :error "It divided by zero."} {:off 500}]}
+                (run basket "1 off when subtotal / 0 > 1" "5 off"))))
+
+  (testing "rules that run too long are skipped"
+    (is (match? {:quote   {:discount 0}
+                 :results [{:off 0 :error string?}]}
+                (sut/run {:max-steps 3 :timeout-ms 1000}
+                         (discount/quote-basket basket [])
+                         [{:label "Long" :rule (sut/parse "5 off when items + 1 + 1 + 1 > 0")}]))))
+    (is (match? {:quote   {:discount 500}
+                 :results [{:off 0 :error string?} {:off 500}]}
+                (sut/run {:max-steps 3 :timeout-ms 1000}
+                         (discount/quote-basket basket [])
+                         [{:label "Long" :rule (sut/parse "5 off when items + 1 + 1 + 1 > 0")}
+                          {:label "Short" :rule (sut/parse "5 off")}])))))
+
+;;; ----------------------------------------------------------------------------
+;;; Saving
+
+(deftest saving
+  (t/with-system [{{:keys [discounts pricing]} :service} (t/system)]
+    (let [tenant-id (random-uuid)]
+      (is (= ::anom/incorrect (::anom/category (sut/create! pricing tenant-id "Nope" "10% off when nope"))))
+      (is (= ::anom/incorrect (::anom/category (sut/create! pricing tenant-id " " "10% off"))))
+      (let [id (sut/create! pricing tenant-id "Big basket" "10% off when subtotal > 100 except sale")]
+        (is (uuid? id))
+        (is (match? [{::postgres.pricing-rule/label "Big basket"}] (sut/list-rules pricing tenant-id)))
+
+        (testing "quotes run the tenant's rules"
+          (is (match? {:discount 600 :rules ["Big basket"]}
+                      (discount/validate discounts tenant-id [] basket)))
+          (is (match? {:discount 0} (discount/validate discounts (random-uuid) [] basket))))
+
+        (is (true? (sut/delete! pricing tenant-id id)))
+        (is (false? (sut/delete! pricing tenant-id id)))
+        (is (match? {:discount 0} (discount/validate discounts tenant-id [] basket)))))))
@@ -0,0 +1,73 @@
+(ns bits.product-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.datomic :as datomic]
+   [bits.license :as license]
+   [bits.product :as sut]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [clojure.test :refer [deftest is]]
+   [datomic.api :as d]
+   [matcher-combinators.test]))
+
+(defn- seed-product!
+  [service]
+  (let [{:keys [tenants]} (fixture/seed! service (fixture/with-products (fixture/tenant "acme") 1))
+        tenant-id         (get-in tenants ["acme" :tenant/id])
+        db                (d/db (datomic/conn (:datomic service)))]
+    [tenant-id (d/q '[:find ?id . :where [_ :product/id ?id]] db)]))
+
+(defn- fields
+  [p]
+  (select-keys p [:product/title :product/version]))
+
+(deftest stale-edits-conflict
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [[tenant-id id] (seed-product! service)
+          conn           (datomic/conn (:datomic service))
+          stale          (sut/lookup (d/db conn) tenant-id id)]
+      (is (= 0 (sut/version stale)))
+      (is (= {:product/title "Mug" :product/version 1}
+             (fields (sut/update! conn stale 0 {:product/title "Mug"}))))
+      ;; A second editor still holding the first read.
+      (let [result (sut/update! conn stale 0 {:product/title "Cup"})]
+        (is (= ::anom/conflict (::anom/category result)))
+        (is (= {:product/title "Mug" :product/version 1}
+               (fields (::sut/current result)))))
+      (is (= {:product/title "Cup" :product/version 2}
+             (fields (sut/update! conn (sut/lookup (d/db conn) tenant-id id) 1 {:product/title "Cup"})))))))
+
+(deftest edits-are-validated
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [[tenant-id id] (seed-product! service)
+          conn           (datomic/conn (:datomic service))
+          p              (sut/lookup (d/db conn) tenant-id id)]
+      (is (match? {::anom/category ::anom/incorrect}
+                  (sut/update! conn p 0 {:product/title " "})))
+      (is (match? {::anom/category ::anom/incorrect}
+                  (sut/update! conn p 0 {:product/status :product.status/sold-out})))
+      (is (= 0 (sut/version (sut/lookup (d/db conn) tenant-id id)))))))
+
+(deftest licenses
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [[tenant-id id] (seed-product! service)
+          conn           (datomic/conn (:datomic service))
+          current        #(sut/lookup (d/db conn) tenant-id id)]
+      (is (= "all-rights-reserved" (:id (license/describe (current)))))
+      (is (match? {::anom/category ::anom/incorrect}
+                  (sut/update! conn (current) 0 {:product/license :product.license/custom})))
+      (is (match? {:id "custom" :terms "Personal use only."}
+                  (license/describe (sut/update! conn (current) 0 {:product/license       :product.license/custom
+                                                                   :product/license-terms "Personal use only."}))))
+      (let [p (sut/update! conn (current) 1 {:product/license :product.license/cc-by})]
+        (is (match? {:id "cc-by" :terms nil} (license/describe p)))
+        (is (nil? (:product/license-terms p))))
+      @(d/transact conn [{:product/id       id
+                          :product/variants [{:db/id "variant" :variant/id (random-uuid)}]}
+                         {:line-item/id      (random-uuid)
+                          :line-item/variant "variant"}])
+      (is (match? {::anom/category ::anom/forbidden}
+                  (sut/update! conn (current) 2 {:product/license :product.license/cc0})))
+      (is (= {:product/title "Mug" :product/version 3}
+             (fields (sut/update! conn (current) 2 {:product/license :product.license/cc-by
+                                                   :product/title   "Mug"})))))))
@@ -0,0 +1,57 @@
+(ns bits.projection-test
+  (:require
+   [bits.datomic :as datomic]
+   [bits.projection :as sut]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [clojure.test :refer [deftest is]]
+   [datomic.api :as d])
+  (:import
+   (java.time LocalDate OffsetDateTime ZoneOffset)))
+
+(def ^:private at
+  (OffsetDateTime/of 2026 10 16 23 30 0 0 (ZoneOffset/ofHours -2)))
+
+(def ^:private day
+  (LocalDate/of 2026 10 17))
+
+(defn- order
+  [tenant-id amount]
+  {:at        at
+   :data      {:id (random-uuid) :amount amount :currency "GBP" :line-item-id (random-uuid)}
+   :tenant-id tenant-id
+   :topic     "order.created"})
+
+(deftest projects-each-event-once
+  (t/with-system [{{:keys [projector]} :service} (t/system)]
+    (let [tenant-id (random-uuid)
+          e         (order tenant-id 1500)]
+      (is (true? (sut/project! projector e)))
+      (is (false? (sut/project! projector e)))
+      (is (true? (sut/project! projector (order tenant-id 500))))
+      (is (true? (sut/project! projector {:at        at
+                                          :data      {:id (random-uuid) :user-id (random-uuid)}
+                                          :tenant-id tenant-id
+                                          :topic     "member.created"})))
+      (is (false? (sut/project! projector {:at        at
+                                           :data      {:id (random-uuid)}
+                                           :tenant-id tenant-id
+                                           :topic     "tenant.suspended"})))
+      (is (= [{:day day :orders 2 :revenue {"GBP" 2000} :signups 1}]
+             (sut/dashboard-stats projector tenant-id day day)))
+      (is (= [] (sut/dashboard-stats projector (random-uuid) day day))))))
+
+(deftest backfill-replaces-projected-totals
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants]} (fixture/seed! service
+                                           (fixture/tenant "acme")
+                                           (-> (fixture/user "owner@example.com")
+                                               (fixture/member-of "acme" :membership.role/owner)))
+          tenant-id         (get-in tenants ["acme" :tenant/id])
+          projector         (:projector service)
+          today             (LocalDate/now ZoneOffset/UTC)]
+      (sut/project! projector (order tenant-id 1500))
+      (is (= 1 (sut/backfill! projector (d/db (datomic/conn (:datomic service))) tenant-id)))
+      (is (= [] (sut/dashboard-stats projector tenant-id day day)))
+      (is (= [{:day today :orders 0 :revenue {} :signups 1}]
+             (sut/dashboard-stats projector tenant-id today today))))))
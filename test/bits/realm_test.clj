@@ -0,0 +1,44 @@
+(ns bits.realm-test
+  (:require
+   [bits.cache :as cache]
+   [bits.datomic :as datomic]
+   [bits.realm :as sut]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [clojure.test :refer [deftest is]]
+   [datomic.api :as d]
+   [matcher-combinators.test])
+  (:import
+   (com.google.common.cache Cache)))
+
+(defn- cached
+  [resolver domain]
+  (.getIfPresent ^Cache (:cache resolver) domain))
+
+(deftest lookup-caches-the-tenant
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [datomic resolver]} service
+          {:keys [tenants]}          (fixture/seed! service (fixture/tenant "acme"))]
+      (is (match? {:creator/handle "acme"
+                   :tenant/id      (get-in tenants ["acme" :tenant/id])}
+                  (sut/lookup resolver (datomic/db datomic) "acme.bits.page.localhost")))
+      (is (int? (cached resolver "acme.bits.page.localhost")))
+      (is (match? {:creator/handle "acme"}
+                  (sut/lookup resolver (datomic/db datomic) "acme.bits.page.localhost"))))))
+
+(deftest lookup-caches-unknown-domains
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [datomic resolver]} service]
+      (is (nil? (sut/lookup resolver (datomic/db datomic) "nope.bits.page.localhost")))
+      (is (= ::cache/nil (cached resolver "nope.bits.page.localhost"))))))
+
+(deftest domain-changes-invalidate
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [datomic keymaster resolver]} service]
+      (is (nil? (sut/lookup resolver (datomic/db datomic) "late.bits.page.localhost")))
+      (let [report @(d/transact (datomic/conn datomic)
+                                (fixture/seed-txes keymaster [(fixture/tenant "late")]))]
+        (sut/handle-report! resolver report)
+        (is (nil? (cached resolver "late.bits.page.localhost")))
+        (is (match? {:creator/handle "late"}
+                    (sut/lookup resolver (:db-after report) "late.bits.page.localhost")))))))
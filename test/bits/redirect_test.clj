@@ -0,0 +1,115 @@
+(ns bits.redirect-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.postgres.redirect :as postgres.redirect]
+   [bits.redirect :as sut]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [clojure.test :refer [are deftest is testing]]
+   [matcher-combinators.test]))
+
+(defn- rule
+  [source destination]
+  {::postgres.redirect/source      source
+   ::postgres.redirect/destination destination
+   ::postgres.redirect/status      301})
+
+(def ^:private rules
+  [(rule "/old" "/new")
+   (rule "/blog/*" "/news/:splat")
+   (rule "/blog/archive/*" "https://archive.example.com/:splat")
+   (rule "/blog/pinned" "/news")])
+
+(deftest matching
+  (are [path destination] (= destination
+                             (when-let [[r matched] (sut/match rules path)]
+                               (sut/location r matched nil)))
+    "/old"                "/new"
+    "/old/"               "/new"
+    "/older"              nil
+    "/blog/hello"         "/news/hello"
+    "/blog"               "/news/"
+    "/blog/pinned"        "/news"
+    "/blog/archive/2019"  "https://archive.example.com/2019"
+    "/elsewhere"          nil)
+  (is (= "/menu" (some->> (sut/match [(rule "/café" "/menu")] "/caf%C3%A9") first ::postgres.redirect/destination))))
+
+(deftest location
+  (let [[r matched] (sut/match rules "/blog/hello")]
+    (is (= "/news/hello?page=2" (sut/location r matched "page=2"))))
+  (is (= "/new?a=1" (sut/location (rule "/x" "/new?a=1") nil "b=2")))
+  (are [in out] (= out (sut/location (rule "/blog/*" "/news/:splat") in nil))
+    "caf%C3%A9"            "/news/caf%C3%A9"
+    "café"                 "/news/caf%C3%A9"
+    "a b"                  "/news/a%20b"
+    "\r\nSet-Cookie: a=b" "/news/%0D%0ASet-Cookie:%20a=b"))
+
+(deftest loops
+  (is (sut/loops? [(rule "/a" "/b") (rule "/b" "/a")] "/a"))
+  (is (sut/loops? [(rule "/a/*" "/a/:splat")] "/a/*"))
+  (is (sut/loops? [(rule "/a" "/a/")] "/a"))
+  (is (not (sut/loops? [(rule "/a" "/b") (rule "/b" "/c")] "/a")))
+  (is (not (sut/loops? [(rule "/a" "https://example.com/a")] "/a"))))
+
+(deftest parsing-csv
+  (is (= [{:source "/a" :destination "/b" :status 301}
+          {:source "/c" :destination "https://example.com/" :status 302}]
+         (sut/parse-csv "source,destination,status\n/a,/b\n\n/c, https://example.com/ ,302\n")))
+  (is (match? {::anom/category ::anom/incorrect
+               ::anom/message  #"Line 2"}
+              (sut/parse-csv "/a,/b\n/c\n")))
+  (is (= ::anom/incorrect (::anom/category (sut/parse-csv "/a,/b,moved")))))
+
+(deftest saving
+  (t/with-system [{:keys [redirects]} (t/system)]
+    (let [tenant-id (random-uuid)]
+      (is (= 2 (sut/save! redirects tenant-id [{:source "/a/" :destination "/b" :status 301}
+                                               {:source "/b" :destination "/c" :status 302}])))
+      (testing "a source is saved once, without its trailing slash"
+        (is (= 1 (sut/save! redirects tenant-id [{:source "/a" :destination "/c" :status 308}])))
+        (is (match? [{::postgres.redirect/source "/a" ::postgres.redirect/status 308}
+                     {::postgres.redirect/source "/b"}]
+                    (sut/list-rules redirects tenant-id))))
+
+      (testing "nothing is saved when one rule would loop"
+        (is (= ::anom/conflict
+               (::anom/category (sut/save! redirects tenant-id [{:source "/d" :destination "/e" :status 301}
+                                                                {:source "/c" :destination "/a" :status 301}]))))
+        (is (= 2 (count (sut/list-rules redirects tenant-id)))))
+
+      (are [r] (= ::anom/incorrect (::anom/category (sut/save! redirects tenant-id [r])))
+        {:source "old" :destination "/new" :status 301}
+        {:source "/a*b" :destination "/new" :status 301}
+        {:source "/x" :destination "javascript:alert(1)" :status 301}
+        {:source "/x" :destination "/new" :status 200}
+        {:source "/x" :destination "/new\r\nSet-Cookie: a=b" :status 301}
+        {:source "/x" :destination "/new\u0000" :status 301}
+        {:source "/x" :destination "https://example.com/\u007f" :status 301}
+        {:source "/x" :destination "//evil.example" :status 301}
+        {:source "/x" :destination "/\\evil.example" :status 301})
+
+      (is (sut/delete! redirects tenant-id (::postgres.redirect/id (first (sut/list-rules redirects tenant-id)))))
+      (is (not (sut/delete! redirects (random-uuid) (::postgres.redirect/id (first (sut/list-rules redirects tenant-id)))))))))
+
+(deftest redirected-on-the-tenant-domain
+  (t/with-system [{:keys [redirects service]} (t/system)]
+    (let [{:keys [tenants]} (fixture/seed! service (fixture/tenant "acme"))
+          tenant-id         (get-in tenants ["acme" :tenant/id])
+          domain            (get-in tenants ["acme" :domain/name])
+          get!              #(t/request service (t/host {:request-method :get :url %} domain))]
+      (sut/save! redirects tenant-id [{:source "/old-shop/*" :destination "/products/:splat" :status 301}
+                                      {:source "/login" :destination "/elsewhere" :status 302}])
+      (is (match? {:status 301 :headers {"location" "/products/hats?colour=red"}}
+                  (get! "/old-shop/hats?colour=red")))
+      (is (match? [{::postgres.redirect/source "/old-shop/*"
+                    ::postgres.redirect/hits   1
+                    ::postgres.redirect/last-hit-at some?}]
+                  (filter #(= "/old-shop/*" (::postgres.redirect/source %))
+                          (sut/list-rules redirects tenant-id))))
+
+      (testing "what a pattern matched can't add headers"
+        (is (match? {:status 301 :headers {"location" "/products/%0D%0ASet-Cookie:%20a=b"}}
+                    (get! "/old-shop/%0D%0ASet-Cookie:%20a=b"))))
+
+      (testing "the app's own routes always win"
+        (is (not= 302 (:status (get! "/login"))))))))
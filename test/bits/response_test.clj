@@ -0,0 +1,34 @@
+(ns bits.response-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.request :as request]
+   [bits.response :as sut]
+   [charred.api :as json]
+   [clojure.test :refer [deftest is]]
+   [matcher-combinators.test]))
+
+(deftest anomalies-map-to-statuses-and-codes
+  (is (= #{} (reduce disj anom/categories (keys anom/statuses))))
+  (is (match? {:status 404
+               :body   #(= {"code"       "not_found"
+                            "message"    "Gone."
+                            "request-id" "abc12345"
+                            "retryable"  false}
+                           (json/read-json %))}
+              (sut/anomaly-response {::request/id "abc12345"} (anom/not-found {::anom/message "Gone."}))))
+  (is (match? {:status  429
+               :headers {"retry-after" "60"}
+               :body    #(= {"code" "busy" "message" "Slow down." "retryable" true}
+                            (json/read-json %))}
+              (sut/anomaly-response {} (anom/busy {::anom/message             "Slow down."
+                                                   ::anom/retry-after-seconds 60}))))
+  (is (match? {:status 409
+               :body   #(= {"code" "conflict" "current" 1 "message" "Something went wrong." "retryable" false}
+                           (json/read-json %))}
+              (sut/anomaly-response {} (anom/conflict {}) {:current 1}))))
+
+(deftest plain-text-errors-carry-a-reference
+  (is (= "Internal server error.\nReference: abc12345\n"
+         (:body (sut/with-reference sut/internal-server-error-response {::request/id "abc12345"}))))
+  (is (= sut/internal-server-error-response
+         (sut/with-reference sut/internal-server-error-response {}))))
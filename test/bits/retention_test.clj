@@ -0,0 +1,73 @@
+(ns bits.retention-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.postgres :as postgres]
+   [bits.retention :as sut]
+   [bits.test.app :as t]
+   [clojure.test :refer [deftest is]])
+  (:import
+   (java.time OffsetDateTime ZoneOffset)))
+
+(def ^:private now
+  (OffsetDateTime/of 2026 10 16 12 0 0 0 ZoneOffset/UTC))
+
+(defn- activity!
+  [postgres tenant-id days-ago]
+  (postgres/execute-one! postgres
+                         {:insert-into :activities
+                          :values      [{:id         (random-uuid)
+                                         :tenant-id  tenant-id
+                                         :kind       "session.signed-in"
+                                         :created-at (.minusDays now days-ago)}]}))
+
+(defn- outbox!
+  [postgres days-ago published?]
+  (postgres/execute-one! postgres
+                         {:insert-into :outbox
+                          :values      [{:id           (random-uuid)
+                                         :tenant-id    (random-uuid)
+                                         :topic        "page.published"
+                                         :data         "{}"
+                                         :created-at   (.minusDays now days-ago)
+                                         :published-at (when published? now)}]}))
+
+(defn- row-count
+  [postgres table]
+  (:count (postgres/execute-one! postgres {:select [[:%count.* :count]] :from [table]})))
+
+(deftest prunes-in-batches-and-keeps-pending-rows
+  (t/with-system [{{:keys [postgres retention]} :service} (t/replace-clock (t/system) (atom now))]
+    (let [retention (assoc retention :batch-size 2)
+          tenant-id (random-uuid)]
+      (dotimes [_ 5] (activity! postgres tenant-id 400))
+      (activity! postgres tenant-id 10)
+      (outbox! postgres 30 true)
+      (outbox! postgres 30 false)
+      (outbox! postgres 1 true)
+      (is (= {:activities         5
+              :api-key-requests   0
+              :hourly-rollups     0
+              :notifications      0
+              :outbox             1
+              :page-revisions     0
+              :projected-events   0
+              :webhook-deliveries 0}
+             (sut/prune! retention)))
+      (is (= 1 (row-count postgres :activities)))
+      (is (= 2 (row-count postgres :outbox))))))
+
+(deftest tenants-keep-their-rows-for-less
+  (t/with-system [{{:keys [postgres retention]} :service} (t/replace-clock (t/system) (atom now))]
+    (let [tenant-id (random-uuid)
+          other-id  (random-uuid)]
+      (activity! postgres tenant-id 40)
+      (activity! postgres other-id 40)
+      (is (= ::anom/incorrect (::anom/category (sut/set-tenant-days! retention tenant-id :outbox 1))))
+      (is (= ::anom/incorrect (::anom/category (sut/set-tenant-days! retention tenant-id :activities 365))))
+      (is (= ::anom/incorrect (::anom/category (sut/set-tenant-days! retention tenant-id :activities 0))))
+      (sut/set-tenant-days! retention tenant-id :activities 30)
+      (is (= {:default 365 :days 30} (get (sut/tenant-days retention tenant-id) :activities)))
+      (is (= 1 (sut/prune-table! retention :activities)))
+      (is (= 1 (row-count postgres :activities)))
+      (sut/set-tenant-days! retention tenant-id :activities nil)
+      (is (= {:default 365 :days nil} (get (sut/tenant-days retention tenant-id) :activities))))))
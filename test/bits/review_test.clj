@@ -0,0 +1,71 @@
+(ns bits.review-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.datomic :as datomic]
+   [bits.postgres.review :as postgres.review]
+   [bits.review :as sut]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [clojure.test :refer [deftest is]]
+   [datomic.api :as d]
+   [matcher-combinators.test]))
+
+(defn- buy!
+  "Records a purchase of a new variant of the product."
+  [conn user-id product-id]
+  @(d/transact conn [{:product/id       product-id
+                      :product/variants [{:db/id      "variant"
+                                          :variant/id (random-uuid)}]}
+                     {:line-item/id      (random-uuid)
+                      :line-item/variant "variant"
+                      :line-item/buyer   [:user/id user-id]}]))
+
+(defn- seed!
+  [service]
+  (let [{:keys [tenants users]} (fixture/seed! service
+                                               (fixture/with-products (fixture/tenant "acme") 1)
+                                               (fixture/user "buyer@example.com")
+                                               (fixture/user "browser@example.com"))
+        conn                    (datomic/conn (:datomic service))]
+    {:conn       conn
+     :buyer-id   (get-in users ["buyer@example.com" :user/id])
+     :browser-id (get-in users ["browser@example.com" :user/id])
+     :product-id (d/q '[:find ?id . :where [_ :product/id ?id]] (d/db conn))
+     :tenant-id  (get-in tenants ["acme" :tenant/id])}))
+
+(deftest only-buyers-can-review
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [browser-id buyer-id conn product-id tenant-id]} (seed! service)
+          review                                                  #(merge {:body       "Lovely mug."
+                                                                           :product-id product-id
+                                                                           :rating     5
+                                                                           :tenant-id  tenant-id}
+                                                                          %)]
+      (buy! conn buyer-id product-id)
+      (is (match? {::anom/category ::anom/forbidden}
+                  (sut/submit! (:reviews service) (d/db conn) (review {:author-id browser-id}))))
+      (is (match? {::anom/category ::anom/incorrect}
+                  (sut/submit! (:reviews service) (d/db conn) (review {:author-id buyer-id :rating 6}))))
+      (is (uuid? (sut/submit! (:reviews service) (d/db conn) (review {:author-id buyer-id})))))))
+
+(deftest resubmitting-revises-the-review
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [buyer-id browser-id conn product-id tenant-id]} (seed! service)
+          reviews                                                 (:reviews service)]
+      (is (= {:average nil :total 0} (sut/summary reviews tenant-id product-id)))
+      (buy! conn buyer-id product-id)
+      (buy! conn browser-id product-id)
+      (let [id (sut/submit! reviews (d/db conn) {:author-id buyer-id :product-id product-id :rating 2 :tenant-id tenant-id})]
+        (is (= id (sut/submit! reviews (d/db conn) {:author-id  buyer-id
+                                                    :body       "Grew on me."
+                                                    :product-id product-id
+                                                    :rating     4
+                                                    :tenant-id  tenant-id}))))
+      (sut/submit! reviews (d/db conn) {:author-id browser-id :product-id product-id :rating 5 :tenant-id tenant-id})
+      (is (= {:average 4.5 :total 2} (sut/summary reviews tenant-id product-id)))
+      (let [{:keys [items next-cursor]} (sut/list-reviews reviews tenant-id product-id {:limit 1})]
+        (is (= [5] (map ::postgres.review/rating items)))
+        (is (= ["Grew on me."]
+               (map ::postgres.review/body
+                    (:items (sut/list-reviews reviews tenant-id product-id {:cursor next-cursor :limit 1}))))))
+      (is (empty? (:items (sut/list-reviews reviews (random-uuid) product-id {:limit 10})))))))
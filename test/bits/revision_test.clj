@@ -0,0 +1,55 @@
+(ns bits.revision-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.postgres.page-revision :as postgres.page-revision]
+   [bits.revision :as sut]
+   [bits.test.app :as t]
+   [clojure.test :refer [are deftest is testing]]
+   [matcher-combinators.test]))
+
+(defn- page
+  [page-id title blocks]
+  {:page/blocks blocks
+   :page/id     page-id
+   :page/slug   "about"
+   :page/title  title})
+
+;;; ----------------------------------------------------------------------------
+;;; Comparing
+
+(deftest diff-blocks
+  (are [a b out] (= out (mapv (juxt :op :block) (sut/diff-blocks a b)))
+    []        []        []
+    [1 2 3]   [1 2 3]   [[:same 1] [:same 2] [:same 3]]
+    [1 2 3]   [1 3]     [[:same 1] [:removed 2] [:same 3]]
+    [1 3]     [1 2 3]   [[:same 1] [:added 2] [:same 3]]
+    [1 2]     [3]       [[:removed 1] [:removed 2] [:added 3]]
+    [1 2 3 4] [2 4 5]   [[:removed 1] [:same 2] [:removed 3] [:same 4] [:added 5]]))
+
+;;; ----------------------------------------------------------------------------
+;;; Recording and pinning
+
+(deftest record-pin-and-diff
+  (t/with-system [{:keys [postgres]} (t/system)]
+    (let [tenant-id (random-uuid)
+          page-id   (random-uuid)
+          first-r   (sut/record! postgres tenant-id (page page-id "About" "[{\"type\": \"heading\", \"text\": \"Hi\"}]") nil)
+          second-r  (sut/record! postgres tenant-id (page page-id "About us" "[]") nil)]
+      (is (= [2 1] (map ::postgres.page-revision/number (sut/list-revisions postgres tenant-id page-id))))
+      (is (= {:title  ["About" "About us"]
+              :slug   nil
+              :blocks [{:op :removed :block {:type "heading" :text "Hi"}}]}
+             (sut/diff first-r second-r)))
+
+      (testing "one revision is pinned at a time"
+        (is (nil? (sut/pinned postgres tenant-id page-id)))
+        (is (match? {::postgres.page-revision/number 1} (sut/pin! postgres tenant-id page-id 1)))
+        (is (match? {::postgres.page-revision/number 2} (sut/pin! postgres tenant-id page-id 2)))
+        (is (match? {::postgres.page-revision/number 2} (sut/pinned postgres tenant-id page-id)))
+        (is (= ::anom/not-found (::anom/category (sut/pin! postgres tenant-id page-id 3))))
+        (sut/unpin! postgres tenant-id page-id)
+        (is (nil? (sut/pinned postgres tenant-id page-id))))
+
+      (testing "other tenants can't see the revisions"
+        (is (empty? (sut/list-revisions postgres (random-uuid) page-id)))
+        (is (nil? (sut/lookup postgres (random-uuid) page-id 1)))))))
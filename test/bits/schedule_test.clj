@@ -0,0 +1,94 @@
+(ns bits.schedule-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.datomic :as datomic]
+   [bits.page :as page]
+   [bits.postgres :as postgres]
+   [bits.product :as product]
+   [bits.schedule :as sut]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [clojure.test :refer [are deftest is testing]]
+   [datomic.api :as d]
+   [matcher-combinators.test])
+  (:import
+   (java.time Duration OffsetDateTime)
+   (java.util Date)))
+
+(def ^:private start
+  (OffsetDateTime/parse "2026-10-17T09:00:00Z"))
+
+(defn- at
+  ^Date [minutes]
+  (sut/->date (.plus start (Duration/ofMinutes minutes))))
+
+(deftest windows
+  (are [publish-at unpublish-at now within? status]
+       (and (= within? (sut/within? publish-at unpublish-at now))
+            (= status (sut/status publish-at unpublish-at now)))
+    nil     nil     (at 0)  true  :live
+    (at 10) nil     (at 0)  false :scheduled
+    (at 10) nil     (at 10) true  :live
+    nil     (at 10) (at 9)  true  :live
+    nil     (at 10) (at 10) false :ended
+    (at 10) (at 20) (at 30) false :ended))
+
+(deftest products-come-and-go
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants]} (fixture/seed! service (fixture/with-products (fixture/tenant "acme") 1))
+          tenant-id         (get-in tenants ["acme" :tenant/id])
+          conn              (datomic/conn (:datomic service))
+          id                (d/q '[:find ?id . :where [_ :product/id ?id]] (d/db conn))
+          current           #(product/lookup (d/db conn) tenant-id id)]
+      (is (match? {::anom/category ::anom/incorrect}
+                  (product/update! conn (current) 0 {:product/publish-at (at 20) :product/unpublish-at (at 10)})))
+      (product/update! conn (current) 0 {:product/publish-at (at 10) :product/unpublish-at (at 20)})
+      (is (nil? (product/lookup-visible (d/db conn) tenant-id id (at 0))))
+      (is (some? (product/lookup-visible (d/db conn) tenant-id id (at 15))))
+      (is (nil? (product/lookup-visible (d/db conn) tenant-id id (at 20))))
+
+      (testing "clearing a time retracts it"
+        (product/update! conn (current) 1 {:product/unpublish-at nil})
+        (is (nil? (:product/unpublish-at (current))))
+        (is (some? (product/lookup-visible (d/db conn) tenant-id id (at 60))))))))
+
+(deftest boundaries-are-announced
+  (let [!now (atom start)]
+    (t/with-system [{{:keys [postgres scheduler] :as service} :service} (t/replace-clock (t/system) !now)]
+      (let [{:keys [tenants]} (fixture/seed! service (fixture/tenant "acme"))
+            tenant-id         (get-in tenants ["acme" :tenant/id])
+            conn              (datomic/conn (:datomic service))
+            create!           #(page/create! conn tenant-id {:blocks "[]" :slug % :title %} (at 0))
+            about             (create! "about")
+            draft             (create! "draft")
+            leading           (assoc scheduler :leader {:leading (atom true)})]
+        @(d/transact conn (page/publish-tx about (at 0)))
+        (page/schedule! conn tenant-id (page/lookup (d/db conn) tenant-id (:page/id about))
+                        {:publish-at (at 10) :unpublish-at (at 20)})
+        (page/schedule! conn tenant-id draft {:publish-at (at 10)})
+        (is (match? {::anom/category ::anom/incorrect}
+                    (page/schedule! conn tenant-id draft {:publish-at (at 10) :unpublish-at (at 10)})))
+
+        (let [about (page/lookup (d/db conn) tenant-id (:page/id about))]
+          (is (not (page/live? about (at 5))))
+          (is (page/live? about (at 10)))
+          (is (not (page/live? about (at 20)))))
+
+        (reset! !now (.plus start (Duration/ofMinutes 15)))
+        (testing "draft pages changed nothing anyone could see"
+          (is (= [{:tenant-id tenant-id
+                   :topic     "page.published"
+                   :data      {:page-id (:page/id about)}}]
+                 (sut/poll! leading))))
+        (is (= ["page.published"]
+               (map :bits.postgres.outbox/topic
+                    (postgres/execute! postgres {:select [:topic] :from [:outbox] :where [:= :tenant-id tenant-id]}))))
+
+        (testing "nothing is announced twice"
+          (is (empty? (sut/poll! leading))))
+
+        (testing "only the leader writes to the outbox"
+          (reset! !now (.plus start (Duration/ofMinutes 25)))
+          (is (= ["page.unpublished"]
+                 (map :topic (sut/poll! (assoc scheduler :leader {:leading (atom false)})))))
+          (is (= 1 (count (postgres/execute! postgres {:select [:id] :from [:outbox] :where [:= :tenant-id tenant-id]})))))))))
@@ -0,0 +1,62 @@
+(ns bits.secret-test
+  (:require
+   [bits.secret :as sut]
+   [clojure.edn :as edn]
+   [clojure.test :refer [are deftest is]])
+  (:import
+   (java.time ZoneOffset ZonedDateTime)))
+
+(deftest read-ref
+  (is (= {:service {:csrf-secret (sut/->Ref "CSRF_SECRET" :value)
+                    :password    (sut/->Ref "secret/data/bits" :password)}}
+         (edn/read-string {:readers {'bits/secret sut/read-ref}}
+                          "{:service {:csrf-secret #bits/secret \"CSRF_SECRET\"
+                                      :password    #bits/secret {:path \"secret/data/bits\" :key :password}}}"))))
+
+(deftest resolve-refs
+  (let [provider (sut/->Env {"CSRF_SECRET" "s3cret"})]
+    (is (= {:service {:csrf-secret "s3cret" :http-port 3000}}
+           (sut/resolve-refs provider {:service {:csrf-secret (sut/->Ref "CSRF_SECRET" :value)
+                                                 :http-port   3000}})))
+    (is (thrown-with-msg? clojure.lang.ExceptionInfo #"not set: MISSING"
+                          (sut/resolve-refs provider {:x (sut/->Ref "MISSING" :value)})))
+    (is (thrown-with-msg? clojure.lang.ExceptionInfo #"has no :password"
+                          (sut/resolve-refs provider {:x (sut/->Ref "CSRF_SECRET" :password)})))))
+
+(deftest resolve-refs-fetches-each-path-once
+  (let [!fetches (atom 0)
+        provider (reify sut/SecretsProvider
+                   (fetch [_ _]
+                     (swap! !fetches inc)
+                     {:data {:password "p" :username "u"}})
+                   (renew [_ _]
+                     nil))]
+    (is (= {:password "p" :username "u"}
+           (sut/resolve-refs provider {:password (sut/->Ref "database/creds/bits" :password)
+                                       :username (sut/->Ref "database/creds/bits" :username)})))
+    (is (= 1 @!fetches))))
+
+(deftest renew-delay-seconds
+  (are [lease delay] (= delay (sut/renew-delay-seconds lease))
+    3600 2400
+    30   20
+    1    1
+    0    1))
+
+(deftest sign
+  (is (= {"authorization" (str "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/secretsmanager/aws4_request, "
+                               "SignedHeaders=content-type;host;x-amz-date;x-amz-target, "
+                               "Signature=a79a8d3fb27f67b0a1a03503092809a5e2fb8ab93333b2bda4a9ba03d75cfcf3")
+          "content-type"  "application/x-amz-json-1.1"
+          "host"          "secretsmanager.us-east-1.amazonaws.com"
+          "x-amz-date"    "20150830T123600Z"
+          "x-amz-target"  "secretsmanager.GetSecretValue"}
+         (sut/sign {:access-key-id     "AKIDEXAMPLE"
+                    :region            "us-east-1"
+                    :secret-access-key "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY"}
+                   "secretsmanager"
+                   "secretsmanager.us-east-1.amazonaws.com"
+                   {"content-type" "application/x-amz-json-1.1"
+                    "x-amz-target" "secretsmanager.GetSecretValue"}
+                   "{\"SecretId\":\"bits/production\"}"
+                   (ZonedDateTime/of 2015 8 30 12 36 0 0 ZoneOffset/UTC)))))
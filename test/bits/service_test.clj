@@ -52,6 +52,31 @@
               "x-xss-protection"                  "1; mode=block"}
              (:headers (t/request service request))))))))
 
+(deftest robots-outside-production
+  (doseq [[environment robots] {:development "noindex, nofollow"
+                                :staging     "noindex, nofollow"
+                                :production  nil}]
+    (t/with-system [{:keys [service]} (assoc-in (t/system) [:service :environment] environment)]
+      @(d/transact (datomic/conn (:datomic service)) (fixture/realm-txes))
+      (is (= robots
+             (get-in (t/request service {:request-method :get :url "/"})
+                     [:headers "x-robots-tag"]))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Request IDs
+
+(deftest request-ids
+  (t/with-system [{:keys [service]} (t/system)]
+    @(d/transact (datomic/conn (:datomic service)) (fixture/realm-txes))
+    (is (match? {"x-request-id" "from-the-proxy-1"}
+                (:headers (t/request service {:headers        {"x-request-id" "from-the-proxy-1"}
+                                              :request-method :get
+                                              :url            "/"}))))
+    (is (match? {"x-request-id" #(parse-uuid %)}
+                (:headers (t/request service {:headers        {"x-request-id" "bad id\r\n"}
+                                              :request-method :get
+                                              :url            "/"}))))))
+
 ;;; ----------------------------------------------------------------------------
 ;;; Errors
 
@@ -154,3 +179,18 @@
       (is (match?
            {:status 404}
            (t/request service request))))))
+
+(deftest creator-realm
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [tenants]} (fixture/seed! service
+                                           (-> (fixture/tenant "acme")
+                                               (fixture/with-products 2))
+                                           (-> (fixture/user "ada@example.com")
+                                               (fixture/member-of "acme" :membership.role/owner)))
+          request           (t/host {:request-method :get
+                                     :url            "/"}
+                                    (get-in tenants ["acme" :domain/name]))]
+      (is (match?
+           {:status 200
+            :body   #"acme"}
+           (t/request service request))))))
@@ -18,48 +18,48 @@
                                (crypto/random-sid randomizer))))))
 
 (deftest get-session-with-a-known-session-id
-  (t/with-system [{:keys [session-store]} (t/system)]
-    (let [{:keys [sid]
-           :as   data} (sut/new-session session-store)
-          sid-hash     (crypto/sha256 sid)
-          expired      (-> session-store :idle-timeout-days time/days)]
-      (sut/create-session! session-store tenant-id sid data)
-      (is (match?
-           {::postgres.session/created-at inst?,
-            ::postgres.session/data       {:nonce string?
-                                           ;; FIXME Remove `:sid` from `data`.
-                                           :sid   sid}
-            ::postgres.session/sid-hash   sid-hash
-            ::postgres.session/user-id    nil}
-           (sut/get-session session-store tenant-id sid)))
-      (time/with-clock (time/mock-clock (time/plus (time/instant) expired))
+  (let [!now (atom (time/offset-date-time))]
+    (t/with-system [{:keys [session-store]} (t/replace-clock (t/system) !now)]
+      (let [{:keys [sid]
+             :as   data} (sut/new-session session-store)
+            sid-hash     (crypto/sha256 sid)
+            expired      (-> session-store :idle-timeout-days time/days)]
+        (sut/create-session! session-store tenant-id sid data)
+        (is (match?
+             {::postgres.session/created-at inst?,
+              ::postgres.session/data       {:nonce string?
+                                             ;; FIXME Remove `:sid` from `data`.
+                                             :sid   sid}
+              ::postgres.session/sid-hash   sid-hash
+              ::postgres.session/user-id    nil}
+             (sut/get-session session-store tenant-id sid)))
+        (swap! !now time/plus expired)
         (is (nil? (sut/get-session session-store tenant-id sid)))))))
 
 (deftest touch-session-extends-expiry
-  (t/with-system [{:keys [session-store]} (t/system)]
-    (let [{:keys [sid] :as data} (sut/new-session session-store)
-          sid-hash               (crypto/sha256 sid)
-          timeout-days           (:idle-timeout-days session-store)
-          almost-expired         (time/hours (- (* timeout-days 24) 1))]
-      (sut/create-session! session-store tenant-id sid data)
-      (time/with-clock (time/mock-clock (time/plus (time/instant) almost-expired))
-        (sut/touch-session! session-store tenant-id sid))
-      (time/with-clock (time/mock-clock (time/plus (time/instant)
-                                                   (time/hours (inc (* timeout-days 24)))))
+  (let [!now (atom (time/offset-date-time))]
+    (t/with-system [{:keys [session-store]} (t/replace-clock (t/system) !now)]
+      (let [{:keys [sid] :as data} (sut/new-session session-store)
+            sid-hash               (crypto/sha256 sid)
+            timeout-days           (:idle-timeout-days session-store)
+            almost-expired         (time/hours (- (* timeout-days 24) 1))]
+        (sut/create-session! session-store tenant-id sid data)
+        (swap! !now time/plus almost-expired)
+        (sut/touch-session! session-store tenant-id sid)
+        (swap! !now time/plus (time/hours 2))
         (is (match?
              {::postgres.session/sid-hash sid-hash}
              (sut/get-session session-store tenant-id sid)))))))
 
 (deftest delete-expired-sessions-removes-only-expired
-  (t/with-system [{:keys [session-store]} (t/system)]
-    (let [valid-session   (sut/new-session session-store)
-          expired-session (sut/new-session session-store)
-          timeout-days    (:idle-timeout-days session-store)]
-      (sut/create-session! session-store tenant-id (:sid valid-session) valid-session)
-      (sut/create-session! session-store tenant-id (:sid expired-session) expired-session)
-      (time/with-clock (time/mock-clock (time/plus (time/instant)
-                                                   (time/days (inc timeout-days))))
-        (let [deleted (sut/delete-expired-sessions! session-store)]
-          (is (= 2 deleted))))
-      (is (nil? (sut/get-session session-store tenant-id (:sid valid-session))))
-      (is (nil? (sut/get-session session-store tenant-id (:sid expired-session)))))))
+  (let [!now (atom (time/offset-date-time))]
+    (t/with-system [{:keys [session-store]} (t/replace-clock (t/system) !now)]
+      (let [valid-session   (sut/new-session session-store)
+            expired-session (sut/new-session session-store)
+            timeout-days    (:idle-timeout-days session-store)]
+        (sut/create-session! session-store tenant-id (:sid valid-session) valid-session)
+        (sut/create-session! session-store tenant-id (:sid expired-session) expired-session)
+        (swap! !now time/plus (time/days (inc timeout-days)))
+        (is (= 2 (sut/delete-expired-sessions! session-store)))
+        (is (nil? (sut/get-session session-store tenant-id (:sid valid-session))))
+        (is (nil? (sut/get-session session-store tenant-id (:sid expired-session))))))))
@@ -0,0 +1,39 @@
+(ns bits.shadow-test
+  (:require
+   [bits.datomic :as datomic]
+   [bits.flag :as flag]
+   [bits.shadow :as sut]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [clojure.string :as str]
+   [clojure.test :refer [deftest is]]
+   [datomic.api :as d]))
+
+(defn- system
+  []
+  (assoc-in (t/system) [:shadow :candidates :csp] {:script-src "'none'"}))
+
+(deftest candidates-are-never-enforced
+  (t/with-system [{{:keys [shadow]} :service} (system)]
+    (let [tenant-id (random-uuid)]
+      (is (false? (sut/shadowing? shadow tenant-id :rate-limits)))
+      (flag/save-flag! (:flags shadow) {:name            "shadow.rate-limits"
+                                        :enabled         true
+                                        :rollout-percent 0})
+      (is (true? (sut/shadowing? shadow tenant-id :rate-limits)))
+      (is (nil? (sut/compare! shadow :rate-limits tenant-id nil :bits.auth.rate-limit/ip)))
+      (is (= :bits.auth.rate-limit/ip
+             (sut/compare! shadow :rate-limits tenant-id :bits.auth.rate-limit/ip nil))))))
+
+(deftest shadowed-csp-is-report-only
+  (t/with-system [{:keys [service]} (system)]
+    @(d/transact (datomic/conn (:datomic service)) (fixture/realm-txes))
+    (let [request {:request-method :get
+                   :url            "/"}]
+      (is (nil? (get-in (t/request service request) [:headers "content-security-policy-report-only"])))
+      (flag/save-flag! (:flags service) {:name            "shadow.csp"
+                                         :enabled         true
+                                         :rollout-percent 0})
+      (let [headers (:headers (t/request service request))]
+        (is (str/includes? (get headers "content-security-policy") "script-src 'self'"))
+        (is (str/includes? (get headers "content-security-policy-report-only") "script-src 'none'"))))))
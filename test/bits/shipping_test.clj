@@ -0,0 +1,88 @@
+(ns bits.shipping-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.shipping :as sut]
+   [bits.test.app :as t]
+   [clojure.test :refer [deftest is testing]]
+   [matcher-combinators.test]))
+
+(defn- zone
+  [regions]
+  {:bits.postgres.shipping-zone/id      (random-uuid)
+   :bits.postgres.shipping-zone/regions regions})
+
+(defn- rate
+  [m]
+  (merge {:bits.postgres.shipping-rate/kind     "flat"
+          :bits.postgres.shipping-rate/amount   500
+          :bits.postgres.shipping-rate/currency "GBP"}
+         m))
+
+(def ^:private shipment
+  {:region "GB" :grams 800 :subtotal 4000 :currency "GBP"})
+
+(deftest subdivisions-beat-countries
+  (let [us         (zone ["US"])
+        california (zone ["US-CA"])
+        zones      [us california]]
+    (is (= california (sut/zone-for zones "us-ca")))
+    (is (= us (sut/zone-for zones "US-NY")))
+    (is (nil? (sut/zone-for zones "GB")))
+    (is (nil? (sut/zone-for zones " ")))))
+
+(deftest pricing-by-kind
+  (is (= 500 (sut/price (rate {}) shipment)))
+  (is (nil? (sut/price (rate {}) (assoc shipment :currency "USD"))))
+
+  (testing "weight rates cover their minimum up to but not including their maximum"
+    (let [light (rate {:bits.postgres.shipping-rate/kind      "weight"
+                       :bits.postgres.shipping-rate/max-grams 800})
+          heavy (rate {:bits.postgres.shipping-rate/kind      "weight"
+                       :bits.postgres.shipping-rate/min-grams 800
+                       :bits.postgres.shipping-rate/max-grams 5000})]
+      (is (nil? (sut/price light shipment)))
+      (is (= 500 (sut/price heavy shipment)))))
+
+  (testing "free-above rates are free from their threshold"
+    (let [free (rate {:bits.postgres.shipping-rate/kind      "free-above"
+                      :bits.postgres.shipping-rate/threshold 4000})]
+      (is (= 0 (sut/price free shipment)))
+      (is (= 500 (sut/price free (assoc shipment :subtotal 3999)))))))
+
+(deftest totals-add-shipping-after-discounts
+  (is (= {:subtotal 4000 :discount 1000 :shipping 450 :total 3450}
+         (sut/totals {:subtotal 4000 :discount 1000} {:amount 450}))))
+
+(deftest options-span-every-carrier
+  (let [courier (reify sut/Carrier
+                  (rates [_ _ {:keys [region]}]
+                    (when (= "GB" region)
+                      [{:id "courier:next-day" :name "Next day" :amount 900 :currency "GBP"}])))]
+    (t/with-system [{{:keys [shipping]} :service} (assoc-in (t/system) [:shipping :extra-carriers] [courier])]
+      (let [tenant-id (random-uuid)
+            uk        (sut/create-zone! shipping tenant-id "UK" ["gb" "GB"])]
+        (is (uuid? uk))
+        (is (= ::anom/conflict (::anom/category (sut/create-zone! shipping tenant-id "Home" ["GB"]))))
+        (is (= ::anom/incorrect (::anom/category (sut/create-zone! shipping tenant-id "Nowhere" ["Britain"]))))
+        (is (= ::anom/not-found (::anom/category (sut/create-rate! shipping tenant-id (random-uuid)
+                                                                   {:name "Lost" :kind "flat" :amount 100 :currency "GBP"}))))
+
+        (is (uuid? (sut/create-rate! shipping tenant-id uk {:name "Standard" :kind "free-above" :amount 450
+                                                            :currency "GBP" :threshold 5000})))
+        (is (uuid? (sut/create-rate! shipping tenant-id uk {:name "Parcel" :kind "weight" :amount 700
+                                                            :currency "GBP" :max-grams 2000})))
+
+        (let [options (sut/options shipping tenant-id shipment)]
+          (is (match? [{:name "Standard" :amount 450}
+                       {:name "Parcel" :amount 700}
+                       {:id "courier:next-day" :amount 900}]
+                      options))
+          (is (= "Standard" (:name (sut/choose options "nope"))))
+          (is (= 900 (:amount (sut/choose options "courier:next-day")))))
+
+        (is (= [0 700 900] (map :amount (sut/options shipping tenant-id (assoc shipment :subtotal 5000)))))
+        (is (empty? (sut/options shipping tenant-id (assoc shipment :region "FR"))))
+
+        (testing "deleting a zone takes its rates"
+          (is (true? (sut/delete-zone! shipping tenant-id uk)))
+          (is (empty? (sut/list-rates shipping tenant-id))))))))
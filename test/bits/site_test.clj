@@ -0,0 +1,127 @@
+(ns bits.site-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.postgres :as postgres]
+   [bits.site :as sut]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [clojure.string :as str]
+   [clojure.test :refer [are deftest is testing]]
+   [matcher-combinators.test])
+  (:import
+   (java.io ByteArrayInputStream ByteArrayOutputStream)
+   (java.time Duration Instant)
+   (java.util.zip ZipEntry ZipOutputStream)))
+
+(defn- zip
+  "A zip of `{name contents}`."
+  [entries]
+  (let [out (ByteArrayOutputStream.)]
+    (with-open [z (ZipOutputStream. out)]
+      (doseq [[entry-name ^String contents] entries]
+        (.putNextEntry z (ZipEntry. ^String entry-name))
+        (.write z (.getBytes contents "UTF-8"))
+        (.closeEntry z)))
+    (ByteArrayInputStream. (.toByteArray out))))
+
+(defn- rename
+  "ZipOutputStream won't write a name twice, so duplicates are made by
+  renaming an entry in the bytes afterwards."
+  [^ByteArrayInputStream in from to]
+  (let [s (String. (.readAllBytes in) "ISO-8859-1")]
+    (ByteArrayInputStream. (.getBytes (str/replace s from to) "ISO-8859-1"))))
+
+(def ^:private limits
+  {:max-files 3 :quota-bytes 64})
+
+(deftest entry-paths
+  (are [entry-name path] (= path (sut/normalize-path entry-name))
+    "index.html"        "/index.html"
+    "docs/a b.html"     "/docs/a b.html"
+    "../etc/passwd"     nil
+    "a/../../b"         nil
+    "/abs.html"         nil
+    "a//b"              nil
+    "dir/"              nil
+    "back\\slash.html"  nil))
+
+(deftest content-types
+  (are [path content-type] (= content-type (sut/content-type path))
+    "/index.html" "text/html; charset=utf-8"
+    "/app.js"     "text/javascript; charset=utf-8"
+    "/logo.svg"   "image/svg+xml; charset=utf-8"
+    "/photo.png"  "image/png"
+    "/mystery"    "application/octet-stream"))
+
+(deftest reading-zips
+  (testing "a zipped folder is the site"
+    (is (= ["/index.html" "/css/site.css"]
+           (map :path (sut/read-zip limits (zip [["site/index.html" "hi"]
+                                                 ["site/css/site.css" "p{}"]]))))))
+
+  (are [entries reason] (= reason (::sut/reason (sut/read-zip limits (zip entries))))
+    []                                          :empty
+    [["../x.html" "x"]]                         :unsafe-path
+    [["a" "1"] ["b" "2"] ["c" "3"] ["d" "4"]]   :too-many-files
+    [["big.txt" (apply str (repeat 65 "x"))]]   :too-large)
+
+  (is (= :duplicate-path
+         (::sut/reason (sut/read-zip limits (rename (zip [["a.html" "1"] ["b.html" "2"]]) "b.html" "a.html")))))
+  (is (= ::anom/incorrect (::anom/category (sut/read-zip limits (ByteArrayInputStream. (.getBytes "not a zip")))))))
+
+(deftest releases-switch-atomically
+  (t/with-system [{:keys [postgres sites]} (t/system)]
+    (let [tenant-id (random-uuid)
+          publish!  #(sut/publish! sites tenant-id (sut/read-zip sites (zip %)))
+          first-id  (publish! [["index.html" "one"] ["logo.svg" "<svg/>"]])
+          second-id (publish! [["index.html" "two"] ["logo.svg" "<svg/>"]])]
+      (testing "unchanged files are stored once"
+        (is (= 3 (count (postgres/execute! postgres {:select [:digest] :from [:blobs]})))))
+
+      (is (match? {:path "/index.html" :content-type "text/html; charset=utf-8"}
+                  (sut/lookup sites tenant-id "/")))
+      (is (= "two" (String. ^bytes (sut/fetch sites (:digest (sut/lookup sites tenant-id "/index.html"))))))
+      (is (nil? (sut/lookup sites (random-uuid) "/")))
+
+      (sut/roll-back! sites tenant-id first-id)
+      (is (= [[second-id false] [first-id true]]
+             (map (juxt :id :active?) (sut/releases sites tenant-id))))
+      (is (= "one" (String. ^bytes (sut/fetch sites (:digest (sut/lookup sites tenant-id "/"))))))
+      (is (= ::anom/not-found (::anom/category (sut/roll-back! sites (random-uuid) first-id))))
+
+      (testing "old releases are pruned, but never the live one"
+        (dotimes [_ (:releases-kept sites)]
+          (publish! [["index.html" (str (random-uuid))]]))
+        (is (= (:releases-kept sites) (count (sut/releases sites tenant-id))))
+        (is (not-any? #{first-id second-id} (map :id (sut/releases sites tenant-id)))))
+
+      (testing "blobs nothing refers to are purged once they're old enough"
+        (is (= 0 (sut/purge-blobs! sites (Instant/now))))
+        (is (= 3 (sut/purge-blobs! sites (.plus (Instant/now) (Duration/ofHours 2))))))
+
+      (sut/take-down! sites tenant-id)
+      (is (nil? (sut/lookup sites tenant-id "/"))))))
+
+(deftest served-from-the-tenant-domain
+  (t/with-system [{:keys [service sites]} (t/system)]
+    (let [{:keys [tenants]} (fixture/seed! service (fixture/tenant "acme"))
+          tenant-id         (get-in tenants ["acme" :tenant/id])
+          domain            (get-in tenants ["acme" :domain/name])
+          get!              #(t/request service (t/host {:request-method :get :url %1 :headers %2} domain))]
+      (sut/publish! sites tenant-id (sut/read-zip sites (zip [["index.html" "<h1>Static acme</h1>"]
+                                                                ["about.html" "About"]
+                                                                ["login.html" "Shadowed"]])))
+      (is (match? {:status  200
+                   :headers {"content-type"            "text/html; charset=utf-8"
+                             "content-security-policy" #"; sandbox allow-forms allow-popups allow-scripts$"
+                             "cache-control"           "no-cache"}
+                   :body    "<h1>Static acme</h1>"}
+                  (get! "/" {})))
+      (is (match? {:status 200 :body "About"} (get! "/about" {})))
+
+      (testing "the app's own routes always win"
+        (is (not= "Shadowed" (:body (get! "/login" {})))))
+
+      (testing "unchanged files aren't sent again"
+        (let [etag (get-in (get! "/about" {}) [:headers "etag"])]
+          (is (= 304 (:status (get! "/about" {"if-none-match" etag})))))))))
@@ -1,8 +1,82 @@
 (ns bits.tailwind-test
   (:require
    [bits.tailwind :as sut]
-   [clojure.test :refer [deftest is]]))
+   [clojure.string :as str]
+   [clojure.test :refer [deftest is]]
+   [clojure.test.check.clojure-test :refer [defspec]]
+   [clojure.test.check.generators :as gen]
+   [clojure.test.check.properties :as prop]))
+
+;;; ----------------------------------------------------------------------------
+;;; Generators
+
+(def ^:private conflict-groups
+  "Classes that set the same property, so only the last one should survive."
+  [["p-0" "p-2" "p-4" "p-6"]
+   ["px-2" "px-3" "px-6"]
+   ["text-xs" "text-sm" "text-lg" "text-2xl"]
+   ["text-primary" "text-muted" "text-secondary" "text-accent"]
+   ["bg-accent" "bg-surface" "bg-surface-raised" "bg-surface-hover"]
+   ["rounded-md" "rounded-lg" "rounded-xl" "rounded-full"]
+   ["font-medium" "font-semibold" "font-bold"]
+   ["flex" "grid" "block" "hidden"]])
+
+(def ^:private variants
+  ["" "hover:" "sm:" "focus-visible:"])
+
+(def gen-variant
+  (gen/elements variants))
+
+(def gen-class
+  (gen/let [variant gen-variant
+            group   (gen/elements conflict-groups)
+            class   (gen/elements group)]
+    (str variant class)))
+
+(def gen-classes
+  (gen/vector gen-class 0 12))
+
+(def gen-conflicting-pair
+  (gen/let [variant gen-variant
+            group   (gen/elements conflict-groups)
+            a       (gen/elements group)
+            b       (gen/elements group)]
+    [(str variant a) (str variant b)]))
+
+(def gen-junk
+  "Class-shaped strings that need not be valid Tailwind."
+  (gen/vector
+   (gen/fmap str/join
+             (gen/vector (gen/elements (seq "abcxyz0129-:/.[]#!_")) 0 16))
+   0 8))
+
+;;; ----------------------------------------------------------------------------
+;;; Utils
+
+(defn- tokens
+  [s]
+  (remove str/blank? (str/split s #"\s+")))
+
+;;; ----------------------------------------------------------------------------
+;;; merge-classes
 
 (deftest merge-classes
   (is (= "text-lg"
          (sut/merge-classes ["text-sm" "text-lg"]))))
+
+(defspec merge-classes-is-idempotent
+  (prop/for-all [classes gen-classes]
+    (let [merged (sut/merge-classes classes)]
+      (= merged (sut/merge-classes (tokens merged))))))
+
+(defspec merge-classes-keeps-the-last-conflicting-class
+  (prop/for-all [[a b] gen-conflicting-pair]
+    (= b (sut/merge-classes [a b]))))
+
+(defspec merge-classes-only-drops-classes
+  (prop/for-all [classes gen-classes]
+    (every? (set classes) (tokens (sut/merge-classes classes)))))
+
+(defspec merge-classes-accepts-arbitrary-input
+  (prop/for-all [classes gen-junk]
+    (string? (sut/merge-classes classes))))
@@ -1,6 +1,7 @@
 (ns bits.test.app
   (:require
    [bits.app :as app]
+   [bits.clock :as clock]
    [bits.cryptex :as cryptex]
    [bits.crypto :as crypto]
    [bits.datomic :as datomic]
@@ -78,6 +79,13 @@
   [system origins]
   (assoc-in system [:service :allowed-origins] origins))
 
+(defn replace-clock
+  "Swaps in a clock that reads the time from `!now`, an atom holding an
+  `OffsetDateTime`. Tests move time along with `swap!`."
+  [system !now]
+  (assoc system :clock (reify clock/Tick
+                         (now [_] @!now))))
+
 (defn replace-random-bytes
   [system source]
   (assoc system :randomizer (reify crypto/Randomize
@@ -4,7 +4,9 @@
    [bits.string :as string]
    [bits.test.app :as t]
    [clojure.pprint :as pprint]
+   [clojure.string :as str]
    [etaoin.api :as e]
+   [hato.client :as http]
    [io.pedestal.log :as log]
    [java-time.api :as time]
    [lambdaisland.uri :as uri]
@@ -45,6 +47,17 @@
   (span/with-span! {:name ::goto :attributes {"browser.path" path}}
     (e/go (->etaoin driver) (t/service-url (->service driver) path))))
 
+(defn goto-domain
+  "Visits path on a subdomain of localhost, which browsers resolve to the
+  loopback address, so the request lands in that domain's realm."
+  [driver subdomain path]
+  (span/with-span! {:name ::goto-domain :attributes {"browser.domain" subdomain
+                                                     "browser.path"   path}}
+    (e/go (->etaoin driver)
+          (str/replace-first (t/service-url (->service driver) path)
+                             "//localhost"
+                             (str "//" subdomain ".localhost")))))
+
 (defn current-path
   [driver]
   (-> (e/get-url (->etaoin driver)) uri/uri :path))
@@ -202,6 +215,58 @@
     (Thread/sleep 350)
     (wait-for-form driver)))
 
+;;; ----------------------------------------------------------------------------
+;;; Accessibility
+;;;
+;;; axe-core is fetched once per checkout and cached under target, then
+;;; injected into the page through the driver so the page's CSP doesn't
+;;; apply.
+
+(def ^:const ^:private axe-version "4.10.2")
+
+(def ^:private axe-url
+  (str "https://cdn.jsdelivr.net/npm/axe-core@" axe-version "/axe.min.js"))
+
+(def ^:private failing-impacts
+  #{"critical" "serious"})
+
+(defn- axe-source
+  []
+  (let [file (fs/file "target" (str "axe-core-" axe-version ".min.js"))]
+    (when-not (fs/exists? file)
+      (fs/create-dirs (fs/parent file))
+      (spit file (:body (http/get axe-url {:throw-exceptions? true}))))
+    (slurp file)))
+
+(def ^:private run-axe
+  "const done = arguments[arguments.length - 1];
+   axe.run(document, {resultTypes: ['violations']})
+      .then(results => done(results.violations))
+      .catch(error => done({error: String(error)}));")
+
+(defn audit
+  "Runs axe-core over the current page. Returns its serious and critical
+  violations, leaving out rules whose IDs are in allowed, as maps of rule ID,
+  impact, help text and the selectors of the offending nodes."
+  ([driver]
+   (audit driver #{}))
+  ([driver allowed]
+   (span/with-span! {:name ::audit :attributes {"browser.path" (current-path driver)}}
+     (let [e          (->etaoin driver)
+           _          (e/js-execute e (axe-source))
+           violations (e/js-async e run-axe)]
+       (when-let [error (and (map? violations) (:error violations))]
+         (throw (ex-info "axe-core failed" {:error error})))
+       (into []
+             (comp (filter #(contains? failing-impacts (:impact %)))
+                   (remove #(contains? allowed (:id %)))
+                   (map (fn [{:keys [help id impact nodes]}]
+                          {:id     id
+                           :impact impact
+                           :help   help
+                           :nodes  (mapv (comp first :target) nodes)})))
+             violations)))))
+
 ;;; ----------------------------------------------------------------------------
 ;;; Debug
 
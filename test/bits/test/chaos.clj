@@ -0,0 +1,116 @@
+(ns bits.test.chaos
+  "Postgres faults injected on demand, to check that callers degrade well.
+
+  `replace-postgres` wraps a system's datasource so every statement first
+  consults a queue of faults. A fault whose `:match` pattern finds the
+  statement's SQL is taken off the queue and applied once:
+
+    :latency               - sleeps `:ms` before the statement runs
+    :dropped-connection    - fails as though the server went away (08006)
+    :serialization-failure - fails as a conflicting transaction would (40001)
+
+  Faults that match nothing stay queued, so tests can inject before driving
+  the code under test:
+
+    (let [faults (chaos/faults)]
+      (t/with-system [system (chaos/replace-postgres (t/system) faults)]
+        (chaos/inject! faults {:fault :dropped-connection :match #\"sessions\"})
+        ...))"
+  (:require
+   [clojure.string :as str])
+  (:import
+   (java.lang.reflect InvocationHandler InvocationTargetException Method Proxy)
+   (java.sql Connection PreparedStatement SQLException Statement)
+   (javax.sql DataSource)))
+
+;;; ----------------------------------------------------------------------------
+;;; Faults
+
+(defn faults
+  []
+  (atom []))
+
+(defn inject!
+  [faults fault]
+  {:pre [(#{:dropped-connection :latency :serialization-failure} (:fault fault))]}
+  (swap! faults conj fault))
+
+(defn pending
+  "Faults that haven't matched a statement yet."
+  [faults]
+  @faults)
+
+(defn- take-fault!
+  [faults sql]
+  (let [matches? (fn [{:keys [match]}]
+                   (or (nil? match) (re-find match (str sql))))
+        [old _]  (swap-vals! faults (fn [queue]
+                                      (let [[before [_ & after]] (split-with (complement matches?) queue)]
+                                        (into (vec before) after))))]
+    (first (filter matches? old))))
+
+(defn- apply-fault!
+  [{:keys [fault ms]}]
+  (case fault
+    :latency               (Thread/sleep (long ms))
+    :dropped-connection    (throw (SQLException. "Injected: connection dropped" "08006"))
+    :serialization-failure (throw (SQLException. "Injected: could not serialize access" "40001"))
+    nil))
+
+;;; ----------------------------------------------------------------------------
+;;; Proxies
+
+(def ^:private executing
+  #{"execute" "executeBatch" "executeLargeUpdate" "executeQuery" "executeUpdate"})
+
+(defn- wrap
+  [^Class iface target handle]
+  (Proxy/newProxyInstance
+   (.getClassLoader iface)
+   (into-array Class [iface])
+   (reify InvocationHandler
+     (invoke [_ _ method args]
+       (try
+         (handle method args #(.invoke ^Method method target args))
+         (catch InvocationTargetException ex
+           (throw (.getCause ex))))))))
+
+(defn- wrap-statement
+  [^Class iface statement faults sql]
+  (wrap iface statement
+        (fn [^Method method args invoke]
+          (when (contains? executing (.getName method))
+            (some-> (take-fault! faults (or sql (some-> args first))) apply-fault!))
+          (invoke))))
+
+(defn- wrap-connection
+  [conn faults]
+  (wrap Connection conn
+        (fn [^Method method args invoke]
+          (let [result (invoke)]
+            (cond
+              (instance? PreparedStatement result)
+              (wrap-statement PreparedStatement result faults (first args))
+
+              (and (instance? Statement result) (str/starts-with? (.getName method) "create"))
+              (wrap-statement Statement result faults nil)
+
+              :else
+              result)))))
+
+(defn- wrap-datasource
+  [faults]
+  (fn [ds]
+    (wrap DataSource ds
+          (fn [^Method method _args invoke]
+            (let [result (invoke)]
+              (if (= "getConnection" (.getName method))
+                (wrap-connection result faults)
+                result))))))
+
+;;; ----------------------------------------------------------------------------
+;;; System
+
+(defn replace-postgres
+  [system faults]
+  (assoc-in system [:postgres :wrap-datasource] (wrap-datasource faults)))
@@ -1,5 +1,10 @@
 (ns bits.test.fixture
-  (:require [java-time.api :as time]))
+  (:require
+   [bits.cryptex :as cryptex]
+   [bits.crypto :as crypto]
+   [bits.datomic :as datomic]
+   [datomic.api :as d]
+   [java-time.api :as time]))
 
 (defn realm-txes
   ([] (realm-txes {}))
@@ -15,3 +20,122 @@
       (-> attributes
           (dissoc :domain/name)
           (assoc :tenant/domains ["domain"]))])))
+
+;;; ----------------------------------------------------------------------------
+;;; Seeding
+;;;
+;;; Builders describe a graph of entities that `seed!` writes in a single
+;;; transaction, returning the generated IDs keyed by handle and email.
+;;; Memberships refer to tenants seeded in the same call:
+;;;
+;;;   (fixture/seed! service
+;;;     (-> (fixture/tenant "acme") (fixture/with-products 3))
+;;;     (-> (fixture/user "ada@example.com")
+;;;         (fixture/with-password "password")
+;;;         (fixture/member-of "acme" :membership.role/owner)))
+;;;
+;;; Every test system gets a fresh database, so there is nothing to tear down.
+
+(defn tenant
+  ([handle]
+   (tenant handle {}))
+  ([handle overrides]
+   (merge {::type                ::tenant
+           :creator/display-name handle
+           :creator/handle       handle
+           :domain/name          (str handle ".bits.page.localhost")
+           :tenant/id            (random-uuid)
+           ::products            0}
+          overrides)))
+
+(defn with-products
+  [tenant n]
+  (assoc tenant ::products n))
+
+(defn user
+  ([email]
+   (user email {}))
+  ([email overrides]
+   (merge {::type        ::user
+           ::memberships {}
+           :user/email   email
+           :user/id      (random-uuid)}
+          overrides)))
+
+(defn with-password
+  [user password]
+  (assoc user ::password password))
+
+(defn member-of
+  ([user handle]
+   (member-of user handle :membership.role/member))
+  ([user handle role]
+   (assoc-in user [::memberships handle] role)))
+
+(defn- product-tx
+  [tenant instant position]
+  {:product/id         (random-uuid)
+   :product/title      (str (:creator/handle tenant) " product " position)
+   :product/status     :product.status/active
+   :product/position   position
+   :product/created-at instant})
+
+(defn- tenant-tx
+  [tenant instant]
+  (let [handle (:creator/handle tenant)]
+    [{:db/id       (str handle "-domain")
+      :domain/name (:domain/name tenant)}
+     (-> tenant
+         (dissoc ::type ::products :domain/name)
+         (assoc :db/id             handle
+                :tenant/created-at instant
+                :tenant/domains    [(str handle "-domain")]
+                :tenant/products   (mapv #(product-tx tenant instant (inc %))
+                                         (range (::products tenant)))))]))
+
+(defn- user-tx
+  [keymaster user instant]
+  (let [user-ref (str "user-" (:user/id user))]
+    (into [(cond-> (-> user
+                       (dissoc ::type ::memberships ::password)
+                       (assoc :db/id           user-ref
+                              :user/created-at instant))
+             (::password user)
+             (assoc :user/password-hash
+                    (crypto/derive keymaster (cryptex/cryptex (::password user)))))]
+          (for [[handle role] (::memberships user)]
+            {:membership/id     (random-uuid)
+             :membership/user   user-ref
+             :membership/tenant handle
+             :membership/role   role}))))
+
+(defn seed-txes
+  [keymaster specs]
+  (let [instant (time/java-date)
+        handles (into #{}
+                      (comp (filter #(= ::tenant (::type %)))
+                            (map :creator/handle))
+                      specs)]
+    (into []
+          (mapcat (fn [spec]
+                    (case (::type spec)
+                      ::tenant (tenant-tx spec instant)
+                      ::user   (do
+                                 (doseq [handle (keys (::memberships spec))]
+                                   (when-not (contains? handles handle)
+                                     (throw (ex-info "Unknown tenant?!" {:handle handle}))))
+                                 (user-tx keymaster spec instant)))))
+          specs)))
+
+(defn seed!
+  [service & specs]
+  (let [{:keys [datomic keymaster]} service]
+    @(d/transact (datomic/conn datomic) (seed-txes keymaster specs))
+    {:tenants (into {}
+                    (comp (filter #(= ::tenant (::type %)))
+                          (map (juxt :creator/handle #(select-keys % [:tenant/id :domain/name]))))
+                    specs)
+     :users   (into {}
+                    (comp (filter #(= ::user (::type %)))
+                          (map (juxt :user/email #(select-keys % [:user/id]))))
+                    specs)}))
@@ -0,0 +1,93 @@
+(ns bits.test.snapshot
+  "Renders views to HTML and compares them with snapshots under
+  test-resources/snapshots.
+
+  Views render against a running service with a made-up realm, session and
+  locale, so no HTTP round trip is needed. Anything that changes from run to
+  run, like IDs, event hashes and asset digests, is replaced with a
+  placeholder before comparing.
+
+  A missing snapshot is recorded by the run that first needs it, so commit new
+  files under test-resources/snapshots with the test that made them. Run with
+  `-Dbits.snapshots.update=true` to record every snapshot afresh after an
+  intended change, then review the diff."
+  (:require
+   [bits.datomic :as datomic]
+   [bits.html :as html]
+   [bits.locale :as locale]
+   [bits.middleware :as mw]
+   [clojure.java.io :as io]
+   [clojure.string :as str]))
+
+;;; ----------------------------------------------------------------------------
+;;; Requests
+
+(defn view-request
+  "A request views can render from. Realm defaults to the platform's; pass a
+  user ID to render as someone signed in."
+  [service {:keys [page path realm user-id]
+            :or   {path  "/"
+                   realm {:realm/type :realm.type/platform}}}]
+  (cond-> {:headers        {"host" "localhost"}
+           :request-method :get
+           :uri            path
+           :query-params   {}
+           :session        {:sid "snapshot-sid" :nonce "snapshot-nonce"}
+           :session/realm  realm
+           ::mw/csrf       "snapshot-csrf"
+           ::mw/db         (datomic/db (:datomic service))
+           ::mw/state      service}
+    page    (assoc :bits/page page)
+    user-id (-> (assoc :session/user {:user/id user-id})
+                (assoc-in [:session :user/id] user-id))))
+
+;;; ----------------------------------------------------------------------------
+;;; Rendering
+
+(def ^:private volatile-patterns
+  [[#"[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}" "[uuid]"]
+   [#"(\w)\.[0-9a-f]{8}\.(\w+)\"" "$1.[digest].$2\""]
+   [#"data-event-id=\"[^\"]*\"" "data-event-id=\"[event-id]\""]
+   [#"nonce-[A-Za-z0-9+/=_-]+" "nonce-[nonce]"]])
+
+(defn sanitize
+  "HTML with run-to-run values replaced and one tag per line, so diffs read
+  well."
+  [s]
+  (-> (reduce (fn [s [pattern replacement]]
+                (str/replace s pattern replacement))
+              s
+              volatile-patterns)
+      (str/replace #">\s*<" ">\n<")
+      (str/trim-newline)
+      (str "\n")))
+
+(defn render
+  "Renders hiccup as the service would, in English unless told otherwise."
+  ([hiccup]
+   (render hiccup "en"))
+  ([hiccup locale-tag]
+   (locale/with-locale (locale/string->locale locale-tag)
+     (sanitize (html/htmx hiccup)))))
+
+;;; ----------------------------------------------------------------------------
+;;; Comparing
+
+(defn- update?
+  []
+  (= "true" (System/getProperty "bits.snapshots.update")))
+
+(defn- snapshot-file
+  ^java.io.File [snapshot-name]
+  (io/file "test-resources" "snapshots" (str snapshot-name ".html")))
+
+(defn expected
+  "The snapshot named snapshot-name, recording html as it when there's no
+  snapshot yet or snapshots are being updated. Compare with `=` so a failure
+  shows both."
+  [snapshot-name html]
+  (let [file (snapshot-file snapshot-name)]
+    (when (or (update?) (not (.exists file)))
+      (io/make-parents file)
+      (spit file html))
+    (slurp file)))
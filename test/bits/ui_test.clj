@@ -0,0 +1,22 @@
+(ns bits.ui-test
+  (:require
+   [bits.test.app :as t]
+   [bits.test.snapshot :as snapshot]
+   [bits.ui :as ui]
+   [clojure.test :refer [deftest is]]))
+
+(deftest nav-header-snapshots
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [signed-out (snapshot/render (ui/nav-header (snapshot/view-request service {:path "/counter"})
+                                                     "/counter"))
+          signed-in  (snapshot/render (ui/nav-header (snapshot/view-request service {:path    "/counter"
+                                                                                     :user-id (random-uuid)})
+                                                     "/counter"))]
+      (is (= (snapshot/expected "ui/nav-header-signed-out" signed-out) signed-out))
+      (is (= (snapshot/expected "ui/nav-header-signed-in" signed-in) signed-in)))))
+
+(deftest layout-snapshot
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [request (snapshot/view-request service {:page {:page/title "Not found"}})
+          html    (snapshot/render (ui/layout request (ui/not-found-view request)))]
+      (is (= (snapshot/expected "ui/layout-not-found" html) html)))))
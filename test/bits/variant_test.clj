@@ -0,0 +1,81 @@
+(ns bits.variant-test
+  (:require
+   [bits.anomaly :as anom]
+   [bits.datomic :as datomic]
+   [bits.product :as product]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [bits.variant :as sut]
+   [clojure.test :refer [deftest is testing]]
+   [datomic.api :as d])
+  (:import
+   (java.util Date)))
+
+(defn- seed!
+  [service]
+  (let [{:keys [tenants]} (fixture/seed! service (fixture/with-products (fixture/tenant "acme") 1))
+        conn              (datomic/conn (:datomic service))
+        tenant-id         (get-in tenants ["acme" :tenant/id])
+        product-id        (d/q '[:find ?id . :where [_ :product/id ?id]] (d/db conn))]
+    {:conn    conn
+     :product #(product/lookup (d/db conn) tenant-id product-id)}))
+
+(defn- value-id
+  [p option-name label]
+  (some (fn [option]
+          (when (= option-name (:option/name option))
+            (some #(when (= label (:option-value/label %)) (:option-value/id %))
+                  (:option/values option))))
+        (sut/options p)))
+
+(defn- variant
+  [sku option-values]
+  {:name           sku
+   :sku            sku
+   :price          1500
+   :currency       "GBP"
+   :type           "physical"
+   :quantity-limit 3
+   :option-values  option-values})
+
+(deftest combinations-are-unique-among-active-variants
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [{:keys [conn product]} (seed! service)
+          now                    (Date.)]
+      (is (uuid? (sut/add-option! conn (product) 0 {:name "Size" :values ["S" "M"]})))
+      (is (uuid? (sut/add-option! conn (product) 1 {:name "Colour" :values ["Red" "Blue"]})))
+      (is (= ::anom/incorrect (::anom/category (sut/add-option! conn (product) 2 {:name "size" :values ["L"]}))))
+
+      (let [p        (product)
+            small    (value-id p "Size" "S")
+            red      (value-id p "Colour" "Red")
+            blue     (value-id p "Colour" "Blue")
+            small-id (sut/create! conn p 2 (variant "TEE-S-RED" [small red]) now)]
+        (is (uuid? small-id))
+
+        (testing "a stale version can't add a variant someone else just added"
+          (is (= ::anom/conflict (::anom/category (sut/create! conn p 2 (variant "TEE-S-RED-2" [small red]) now)))))
+
+        (testing "the same combination is refused"
+          (is (= ::anom/incorrect (::anom/category (sut/create! conn (product) 3 (variant "TEE-S-RED-2" [red small]) now)))))
+
+        (testing "every option needs exactly one value"
+          (is (= ::anom/incorrect (::anom/category (sut/create! conn (product) 3 (variant "TEE-S" [small]) now))))
+          (is (= ::anom/incorrect (::anom/category (sut/create! conn (product) 3 (variant "TEE-RB" [red blue]) now)))))
+
+        (is (uuid? (sut/create! conn (product) 3 (variant "TEE-S-BLUE" [small blue]) now)))
+
+        (let [p (product)]
+          (is (= ["S" "S" "M" "M"] (map #(-> % :values first :option-value/label) (sut/matrix p))))
+          (is (= [true true false false] (map (comp some? :variant) (sut/matrix p))))
+          (is (= small-id (:variant/id (sut/select-variant p [red small]))))
+          (is (nil? (sut/select-variant p [(value-id p "Size" "M") red])))
+          (is (= 3 (sut/stock (d/db conn) (sut/select-variant p [small red])))))
+
+        (testing "options can't be added while variants are active"
+          (is (= ::anom/incorrect (::anom/category (sut/add-option! conn (product) 4 {:name "Fit" :values ["Slim"]})))))
+
+        (testing "deactivating a variant frees its combination"
+          (is (= small-id (sut/deactivate! conn (product) 4 small-id)))
+          (is (nil? (sut/select-variant (product) [small red])))
+          (is (uuid? (sut/create! conn (product) 5 (assoc (variant "TEE-S-RED-V2" [small red]) :price 1800) now))))))))
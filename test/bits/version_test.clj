@@ -0,0 +1,14 @@
+(ns bits.version-test
+  (:require
+   [bits.version :as sut]
+   [clojure.test :refer [are deftest is]]))
+
+(deftest etags-round-trip
+  (is (= 3 (sut/parse-etag (sut/etag 3))))
+  (are [header version] (= version (sut/parse-etag header))
+    "\"0\""    0
+    "W/\"12\"" 12
+    " \"7\" "  7
+    "*"        nil
+    "7"        nil
+    nil        nil))
@@ -0,0 +1,127 @@
+(ns bits.webhook-test
+  (:require
+   [bits.cryptex :as cryptex]
+   [bits.crypto :as crypto]
+   [bits.module.webhook :as module.webhook]
+   [bits.postgres :as postgres]
+   [bits.response]
+   [bits.test.app :as t]
+   [bits.test.fixture :as fixture]
+   [bits.test.snapshot :as snapshot]
+   [bits.webhook :as sut]
+   [clojure.string :as str]
+   [clojure.test :refer [are deftest is]]
+   [matcher-combinators.test]))
+
+(def ^:private tenant-id
+  #uuid "5b7c2a8e-3f0d-4d1e-9a6b-2c4e8f1a7d30")
+
+;;; ----------------------------------------------------------------------------
+;;; Signing
+
+(deftest signature
+  (is (= "t=1700000000,v1=35495024f4ef3f94e5a93e22221544c4b75e9a42300cd965ab81cb85cd994e91"
+         (sut/signature "whsec_test" 1700000000 "{}"))))
+
+;;; ----------------------------------------------------------------------------
+;;; Backoff
+
+(deftest backoff-seconds
+  (are [attempts expected] (= expected (sut/backoff-seconds 30 attempts))
+    1 30
+    2 60
+    3 120
+    8 3840))
+
+;;; ----------------------------------------------------------------------------
+;;; Delivery
+
+(deftest publish-only-queues-for-subscribers
+  (t/with-system [{:keys [webhooks]} (t/system)]
+    (sut/register! webhooks {:tenant-id tenant-id
+                             :url       "https://127.0.0.1:1/hooks"
+                             :events    #{"member.created"}})
+    (is (= 1 (sut/publish! webhooks tenant-id "member.created" {:id "m_1"})))
+    (is (= 0 (sut/publish! webhooks tenant-id "order.created" {:id "o_1"})))
+    (is (= 0 (sut/publish! webhooks (random-uuid) "member.created" {:id "m_2"})))
+    (is (match?
+         [{:bits.postgres.webhook-delivery/event  "member.created"
+           :bits.postgres.webhook-delivery/status "pending"}]
+         (:items (sut/list-deliveries webhooks tenant-id {:limit 10}))))))
+
+(deftest failed-deliveries-retry-then-dead-letter
+  (t/with-system [{:keys [webhooks]} (t/system)]
+    (let [webhooks (assoc webhooks :max-attempts 1)]
+      (sut/register! webhooks {:tenant-id tenant-id
+                               :url       "https://127.0.0.1:1/hooks"
+                               :events    #{"order.created"}})
+      (sut/publish! webhooks tenant-id "order.created" {:id "o_1"})
+      (is (= {:dead 1} (sut/deliver-due! webhooks)))
+      (is (match?
+           [{:bits.postgres.webhook-delivery/attempts   1
+             :bits.postgres.webhook-delivery/last-error #"non-public address"
+             :bits.postgres.webhook-delivery/status     "dead"}]
+           (:items (sut/list-deliveries webhooks tenant-id {:limit 10}))))
+      (is (= {} (sut/deliver-due! webhooks))))))
+
+(deftest disabling-an-endpoint-dead-letters-pending-deliveries
+  (t/with-system [{:keys [webhooks]} (t/system)]
+    (let [{::sut/keys [id]} (sut/register! webhooks {:tenant-id tenant-id
+                                                     :url       "https://127.0.0.1:1/hooks"
+                                                     :events    #{"domain.verified"}})]
+      (sut/publish! webhooks tenant-id "domain.verified" {:domain "example.com"})
+      (is (true? (sut/disable! webhooks tenant-id id)))
+      (is (empty? (sut/list-endpoints webhooks tenant-id)))
+      (is (match?
+           [{:bits.postgres.webhook-delivery/status "dead"}]
+           (:items (sut/list-deliveries webhooks tenant-id {:limit 10})))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Sealed secrets
+
+(deftest secrets-are-sealed-at-rest
+  (t/with-system [{:keys [postgres webhooks]} (t/system)]
+    (let [{::sut/keys [id secret]} (sut/register! webhooks {:tenant-id tenant-id
+                                                            :url       "https://127.0.0.1:1/hooks"
+                                                            :events    #{"order.created"}})
+          stored                   (:bits.postgres.webhook-endpoint/secret
+                                    (postgres/execute-one! postgres {:select [:secret]
+                                                                     :from   [:webhook-endpoints]
+                                                                     :where  [:= :id id]}))]
+      (is (crypto/sealed? stored))
+      (is (not (str/includes? stored (cryptex/reveal secret))))
+      (is (= 0 (sut/reseal-secrets! webhooks))))))
+
+;;; ----------------------------------------------------------------------------
+;;; Actions
+
+(defn- acme-request
+  "Renders requests as members of a seeded tenant, by email."
+  [service]
+  (let [{:keys [tenants users]} (fixture/seed! service
+                                               (fixture/tenant "acme")
+                                               (-> (fixture/user "owner@acme.test")
+                                                   (fixture/member-of "acme" :membership.role/owner))
+                                               (-> (fixture/user "member@acme.test")
+                                                   (fixture/member-of "acme")))]
+    (fn [email]
+      (snapshot/view-request service {:realm   {:tenant/id (get-in tenants ["acme" :tenant/id])}
+                                      :user-id (get-in users [email :user/id])}))))
+
+(deftest register
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [request (acme-request service)]
+      (are [email forbidden?] (= forbidden? (= bits.response/forbidden-response
+                                               (module.webhook/register (request email))))
+        "owner@acme.test"  false
+        "member@acme.test" true
+        "nobody@acme.test" true))))
+
+(deftest disable
+  (t/with-system [{:keys [service]} (t/system)]
+    (let [request (acme-request service)]
+      (are [email forbidden?] (= forbidden? (= bits.response/forbidden-response
+                                               (module.webhook/disable (request email))))
+        "owner@acme.test"  false
+        "member@acme.test" true
+        "nobody@acme.test" true))))
@@ -0,0 +1,34 @@
+(ns bits.wishlist-test
+  (:require
+   [bits.test.app :as t]
+   [bits.wishlist :as sut]
+   [clojure.test :refer [deftest is testing]]))
+
+(deftest saving-and-merging-on-sign-in
+  (t/with-system [{{:keys [wishlists]} :service} (t/system)]
+    (let [tenant-id        (random-uuid)
+          user             {:user-id (random-uuid)}
+          anonymous        {:sid "anonymous-session"}
+          [mug poster tee] (repeatedly 3 random-uuid)]
+      (is (true? (sut/toggle! wishlists tenant-id user mug)))
+      (is (true? (sut/toggle! wishlists tenant-id anonymous mug)))
+      (is (true? (sut/toggle! wishlists tenant-id anonymous poster)))
+      (is (true? (sut/toggle! wishlists tenant-id anonymous tee)))
+
+      (testing "toggling again removes"
+        (is (false? (sut/toggle! wishlists tenant-id anonymous tee)))
+        (is (= #{mug poster} (set (sut/saved wishlists tenant-id anonymous)))))
+
+      (is (= [{:product-id mug :saves 2} {:product-id poster :saves 1}]
+             (sut/popular wishlists tenant-id 10)))
+
+      (testing "products saved both ways are kept once"
+        (is (= 1 (sut/merge! wishlists tenant-id (:sid anonymous) (:user-id user))))
+        (is (= #{mug poster} (set (sut/saved wishlists tenant-id user))))
+        (is (empty? (sut/saved wishlists tenant-id anonymous)))
+        (is (= 0 (sut/merge! wishlists tenant-id nil (:user-id user)))))
+
+      (testing "anonymous items outlive their session only until the reaper comes"
+        (sut/toggle! wishlists tenant-id {:sid "expired-session"} tee)
+        (is (= 1 (sut/purge-orphans! wishlists)))
+        (is (= 2 (count (sut/saved wishlists tenant-id user))))))))